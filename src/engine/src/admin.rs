@@ -0,0 +1,184 @@
+//! This module contains [`serve_admin`], the built-in admin listener. It is available with the `admin` feature.
+//!
+//! The listener answers HTTP/1.0 `GET` requests of operators and probes:
+//!
+//! - `/healthz` returns `ok`, while the worker runs coroutines;
+//!
+//! - `/metrics` returns the [`SchedulerMetrics`] and [`SubmissionStats`] of the worker in the Prometheus text format;
+//!
+//! - `/dump` returns the [`BuildInfo`](crate::BuildInfo), the metrics, the [`MemoryUsage`](crate::scheduler::MemoryUsage)
+//! and the latest samples of the [queue trace](crate::scheduler::Scheduler::dump_queue_trace) as plain text.
+//!
+//! Every connection handles one request and is closed after the response, like in HTTP/1.0.
+use std::fmt::Write;
+use std::io::Error;
+use crate::buf::buffer;
+use crate::{build_info, coro, local_scheduler, wait};
+use crate::io::{submission_stats, AsyncRead, AsyncWrite};
+use crate::local::get_worker_id;
+use crate::net::{TcpListener, TcpStream};
+use crate::scheduler::SchedulerMetrics;
+use crate::stream::CoStream;
+
+/// The maximum number of connections, that the admin listener handles at the same time.
+const MAX_ADMIN_CONNECTIONS: usize = 16;
+/// The maximum length of a request head. Longer requests are rejected.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+/// The number of the latest queue samples in `/dump`.
+const DUMP_QUEUE_SAMPLES: usize = 32;
+
+/// Serves the admin endpoints on the listener. Read [the module](crate::admin) for the list of them.
+///
+/// The endpoints report the worker, that runs the listener, so run it on every worker with its own port
+/// or on the worker, that should be observed. Start it with [`Scheduler::sched`](crate::scheduler::Scheduler::sched),
+/// so it doesn't keep the worker alive.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::ptr::null_mut;
+/// use engine::{coro, local_scheduler};
+/// use engine::admin::serve_admin;
+/// use engine::net::TcpListener;
+///
+/// #[coro]
+/// fn start_admin() {
+///     let listener: TcpListener = yield TcpListener::new("127.0.0.1:9090".parse().unwrap());
+///     local_scheduler().sched(serve_admin(listener, null_mut()));
+/// }
+/// ```
+#[coro(crate="crate")]
+pub fn serve_admin(listener: TcpListener) {
+    wait!(listener.for_each_concurrent(MAX_ADMIN_CONNECTIONS, handle_admin_request));
+}
+
+#[coro(crate="crate")]
+fn handle_admin_request(stream_: Result<TcpStream, Error>) {
+    let mut stream = match stream_ {
+        Ok(stream) => stream,
+        Err(_) => return
+    };
+
+    let mut head = Vec::new();
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let res: Result<&[u8], Error> = yield stream.read();
+        match res {
+            Ok(slice) if !slice.is_empty() && head.len() + slice.len() <= MAX_REQUEST_LEN => head.extend_from_slice(slice),
+            _ => return
+        };
+    }
+
+    let (status, body) = respond(&head);
+    let mut buf = buffer();
+    buf.append(format!(
+        "HTTP/1.0 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    ).as_bytes());
+    let _: Result<(), Error> = yield stream.write_all(buf);
+}
+
+/// Returns the status and the body of the response to the request head.
+fn respond(head: &[u8]) -> (&'static str, String) {
+    let request_line = head.split(|&byte| byte == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&byte| byte == b' ');
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    if method != b"GET" {
+        return ("405 Method Not Allowed", "only GET is allowed\n".to_string());
+    }
+
+    match path {
+        b"/healthz" => ("200 OK", "ok\n".to_string()),
+        b"/metrics" => ("200 OK", render_metrics(&local_scheduler().metrics())),
+        b"/dump" => ("200 OK", render_dump()),
+        _ => ("404 Not Found", "not found\n".to_string())
+    }
+}
+
+/// Renders the metrics of the worker in the Prometheus text format.
+fn render_metrics(metrics: &SchedulerMetrics) -> String {
+    let worker = get_worker_id();
+    let stats = submission_stats();
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, value: u64| {
+        let _ = writeln!(out, "# TYPE coroeng_{} {}\ncoroeng_{}{{worker=\"{}\"}} {}", name, kind, name, worker, value);
+    };
+    metric("spawned_total", "counter", metrics.spawned_total);
+    metric("completed_total", "counter", metrics.completed_total);
+    metric("panicked_total", "counter", metrics.panicked_total);
+    metric("shed_total", "counter", metrics.shed_total);
+    metric("resumed_total", "counter", metrics.resumed_total);
+    metric("polls_total", "counter", metrics.polls_total);
+    metric("ready", "gauge", metrics.ready as u64);
+    metric("idle", "gauge", metrics.idle as u64);
+    metric("sleeping", "gauge", metrics.sleeping as u64);
+    metric("pending_states", "gauge", metrics.pending_states as u64);
+    metric("submission_backlog", "gauge", metrics.submission_backlog as u64);
+    metric("submission_spilled_total", "counter", stats.spilled);
+    metric("submission_busy_total", "counter", stats.busy);
+    metric("cq_overflows_total", "counter", metrics.cq_overflows);
+    metric("offloaded_file_ops_total", "counter", metrics.offloaded_file_ops);
+    metric("dropped_completions_total", "counter", stats.dropped_completions);
+    metric("resubmit_batches_total", "counter", stats.resubmit_batches);
+    metric("resubmitted_total", "counter", stats.resubmitted);
+    out
+}
+
+/// Renders the state of the worker for a human.
+fn render_dump() -> String {
+    let scheduler = local_scheduler();
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", build_info());
+    let _ = writeln!(out, "worker: {}", get_worker_id());
+    let _ = writeln!(out, "metrics: {:?}", scheduler.metrics());
+    let _ = writeln!(out, "memory: {:?}", scheduler.memory_usage());
+    let _ = writeln!(out, "submission: {:?}", submission_stats());
+    let _ = writeln!(out, "queue trace (millis run_queue_len completions wakeups):");
+    let samples = scheduler.dump_queue_trace();
+    for sample in &samples[samples.len().saturating_sub(DUMP_QUEUE_SAMPLES)..] {
+        let _ = writeln!(out, "{} {} {} {}", sample.millis, sample.run_queue_len, sample.completions, sample.wakeups);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{local_scheduler, test_local};
+    use crate::admin::serve_admin;
+    use crate::net::TcpListener;
+    use crate::sleep::sleep;
+
+    #[test_local(crate="crate")]
+    fn test_admin() {
+        let listener: TcpListener = yield TcpListener::new("127.0.0.1:0".parse().unwrap());
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        local_scheduler().sched(serve_admin(listener, null_mut()));
+
+        let client = std::thread::spawn(move || {
+            ["GET /healthz HTTP/1.0", "GET /metrics HTTP/1.0", "GET /dump HTTP/1.1", "GET /nope HTTP/1.0", "POST /healthz HTTP/1.0"]
+                .map(|request_line| {
+                    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+                    stream.write_all(format!("{}\r\nHost: localhost\r\n\r\n", request_line).as_bytes()).unwrap();
+                    let mut response = String::new();
+                    stream.read_to_string(&mut response).unwrap();
+                    response
+                })
+        });
+        while !client.is_finished() {
+            yield sleep(Duration::from_millis(1));
+        }
+
+        let [healthz, metrics, dump, not_found, post] = client.join().unwrap();
+        assert!(healthz.starts_with("HTTP/1.0 200 OK\r\n"), "{}", healthz);
+        assert!(healthz.ends_with("\r\n\r\nok\n"), "{}", healthz);
+        assert!(metrics.contains("# TYPE coroeng_spawned_total counter\n"), "{}", metrics);
+        assert!(metrics.contains("coroeng_sleeping{worker=\""), "{}", metrics);
+        assert!(dump.contains("metrics: SchedulerMetrics {"), "{}", dump);
+        assert!(dump.contains("queue trace"), "{}", dump);
+        assert!(not_found.starts_with("HTTP/1.0 404 Not Found\r\n"), "{}", not_found);
+        assert!(post.starts_with("HTTP/1.0 405 Method Not Allowed\r\n"), "{}", post);
+    }
+}
@@ -0,0 +1,101 @@
+//! This module contains [`blocking`].
+use std::io::Error;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::coroutine::YieldStatus;
+
+/// The pointer to the result of the closure. It is written by the thread of the blocking pool,
+/// while the coroutine, that owns the result, is suspended.
+struct ResultPtr<T>(*mut Result<T, Error>);
+
+impl<T> ResultPtr<T> {
+    /// Writes the result. It is a method, so the closure captures the whole [`ResultPtr`], that is `Send`.
+    #[inline(always)]
+    unsafe fn write(&self, result: Result<T, Error>) {
+        unsafe { self.0.write(result) };
+    }
+}
+
+unsafe impl<T: Send> Send for ResultPtr<T> {}
+
+/// Tells the scheduler to run `func` on the blocking pool and to wake the coroutine up with its result.
+///
+/// Use it for CPU-heavy work and for syscalls, that neither io_uring nor epoll can do asynchronously
+/// (`getdents`, `getaddrinfo`, compression and so on), so they don't stall the worker.
+///
+/// The pool has at most [`config_blocking_threads`](crate::cfg::config_blocking_threads) threads per worker.
+/// Threads are started on demand, so workers without blocking work don't have them.
+///
+/// # Errors
+///
+/// Returns an error with [`ErrorKind::Other`](std::io::ErrorKind::Other), if `func` panics.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::blocking::blocking;
+///
+/// #[coro]
+/// fn resolve() {
+///     let res: Result<Vec<std::net::SocketAddr>, Error> = yield blocking(|| {
+///         std::net::ToSocketAddrs::to_socket_addrs("localhost:80").map(|addrs| addrs.collect())
+///     });
+///     let addrs = res.unwrap();
+/// }
+/// ```
+pub fn blocking<T, F>(func: F, res: *mut Result<T, Error>) -> YieldStatus
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static
+{
+    let res = ResultPtr(res);
+    YieldStatus::blocking(Box::new(move || {
+        let result = catch_unwind(AssertUnwindSafe(func))
+            .map_err(|_| Error::other("the blocking closure panicked"));
+        unsafe { res.write(result) };
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::time::{Duration, Instant};
+    use crate::{coro, test_local};
+    use crate::blocking::blocking;
+    use crate::local::Local;
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+    use std::ptr::null_mut;
+
+    #[test_local(crate="crate")]
+    fn test_blocking() {
+        #[coro(crate="crate")]
+        fn sleep_blocking(done: Local<usize>) {
+            let res: Result<(), Error> = yield blocking(|| std::thread::sleep(Duration::from_millis(50)));
+            res.unwrap();
+            *done.get_mut() += 1;
+        }
+
+        let res: Result<u64, Error> = yield blocking(|| (1..=10u64).product());
+        assert_eq!(res.unwrap(), 3_628_800);
+
+        let res: Result<(), Error> = yield blocking(|| panic!("expected panic"));
+        assert!(res.is_err());
+
+        // The worker is not blocked, and the closures run in parallel.
+        let done = Local::new(0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            local_scheduler().sched(sleep_blocking(done.clone(), null_mut()));
+        }
+        for _ in 0..1000 {
+            if *done.get() == 3 {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*done.get(), 3);
+        assert!(start.elapsed() < Duration::from_millis(140));
+    }
+}
@@ -22,11 +22,38 @@ pub fn buffer() -> Buffer {
     buf_pool().get()
 }
 
+/// Get [`Buffer`] for a high-priority operation from local [`BufPool`]. Read [`BufPool::get_priority`] for more information.
+#[inline(always)]
+pub fn priority_buffer() -> Buffer {
+    buf_pool().get_priority()
+}
+
 
 /// Pool of [`Buffer`]s. It is used for reusing memory. If you need to change default buffer size, use [`BufPool::tune_buffer_len`].
+///
+/// # Priority reservation
+///
+/// A small number of buffers can be reserved for high-priority operations (health checks, control channel)
+/// with [`BufPool::reserve_for_priority`]. These buffers are taken only by [`BufPool::get_priority`],
+/// so the data plane can't starve the control plane, when the pool is exhausted.
+///
+/// # Pressure
+///
+/// [`BufPool::in_use`] returns how many buffers are taken and not returned.
+/// It is a signal of memory pressure, that is used by [`OverloadProtection`](crate::scheduler::OverloadProtection).
+///
+/// # Registered buffers
+///
+/// [`BufPool::alloc_fixed`] allocates buffers, which memory is registered with io_uring by the selector.
+/// Read [`set_fixed_buffers`](crate::cfg::set_fixed_buffers) for more information.
 pub struct BufPool {
     pool: Vec<Buffer>,
-    buffer_len: usize
+    reserved: Vec<Buffer>,
+    reserved_len: usize,
+    buffer_len: usize,
+    in_use: usize,
+    /// The memory of the registered buffers by their indexes.
+    fixed: Vec<libc::iovec>
 }
 
 impl BufPool {
@@ -36,7 +63,11 @@ impl BufPool {
             let pool_ref = unsafe { &mut *pool.get() };
             *pool_ref = MaybeUninit::new(BufPool {
                 pool: Vec::with_capacity(0),
-                buffer_len
+                reserved: Vec::with_capacity(0),
+                reserved_len: 0,
+                buffer_len,
+                in_use: 0,
+                fixed: Vec::new()
             });
         });
     }
@@ -44,18 +75,80 @@ impl BufPool {
     /// Uninitialize [`BufPool`] in local thread.
     pub(crate) fn uninit_in_local_thread() {
         BUF_POOL.with(|pool| {
-            unsafe { (&mut *pool.get()).assume_init_drop()};
+            let pool = unsafe { &mut *pool.get() };
+            // Buffers return themselves to the pool on drop, so they must forget about the pool before it is dropped.
+            let pool_ref = unsafe { pool.assume_init_mut() };
+            for buf in pool_ref.pool.iter_mut().chain(pool_ref.reserved.iter_mut()) {
+                buf.from_pool = false;
+            }
+            unsafe { pool.assume_init_drop()};
         });
     }
 
     /// Change default buffer size.
     pub fn tune_buffer_len(&mut self, buffer_len: usize) {
         self.buffer_len = buffer_len;
+        // Old buffers must not return themselves to the pool on drop, because they have the old length.
+        // Their memory is freed, so it can't be registered anymore.
+        for buf in self.pool.iter_mut().chain(self.reserved.iter_mut()) {
+            buf.from_pool = false;
+            buf.fixed_index = None;
+        }
+        self.fixed.clear();
         self.pool = Vec::with_capacity(0);
+        self.reserved = Vec::with_capacity(0);
+        self.reserve_for_priority(self.reserved_len);
+    }
+
+    /// Reserves `count` buffers for high-priority operations. They are allocated at once.
+    ///
+    /// Reserved buffers are taken only by [`BufPool::get_priority`].
+    /// Returned buffers refill the reserve first, so it is restored after the high-priority operations.
+    pub fn reserve_for_priority(&mut self, count: usize) {
+        self.reserved_len = count;
+        if self.reserved.len() > count {
+            let extra = self.reserved.split_off(count);
+            self.pool.extend(extra);
+            return;
+        }
+
+        while self.reserved.len() < count {
+            self.reserved.push(Buffer::new_from_pool(self.buffer_len));
+        }
+    }
+
+    /// Allocates `count` buffers, which memory is registered with io_uring, and puts them to the pool.
+    /// Returns the memory of the registered buffers by their indexes.
+    ///
+    /// It does nothing, if the buffers are already allocated.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn alloc_fixed(&mut self, count: usize) -> &[libc::iovec] {
+        if self.fixed.is_empty() {
+            for index in 0..count {
+                let mut buf = Buffer::new_fixed(self.buffer_len, index as u16);
+                self.fixed.push(libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.cap() });
+                self.pool.push(buf);
+            }
+        }
+
+        &self.fixed
+    }
+
+    /// Returns the memory of the registered buffers by their indexes.
+    #[cfg(target_os = "linux")]
+    #[inline(always)]
+    pub(crate) fn fixed(&self) -> &[libc::iovec] {
+        &self.fixed
+    }
+
+    /// Puts the registered memory of a buffer, that has grown, back to the pool.
+    pub(crate) fn return_fixed(&mut self, slice: Box<[u8]>, index: u16) {
+        self.pool.push(Buffer::from_fixed_slice(slice, index));
     }
 
     /// Get [`Buffer`] from [`BufPool`].
     pub fn get(&mut self) -> Buffer {
+        self.in_use += 1;
         if unlikely(self.pool.is_empty()) {
             return Buffer::new_from_pool(self.buffer_len);
         }
@@ -63,11 +156,124 @@ impl BufPool {
         unsafe { self.pool.pop().unwrap_unchecked() }
     }
 
+    /// Get [`Buffer`] for a high-priority operation from [`BufPool`].
+    ///
+    /// It takes a reserved buffer (read [`BufPool::reserve_for_priority`]), and falls back to [`BufPool::get`], if the reserve is empty.
+    pub fn get_priority(&mut self) -> Buffer {
+        match self.reserved.pop() {
+            Some(buf) => {
+                self.in_use += 1;
+                buf
+            }
+            None => self.get()
+        }
+    }
+
+    /// Returns the length of buffers in the pool.
+    #[inline(always)]
+    pub fn buffer_len(&self) -> usize {
+        self.buffer_len
+    }
+
+    /// Returns the number of buffers, that are taken from [`BufPool`] and not returned yet.
+    #[inline(always)]
+    pub fn in_use(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns the approximate number of bytes, that are held by buffers of [`BufPool`]: pooled, reserved and in use.
+    ///
+    /// Buffers in use are counted with the length of the pool, even if they have grown.
+    pub fn held_bytes(&self) -> usize {
+        (self.pool.len() + self.reserved.len() + self.in_use) * self.buffer_len
+    }
+
+    /// Forgets a taken buffer, that will never be returned (for example, it was resized).
+    #[inline(always)]
+    pub(crate) fn forget(&mut self) {
+        self.in_use = self.in_use.saturating_sub(1);
+    }
+
     /// Put [`Buffer`] to [`BufPool`].
     pub fn put(&mut self, mut buf: Buffer) {
         if likely(buf.from_pool) {
+            self.forget();
             buf.clear();
+            if unlikely(self.fixed.is_empty()) {
+                buf.fixed_index = None;
+            }
+            if unlikely(self.reserved.len() < self.reserved_len) {
+                self.reserved.push(buf);
+                return;
+            }
             self.pool.push(buf);
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_reservation() {
+        BufPool::init_in_local_thread(64);
+        let pool = buf_pool();
+        pool.reserve_for_priority(2);
+
+        // The data plane can't take reserved buffers.
+        let data: Vec<Buffer> = (0..4).map(|_| buffer()).collect();
+        assert_eq!(pool.reserved.len(), 2);
+
+        let control = priority_buffer();
+        assert_eq!(pool.reserved.len(), 1);
+
+        // Returned buffers refill the reserve first.
+        drop(control);
+        drop(data);
+        assert_eq!(pool.reserved.len(), 2);
+        assert_eq!(pool.pool.len(), 4);
+
+        pool.reserve_for_priority(1);
+        assert_eq!(pool.reserved.len(), 1);
+        assert_eq!(pool.pool.len(), 5);
+        assert_eq!(pool.in_use(), 0);
+
+        let mut grown = buffer();
+        let taken = priority_buffer();
+        assert_eq!(pool.in_use(), 2);
+        grown.append(&[0; 100]);
+        drop(grown);
+        drop(taken);
+        assert_eq!(pool.in_use(), 0);
+
+        BufPool::uninit_in_local_thread();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fixed() {
+        BufPool::init_in_local_thread(64);
+        let pool = buf_pool();
+        let fixed = pool.alloc_fixed(2).to_vec();
+        assert_eq!(fixed.len(), 2);
+        assert_eq!(pool.alloc_fixed(4).len(), 2);
+
+        let mut buf = buffer();
+        let index = buf.fixed_index().unwrap();
+        assert_eq!(buf.as_ptr(), fixed[index as usize].iov_base as *const u8);
+
+        // The grown buffer leaves the pool, but its registered memory stays there.
+        buf.append(&[1; 100]);
+        assert_eq!(buf.fixed_index(), None);
+        assert_eq!(pool.in_use(), 0);
+        let again = (0..2).map(|_| buffer()).find(|buf| buf.fixed_index() == Some(index)).unwrap();
+        assert_eq!(again.as_ptr(), fixed[index as usize].iov_base as *const u8);
+        drop(again);
+
+        pool.tune_buffer_len(128);
+        assert!(pool.fixed().is_empty());
+        assert_eq!(buffer().fixed_index(), None);
+
+        BufPool::uninit_in_local_thread();
+    }
+}
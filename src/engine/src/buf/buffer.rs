@@ -1,7 +1,10 @@
+use std::any::Any;
 use std::fmt::Debug;
 use std::intrinsics::unlikely;
 use std::io::{Read, Write};
-use std::{cmp, mem};
+use std::{cmp, mem, ptr};
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::rc::Rc;
 use crate::buf::buf_pool::buf_pool;
 
 /// Buffer for data transfer. Buffer is allocated in heap.
@@ -36,12 +39,61 @@ use crate::buf::buf_pool::buf_pool;
 /// 5 blocks occupied (X), 3 blocks free (blank)
 /// ```
 /// 
+/// # Alignment
+///
+/// Files opened with [`OpenOptions::direct`](crate::fs::OpenOptions::direct) require aligned memory.
+/// Use [`Buffer::new_aligned`] for them. The alignment is kept, when the buffer grows.
+///
+/// # Views
+///
+/// A buffer can be a read-only view of memory, that it doesn't own, like a region of [`Mmap`](crate::fs::Mmap).
+/// It keeps the owner alive and never frees the memory. When a view grows, it copies the data into its own memory
+/// and stops being a view.
+///
+/// # Registered buffers
+///
+/// If [`set_fixed_buffers`](crate::cfg::set_fixed_buffers) is not 0, some buffers of the [`BufPool`] are registered
+/// with io_uring, so the kernel doesn't pin their pages on every operation. Such a buffer has a
+/// [`fixed_index`](Buffer::fixed_index). When it grows, its memory is returned to the pool, and it stops being registered.
+///
 /// [`BufPool`]: crate::buf::BufPool
 pub struct Buffer {
+    /// It is allocated with `align`, so it must be freed with [`free_slice`], not dropped.
     pub(crate) slice: Box<[u8]>,
     written: usize,
     offset: usize,
-    pub(crate) from_pool: bool
+    pub(crate) from_pool: bool,
+    align: usize,
+    /// The owner of the memory of a view. If it is set, the slice is not freed by the buffer.
+    owner: Option<Rc<dyn Any>>,
+    /// The index of the memory in the buffers, that are registered with io_uring.
+    pub(crate) fixed_index: Option<u16>
+}
+
+/// Allocates a zeroed slice with the given alignment. The slice is readable as `&[u8]`, so it can't be uninitialized.
+#[inline(always)]
+fn alloc_slice(size: usize, align: usize) -> Box<[u8]> {
+    if align <= 1 || size == 0 {
+        return vec![0; size].into_boxed_slice();
+    }
+
+    let layout = Layout::from_size_align(size, align).expect("invalid buffer alignment");
+    let ptr = unsafe { alloc_zeroed(layout) };
+    if ptr.is_null() {
+        handle_alloc_error(layout);
+    }
+    unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, size)) }
+}
+
+/// Frees a slice allocated by [`alloc_slice`] with the same alignment.
+#[inline(always)]
+fn free_slice(slice: Box<[u8]>, align: usize) {
+    if align <= 1 || slice.is_empty() {
+        return drop(slice);
+    }
+
+    let layout = Layout::from_size_align(slice.len(), align).expect("invalid buffer alignment");
+    unsafe { dealloc(Box::into_raw(slice) as *mut u8, layout) };
 }
 
 impl Buffer {
@@ -49,25 +101,117 @@ impl Buffer {
     /// So, use it only for creating a buffer with specific size.
     #[inline(always)]
     pub fn new(size: usize) -> Self {
-        let mut v = Vec::with_capacity(size);
-        unsafe { v.set_len(size) };
         Buffer {
-            slice: v.into_boxed_slice(),
+            slice: alloc_slice(size, 1),
             written: 0,
             offset: 0,
-            from_pool: false
+            from_pool: false,
+            align: 1,
+            owner: None,
+            fixed_index: None
+        }
+    }
+
+    /// Creates a new buffer with given size, which memory is aligned to `align` (a power of two).
+    /// This buffer will not be put to the pool.
+    ///
+    /// It is needed for files opened with [`OpenOptions::direct`](crate::fs::OpenOptions::direct).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn new_aligned(size: usize, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "buffer alignment must be a power of two");
+        Buffer {
+            slice: alloc_slice(size, align),
+            written: 0,
+            offset: 0,
+            from_pool: false,
+            align,
+            owner: None,
+            fixed_index: None
         }
     }
 
     /// Creates a new buffer from a pool with the given size.
     pub(crate) fn new_from_pool(size: usize) -> Self {
-        let mut v = Vec::with_capacity(size);
-        unsafe { v.set_len(size) };
         Buffer {
-            slice: v.into_boxed_slice(),
+            slice: alloc_slice(size, 1),
             written: 0,
             offset: 0,
-            from_pool: true
+            from_pool: true,
+            align: 1,
+            owner: None,
+            fixed_index: None
+        }
+    }
+
+    /// Creates a new buffer from a pool with the given size, which memory is registered with io_uring by the `index`.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn new_fixed(size: usize, index: u16) -> Self {
+        Self::from_fixed_slice(alloc_slice(size, 1), index)
+    }
+
+    /// Creates a new buffer from a pool with the registered memory of another buffer.
+    pub(crate) fn from_fixed_slice(slice: Box<[u8]>, index: u16) -> Self {
+        Buffer {
+            slice,
+            written: 0,
+            offset: 0,
+            from_pool: true,
+            align: 1,
+            owner: None,
+            fixed_index: Some(index)
+        }
+    }
+
+    /// Creates a read-only view of `len` bytes at `ptr`, that are kept alive by `owner`.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for reads, while `owner` is alive.
+    pub(crate) unsafe fn new_view(ptr: *const u8, len: usize, owner: Rc<dyn Any>) -> Self {
+        Buffer {
+            slice: unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr as *mut u8, len)) },
+            written: len,
+            offset: 0,
+            from_pool: false,
+            align: 1,
+            owner: Some(owner),
+            fixed_index: None
+        }
+    }
+
+    /// Returns true, if the buffer is a read-only view of memory, that it doesn't own.
+    #[inline(always)]
+    pub fn is_view(&self) -> bool {
+        self.owner.is_some()
+    }
+
+    /// Returns the index of the memory of the buffer in the buffers, that are registered with io_uring,
+    /// if the buffer is registered. Read [`set_fixed_buffers`](crate::cfg::set_fixed_buffers).
+    #[inline(always)]
+    pub fn fixed_index(&self) -> Option<u16> {
+        self.fixed_index
+    }
+
+    /// Returns the alignment of the memory of the buffer. It is 1 for usual buffers.
+    #[inline(always)]
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// Replaces the memory of the buffer and frees the old one.
+    #[inline(always)]
+    fn replace_slice(&mut self, slice: Box<[u8]>) {
+        let old = mem::replace(&mut self.slice, slice);
+        if let Some(index) = self.fixed_index.take() {
+            // The registered memory must live, while the ring can use it, so it goes back to the pool.
+            buf_pool().return_fixed(old, index);
+        } else if self.owner.take().is_some() {
+            mem::forget(old);
+        } else {
+            free_slice(old, self.align);
         }
     }
 
@@ -94,24 +238,42 @@ impl Buffer {
         self.offset = offset;
     }
 
+    /// Sets how many bytes have been written into the buffer.
+    /// It is used after the kernel has written data into the buffer, for example, in [`File::read`](crate::fs::File).
+    /// Used it only if you know what you are doing. In most cases it is need only for inner work.
+    #[inline(always)]
+    pub fn set_written(&mut self, written: usize) {
+        self.written = written;
+    }
+
     /// Returns capacity of the buffer.
     #[inline(always)]
     pub fn cap(&self) -> usize {
         self.slice.len()
     }
 
+    /// Reserves capacity for at least `additional` more bytes after the written ones.
+    /// If the buffer is resized, it will not be put to the pool.
+    pub fn reserve(&mut self, additional: usize) {
+        if unlikely(additional > self.slice.len() - self.written || self.owner.is_some()) {
+            let new_len = self.written + additional;
+            let mut slice = alloc_slice(new_len, self.align);
+            slice[..self.written].copy_from_slice(&self.slice[..self.written]);
+            self.replace_slice(slice);
+            self.leave_pool();
+        }
+    }
+
     /// Appends data to the buffer. If a capacity is not enough, the buffer will be resized and will not be put to the pool.
     // TODO: need test
     pub fn append(&mut self, buf: &[u8]) {
         let len = buf.len();
-        if unlikely(len > self.slice.len() - self.written) {
-            let temp = self.slice[..self.written].to_vec();
-            let new_len = (temp.len() + len) * 2;
-            let mut v = Vec::with_capacity(new_len);
-            unsafe { v.set_len(new_len) };
-            self.slice = v.into_boxed_slice();
-            self.slice[..self.written].copy_from_slice(&temp);
-            self.from_pool = false;
+        if unlikely(len > self.slice.len() - self.written || self.owner.is_some()) {
+            let new_len = (self.written + len) * 2;
+            let mut slice = alloc_slice(new_len, self.align);
+            slice[..self.written].copy_from_slice(&self.slice[..self.written]);
+            self.replace_slice(slice);
+            self.leave_pool();
         }
 
         self.slice[self.written..self.written + len].copy_from_slice(buf);
@@ -132,7 +294,12 @@ impl Buffer {
     /// # Note
     ///
     /// The pointer is shifted by `offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is a view.
     pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        assert!(self.owner.is_none(), "a view buffer is read-only");
         unsafe { self.slice.as_mut_ptr().offset(self.offset as isize) }
     }
 
@@ -142,6 +309,15 @@ impl Buffer {
         self.offset = 0;
     }
 
+    /// Marks the buffer as not from the pool, so it will not be returned to it.
+    #[inline(always)]
+    fn leave_pool(&mut self) {
+        if self.from_pool {
+            self.from_pool = false;
+            buf_pool().forget();
+        }
+    }
+
     /// Puts the buffer to the pool. You can not to use it, and then this method will be called automatically by drop.
     pub fn release(self) {
         buf_pool().put(self);
@@ -176,6 +352,7 @@ impl AsRef<[u8]> for Buffer {
 
 impl AsMut<[u8]> for Buffer {
     fn as_mut(&mut self) -> &mut [u8] {
+        assert!(self.owner.is_none(), "a view buffer is read-only");
         &mut self.slice[self.offset..self.written]
     }
 }
@@ -194,7 +371,46 @@ impl Debug for Buffer {
 
 impl Drop for Buffer {
     fn drop(&mut self) {
-        let buf = mem::take(self);
-        buf.release();
+        if self.owner.is_some() {
+            mem::forget(mem::take(&mut self.slice));
+        } else if self.from_pool {
+            let buf = mem::take(self);
+            buf.release();
+        } else if self.align > 1 {
+            let slice = mem::take(&mut self.slice);
+            free_slice(slice, self.align);
+        }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned() {
+        let mut buf = Buffer::new_aligned(4096, 4096);
+        assert_eq!(buf.align(), 4096);
+        assert_eq!(buf.as_ptr() as usize % 4096, 0);
+
+        buf.append(&[1; 4096]);
+        buf.append(&[2; 10]);
+        assert!(buf.cap() > 4096);
+        assert_eq!(buf.as_ptr() as usize % 4096, 0);
+        assert_eq!(&buf.as_ref()[4090..], &[1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+
+        buf.reserve(100_000);
+        assert_eq!(buf.as_ptr() as usize % 4096, 0);
+        assert_eq!(buf.len(), 4106);
+    }
+
+    #[test]
+    fn test_reserve_is_zeroed() {
+        for align in [1, 4096] {
+            let mut buf = Buffer::new_aligned(16, align);
+            buf.append(&[7; 16]);
+            buf.reserve(100);
+            assert_eq!(&buf.slice[..16], &[7; 16]);
+            assert!(buf.slice[16..].iter().all(|&byte| byte == 0));
+        }
+    }
+}
@@ -4,4 +4,4 @@ pub mod buf_pool;
 pub mod buffer;
 
 pub use self::buffer::Buffer;
-pub use self::buf_pool::{BufPool, buffer, buf_pool};
\ No newline at end of file
+pub use self::buf_pool::{BufPool, buffer, buf_pool, priority_buffer};
\ No newline at end of file
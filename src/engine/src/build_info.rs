@@ -0,0 +1,162 @@
+//! This module contains [`build_info`].
+use std::fmt::{Display, Formatter};
+use crate::cfg::{config_blocking_threads, config_buf_len, config_selector, config_write_turn_cap, SelectorType};
+#[cfg(target_os = "linux")]
+use crate::cfg::config_ring_setup;
+#[cfg(target_os = "linux")]
+use crate::io::KernelVersion;
+#[cfg(target_os = "linux")]
+use crate::io::sys::unix::{MAX_EPOLL_EVENTS_RETURNED as MAX_EVENTS_RETURNED, REQ_BUF_LEN};
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+use crate::io::sys::bsd::{MAX_KQUEUE_EVENTS_RETURNED as MAX_EVENTS_RETURNED, REQ_BUF_LEN};
+
+/// Enabled cargo features of the engine.
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "profiling")]
+    "profiling",
+    #[cfg(feature = "admin")]
+    "admin"
+];
+
+/// The parameters of the [`Selector`](crate::io::selector::Selector)s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorParams {
+    /// The number of entries in the submission queue of the ring. It is 0 off Linux.
+    pub ring_entries: u32,
+    /// The maximum number of events, that one `epoll_wait` (`kevent` off Linux) returns.
+    pub epoll_max_events: usize,
+    /// The length of the buffer, that epoll (kqueue off Linux) reads sockets into.
+    pub epoll_read_buf_len: usize
+}
+
+/// The version, the build and the runtime configuration of the engine.
+///
+/// Read [`build_info`] for more information.
+#[derive(Debug, Clone)]
+pub struct BuildInfo {
+    /// The version of the engine crate.
+    pub version: &'static str,
+    /// Enabled cargo features.
+    pub features: &'static [&'static str],
+    /// `debug` or `release`.
+    pub profile: &'static str,
+    /// The target architecture, like `x86_64`.
+    pub target_arch: &'static str,
+    /// The configured selector.
+    pub selector: SelectorType,
+    /// The parameters of the selectors.
+    pub selector_params: SelectorParams,
+    /// The length of buffers of the pool.
+    pub buf_len: usize,
+    /// The maximum number of threads of the blocking pool of each worker.
+    pub blocking_threads: usize,
+    /// The maximum number of bytes, that one `write_all` sends per turn.
+    pub write_turn_cap: usize,
+    /// The version of the running kernel, if it can be read.
+    #[cfg(target_os = "linux")]
+    pub kernel_version: Option<KernelVersion>
+}
+
+/// Returns the version, the build and the runtime configuration of the engine. It is cheap enough for health endpoints.
+///
+/// The configuration is read from the current [`SchedulerCfg`](crate::cfg::SchedulerCfg).
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::build_info;
+///
+/// // engine 0.1.0 (release, x86_64), selector: ring, ring entries: 1024, ...
+/// println!("{}", build_info());
+/// ```
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features: FEATURES,
+        profile: if cfg!(debug_assertions) { "debug" } else { "release" },
+        target_arch: std::env::consts::ARCH,
+        selector: config_selector(),
+        selector_params: SelectorParams {
+            ring_entries: ring_entries(),
+            epoll_max_events: MAX_EVENTS_RETURNED,
+            epoll_read_buf_len: REQ_BUF_LEN
+        },
+        buf_len: config_buf_len(),
+        blocking_threads: config_blocking_threads(),
+        write_turn_cap: config_write_turn_cap(),
+        #[cfg(target_os = "linux")]
+        kernel_version: KernelVersion::current().ok()
+    }
+}
+
+/// Returns the number of entries of the configured ring.
+#[cfg(target_os = "linux")]
+fn ring_entries() -> u32 {
+    config_ring_setup().entries
+}
+
+/// Returns 0, because there is no ring off Linux.
+#[cfg(not(target_os = "linux"))]
+fn ring_entries() -> u32 {
+    0
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "engine {} ({}, {})", self.version, self.profile, self.target_arch)?;
+        if !self.features.is_empty() {
+            write!(f, ", features: {}", self.features.join(","))?;
+        }
+        match self.selector {
+            SelectorType::Ring => write!(f, ", selector: ring, ring entries: {}", self.selector_params.ring_entries)?,
+            SelectorType::Poller => write!(
+                f, ", selector: epoll, max events: {}, read buffer: {}",
+                self.selector_params.epoll_max_events, self.selector_params.epoll_read_buf_len
+            )?,
+            SelectorType::Auto => write!(
+                f, ", selector: auto, ring entries: {}, max events: {}, read buffer: {}", self.selector_params.ring_entries,
+                self.selector_params.epoll_max_events, self.selector_params.epoll_read_buf_len
+            )?,
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly"
+            ))]
+            SelectorType::Kqueue => write!(
+                f, ", selector: kqueue, max events: {}, read buffer: {}",
+                self.selector_params.epoll_max_events, self.selector_params.epoll_read_buf_len
+            )?
+        }
+        write!(f, ", buffer: {}, blocking threads: {}", self.buf_len, self.blocking_threads)?;
+        #[cfg(target_os = "linux")]
+        if let Some(kernel_version) = self.kernel_version {
+            write!(f, ", kernel: {}", kernel_version)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_info;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.buf_len > 0);
+        let line = info.to_string();
+        assert!(line.starts_with(&format!("engine {}", info.version)));
+        assert!(line.contains("selector: "));
+    }
+}
@@ -1,19 +1,121 @@
+use std::cell::RefCell;
+use std::sync::RwLock;
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use crate::io::RingSetup;
+#[cfg(target_os = "linux")]
+use crate::sandbox::Sandbox;
+use crate::scheduler::{AcceptWarmup, CoroutineLimits, IdleStrategy, OverloadProtection, PanicHook};
+
 /// A type of the [`Selector`](crate::io::selector::Selector).
-/// It can be `Poller` or `Ring`.
+/// It can be `Poller`, `Ring`, `Auto` or `Kqueue`.
 ///
 /// Ring based on `io-uring` for Linux.
 ///
 /// Poller based on `epoll` for Linux.
-#[derive(Copy, Clone)]
+///
+/// Auto is Ring, if io_uring is available, else Poller. io_uring is not available on old kernels
+/// (the engine requires Linux 5.11), when it is disabled by `io_uring_disabled` or when it is forbidden by seccomp
+/// (for example, in containers).
+///
+/// Kqueue based on `kqueue` for BSD-like systems (macOS, FreeBSD and others). It is the default there,
+/// and the other types fall back to it, because neither epoll nor io_uring exists.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SelectorType {
     Poller,
-    Ring
+    Ring,
+    Auto,
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly"
+    ))]
+    Kqueue
+}
+
+/// The default [`SelectorType`] of the target.
+#[cfg(target_os = "linux")]
+const DEFAULT_SELECTOR: SelectorType = SelectorType::Ring;
+/// The default [`SelectorType`] of the target.
+#[cfg(not(target_os = "linux"))]
+const DEFAULT_SELECTOR: SelectorType = SelectorType::Kqueue;
+
+/// The order, in which ready coroutines of the same [`Priority`](crate::scheduler::Priority) are run.
+///
+/// Read [`Scheduler`](crate::scheduler::Scheduler) for why LIFO is the default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SchedulingPolicy {
+    /// The last spawned coroutine runs first. Yielded coroutines run after all ready ones.
+    Lifo,
+    /// Coroutines run in the order, in which they became ready.
+    Fifo,
+    /// Like [`Lifo`](SchedulingPolicy::Lifo), but after `n` pops in a row the oldest ready coroutine runs,
+    /// so long chains of spawned coroutines can't starve earlier ones.
+    LifoWithBudget { n: usize }
 }
 
 /// The configuration of the scheduler.
+///
+/// Every worker gets its own copy, when it is started, so changing the global configuration later doesn't affect
+/// running workers. Pass a configuration to [`run_on_core_with_config`](crate::run::run_on_core_with_config) or
+/// [`run_on_all_cores_with_config`](crate::run::run_on_all_cores_with_config) to tune workers separately.
+/// Otherwise, workers copy the global configuration, that is changed by the setters of this module.
+///
+/// Inside a worker the getters of this module return the values of its configuration.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::run_on_core_with_config;
+/// use engine::cfg::{config, SelectorType};
+/// use engine::scheduler::IdleStrategy;
+/// use engine::utils::get_core_ids;
+///
+/// fn main() {
+///     let cores = get_core_ids().unwrap();
+///     // A latency-critical worker spins, and the other one sleeps, when it is idle.
+///     let fast = config().with_selector(SelectorType::Ring).with_idle_strategy(IdleStrategy::spin());
+///     let slow = config().with_buf_len(64 * 1024).with_idle_strategy(IdleStrategy::backoff());
+///     let core = cores[1];
+///     let background = std::thread::spawn(move || run_on_core_with_config(start_background, core, slow));
+///     run_on_core_with_config(start_server, cores[0], fast).unwrap();
+///     background.join().unwrap().unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug)]
 pub struct SchedulerCfg {
-    buf_len: usize,
-    selector: SelectorType
+    pub(crate) buf_len: usize,
+    pub(crate) selector: SelectorType,
+    #[cfg(target_os = "linux")]
+    pub(crate) sandbox: Option<Sandbox>,
+    pub(crate) overload_protection: Option<OverloadProtection>,
+    pub(crate) accept_warmup: Option<AcceptWarmup>,
+    pub(crate) blocking_threads: usize,
+    pub(crate) write_turn_cap: usize,
+    pub(crate) soft_memory_limit: Option<usize>,
+    pub(crate) work_stealing: bool,
+    pub(crate) worker_stack_size: Option<usize>,
+    pub(crate) worker_nice: Option<i32>,
+    pub(crate) skip_smt_siblings: bool,
+    pub(crate) reserved_cores: usize,
+    pub(crate) scheduling_policy: SchedulingPolicy,
+    pub(crate) timer_tick: Duration,
+    pub(crate) ring_backlog_cap: usize,
+    pub(crate) ring_resize: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) ring_setup: RingSetup,
+    pub(crate) fixed_buffers: usize,
+    pub(crate) fixed_files: u32,
+    pub(crate) multishot_recv_buffers: u16,
+    pub(crate) ring_timeouts: bool,
+    pub(crate) isolate_panics: bool,
+    pub(crate) panic_hook: Option<PanicHook>,
+    pub(crate) poll_interval: u32,
+    pub(crate) coroutine_limits: Option<CoroutineLimits>,
+    pub(crate) idle_strategy: IdleStrategy
 }
 
 impl SchedulerCfg {
@@ -22,39 +124,646 @@ impl SchedulerCfg {
     pub const fn default() -> Self {
         Self {
             buf_len: 4096,
-            selector: SelectorType::Ring
+            selector: DEFAULT_SELECTOR,
+            #[cfg(target_os = "linux")]
+            sandbox: None,
+            overload_protection: None,
+            accept_warmup: None,
+            blocking_threads: 4,
+            write_turn_cap: 256 * 1024,
+            soft_memory_limit: None,
+            work_stealing: false,
+            worker_stack_size: None,
+            worker_nice: None,
+            skip_smt_siblings: false,
+            reserved_cores: 0,
+            scheduling_policy: SchedulingPolicy::Lifo,
+            timer_tick: Duration::from_millis(1),
+            ring_backlog_cap: 64 * 1024,
+            ring_resize: false,
+            #[cfg(target_os = "linux")]
+            ring_setup: RingSetup::default(),
+            fixed_buffers: 0,
+            fixed_files: 0,
+            multishot_recv_buffers: 0,
+            ring_timeouts: false,
+            isolate_panics: true,
+            panic_hook: None,
+            poll_interval: 32,
+            coroutine_limits: None,
+            idle_strategy: IdleStrategy::adaptive()
         }
     }
+
+    /// Sets the length of buffers of the [`BufPool`](crate::buf::BufPool). Read [`set_buf_len`] for more information.
+    pub fn with_buf_len(mut self, buf_len: usize) -> Self {
+        self.buf_len = buf_len;
+        self
+    }
+
+    /// Sets the [`SelectorType`]. Read [`set_selector`] for more information.
+    pub fn with_selector(mut self, selector: SelectorType) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Sets the [`Sandbox`], that every worker installs. Read [`set_sandbox`] for more information.
+    #[cfg(target_os = "linux")]
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Sets the [`OverloadProtection`]. Read [`set_overload_protection`] for more information.
+    pub fn with_overload_protection(mut self, overload_protection: Option<OverloadProtection>) -> Self {
+        self.overload_protection = overload_protection;
+        self
+    }
+
+    /// Sets the [`AcceptWarmup`]. Read [`set_accept_warmup`] for more information.
+    pub fn with_accept_warmup(mut self, accept_warmup: Option<AcceptWarmup>) -> Self {
+        self.accept_warmup = accept_warmup;
+        self
+    }
+
+    /// Sets the maximum number of threads of the blocking pool of a worker. Read [`set_blocking_threads`] for more information.
+    pub fn with_blocking_threads(mut self, blocking_threads: usize) -> Self {
+        self.blocking_threads = blocking_threads;
+        self
+    }
+
+    /// Sets the number of bytes, that a write can send in one turn. Read [`set_write_turn_cap`] for more information.
+    pub fn with_write_turn_cap(mut self, write_turn_cap: usize) -> Self {
+        self.write_turn_cap = write_turn_cap;
+        self
+    }
+
+    /// Sets the soft memory limit of a worker. Read [`set_soft_memory_limit`] for more information.
+    pub fn with_soft_memory_limit(mut self, soft_memory_limit: Option<usize>) -> Self {
+        self.soft_memory_limit = soft_memory_limit;
+        self
+    }
+
+    /// Sets whether idle workers steal not started coroutines. Read [`set_work_stealing`] for more information.
+    pub fn with_work_stealing(mut self, work_stealing: bool) -> Self {
+        self.work_stealing = work_stealing;
+        self
+    }
+
+    /// Sets the stack size of worker threads. Read [`set_worker_stack_size`] for more information.
+    pub fn with_worker_stack_size(mut self, worker_stack_size: Option<usize>) -> Self {
+        self.worker_stack_size = worker_stack_size;
+        self
+    }
+
+    /// Sets the nice value of worker threads. Read [`set_worker_nice`] for more information.
+    pub fn with_worker_nice(mut self, worker_nice: Option<i32>) -> Self {
+        self.worker_nice = worker_nice;
+        self
+    }
+
+    /// Sets whether workers are started only on the first hardware thread of every physical core. Read [`set_skip_smt_siblings`] for more information.
+    pub fn with_skip_smt_siblings(mut self, skip_smt_siblings: bool) -> Self {
+        self.skip_smt_siblings = skip_smt_siblings;
+        self
+    }
+
+    /// Sets the number of cores, that are left to other threads. Read [`set_reserved_cores`] for more information.
+    pub fn with_reserved_cores(mut self, reserved_cores: usize) -> Self {
+        self.reserved_cores = reserved_cores;
+        self
+    }
+
+    /// Sets the [`SchedulingPolicy`]. Read [`set_scheduling_policy`] for more information.
+    pub fn with_scheduling_policy(mut self, scheduling_policy: SchedulingPolicy) -> Self {
+        self.scheduling_policy = scheduling_policy;
+        self
+    }
+
+    /// Sets the tick of the timing wheel. Read [`set_timer_tick`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timer_tick` is zero.
+    pub fn with_timer_tick(mut self, timer_tick: Duration) -> Self {
+        assert!(!timer_tick.is_zero(), "the tick of the timer wheel must be greater than zero");
+        self.timer_tick = timer_tick;
+        self
+    }
+
+    /// Sets the maximum length of the backlog of the ring. Read [`set_ring_backlog_cap`] for more information.
+    pub fn with_ring_backlog_cap(mut self, ring_backlog_cap: usize) -> Self {
+        self.ring_backlog_cap = ring_backlog_cap;
+        self
+    }
+
+    /// Sets whether the ring is resized, when its backlog grows. Read [`set_ring_resize`] for more information.
+    pub fn with_ring_resize(mut self, ring_resize: bool) -> Self {
+        self.ring_resize = ring_resize;
+        self
+    }
+
+    /// Sets the [`RingSetup`]. Read [`set_ring_setup`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ring_setup.entries` is 0.
+    #[cfg(target_os = "linux")]
+    pub fn with_ring_setup(mut self, ring_setup: RingSetup) -> Self {
+        assert!(ring_setup.entries > 0, "the ring must have entries");
+        self.ring_setup = ring_setup;
+        self
+    }
+
+    /// Sets the number of registered buffers. Read [`set_fixed_buffers`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fixed_buffers` is greater than [`MAX_FIXED_BUFFERS`].
+    pub fn with_fixed_buffers(mut self, fixed_buffers: usize) -> Self {
+        assert!(fixed_buffers <= MAX_FIXED_BUFFERS, "io_uring can't register more than {} buffers", MAX_FIXED_BUFFERS);
+        self.fixed_buffers = fixed_buffers;
+        self
+    }
+
+    /// Sets the number of slots for registered files. Read [`set_fixed_files`] for more information.
+    pub fn with_fixed_files(mut self, fixed_files: u32) -> Self {
+        self.fixed_files = fixed_files;
+        self
+    }
+
+    /// Sets the number of provided buffers of multishot receives. Read [`set_multishot_recv_buffers`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is not 0 and not a power of two.
+    pub fn with_multishot_recv_buffers(mut self, count: u16) -> Self {
+        assert!(count == 0 || count.is_power_of_two(), "the number of provided buffers must be a power of two");
+        self.multishot_recv_buffers = count;
+        self
+    }
+
+    /// Sets whether the ring wakes sleeping coroutines up. Read [`set_ring_timeouts`] for more information.
+    pub fn with_ring_timeouts(mut self, ring_timeouts: bool) -> Self {
+        self.ring_timeouts = ring_timeouts;
+        self
+    }
+
+    /// Sets whether panics of coroutines are caught. Read [`set_isolate_panics`] for more information.
+    pub fn with_isolate_panics(mut self, isolate_panics: bool) -> Self {
+        self.isolate_panics = isolate_panics;
+        self
+    }
+
+    /// Sets the [`PanicHook`]. Read [`set_panic_hook`] for more information.
+    pub fn with_panic_hook(mut self, panic_hook: Option<PanicHook>) -> Self {
+        self.panic_hook = panic_hook;
+        self
+    }
+
+    /// Sets the maximum number of coroutines, that are run between two iterations of the background work. Read [`set_poll_interval`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_interval` is 0.
+    pub fn with_poll_interval(mut self, poll_interval: u32) -> Self {
+        assert!(poll_interval > 0, "the poll interval must be positive");
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets the [`CoroutineLimits`]. Read [`set_coroutine_limits`] for more information.
+    pub fn with_coroutine_limits(mut self, coroutine_limits: Option<CoroutineLimits>) -> Self {
+        self.coroutine_limits = coroutine_limits;
+        self
+    }
+
+    /// Sets the [`IdleStrategy`]. Read [`set_idle_strategy`] for more information.
+    pub fn with_idle_strategy(mut self, idle_strategy: IdleStrategy) -> Self {
+        self.idle_strategy = idle_strategy;
+        self
+    }
 }
 
-/// The configuration of the scheduler.
-/// This will be read only on [`run_on_core`](crate::run::run_on_core) and [`run_on_all_cores`](crate::run::run_on_all_cores).
-pub(crate) static mut SCHEDULER_CFG: SchedulerCfg = SchedulerCfg::default();
+/// The maximum number of buffers, that io_uring can register. Read [`set_fixed_buffers`].
+pub const MAX_FIXED_BUFFERS: usize = 16 * 1024;
+
+/// The global configuration of the scheduler.
+/// This will be copied only on [`run_on_core`](crate::run::run_on_core) and [`run_on_all_cores`](crate::run::run_on_all_cores).
+static SCHEDULER_CFG: RwLock<SchedulerCfg> = RwLock::new(SchedulerCfg::default());
+
+thread_local! {
+    /// The configuration of the worker of the current thread, if the thread runs a worker.
+    static WORKER_CFG: RefCell<Option<SchedulerCfg>> = const { RefCell::new(None) };
+}
+
+/// Calls `f` with the configuration of the worker of the current thread or with the global configuration.
+#[inline(always)]
+fn read<R, F: FnOnce(&SchedulerCfg) -> R>(f: F) -> R {
+    WORKER_CFG.with(|worker_cfg| match worker_cfg.borrow().as_ref() {
+        Some(cfg) => f(cfg),
+        None => f(&SCHEDULER_CFG.read().unwrap())
+    })
+}
+
+/// Calls `f` with the global configuration to change it.
+fn write<F: FnOnce(&mut SchedulerCfg)>(f: F) {
+    f(&mut SCHEDULER_CFG.write().unwrap())
+}
+
+/// Returns a copy of the configuration of the worker of the current thread or of the global configuration.
+/// Use it as the base for [`run_on_core_with_config`](crate::run::run_on_core_with_config).
+pub fn config() -> SchedulerCfg {
+    read(|cfg| cfg.clone())
+}
+
+/// Makes `cfg` the configuration of the worker of the current thread.
+pub(crate) fn enter_worker(cfg: SchedulerCfg) {
+    WORKER_CFG.with(|worker_cfg| *worker_cfg.borrow_mut() = Some(cfg));
+}
+
+/// Drops the configuration of the worker of the current thread, when the worker is stopped.
+pub(crate) fn leave_worker() {
+    WORKER_CFG.with(|worker_cfg| *worker_cfg.borrow_mut() = None);
+}
 
 /// Getter for [`SCHEDULER_CFG::buf_len`].
 pub fn config_buf_len() -> usize {
-    unsafe { SCHEDULER_CFG.buf_len }
+    read(|cfg| cfg.buf_len)
 }
 
 /// Getter for [`SCHEDULER_CFG::selector`].
 pub fn config_selector() -> SelectorType {
-    unsafe { SCHEDULER_CFG.selector }
+    read(|cfg| cfg.selector)
 }
 
 /// Setter for [`SCHEDULER_CFG::selector`].
 #[allow(dead_code)]
 pub fn set_selector(selector: SelectorType) {
-    unsafe { SCHEDULER_CFG.selector = selector }
+    write(|cfg| cfg.selector = selector)
 }
 
 /// Setter for [`SCHEDULER_CFG::buf_len`].
 #[allow(dead_code)]
 pub fn set_buf_len(buf_len: usize) {
-    unsafe { SCHEDULER_CFG.buf_len = buf_len }
+    write(|cfg| cfg.buf_len = buf_len)
 }
 
 /// Setter for [`SCHEDULER_CFG`].
 #[allow(dead_code)]
 pub fn set_config(config: SchedulerCfg) {
-    unsafe { SCHEDULER_CFG = config }
-}
\ No newline at end of file
+    write(|cfg| *cfg = config)
+}
+
+/// Getter for [`SCHEDULER_CFG::sandbox`].
+#[cfg(target_os = "linux")]
+pub fn config_sandbox() -> Option<Sandbox> {
+    read(|cfg| cfg.sandbox.clone())
+}
+
+/// Setter for [`SCHEDULER_CFG::sandbox`].
+#[cfg(target_os = "linux")]
+#[allow(dead_code)]
+pub fn set_sandbox(sandbox: Sandbox) {
+    write(|cfg| cfg.sandbox = Some(sandbox))
+}
+/// Getter for [`SCHEDULER_CFG::overload_protection`].
+pub fn config_overload_protection() -> Option<OverloadProtection> {
+    read(|cfg| cfg.overload_protection)
+}
+
+/// Setter for [`SCHEDULER_CFG::overload_protection`]. Read [`OverloadProtection`] for more information.
+#[allow(dead_code)]
+pub fn set_overload_protection(protection: Option<OverloadProtection>) {
+    write(|cfg| cfg.overload_protection = protection)
+}
+
+/// Getter for [`SCHEDULER_CFG::accept_warmup`].
+pub fn config_accept_warmup() -> Option<AcceptWarmup> {
+    read(|cfg| cfg.accept_warmup)
+}
+
+/// Setter for [`SCHEDULER_CFG::accept_warmup`]. Read [`AcceptWarmup`] for more information.
+#[allow(dead_code)]
+pub fn set_accept_warmup(warmup: Option<AcceptWarmup>) {
+    write(|cfg| cfg.accept_warmup = warmup)
+}
+
+/// Getter for [`SCHEDULER_CFG::blocking_threads`].
+pub fn config_blocking_threads() -> usize {
+    read(|cfg| cfg.blocking_threads)
+}
+
+/// Setter for [`SCHEDULER_CFG::blocking_threads`]. It is the maximum number of threads of the blocking pool of each worker.
+#[allow(dead_code)]
+pub fn set_blocking_threads(threads: usize) {
+    write(|cfg| cfg.blocking_threads = threads)
+}
+
+/// Getter for [`SCHEDULER_CFG::write_turn_cap`].
+pub fn config_write_turn_cap() -> usize {
+    read(|cfg| cfg.write_turn_cap)
+}
+
+/// Setter for [`SCHEDULER_CFG::write_turn_cap`]. It is the maximum number of bytes, that one `write_all` to a connection
+/// sends per poll iteration. The rest is sent in the next iterations in round-robin order with other connections,
+/// so one large write can't monopolize the worker. `usize::MAX` disables the limit.
+#[allow(dead_code)]
+pub fn set_write_turn_cap(cap: usize) {
+    write(|cfg| cfg.write_turn_cap = cap)
+}
+
+/// Getter for [`SCHEDULER_CFG::soft_memory_limit`].
+pub fn config_soft_memory_limit() -> Option<usize> {
+    read(|cfg| cfg.soft_memory_limit)
+}
+
+/// Setter for [`SCHEDULER_CFG::soft_memory_limit`]. It is the soft memory limit of each worker in bytes.
+/// Read [`Scheduler::set_soft_memory_limit`](crate::scheduler::Scheduler::set_soft_memory_limit) for more information.
+#[allow(dead_code)]
+pub fn set_soft_memory_limit(limit: Option<usize>) {
+    write(|cfg| cfg.soft_memory_limit = limit)
+}
+
+/// Getter for [`SCHEDULER_CFG::work_stealing`].
+pub fn config_work_stealing() -> bool {
+    read(|cfg| cfg.work_stealing)
+}
+
+/// Setter for [`SCHEDULER_CFG::work_stealing`]. Read [`work_stealing`](crate::scheduler::work_stealing) for more information.
+#[allow(dead_code)]
+pub fn set_work_stealing(work_stealing: bool) {
+    write(|cfg| cfg.work_stealing = work_stealing)
+}
+
+/// Getter for [`SCHEDULER_CFG::worker_stack_size`].
+pub fn config_worker_stack_size() -> Option<usize> {
+    read(|cfg| cfg.worker_stack_size)
+}
+
+/// Setter for [`SCHEDULER_CFG::worker_stack_size`]. It is the stack size of worker threads, that are spawned by
+/// [`run_on_all_cores`](crate::run::run_on_all_cores). `None` means the default stack size of Rust threads.
+/// The calling thread keeps its own stack.
+#[allow(dead_code)]
+pub fn set_worker_stack_size(stack_size: Option<usize>) {
+    write(|cfg| cfg.worker_stack_size = stack_size)
+}
+
+/// Getter for [`SCHEDULER_CFG::worker_nice`].
+pub fn config_worker_nice() -> Option<i32> {
+    read(|cfg| cfg.worker_nice)
+}
+
+/// Setter for [`SCHEDULER_CFG::worker_nice`]. It is the nice value of worker threads, that is set in
+/// [`run_on_core`](crate::run::run_on_core). `None` keeps the inherited one.
+#[allow(dead_code)]
+pub fn set_worker_nice(nice: Option<i32>) {
+    write(|cfg| cfg.worker_nice = nice)
+}
+
+/// Getter for [`SCHEDULER_CFG::skip_smt_siblings`].
+pub fn config_skip_smt_siblings() -> bool {
+    read(|cfg| cfg.skip_smt_siblings)
+}
+
+/// Setter for [`SCHEDULER_CFG::skip_smt_siblings`]. If it is true, [`run_on_all_cores`](crate::run::run_on_all_cores)
+/// starts one worker per physical core, so workers don't share execution units with each other.
+/// Read [`get_worker_core_ids`](crate::utils::get_worker_core_ids).
+#[allow(dead_code)]
+pub fn set_skip_smt_siblings(skip: bool) {
+    write(|cfg| cfg.skip_smt_siblings = skip)
+}
+
+/// Getter for [`SCHEDULER_CFG::reserved_cores`].
+pub fn config_reserved_cores() -> usize {
+    read(|cfg| cfg.reserved_cores)
+}
+
+/// Setter for [`SCHEDULER_CFG::reserved_cores`]. It is the number of cores, that [`run_on_all_cores`](crate::run::run_on_all_cores)
+/// leaves to threads outside the engine (the last ones of the list). At least one worker is started anyway.
+/// Read [`get_worker_core_ids`](crate::utils::get_worker_core_ids).
+#[allow(dead_code)]
+pub fn set_reserved_cores(reserved: usize) {
+    write(|cfg| cfg.reserved_cores = reserved)
+}
+
+/// Getter for [`SCHEDULER_CFG::scheduling_policy`].
+pub fn config_scheduling_policy() -> SchedulingPolicy {
+    read(|cfg| cfg.scheduling_policy)
+}
+
+/// Setter for [`SCHEDULER_CFG::scheduling_policy`]. Read [`SchedulingPolicy`] for more information.
+#[allow(dead_code)]
+pub fn set_scheduling_policy(policy: SchedulingPolicy) {
+    write(|cfg| cfg.scheduling_policy = policy)
+}
+
+/// Getter for [`SCHEDULER_CFG::timer_tick`].
+pub fn config_timer_tick() -> Duration {
+    read(|cfg| cfg.timer_tick)
+}
+
+/// Setter for [`SCHEDULER_CFG::timer_tick`]. It is the precision of the timer wheel, that stores sleeping coroutines,
+/// when there are many of them. A larger tick makes waking up cheaper, but sleeping coroutines can be woken up
+/// up to one tick later.
+///
+/// # Panics
+///
+/// Panics if `tick` is zero.
+#[allow(dead_code)]
+pub fn set_timer_tick(tick: Duration) {
+    assert!(!tick.is_zero(), "the tick of the timer wheel must be greater than zero");
+    write(|cfg| cfg.timer_tick = tick)
+}
+
+/// Getter for [`SCHEDULER_CFG::ring_backlog_cap`].
+pub fn config_ring_backlog_cap() -> usize {
+    read(|cfg| cfg.ring_backlog_cap)
+}
+
+/// Setter for [`SCHEDULER_CFG::ring_backlog_cap`]. It is the maximum number of submission queue entries of io_uring,
+/// that wait in the backlog of the worker, when the submission queue is full or the kernel returns `EBUSY`.
+/// Over the cap, the worker keeps submitting and reaping completions, until the backlog is under the cap again.
+/// Read [`SubmissionStats`](crate::io::SubmissionStats) for more information.
+#[allow(dead_code)]
+pub fn set_ring_backlog_cap(cap: usize) {
+    write(|cfg| cfg.ring_backlog_cap = cap)
+}
+
+/// Getter for [`SCHEDULER_CFG::ring_resize`].
+pub fn config_ring_resize() -> bool {
+    read(|cfg| cfg.ring_resize)
+}
+
+/// Setter for [`SCHEDULER_CFG::ring_resize`]. If it is true, the worker recreates io_uring with twice as many entries,
+/// when the submission queue is persistently full. The ring is recreated only when no operations are in flight.
+#[allow(dead_code)]
+pub fn set_ring_resize(resize: bool) {
+    write(|cfg| cfg.ring_resize = resize)
+}
+
+/// Getter for [`SCHEDULER_CFG::ring_setup`].
+#[cfg(target_os = "linux")]
+pub fn config_ring_setup() -> RingSetup {
+    read(|cfg| cfg.ring_setup)
+}
+
+/// Setter for [`SCHEDULER_CFG::ring_setup`]. It is the number of entries and the flags of io_uring of every worker.
+/// Read [`RingSetup`] for more information.
+///
+/// # Panics
+///
+/// Panics if `setup.entries` is 0.
+#[cfg(target_os = "linux")]
+#[allow(dead_code)]
+pub fn set_ring_setup(setup: RingSetup) {
+    assert!(setup.entries > 0, "the ring must have entries");
+    write(|cfg| cfg.ring_setup = setup)
+}
+
+/// Getter for [`SCHEDULER_CFG::fixed_buffers`].
+pub fn config_fixed_buffers() -> usize {
+    read(|cfg| cfg.fixed_buffers)
+}
+
+/// Setter for [`SCHEDULER_CFG::fixed_buffers`]. It is the number of buffers of the [`BufPool`](crate::buf::BufPool),
+/// that are allocated at the start of every worker and registered with io_uring. 0 (the default) disables it.
+///
+/// Reads and writes of TCP streams and files with registered buffers use `IORING_OP_READ_FIXED`
+/// and `IORING_OP_WRITE_FIXED`, so the kernel doesn't pin the pages of the buffer on every operation.
+/// Other buffers (taken, when the registered ones are in use, or grown) work as usual.
+///
+/// The registered memory is locked, so `count` * [`buf_len`](set_buf_len) must fit into `RLIMIT_MEMLOCK`
+/// on kernels before 5.12, otherwise the selector can't be created. Only the `Ring` selector uses it.
+///
+/// # Panics
+///
+/// Panics if `count` is greater than [`MAX_FIXED_BUFFERS`].
+#[allow(dead_code)]
+pub fn set_fixed_buffers(count: usize) {
+    assert!(count <= MAX_FIXED_BUFFERS, "io_uring can't register more than {} buffers", MAX_FIXED_BUFFERS);
+    write(|cfg| cfg.fixed_buffers = count)
+}
+
+/// Getter for [`SCHEDULER_CFG::fixed_files`].
+pub fn config_fixed_files() -> u32 {
+    read(|cfg| cfg.fixed_files)
+}
+
+/// Setter for [`SCHEDULER_CFG::fixed_files`]. It is the number of slots for registered (fixed) files of io_uring
+/// of every worker. 0 (the default) disables it.
+///
+/// Long-lived fds, like listeners and hot files, are registered with
+/// [`TcpListener::register_fixed`](crate::net::TcpListener::register_fixed) and
+/// [`File::register_fixed`](crate::fs::File::register_fixed). Operations on them use the slot,
+/// so the kernel doesn't look the fd up on every operation. The slot is freed, when the fd is closed.
+/// Only the `Ring` selector uses it.
+#[allow(dead_code)]
+pub fn set_fixed_files(count: u32) {
+    write(|cfg| cfg.fixed_files = count)
+}
+
+/// Getter for [`SCHEDULER_CFG::multishot_recv_buffers`].
+pub fn config_multishot_recv_buffers() -> u16 {
+    read(|cfg| cfg.multishot_recv_buffers)
+}
+
+/// Setter for [`SCHEDULER_CFG::multishot_recv_buffers`]. It is the number of buffers of [`buf_len`](set_buf_len) bytes,
+/// that every worker provides to io_uring for multishot receives. 0 (the default) disables them.
+///
+/// With multishot receives a read of a [`TcpStream`](crate::net::TcpStream) arms one receive, that stays in the kernel,
+/// and every message is received into a provided buffer without a poll and a `recv` per message.
+/// The slice of the read is valid until the next read of the stream, like without them.
+/// If all buffers are taken, the read falls back to a poll and a `recv`.
+///
+/// Only the `Ring` selector uses it, since Linux 6.0. On older kernels it is ignored.
+///
+/// # Panics
+///
+/// Panics if `count` is not 0 and not a power of two.
+#[allow(dead_code)]
+pub fn set_multishot_recv_buffers(count: u16) {
+    assert!(count == 0 || count.is_power_of_two(), "the number of provided buffers must be a power of two");
+    write(|cfg| cfg.multishot_recv_buffers = count)
+}
+
+/// Getter for [`SCHEDULER_CFG::ring_timeouts`].
+pub fn config_ring_timeouts() -> bool {
+    read(|cfg| cfg.ring_timeouts)
+}
+
+/// Setter for [`SCHEDULER_CFG::ring_timeouts`]. If it is true, [`sleep`](crate::sleep::sleep) and
+/// [`sleep_until`](crate::sleep::sleep_until) submit `IORING_OP_TIMEOUT` entries instead of storing coroutines
+/// in the timer wheel, so the kernel wakes coroutines up exactly when they are due, not at the next [tick](set_timer_tick).
+///
+/// Every sleep costs an entry and a completion, so it pays off for few precise sleeps, not for many timers.
+/// False by default. Only the `Ring` selector uses it.
+#[allow(dead_code)]
+pub fn set_ring_timeouts(ring_timeouts: bool) {
+    write(|cfg| cfg.ring_timeouts = ring_timeouts)
+}
+
+/// Getter for [`SCHEDULER_CFG::isolate_panics`].
+pub fn config_isolate_panics() -> bool {
+    read(|cfg| cfg.isolate_panics)
+}
+
+/// Setter for [`SCHEDULER_CFG::isolate_panics`]. If it is true (the default), a panic of a coroutine is caught,
+/// and the worker keeps running other coroutines. Otherwise, the panic stops the worker thread.
+#[allow(dead_code)]
+pub fn set_isolate_panics(isolate_panics: bool) {
+    write(|cfg| cfg.isolate_panics = isolate_panics)
+}
+
+/// Getter for [`SCHEDULER_CFG::panic_hook`].
+pub fn config_panic_hook() -> Option<PanicHook> {
+    read(|cfg| cfg.panic_hook)
+}
+
+/// Setter for [`SCHEDULER_CFG::panic_hook`]. Read [`PanicHook`] for more information.
+#[allow(dead_code)]
+pub fn set_panic_hook(hook: Option<PanicHook>) {
+    write(|cfg| cfg.panic_hook = hook)
+}
+
+/// Getter for [`SCHEDULER_CFG::poll_interval`].
+pub fn config_poll_interval() -> u32 {
+    read(|cfg| cfg.poll_interval)
+}
+
+/// Setter for [`SCHEDULER_CFG::poll_interval`]. It is the maximum number of coroutines, that are run between
+/// two iterations of the background work (polling the selector, waking up timers, starting injected coroutines).
+/// The background work also runs, when no coroutine is ready.
+///
+/// A lower value reduces the latency of IO and timers, while coroutines are busy, but costs more polls.
+///
+/// # Panics
+///
+/// Panics if `interval` is 0.
+#[allow(dead_code)]
+pub fn set_poll_interval(interval: u32) {
+    assert!(interval > 0, "the poll interval must be positive");
+    write(|cfg| cfg.poll_interval = interval)
+}
+
+/// Getter for [`SCHEDULER_CFG::coroutine_limits`].
+pub fn config_coroutine_limits() -> Option<CoroutineLimits> {
+    read(|cfg| cfg.coroutine_limits)
+}
+
+/// Setter for [`SCHEDULER_CFG::coroutine_limits`]. Read [`CoroutineLimits`] for more information.
+#[allow(dead_code)]
+pub fn set_coroutine_limits(limits: Option<CoroutineLimits>) {
+    write(|cfg| cfg.coroutine_limits = limits)
+}
+
+/// Getter for [`SCHEDULER_CFG::idle_strategy`].
+pub fn config_idle_strategy() -> IdleStrategy {
+    read(|cfg| cfg.idle_strategy)
+}
+
+/// Setter for [`SCHEDULER_CFG::idle_strategy`]. Read [`IdleStrategy`] for more information.
+#[allow(dead_code)]
+pub fn set_idle_strategy(strategy: IdleStrategy) {
+    write(|cfg| cfg.idle_strategy = strategy)
+}
@@ -0,0 +1,342 @@
+//! This module contains [`JoinHandle`] and [`Elapsed`].
+use std::cell::UnsafeCell;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::mem::MaybeUninit;
+use std::ops::CoroutineState;
+use std::rc::Rc;
+use std::time::Duration;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local_scheduler;
+use crate::utils::Ptr;
+
+/// The error of [`JoinHandle::wait_timeout`] and [`wait_timeout!`](crate::wait_timeout),
+/// that means, that the child has not finished in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the coroutine has not finished in time")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl From<Elapsed> for Error {
+    fn from(elapsed: Elapsed) -> Self {
+        Error::new(ErrorKind::TimedOut, elapsed)
+    }
+}
+
+/// Where the result is moved for the waiting parent.
+enum WaiterResult<T> {
+    Plain(*mut T),
+    Timeout(*mut Result<T, Elapsed>)
+}
+
+/// The shared state of the child coroutine and its [`JoinHandle`].
+struct JoinSlot<T> {
+    /// The child writes its result here through the return pointer.
+    result: MaybeUninit<T>,
+    is_finished: bool,
+    is_taken: bool,
+    /// The child was dropped by a panic.
+    is_panicked: bool,
+    /// The parent, that waits for the result.
+    waiter: Option<CoroutineImpl>,
+    waiter_result: WaiterResult<T>,
+    /// The state of the operation, that the child waits for now, if it can be cancelled.
+    pending: Option<Ptr<PollState>>
+}
+
+impl<T> Drop for JoinSlot<T> {
+    fn drop(&mut self) {
+        if self.is_finished && !self.is_taken {
+            unsafe { self.result.assume_init_drop() };
+        }
+    }
+}
+
+/// A handle to a coroutine, that is spawned with [`spawn_local_with_handle!`](crate::spawn_local_with_handle)
+/// or [`JoinHandle::spawn`].
+///
+/// Unlike [`wait!`](crate::wait), the parent keeps running after the spawn, so several children run concurrently,
+/// and the parent gets their results later with `yield handle.wait()`.
+///
+/// Dropping the handle detaches the child: it keeps running, and its result is dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::{coro, spawn_local_with_handle};
+/// use engine::coroutine::JoinHandle;
+///
+/// #[coro]
+/// fn count(n: usize) -> usize {
+///     n * 2
+/// }
+///
+/// #[coro]
+/// fn parent() {
+///     let mut first: JoinHandle<usize> = spawn_local_with_handle!(count(1));
+///     let mut second: JoinHandle<usize> = spawn_local_with_handle!(count(2));
+///     let first: usize = yield first.wait();
+///     let second: usize = yield second.wait();
+///     assert_eq!(first + second, 6);
+/// }
+/// ```
+pub struct JoinHandle<T> {
+    slot: Rc<UnsafeCell<JoinSlot<T>>>
+}
+
+impl<T: 'static> JoinHandle<T> {
+    /// Creates the child with `creator`, that gets the return pointer, and schedules it in the local scheduler.
+    ///
+    /// It is used by [`spawn_local_with_handle!`](crate::spawn_local_with_handle).
+    pub fn spawn<F: FnOnce(*mut T) -> CoroutineImpl>(creator: F) -> Self {
+        let slot = Rc::new(UnsafeCell::new(JoinSlot {
+            result: MaybeUninit::uninit(),
+            is_finished: false,
+            is_taken: false,
+            is_panicked: false,
+            waiter: None,
+            waiter_result: WaiterResult::Plain(std::ptr::null_mut()),
+            pending: None
+        }));
+        let child = creator(unsafe { (*slot.get()).result.as_mut_ptr() });
+        local_scheduler().spawn(run_child(child, slot.clone()));
+        Self { slot }
+    }
+
+    /// Like [`JoinHandle::wait`], but returns [`Elapsed`], if the child doesn't finish within the duration.
+    ///
+    /// The child keeps running after the timeout, so the result can be waited for again.
+    /// Use [`JoinHandle::cancel`] to stop its pending IO.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result has already been taken.
+    pub fn wait_timeout(&mut self, dur: Duration, res: *mut Result<T, Elapsed>) -> YieldStatus {
+        let slot = unsafe { &mut *self.slot.get() };
+        assert!(!slot.is_taken, "the result of the coroutine has already been taken");
+        assert!(!slot.is_panicked, "the child coroutine panicked");
+        if slot.is_finished {
+            slot.is_taken = true;
+            return YieldStatus::ready(res, Ok(unsafe { slot.result.assume_init_read() }));
+        }
+
+        slot.waiter_result = WaiterResult::Timeout(res);
+        YieldStatus::join_with_timeout(&mut slot.waiter, dur, run_timer(self.slot.clone()))
+    }
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns true, if the child has finished.
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        unsafe { (*self.slot.get()).is_finished }
+    }
+
+    /// Returns the result, if the child has finished, and the result has not been taken yet.
+    pub fn try_take(&mut self) -> Option<T> {
+        let slot = unsafe { &mut *self.slot.get() };
+        if !slot.is_finished || slot.is_taken {
+            return None;
+        }
+        slot.is_taken = true;
+        Some(unsafe { slot.result.assume_init_read() })
+    }
+
+    /// Returns true, if the child was dropped by a panic. Its result will never be available.
+    #[inline(always)]
+    pub fn is_panicked(&self) -> bool {
+        unsafe { (*self.slot.get()).is_panicked }
+    }
+
+    /// Suspends the coroutine until the child finishes and returns its result. Use it with `yield`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result has already been taken or if the child has panicked.
+    /// If the child panics, while the parent waits for it, the parent is dropped with a panic.
+    pub fn wait(&mut self, res: *mut T) -> YieldStatus {
+        let slot = unsafe { &mut *self.slot.get() };
+        assert!(!slot.is_taken, "the result of the coroutine has already been taken");
+        assert!(!slot.is_panicked, "the child coroutine panicked");
+        if slot.is_finished {
+            slot.is_taken = true;
+            return YieldStatus::ready(res, unsafe { slot.result.assume_init_read() });
+        }
+
+        slot.waiter_result = WaiterResult::Plain(res);
+        YieldStatus::join(&mut slot.waiter)
+    }
+
+    /// Cancels the pending IO of the child, so the operation fails with [`ECANCELED`](libc::ECANCELED). Use it with `yield`.
+    ///
+    /// Only the waiting for readiness (accept, read and waiting for a readable fd) is cancelled.
+    /// The child is not stopped, so it must return on the error.
+    pub fn cancel(&mut self, _res: *mut ()) -> YieldStatus {
+        match unsafe { (*self.slot.get()).pending.take() } {
+            Some(state_ref) => YieldStatus::cancel(state_ref),
+            None => YieldStatus::yield_now()
+        }
+    }
+
+    /// Like [`JoinHandle::wait`], but consumes the handle.
+    pub fn join(mut self, res: *mut T) -> YieldStatus {
+        // The slot is shared with the child, so it outlives the handle.
+        self.wait(res)
+    }
+}
+
+/// Marks the child as panicked and fails the waiting parent, when the child is dropped by a panic.
+struct ChildPanicGuard<T> {
+    slot: Rc<UnsafeCell<JoinSlot<T>>>
+}
+
+impl<T> Drop for ChildPanicGuard<T> {
+    fn drop(&mut self) {
+        let slot = unsafe { &mut *self.slot.get() };
+        if slot.is_finished || !std::thread::panicking() {
+            return;
+        }
+        slot.is_panicked = true;
+        if let Some(waiter) = slot.waiter.take() {
+            local_scheduler().sched(fail_waiter(waiter));
+        }
+    }
+}
+
+/// Drops the parent of the panicked child with a panic. The result of the child will never be written,
+/// so the parent can't be resumed.
+fn fail_waiter(waiter: CoroutineImpl) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let _waiter = waiter;
+        if false {
+            yield YieldStatus::yield_now();
+        }
+        panic!("the child coroutine panicked");
+    })
+}
+
+/// Runs the child and passes its result to the waiting parent or keeps it in the slot.
+fn run_child<T: 'static>(mut child: CoroutineImpl, slot: Rc<UnsafeCell<JoinSlot<T>>>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let _guard = ChildPanicGuard { slot: slot.clone() };
+        while let CoroutineState::Yielded(status) = child.as_mut().resume(()) {
+            unsafe { (*slot.get()).pending = status.cancellable_state() };
+            yield status;
+            unsafe { (*slot.get()).pending = None };
+        }
+
+        let slot = unsafe { &mut *slot.get() };
+        slot.is_finished = true;
+        if let Some(waiter) = slot.waiter.take() {
+            slot.is_taken = true;
+            let result = unsafe { slot.result.assume_init_read() };
+            match slot.waiter_result {
+                WaiterResult::Plain(ptr) => unsafe { ptr.write(result) },
+                WaiterResult::Timeout(ptr) => unsafe { ptr.write(Ok(result)) }
+            }
+            local_scheduler().sched(waiter);
+        }
+    })
+}
+
+/// Wakes the parent up with [`Elapsed`], if it still waits for the child.
+fn run_timer<T: 'static>(slot: Rc<UnsafeCell<JoinSlot<T>>>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let slot = unsafe { &mut *slot.get() };
+        if let WaiterResult::Timeout(ptr) = slot.waiter_result && let Some(waiter) = slot.waiter.take() {
+            unsafe { ptr.write(Err(Elapsed)) };
+            local_scheduler().sched(waiter);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::net::SocketAddr;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::coroutine::{Elapsed, JoinHandle};
+    use crate::io::AsyncRead;
+    use crate::net::TcpStream;
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn double_after(n: usize, delay: Duration) -> usize {
+        yield sleep(delay);
+        n * 2
+    }
+
+    #[test_local(crate="crate")]
+    fn test_join_handle() {
+        let mut slow: JoinHandle<usize> = JoinHandle::spawn(|res| double_after(1, Duration::from_millis(20), res));
+        let mut fast: JoinHandle<usize> = JoinHandle::spawn(|res| double_after(2, Duration::from_millis(1), res));
+        assert!(!slow.is_finished());
+        assert_eq!(fast.try_take(), None);
+
+        let slow_res: usize = yield slow.wait();
+        assert_eq!(slow_res, 2);
+        // Both children ran concurrently, so the fast one has finished meanwhile.
+        assert!(fast.is_finished());
+        let fast_res: usize = yield fast.join();
+        assert_eq!(fast_res, 4);
+
+        let mut ready: JoinHandle<usize> = JoinHandle::spawn(|res| double_after(3, Duration::ZERO, res));
+        yield sleep(Duration::from_millis(5));
+        assert_eq!(ready.try_take(), Some(6));
+        assert_eq!(ready.try_take(), None);
+
+        // A detached child still runs to the end.
+        drop(JoinHandle::spawn(|res| double_after(4, Duration::from_millis(1), res)));
+        yield sleep(Duration::from_millis(5));
+    }
+
+    #[coro(crate="crate")]
+    fn read_len(mut stream: TcpStream) -> Result<usize, Error> {
+        let res: Result<&[u8], Error> = yield stream.read();
+        res.map(|slice| slice.len())
+    }
+
+    #[test_local(crate="crate")]
+    fn test_wait_timeout() {
+        let mut fast: JoinHandle<usize> = JoinHandle::spawn(|res| double_after(1, Duration::from_millis(1), res));
+        let res: Result<usize, Elapsed> = yield fast.wait_timeout(Duration::from_millis(50));
+        assert_eq!(res, Ok(2));
+
+        let mut slow: JoinHandle<usize> = JoinHandle::spawn(|res| double_after(2, Duration::from_millis(30), res));
+        let res: Result<usize, Elapsed> = yield slow.wait_timeout(Duration::from_millis(1));
+        assert_eq!(res, Err(Elapsed));
+        // The child keeps running, so its result can be waited for again.
+        let res: usize = yield slow.wait();
+        assert_eq!(res, 4);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel::<()>();
+        let peer = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            // The peer sends nothing, until the read is cancelled.
+            receiver.recv().unwrap();
+            stream
+        });
+
+        let stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut reader: JoinHandle<Result<usize, Error>> = JoinHandle::spawn(|res| read_len(stream, res));
+        let res: Result<Result<usize, Error>, Elapsed> = yield reader.wait_timeout(Duration::from_millis(10));
+        assert!(res.is_err());
+        yield reader.cancel();
+        let res: Result<usize, Error> = yield reader.wait();
+        assert_eq!(res.unwrap_err().raw_os_error(), Some(libc::ECANCELED));
+
+        sender.send(()).unwrap();
+        drop(peer.join().unwrap());
+    }
+}
@@ -9,11 +9,26 @@
 //! # [`yield_status`]
 //! This module contains a description of [`YieldStatus`] for low-level work with the scheduler.
 //! Please use high-level functions for working with the scheduler if it is possible.
+//!
+//! # [`join_handle`]
+//! This module contains [`JoinHandle`] for getting the result of a spawned coroutine and [`Elapsed`] for timeouts.
+//!
+//! # [`select`]
+//! This module contains [`Select`], the state of [`select!`](crate::select).
+//!
+//! # [`scope`]
+//! This module contains [`Scope`], that joins all child coroutines of [`scope!`](crate::scope).
 
 pub mod coroutine;
 pub mod yielding;
 pub mod yield_status;
+pub mod join_handle;
+pub mod select;
+pub mod scope;
 
 pub use coroutine::*;
 pub use yielding::*;
 pub use yield_status::*;
+pub use join_handle::{Elapsed, JoinHandle};
+pub use select::Select;
+pub use scope::{Scope, ScopeOutput};
@@ -0,0 +1,297 @@
+//! This module contains [`Scope`], the structured concurrency of [`scope!`](crate::scope).
+use std::cell::{Cell, UnsafeCell};
+use std::ops::CoroutineState;
+use std::rc::Rc;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local_scheduler;
+use crate::utils::Ptr;
+
+/// The result of the body of a [`Scope`]. Children are cancelled, if the body has failed.
+pub trait ScopeOutput {
+    /// Returns true, if the children of the scope must be cancelled.
+    fn is_failure(&self) -> bool;
+}
+
+impl ScopeOutput for () {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+impl<T, E> ScopeOutput for Result<T, E> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+}
+
+/// The shared state of the body and the children of the [`Scope`].
+struct ScopeState {
+    /// The number of children, that are not finished.
+    children: usize,
+    is_cancelled: bool,
+    /// The body, that waits for the children.
+    waiter: Option<CoroutineImpl>,
+    /// The states of the operations, that the children wait for now, if they can be cancelled.
+    pending: Vec<Rc<Cell<Option<Ptr<PollState>>>>>
+}
+
+impl ScopeState {
+    /// Returns the next pending state of a child to cancel, if the scope is cancelled.
+    fn take_cancelled(&mut self) -> Option<Ptr<PollState>> {
+        if !self.is_cancelled {
+            return None;
+        }
+
+        self.pending.iter().find_map(|pending| pending.take())
+    }
+
+    /// Wakes the waiting body up.
+    fn wake(&mut self) {
+        if let Some(waiter) = self.waiter.take() {
+            local_scheduler().sched(waiter);
+        }
+    }
+}
+
+/// The scope of child coroutines. The scope is left only after all its children are finished,
+/// so children can safely use the data of the parent, even by raw pointers.
+///
+/// The body of the scope is run with [`scope!`](crate::scope) or [`Scope::enter`].
+/// It gets the scope as the last argument before the result pointer and spawns children with [`Scope::spawn`].
+/// The scope can be cloned and passed to the children, so they spawn into the same scope.
+///
+/// If the body fails (read [`ScopeOutput`]), [`Scope::cancel`] is called, or a child panics,
+/// the pending IO of the children (accept, read and waiting for a readable fd) is cancelled,
+/// so the operation fails with [`ECANCELED`](libc::ECANCELED), and the children must return on the error.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, scope};
+/// use engine::coroutine::Scope;
+/// use engine::net::TcpStream;
+///
+/// #[coro]
+/// fn send_updates(stream: *mut TcpStream) {
+///     // The stream outlives the child, because the scope waits for it.
+///     let stream = unsafe { &mut *stream };
+///     // write to the stream
+/// }
+///
+/// #[coro]
+/// fn serve(mut stream: TcpStream, scope: Scope) -> Result<(), Error> {
+///     scope.spawn(|res| send_updates(&mut stream, res));
+///     // read from the stream
+///     Ok(())
+/// }
+///
+/// #[coro]
+/// fn handle_tcp_stream(stream: TcpStream) {
+///     let res: Result<(), Error> = scope!(serve(stream));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Scope {
+    state: Rc<UnsafeCell<ScopeState>>
+}
+
+impl Scope {
+    fn new() -> Self {
+        Self {
+            state: Rc::new(UnsafeCell::new(ScopeState {
+                children: 0,
+                is_cancelled: false,
+                waiter: None,
+                pending: Vec::new()
+            }))
+        }
+    }
+
+    /// Returns the coroutine, that runs the body of the `creator` with a new scope
+    /// and completes after the body and all children of the scope.
+    ///
+    /// It takes the result pointer as the last argument, so it can be used with [`wait!`](crate::wait).
+    /// [`scope!`](crate::scope) calls it.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let res: Result<(), Error> = wait!(Scope::enter(|scope, res| serve(stream, scope, res)));
+    /// ```
+    pub fn enter<R: ScopeOutput + 'static, C: FnOnce(Scope, *mut R) -> CoroutineImpl>(creator: C, res: *mut R) -> CoroutineImpl {
+        let scope = Scope::new();
+        let mut body = creator(scope.clone(), res);
+        Box::pin(#[coroutine] static move || {
+            while let CoroutineState::Yielded(status) = body.as_mut().resume(()) {
+                yield status;
+            }
+
+            if is_failure(res) {
+                scope.cancel();
+            }
+            loop {
+                // The states are taken one by one, and the state of the scope is read again after every yield,
+                // because children can finish or cancel the scope, while the body is cancelling.
+                while let Some(state_ref) = unsafe { &mut *scope.state.get() }.take_cancelled() {
+                    yield YieldStatus::cancel(state_ref);
+                }
+                let state = unsafe { &mut *scope.state.get() };
+                if state.children == 0 {
+                    break;
+                }
+                yield YieldStatus::join(&mut state.waiter);
+            }
+        })
+    }
+
+    /// Spawns the child of the `creator`, that gets the return pointer, in the local scheduler.
+    /// The result of the child is dropped.
+    pub fn spawn<T: 'static, F: FnOnce(*mut T) -> CoroutineImpl>(&self, creator: F) {
+        let result = Box::into_raw(Box::new(std::mem::MaybeUninit::<T>::uninit()));
+        let child = creator(unsafe { (*result).as_mut_ptr() });
+        let pending = Rc::new(Cell::new(None));
+        let state = unsafe { &mut *self.state.get() };
+        state.children += 1;
+        state.pending.push(pending.clone());
+        local_scheduler().spawn(run_child(child, self.clone(), pending, result));
+    }
+
+    /// Cancels the pending IO of the children. Children, that start new IO later, are cancelled too,
+    /// so they must return on [`ECANCELED`](libc::ECANCELED).
+    pub fn cancel(&self) {
+        let state = unsafe { &mut *self.state.get() };
+        state.is_cancelled = true;
+        state.wake();
+    }
+
+    /// Returns true, if the scope is cancelled.
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        unsafe { (*self.state.get()).is_cancelled }
+    }
+
+    /// Returns the number of the children, that are not finished.
+    #[inline(always)]
+    pub fn children(&self) -> usize {
+        unsafe { (*self.state.get()).children }
+    }
+}
+
+/// Returns true, if the body has written the failed result to the `res`. Read [`ScopeOutput`].
+fn is_failure<R: ScopeOutput>(res: *mut R) -> bool {
+    !res.is_null() && unsafe { &*res }.is_failure()
+}
+
+/// Leaves the scope, when the child is finished or dropped by a panic. A panic cancels the other children.
+struct ChildGuard<T> {
+    scope: Scope,
+    pending: Rc<Cell<Option<Ptr<PollState>>>>,
+    result: *mut std::mem::MaybeUninit<T>,
+    is_finished: bool
+}
+
+impl<T> Drop for ChildGuard<T> {
+    fn drop(&mut self) {
+        let state = unsafe { &mut *self.scope.state.get() };
+        state.pending.retain(|pending| !Rc::ptr_eq(pending, &self.pending));
+        state.children -= 1;
+        let mut result = unsafe { Box::from_raw(self.result) };
+        if self.is_finished {
+            unsafe { result.assume_init_drop() };
+        } else {
+            state.is_cancelled = true;
+        }
+        if state.children == 0 || state.is_cancelled {
+            state.wake();
+        }
+    }
+}
+
+/// Runs the child and tracks its pending IO for the cancellation.
+fn run_child<T: 'static>(mut child: CoroutineImpl, scope: Scope, pending: Rc<Cell<Option<Ptr<PollState>>>>, result: *mut std::mem::MaybeUninit<T>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let mut guard = ChildGuard { scope, pending, result, is_finished: false };
+        while let CoroutineState::Yielded(status) = child.as_mut().resume(()) {
+            let state_ref = status.cancellable_state();
+            guard.pending.set(state_ref);
+            // The body cancels the new operation after it is registered.
+            if state_ref.is_some() && guard.scope.is_cancelled() {
+                unsafe { (*guard.scope.state.get()).wake() };
+            }
+            yield status;
+            guard.pending.set(None);
+        }
+        guard.is_finished = true;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use crate::{coro, test_local, wait};
+    use crate::coroutine::Scope;
+    use crate::io::AsyncRead;
+    use crate::net::TcpStream;
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn push_after(log: *mut Vec<u64>, n: u64) -> u64 {
+        yield sleep(Duration::from_millis(n));
+        let log = unsafe { &mut *log };
+        log.push(n);
+        return n;
+    }
+
+    #[coro(crate="crate")]
+    fn spawn_children(log: *mut Vec<u64>, scope: Scope) -> Result<usize, Error> {
+        for n in [5, 1, 3] {
+            scope.spawn(|res| push_after(log, n, res));
+        }
+        let nested = scope.clone();
+        scope.spawn(move |res| spawn_nested(nested, log, res));
+        return Ok(scope.children());
+    }
+
+    #[coro(crate="crate")]
+    fn spawn_nested(scope: Scope, log: *mut Vec<u64>) {
+        scope.spawn(|res| push_after(log, 2, res));
+    }
+
+    #[coro(crate="crate")]
+    fn read_forever(mut stream: TcpStream) -> Result<usize, Error> {
+        let res: Result<&[u8], Error> = yield stream.read();
+        let res = res.map(|slice| slice.len());
+        assert_eq!(res.as_ref().unwrap_err().raw_os_error(), Some(libc::ECANCELED));
+        return res;
+    }
+
+    #[coro(crate="crate")]
+    fn fail_with_reader(addr: SocketAddr, scope: Scope) -> Result<(), Error> {
+        let stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        scope.spawn(|res| read_forever(stream, res));
+        yield sleep(Duration::from_millis(1));
+        return Err(Error::new(ErrorKind::Other, "the body failed"));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_scope() {
+        // The children use the data of the parent by a raw pointer, because the scope outlives them.
+        let mut log = Vec::new();
+        let log_ptr: *mut Vec<u64> = &mut log;
+        let children: Result<usize, Error> = wait!(Scope::enter(|scope, res| spawn_children(log_ptr, scope, res)));
+        assert_eq!(children.unwrap(), 4);
+        assert_eq!(log, vec![1, 2, 3, 5]);
+
+        // The peer sends nothing, so the child reads until it is cancelled.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || listener.accept().unwrap().0);
+        let res: Result<(), Error> = wait!(Scope::enter(|scope, res| fail_with_reader(addr, scope, res)));
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+        drop(peer.join().unwrap());
+    }
+}
@@ -0,0 +1,267 @@
+//! This module contains [`Select`], the state of [`select!`](crate::select).
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::rc::Rc;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local_scheduler;
+use crate::utils::Ptr;
+
+/// The state, that is shared by the waiting coroutine and its branches.
+struct SelectSlot {
+    /// The index of the first completed branch.
+    winner: Option<usize>,
+    /// The waiting coroutine. It is woken up by the winner, and then by the last lost branch, that is waited for.
+    waiter: Option<CoroutineImpl>,
+    /// The states of the operations, that the branches wait for now, if they can be cancelled.
+    pending: Vec<Option<Ptr<PollState>>>,
+    /// True for the branches, that wait for operations, that use the state of the waiting coroutine (like a write to its stream).
+    /// The waiting coroutine continues only after their completions.
+    in_flight: Vec<bool>,
+    /// The index of the next branch to cancel.
+    next_cancel: usize,
+    /// True, if the waiting coroutine waits for the lost branches.
+    is_draining: bool
+}
+
+impl SelectSlot {
+    #[inline(always)]
+    fn has_pending(&self) -> bool {
+        self.in_flight.iter().any(|in_flight| *in_flight)
+    }
+}
+
+/// The state of [`select!`](crate::select). Read it for more information.
+///
+/// Every branch is a separate coroutine, that yields the operation, so the scheduler registers all operations at once.
+/// The first completed branch moves its result to the waiting coroutine and wakes it up.
+/// The pending IO of other branches (accept, read and waiting for a readable fd) is cancelled.
+/// Other operations, that use the state of the waiting coroutine (like writes), can't be cancelled,
+/// so the waiting coroutine continues only after all of them complete, and the fds can be used at once.
+///
+/// A lost branch drops its result. [`sleep`](crate::sleep::sleep) and [`yield_now`](crate::coroutine::yield_now)
+/// are not waited for, they complete in the background and do nothing.
+pub struct Select {
+    slot: Rc<UnsafeCell<SelectSlot>>
+}
+
+impl Select {
+    /// Creates the state for `branches` branches.
+    pub fn new(branches: usize) -> Self {
+        Self {
+            slot: Rc::new(UnsafeCell::new(SelectSlot {
+                winner: None,
+                waiter: None,
+                pending: vec![None; branches],
+                in_flight: vec![false; branches],
+                next_cancel: 0,
+                is_draining: false
+            }))
+        }
+    }
+
+    /// Starts the branch with the `index`. `creator` gets the pointer to the result and returns the operation.
+    /// If the branch wins, the result is moved to `out`.
+    ///
+    /// # Safety
+    ///
+    /// `out` must be valid until the waiting coroutine is woken up.
+    pub unsafe fn branch<T: 'static, F: FnOnce(*mut T) -> YieldStatus>(&self, index: usize, out: *mut T, creator: F) {
+        // The result is on the heap, so the operation can complete after the waiting coroutine is gone.
+        let mut result: Box<MaybeUninit<T>> = Box::new(MaybeUninit::uninit());
+        let status = creator(result.as_mut_ptr());
+        let slot = unsafe { &mut *self.slot.get() };
+        slot.pending[index] = status.cancellable_state();
+        slot.in_flight[index] = !status.is_detached();
+
+        let slot = self.slot.clone();
+        local_scheduler().sched(Box::pin(#[coroutine] static move || {
+            yield status;
+
+            let slot = unsafe { &mut *slot.get() };
+            slot.pending[index] = None;
+            slot.in_flight[index] = false;
+            if slot.winner.is_none() {
+                slot.winner = Some(index);
+                unsafe { out.write(result.assume_init_read()) };
+                if let Some(waiter) = slot.waiter.take() {
+                    local_scheduler().sched(waiter);
+                }
+                return;
+            }
+
+            unsafe { result.assume_init_drop() };
+            if slot.is_draining && !slot.has_pending() && let Some(waiter) = slot.waiter.take() {
+                local_scheduler().sched(waiter);
+            }
+        }));
+    }
+
+    /// Suspends the coroutine until the first branch completes. Use it with `yield`.
+    pub fn wait(&mut self, _res: *mut ()) -> YieldStatus {
+        let slot = unsafe { &mut *self.slot.get() };
+        if slot.winner.is_some() {
+            return YieldStatus::yield_now();
+        }
+        YieldStatus::join(&mut slot.waiter)
+    }
+
+    /// Returns the status, that cancels the next pending branch, or [`None`], if all of them are cancelled.
+    /// Use it with `yield` until it returns [`None`].
+    pub fn cancel_next(&mut self) -> Option<YieldStatus> {
+        let slot = unsafe { &mut *self.slot.get() };
+        // The branch clears its pending state, when it completes, so the cursor is used not to cancel it twice.
+        while slot.next_cancel < slot.pending.len() {
+            let index = slot.next_cancel;
+            slot.next_cancel += 1;
+            if let Some(state_ref) = slot.pending[index] {
+                return Some(YieldStatus::cancel(state_ref));
+            }
+        }
+        None
+    }
+
+    /// Returns true, if some branches still wait for operations, that must complete before the waiting coroutine continues.
+    pub fn has_pending(&self) -> bool {
+        unsafe { (*self.slot.get()).has_pending() }
+    }
+
+    /// Suspends the coroutine until all cancelled and in-flight branches complete. Use it with `yield`.
+    pub fn drain(&mut self, _res: *mut ()) -> YieldStatus {
+        let slot = unsafe { &mut *self.slot.get() };
+        if !slot.has_pending() {
+            return YieldStatus::yield_now();
+        }
+        slot.is_draining = true;
+        YieldStatus::join(&mut slot.waiter)
+    }
+
+    /// Returns the index of the first completed branch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no branch has completed.
+    pub fn winner(&self) -> usize {
+        unsafe { (*self.slot.get()).winner }.expect("no branch of select has completed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read, Write};
+    use std::net::SocketAddr;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use crate::test_local;
+    use crate::coroutine::{Select, YieldStatus};
+    use crate::buf::Buffer;
+    use crate::io::{AsyncRead, AsyncWrite};
+    use crate::net::TcpStream;
+    use crate::sleep::sleep;
+
+    /// Yields the status as is. The `yield` of `#[coro]` needs a call.
+    fn pass(status: YieldStatus, _res: *mut ()) -> YieldStatus {
+        status
+    }
+
+    #[test_local(crate="crate")]
+    fn test_select() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel::<()>();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            receiver.recv().unwrap();
+            stream.write_all(b"ping").unwrap();
+            receiver.recv().unwrap();
+            stream.write_all(b"pong").unwrap();
+            receiver.recv().unwrap();
+        });
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+
+        // The peer sends nothing, so the sleep wins, and the read is cancelled.
+        let mut select = Select::new(2);
+        let mut read = std::mem::MaybeUninit::<Result<&'static [u8], Error>>::uninit();
+        let mut slept = std::mem::MaybeUninit::<()>::uninit();
+        unsafe {
+            select.branch(0, read.as_mut_ptr(), |res| stream.read(res));
+            select.branch(1, slept.as_mut_ptr(), |res| sleep(Duration::from_millis(10), res));
+        }
+        yield select.wait();
+        assert_eq!(select.winner(), 1);
+        loop {
+            let status = match select.cancel_next() {
+                Some(status) => status,
+                None => break
+            };
+            yield pass(status);
+        }
+        if select.has_pending() {
+            yield select.drain();
+        }
+
+        // The stream can be read at once after the cancellation.
+        sender.send(()).unwrap();
+        let res: Result<&[u8], Error> = yield stream.read();
+        assert_eq!(res.unwrap(), b"ping");
+
+        let mut select = Select::new(2);
+        let mut read = std::mem::MaybeUninit::<Result<&'static [u8], Error>>::uninit();
+        let mut slept = std::mem::MaybeUninit::<()>::uninit();
+        unsafe {
+            select.branch(0, read.as_mut_ptr(), |res| stream.read(res));
+            select.branch(1, slept.as_mut_ptr(), |res| sleep(Duration::from_secs(1), res));
+        }
+        sender.send(()).unwrap();
+        yield select.wait();
+        assert_eq!(select.winner(), 0);
+        assert_eq!(unsafe { read.assume_init() }.unwrap(), b"pong");
+        assert!(select.cancel_next().is_none());
+        assert!(!select.has_pending());
+
+        sender.send(()).unwrap();
+        peer.join().unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_select_waits_for_writes() {
+        const SIZE: usize = 16 * 1024 * 1024;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The write doesn't fit into the socket buffers, so it completes only after the peer reads.
+            std::thread::sleep(Duration::from_millis(50));
+            let mut data = vec![0; SIZE + 3];
+            stream.read_exact(&mut data).unwrap();
+            data
+        });
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+
+        let mut buf = Buffer::new(SIZE);
+        buf.append(&vec![1; SIZE]);
+        let mut select = Select::new(2);
+        let mut written = std::mem::MaybeUninit::<Result<(), Error>>::uninit();
+        let mut slept = std::mem::MaybeUninit::<()>::uninit();
+        unsafe {
+            select.branch(0, written.as_mut_ptr(), |res| stream.write_all(buf, res));
+            select.branch(1, slept.as_mut_ptr(), |res| sleep(Duration::from_millis(1), res));
+        }
+        yield select.wait();
+        assert_eq!(select.winner(), 1);
+        assert!(select.cancel_next().is_none());
+        // The lost write can't be cancelled, so the stream is used only after it completes.
+        assert!(select.has_pending());
+        yield select.drain();
+        assert!(!select.has_pending());
+
+        let mut end = Buffer::new(3);
+        end.append(b"end");
+        let res: Result<(), Error> = yield stream.write_all(end);
+        res.unwrap();
+
+        let data = peer.join().unwrap();
+        assert!(data[..SIZE].iter().all(|byte| *byte == 1));
+        assert_eq!(&data[SIZE..], b"end");
+    }
+}
@@ -1,11 +1,17 @@
 //! This module contains a description of [`YieldStatus`] for low-level work with the scheduler.
 //! Please use high-level functions for working with the scheduler if it is possible.
 
+use std::ffi::CString;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::os::fd::RawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use crate::io::PollState;
 use crate::net::{TcpListener, TcpStream};
+use crate::fs::File;
 use crate::buf::{Buffer};
+use crate::coroutine::CoroutineImpl;
+use crate::scheduler::{ExtensionId, LimitExceeded};
 use crate::utils::Ptr;
 
 /// Represents a new TCP listener to be created.
@@ -22,6 +28,8 @@ pub struct NewTcpListener {
 pub struct TcpConnect {
     /// The address on which the TCP listener will listen.
     pub(crate) address: SocketAddr,
+    /// The connection fails with [`TimedOut`](std::io::ErrorKind::TimedOut), if it is not established in time.
+    pub(crate) timeout: Option<Duration>,
     /// Pointer to store the newly created [`TcpStream`].
     pub(crate) stream_ptr: *mut Result<TcpStream, std::io::Error>,
 }
@@ -43,8 +51,12 @@ pub struct TcpAccept {
 pub struct TcpRead {
     /// Indicates whether the socket is registered to the selector.
     pub(crate) is_registered: bool,
+    /// Indicates whether the other side has closed the connection, so the read returns an empty slice at once.
+    pub(crate) is_eof: bool,
     /// The state ID associated with the TCP read operation.
     pub(crate) state_ref: Ptr<PollState>,
+    /// The maximum number of bytes to read per wakeup with read coalescing. 0 disables it.
+    pub(crate) coalesce: usize,
     /// Pointer to store the result of the TCP read operation.
     /// If success, the result will contain a slice of bytes read.
     pub(crate) result_ptr: *mut Result<&'static [u8], std::io::Error>,
@@ -60,6 +72,8 @@ pub struct TcpWrite {
     /// Pointer to store the result of the TCP write operation.
     /// If success, the result will contain the number of bytes written.
     pub(crate) result_ptr: *mut Result<Option<Buffer>, std::io::Error>,
+    /// True, if the buffer is sent without copying. Read [`TcpStream::write_zc`](crate::net::TcpStream::write_zc).
+    pub(crate) zero_copy: bool,
 }
 
 /// Represents a TCP write all operation.
@@ -81,6 +95,327 @@ pub struct TcpClose {
     pub(crate) state_ptr: Ptr<PollState>,
 }
 
+/// Represents waiting until the fd is readable.
+#[derive(Debug)]
+pub struct WaitReadable {
+    /// Indicates whether the fd is registered to the selector.
+    pub(crate) is_registered: bool,
+    /// The state associated with the fd.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// Pointer to store the result of the operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents an open file operation.
+#[derive(Debug)]
+pub struct OpenFile {
+    /// The path of the file.
+    pub(crate) path: CString,
+    /// The flags for `openat`.
+    pub(crate) flags: i32,
+    /// The mode for a newly created file.
+    pub(crate) mode: u32,
+    /// Pointer to store the result of the open operation.
+    /// If success, the result will contain a [`File`].
+    pub(crate) result_ptr: *mut Result<File, std::io::Error>,
+}
+
+/// Represents a file read operation.
+#[derive(Debug)]
+pub struct FileRead {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The offset in the file to read from.
+    pub(crate) offset: u64,
+    /// Pointer to the cursor of the [`File`], that will be moved by the number of bytes read.
+    /// It is null for reads with an explicit offset.
+    pub(crate) cursor: *mut u64,
+    /// Whether the file is opened with `O_DIRECT`, so the read needs an aligned buffer.
+    pub(crate) direct: bool,
+    /// Pointer to store the result of the file read operation.
+    /// If success, the result will contain a [`Buffer`] with read bytes.
+    pub(crate) result_ptr: *mut Result<Buffer, std::io::Error>,
+}
+
+/// Represents a file read to end operation.
+#[derive(Debug)]
+pub struct FileReadToEnd {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The offset in the file to read from.
+    pub(crate) offset: u64,
+    /// Pointer to the cursor of the [`File`], that will be moved by the number of bytes read.
+    /// It is null for reads with an explicit offset.
+    pub(crate) cursor: *mut u64,
+    /// Whether the file is opened with `O_DIRECT`, so the read needs an aligned buffer.
+    pub(crate) direct: bool,
+    /// Pointer to store the result of the file read to end operation.
+    /// If success, the result will contain a [`Buffer`] with all bytes from the offset to the end of the file.
+    pub(crate) result_ptr: *mut Result<Buffer, std::io::Error>,
+}
+
+/// Represents a file write operation.
+#[derive(Debug)]
+pub struct FileWrite {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The buffer containing data to be written.
+    pub(crate) buffer: Buffer,
+    /// The offset in the file to write to.
+    pub(crate) offset: u64,
+    /// Pointer to the cursor of the [`File`], that will be moved by the number of bytes written.
+    /// It is null for writes with an explicit offset.
+    pub(crate) cursor: *mut u64,
+    /// Pointer to store the result of the file write operation.
+    pub(crate) result_ptr: *mut Result<Option<Buffer>, std::io::Error>,
+}
+
+/// Represents a file write all operation.
+#[derive(Debug)]
+pub struct FileWriteAll {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The buffer containing data to be written.
+    pub(crate) buffer: Buffer,
+    /// The offset in the file to write to.
+    pub(crate) offset: u64,
+    /// Pointer to the cursor of the [`File`], that will be moved by the number of bytes written.
+    /// It is null for writes with an explicit offset.
+    pub(crate) cursor: *mut u64,
+    /// `Some(data_only)`, if the file is synced after the write, like [`FileSync`].
+    pub(crate) sync: Option<bool>,
+    /// Pointer to store the result of the file write all operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a file copy operation.
+#[derive(Debug)]
+pub struct CopyFile {
+    /// The fd of the source file.
+    pub(crate) src_fd: RawFd,
+    /// The fd of the destination file.
+    pub(crate) dst_fd: RawFd,
+    /// Pointer to store the result of the copy operation.
+    /// If success, the result will contain the number of copied bytes.
+    pub(crate) result_ptr: *mut Result<u64, std::io::Error>,
+}
+
+/// Represents a symbolic link creation operation.
+#[derive(Debug)]
+pub struct Symlink {
+    /// The path, that the link points to.
+    pub(crate) original: CString,
+    /// The path of the link.
+    pub(crate) link: CString,
+    /// Pointer to store the result of the symlink operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a hard link creation operation.
+#[derive(Debug)]
+pub struct HardLink {
+    /// The path of the existing file.
+    pub(crate) original: CString,
+    /// The path of the link.
+    pub(crate) link: CString,
+    /// Pointer to store the result of the hard link operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a symbolic link read operation.
+#[derive(Debug)]
+pub struct ReadLink {
+    /// The path of the link.
+    pub(crate) path: CString,
+    /// Pointer to store the result of the read link operation.
+    /// If success, the result will contain the target of the link.
+    pub(crate) result_ptr: *mut Result<PathBuf, std::io::Error>,
+}
+
+/// Represents a permissions change operation by the path.
+#[derive(Debug)]
+pub struct SetPermissions {
+    /// The path of the file.
+    pub(crate) path: CString,
+    /// The unix mode.
+    pub(crate) mode: u32,
+    /// Pointer to store the result of the operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a permissions change operation of the open file.
+#[derive(Debug)]
+pub struct FileSetPermissions {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The unix mode.
+    pub(crate) mode: u32,
+    /// Pointer to store the result of the operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents an access pattern hint for the open file.
+#[derive(Debug)]
+pub struct FileAdvise {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// The start of the range.
+    pub(crate) offset: u64,
+    /// The length of the range. 0 means to the end of the file.
+    pub(crate) len: u64,
+    /// The advice for `posix_fadvise`.
+    pub(crate) advice: i32,
+    /// Pointer to store the result of the operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a directory creation operation.
+#[derive(Debug)]
+pub struct CreateDir {
+    /// The path of the directory.
+    pub(crate) path: CString,
+    /// The unix mode of the new directory.
+    pub(crate) mode: u32,
+    /// Pointer to store the result of the create operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a file remove operation.
+#[derive(Debug)]
+pub struct RemoveFile {
+    /// The path of the file.
+    pub(crate) path: CString,
+    /// Pointer to store the result of the remove operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a directory remove operation.
+#[derive(Debug)]
+pub struct RemoveDir {
+    /// The path of the directory.
+    pub(crate) path: CString,
+    /// Pointer to store the result of the remove operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a rename operation.
+#[derive(Debug)]
+pub struct Rename {
+    /// The current path of the file or the directory.
+    pub(crate) from: CString,
+    /// The new path.
+    pub(crate) to: CString,
+    /// Pointer to store the result of the rename operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a sync operation of the open file.
+#[derive(Debug)]
+pub struct FileSync {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+    /// Whether only the data (and the metadata, that is needed to read it) is synced, like `fdatasync`.
+    pub(crate) data_only: bool,
+    /// Pointer to store the result of the operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents a registration of a long-lived fd with the selector.
+#[derive(Debug)]
+pub struct RegisterFile {
+    /// The fd to register.
+    pub(crate) fd: RawFd,
+    /// Pointer to store whether the fd is registered.
+    pub(crate) result_ptr: *mut Result<bool, std::io::Error>,
+}
+
+/// Represents a file close operation.
+#[derive(Debug)]
+pub struct FileClose {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The state associated with the file.
+    pub(crate) state_ref: Ptr<PollState>,
+}
+
+/// Represents an advisory lock operation of the open file.
+#[derive(Debug)]
+pub struct LockFile {
+    /// The fd of the file.
+    pub(crate) fd: RawFd,
+    /// The operation for `flock`: `LOCK_EX` or `LOCK_SH`.
+    pub(crate) operation: i32,
+    /// Pointer to store the result of the lock operation.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents an operation of a registered extension.
+#[derive(Debug)]
+pub struct Extension {
+    /// The id of the extension, returned by [`register_extension`](crate::scheduler::Scheduler::register_extension).
+    pub(crate) id: ExtensionId,
+    /// The payload, that will be passed to the handler of the extension.
+    pub(crate) payload: Ptr<()>,
+    /// Pointer to store the result of the operation. It is an error, if the extension is not registered in the worker.
+    pub(crate) result_ptr: *mut Result<(), std::io::Error>,
+}
+
+/// Represents waiting for a coroutine, that is spawned with [`JoinHandle`](crate::coroutine::JoinHandle).
+pub struct Join {
+    /// The waiting coroutine is stored here, and the child wakes it up, when it finishes.
+    pub(crate) waiter: *mut Option<CoroutineImpl>,
+    /// The timer, that wakes the waiting coroutine up, if the child doesn't finish in time.
+    /// Read [`JoinHandle::wait_timeout`](crate::coroutine::JoinHandle::wait_timeout).
+    pub(crate) timeout: Option<(Duration, CoroutineImpl)>,
+}
+
+/// Represents a closure, that runs on the blocking pool. Read [`blocking`](crate::blocking::blocking).
+pub struct Blocking {
+    /// The closure. It writes the result itself.
+    pub(crate) job: Box<dyn FnOnce() + Send>,
+}
+
+impl std::fmt::Debug for Join {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Join").field("waiter", &self.waiter).field("has_timeout", &self.timeout.is_some()).finish()
+    }
+}
+
+/// Represents a spawn, that waits, until the worker is under its [`CoroutineLimits`](crate::scheduler::CoroutineLimits).
+/// Read [`spawn_limited`](crate::scheduler::spawn_limited).
+pub struct WaitCapacity {
+    /// The coroutine to spawn.
+    pub(crate) func: CoroutineImpl,
+    /// Pointer to store the result of the spawn.
+    pub(crate) result_ptr: *mut Result<(), LimitExceeded>,
+}
+
+impl std::fmt::Debug for WaitCapacity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WaitCapacity")
+    }
+}
+
+impl std::fmt::Debug for Blocking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Blocking")
+    }
+}
+
 /// The status of the coroutine yield. This is the one way to communicate with the scheduler.
 /// It uses instead of await for async programming, and uses for creating new coroutines and for let the scheduler wake other coroutines up.
 #[derive(Debug)]
@@ -113,12 +448,21 @@ pub enum YieldStatus {
     /// If yielded, the coroutine will sleep for at least the duration.
     Sleep(Duration),
 
+    /// [`SleepUntil`] takes the instant.
+    ///
+    /// # Arguments
+    ///
+    /// * [`Instant`] - The instant to sleep until.
+    ///
+    /// If yielded, the coroutine will sleep until the instant or later. An instant in the past wakes it up at the next poll.
+    SleepUntil(Instant),
+
     /// [`NewTcpListener`] takes the address and a pointer.
     ///
     /// If yielded, the new listener will be stored in the pointer.
     NewTcpListener(NewTcpListener),
 
-    /// [`TcpConnect`] takes the address and a pointer.
+    /// [`TcpConnect`] takes the address, an optional timeout and a pointer.
     ///
     /// If yielded, the new connection will be stored in the pointer.
     TcpConnect(TcpConnect),
@@ -133,7 +477,8 @@ pub enum YieldStatus {
     /// If yielded, the connection assigned to this state will be read into the inner buffer.
     /// The read result will be stored in the result pointer.
     /// If successful, the slice reference will be stored in the result pointer.
-    /// If the length of the slice is 0, the connection has been terminated by the other side.
+    /// If the length of the slice is 0, the connection has been terminated by the other side,
+    /// and all next reads return an empty slice too. A failed read returns an error, never an empty slice.
     ///
     /// After next yield or return, the buffer will be rewritten.
     ///
@@ -157,7 +502,147 @@ pub enum YieldStatus {
 
     /// [`TcpClose`] takes the state id.
     /// If yielded, the connection assigned to this state will be closed, and the state will be removed.
-    TcpClose(TcpClose)
+    TcpClose(TcpClose),
+
+    /// [`WaitReadable`] takes is registered to the selector, the state and a result pointer.
+    ///
+    /// If yielded, the coroutine will be woken up, when the fd assigned to this state is readable.
+    /// Nothing is read, so the caller must read the fd itself.
+    WaitReadable(WaitReadable),
+
+    /// [`OpenFile`] takes the path, flags, mode and a result pointer.
+    ///
+    /// If yielded, the file will be opened and [`File`] will be stored in the result pointer.
+    OpenFile(OpenFile),
+
+    /// [`FileRead`] takes the fd, the state, the offset, the cursor and a result pointer.
+    ///
+    /// If yielded, the file will be read from the offset into a new [`Buffer`].
+    /// If the length of the buffer is 0, the end of the file has been reached.
+    FileRead(FileRead),
+
+    /// [`FileReadToEnd`] takes the fd, the state, the offset, the cursor and a result pointer.
+    ///
+    /// If yielded, the file will be read from the offset to the end (maybe with multiple syscalls) into a single [`Buffer`].
+    /// The buffer grows as needed.
+    FileReadToEnd(FileReadToEnd),
+
+    /// [`FileWrite`] takes the fd, the state, a buffer, the offset, the cursor and a result pointer.
+    ///
+    /// If yielded, a part of the buffer will be written (with a single syscall) to the file from the offset.
+    FileWrite(FileWrite),
+
+    /// [`FileWriteAll`] takes the fd, the state, a buffer, the offset, the cursor and a result pointer.
+    ///
+    /// If yielded, the buffer will be written whole (maybe with multiple syscalls) to the file from the offset.
+    /// If `sync` is set, the file will be synced after the write.
+    FileWriteAll(FileWriteAll),
+
+    /// [`FileClose`] takes the fd and the state.
+    ///
+    /// If yielded, the file will be closed.
+    FileClose(FileClose),
+
+    /// [`CopyFile`] takes the fd of the source file, the fd of the destination file and a result pointer.
+    ///
+    /// If yielded, the rest of the source file will be copied in the kernel (maybe with multiple syscalls) to the destination file
+    /// from their current positions.
+    CopyFile(CopyFile),
+
+    /// [`Symlink`] takes the original path, the link path and a result pointer.
+    ///
+    /// If yielded, the symbolic link will be created.
+    Symlink(Symlink),
+
+    /// [`HardLink`] takes the original path, the link path and a result pointer.
+    ///
+    /// If yielded, the hard link will be created.
+    HardLink(HardLink),
+
+    /// [`ReadLink`] takes the path and a result pointer.
+    ///
+    /// If yielded, the target of the symbolic link will be stored in the result pointer.
+    ReadLink(ReadLink),
+
+    /// [`SetPermissions`] takes the path, the mode and a result pointer.
+    ///
+    /// If yielded, the permissions of the file will be changed.
+    SetPermissions(SetPermissions),
+
+    /// [`FileSetPermissions`] takes the fd, the state, the mode and a result pointer.
+    ///
+    /// If yielded, the permissions of the open file will be changed.
+    FileSetPermissions(FileSetPermissions),
+
+    /// [`FileAdvise`] takes the fd, the state, the range, the advice and a result pointer.
+    ///
+    /// If yielded, the kernel will be told the access pattern of the range of the file.
+    FileAdvise(FileAdvise),
+
+    /// [`CreateDir`] takes the path, the mode and a result pointer.
+    ///
+    /// If yielded, the directory will be created. Parent directories are not created.
+    CreateDir(CreateDir),
+
+    /// [`RemoveFile`] takes the path and a result pointer.
+    ///
+    /// If yielded, the file will be removed. Directories are not removed.
+    RemoveFile(RemoveFile),
+
+    /// [`RemoveDir`] takes the path and a result pointer.
+    ///
+    /// If yielded, the empty directory will be removed. Files are not removed.
+    RemoveDir(RemoveDir),
+
+    /// [`Rename`] takes the current path, the new path and a result pointer.
+    ///
+    /// If yielded, the file or the directory will be renamed. An existing file at the new path is replaced atomically.
+    Rename(Rename),
+
+    /// [`FileSync`] takes the fd, the state, whether only the data is synced and a result pointer.
+    ///
+    /// If yielded, the written data of the file will be flushed to the storage device.
+    FileSync(FileSync),
+
+    /// [`RegisterFile`] takes the fd and a result pointer.
+    ///
+    /// If yielded, the fd will be registered with the selector, and the result will be stored in the pointer at once.
+    /// Read [`Selector::register_file`](crate::io::Selector::register_file).
+    RegisterFile(RegisterFile),
+
+    /// [`LockFile`] takes the fd, the operation and a result pointer.
+    ///
+    /// If yielded, the advisory lock of the file will be taken on the blocking pool,
+    /// so the worker is not blocked, while the lock is held by another process.
+    LockFile(LockFile),
+
+    /// [`Join`] takes the place for the waiting coroutine.
+    ///
+    /// If yielded, the coroutine will be stored there, and it will be woken up by the child, when it finishes.
+    Join(Join),
+
+    /// [`Cancel`](YieldStatus::Cancel) takes the state of the pending operation.
+    ///
+    /// If yielded, the operation will be cancelled, and its coroutine will get [`ECANCELED`](libc::ECANCELED).
+    /// Only the waiting for readiness (accept, read and [`WaitReadable`]) can be cancelled.
+    Cancel(Ptr<PollState>),
+
+    /// [`Blocking`] takes the closure.
+    ///
+    /// If yielded, the closure will be run on the blocking pool, and the coroutine will be woken up after it.
+    Blocking(Blocking),
+
+    /// [`WaitCapacity`] takes the coroutine to spawn and a result pointer.
+    ///
+    /// If yielded, the coroutine will be woken up, when the worker is under its limits and the coroutine is spawned.
+    WaitCapacity(WaitCapacity),
+
+    /// [`Extension`] takes the id of the extension, a payload and a result pointer.
+    ///
+    /// If yielded, the coroutine and the payload will be passed to the handler of the extension.
+    /// If the extension is not registered in the worker, the coroutine will be woken up with an error of [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported).
+    /// The handler is responsible for waking the coroutine up. Read [`ExtensionHandler`](crate::scheduler::ExtensionHandler) for more details.
+    Extension(Extension)
 }
 
 impl YieldStatus {
@@ -166,6 +651,14 @@ impl YieldStatus {
         YieldStatus::Yield
     }
 
+    /// Writes the `value` to the `res` and creates a YieldStatus variant [`Yield`](YieldStatus::Yield),
+    /// so the coroutine reads the value after the yield. It is used by operations, that are done before they are started
+    /// (for example, with an invalid path).
+    pub(crate) fn ready<T>(res: *mut T, value: T) -> Self {
+        unsafe { res.write(value) };
+        YieldStatus::Yield
+    }
+
     /// Create a YieldStatus variant [`End`](YieldStatus::End).
     pub fn end() -> Self {
         YieldStatus::End
@@ -176,6 +669,11 @@ impl YieldStatus {
         YieldStatus::Sleep(duration)
     }
 
+    /// Create a YieldStatus variant [`SleepUntil`](YieldStatus::SleepUntil).
+    pub fn sleep_until(instant: Instant) -> Self {
+        YieldStatus::SleepUntil(instant)
+    }
+
     /// Create a YieldStatus variant [`NewTcpListener`](YieldStatus::NewTcpListener).
     pub fn new_tcp_listener(address: SocketAddr, listener_ptr: *mut TcpListener) -> Self {
         YieldStatus::NewTcpListener(NewTcpListener { address, listener_ptr })
@@ -183,7 +681,12 @@ impl YieldStatus {
 
     /// Create a YieldStatus variant [`TcpConnect`](YieldStatus::TcpConnect).
     pub fn tcp_connect(address: SocketAddr, result_ptr: *mut Result<TcpStream, std::io::Error>) -> Self {
-        YieldStatus::TcpConnect(TcpConnect { address, stream_ptr: result_ptr })
+        YieldStatus::TcpConnect(TcpConnect { address, timeout: None, stream_ptr: result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`TcpConnect`](YieldStatus::TcpConnect) with a timeout.
+    pub fn tcp_connect_timeout(address: SocketAddr, timeout: Duration, result_ptr: *mut Result<TcpStream, std::io::Error>) -> Self {
+        YieldStatus::TcpConnect(TcpConnect { address, timeout: Some(timeout), stream_ptr: result_ptr })
     }
 
     /// Create a YieldStatus variant [`TcpAccept`](YieldStatus::TcpAccept).
@@ -192,13 +695,18 @@ impl YieldStatus {
     }
 
     /// Create a YieldStatus variant [`TcpRead`](YieldStatus::TcpRead).
-    pub fn tcp_read(is_registered: bool, state_ref: Ptr<PollState>, result_ptr: *mut Result<&'static [u8], std::io::Error>) -> Self {
-        YieldStatus::TcpRead(TcpRead { is_registered, state_ref, result_ptr })
+    pub fn tcp_read(is_registered: bool, is_eof: bool, state_ref: Ptr<PollState>, coalesce: usize, result_ptr: *mut Result<&'static [u8], std::io::Error>) -> Self {
+        YieldStatus::TcpRead(TcpRead { is_registered, is_eof, state_ref, coalesce, result_ptr })
     }
 
     /// Create a YieldStatus variant [`TcpWrite`](YieldStatus::TcpWrite).
     pub fn tcp_write(state_ref: Ptr<PollState>, buffer: Buffer, result_ptr: *mut Result<Option<Buffer>, std::io::Error>) -> Self {
-        YieldStatus::TcpWrite(TcpWrite { state_ref, buffer, result_ptr })
+        YieldStatus::TcpWrite(TcpWrite { state_ref, buffer, result_ptr, zero_copy: false })
+    }
+
+    /// Create a YieldStatus variant [`TcpWrite`](YieldStatus::TcpWrite), that sends the buffer without copying.
+    pub fn tcp_write_zc(state_ref: Ptr<PollState>, buffer: Buffer, result_ptr: *mut Result<Option<Buffer>, std::io::Error>) -> Self {
+        YieldStatus::TcpWrite(TcpWrite { state_ref, buffer, result_ptr, zero_copy: true })
     }
 
     /// Create a YieldStatus variant [`TcpWriteAll`](YieldStatus::TcpWriteAll).
@@ -210,4 +718,182 @@ impl YieldStatus {
     pub fn tcp_close(state_ref: Ptr<PollState>) -> Self {
         YieldStatus::TcpClose(TcpClose { state_ptr: state_ref })
     }
+
+    /// Create a YieldStatus variant [`WaitReadable`](YieldStatus::WaitReadable).
+    pub fn wait_readable(is_registered: bool, state_ref: Ptr<PollState>, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::WaitReadable(WaitReadable { is_registered, state_ref, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`OpenFile`](YieldStatus::OpenFile).
+    pub fn open_file(path: CString, flags: i32, mode: u32, result_ptr: *mut Result<File, std::io::Error>) -> Self {
+        YieldStatus::OpenFile(OpenFile { path, flags, mode, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileRead`](YieldStatus::FileRead).
+    pub fn file_read(fd: RawFd, state_ref: Ptr<PollState>, offset: u64, cursor: *mut u64, direct: bool, result_ptr: *mut Result<Buffer, std::io::Error>) -> Self {
+        YieldStatus::FileRead(FileRead { fd, state_ref, offset, cursor, direct, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileReadToEnd`](YieldStatus::FileReadToEnd).
+    pub fn file_read_to_end(fd: RawFd, state_ref: Ptr<PollState>, offset: u64, cursor: *mut u64, direct: bool, result_ptr: *mut Result<Buffer, std::io::Error>) -> Self {
+        YieldStatus::FileReadToEnd(FileReadToEnd { fd, state_ref, offset, cursor, direct, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileWrite`](YieldStatus::FileWrite).
+    pub fn file_write(
+        fd: RawFd,
+        state_ref: Ptr<PollState>,
+        buffer: Buffer,
+        offset: u64,
+        cursor: *mut u64,
+        result_ptr: *mut Result<Option<Buffer>, std::io::Error>
+    ) -> Self {
+        YieldStatus::FileWrite(FileWrite { fd, state_ref, buffer, offset, cursor, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileWriteAll`](YieldStatus::FileWriteAll).
+    pub fn file_write_all(
+        fd: RawFd,
+        state_ref: Ptr<PollState>,
+        buffer: Buffer,
+        offset: u64,
+        cursor: *mut u64,
+        result_ptr: *mut Result<(), std::io::Error>
+    ) -> Self {
+        YieldStatus::FileWriteAll(FileWriteAll { fd, state_ref, buffer, offset, cursor, sync: None, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileWriteAll`](YieldStatus::FileWriteAll), that syncs the file after the write.
+    pub fn file_write_all_sync(
+        fd: RawFd,
+        state_ref: Ptr<PollState>,
+        buffer: Buffer,
+        offset: u64,
+        cursor: *mut u64,
+        data_only: bool,
+        result_ptr: *mut Result<(), std::io::Error>
+    ) -> Self {
+        YieldStatus::FileWriteAll(FileWriteAll { fd, state_ref, buffer, offset, cursor, sync: Some(data_only), result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileClose`](YieldStatus::FileClose).
+    pub fn file_close(fd: RawFd, state_ref: Ptr<PollState>) -> Self {
+        YieldStatus::FileClose(FileClose { fd, state_ref })
+    }
+
+    /// Create a YieldStatus variant [`CopyFile`](YieldStatus::CopyFile).
+    pub fn copy_file(src_fd: RawFd, dst_fd: RawFd, result_ptr: *mut Result<u64, std::io::Error>) -> Self {
+        YieldStatus::CopyFile(CopyFile { src_fd, dst_fd, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`Symlink`](YieldStatus::Symlink).
+    pub fn symlink(original: CString, link: CString, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::Symlink(Symlink { original, link, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`HardLink`](YieldStatus::HardLink).
+    pub fn hard_link(original: CString, link: CString, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::HardLink(HardLink { original, link, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`ReadLink`](YieldStatus::ReadLink).
+    pub fn read_link(path: CString, result_ptr: *mut Result<PathBuf, std::io::Error>) -> Self {
+        YieldStatus::ReadLink(ReadLink { path, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`SetPermissions`](YieldStatus::SetPermissions).
+    pub fn set_permissions(path: CString, mode: u32, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::SetPermissions(SetPermissions { path, mode, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileSetPermissions`](YieldStatus::FileSetPermissions).
+    pub fn file_set_permissions(fd: RawFd, state_ref: Ptr<PollState>, mode: u32, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::FileSetPermissions(FileSetPermissions { fd, state_ref, mode, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`LockFile`](YieldStatus::LockFile).
+    pub fn lock_file(fd: RawFd, operation: i32, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::LockFile(LockFile { fd, operation, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`Join`](YieldStatus::Join).
+    pub fn join(waiter: *mut Option<CoroutineImpl>) -> Self {
+        YieldStatus::Join(Join { waiter, timeout: None })
+    }
+
+    /// Create a YieldStatus variant [`Join`](YieldStatus::Join) with the timer, that runs after the duration.
+    pub fn join_with_timeout(waiter: *mut Option<CoroutineImpl>, dur: Duration, timer: CoroutineImpl) -> Self {
+        YieldStatus::Join(Join { waiter, timeout: Some((dur, timer)) })
+    }
+
+    /// Create a YieldStatus variant [`Cancel`](YieldStatus::Cancel).
+    pub fn cancel(state_ref: Ptr<PollState>) -> Self {
+        YieldStatus::Cancel(state_ref)
+    }
+
+    /// Returns the state of the operation, that can be cancelled with [`YieldStatus::Cancel`].
+    pub(crate) fn cancellable_state(&self) -> Option<Ptr<PollState>> {
+        match self {
+            YieldStatus::TcpAccept(status) => Some(status.state_ref),
+            YieldStatus::TcpRead(status) => Some(status.state_ref),
+            YieldStatus::WaitReadable(status) => Some(status.state_ref),
+            _ => None
+        }
+    }
+
+    /// Returns true, if the operation uses nothing of the yielding coroutine except the result pointer,
+    /// so it can complete in the background after the coroutine is gone.
+    pub(crate) fn is_detached(&self) -> bool {
+        matches!(self, YieldStatus::Yield | YieldStatus::Sleep(_) | YieldStatus::SleepUntil(_))
+    }
+
+    /// Create a YieldStatus variant [`WaitCapacity`](YieldStatus::WaitCapacity).
+    pub fn wait_capacity(func: CoroutineImpl, result_ptr: *mut Result<(), LimitExceeded>) -> Self {
+        YieldStatus::WaitCapacity(WaitCapacity { func, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`Blocking`](YieldStatus::Blocking).
+    pub fn blocking(job: Box<dyn FnOnce() + Send>) -> Self {
+        YieldStatus::Blocking(Blocking { job })
+    }
+
+    /// Create a YieldStatus variant [`FileAdvise`](YieldStatus::FileAdvise).
+    pub fn file_advise(fd: RawFd, state_ref: Ptr<PollState>, offset: u64, len: u64, advice: i32, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::FileAdvise(FileAdvise { fd, state_ref, offset, len, advice, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`CreateDir`](YieldStatus::CreateDir).
+    pub fn create_dir(path: CString, mode: u32, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::CreateDir(CreateDir { path, mode, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`RemoveFile`](YieldStatus::RemoveFile).
+    pub fn remove_file(path: CString, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::RemoveFile(RemoveFile { path, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`RemoveDir`](YieldStatus::RemoveDir).
+    pub fn remove_dir(path: CString, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::RemoveDir(RemoveDir { path, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`Rename`](YieldStatus::Rename).
+    pub fn rename(from: CString, to: CString, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::Rename(Rename { from, to, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`FileSync`](YieldStatus::FileSync).
+    pub fn file_sync(fd: RawFd, state_ref: Ptr<PollState>, data_only: bool, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::FileSync(FileSync { fd, state_ref, data_only, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`RegisterFile`](YieldStatus::RegisterFile).
+    pub fn register_file(fd: RawFd, result_ptr: *mut Result<bool, std::io::Error>) -> Self {
+        YieldStatus::RegisterFile(RegisterFile { fd, result_ptr })
+    }
+
+    /// Create a YieldStatus variant [`Extension`](YieldStatus::Extension).
+    pub fn extension(id: ExtensionId, payload: Ptr<()>, result_ptr: *mut Result<(), std::io::Error>) -> Self {
+        YieldStatus::Extension(Extension { id, payload, result_ptr })
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 //! This module contains functions for the high-level working with the scheduler. For example, [`yield_now`].
 use crate::coroutine::YieldStatus;
+use crate::scheduler::ExtensionId;
+use crate::utils::Ptr;
 
 /// Returns [`YieldStatus::Yield`]. If yielded, the [`scheduler`](crate::scheduler::Scheduler) will wake the coroutine up later.
 ///
@@ -31,4 +33,12 @@ pub fn yield_now(_res: *mut ()) -> YieldStatus {
 /// Because it can lead to a memory leak and coroutine leak (that can cause a deadlock). It uses only for test and recommended to use it only for testing.
 pub fn end(_res: *mut ()) -> YieldStatus {
     YieldStatus::end()
-}
\ No newline at end of file
+}
+
+/// Returns [`YieldStatus::Extension`]. If yielded, the coroutine and the payload will be passed to the handler of the extension,
+/// registered by [`register_extension`](crate::scheduler::Scheduler::register_extension).
+///
+/// Returns an error of [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported), if the extension is not registered in this worker.
+pub fn extension(id: ExtensionId, payload: Ptr<()>, res: *mut Result<(), std::io::Error>) -> YieldStatus {
+    YieldStatus::extension(id, payload, res)
+}
@@ -0,0 +1,42 @@
+//! This module contains [`Advice`].
+
+/// The expected access pattern of a range of a [`File`](crate::fs::File). Read [`File::advise`](crate::fs::File::advise).
+///
+/// It is only a hint, the kernel can ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// No special treatment. It is the default.
+    Normal,
+    /// The range will be read sequentially, so the kernel reads ahead more aggressively.
+    Sequential,
+    /// The range will be read in a random order, so the kernel does not read ahead.
+    Random,
+    /// The range will be read soon, so the kernel starts reading it into the page cache.
+    WillNeed,
+    /// The range will not be read soon, so the kernel can drop it from the page cache.
+    /// Use it after a large scan to avoid cache pollution.
+    DontNeed,
+    /// The range will be read only once.
+    NoReuse
+}
+
+impl Advice {
+    /// Returns the advice for `posix_fadvise`.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    pub(crate) fn as_raw(self) -> i32 {
+        match self {
+            Advice::Normal => libc::POSIX_FADV_NORMAL,
+            Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Advice::Random => libc::POSIX_FADV_RANDOM,
+            Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+            Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+            Advice::NoReuse => libc::POSIX_FADV_NOREUSE
+        }
+    }
+
+    /// Returns 0, because `posix_fadvise` is not available, so the advice is ignored.
+    #[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+    pub(crate) fn as_raw(self) -> i32 {
+        0
+    }
+}
@@ -0,0 +1,241 @@
+//! This module contains [`BufReader`] and [`Lines`].
+use std::io::{Error, ErrorKind};
+use std::mem;
+use crate::buf::Buffer;
+use crate::{coro, wait};
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::AsyncRead;
+
+/// Adds buffering to any reader, that reads into [`Buffer`]s, like [`File`](crate::fs::File).
+///
+/// Every read from the inner reader fills a whole pool [`Buffer`], and small reads (like [`BufReader::read_line`])
+/// are served from it, so the selector is used only when the buffer is drained.
+///
+/// # Coroutines
+///
+/// [`BufReader::read_line`] and [`Lines::next`] return coroutines, so use them with [`wait!`](crate::wait).
+/// When the data is already buffered, the coroutine completes without yielding.
+/// The reader must not be moved, while the coroutine is running.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::{BufReader, File};
+///
+/// #[coro]
+/// fn parse_config() {
+///     let file: File = (yield File::open("config.toml")).unwrap();
+///     let mut lines = BufReader::new(file).lines();
+///     loop {
+///         let line: Result<Option<String>, Error> = wait!(lines.next());
+///         match line.unwrap() {
+///             Some(line) => println!("{}", line),
+///             None => break
+///         }
+///     }
+/// }
+/// ```
+pub struct BufReader<R: AsyncRead<Buffer>> {
+    inner: R,
+    /// Unconsumed bytes are from `offset` to `written`.
+    buf: Option<Buffer>,
+    eof: bool
+}
+
+impl<R: AsyncRead<Buffer> + 'static> BufReader<R> {
+    /// Creates a new [`BufReader`].
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: None,
+            eof: false
+        }
+    }
+
+    /// Returns a reference to the inner reader.
+    #[inline(always)]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    ///
+    /// Reading directly from the inner reader will skip the buffered data.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader. The buffered data is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the buffered, but not consumed, bytes.
+    pub fn buffer(&self) -> &[u8] {
+        match &self.buf {
+            Some(buf) => buf.as_ref(),
+            None => &[]
+        }
+    }
+
+    /// Reads bytes until a newline (`\n`, included) or the end of the reader and appends them to `line`.
+    ///
+    /// Returns the number of appended bytes. 0 means the end of the reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidData`] if the line is not valid UTF-8. In this case `line` is not changed.
+    pub fn read_line(&mut self, line: &mut String, res: *mut Result<usize, Error>) -> CoroutineImpl {
+        read_line(self, line, res)
+    }
+
+    /// Returns [`Lines`] over the lines of this reader.
+    pub fn lines(self) -> Lines<R> {
+        Lines { reader: self }
+    }
+
+    /// Consumes up to a newline from the buffered data and appends it to `bytes`. Returns true, if the newline was found.
+    fn consume_line(&mut self, bytes: &mut Vec<u8>) -> bool {
+        let buf = match &mut self.buf {
+            Some(buf) => buf,
+            None => return false
+        };
+
+        let data = buf.as_ref();
+        let (n, found) = match data.iter().position(|&b| b == b'\n') {
+            Some(pos) => (pos + 1, true),
+            None => (data.len(), false)
+        };
+        bytes.extend_from_slice(&data[..n]);
+        buf.set_offset(buf.offset() + n);
+        if buf.len() == 0 {
+            self.buf = None;
+        }
+
+        found
+    }
+}
+
+impl<R: AsyncRead<Buffer> + 'static> AsyncRead<Buffer> for BufReader<R> {
+    /// Returns the buffered data if any, otherwise reads from the inner reader.
+    ///
+    /// If the data is buffered, the result is written at once, and the coroutine is only yielded.
+    fn read(&mut self, res: *mut Result<Buffer, Error>) -> YieldStatus {
+        match self.buf.take() {
+            Some(buf) => YieldStatus::ready(res, Ok(buf)),
+            None => self.inner.read(res)
+        }
+    }
+}
+
+#[coro(crate="crate")]
+fn read_line<R: AsyncRead<Buffer> + 'static>(reader: *mut BufReader<R>, line: *mut String) -> Result<usize, Error> {
+    let reader = unsafe { &mut *reader };
+    let mut bytes = Vec::new();
+    loop {
+        if reader.consume_line(&mut bytes) || reader.eof {
+            break;
+        }
+
+        if reader.buf.is_none() {
+            let res: Result<Buffer, Error> = yield reader.inner.read();
+            match res {
+                Ok(buf) if buf.len() == 0 => reader.eof = true,
+                Ok(buf) => reader.buf = Some(buf),
+                Err(err) => return Err(err)
+            };
+        }
+    }
+
+    let n = bytes.len();
+    let s = match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => return Err(Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+    };
+    unsafe { (*line).push_str(&s); }
+
+    return Ok(n);
+}
+
+/// An iterator-like handle over the lines of a [`BufReader`]. Read [`BufReader::lines`].
+pub struct Lines<R: AsyncRead<Buffer>> {
+    reader: BufReader<R>
+}
+
+impl<R: AsyncRead<Buffer> + 'static> Lines<R> {
+    /// Returns the next line without the newline (`\n` or `\r\n`), or [`None`] at the end of the reader.
+    ///
+    /// Read [`BufReader::read_line`] for more information.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self, res: *mut Result<Option<String>, Error>) -> CoroutineImpl {
+        next_line(&mut self.reader, res)
+    }
+
+    /// Returns the inner [`BufReader`].
+    pub fn into_inner(self) -> BufReader<R> {
+        self.reader
+    }
+}
+
+#[coro(crate="crate")]
+fn next_line<R: AsyncRead<Buffer> + 'static>(reader: *mut BufReader<R>) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+    let res: Result<usize, Error> = wait!(read_line(reader, &mut line));
+    return match res {
+        Ok(0) => Ok(None),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Some(mem::take(&mut line)))
+        }
+        Err(err) => Err(err)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::buf::Buffer;
+    use crate::fs::{BufReader, File};
+    use crate::io::AsyncRead;
+
+    #[test_local(crate="crate")]
+    fn test_lines() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_buf_reader_{}", std::process::id()));
+        let long_line = "x".repeat(10_000);
+        std::fs::write(&path, format!("first\r\nsecond\n\n{}\nlast", long_line)).unwrap();
+
+        let file: File = (yield File::open(path.clone())).unwrap();
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        let res: Result<usize, Error> = wait!(reader.read_line(&mut line));
+        assert_eq!(res.unwrap(), 7);
+        assert_eq!(line, "first\r\n");
+        assert!(reader.buffer().starts_with(b"second\n"), "the rest of the read is not buffered");
+
+        let mut lines = reader.lines();
+        let mut got = Vec::new();
+        loop {
+            let res: Result<Option<String>, Error> = wait!(lines.next());
+            match res.unwrap() {
+                Some(line) => got.push(line),
+                None => break
+            }
+        }
+        assert_eq!(got, vec!["second".to_string(), String::new(), long_line, "last".to_string()]);
+
+        let mut reader = lines.into_inner();
+        let res: Result<Buffer, Error> = yield reader.read();
+        assert_eq!(res.unwrap().len(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
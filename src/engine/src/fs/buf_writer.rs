@@ -0,0 +1,153 @@
+//! This module contains [`BufWriter`].
+use std::io::Error;
+use std::mem;
+use crate::buf::{buffer, Buffer};
+use crate::{coro, wait};
+use crate::coroutine::CoroutineImpl;
+use crate::io::AsyncWrite;
+
+/// Adds buffering to any writer, that writes [`Buffer`]s, like [`File`](crate::fs::File).
+///
+/// Small writes are appended to a pool [`Buffer`], and the buffer is written to the inner writer
+/// only when it is full or [`BufWriter::flush`] is called.
+///
+/// # Coroutines
+///
+/// [`BufWriter::write`] and [`BufWriter::flush`] return coroutines, so use them with [`wait!`](crate::wait).
+/// When the data fits into the buffer, the coroutine completes without yielding.
+/// The writer and the written slice must not be moved, while the coroutine is running.
+///
+/// # Flush
+///
+/// The buffered data is lost, if [`BufWriter`] is dropped without [`BufWriter::flush`],
+/// because a drop can't wait for the write.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::{BufWriter, File};
+///
+/// #[coro]
+/// fn write_report() {
+///     let file: File = (yield File::create("report.txt")).unwrap();
+///     let mut writer = BufWriter::new(file);
+///     for i in 0..100 {
+///         let res: Result<(), Error> = wait!(writer.write(format!("line {}\n", i).as_bytes()));
+///     }
+///     let res: Result<(), Error> = wait!(writer.flush());
+/// }
+/// ```
+pub struct BufWriter<W: AsyncWrite<Buffer>> {
+    inner: W,
+    buf: Buffer
+}
+
+impl<W: AsyncWrite<Buffer> + 'static> BufWriter<W> {
+    /// Creates a new [`BufWriter`] with a buffer from the pool.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: buffer()
+        }
+    }
+
+    /// Returns a reference to the inner writer.
+    #[inline(always)]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    ///
+    /// Writing directly to the inner writer will skip the buffered data.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the buffered, but not written, bytes.
+    #[inline(always)]
+    pub fn buffer(&self) -> &[u8] {
+        self.buf.as_ref()
+    }
+
+    /// Appends `data` to the buffer. The buffer is written to the inner writer first, if `data` doesn't fit into it.
+    pub fn write(&mut self, data: &[u8], res: *mut Result<(), Error>) -> CoroutineImpl {
+        write(self, data, res)
+    }
+
+    /// Writes the buffered data to the inner writer.
+    pub fn flush(&mut self, res: *mut Result<(), Error>) -> CoroutineImpl {
+        flush(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn write<W: AsyncWrite<Buffer> + 'static>(writer: *mut BufWriter<W>, data: *const [u8]) -> Result<(), Error> {
+    let writer = unsafe { &mut *writer };
+    let data = unsafe { &*data };
+    if writer.buf.len() + data.len() > writer.buf.cap() && writer.buf.len() > 0 {
+        let res: Result<(), Error> = wait!(flush(writer));
+        if res.is_err() {
+            return res;
+        }
+    }
+
+    // A slice, that is larger than the buffer, grows it, so it is written with a single syscall too.
+    writer.buf.append(data);
+    if writer.buf.len() >= writer.buf.cap() {
+        let res: Result<(), Error> = wait!(flush(writer));
+        return res;
+    }
+
+    return Ok(());
+}
+
+#[coro(crate="crate")]
+fn flush<W: AsyncWrite<Buffer> + 'static>(writer: *mut BufWriter<W>) -> Result<(), Error> {
+    let writer = unsafe { &mut *writer };
+    if writer.buf.len() == 0 {
+        return Ok(());
+    }
+
+    let buf = mem::replace(&mut writer.buf, buffer());
+    let res: Result<(), Error> = yield writer.inner.write_all(buf);
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::fs::{BufWriter, File};
+
+    #[test_local(crate="crate")]
+    fn test_buf_writer() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_buf_writer_{}", std::process::id()));
+        let file: File = (yield File::create(path.clone())).unwrap();
+        let mut writer = BufWriter::new(file);
+
+        let mut expected = String::new();
+        for i in 0..2000 {
+            let line = format!("line {}\n", i);
+            let res: Result<(), Error> = wait!(writer.write(line.as_bytes()));
+            res.unwrap();
+            expected.push_str(&line);
+        }
+        assert!(writer.buffer().len() > 0);
+
+        let big = vec![b'y'; 20_000];
+        let res: Result<(), Error> = wait!(writer.write(&big));
+        res.unwrap();
+        expected.push_str(std::str::from_utf8(&big).unwrap());
+
+        let res: Result<(), Error> = wait!(writer.flush());
+        res.unwrap();
+        assert_eq!(writer.buffer().len(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
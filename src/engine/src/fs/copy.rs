@@ -0,0 +1,70 @@
+//! This module contains [`copy`].
+use std::io::Error;
+use std::path::Path;
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::fs::File;
+
+/// Copies the contents of one file to another. Returns the number of copied bytes.
+///
+/// It creates the destination file if it does not exist, and truncates it if it does.
+/// The data is copied in the kernel with `copy_file_range` (or `sendfile` as a fallback),
+/// so it is not moved through user space buffers.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs;
+///
+/// #[coro]
+/// fn backup() {
+///     let copied: Result<u64, Error> = wait!(fs::copy("data.db", "data.db.bak"));
+/// }
+/// ```
+pub fn copy<P: AsRef<Path> + 'static, Q: AsRef<Path> + 'static>(from: P, to: Q, res: *mut Result<u64, Error>) -> CoroutineImpl {
+    copy_file(from, to, res)
+}
+
+#[coro(crate="crate")]
+fn copy_file<P: AsRef<Path> + 'static, Q: AsRef<Path> + 'static>(from: P, to: Q) -> Result<u64, Error> {
+    let res: Result<File, Error> = yield File::open(from);
+    let src = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<File, Error> = yield File::create(to);
+    let dst = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<u64, Error> = yield YieldStatus::copy_file(src.fd(), dst.fd());
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::fs;
+
+    #[test_local(crate="crate")]
+    fn test_copy() {
+        let from = std::env::temp_dir().join(format!("coroeng_test_copy_from_{}", std::process::id()));
+        let to = std::env::temp_dir().join(format!("coroeng_test_copy_to_{}", std::process::id()));
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&from, &data).unwrap();
+        std::fs::write(&to, b"old contents, that must be truncated").unwrap();
+
+        let res: Result<u64, Error> = wait!(fs::copy(from.clone(), to.clone()));
+        assert_eq!(res.unwrap(), data.len() as u64);
+        assert_eq!(std::fs::read(&to).unwrap(), data);
+
+        std::fs::remove_file(&from).unwrap();
+        std::fs::remove_file(&to).unwrap();
+    }
+}
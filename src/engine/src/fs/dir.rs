@@ -0,0 +1,127 @@
+//! This module contains [`create_dir`] and [`create_dir_all`].
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::utils::{normalize_path, path_to_c_string};
+use crate::write_err;
+
+/// The mode of new directories. It is masked by the umask of the process.
+pub(crate) const DIR_MODE: u32 = 0o777;
+
+/// Creates a new empty directory.
+///
+/// It does not create parent directories, use [`create_dir_all`] for it.
+///
+/// If the path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::create_dir;
+///
+/// #[coro]
+/// fn create_cache_dir() {
+///     let res: Result<(), Error> = yield create_dir("cache");
+/// }
+/// ```
+pub fn create_dir<P: AsRef<Path>>(path: P, res: *mut Result<(), Error>) -> YieldStatus {
+    create_dir_with_mode(path, DIR_MODE, res)
+}
+
+/// Creates a new empty directory with the unix `mode`. Read [`create_dir`] for more information.
+pub(crate) fn create_dir_with_mode<P: AsRef<Path>>(path: P, mode: u32, res: *mut Result<(), Error>) -> YieldStatus {
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::create_dir(path, mode, res),
+        Err(err) => {
+            write_err!(res, err);
+            YieldStatus::yield_now()
+        }
+    }
+}
+
+/// Creates a directory and all of its missing parents.
+///
+/// The directories are created one by one from the outermost.
+/// A directory, that already exists (or was created concurrently), is not an error.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs;
+///
+/// #[coro]
+/// fn create_data_dirs() {
+///     let res: Result<(), Error> = wait!(fs::create_dir_all("data/logs/2024"));
+/// }
+/// ```
+pub fn create_dir_all<P: AsRef<Path> + 'static>(path: P, res: *mut Result<(), Error>) -> CoroutineImpl {
+    create_dir_all_with_mode(path, DIR_MODE, res)
+}
+
+/// Creates a directory and all of its missing parents with the unix `mode`. Read [`create_dir_all`] for more information.
+#[coro(crate="crate")]
+pub(crate) fn create_dir_all_with_mode<P: AsRef<Path> + 'static>(path: P, mode: u32) -> Result<(), Error> {
+    let path = normalize_path(path);
+
+    // The common case: only the last directory is missing.
+    let res: Result<(), Error> = yield create_dir_with_mode(path.clone(), mode);
+    if res.is_ok() || path.is_dir() {
+        return Ok(());
+    }
+
+    let mut ancestors: Vec<PathBuf> = path.ancestors().skip(1).map(Path::to_path_buf).collect();
+    ancestors.reverse();
+    ancestors.push(path);
+
+    for dir in ancestors {
+        if dir.parent().is_none() {
+            continue;
+        }
+
+        let res: Result<(), Error> = yield create_dir_with_mode(dir.clone(), mode);
+        match res {
+            Ok(()) => {}
+            Err(_) if dir.is_dir() => {}
+            Err(err) => return Err(err)
+        };
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::fs::{create_dir, create_dir_all};
+
+    #[test_local(crate="crate")]
+    fn test_create_dir() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_create_dir_{}", std::process::id()));
+        let nested = dir.join("a/b/c");
+
+        let res: Result<(), Error> = yield create_dir(nested.clone());
+        assert!(res.is_err(), "a directory was created without its parents");
+
+        let res: Result<(), Error> = wait!(create_dir_all(nested.clone()));
+        res.unwrap();
+        assert!(nested.is_dir());
+
+        let res: Result<(), Error> = wait!(create_dir_all(nested.clone()));
+        res.unwrap();
+        let res: Result<(), Error> = yield create_dir(nested.clone());
+        assert!(res.is_err(), "an existing directory was created");
+
+        std::fs::write(dir.join("file"), b"data").unwrap();
+        let res: Result<(), Error> = wait!(create_dir_all(dir.join("file/d")));
+        assert!(res.is_err(), "a directory was created in a file");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! This module contains [`DirBuilder`].
+use std::io::Error;
+use std::path::Path;
+use crate::coro;
+use crate::coroutine::CoroutineImpl;
+use crate::fs::dir::{create_dir_all_with_mode, create_dir_with_mode, DIR_MODE};
+
+/// A builder used to create directories in various manners.
+///
+/// It mirrors [`std::fs::DirBuilder`], but [`DirBuilder::create`] returns a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::DirBuilder;
+///
+/// #[coro]
+/// fn create_private_dir() {
+///     let res: Result<(), Error> = wait!(DirBuilder::new().recursive(true).mode(0o700).create("data/private"));
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirBuilder {
+    recursive: bool,
+    mode: u32
+}
+
+impl DirBuilder {
+    /// Creates a new set of options with default mode (`0o777`) and not recursive.
+    pub fn new() -> Self {
+        Self {
+            recursive: false,
+            mode: DIR_MODE
+        }
+    }
+
+    /// Sets the option to create all parent directories if they do not exist.
+    /// A directory, that already exists, is not an error in this case.
+    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sets the unix mode to create new directories with. It is masked by the umask of the process.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Creates the directory at `path` with the options specified by `self`.
+    pub fn create<P: AsRef<Path> + 'static>(&self, path: P, res: *mut Result<(), Error>) -> CoroutineImpl {
+        if self.recursive {
+            create_dir_all_with_mode(path, self.mode, res)
+        } else {
+            create_one_dir(path, self.mode, res)
+        }
+    }
+}
+
+impl Default for DirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates a single directory. It is a coroutine to make [`DirBuilder::create`] return the same type for both cases.
+#[coro(crate="crate")]
+fn create_one_dir<P: AsRef<Path> + 'static>(path: P, mode: u32) -> Result<(), Error> {
+    let res: Result<(), Error> = yield create_dir_with_mode(path, mode);
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::os::unix::fs::PermissionsExt;
+    use crate::{test_local, wait};
+    use crate::fs::DirBuilder;
+
+    #[test_local(crate="crate")]
+    fn test_dir_builder() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_dir_builder_{}", std::process::id()));
+        let nested = dir.join("a/b");
+
+        let res: Result<(), Error> = wait!(DirBuilder::new().mode(0o700).create(nested.clone()));
+        assert!(res.is_err(), "a directory was created without its parents");
+
+        let res: Result<(), Error> = wait!(DirBuilder::new().recursive(true).mode(0o700).create(nested.clone()));
+        res.unwrap();
+        assert_eq!(std::fs::metadata(&nested).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let res: Result<(), Error> = wait!(DirBuilder::new().recursive(true).create(nested.clone()));
+        res.unwrap();
+        let res: Result<(), Error> = wait!(DirBuilder::new().create(nested.clone()));
+        assert!(res.is_err(), "an existing directory was created");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,518 @@
+//! This module contains [`File`].
+use std::fmt::{Debug, Formatter};
+use std::io::{Error, ErrorKind, SeekFrom};
+use std::mem::MaybeUninit;
+use std::ops::Range;
+use std::os::fd::RawFd;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use crate::buf::Buffer;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::fs::{Advice, Mmap, OpenOptions};
+use crate::fs::open_options::is_direct;
+use crate::io::{AsyncRead, AsyncWrite, PollState};
+use crate::local_scheduler;
+use crate::utils::Ptr;
+
+/// An open file on the filesystem.
+///
+/// # Cursor
+///
+/// [`File`] keeps its own cursor in user space. [`read`](AsyncRead::read), [`write`](AsyncWrite::write)
+/// and [`write_all`](AsyncWrite::write_all) start from the cursor and move it by the number of bytes transferred,
+/// like [`std::fs::File`] does. Use [`File::seek`] to move the cursor.
+///
+/// [`File::pread`] and [`File::pwrite`] take an explicit offset and do not move the cursor.
+///
+/// # Close
+///
+/// [`File`] is automatically closed when it is dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::{Error, SeekFrom};
+/// use engine::coro;
+/// use engine::fs::File;
+/// use engine::buf::{buffer, Buffer};
+/// use engine::io::{AsyncRead, AsyncWrite};
+///
+/// #[coro]
+/// fn rewrite_header() {
+///     let mut file: File = (yield File::create("data.bin")).unwrap();
+///     let mut buf = buffer();
+///     buf.append(b"header and body");
+///     let _: Result<(), Error> = yield file.write_all(buf);
+///
+///     file.seek(SeekFrom::Start(0)).unwrap();
+///     let header: Buffer = (yield file.read()).unwrap();
+/// }
+/// ```
+pub struct File {
+    fd: RawFd,
+    data: Ptr<PollState>,
+    cursor: u64,
+    /// Whether the file is opened with `O_DIRECT`. Read [`OpenOptions::direct`].
+    direct: bool,
+    /// The path, that the file was opened with. It is `None` for anonymous files.
+    path: Option<PathBuf>,
+    options: OpenOptions
+}
+
+impl File {
+    /// Creates a new [`File`] from a raw file descriptor, that is opened at `path` with `flags` and `mode`.
+    pub(crate) fn from_opened(fd: RawFd, path: CString, flags: i32, mode: u32) -> Self {
+        // With O_TMPFILE the path is the directory, and the file itself has no name.
+        #[cfg(target_os = "linux")]
+        let is_anonymous = flags & libc::O_TMPFILE == libc::O_TMPFILE;
+        #[cfg(not(target_os = "linux"))]
+        let is_anonymous = false;
+        let path = if is_anonymous {
+            None
+        } else {
+            Some(PathBuf::from(OsString::from_vec(path.into_bytes())))
+        };
+
+        Self {
+            fd,
+            data: Ptr::new(PollState::new_empty(fd)),
+            cursor: 0,
+            direct: is_direct(flags),
+            path,
+            options: OpenOptions::from_flags(flags, mode)
+        }
+    }
+
+    /// Opens a file in read-only mode.
+    ///
+    /// Read [`OpenOptions::open`] for more information.
+    pub fn open<P: AsRef<Path>>(path: P, res: *mut Result<File, Error>) -> YieldStatus {
+        OpenOptions::new().read(true).open(path, res)
+    }
+
+    /// Opens a file in write-only mode. It creates the file if it does not exist, and truncates it if it does.
+    ///
+    /// Read [`OpenOptions::open`] for more information.
+    pub fn create<P: AsRef<Path>>(path: P, res: *mut Result<File, Error>) -> YieldStatus {
+        OpenOptions::new().write(true).create(true).truncate(true).open(path, res)
+    }
+
+    /// Returns the raw file descriptor.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns the path, that the file was opened with. Relative paths are resolved against
+    /// the [`working_dir`](crate::utils::working_dir).
+    ///
+    /// It is `None` for anonymous files, like [`tempfile`](crate::fs::tempfile).
+    /// The path is not updated, if the file is renamed or removed after opening.
+    #[inline(always)]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the options, that the file was opened with.
+    #[inline(always)]
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Moves the cursor. Returns the new position from the start of the file.
+    ///
+    /// It does not yield, because it does not touch the file, except [`SeekFrom::End`], that reads the size of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if the new position is negative or overflows.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => {
+                self.cursor = offset;
+                return Ok(offset);
+            }
+            SeekFrom::Current(delta) => (self.cursor, delta),
+            SeekFrom::End(delta) => (self.size()?, delta)
+        };
+
+        match base.checked_add_signed(delta) {
+            Some(new) => {
+                self.cursor = new;
+                Ok(new)
+            }
+            None => Err(Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))
+        }
+    }
+
+    /// Returns the current position of the cursor from the start of the file.
+    #[inline(always)]
+    pub fn stream_position(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Returns the size of the file.
+    fn size(&self) -> Result<u64, Error> {
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(self.fd, stat.as_mut_ptr()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(unsafe { stat.assume_init() }.st_size as u64)
+    }
+
+    /// Reads data from the file at the offset into a new [`Buffer`]. It does not move the cursor.
+    ///
+    /// If the length of the buffer is 0, the end of the file has been reached.
+    pub fn pread(&mut self, offset: u64, res: *mut Result<Buffer, Error>) -> YieldStatus {
+        YieldStatus::file_read(self.fd, self.data, offset, std::ptr::null_mut(), self.direct, res)
+    }
+
+    /// Reads all bytes from the cursor to the end of the file into a single [`Buffer`] and moves the cursor.
+    ///
+    /// The buffer grows as needed, so it can be larger than the buffers from the pool
+    /// and in this case it will not be put to the pool after drop.
+    pub fn read_to_end(&mut self, res: *mut Result<Buffer, Error>) -> YieldStatus {
+        YieldStatus::file_read_to_end(self.fd, self.data, self.cursor, &mut self.cursor, self.direct, res)
+    }
+
+    /// Writes a part of the buffer (with a single syscall) to the file at the offset. It does not move the cursor.
+    ///
+    /// Read [`AsyncWrite::write`] for more information about the result.
+    pub fn pwrite(&mut self, data: Buffer, offset: u64, res: *mut Result<Option<Buffer>, Error>) -> YieldStatus {
+        YieldStatus::file_write(self.fd, self.data, data, offset, std::ptr::null_mut(), res)
+    }
+
+    /// Changes the permissions of the file. `mode` is the unix mode, like `0o644`.
+    pub fn set_permissions(&mut self, mode: u32, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_set_permissions(self.fd, self.data, mode, res)
+    }
+
+    /// Flushes the written data and the metadata of the file to the storage device, like `fsync`.
+    ///
+    /// Without it, the written data can be lost, if the machine crashes, even after the file is closed.
+    pub fn sync_all(&mut self, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_sync(self.fd, self.data, false, res)
+    }
+
+    /// Flushes the written data of the file to the storage device, like `fdatasync`.
+    /// Metadata, that is not needed to read the data, like the modification time, is not flushed,
+    /// so it is cheaper than [`File::sync_all`].
+    pub fn sync_data(&mut self, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_sync(self.fd, self.data, true, res)
+    }
+
+    /// Writes the whole buffer to the file from the cursor, moves the cursor and flushes the file to the storage device,
+    /// like [`AsyncWrite::write_all`] followed by [`File::sync_all`].
+    ///
+    /// The `Ring` selector links the sync to the write and submits them together, so the worker is woken up once.
+    /// If the write fails, the sync is not done.
+    pub fn write_all_sync(&mut self, data: Buffer, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_write_all_sync(self.fd, self.data, data, self.cursor, &mut self.cursor, false, res)
+    }
+
+    /// Like [`File::write_all_sync`], but flushes only the data, like [`File::sync_data`].
+    pub fn write_all_sync_data(&mut self, data: Buffer, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_write_all_sync(self.fd, self.data, data, self.cursor, &mut self.cursor, true, res)
+    }
+
+    /// Registers the fd of the file with the selector, so reads and writes don't look it up in the fd table.
+    /// It is worth it for files, that are read or written often.
+    /// Returns false, if the selector doesn't register fds or has no free slots.
+    /// Read [`set_fixed_files`](crate::cfg::set_fixed_files) for more information.
+    pub fn register_fixed(&self, res: *mut Result<bool, Error>) -> YieldStatus {
+        YieldStatus::register_file(self.fd, res)
+    }
+
+    /// Tells the kernel the access pattern of the range of the file, starting at `offset`.
+    /// `len` 0 means to the end of the file.
+    ///
+    /// For example, [`Advice::Sequential`] before a large scan and [`Advice::DontNeed`] after it
+    /// make the scan faster and keep the page cache for other data.
+    pub fn advise(&mut self, advice: Advice, offset: u64, len: u64, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_advise(self.fd, self.data, offset, len, advice.as_raw(), res)
+    }
+
+    /// Maps `range` of the file into memory read-only. The file must be opened for reading.
+    ///
+    /// Read [`Mmap`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] if the range is empty.
+    pub fn mmap(&self, range: Range<u64>) -> Result<Mmap, Error> {
+        Mmap::map(self.fd, range)
+    }
+
+    /// Takes an exclusive advisory lock (`flock`) of the file, waiting until it is released by other holders.
+    ///
+    /// Waiting happens on the blocking pool of the scheduler, so the worker handles other coroutines meanwhile.
+    /// The lock belongs to the open file, so other [`File`]s of the same path (in this or other processes) conflict with it.
+    /// It is released by [`File::unlock`] or when the file is closed.
+    pub fn lock_exclusive(&self, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::lock_file(self.fd, libc::LOCK_EX, res)
+    }
+
+    /// Takes a shared advisory lock of the file, waiting until an exclusive lock is released by its holder.
+    ///
+    /// Read [`File::lock_exclusive`] for more information.
+    pub fn lock_shared(&self, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::lock_file(self.fd, libc::LOCK_SH, res)
+    }
+
+    /// Tries to take an exclusive advisory lock of the file without waiting.
+    ///
+    /// It does not yield, because the syscall does not block. Returns false, if the lock is held by another [`File`].
+    pub fn try_lock(&self) -> Result<bool, Error> {
+        if unsafe { libc::flock(self.fd, libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(true);
+        }
+
+        let err = Error::last_os_error();
+        if err.kind() == ErrorKind::WouldBlock {
+            return Ok(false);
+        }
+        Err(err)
+    }
+
+    /// Releases the advisory lock of the file. It does not yield, because the syscall does not block.
+    pub fn unlock(&self) -> Result<(), Error> {
+        if unsafe { libc::flock(self.fd, libc::LOCK_UN) } == 0 {
+            return Ok(());
+        }
+
+        Err(Error::last_os_error())
+    }
+
+    /// Closes the file.
+    fn close(fd: RawFd, state_ref: Ptr<PollState>) -> YieldStatus {
+        YieldStatus::file_close(fd, state_ref)
+    }
+}
+
+impl AsyncRead<Buffer> for File {
+    /// Reads data from the cursor into a new [`Buffer`] and moves the cursor.
+    ///
+    /// If the length of the buffer is 0, the end of the file has been reached.
+    #[inline(always)]
+    fn read(&mut self, res: *mut Result<Buffer, Error>) -> YieldStatus {
+        YieldStatus::file_read(self.fd, self.data, self.cursor, &mut self.cursor, self.direct, res)
+    }
+}
+
+impl AsyncWrite<Buffer> for File {
+    #[inline(always)]
+    fn write(&mut self, data: Buffer, res: *mut Result<Option<Buffer>, Error>) -> YieldStatus {
+        YieldStatus::file_write(self.fd, self.data, data, self.cursor, &mut self.cursor, res)
+    }
+
+    #[inline(always)]
+    fn write_all(&mut self, data: Buffer, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_write_all(self.fd, self.data, data, self.cursor, &mut self.cursor, res)
+    }
+}
+
+impl Debug for File {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("File");
+        debug.field("fd", &self.fd);
+        if let Some(path) = &self.path {
+            debug.field("path", path);
+        }
+        debug.field("cursor", &self.cursor);
+        debug.field("direct", &self.direct);
+        debug.field("state", &unsafe { self.data.as_ref() }.kind());
+        debug.finish()
+    }
+}
+
+fn close_file(fd: RawFd, state_ref: Ptr<PollState>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        // An operation of another coroutine (like a lost branch of select!) can still use the state.
+        // It is cancelled, and the file is closed only after its completion.
+        if !unsafe { state_ref.as_ref() }.is_empty() {
+            yield YieldStatus::cancel(state_ref);
+            while !unsafe { state_ref.as_ref() }.is_empty() {
+                yield YieldStatus::yield_now();
+            }
+        }
+        yield File::close(fd, state_ref);
+        unsafe { state_ref.drop_in_place(); }
+    })
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        local_scheduler().sched(close_file(self.fd, self.data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, SeekFrom};
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, local_scheduler, test_local};
+    use crate::local::Local;
+    use crate::sleep::sleep;
+    use crate::buf::{buffer, Buffer};
+    use crate::fs::{Advice, File, OpenOptions};
+    use crate::io::{AsyncRead, AsyncWrite};
+
+    #[test_local(crate="crate")]
+    fn test_seek_and_cursor() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_seek_{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true);
+        let mut file: File = (yield options.open(path.clone())).unwrap();
+
+        let mut buf = buffer();
+        buf.append(b"hello, world");
+        let res: Result<(), Error> = yield file.write_all(buf);
+        res.unwrap();
+        assert_eq!(file.stream_position(), 12);
+
+        assert_eq!(file.seek(SeekFrom::Start(7)).unwrap(), 7);
+        let buf: Buffer = (yield file.read()).unwrap();
+        assert_eq!(buf.as_ref(), b"world");
+        assert_eq!(file.stream_position(), 12);
+
+        assert_eq!(file.seek(SeekFrom::End(-5)).unwrap(), 7);
+        assert_eq!(file.seek(SeekFrom::Current(-7)).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-1)).is_err());
+        assert_eq!(file.stream_position(), 0);
+
+        let buf: Buffer = (yield file.pread(7)).unwrap();
+        assert_eq!(buf.as_ref(), b"world");
+        assert_eq!(file.stream_position(), 0);
+
+        let buf: Buffer = (yield file.read()).unwrap();
+        assert_eq!(buf.as_ref(), b"hello, world");
+
+        let buf: Buffer = (yield file.read()).unwrap();
+        assert!(buf.as_ref().is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_write_all_sync() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_write_all_sync_{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true);
+        let mut file: File = (yield options.open(path.clone())).unwrap();
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut buf = Buffer::new(data.len());
+        buf.append(&data);
+        let res: Result<(), Error> = yield file.write_all_sync(buf);
+        res.unwrap();
+        let mut buf = buffer();
+        buf.append(b"end");
+        let res: Result<(), Error> = yield file.write_all_sync_data(buf);
+        res.unwrap();
+        assert_eq!(file.stream_position(), data.len() as u64 + 3);
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(&written[..data.len()], data.as_slice());
+        assert_eq!(&written[data.len()..], b"end");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_path_and_options() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_path_{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.append(true).create(true).mode(0o600);
+        let file: File = (yield options.open(path.clone())).unwrap();
+        assert_eq!(file.path(), Some(path.as_path()));
+        assert!(file.options().is_append());
+        assert!(file.options().is_write());
+        assert!(!file.options().is_read());
+        assert_eq!(file.options().get_mode(), 0o600);
+        assert!(format!("{:?}", file).contains(path.to_str().unwrap()));
+        drop(file);
+
+        let file: File = (yield File::open(path.clone())).unwrap();
+        assert!(file.options().is_read());
+        assert!(!file.options().is_write());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_read_to_end() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_read_to_end_{}", std::process::id()));
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let mut file: File = (yield File::open(path.clone())).unwrap();
+        file.seek(SeekFrom::Start(10)).unwrap();
+
+        let buf: Buffer = (yield file.read_to_end()).unwrap();
+        assert_eq!(buf.as_ref(), &data[10..]);
+        assert_eq!(file.stream_position(), data.len() as u64);
+
+        let buf: Buffer = (yield file.read_to_end()).unwrap();
+        assert!(buf.as_ref().is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_advise() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_advise_{}", std::process::id()));
+        std::fs::write(&path, vec![1u8; 100_000]).unwrap();
+
+        let mut file: File = (yield File::open(path.clone())).unwrap();
+        let debug = format!("{:?}", file);
+        assert!(debug.contains(&format!("path: {:?}", path)), "{}", debug);
+        let res: Result<(), Error> = yield file.advise(Advice::Sequential, 0, 0);
+        res.unwrap();
+        let buf: Buffer = (yield file.read_to_end()).unwrap();
+        assert_eq!(buf.len(), 100_000);
+        let res: Result<(), Error> = yield file.advise(Advice::DontNeed, 0, 100_000);
+        res.unwrap();
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_lock() {
+        #[coro(crate="crate")]
+        fn lock_shared(file: File, locked: Local<bool>) {
+            let res: Result<(), Error> = yield file.lock_shared();
+            res.unwrap();
+            *locked.get_mut() = true;
+            file.unlock().unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("coroeng_test_lock_{}", std::process::id()));
+        let holder: File = (yield File::create(path.clone())).unwrap();
+        let res: Result<(), Error> = yield holder.lock_exclusive();
+        res.unwrap();
+
+        let other: File = (yield File::open(path.clone())).unwrap();
+        assert!(!other.try_lock().unwrap());
+
+        let locked = Local::new(false);
+        local_scheduler().sched(lock_shared(other, locked.clone(), null_mut()));
+        // The worker is not blocked, while the other file waits for the lock.
+        yield sleep(Duration::from_millis(10));
+        assert!(!*locked.get());
+
+        holder.unlock().unwrap();
+        for _ in 0..100 {
+            if *locked.get() {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert!(*locked.get());
+        assert!(holder.try_lock().unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
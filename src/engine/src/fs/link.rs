@@ -0,0 +1,112 @@
+//! This module contains [`symlink`], [`hard_link`] and [`read_link`].
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use crate::coroutine::YieldStatus;
+use crate::utils::{path_to_c_string, raw_path_to_c_string};
+
+/// Creates a new symbolic link at `link`, that points to `original`.
+///
+/// If a path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Note
+///
+/// `original` is stored in the link as is, so a relative `original` is resolved against the directory of the link.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::symlink;
+///
+/// #[coro]
+/// fn link_current_release() {
+///     let res: Result<(), Error> = yield symlink("releases/v2", "current");
+/// }
+/// ```
+pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q, res: *mut Result<(), Error>) -> YieldStatus {
+    match (raw_path_to_c_string(original), path_to_c_string(link)) {
+        (Ok(original), Ok(link)) => YieldStatus::symlink(original, link, res),
+        (Err(err), _) | (_, Err(err)) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+/// Creates a new hard link at `link`, that points to the same file as `original`.
+///
+/// If a path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::hard_link;
+///
+/// #[coro]
+/// fn snapshot() {
+///     let res: Result<(), Error> = yield hard_link("data.db", "snapshot.db");
+/// }
+/// ```
+pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q, res: *mut Result<(), Error>) -> YieldStatus {
+    match (path_to_c_string(original), path_to_c_string(link)) {
+        (Ok(original), Ok(link)) => YieldStatus::hard_link(original, link, res),
+        (Err(err), _) | (_, Err(err)) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+/// Reads the target of the symbolic link.
+///
+/// If the path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::path::PathBuf;
+/// use engine::coro;
+/// use engine::fs::read_link;
+///
+/// #[coro]
+/// fn current_release() {
+///     let target: Result<PathBuf, Error> = yield read_link("current");
+/// }
+/// ```
+pub fn read_link<P: AsRef<Path>>(path: P, res: *mut Result<PathBuf, Error>) -> YieldStatus {
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::read_link(path, res),
+        Err(err) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::path::PathBuf;
+    use crate::test_local;
+    use crate::fs::{hard_link, read_link, symlink};
+
+    #[test_local(crate="crate")]
+    fn test_links() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_links_{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let original = dir.join("original");
+        std::fs::write(&original, b"data").unwrap();
+
+        let res: Result<(), Error> = yield symlink("original", dir.join("symlink"));
+        res.unwrap();
+        let res: Result<PathBuf, Error> = yield read_link(dir.join("symlink"));
+        assert_eq!(res.unwrap(), PathBuf::from("original"));
+        assert_eq!(std::fs::read(dir.join("symlink")).unwrap(), b"data");
+
+        let res: Result<(), Error> = yield hard_link(original.clone(), dir.join("hard_link"));
+        res.unwrap();
+        assert_eq!(std::fs::read(dir.join("hard_link")).unwrap(), b"data");
+
+        let res: Result<(), Error> = yield hard_link(original.clone(), dir.join("hard_link"));
+        assert!(res.is_err(), "hard link was created over an existing file");
+        let res: Result<PathBuf, Error> = yield read_link(original);
+        assert!(res.is_err(), "read_link succeeded on a regular file");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
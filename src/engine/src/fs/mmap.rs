@@ -0,0 +1,207 @@
+//! This module contains [`Mmap`].
+use std::fmt::{Debug, Formatter};
+use std::io::{Error, ErrorKind};
+use std::ops::{Deref, Range};
+use std::os::fd::RawFd;
+use std::rc::Rc;
+use crate::buf::Buffer;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::local_scheduler;
+
+/// Mapped pages. They are unmapped, when the last [`Mmap`] or view [`Buffer`] of them is dropped.
+struct MmapRegion {
+    ptr: *mut u8,
+    len: usize
+}
+
+impl Drop for MmapRegion {
+    fn drop(&mut self) {
+        // The last view can be dropped by the selector, while it handles completions, so unmapping is scheduled.
+        local_scheduler().sched(unmap(self.ptr, self.len));
+    }
+}
+
+fn unmap(ptr: *mut u8, len: usize) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        // Let the selector finish the current batch of completions first.
+        yield YieldStatus::yield_now();
+        unsafe { libc::munmap(ptr.cast(), len) };
+    })
+}
+
+/// A read-only memory-mapped range of a file. It is created by [`File::mmap`](crate::fs::File::mmap).
+///
+/// The range is available as `&[u8]` and as view [`Buffer`]s, that can be written to a [`TcpStream`](crate::net::TcpStream)
+/// without copying into a pool buffer. So, large static assets are served from the page cache directly.
+///
+/// The pages are unmapped, when the [`Mmap`] and all its views are dropped, so a write in progress is never broken.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::{File, Mmap};
+/// use engine::io::AsyncWrite;
+/// use engine::net::TcpStream;
+///
+/// #[coro]
+/// fn send_asset(mut stream: TcpStream, file: File, len: u64) {
+///     let mmap: Mmap = file.mmap(0..len).unwrap();
+///     let res: Result<(), Error> = yield stream.write_all(mmap.buffer(0..mmap.len()));
+/// }
+/// ```
+pub struct Mmap {
+    region: Rc<MmapRegion>,
+    ptr: *const u8,
+    len: usize
+}
+
+impl Mmap {
+    /// Maps `range` of the file at `fd`. The file must be opened for reading.
+    pub(crate) fn map(fd: RawFd, range: Range<u64>) -> Result<Self, Error> {
+        if range.start >= range.end {
+            return Err(Error::new(ErrorKind::InvalidInput, "the range to map is empty"));
+        }
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        // The offset of mmap must be aligned to the page size.
+        let aligned_start = range.start - range.start % page_size;
+        let skip = (range.start - aligned_start) as usize;
+        let len = (range.end - range.start) as usize;
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), skip + len, libc::PROT_READ, libc::MAP_SHARED, fd, aligned_start as libc::off_t)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+
+        let region = Rc::new(MmapRegion { ptr: ptr.cast(), len: skip + len });
+        Ok(Self {
+            ptr: unsafe { region.ptr.add(skip) },
+            region,
+            len
+        })
+    }
+
+    /// Returns the length of the mapped range.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true, if the mapped range is empty.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a read-only view [`Buffer`] of `range` of the mapped bytes. The bytes are not copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of the mapped range.
+    pub fn buffer(&self, range: Range<usize>) -> Buffer {
+        assert!(range.start <= range.end && range.end <= self.len, "the range is out of the mapped range");
+        unsafe { Buffer::new_view(self.ptr.add(range.start), range.end - range.start, self.region.clone()) }
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Debug for Mmap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mmap")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .field("views", &(Rc::strong_count(&self.region) - 1))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read, SeekFrom};
+    use std::net::SocketAddr;
+    use crate::{test_local, wait};
+    use crate::buf::Buffer;
+    use crate::cfg::config_write_turn_cap;
+    use crate::fs::{tempfile, File, Mmap};
+    use crate::io::{AsyncRead, AsyncWrite};
+    use crate::net::TcpStream;
+
+    #[test_local(crate="crate")]
+    fn test_mmap() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_mmap_{}", std::process::id()));
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let file: File = (yield File::open(path.clone())).unwrap();
+        let mmap: Mmap = file.mmap(5_000..9_000).unwrap();
+        assert_eq!(mmap.len(), 4_000);
+        assert_eq!(&mmap[..], &content[5_000..9_000]);
+        assert!(file.mmap(10..10).is_err());
+
+        let view = mmap.buffer(100..200);
+        assert!(view.is_view());
+        assert_eq!(view.as_ref(), &content[5_100..5_200]);
+        drop(mmap);
+
+        let res: Result<File, Error> = wait!(tempfile());
+        let mut copy = res.unwrap();
+        let res: Result<(), Error> = yield copy.write_all(view);
+        res.unwrap();
+        copy.seek(SeekFrom::Start(0)).unwrap();
+        let read: Buffer = (yield copy.read()).unwrap();
+        assert_eq!(read.as_ref(), &content[5_100..5_200]);
+
+        let mut grown = file.mmap(0..10).unwrap().buffer(0..10);
+        grown.append(b"tail");
+        assert!(!grown.is_view());
+        assert_eq!(&grown.as_ref()[10..], b"tail");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_mmap_to_socket() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_mmap_to_socket_{}", std::process::id()));
+        // The view is sent in several turns, so the kernel reads the mapping after the Mmap is dropped.
+        let len = config_write_turn_cap() * 3 + 17;
+        let content: Vec<u8> = (0..len).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = vec![0; len];
+            stream.read_exact(&mut received).unwrap();
+            received
+        });
+
+        let file: File = (yield File::open(path.clone())).unwrap();
+        let mmap: Mmap = file.mmap(0..len as u64).unwrap();
+        let view = mmap.buffer(0..len);
+        drop(mmap);
+
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let res: Result<(), Error> = yield stream.write_all(view);
+        res.unwrap();
+
+        assert!(peer.join().unwrap() == content);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+//! This module contains [`File`], [`OpenOptions`] and functions for working with the filesystem.
+//! Read [`File`] for more information.
+pub mod advice;
+pub mod buf_reader;
+pub mod buf_writer;
+pub mod copy;
+pub mod dir;
+pub mod dir_builder;
+pub mod file;
+pub mod link;
+pub mod mmap;
+pub mod open_options;
+pub mod permissions;
+pub mod read_dir;
+pub mod read_write;
+pub mod remove;
+pub mod rename;
+pub mod replace;
+pub mod temp;
+#[cfg(target_os = "linux")]
+pub mod watch;
+
+pub use advice::Advice;
+pub use buf_reader::{BufReader, Lines};
+pub use buf_writer::BufWriter;
+pub use copy::copy;
+pub use dir::{create_dir, create_dir_all};
+pub use dir_builder::DirBuilder;
+pub use file::File;
+pub use link::{hard_link, read_link, symlink};
+pub use mmap::Mmap;
+pub use open_options::{OpenOptions, DIRECT_IO_ALIGN};
+pub use permissions::set_permissions;
+pub use read_dir::{read_dir, ReadDir};
+pub use read_write::{read, write};
+pub use remove::{remove_dir, remove_dir_all, remove_file};
+pub use rename::rename;
+pub use replace::replace_file;
+pub use temp::{tempfile, NamedTempFile};
+#[cfg(target_os = "linux")]
+pub use watch::{watch, WatchEvent, WatchEventKind, WatchStream};
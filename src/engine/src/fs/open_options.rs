@@ -0,0 +1,305 @@
+//! This module contains [`OpenOptions`].
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crate::coroutine::YieldStatus;
+use crate::fs::File;
+use crate::utils::path_to_c_string;
+
+/// The alignment of buffers, that are allocated for reads of files opened with [`OpenOptions::direct`].
+/// It is enough for the logical block size of all common filesystems and devices.
+pub const DIRECT_IO_ALIGN: usize = 4096;
+
+/// Returns true, if `flags` contain `O_DIRECT`.
+#[cfg(target_os = "linux")]
+#[inline(always)]
+pub(crate) fn is_direct(flags: i32) -> bool {
+    flags & libc::O_DIRECT != 0
+}
+
+/// Returns false, because `O_DIRECT` is Linux-only.
+#[cfg(not(target_os = "linux"))]
+#[inline(always)]
+pub(crate) fn is_direct(_flags: i32) -> bool {
+    false
+}
+
+/// Options and flags which can be used to configure how a [`File`] is opened.
+///
+/// It mirrors [`std::fs::OpenOptions`] (with [`OpenOptionsExt::mode`](std::os::unix::fs::OpenOptionsExt::mode)),
+/// but [`OpenOptions::open`] returns [`YieldStatus`].
+///
+/// # Append
+///
+/// In append mode every write goes to the end of the file, whatever the cursor of [`File`] is.
+/// The cursor is still moved by the number of bytes written.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::{File, OpenOptions};
+///
+/// #[coro]
+/// fn open_log() {
+///     let file: Result<File, Error> = yield OpenOptions::new().write(true).create(true).open("app.log");
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    direct: bool,
+    mode: u32
+}
+
+impl OpenOptions {
+    /// Creates a blank new set of options. All options are initially set to `false`, and the mode is `0o666`.
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            direct: false,
+            mode: 0o666
+        }
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for the append mode. It implies write access.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option for truncating a previous file. It requires write access.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option to create a new file if it does not exist. It requires write or append access.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option to create a new file, failing with [`ErrorKind::AlreadyExists`] if it already exists.
+    /// It requires write or append access.
+    ///
+    /// If it is set, [`OpenOptions::create`] and [`OpenOptions::truncate`] are ignored.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Sets the option for direct IO (`O_DIRECT`), that bypasses the page cache.
+    ///
+    /// Direct IO requires the memory, the offset and the length to be aligned to the logical block size
+    /// of the filesystem. [`File::read`](crate::io::AsyncRead::read) of a direct file reads into
+    /// a [`Buffer::new_aligned`](crate::buf::Buffer::new_aligned) buffer aligned to [`DIRECT_IO_ALIGN`],
+    /// but the cursor (or the offset) and written buffers must be aligned by the caller.
+    ///
+    /// `O_DIRECT` is Linux-only, so off Linux opening a direct file fails with [`ErrorKind::Unsupported`].
+    pub fn direct(&mut self, direct: bool) -> &mut Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Sets the unix mode for a newly created file. It is masked by the umask of the process.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns options, that produce `flags`. It is the reverse of [`OpenOptions::flags`].
+    pub(crate) fn from_flags(flags: i32, mode: u32) -> Self {
+        let access = flags & libc::O_ACCMODE;
+        let append = flags & libc::O_APPEND != 0;
+        let create_new = flags & (libc::O_CREAT | libc::O_EXCL) == libc::O_CREAT | libc::O_EXCL;
+        Self {
+            read: access == libc::O_RDONLY || access == libc::O_RDWR,
+            write: !append && (access == libc::O_WRONLY || access == libc::O_RDWR),
+            append,
+            truncate: !create_new && flags & libc::O_TRUNC != 0,
+            create: !create_new && flags & libc::O_CREAT != 0,
+            create_new,
+            direct: is_direct(flags),
+            mode
+        }
+    }
+
+    /// Returns true, if the file is opened for reading.
+    #[inline(always)]
+    pub fn is_read(&self) -> bool {
+        self.read
+    }
+
+    /// Returns true, if the file is opened for writing (including the append mode).
+    #[inline(always)]
+    pub fn is_write(&self) -> bool {
+        self.write || self.append
+    }
+
+    /// Returns true, if the file is opened in the append mode.
+    #[inline(always)]
+    pub fn is_append(&self) -> bool {
+        self.append
+    }
+
+    /// Returns the unix mode for a newly created file.
+    #[inline(always)]
+    pub fn get_mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Returns flags for `openat`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidInput`] for invalid combinations of options, like [`std::fs::OpenOptions`] does.
+    pub(crate) fn flags(&self) -> Result<i32, Error> {
+        let mut flags = libc::O_CLOEXEC;
+        flags |= match (self.read, self.write, self.append) {
+            (true, false, false) => libc::O_RDONLY,
+            (false, true, false) => libc::O_WRONLY,
+            (true, true, false) => libc::O_RDWR,
+            (false, _, true) => libc::O_WRONLY | libc::O_APPEND,
+            (true, _, true) => libc::O_RDWR | libc::O_APPEND,
+            (false, false, false) => return Err(Error::new(ErrorKind::InvalidInput, "no access mode is set"))
+        };
+
+        if !self.write && !self.append && (self.truncate || self.create || self.create_new) {
+            return Err(Error::new(ErrorKind::InvalidInput, "creating or truncating a file requires write or append access"));
+        }
+        if self.truncate && self.append && !self.create_new {
+            return Err(Error::new(ErrorKind::InvalidInput, "a file can't be truncated in the append mode"));
+        }
+
+        flags |= match (self.create, self.truncate, self.create_new) {
+            (_, _, true) => libc::O_CREAT | libc::O_EXCL,
+            (true, true, false) => libc::O_CREAT | libc::O_TRUNC,
+            (true, false, false) => libc::O_CREAT,
+            (false, true, false) => libc::O_TRUNC,
+            (false, false, false) => 0
+        };
+
+        if self.direct {
+            #[cfg(target_os = "linux")]
+            {
+                flags |= libc::O_DIRECT;
+            }
+            #[cfg(not(target_os = "linux"))]
+            return Err(Error::new(ErrorKind::Unsupported, "direct IO is supported only on Linux"));
+        }
+
+        Ok(flags)
+    }
+
+    /// Opens a file at `path` with the options specified by `self`.
+    ///
+    /// If the path or the options are invalid, the error is written at once, and the coroutine is only yielded.
+    pub fn open<P: AsRef<Path>>(&self, path: P, res: *mut Result<File, Error>) -> YieldStatus {
+        let flags = match self.flags() {
+            Ok(flags) => flags,
+            Err(err) => return YieldStatus::ready(res, Err(err))
+        };
+
+        match path_to_c_string(path) {
+            Ok(path) => YieldStatus::open_file(path, flags, self.mode, res),
+            Err(err) => YieldStatus::ready(res, Err(err))
+        }
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::fs::PermissionsExt;
+    use crate::test_local;
+    use crate::buf::{buffer, Buffer};
+    use crate::fs::{File, OpenOptions, DIRECT_IO_ALIGN};
+    use crate::io::{AsyncRead, AsyncWrite};
+
+    #[test_local(crate="crate")]
+    fn test_open_options() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_open_options_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true).mode(0o600);
+        let file: File = (yield options.open(path.clone())).unwrap();
+        drop(file);
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let res: Result<File, Error> = yield options.open(path.clone());
+        assert_eq!(res.err().unwrap().kind(), ErrorKind::AlreadyExists);
+
+        std::fs::write(&path, b"head").unwrap();
+        let mut file: File = (yield OpenOptions::new().append(true).open(path.clone())).unwrap();
+        let mut buf = buffer();
+        buf.append(b", tail");
+        let res: Result<(), Error> = yield file.write_all(buf);
+        res.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"head, tail");
+
+        let res: Result<File, Error> = yield OpenOptions::new().read(true).create(true).open(path.clone());
+        assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidInput);
+        let res: Result<File, Error> = yield OpenOptions::new().append(true).truncate(true).open(path.clone());
+        assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidInput);
+        let res: Result<File, Error> = yield OpenOptions::new().open(path.clone());
+        assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidInput);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_direct() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_direct_{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true).direct(true);
+        let res: Result<File, Error> = yield options.open(path.clone());
+        let mut file = match res {
+            Ok(file) => file,
+            // Some filesystems (like tmpfs on old kernels) don't support direct IO.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => return,
+            Err(err) => panic!("{}", err)
+        };
+
+        let mut buf = Buffer::new_aligned(DIRECT_IO_ALIGN, DIRECT_IO_ALIGN);
+        buf.append(&[7; DIRECT_IO_ALIGN]);
+        let res: Result<(), Error> = yield file.write_all(buf);
+        res.unwrap();
+
+        file.seek(std::io::SeekFrom::Start(0)).unwrap();
+        let buf: Buffer = (yield file.read()).unwrap();
+        assert_eq!(buf.as_ptr() as usize % DIRECT_IO_ALIGN, 0);
+        assert_eq!(buf.as_ref(), &[7; DIRECT_IO_ALIGN]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
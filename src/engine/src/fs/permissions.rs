@@ -0,0 +1,58 @@
+//! This module contains [`set_permissions`].
+use std::io::Error;
+use std::path::Path;
+use crate::coroutine::YieldStatus;
+use crate::utils::path_to_c_string;
+
+/// Changes the permissions of the file or the directory. `mode` is the unix mode, like `0o644`.
+///
+/// Use [`File::set_permissions`](crate::fs::File::set_permissions) for an open file.
+///
+/// If the path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::set_permissions;
+///
+/// #[coro]
+/// fn make_executable() {
+///     let res: Result<(), Error> = yield set_permissions("run.sh", 0o755);
+/// }
+/// ```
+pub fn set_permissions<P: AsRef<Path>>(path: P, mode: u32, res: *mut Result<(), Error>) -> YieldStatus {
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::set_permissions(path, mode, res),
+        Err(err) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::os::unix::fs::PermissionsExt;
+    use crate::test_local;
+    use crate::fs::{set_permissions, File};
+
+    #[test_local(crate="crate")]
+    fn test_set_permissions() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_permissions_{}", std::process::id()));
+        std::fs::write(&path, b"data").unwrap();
+
+        let res: Result<(), Error> = yield set_permissions(path.clone(), 0o600);
+        res.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let mut file: File = (yield File::open(path.clone())).unwrap();
+        let res: Result<(), Error> = yield file.set_permissions(0o640);
+        res.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+
+        let res: Result<(), Error> = yield set_permissions(path.join("missing"), 0o600);
+        assert!(res.is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! This module contains [`read_dir`] and [`ReadDir`].
+use std::collections::VecDeque;
+use std::fs::DirEntry;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use crate::coro;
+use crate::blocking::blocking;
+use crate::coroutine::CoroutineImpl;
+use crate::stream::CoStream;
+
+/// How many entries are read by one job of the blocking pool.
+const READ_DIR_BATCH: usize = 64;
+
+/// Returns the [`CoStream`] over the entries of the directory. The entries `.` and `..` are skipped.
+///
+/// Neither io_uring nor epoll can read directories, so the entries are read in batches on the blocking pool,
+/// and the worker is not stalled by large directories. The directory is opened by the first [`CoStream::next`],
+/// so an error of opening is the first item.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::fs::DirEntry;
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::read_dir;
+/// use engine::stream::CoStream;
+///
+/// #[coro]
+/// fn list_logs() {
+///     let mut entries = read_dir("logs");
+///     loop {
+///         let entry: Option<Result<DirEntry, Error>> = wait!(entries.next());
+///         match entry {
+///             Some(entry) => println!("{:?}", entry.unwrap().path()),
+///             None => break
+///         }
+///     }
+/// }
+/// ```
+pub fn read_dir<P: AsRef<Path>>(path: P) -> ReadDir {
+    ReadDir {
+        path: path.as_ref().to_path_buf(),
+        inner: None,
+        entries: VecDeque::new(),
+        is_ended: false
+    }
+}
+
+/// The [`CoStream`] over the entries of a directory. It is created by [`read_dir`].
+pub struct ReadDir {
+    path: PathBuf,
+    /// The opened directory. It is moved to the blocking pool, while a batch is read.
+    inner: Option<std::fs::ReadDir>,
+    /// The read entries, that are not returned yet.
+    entries: VecDeque<Result<DirEntry, Error>>,
+    is_ended: bool
+}
+
+impl ReadDir {
+    /// Returns the path of the directory.
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CoStream<Result<DirEntry, Error>> for ReadDir {
+    fn next(&mut self, res: *mut Option<Result<DirEntry, Error>>) -> CoroutineImpl {
+        next_entry(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_entry(read_dir: *mut ReadDir) -> Option<Result<DirEntry, Error>> {
+    let read_dir = unsafe { &mut *read_dir };
+    if read_dir.entries.is_empty() && !read_dir.is_ended {
+        let path = read_dir.path.clone();
+        let inner = read_dir.inner.take();
+        let res: Result<Batch, Error> = yield blocking(move || read_batch(&path, inner));
+        match res {
+            Ok((inner, entries)) => {
+                read_dir.is_ended = inner.is_none();
+                read_dir.inner = inner;
+                read_dir.entries.extend(entries);
+            }
+            Err(err) => {
+                read_dir.is_ended = true;
+                read_dir.entries.push_back(Err(err));
+            }
+        };
+    }
+
+    return read_dir.entries.pop_front();
+}
+
+/// The directory, that is not over yet, and the read entries.
+type Batch = (Option<std::fs::ReadDir>, Vec<Result<DirEntry, Error>>);
+
+/// Opens the directory, if it is not opened yet, and reads the next batch of entries.
+/// Returns [`None`] instead of the directory, if it is over.
+fn read_batch(path: &Path, inner: Option<std::fs::ReadDir>) -> Batch {
+    let mut inner = match inner {
+        Some(inner) => inner,
+        None => match std::fs::read_dir(path) {
+            Ok(inner) => inner,
+            Err(err) => return (None, vec![Err(err)])
+        }
+    };
+
+    let entries: Vec<Result<DirEntry, Error>> = inner.by_ref().take(READ_DIR_BATCH).collect();
+    if entries.len() < READ_DIR_BATCH {
+        return (None, entries);
+    }
+    (Some(inner), entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::DirEntry;
+    use std::io::{Error, ErrorKind};
+    use crate::{test_local, wait};
+    use crate::fs::read_dir;
+    use crate::stream::CoStream;
+
+    #[test_local(crate="crate")]
+    fn test_read_dir() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_read_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // More than one batch.
+        for i in 0..100 {
+            std::fs::write(dir.join(format!("file_{}", i)), b"").unwrap();
+        }
+
+        let mut entries = read_dir(&dir);
+        let mut names = Vec::new();
+        loop {
+            let entry: Option<Result<DirEntry, Error>> = wait!(entries.next());
+            match entry {
+                Some(entry) => names.push(entry.unwrap().file_name().into_string().unwrap()),
+                None => break
+            }
+        }
+        names.sort_by_key(|name| name["file_".len()..].parse::<usize>().unwrap());
+        assert_eq!(names, (0..100).map(|i| format!("file_{}", i)).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut missing = read_dir(&dir);
+        let entry: Option<Result<DirEntry, Error>> = wait!(missing.next());
+        assert_eq!(entry.unwrap().unwrap_err().kind(), ErrorKind::NotFound);
+        let entry: Option<Result<DirEntry, Error>> = wait!(missing.next());
+        assert!(entry.is_none());
+    }
+}
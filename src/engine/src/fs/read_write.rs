@@ -0,0 +1,103 @@
+//! This module contains [`read`] and [`write`].
+use std::io::Error;
+use std::path::Path;
+use crate::buf::Buffer;
+use crate::coro;
+use crate::coroutine::CoroutineImpl;
+use crate::fs::File;
+use crate::io::AsyncWrite;
+
+/// Reads the entire contents of a file into a [`Buffer`].
+///
+/// It opens the file, reads it with [`File::read_to_end`] and closes it.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::buf::Buffer;
+/// use engine::fs;
+///
+/// #[coro]
+/// fn load_config() {
+///     let config: Result<Buffer, Error> = wait!(fs::read("config.toml"));
+/// }
+/// ```
+pub fn read<P: AsRef<Path> + 'static>(path: P, res: *mut Result<Buffer, Error>) -> CoroutineImpl {
+    read_file(path, res)
+}
+
+#[coro(crate="crate")]
+fn read_file<P: AsRef<Path> + 'static>(path: P) -> Result<Buffer, Error> {
+    let res: Result<File, Error> = yield File::open(path);
+    let mut file = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<Buffer, Error> = yield file.read_to_end();
+    return res;
+}
+
+/// Writes the [`Buffer`] as the entire contents of a file.
+///
+/// It creates the file if it does not exist, and truncates it if it does.
+/// It opens the file, writes the whole buffer with [`AsyncWrite::write_all`] and closes it.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::buf::buffer;
+/// use engine::fs;
+///
+/// #[coro]
+/// fn save_config() {
+///     let mut buf = buffer();
+///     buf.append(b"workers = 4");
+///     let res: Result<(), Error> = wait!(fs::write("config.toml", buf));
+/// }
+/// ```
+pub fn write<P: AsRef<Path> + 'static>(path: P, data: Buffer, res: *mut Result<(), Error>) -> CoroutineImpl {
+    write_file(path, data, res)
+}
+
+#[coro(crate="crate")]
+fn write_file<P: AsRef<Path> + 'static>(path: P, data: Buffer) -> Result<(), Error> {
+    let res: Result<File, Error> = yield File::create(path);
+    let mut file = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<(), Error> = yield file.write_all(data);
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::buf::{buffer, Buffer};
+    use crate::fs;
+
+    #[test_local(crate="crate")]
+    fn test_write_and_read() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_read_write_{}", std::process::id()));
+        let mut buf = buffer();
+        buf.append(b"one-shot");
+
+        let res: Result<(), Error> = wait!(fs::write(path.clone(), buf));
+        res.unwrap();
+        let res: Result<Buffer, Error> = wait!(fs::read(path.clone()));
+        assert_eq!(res.unwrap().as_ref(), b"one-shot");
+
+        std::fs::remove_file(&path).unwrap();
+        let res: Result<Buffer, Error> = wait!(fs::read(path.clone()));
+        assert!(res.is_err());
+    }
+}
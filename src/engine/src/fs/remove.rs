@@ -0,0 +1,197 @@
+//! This module contains [`remove_file`], [`remove_dir`] and [`remove_dir_all`].
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::utils::{normalize_path, path_to_c_string};
+
+/// Removes a file from the filesystem.
+///
+/// It does not remove directories, use [`remove_dir`] for them.
+///
+/// If the path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::remove_file;
+///
+/// #[coro]
+/// fn remove_log() {
+///     let res: Result<(), Error> = yield remove_file("app.log");
+/// }
+/// ```
+pub fn remove_file<P: AsRef<Path>>(path: P, res: *mut Result<(), Error>) -> YieldStatus {
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::remove_file(path, res),
+        Err(err) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+/// Removes an empty directory from the filesystem.
+///
+/// It does not remove files, use [`remove_file`] for them.
+///
+/// If the path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::remove_dir;
+///
+/// #[coro]
+/// fn remove_cache_dir() {
+///     let res: Result<(), Error> = yield remove_dir("cache");
+/// }
+/// ```
+pub fn remove_dir<P: AsRef<Path>>(path: P, res: *mut Result<(), Error>) -> YieldStatus {
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::remove_dir(path, res),
+        Err(err) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+/// Removes a directory with all its contents.
+///
+/// The tree is walked from the path, files and symbolic links are removed with [`remove_file`],
+/// and directories are removed with [`remove_dir`] after their contents. Symbolic links are not followed.
+/// If the path is a symbolic link, only the link is removed.
+/// Entries, that were removed concurrently, are not an error.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Note
+///
+/// Directories are listed with blocking syscalls, because neither io_uring nor epoll can list them.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs;
+///
+/// #[coro]
+/// fn clear_cache() {
+///     let res: Result<(), Error> = wait!(fs::remove_dir_all("cache"));
+/// }
+/// ```
+pub fn remove_dir_all<P: AsRef<Path> + 'static>(path: P, res: *mut Result<(), Error>) -> CoroutineImpl {
+    remove_tree(path, res)
+}
+
+#[coro(crate="crate")]
+fn remove_tree<P: AsRef<Path> + 'static>(path: P) -> Result<(), Error> {
+    let path = normalize_path(path);
+    match std::fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.is_symlink() => {
+            let res: Result<(), Error> = yield remove_file(path);
+            return res;
+        }
+        Ok(_) => {}
+        Err(err) => return Err(err)
+    };
+
+    // Every directory is pushed twice: to list it and to remove it after its contents.
+    let mut stack: Vec<(PathBuf, bool)> = vec![(path, false)];
+    while let Some((dir, is_listed)) = stack.pop() {
+        if is_listed {
+            let res: Result<(), Error> = yield remove_dir(dir);
+            match res {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err)
+            };
+            continue;
+        }
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err)
+        };
+        stack.push((dir, true));
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => return Err(err)
+            };
+            let is_dir = match entry.file_type() {
+                Ok(file_type) => file_type.is_dir(),
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err)
+            };
+
+            if is_dir {
+                stack.push((entry.path(), false));
+                continue;
+            }
+
+            let res: Result<(), Error> = yield remove_file(entry.path());
+            match res {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err)
+            };
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::fs::{remove_dir, remove_dir_all, remove_file};
+
+    #[test_local(crate="crate")]
+    fn test_remove_file_and_dir() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_remove_{}", std::process::id()));
+        let file = dir.join("file");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(&file, b"data").unwrap();
+
+        let res: Result<(), Error> = yield remove_dir(dir.clone());
+        assert!(res.is_err(), "a non-empty directory was removed");
+        let res: Result<(), Error> = yield remove_file(dir.clone());
+        assert!(res.is_err(), "a directory was removed as a file");
+
+        let res: Result<(), Error> = yield remove_file(file.clone());
+        res.unwrap();
+        assert!(!file.exists());
+        let res: Result<(), Error> = yield remove_file(file.clone());
+        assert!(res.is_err(), "a missing file was removed");
+
+        let res: Result<(), Error> = yield remove_dir(dir.clone());
+        res.unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_remove_dir_all() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_remove_dir_all_{}", std::process::id()));
+        let outside = std::env::temp_dir().join(format!("coroeng_test_remove_dir_all_outside_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::create_dir_all(dir.join("c")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(dir.join("file"), b"data").unwrap();
+        std::fs::write(dir.join("a/b/file"), b"data").unwrap();
+        std::fs::write(outside.join("file"), b"data").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("a/link")).unwrap();
+
+        let res: Result<(), Error> = wait!(remove_dir_all(dir.clone()));
+        res.unwrap();
+        assert!(!dir.exists());
+        assert!(outside.join("file").exists(), "a symbolic link was followed");
+
+        let res: Result<(), Error> = wait!(remove_dir_all(dir.clone()));
+        assert!(res.is_err(), "a missing directory was removed");
+
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+}
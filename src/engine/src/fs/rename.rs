@@ -0,0 +1,55 @@
+//! This module contains [`rename`].
+use std::io::Error;
+use std::path::Path;
+use crate::coroutine::YieldStatus;
+use crate::utils::path_to_c_string;
+
+/// Renames a file or a directory. If a file exists at `to`, it is replaced atomically:
+/// other processes see either the old file or the new one.
+///
+/// Both paths must be on the same filesystem.
+///
+/// If a path is invalid, the error is written at once, and the coroutine is only yielded.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::fs::rename;
+///
+/// #[coro]
+/// fn rotate_log() {
+///     let res: Result<(), Error> = yield rename("app.log", "app.log.1");
+/// }
+/// ```
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, res: *mut Result<(), Error>) -> YieldStatus {
+    match (path_to_c_string(from), path_to_c_string(to)) {
+        (Ok(from), Ok(to)) => YieldStatus::rename(from, to, res),
+        (Err(err), _) | (_, Err(err)) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::test_local;
+    use crate::fs::rename;
+
+    #[test_local(crate="crate")]
+    fn test_rename() {
+        let from = std::env::temp_dir().join(format!("coroeng_test_rename_from_{}", std::process::id()));
+        let to = std::env::temp_dir().join(format!("coroeng_test_rename_to_{}", std::process::id()));
+        std::fs::write(&from, b"new").unwrap();
+        std::fs::write(&to, b"old").unwrap();
+
+        let res: Result<(), Error> = yield rename(from.clone(), to.clone());
+        res.unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read(&to).unwrap(), b"new");
+
+        let res: Result<(), Error> = yield rename(from.clone(), to.clone());
+        assert!(res.is_err(), "a missing file was renamed");
+        std::fs::remove_file(&to).unwrap();
+    }
+}
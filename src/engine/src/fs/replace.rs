@@ -0,0 +1,167 @@
+//! This module contains [`replace_file`].
+use std::io::{Error, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use crate::buf::Buffer;
+use crate::{coro, wait};
+use crate::coroutine::CoroutineImpl;
+use crate::fs::{remove_file, rename, File, OpenOptions};
+use crate::fs::temp::temp_path_next_to;
+use crate::io::AsyncWrite;
+use crate::utils::normalize_path;
+
+/// Replaces the contents of the file at `path` with `data` durably and atomically.
+///
+/// Readers see either the old contents or the new ones, never a partially written file,
+/// and after a successful return the new contents survive a crash of the machine. It makes the whole sequence:
+///
+/// 1. creates a temporary file next to `path` (on the same filesystem);
+///
+/// 2. writes `data` to it and flushes it with [`File::sync_all`];
+///
+/// 3. renames it to `path` with [`rename`];
+///
+/// 4. flushes the directory, so the rename itself is durable.
+///
+/// The new file gets the permissions of the replaced file. If `path` does not exist, it is created
+/// with `0o666` (modified by the umask), like [`File::create`] does. If `path` is a symbolic link,
+/// the link itself is replaced, not its target.
+///
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Errors
+///
+/// - If an error occurs before the rename, the file at `path` is untouched, and the temporary file is removed.
+///
+/// - If flushing the directory fails, the new contents are already at `path`, but the rename can be lost after a crash.
+///   The error is returned, so the caller can retry the whole replace.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::buf::buffer;
+/// use engine::fs;
+///
+/// #[coro]
+/// fn save_state() {
+///     let mut buf = buffer();
+///     buf.append(b"offset = 42");
+///     let res: Result<(), Error> = wait!(fs::replace_file("state.toml", buf));
+/// }
+/// ```
+pub fn replace_file<P: AsRef<Path> + 'static>(path: P, data: Buffer, res: *mut Result<(), Error>) -> CoroutineImpl {
+    replace(path, data, res)
+}
+
+#[coro(crate="crate")]
+fn replace<P: AsRef<Path> + 'static>(path: P, data: Buffer) -> Result<(), Error> {
+    let path = normalize_path(path);
+    if path.file_name().is_none() {
+        return Err(Error::new(ErrorKind::InvalidInput, "the path to replace has no file name"));
+    }
+    // A stat is cheap, and neither io_uring nor epoll can make it asynchronously.
+    let mode = std::fs::metadata(&path).ok().map(|metadata| metadata.permissions().mode() & 0o7777);
+
+    let tmp = temp_path_next_to(&path);
+    let res: Result<(), Error> = wait!(write_and_rename(tmp.clone(), path.clone(), data, mode));
+    if let Err(err) = res {
+        // The error of the removal is ignored, because the temporary file can be not created or already renamed.
+        let _: Result<(), Error> = yield remove_file(tmp);
+        return Err(err);
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from(".")
+    };
+    let res: Result<File, Error> = yield File::open(dir);
+    let mut dir = match res {
+        Ok(dir) => dir,
+        Err(err) => return Err(err)
+    };
+    let res: Result<(), Error> = yield dir.sync_all();
+    return res;
+}
+
+/// Writes `data` to the new temporary file, flushes it and renames it to `path`. It is the part of [`replace_file`],
+/// after which the temporary file must be removed on error.
+#[coro(crate="crate")]
+fn write_and_rename(tmp: PathBuf, path: PathBuf, data: Buffer, mode: Option<u32>) -> Result<(), Error> {
+    let res: Result<File, Error> = yield OpenOptions::new().write(true).create_new(true).open(&tmp);
+    let mut file = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<(), Error> = yield file.write_all(data);
+    if res.is_err() {
+        return res;
+    }
+    if let Some(mode) = mode {
+        let res: Result<(), Error> = yield file.set_permissions(mode);
+        if res.is_err() {
+            return res;
+        }
+    }
+    let res: Result<(), Error> = yield file.sync_all();
+    if res.is_err() {
+        return res;
+    }
+
+    let res: Result<(), Error> = yield rename(tmp, path);
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use crate::{test_local, wait};
+    use crate::buf::buffer;
+    use crate::fs::replace_file;
+
+    fn temp_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().contains("coroeng_tmp"))
+            .count()
+    }
+
+    #[test_local(crate="crate")]
+    fn test_replace_file() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_replace_{}", std::process::id()));
+        let path = dir.join("state");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(&path, b"old contents").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut buf = buffer();
+        buf.append(b"new");
+        let res: Result<(), Error> = wait!(replace_file(path.clone(), buf));
+        res.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(temp_files(&dir), 0);
+
+        let created = dir.join("created");
+        let mut buf = buffer();
+        buf.append(b"created");
+        let res: Result<(), Error> = wait!(replace_file(created.clone(), buf));
+        res.unwrap();
+        assert_eq!(std::fs::read(&created).unwrap(), b"created");
+
+        // A directory can't be replaced with a file, so the rename fails, and the temporary file must be removed.
+        std::fs::create_dir(dir.join("busy")).unwrap();
+        let res: Result<(), Error> = wait!(replace_file(dir.join("busy"), buffer()));
+        assert!(res.is_err(), "a directory was replaced with a file");
+        assert!(dir.join("busy").is_dir());
+        assert_eq!(temp_files(&dir), 0);
+
+        let res: Result<(), Error> = wait!(replace_file(dir.join("missing/state"), buffer()));
+        assert!(res.is_err(), "a file was created in a missing directory");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
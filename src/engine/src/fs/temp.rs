@@ -0,0 +1,210 @@
+//! This module contains [`tempfile`] and [`NamedTempFile`].
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::fs::{remove_file, File};
+use crate::local_scheduler;
+use crate::utils::path_to_c_string;
+
+/// The counter to make names of temporary files unique in the process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a new unique path in the temporary directory.
+fn temp_path() -> PathBuf {
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(".coroeng_tmp_{}_{}", std::process::id(), n))
+}
+
+/// Returns a new unique path in the directory of `path`, so the file at it can be renamed to `path` atomically.
+pub(crate) fn temp_path_next_to(path: &Path) -> PathBuf {
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.coroeng_tmp_{}_{}", name, std::process::id(), n))
+}
+
+/// Opens a new file in read-write mode with `O_CREAT | O_EXCL`, so an existing file is never reused.
+fn create_new(path: &Path, res: *mut Result<File, Error>) -> YieldStatus {
+    let flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC;
+    match path_to_c_string(path) {
+        Ok(path) => YieldStatus::open_file(path, flags, 0o600, res),
+        Err(err) => YieldStatus::ready(res, Err(err))
+    }
+}
+
+/// Creates a new anonymous temporary file in read-write mode.
+///
+/// The file has no name, so it is removed by the OS, when it is closed, even if the process crashes.
+/// It is created with `O_TMPFILE`. If the filesystem does not support it (or off Linux, where there is no `O_TMPFILE`),
+/// the file is created with a unique name and removed at once.
+/// It is a coroutine, so use it with [`wait!`](crate::wait).
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::{self, File};
+///
+/// #[coro]
+/// fn spill_to_disk() {
+///     let file: Result<File, Error> = wait!(fs::tempfile());
+/// }
+/// ```
+pub fn tempfile(res: *mut Result<File, Error>) -> CoroutineImpl {
+    open_tempfile(res)
+}
+
+#[coro(crate="crate")]
+fn open_tempfile() -> Result<File, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        let dir = match path_to_c_string(std::env::temp_dir()) {
+            Ok(dir) => dir,
+            Err(err) => return Err(err)
+        };
+        let res: Result<File, Error> = yield YieldStatus::open_file(dir, libc::O_TMPFILE | libc::O_RDWR | libc::O_CLOEXEC, 0o600);
+        if res.is_ok() {
+            return res;
+        }
+    }
+
+    let path = temp_path();
+    let res: Result<File, Error> = yield create_new(&path);
+    let file = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+
+    let res: Result<(), Error> = yield remove_file(path);
+    return match res {
+        Ok(()) => Ok(file),
+        Err(err) => Err(err)
+    };
+}
+
+/// A temporary file with a name in the temporary directory.
+///
+/// The file is removed, when [`NamedTempFile`] is dropped. Removing is scheduled through the local scheduler.
+/// Use [`tempfile`], if the name is not needed.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::NamedTempFile;
+///
+/// #[coro]
+/// fn with_config_file() {
+///     let tmp: NamedTempFile = wait!(NamedTempFile::new()).unwrap();
+///     println!("config is at {:?}", tmp.path());
+/// }
+/// ```
+pub struct NamedTempFile {
+    path: PathBuf,
+    file: File
+}
+
+#[coro(crate="crate")]
+fn create_named() -> Result<NamedTempFile, Error> {
+    let path = temp_path();
+    let res: Result<File, Error> = yield create_new(&path);
+    return match res {
+        Ok(file) => Ok(NamedTempFile { path, file }),
+        Err(err) => Err(err)
+    };
+}
+
+impl NamedTempFile {
+    /// Creates a new temporary file with a unique name in read-write mode.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait).
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(res: *mut Result<NamedTempFile, Error>) -> CoroutineImpl {
+        create_named(res)
+    }
+
+    /// Returns the path of the file.
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a reference to the open [`File`].
+    #[inline(always)]
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns a mutable reference to the open [`File`].
+    #[inline(always)]
+    pub fn as_file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+}
+
+impl Drop for NamedTempFile {
+    fn drop(&mut self) {
+        local_scheduler().sched(remove_temp_file(std::mem::take(&mut self.path)));
+    }
+}
+
+fn remove_temp_file(path: PathBuf) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let mut res = MaybeUninit::<Result<(), Error>>::uninit();
+        yield remove_file(path, res.as_mut_ptr());
+        unsafe { res.assume_init_drop(); }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, SeekFrom};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::{test_local, wait};
+    use crate::buf::{buffer, Buffer};
+    use crate::sleep::sleep;
+    use crate::fs::{tempfile, File, NamedTempFile};
+    use crate::io::{AsyncRead, AsyncWrite};
+
+    #[test_local(crate="crate")]
+    fn test_tempfile() {
+        let res: Result<File, Error> = wait!(tempfile());
+        let mut file = res.unwrap();
+
+        let mut buf = buffer();
+        buf.append(b"spilled");
+        let res: Result<(), Error> = yield file.write_all(buf);
+        res.unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let res: Result<Buffer, Error> = yield file.read();
+        assert_eq!(res.unwrap().as_ref(), b"spilled");
+    }
+
+    #[test_local(crate="crate")]
+    fn test_named_temp_file() {
+        let res: Result<NamedTempFile, Error> = wait!(NamedTempFile::new());
+        let mut tmp = res.unwrap();
+        let path: PathBuf = tmp.path().to_path_buf();
+        assert!(path.exists());
+
+        let mut buf = buffer();
+        buf.append(b"data");
+        let res: Result<(), Error> = yield tmp.as_file_mut().write_all(buf);
+        res.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+
+        drop(tmp);
+        // The removal is scheduled, so give it some time to complete.
+        for _ in 0..100 {
+            if !path.exists() {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,349 @@
+//! This module contains [`watch`] and [`WatchStream`].
+//!
+//! It is based on inotify, so it is Linux-only.
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::fmt::{Debug, Formatter};
+use std::io::{Error, ErrorKind};
+use std::mem::size_of;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local_scheduler;
+use crate::utils::{path_to_c_string, Ptr};
+
+/// Events, that [`WatchStream`] subscribes to.
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_MODIFY
+    | libc::IN_ATTRIB
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF;
+
+/// The length of the buffer for one read of the inotify fd. It fits at least one event with the longest name.
+const EVENTS_BUF_LEN: usize = 4096;
+
+/// The kind of [`WatchEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchEventKind {
+    /// A file or a directory was created in the watched directory.
+    Create,
+    /// The file was modified.
+    Modify,
+    /// Metadata (permissions, timestamps, owner, etc.) was changed.
+    Attrib,
+    /// The file, that was opened for writing, was closed.
+    CloseWrite,
+    /// A file or a directory was removed from the watched directory.
+    Remove,
+    /// A file or a directory was moved out of the watched directory.
+    MovedFrom,
+    /// A file or a directory was moved into the watched directory.
+    MovedTo,
+    /// The watched path itself was removed. The watch is removed too.
+    RemoveSelf,
+    /// The watched path itself was moved.
+    MoveSelf,
+    /// The kernel queue of events overflowed, so some events are lost.
+    Overflow
+}
+
+impl WatchEventKind {
+    /// Returns the kind of the inotify event mask, or [`None`] for events, that are not reported.
+    fn from_mask(mask: u32) -> Option<Self> {
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            Some(WatchEventKind::Overflow)
+        } else if mask & libc::IN_CREATE != 0 {
+            Some(WatchEventKind::Create)
+        } else if mask & libc::IN_MODIFY != 0 {
+            Some(WatchEventKind::Modify)
+        } else if mask & libc::IN_ATTRIB != 0 {
+            Some(WatchEventKind::Attrib)
+        } else if mask & libc::IN_CLOSE_WRITE != 0 {
+            Some(WatchEventKind::CloseWrite)
+        } else if mask & libc::IN_DELETE != 0 {
+            Some(WatchEventKind::Remove)
+        } else if mask & libc::IN_MOVED_FROM != 0 {
+            Some(WatchEventKind::MovedFrom)
+        } else if mask & libc::IN_MOVED_TO != 0 {
+            Some(WatchEventKind::MovedTo)
+        } else if mask & libc::IN_DELETE_SELF != 0 {
+            Some(WatchEventKind::RemoveSelf)
+        } else if mask & libc::IN_MOVE_SELF != 0 {
+            Some(WatchEventKind::MoveSelf)
+        } else {
+            None
+        }
+    }
+}
+
+/// A change of the filesystem, that is returned by [`WatchStream::next_event`].
+#[derive(Clone, Debug)]
+pub struct WatchEvent {
+    kind: WatchEventKind,
+    path: PathBuf,
+    mask: u32
+}
+
+impl WatchEvent {
+    /// Returns the kind of the event.
+    #[inline(always)]
+    pub fn kind(&self) -> WatchEventKind {
+        self.kind
+    }
+
+    /// Returns the path of the changed file. For events in a watched directory it is the path of the entry in it.
+    /// It is empty for [`WatchEventKind::Overflow`].
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true, if the changed entry is a directory.
+    #[inline(always)]
+    pub fn is_dir(&self) -> bool {
+        self.mask & libc::IN_ISDIR != 0
+    }
+
+    /// Returns the raw inotify mask of the event.
+    #[inline(always)]
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+}
+
+/// Starts watching the file or the directory at `path` for changes. Directories are not watched recursively.
+///
+/// Read [`WatchStream`] for more information.
+///
+/// # Errors
+///
+/// Returns an error if the path doesn't exist or the inotify limits of the user are reached.
+pub fn watch<P: AsRef<Path>>(path: P) -> Result<WatchStream, Error> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let mut stream = WatchStream {
+        fd,
+        state: Ptr::new(PollState::new_empty(fd)),
+        is_registered: false,
+        watches: HashMap::new(),
+        events: VecDeque::new()
+    };
+    stream.add(path)?;
+
+    Ok(stream)
+}
+
+/// A stream of changes of watched files and directories. It is created by [`watch`].
+///
+/// It is based on inotify. The inotify fd is registered with the selector,
+/// so [`WatchStream::next_event`] doesn't block the worker and doesn't need a dedicated thread.
+/// It allows hot-reloading configs and tailing logs.
+///
+/// The stream is closed, when it is dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::fs::{self, WatchEvent, WatchEventKind};
+///
+/// #[coro]
+/// fn reload_on_change() {
+///     let mut watch = fs::watch("config.toml").unwrap();
+///     loop {
+///         let event: Result<WatchEvent, Error> = wait!(watch.next_event());
+///         if event.unwrap().kind() == WatchEventKind::CloseWrite {
+///             // reload the config
+///         }
+///     }
+/// }
+/// ```
+pub struct WatchStream {
+    fd: RawFd,
+    state: Ptr<PollState>,
+    is_registered: bool,
+    watches: HashMap<i32, PathBuf>,
+    events: VecDeque<WatchEvent>
+}
+
+impl WatchStream {
+    /// Returns the inotify fd.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Starts watching one more path. Watching an already watched path does nothing.
+    pub fn add<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let c_path = path_to_c_string(path.as_ref())?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, c_path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        self.watches.insert(wd, path.as_ref().to_path_buf());
+        Ok(())
+    }
+
+    /// Stops watching the path.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::NotFound`], if the path is not watched.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let wd = self.watches.iter()
+            .find(|(_, watched)| watched.as_path() == path.as_ref())
+            .map(|(wd, _)| *wd)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "the path is not watched"))?;
+
+        self.watches.remove(&wd);
+        if unsafe { libc::inotify_rm_watch(self.fd, wd) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Returns the next change. If there are no changes, the coroutine waits until inotify reports them.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait).
+    pub fn next_event(&mut self, res: *mut Result<WatchEvent, Error>) -> CoroutineImpl {
+        next_watch_event(self, res)
+    }
+
+    /// Reads all available events from the inotify fd into the queue.
+    /// Returns false, if there are no events to read.
+    fn read_events(&mut self) -> Result<bool, Error> {
+        // u64 aligns the buffer enough for `inotify_event`.
+        let mut buf = [0u64; EVENTS_BUF_LEN / size_of::<u64>()];
+        let n = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), EVENTS_BUF_LEN) };
+        if n < 0 {
+            let err = Error::last_os_error();
+            if err.kind() == ErrorKind::WouldBlock {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), n as usize) };
+        let mut offset = 0;
+        while offset + size_of::<libc::inotify_event>() <= bytes.len() {
+            let event = unsafe { bytes.as_ptr().add(offset).cast::<libc::inotify_event>().read_unaligned() };
+            let name_start = offset + size_of::<libc::inotify_event>();
+            let name = &bytes[name_start..name_start + event.len as usize];
+            offset = name_start + event.len as usize;
+
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.watches.remove(&event.wd);
+                continue;
+            }
+            let Some(kind) = WatchEventKind::from_mask(event.mask) else { continue };
+
+            let mut path = self.watches.get(&event.wd).cloned().unwrap_or_default();
+            // The name is padded with nul bytes.
+            let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            if name_len > 0 {
+                path.push(OsStr::from_bytes(&name[..name_len]));
+            }
+            self.events.push_back(WatchEvent { kind, path, mask: event.mask });
+        }
+
+        Ok(true)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_watch_event(stream: *mut WatchStream) -> Result<WatchEvent, Error> {
+    let stream = unsafe { &mut *stream };
+    loop {
+        if let Some(event) = stream.events.pop_front() {
+            return Ok(event);
+        }
+
+        let has_events = match stream.read_events() {
+            Ok(has_events) => has_events,
+            Err(err) => return Err(err)
+        };
+        if has_events {
+            continue;
+        }
+
+        let res: Result<(), Error> = yield YieldStatus::wait_readable(stream.is_registered, stream.state);
+        stream.is_registered = true;
+        if let Err(err) = res {
+            return Err(err);
+        }
+    };
+}
+
+impl Debug for WatchStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchStream")
+            .field("fd", &self.fd)
+            .field("paths", &self.watches.values().collect::<Vec<_>>())
+            .field("queued_events", &self.events.len())
+            .finish()
+    }
+}
+
+fn close_watch(fd: RawFd, state_ref: Ptr<PollState>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        yield YieldStatus::file_close(fd, state_ref);
+        unsafe { state_ref.drop_in_place(); }
+    })
+}
+
+impl Drop for WatchStream {
+    fn drop(&mut self) {
+        local_scheduler().sched(close_watch(self.fd, self.state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use crate::{test_local, wait};
+    use crate::fs::{watch, WatchEvent, WatchEventKind};
+
+    #[test_local(crate="crate")]
+    fn test_watch() {
+        let dir = std::env::temp_dir().join(format!("coroeng_test_watch_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        let mut watch = watch(&dir).unwrap();
+
+        let path = dir.join("config");
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            std::fs::write(writer_path, b"reloaded").unwrap();
+        });
+
+        let res: Result<WatchEvent, Error> = wait!(watch.next_event());
+        let event = res.unwrap();
+        assert_eq!(event.kind(), WatchEventKind::Create);
+        assert_eq!(event.path(), path.as_path());
+        assert!(!event.is_dir());
+        writer.join().unwrap();
+
+        let mut kinds = Vec::new();
+        while !kinds.contains(&WatchEventKind::CloseWrite) {
+            let res: Result<WatchEvent, Error> = wait!(watch.next_event());
+            kinds.push(res.unwrap().kind());
+        }
+        assert!(kinds.contains(&WatchEventKind::Modify));
+
+        watch.remove(&dir).unwrap();
+        assert!(watch.remove(&dir).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,8 @@
-/// TODO r
-
+//! This module contains [`BlockingState`].
 use std::fmt::Debug;
 use std::io::Error;
 use std::net::SocketAddr;
+use std::os::fd::RawFd;
 use crate::coroutine::CoroutineImpl;
 use crate::net::TcpStream;
 
@@ -12,15 +12,42 @@ pub struct ConnectTcpState {
     pub(crate) result: *mut Result<TcpStream, Error>
 }
 
+pub struct LockFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) operation: i32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct RunClosureState {
+    pub(crate) job: Box<dyn FnOnce() + Send>,
+    pub(crate) coroutine: CoroutineImpl
+}
+
+/// The state of an operation, that can only be done with a blocking syscall.
+/// It is handled by the blocking pool of the scheduler.
 pub enum BlockingState {
-    ConnectTcp(Box<ConnectTcpState>)
+    ConnectTcp(Box<ConnectTcpState>),
+    LockFile(Box<LockFileState>),
+    RunClosure(Box<RunClosureState>)
 }
 
 impl BlockingState {
     #[inline(always)]
+    #[allow(dead_code)]
     pub(crate) fn new_connect_tcp(address: SocketAddr, coroutine: CoroutineImpl, result: *mut Result<TcpStream, Error>) -> Self {
         BlockingState::ConnectTcp(Box::new(ConnectTcpState { address, coroutine, result }))
     }
+
+    #[inline(always)]
+    pub(crate) fn new_lock_file(fd: RawFd, operation: i32, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        BlockingState::LockFile(Box::new(LockFileState { fd, operation, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub(crate) fn new_run_closure(job: Box<dyn FnOnce() + Send>, coroutine: CoroutineImpl) -> Self {
+        BlockingState::RunClosure(Box::new(RunClosureState { job, coroutine }))
+    }
 }
 
 impl Debug for BlockingState {
@@ -29,6 +56,12 @@ impl Debug for BlockingState {
             BlockingState::ConnectTcp(state) => {
                 write!(f, "ConnectTcp to addr {:?}", state.address)
             }
+            BlockingState::LockFile(state) => {
+                write!(f, "LockFile, fd: {:?}, operation: {:?}", state.fd, state.operation)
+            }
+            BlockingState::RunClosure(_) => {
+                write!(f, "RunClosure")
+            }
         }
     }
-}
\ No newline at end of file
+}
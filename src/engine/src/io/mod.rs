@@ -3,8 +3,18 @@ pub mod poll_state;
 pub mod selector;
 pub mod write;
 pub mod read;
+pub mod blocking_state;
+pub mod stdio;
+pub mod tty;
+pub mod state_trace;
 
 pub use poll_state::*;
 pub use selector::*;
 pub use write::*;
-pub use read::*;
\ No newline at end of file
+pub use read::*;
+pub use blocking_state::BlockingState;
+pub use stdio::{stderr, stdin, stdout, Stderr, Stdin, Stdout};
+pub use tty::{Tty, WindowSize};
+pub use state_trace::{clear_state_trace, disable_state_trace, enable_state_trace, is_state_trace_enabled, state_trace, StateEvent, StateEventKind, StateTrace};
+#[cfg(target_os = "linux")]
+pub use sys::unix::io_uring::{submission_stats, uring_capabilities, KernelVersion, RingSetup, SubmissionStats, UringCapabilities};
\ No newline at end of file
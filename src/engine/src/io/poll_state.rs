@@ -1,14 +1,22 @@
+use std::ffi::CString;
 use std::io::Error;
+use std::path::PathBuf;
 use std::fmt::{Debug, Formatter};
+use std::time::Duration;
 import_fd_for_os!();
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use crate::coroutine::coroutine::CoroutineImpl;
 use crate::net::tcp::TcpStream;
 use crate::buf::Buffer;
+use crate::fs::File;
 use crate::import_fd_for_os;
+use crate::utils::Ptr;
+use crate::write_err;
 
 pub struct EmptyState {
-    fd: RawFd
+    fd: RawFd,
+    /// The other side has closed the connection, so the last read has returned an empty slice.
+    is_eof: bool
 }
 
 pub struct AcceptTcpState {
@@ -20,28 +28,41 @@ pub struct AcceptTcpState {
 pub struct ConnectTcpState {
     pub(crate) address: SockAddr,
     pub(crate) socket: Socket,
+    /// The connection fails with [`TimedOut`](std::io::ErrorKind::TimedOut), if it is not established in time.
+    pub(crate) timeout: Option<Duration>,
     pub(crate) coroutine: CoroutineImpl,
     pub(crate) result: *mut Result<TcpStream, Error>
 }
 
+// Only the Linux selectors read all fields.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
 pub struct PollTcpState {
     pub(crate) fd: RawFd,
+    pub(crate) coalesce: usize,
     pub(crate) coroutine: CoroutineImpl,
     pub(crate) result: *mut Result<&'static [u8], Error>
 }
 
+// Only the Linux selectors read all fields.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
 pub struct ReadTcpState {
     pub(crate) fd: RawFd,
     pub(crate) buffer: Buffer,
+    pub(crate) coalesce: usize,
     pub(crate) coroutine: CoroutineImpl,
     pub(crate) result: *mut Result<&'static [u8], Error>
 }
 
+// Only the Linux selectors read all fields.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
 pub struct WriteTcpState {
     pub(crate) fd: RawFd,
     pub(crate) buffer: Buffer,
     pub(crate) coroutine: CoroutineImpl,
-    pub(crate) result: *mut Result<Option<Buffer>, Error>
+    pub(crate) result: *mut Result<Option<Buffer>, Error>,
+    pub(crate) zero_copy: bool,
+    /// The result of the zero-copy send, that waits for the notification, that the kernel has released the buffer.
+    pub(crate) sent: Option<i32>
 }
 
 pub struct WriteAllTcpState {
@@ -56,6 +77,149 @@ pub struct CloseTcpState {
     pub(crate) coroutine: CoroutineImpl
 }
 
+pub struct WaitReadableState {
+    pub(crate) fd: RawFd,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct OpenFileState {
+    pub(crate) path: CString,
+    pub(crate) flags: i32,
+    pub(crate) mode: u32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<File, Error>
+}
+
+pub struct ReadFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) buffer: Buffer,
+    pub(crate) offset: u64,
+    pub(crate) cursor: *mut u64,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<Buffer, Error>
+}
+
+pub struct ReadToEndFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) buffer: Buffer,
+    pub(crate) offset: u64,
+    pub(crate) cursor: *mut u64,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<Buffer, Error>
+}
+
+pub struct WriteFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) buffer: Buffer,
+    pub(crate) offset: u64,
+    pub(crate) cursor: *mut u64,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<Option<Buffer>, Error>
+}
+
+pub struct WriteAllFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) buffer: Buffer,
+    pub(crate) offset: u64,
+    pub(crate) cursor: *mut u64,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+/// The state of [`File::write_all_sync`]: the write and the sync are linked and submitted together.
+// Only the Linux selectors read all fields.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct WriteAllSyncFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) buffer: Buffer,
+    pub(crate) offset: u64,
+    pub(crate) cursor: *mut u64,
+    pub(crate) data_only: bool,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>,
+    /// The result of the write, if it has completed. The state is handled, when both the write and the sync have completed.
+    pub(crate) written: Option<i32>,
+    /// The result of the sync, if it has completed.
+    pub(crate) synced: Option<i32>
+}
+
+pub struct CopyFileState {
+    pub(crate) src_fd: RawFd,
+    pub(crate) dst_fd: RawFd,
+    pub(crate) copied: u64,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<u64, Error>
+}
+
+pub struct LinkState {
+    pub(crate) original: CString,
+    pub(crate) link: CString,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct ReadLinkState {
+    pub(crate) path: CString,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<PathBuf, Error>
+}
+
+pub struct SetPermissionsState {
+    pub(crate) path: CString,
+    pub(crate) mode: u32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct SetFilePermissionsState {
+    pub(crate) fd: RawFd,
+    pub(crate) mode: u32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct AdviseFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) offset: u64,
+    pub(crate) len: u64,
+    pub(crate) advice: i32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct CreateDirState {
+    pub(crate) path: CString,
+    pub(crate) mode: u32,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct RemoveState {
+    pub(crate) path: CString,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct RenameState {
+    pub(crate) from: CString,
+    pub(crate) to: CString,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct SyncFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) data_only: bool,
+    pub(crate) coroutine: CoroutineImpl,
+    pub(crate) result: *mut Result<(), Error>
+}
+
+pub struct CloseFileState {
+    pub(crate) fd: RawFd,
+    pub(crate) coroutine: CoroutineImpl
+}
+
 
 /// # Why using [`Box`]?
 ///
@@ -72,7 +236,27 @@ pub enum PollState {
     ReadTcp(Box<ReadTcpState>),
     WriteTcp(Box<WriteTcpState>),
     WriteAllTcp(Box<WriteAllTcpState>),
-    CloseTcp(Box<CloseTcpState>)
+    CloseTcp(Box<CloseTcpState>),
+    WaitReadable(Box<WaitReadableState>),
+    OpenFile(Box<OpenFileState>),
+    ReadFile(Box<ReadFileState>),
+    ReadToEndFile(Box<ReadToEndFileState>),
+    WriteFile(Box<WriteFileState>),
+    WriteAllFile(Box<WriteAllFileState>),
+    WriteAllSyncFile(Box<WriteAllSyncFileState>),
+    CloseFile(Box<CloseFileState>),
+    CopyFile(Box<CopyFileState>),
+    Symlink(Box<LinkState>),
+    HardLink(Box<LinkState>),
+    ReadLink(Box<ReadLinkState>),
+    SetPermissions(Box<SetPermissionsState>),
+    SetFilePermissions(Box<SetFilePermissionsState>),
+    AdviseFile(Box<AdviseFileState>),
+    CreateDir(Box<CreateDirState>),
+    RemoveFile(Box<RemoveState>),
+    RemoveDir(Box<RemoveState>),
+    Rename(Box<RenameState>),
+    SyncFile(Box<SyncFileState>)
 }
 
 impl PollState {
@@ -86,13 +270,63 @@ impl PollState {
             PollState::WriteTcp(state) => { state.fd }
             PollState::WriteAllTcp(state) => { state.fd }
             PollState::CloseTcp(state) => { state.fd }
+            PollState::WaitReadable(state) => { state.fd }
+            PollState::ReadFile(state) => { state.fd }
+            PollState::ReadToEndFile(state) => { state.fd }
+            PollState::WriteFile(state) => { state.fd }
+            PollState::WriteAllFile(state) => { state.fd }
+            PollState::WriteAllSyncFile(state) => { state.fd }
+            PollState::CloseFile(state) => { state.fd }
+            PollState::SetFilePermissions(state) => { state.fd }
+            PollState::AdviseFile(state) => { state.fd }
+            PollState::SyncFile(state) => { state.fd }
 
             _ => { panic!("[BUG] tried to get fd from {self:?} token") }
         }
     }
 
+    /// Returns the name of the variant. It does not read the inner state, so it is safe to call for any state.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PollState::Empty(_) => "Empty",
+            PollState::AcceptTcp(_) => "AcceptTcp",
+            PollState::ConnectTcp(_) => "ConnectTcp",
+            PollState::PollTcp(_) => "PollTcp",
+            PollState::ReadTcp(_) => "ReadTcp",
+            PollState::WriteTcp(_) => "WriteTcp",
+            PollState::WriteAllTcp(_) => "WriteAllTcp",
+            PollState::CloseTcp(_) => "CloseTcp",
+            PollState::WaitReadable(_) => "WaitReadable",
+            PollState::OpenFile(_) => "OpenFile",
+            PollState::ReadFile(_) => "ReadFile",
+            PollState::ReadToEndFile(_) => "ReadToEndFile",
+            PollState::WriteFile(_) => "WriteFile",
+            PollState::WriteAllFile(_) => "WriteAllFile",
+            PollState::WriteAllSyncFile(_) => "WriteAllSyncFile",
+            PollState::CloseFile(_) => "CloseFile",
+            PollState::CopyFile(_) => "CopyFile",
+            PollState::Symlink(_) => "Symlink",
+            PollState::HardLink(_) => "HardLink",
+            PollState::ReadLink(_) => "ReadLink",
+            PollState::SetPermissions(_) => "SetPermissions",
+            PollState::SetFilePermissions(_) => "SetFilePermissions",
+            PollState::AdviseFile(_) => "AdviseFile",
+            PollState::CreateDir(_) => "CreateDir",
+            PollState::RemoveFile(_) => "RemoveFile",
+            PollState::RemoveDir(_) => "RemoveDir",
+            PollState::Rename(_) => "Rename",
+            PollState::SyncFile(_) => "SyncFile"
+        }
+    }
+
     pub fn new_empty(fd: RawFd) -> Self {
-        PollState::Empty(EmptyState { fd })
+        PollState::Empty(EmptyState { fd, is_eof: false })
+    }
+
+    /// Creates the [`Empty`](PollState::Empty) state of the connection, that the other side has closed.
+    /// Read [`PollState::is_eof`].
+    pub(crate) fn new_eof(fd: RawFd) -> Self {
+        PollState::Empty(EmptyState { fd, is_eof: true })
     }
 
     #[inline(always)]
@@ -101,7 +335,12 @@ impl PollState {
     }
 
     #[inline(always)]
-    pub fn new_connect_tcp(address: SockAddr, coroutine: CoroutineImpl, result: *mut Result<TcpStream, Error>) -> Result<Self, (Error, CoroutineImpl)> {
+    pub fn new_connect_tcp(
+        address: SockAddr,
+        timeout: Option<Duration>,
+        coroutine: CoroutineImpl,
+        result: *mut Result<TcpStream, Error>
+    ) -> Result<Self, (Error, CoroutineImpl)> {
         let socket_ = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP));
         if socket_.is_err() {
             unsafe {
@@ -110,25 +349,25 @@ impl PollState {
         }
 
         unsafe {
-            Ok(PollState::ConnectTcp(Box::new(ConnectTcpState { address, socket: socket_.unwrap_unchecked(), coroutine, result })))
+            Ok(PollState::ConnectTcp(Box::new(ConnectTcpState { address, socket: socket_.unwrap_unchecked(), timeout, coroutine, result })))
         }
     }
 
     #[inline(always)]
-    pub fn new_poll_tcp(stream: RawFd, coroutine: CoroutineImpl, result: *mut Result<&'_ [u8], Error>) -> Self {
-        let result = unsafe { std::mem::transmute(result) };
-        PollState::PollTcp(Box::new(PollTcpState { fd: stream, coroutine, result }))
+    pub fn new_poll_tcp(stream: RawFd, coalesce: usize, coroutine: CoroutineImpl, result: *mut Result<&'_ [u8], Error>) -> Self {
+        let result = result.cast();
+        PollState::PollTcp(Box::new(PollTcpState { fd: stream, coalesce, coroutine, result }))
     }
 
     #[inline(always)]
-    pub fn new_read_tcp(stream: RawFd, buf: Buffer, coroutine: CoroutineImpl, result: *mut Result<&'_ [u8], Error>) -> Self {
-        let result = unsafe { std::mem::transmute(result) };
-        PollState::ReadTcp(Box::new(ReadTcpState { fd: stream, buffer: buf, coroutine, result }))
+    pub fn new_read_tcp(stream: RawFd, buf: Buffer, coalesce: usize, coroutine: CoroutineImpl, result: *mut Result<&'_ [u8], Error>) -> Self {
+        let result = result.cast();
+        PollState::ReadTcp(Box::new(ReadTcpState { fd: stream, buffer: buf, coalesce, coroutine, result }))
     }
 
     #[inline(always)]
-    pub fn new_write_tcp(stream: RawFd, buf: Buffer, coroutine: CoroutineImpl, result: *mut Result<Option<Buffer>, Error>) -> Self {
-        PollState::WriteTcp(Box::new(WriteTcpState { fd: stream, buffer: buf, coroutine, result }))
+    pub fn new_write_tcp(stream: RawFd, buf: Buffer, zero_copy: bool, coroutine: CoroutineImpl, result: *mut Result<Option<Buffer>, Error>) -> Self {
+        PollState::WriteTcp(Box::new(WriteTcpState { fd: stream, buffer: buf, coroutine, result, zero_copy, sent: None }))
     }
 
     #[inline(always)]
@@ -140,12 +379,216 @@ impl PollState {
     pub fn new_close_tcp(stream: RawFd, coroutine: CoroutineImpl) -> Self {
         PollState::CloseTcp(Box::new(CloseTcpState { fd: stream, coroutine }))
     }
+
+    #[inline(always)]
+    pub fn new_wait_readable(fd: RawFd, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::WaitReadable(Box::new(WaitReadableState { fd, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_open_file(path: CString, flags: i32, mode: u32, coroutine: CoroutineImpl, result: *mut Result<File, Error>) -> Self {
+        PollState::OpenFile(Box::new(OpenFileState { path, flags, mode, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_read_file(fd: RawFd, buf: Buffer, offset: u64, cursor: *mut u64, coroutine: CoroutineImpl, result: *mut Result<Buffer, Error>) -> Self {
+        PollState::ReadFile(Box::new(ReadFileState { fd, buffer: buf, offset, cursor, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_read_to_end_file(fd: RawFd, buf: Buffer, offset: u64, cursor: *mut u64, coroutine: CoroutineImpl, result: *mut Result<Buffer, Error>) -> Self {
+        PollState::ReadToEndFile(Box::new(ReadToEndFileState { fd, buffer: buf, offset, cursor, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_write_file(fd: RawFd, buf: Buffer, offset: u64, cursor: *mut u64, coroutine: CoroutineImpl, result: *mut Result<Option<Buffer>, Error>) -> Self {
+        PollState::WriteFile(Box::new(WriteFileState { fd, buffer: buf, offset, cursor, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_write_all_file(fd: RawFd, buf: Buffer, offset: u64, cursor: *mut u64, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::WriteAllFile(Box::new(WriteAllFileState { fd, buffer: buf, offset, cursor, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_write_all_sync_file(
+        fd: RawFd,
+        buf: Buffer,
+        offset: u64,
+        cursor: *mut u64,
+        data_only: bool,
+        coroutine: CoroutineImpl,
+        result: *mut Result<(), Error>
+    ) -> Self {
+        PollState::WriteAllSyncFile(Box::new(WriteAllSyncFileState {
+            fd, buffer: buf, offset, cursor, data_only, coroutine, result, written: None, synced: None
+        }))
+    }
+
+    #[inline(always)]
+    pub fn new_close_file(fd: RawFd, coroutine: CoroutineImpl) -> Self {
+        PollState::CloseFile(Box::new(CloseFileState { fd, coroutine }))
+    }
+
+    #[inline(always)]
+    pub fn new_copy_file(src_fd: RawFd, dst_fd: RawFd, coroutine: CoroutineImpl, result: *mut Result<u64, Error>) -> Self {
+        PollState::CopyFile(Box::new(CopyFileState { src_fd, dst_fd, copied: 0, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_symlink(original: CString, link: CString, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::Symlink(Box::new(LinkState { original, link, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_hard_link(original: CString, link: CString, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::HardLink(Box::new(LinkState { original, link, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_read_link(path: CString, coroutine: CoroutineImpl, result: *mut Result<PathBuf, Error>) -> Self {
+        PollState::ReadLink(Box::new(ReadLinkState { path, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_set_permissions(path: CString, mode: u32, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::SetPermissions(Box::new(SetPermissionsState { path, mode, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_set_file_permissions(fd: RawFd, mode: u32, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::SetFilePermissions(Box::new(SetFilePermissionsState { fd, mode, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_advise_file(fd: RawFd, offset: u64, len: u64, advice: i32, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::AdviseFile(Box::new(AdviseFileState { fd, offset, len, advice, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_create_dir(path: CString, mode: u32, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::CreateDir(Box::new(CreateDirState { path, mode, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_remove_file(path: CString, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::RemoveFile(Box::new(RemoveState { path, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_remove_dir(path: CString, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::RemoveDir(Box::new(RemoveState { path, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_rename(from: CString, to: CString, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::Rename(Box::new(RenameState { from, to, coroutine, result }))
+    }
+
+    #[inline(always)]
+    pub fn new_sync_file(fd: RawFd, data_only: bool, coroutine: CoroutineImpl, result: *mut Result<(), Error>) -> Self {
+        PollState::SyncFile(Box::new(SyncFileState { fd, data_only, coroutine, result }))
+    }
+
+    /// Returns true, if the state is [`Empty`](PollState::Empty), so no operation uses it.
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        matches!(self, PollState::Empty(_))
+    }
+
+    /// Returns true, if the last read of the connection has returned an empty slice, because the other side
+    /// has closed it. The stream remembers it, so the next reads return an empty slice without waiting.
+    #[inline(always)]
+    pub(crate) fn is_eof(&self) -> bool {
+        matches!(self, PollState::Empty(EmptyState { is_eof: true, .. }))
+    }
+
+    /// Returns true, if the operation of the state can be made again, when it fails with
+    /// [`EINTR`](libc::EINTR) or [`EAGAIN`](libc::EAGAIN): it has no side effects, that have happened before the failure.
+    /// Connecting and closing are not retried: the connection goes on in the background, and the fd is closed anyway.
+    #[cfg(target_os = "linux")]
+    #[inline(always)]
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            PollState::AcceptTcp(_)
+                | PollState::PollTcp(_)
+                | PollState::ReadTcp(_)
+                | PollState::WriteTcp(_)
+                | PollState::WriteAllTcp(_)
+                | PollState::WaitReadable(_)
+                | PollState::ReadFile(_)
+                | PollState::ReadToEndFile(_)
+                | PollState::WriteFile(_)
+                | PollState::WriteAllFile(_)
+                | PollState::SyncFile(_)
+        )
+    }
+
+    /// Returns true, if the state is a file operation. Files can't be polled for readiness, so these states are always ready.
+    #[inline(always)]
+    pub fn is_file_op(&self) -> bool {
+        matches!(
+            self,
+            PollState::OpenFile(_)
+                | PollState::ReadFile(_)
+                | PollState::ReadToEndFile(_)
+                | PollState::WriteFile(_)
+                | PollState::WriteAllFile(_)
+                | PollState::WriteAllSyncFile(_)
+                | PollState::CloseFile(_)
+                | PollState::CopyFile(_)
+                | PollState::Symlink(_)
+                | PollState::HardLink(_)
+                | PollState::ReadLink(_)
+                | PollState::SetPermissions(_)
+                | PollState::SetFilePermissions(_)
+                | PollState::AdviseFile(_)
+                | PollState::CreateDir(_)
+                | PollState::RemoveFile(_)
+                | PollState::RemoveDir(_)
+                | PollState::Rename(_)
+                | PollState::SyncFile(_)
+        )
+    }
+
+    /// Cancels the waiting for readiness of the state behind the pointer.
+    ///
+    /// If the state is [`AcceptTcp`](PollState::AcceptTcp), [`PollTcp`](PollState::PollTcp)
+    /// or [`WaitReadable`](PollState::WaitReadable), writes [`ECANCELED`](libc::ECANCELED) to its result,
+    /// leaves the [`Empty`](PollState::Empty) state in the pointer and returns the coroutine to wake up.
+    /// Other states are left as is.
+    pub(crate) fn cancel(state_ptr: Ptr<PollState>) -> Option<CoroutineImpl> {
+        let state = unsafe { state_ptr.read() };
+        let fd = state.fd();
+        let coroutine = match state {
+            PollState::AcceptTcp(state) => {
+                write_err!(state.result, Error::from_raw_os_error(libc::ECANCELED));
+                state.coroutine
+            }
+            PollState::PollTcp(state) => {
+                write_err!(state.result, Error::from_raw_os_error(libc::ECANCELED));
+                state.coroutine
+            }
+            PollState::WaitReadable(state) => {
+                write_err!(state.result, Error::from_raw_os_error(libc::ECANCELED));
+                state.coroutine
+            }
+            state => {
+                unsafe { state_ptr.write(state) };
+                return None;
+            }
+        };
+
+        unsafe { state_ptr.write(PollState::new_empty(fd)) };
+        Some(coroutine)
+    }
 }
 
 impl Debug for PollState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            PollState::Empty(state) => { write!(f, "Empty, fd: {:?}", state.fd) }
+            PollState::Empty(state) => { write!(f, "Empty, fd: {:?}, is eof: {:?}", state.fd, state.is_eof) }
             PollState::AcceptTcp(state) => { write!(f, "AcceptTcp, fd: {:?}", state.fd) }
             PollState::ConnectTcp(state) => {
                 write!(
@@ -159,6 +602,30 @@ impl Debug for PollState {
             PollState::WriteTcp(state) => { write!(f, "WriteTcp, fd: {:?}", state.fd) }
             PollState::WriteAllTcp(state) => { write!(f, "WriteAllTcp, fd: {:?}", state.fd) }
             PollState::CloseTcp(state) => { write!(f, "CloseTcp, fd: {:?}", state.fd) }
+            PollState::WaitReadable(state) => { write!(f, "WaitReadable, fd: {:?}", state.fd) }
+            PollState::OpenFile(state) => { write!(f, "OpenFile, path: {:?}", state.path) }
+            PollState::ReadFile(state) => { write!(f, "ReadFile, fd: {:?}, offset: {}", state.fd, state.offset) }
+            PollState::ReadToEndFile(state) => { write!(f, "ReadToEndFile, fd: {:?}, offset: {}", state.fd, state.offset) }
+            PollState::WriteFile(state) => { write!(f, "WriteFile, fd: {:?}, offset: {}", state.fd, state.offset) }
+            PollState::WriteAllFile(state) => { write!(f, "WriteAllFile, fd: {:?}, offset: {}", state.fd, state.offset) }
+            PollState::WriteAllSyncFile(state) => {
+                write!(f, "WriteAllSyncFile, fd: {:?}, offset: {}, data only: {}", state.fd, state.offset, state.data_only)
+            }
+            PollState::CloseFile(state) => { write!(f, "CloseFile, fd: {:?}", state.fd) }
+            PollState::CopyFile(state) => { write!(f, "CopyFile, src fd: {:?}, dst fd: {:?}", state.src_fd, state.dst_fd) }
+            PollState::Symlink(state) => { write!(f, "Symlink, original: {:?}, link: {:?}", state.original, state.link) }
+            PollState::HardLink(state) => { write!(f, "HardLink, original: {:?}, link: {:?}", state.original, state.link) }
+            PollState::ReadLink(state) => { write!(f, "ReadLink, path: {:?}", state.path) }
+            PollState::SetPermissions(state) => { write!(f, "SetPermissions, path: {:?}, mode: {:o}", state.path, state.mode) }
+            PollState::SetFilePermissions(state) => { write!(f, "SetFilePermissions, fd: {:?}, mode: {:o}", state.fd, state.mode) }
+            PollState::AdviseFile(state) => {
+                write!(f, "AdviseFile, fd: {:?}, offset: {}, len: {}, advice: {}", state.fd, state.offset, state.len, state.advice)
+            }
+            PollState::CreateDir(state) => { write!(f, "CreateDir, path: {:?}, mode: {:o}", state.path, state.mode) }
+            PollState::RemoveFile(state) => { write!(f, "RemoveFile, path: {:?}", state.path) }
+            PollState::RemoveDir(state) => { write!(f, "RemoveDir, path: {:?}", state.path) }
+            PollState::Rename(state) => { write!(f, "Rename, from: {:?}, to: {:?}", state.from, state.to) }
+            PollState::SyncFile(state) => { write!(f, "SyncFile, fd: {:?}, data only: {}", state.fd, state.data_only) }
         }
     }
 }
\ No newline at end of file
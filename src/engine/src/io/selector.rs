@@ -2,8 +2,11 @@
 //!
 //! Selector is a trait for working with systems selectors like epoll, kqueue, io_uring etc.
 
+use std::io::Error;
+use std::time::Duration;
 use crate::import_fd_for_os;
 import_fd_for_os!();
+use crate::coroutine::CoroutineImpl;
 use crate::io::PollState;
 use crate::scheduler::Scheduler;
 use crate::utils::Ptr;
@@ -22,11 +25,24 @@ use crate::utils::Ptr;
 /// * If it's necessary to return the result of the syscall, the [`PollState`] contains a pointer to the result variable.
 ///
 /// * The [`Selector`] itself will awaken the coroutine when it's ready.
+///
+/// * What a selector can do beyond sockets is told by its capabilities:
+///   [`Selector::supports_multishot`] and [`Selector::file_op_support`].
 pub trait Selector {
-    /// Returns true, if the [`Selector`] needs to have the [`PollState`] re-registered fd every time.
-    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) returns false.
-    /// [`IoUringSelector`](crate::io::sys::unix::IoUringSelector) returns true.
-    fn need_reregister(&self) -> bool;
+    /// Returns true, if one registration of a fd wakes all its later operations up,
+    /// so the [`Scheduler`] only writes the new [`PollState`] for an already registered fd and doesn't register it again.
+    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) returns true.
+    ///
+    /// Returns false, if every operation is a separate submission, that completes once, so every [`PollState`] is registered.
+    /// [`IoUringSelector`](crate::io::sys::unix::IoUringSelector) returns false.
+    fn supports_multishot(&self) -> bool;
+    /// Returns the file operations (states with [`PollState::is_file_op`]), that the selector handles.
+    /// It is read once, when the worker is started.
+    ///
+    /// The [`Scheduler`] runs other file operations on its blocking pool and never registers them with the selector.
+    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) handles none of them, because regular files are always ready for epoll,
+    /// and [`IoUringSelector`](crate::io::sys::unix::IoUringSelector) handles the ones, which opcodes the running kernel supports.
+    fn file_op_support(&self) -> FileOpSupport;
     /// Polls the [`Selector`] for coroutines that are ready.
     /// This method will wake the coroutines up.
     ///
@@ -37,11 +53,20 @@ pub trait Selector {
     ///
     /// So, you should call this method, when no ready coroutines.
     ///
+    /// It waits for completions at most `timeout` (`None` means without a timeout). Read [`IdleStrategy`](crate::scheduler::IdleStrategy).
+    ///
     /// # Return
     ///
     /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
-    fn poll(&mut self, scheduler: &mut Scheduler) -> Result<bool, ()>;
-    /// Registers the [`PollState`] with the selector.
+    ///
+    /// Returns an error, if the selector itself failed (not an operation of a coroutine), so the worker can't go on.
+    fn poll(&mut self, scheduler: &mut Scheduler, timeout: Option<Duration>) -> Result<bool, Error>;
+    /// Registers the [`PollState`] with the selector. The operation is done in [`Selector::poll`] (or before it),
+    /// and the selector wakes the coroutine of the state up.
+    ///
+    /// Every operation is registered with this method: reads and accepts, that wait for readiness,
+    /// writes (the part of the buffer or the whole buffer), closes of connections and
+    /// file operations, that are in the [`Selector::file_op_support`].
     fn register(&mut self, state_ptr: Ptr<PollState>);
     /// Deregisters the [`PollState`] with the selector by the fd.
     ///
@@ -50,30 +75,124 @@ pub trait Selector {
     /// After deregistering, the [`PollState`] will be ignored in [`Selector::poll`].
     fn deregister(&mut self, fd: RawFd);
 
-    // TODO need test, because I change was_written to Buffer::offset()
-    /// Tells the selector that [`WriteTcpState`](crate::io::WriteTcpState) or another writable [`PollState`] is ready.
-    /// So, this method returns before the write syscall is done. The writing will be done in [`Selector::poll`].
-    ///
-    /// # Panics
-    ///
-    /// Will lead to panic if the [`PollState`] does not exist at the time of [`Selector::poll`].
-    ///
-    /// # Note
-    ///
-    /// This method will lead to one syscall. Only the part of the buffer will be written.
-    /// The number of bytes written will be stored in the result variable.
-    fn write(&mut self, state_ref: Ptr<PollState>);
-    /// Tells the selector that [`WriteAllTcpState`](crate::io::WriteAllTcpState) or another writable [`PollState`] is ready.
-    /// So, this method returns before the write syscall is done. The writing will be done in [`Selector::poll`].
+    /// Registers the long-lived fd with the selector, so operations on it don't look it up in the fd table.
+    /// The fd is unregistered, when it is closed by the engine.
     ///
-    /// # Panics
+    /// Returns false, if the selector doesn't register fds or has no free slots.
+    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) always returns false.
+    fn register_file(&mut self, fd: RawFd) -> Result<bool, Error>;
+    /// Cancels the pending operation of the [`PollState`]. The operation fails with [`ECANCELED`](libc::ECANCELED).
     ///
-    /// Will lead to panic if the [`PollState`] does not exist at the time of [`Selector::poll`].
+    /// Returns the coroutine of the state, if it is cancelled immediately and should be woken up by the caller.
+    /// Otherwise, the selector wakes it up in [`Selector::poll`], or the operation has already completed.
+    fn cancel(&mut self, state_ref: Ptr<PollState>) -> Option<CoroutineImpl>;
+    /// Wakes the coroutine up after `dur` with a timer of the selector. Read [`set_ring_timeouts`](crate::cfg::set_ring_timeouts).
     ///
-    /// # Note
-    ///
-    /// This method can lead to one or more syscalls.
-    fn write_all(&mut self, state_ref: Ptr<PollState>);
-    /// TODO docs
-    fn close_connection(&mut self, state_ref: Ptr<PollState>);
-}
\ No newline at end of file
+    /// Returns the coroutine back, if the selector doesn't sleep coroutines, so the [`Scheduler`] puts it to its timer wheel.
+    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) always returns it.
+    fn sleep(&mut self, dur: Duration, coroutine: CoroutineImpl) -> Option<CoroutineImpl>;
+    /// Returns the fd of the io_uring of the selector, so other threads can wake the worker up with `IORING_OP_MSG_RING`.
+    /// Read [`Injector::wake`](crate::scheduler::Injector::wake).
+    /// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) returns `None`.
+    fn ring_fd(&self) -> Option<RawFd>;
+    /// Returns the number of [`PollState`]s, that the selector holds: registered fds and operations, that are not completed yet.
+    /// It is read by [`Scheduler::metrics`](crate::scheduler::Scheduler::metrics).
+    fn pending_states(&self) -> usize;
+}
+
+/// The set of file operations, that a [`Selector`] handles. Read [`Selector::file_op_support`].
+///
+/// It is reported in [`SchedulerMetrics`](crate::scheduler::SchedulerMetrics), so operators can see,
+/// which operations of an old kernel are run on the blocking pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileOpSupport {
+    /// [`OpenFile`](PollState::OpenFile).
+    pub open: bool,
+    /// [`ReadFile`](PollState::ReadFile) and [`ReadToEndFile`](PollState::ReadToEndFile).
+    pub read: bool,
+    /// [`WriteFile`](PollState::WriteFile) and [`WriteAllFile`](PollState::WriteAllFile).
+    pub write: bool,
+    /// [`SyncFile`](PollState::SyncFile). [`WriteAllSyncFile`](PollState::WriteAllSyncFile) needs `write` too.
+    pub sync: bool,
+    /// [`CloseFile`](PollState::CloseFile).
+    pub close: bool,
+    /// [`CopyFile`](PollState::CopyFile).
+    pub copy: bool,
+    /// [`Symlink`](PollState::Symlink).
+    pub symlink: bool,
+    /// [`HardLink`](PollState::HardLink).
+    pub hard_link: bool,
+    /// [`ReadLink`](PollState::ReadLink).
+    pub read_link: bool,
+    /// [`SetPermissions`](PollState::SetPermissions) and [`SetFilePermissions`](PollState::SetFilePermissions).
+    pub set_permissions: bool,
+    /// [`AdviseFile`](PollState::AdviseFile).
+    pub advise: bool,
+    /// [`CreateDir`](PollState::CreateDir).
+    pub create_dir: bool,
+    /// [`RemoveFile`](PollState::RemoveFile) and [`RemoveDir`](PollState::RemoveDir).
+    pub remove: bool,
+    /// [`Rename`](PollState::Rename).
+    pub rename: bool
+}
+
+impl FileOpSupport {
+    /// All file operations are handled.
+    pub const ALL: Self = Self {
+        open: true,
+        read: true,
+        write: true,
+        sync: true,
+        close: true,
+        copy: true,
+        symlink: true,
+        hard_link: true,
+        read_link: true,
+        set_permissions: true,
+        advise: true,
+        create_dir: true,
+        remove: true,
+        rename: true
+    };
+
+    /// No file operations are handled, so all of them are run on the blocking pool.
+    pub const NONE: Self = Self {
+        open: false,
+        read: false,
+        write: false,
+        sync: false,
+        close: false,
+        copy: false,
+        symlink: false,
+        hard_link: false,
+        read_link: false,
+        set_permissions: false,
+        advise: false,
+        create_dir: false,
+        remove: false,
+        rename: false
+    };
+
+    /// Returns true, if the state is a file operation, that is handled. Other states are not file operations,
+    /// so it returns false for them.
+    pub fn supports(&self, state: &PollState) -> bool {
+        match state {
+            PollState::OpenFile(_) => self.open,
+            PollState::ReadFile(_) | PollState::ReadToEndFile(_) => self.read,
+            PollState::WriteFile(_) | PollState::WriteAllFile(_) => self.write,
+            PollState::WriteAllSyncFile(_) => self.write && self.sync,
+            PollState::SyncFile(_) => self.sync,
+            PollState::CloseFile(_) => self.close,
+            PollState::CopyFile(_) => self.copy,
+            PollState::Symlink(_) => self.symlink,
+            PollState::HardLink(_) => self.hard_link,
+            PollState::ReadLink(_) => self.read_link,
+            PollState::SetPermissions(_) | PollState::SetFilePermissions(_) => self.set_permissions,
+            PollState::AdviseFile(_) => self.advise,
+            PollState::CreateDir(_) => self.create_dir,
+            PollState::RemoveFile(_) | PollState::RemoveDir(_) => self.remove,
+            PollState::Rename(_) => self.rename,
+            _ => false
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! This module contains the trace of [`PollState`](crate::io::PollState) transitions, that selectors record per fd,
+//! and its export to Graphviz and Chrome trace formats.
+//!
+//! It is a debug facility for the selector logic and hangs: the trace shows, which operation every fd waits for
+//! and how states follow each other (like `PollTcp` → `ReadTcp` or resubmits of `WriteAllTcp`).
+//!
+//! The trace is disabled by default, and a disabled trace costs one atomic load per operation.
+//! Enable it with [`enable_state_trace`] before starting coroutines, that should be traced.
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::intrinsics::unlikely;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+use crate::io::PollState;
+use crate::local::get_worker_id;
+
+static IS_ENABLED: AtomicBool = AtomicBool::new(false);
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The events of the current worker.
+    static EVENTS: UnsafeCell<Option<EventBuffer>> = const { UnsafeCell::new(None) };
+}
+
+/// What happened with the state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateEventKind {
+    /// The state was passed to the selector.
+    Submit,
+    /// The selector handled the state: the operation was completed or the fd became ready.
+    Complete
+}
+
+/// One recorded operation of the selector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateEvent {
+    /// Microseconds from the enabling of the trace in the worker.
+    pub micros: u64,
+    /// The fd of the state or -1 for operations by path.
+    pub fd: RawFd,
+    /// The [`kind`](crate::io::PollState::kind) of the state.
+    pub state: &'static str,
+    pub kind: StateEventKind,
+    /// The result of the operation for [`StateEventKind::Complete`] in the [`IoUringSelector`](crate::io::sys::unix::IoUringSelector),
+    /// otherwise 0.
+    pub result: i32
+}
+
+/// The ring buffer of [`StateEvent`]s of one worker.
+struct EventBuffer {
+    start: Instant,
+    events: Vec<StateEvent>,
+    /// The index of the oldest event, when the buffer is full.
+    head: usize
+}
+
+/// Enables recording of state transitions in all workers. Every worker keeps the last `capacity` events.
+pub fn enable_state_trace(capacity: usize) {
+    CAPACITY.store(capacity.max(1), Ordering::Relaxed);
+    IS_ENABLED.store(true, Ordering::Release);
+}
+
+/// Disables recording of state transitions. Already recorded events are kept.
+pub fn disable_state_trace() {
+    IS_ENABLED.store(false, Ordering::Release);
+}
+
+/// Returns true, if recording of state transitions is enabled.
+#[inline(always)]
+pub fn is_state_trace_enabled() -> bool {
+    IS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records the event for the state in the current worker, if the trace is enabled. Empty states are not recorded.
+#[inline(always)]
+pub(crate) fn record_state(state: &PollState, kind: StateEventKind, result: i32) {
+    if unlikely(is_state_trace_enabled()) {
+        let fd = match state {
+            PollState::Empty(_) => return,
+            PollState::ConnectTcp(state) => state.socket.as_raw_fd(),
+            // Operations by path have no fd.
+            PollState::OpenFile(_) | PollState::CopyFile(_) | PollState::Symlink(_) | PollState::HardLink(_)
+            | PollState::ReadLink(_) | PollState::SetPermissions(_) | PollState::CreateDir(_)
+            | PollState::RemoveFile(_) | PollState::RemoveDir(_) | PollState::Rename(_) => -1,
+            _ => state.fd()
+        };
+        record(fd, state.kind(), kind, result);
+    }
+}
+
+#[cold]
+fn record(fd: RawFd, state: &'static str, kind: StateEventKind, result: i32) {
+    EVENTS.with(|events| {
+        let buffer = unsafe { &mut *events.get() }.get_or_insert_with(|| EventBuffer {
+            start: Instant::now(),
+            events: Vec::new(),
+            head: 0
+        });
+        let event = StateEvent { micros: buffer.start.elapsed().as_micros() as u64, fd, state, kind, result };
+        let capacity = CAPACITY.load(Ordering::Relaxed);
+        if buffer.events.len() < capacity {
+            buffer.events.push(event);
+        } else {
+            buffer.events[buffer.head] = event;
+            buffer.head = (buffer.head + 1) % buffer.events.len();
+        }
+    });
+}
+
+/// Returns the recorded events of the current worker from the oldest to the newest.
+pub fn state_trace() -> StateTrace {
+    EVENTS.with(|events| {
+        let events = match unsafe { &*events.get() } {
+            Some(buffer) => {
+                let mut events = Vec::with_capacity(buffer.events.len());
+                events.extend_from_slice(&buffer.events[buffer.head..]);
+                events.extend_from_slice(&buffer.events[..buffer.head]);
+                events
+            }
+            None => Vec::new()
+        };
+        StateTrace { worker_id: get_worker_id(), events }
+    })
+}
+
+/// Removes the recorded events of the current worker.
+pub fn clear_state_trace() {
+    EVENTS.with(|events| {
+        unsafe { *events.get() = None };
+    });
+}
+
+/// The recorded state transitions of one worker. It is returned by [`state_trace`].
+#[derive(Clone, Debug)]
+pub struct StateTrace {
+    pub worker_id: usize,
+    pub events: Vec<StateEvent>
+}
+
+impl StateTrace {
+    /// Returns the transitions between states of the same fd with their counts, sorted by the count.
+    ///
+    /// Only [`Complete`](StateEventKind::Complete) events are used, so a resubmit is a transition of the state to itself.
+    pub fn transitions(&self) -> Vec<((&'static str, &'static str), usize)> {
+        let mut last = HashMap::new();
+        let mut counts: HashMap<(&'static str, &'static str), usize> = HashMap::new();
+        for event in self.events.iter().filter(|event| event.kind == StateEventKind::Complete) {
+            if let Some(previous) = last.insert(event.fd, event.state) {
+                *counts.entry((previous, event.state)).or_default() += 1;
+            }
+        }
+
+        let mut transitions: Vec<_> = counts.into_iter().collect();
+        transitions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        transitions
+    }
+
+    /// Returns the states, that were submitted, but not completed yet, by fd. They are what the fds are waiting for.
+    pub fn pending(&self) -> Vec<(RawFd, &'static str)> {
+        let mut pending = HashMap::new();
+        for event in self.events.iter() {
+            match event.kind {
+                StateEventKind::Submit => { pending.insert(event.fd, event.state); }
+                StateEventKind::Complete => { pending.remove(&event.fd); }
+            }
+        }
+
+        let mut pending: Vec<_> = pending.into_iter().collect();
+        pending.sort();
+        pending
+    }
+
+    /// Exports [`StateTrace::transitions`] as a Graphviz digraph. Edges are labeled with counts.
+    pub fn to_graphviz(&self) -> String {
+        let mut out = format!("digraph worker_{} {{\n", self.worker_id);
+        for ((from, to), count) in self.transitions() {
+            let _ = writeln!(out, "    \"{}\" -> \"{}\" [label=\"{}\"];", from, to, count);
+        }
+        for (fd, state) in self.pending() {
+            let _ = writeln!(out, "    \"{}\" [color=red, xlabel=\"pending fd {}\"];", state, fd);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Exports the events in the Chrome trace format (for `chrome://tracing` or Perfetto).
+    ///
+    /// Every fd is a thread. A completed operation is a slice from the submit to the completion,
+    /// other events are instant events, and pending operations have the `pending` argument.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut out = String::from("[");
+        let mut submitted: HashMap<RawFd, StateEvent> = HashMap::new();
+        let mut is_first = true;
+        let mut push = |out: &mut String, event: &StateEvent, phase: &str, extra: String| {
+            if !is_first {
+                out.push(',');
+            }
+            is_first = false;
+            let _ = write!(
+                out,
+                "\n{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{},\"pid\":{},\"tid\":{}{}}}",
+                event.state, phase, event.micros, self.worker_id, event.fd, extra
+            );
+        };
+
+        for event in self.events.iter() {
+            match event.kind {
+                StateEventKind::Submit => {
+                    if let Some(previous) = submitted.insert(event.fd, *event) {
+                        push(&mut out, &previous, "i", ",\"s\":\"t\"".to_string());
+                    }
+                }
+                StateEventKind::Complete => match submitted.remove(&event.fd) {
+                    Some(submit) if submit.state == event.state => {
+                        let extra = format!(",\"dur\":{},\"args\":{{\"result\":{}}}", event.micros - submit.micros, event.result);
+                        push(&mut out, &submit, "X", extra);
+                    }
+                    submit => {
+                        if let Some(submit) = submit {
+                            push(&mut out, &submit, "i", ",\"s\":\"t\"".to_string());
+                        }
+                        push(&mut out, event, "i", format!(",\"s\":\"t\",\"args\":{{\"result\":{}}}", event.result));
+                    }
+                }
+            }
+        }
+
+        let mut pending: Vec<_> = submitted.into_values().collect();
+        pending.sort_by_key(|event| event.micros);
+        for event in pending {
+            push(&mut out, &event, "i", ",\"s\":\"t\",\"args\":{\"pending\":true}".to_string());
+        }
+        out.push_str("\n]\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Write};
+    use std::net::SocketAddr;
+    use crate::test_local;
+    use crate::io::{AsyncRead, disable_state_trace, enable_state_trace, state_trace, StateEvent, StateEventKind, StateTrace};
+    use crate::net::TcpStream;
+
+    fn event(micros: u64, fd: i32, state: &'static str, kind: StateEventKind) -> StateEvent {
+        StateEvent { micros, fd, state, kind, result: 0 }
+    }
+
+    #[test]
+    fn test_export() {
+        let trace = StateTrace {
+            worker_id: 1,
+            events: vec![
+                event(0, 5, "PollTcp", StateEventKind::Submit),
+                event(10, 5, "PollTcp", StateEventKind::Complete),
+                event(11, 5, "ReadTcp", StateEventKind::Submit),
+                event(15, 5, "ReadTcp", StateEventKind::Complete),
+                event(20, 6, "WriteAllTcp", StateEventKind::Submit),
+                event(25, 6, "WriteAllTcp", StateEventKind::Complete),
+                event(26, 6, "WriteAllTcp", StateEventKind::Submit),
+                event(30, 6, "WriteAllTcp", StateEventKind::Complete),
+                event(40, 5, "PollTcp", StateEventKind::Submit)
+            ]
+        };
+
+        assert_eq!(trace.transitions(), vec![(("PollTcp", "ReadTcp"), 1), (("WriteAllTcp", "WriteAllTcp"), 1)]);
+        assert_eq!(trace.pending(), vec![(5, "PollTcp")]);
+
+        let graphviz = trace.to_graphviz();
+        assert!(graphviz.starts_with("digraph worker_1 {"));
+        assert!(graphviz.contains("\"PollTcp\" -> \"ReadTcp\" [label=\"1\"];"));
+        assert!(graphviz.contains("pending fd 5"));
+
+        let chrome = trace.to_chrome_trace();
+        assert!(chrome.contains("{\"name\":\"PollTcp\",\"ph\":\"X\",\"ts\":0,\"pid\":1,\"tid\":5,\"dur\":10,\"args\":{\"result\":0}}"));
+        assert!(chrome.contains("{\"name\":\"PollTcp\",\"ph\":\"i\",\"ts\":40,\"pid\":1,\"tid\":5,\"s\":\"t\",\"args\":{\"pending\":true}}"));
+        assert_eq!(chrome.matches("\"ph\":\"X\"").count(), 4);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_state_trace() {
+        enable_state_trace(1024);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"ping").unwrap();
+            stream
+        });
+
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let res: Result<&[u8], Error> = yield stream.read();
+        assert_eq!(res.unwrap(), b"ping");
+        let fd = stream.fd();
+        drop(peer.join().unwrap());
+
+        let trace = state_trace();
+        let states: Vec<&str> = trace.events.iter()
+            .filter(|event| event.fd == fd && event.kind == StateEventKind::Complete)
+            .map(|event| event.state)
+            .collect();
+        assert!(states.contains(&"ReadTcp") || states.contains(&"PollTcp"), "{:?}", trace.events);
+        disable_state_trace();
+    }
+}
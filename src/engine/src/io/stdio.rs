@@ -0,0 +1,261 @@
+//! This module contains [`stdin`], [`stdout`], [`stderr`] and their handles.
+use std::cell::UnsafeCell;
+use std::io::Error;
+use std::os::fd::RawFd;
+use std::ptr::null_mut;
+use crate::buf::Buffer;
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::{AsyncWrite, PollState};
+use crate::io::sys::unix::fs::CURRENT_POSITION;
+use crate::utils::Ptr;
+
+/// The state of the standard input of the worker.
+///
+/// It is never freed, because under epoll the fd stays registered with the state after the first read.
+struct StdinState {
+    state: Ptr<PollState>,
+    is_registered: bool,
+    is_pollable: bool
+}
+
+thread_local! {
+    /// Local [`StdinState`]. It is created on the first use.
+    static STDIN: UnsafeCell<Option<StdinState>> = const { UnsafeCell::new(None) };
+}
+
+fn stdin_state() -> &'static mut StdinState {
+    STDIN.with(|stdin| {
+        let stdin = unsafe { &mut *stdin.get() };
+        stdin.get_or_insert_with(|| StdinState {
+            state: Ptr::new(PollState::new_empty(libc::STDIN_FILENO)),
+            is_registered: false,
+            is_pollable: is_pollable(libc::STDIN_FILENO)
+        })
+    })
+}
+
+/// Returns true, if the fd can be polled for readiness. Regular files and `/dev/null` can't.
+#[cfg(target_os = "linux")]
+fn is_pollable(fd: RawFd) -> bool {
+    let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+    if epoll < 0 {
+        return false;
+    }
+    let mut event = libc::epoll_event { events: libc::EPOLLIN as u32, u64: 0 };
+    let res = unsafe { libc::epoll_ctl(epoll, libc::EPOLL_CTL_ADD, fd, &mut event) };
+    unsafe { libc::close(epoll) };
+    res == 0
+}
+
+/// Returns true, if the fd can be polled for readiness. kqueue accepts regular files, but they are always ready,
+/// so only pipes, sockets and terminals are polled.
+#[cfg(not(target_os = "linux"))]
+fn is_pollable(fd: RawFd) -> bool {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+        return false;
+    }
+    match stat.st_mode & libc::S_IFMT {
+        libc::S_IFIFO | libc::S_IFSOCK => true,
+        libc::S_IFCHR => unsafe { libc::isatty(fd) == 1 },
+        _ => false
+    }
+}
+
+/// Returns a handle to the standard input of the process.
+///
+/// Read [`Stdin`] for more information.
+pub fn stdin() -> Stdin {
+    Stdin { _private: () }
+}
+
+/// Returns a handle to the standard output of the process.
+///
+/// Read [`Stdout`] for more information.
+pub fn stdout() -> Stdout {
+    Stdout::from_fd(libc::STDOUT_FILENO)
+}
+
+/// Returns a handle to the standard error of the process.
+///
+/// Read [`Stderr`] for more information.
+pub fn stderr() -> Stderr {
+    Stderr(Stdout::from_fd(libc::STDERR_FILENO))
+}
+
+/// A handle to the standard input of the process. It is created by [`stdin`].
+///
+/// Reading waits until the input is readable without blocking the worker, so CLI tools can interleave
+/// console IO with network IO in the same coroutine. All handles of the worker share one state,
+/// so only one coroutine of the worker can read the standard input at a time.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::buf::Buffer;
+/// use engine::io::stdin;
+///
+/// #[coro]
+/// fn read_commands() {
+///     let mut stdin = stdin();
+///     loop {
+///         let input: Buffer = wait!(stdin.read()).unwrap();
+///         if input.is_empty() {
+///             break;
+///         }
+///         println!("command: {}", String::from_utf8_lossy(input.as_ref()));
+///     }
+/// }
+/// ```
+pub struct Stdin {
+    _private: ()
+}
+
+impl Stdin {
+    /// Reads available bytes from the standard input. An empty [`Buffer`] means the end of the input.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait).
+    pub fn read(&mut self, res: *mut Result<Buffer, Error>) -> CoroutineImpl {
+        read_stdin(res)
+    }
+}
+
+#[coro(crate="crate")]
+fn read_stdin() -> Result<Buffer, Error> {
+    let stdin = stdin_state();
+    if stdin.is_pollable {
+        let res: Result<(), Error> = yield YieldStatus::wait_readable(stdin.is_registered, stdin.state);
+        stdin.is_registered = true;
+        if let Err(err) = res {
+            return Err(err);
+        }
+    }
+
+    let res: Result<Buffer, Error> = yield YieldStatus::file_read(libc::STDIN_FILENO, stdin.state, CURRENT_POSITION, null_mut(), false);
+    return res;
+}
+
+/// A handle to the standard output of the process. It is created by [`stdout`].
+///
+/// It is not buffered and it is not synchronized with [`println!`], so don't mix them for the same output.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::buf::buffer;
+/// use engine::io::{stdout, AsyncWrite};
+///
+/// #[coro]
+/// fn greet() {
+///     let mut buf = buffer();
+///     buf.append(b"Hello, world!\n");
+///     let res: Result<(), Error> = yield stdout().write_all(buf);
+/// }
+/// ```
+pub struct Stdout {
+    fd: RawFd,
+    data: Ptr<PollState>
+}
+
+impl Stdout {
+    pub(crate) fn from_fd(fd: RawFd) -> Self {
+        Self {
+            fd,
+            data: Ptr::new(PollState::new_empty(fd))
+        }
+    }
+}
+
+impl AsyncWrite<Buffer> for Stdout {
+    #[inline(always)]
+    fn write(&mut self, data: Buffer, res: *mut Result<Option<Buffer>, Error>) -> YieldStatus {
+        YieldStatus::file_write(self.fd, self.data, data, CURRENT_POSITION, null_mut(), res)
+    }
+
+    #[inline(always)]
+    fn write_all(&mut self, data: Buffer, res: *mut Result<(), Error>) -> YieldStatus {
+        YieldStatus::file_write_all(self.fd, self.data, data, CURRENT_POSITION, null_mut(), res)
+    }
+}
+
+impl Drop for Stdout {
+    fn drop(&mut self) {
+        // The standard fds are not closed, and the state is always empty, when no write is in progress.
+        unsafe { self.data.drop_in_place(); }
+    }
+}
+
+/// A handle to the standard error of the process. It is created by [`stderr`].
+///
+/// It works like [`Stdout`].
+pub struct Stderr(Stdout);
+
+impl AsyncWrite<Buffer> for Stderr {
+    #[inline(always)]
+    fn write(&mut self, data: Buffer, res: *mut Result<Option<Buffer>, Error>) -> YieldStatus {
+        self.0.write(data, res)
+    }
+
+    #[inline(always)]
+    fn write_all(&mut self, data: Buffer, res: *mut Result<(), Error>) -> YieldStatus {
+        self.0.write_all(data, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use crate::{test_local, wait};
+    use crate::buf::{buffer, Buffer};
+    use crate::io::{stdin, AsyncWrite};
+    use crate::io::stdio::Stdout;
+
+    fn pipe() -> (std::fs::File, std::fs::File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (std::fs::File::from_raw_fd(fds[0]), std::fs::File::from_raw_fd(fds[1])) }
+    }
+
+    #[test_local(crate="crate")]
+    fn test_stdout() {
+        let (mut reader, writer) = pipe();
+        let mut out = Stdout::from_fd(writer.as_raw_fd());
+        let mut buf = buffer();
+        buf.append(b"console line\n");
+        let res: Result<(), Error> = yield out.write_all(buf);
+        res.unwrap();
+        drop(out);
+        drop(writer);
+
+        let mut written = String::new();
+        reader.read_to_string(&mut written).unwrap();
+        assert_eq!(written, "console line\n");
+    }
+
+    #[test_local(crate="crate")]
+    fn test_stdin() {
+        let (reader, mut writer) = pipe();
+        let saved = unsafe { libc::dup(libc::STDIN_FILENO) };
+        assert!(saved >= 0);
+        assert!(unsafe { libc::dup2(reader.as_raw_fd(), libc::STDIN_FILENO) } >= 0);
+
+        let input = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            writer.write_all(b"command").unwrap();
+        });
+        let res: Result<Buffer, Error> = wait!(stdin().read());
+        let read = res.map(|buf| buf.as_ref().to_vec());
+        input.join().unwrap();
+
+        unsafe {
+            libc::dup2(saved, libc::STDIN_FILENO);
+            libc::close(saved);
+        }
+        assert_eq!(read.unwrap(), b"command");
+    }
+}
@@ -0,0 +1,5 @@
+//! This module is for kqueue. It provides [`KqueueSelector`] for working with the kqueue.
+
+pub(crate) mod selector;
+
+pub(crate) use selector::*;
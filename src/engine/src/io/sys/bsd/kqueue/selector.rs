@@ -0,0 +1,644 @@
+use std::{cmp, io, mem};
+use std::collections::{HashMap, HashSet};
+use std::intrinsics::{likely, unlikely};
+use std::time::{Duration, Instant};
+use std::os::fd::{AsRawFd, BorrowedFd, IntoRawFd, RawFd};
+use nix::errno::Errno;
+use nix::sys::event::{EventFilter, EventFlag, FilterFlag, KEvent, Kqueue};
+use nix::sys::socket::{accept, recvfrom, setsockopt};
+use nix::sys::socket::sockopt::{Linger, TcpNoDelay};
+use nix::unistd::write;
+use crate::cfg::config_write_turn_cap;
+use crate::io::selector::{FileOpSupport, Selector};
+use crate::io::PollState;
+use crate::coroutine::CoroutineImpl;
+use crate::io::state_trace::{self, StateEventKind};
+use crate::scheduler::Scheduler;
+use crate::net::TcpStream;
+use crate::{write_err, write_ok};
+use crate::utils::Ptr;
+
+pub(crate) const REQ_BUF_LEN: usize = 64 * 1024;
+pub(crate) const MAX_KQUEUE_EVENTS_RETURNED: usize = 256;
+
+/// The selector for BSD-like systems (macOS, FreeBSD and others). It works like
+/// [`EpolledSelector`](crate::io::sys::unix::EpolledSelector): sockets wait for readiness in the kqueue,
+/// and the syscalls are made, when they are ready.
+///
+/// kqueue can't wait for regular files, so it doesn't [`support files`](Selector::file_op_support) at all,
+/// and the scheduler runs file operations on its blocking pool.
+pub(crate) struct KqueueSelector {
+    kqueue: Kqueue,
+    unhandled_states: Vec<Ptr<PollState>>,
+    events: [KEvent; MAX_KQUEUE_EVENTS_RETURNED],
+    req_buf: [u8; REQ_BUF_LEN],
+    /// Partially written [`WriteAllTcpState`](crate::io::WriteAllTcpState)s. They are handled in FIFO order
+    /// at the next poll, so connections take turns.
+    pending_writes: Vec<Ptr<PollState>>,
+    /// The maximum number of bytes, that one [`WriteAllTcpState`](crate::io::WriteAllTcpState) writes per turn.
+    write_turn_cap: usize,
+    /// The fds, which writes wait for `EVFILT_WRITE`, because their send buffers are full, with true,
+    /// if the read filter of the fd is disabled for the write. Read [`KqueueSelector::wait_writable`].
+    write_waits: HashMap<RawFd, bool>,
+    /// The number of fds, that are watched by the kqueue.
+    registered: usize,
+    /// The sockets of [`ConnectTcpState`](crate::io::ConnectTcpState)s, that are watched for `EVFILT_WRITE`.
+    connecting: HashSet<RawFd>,
+    /// The deadlines of [`ConnectTcpState`](crate::io::ConnectTcpState)s with a timeout, that are not connected yet.
+    connects: Vec<(Instant, Ptr<PollState>)>
+}
+
+impl KqueueSelector {
+    pub(crate) fn new() -> io::Result<Self> {
+        Ok(KqueueSelector {
+            kqueue: Kqueue::new()?,
+            unhandled_states: Vec::with_capacity(8),
+            events: [KEvent::new(0, EventFilter::EVFILT_READ, EventFlag::empty(), FilterFlag::empty(), 0, 0); MAX_KQUEUE_EVENTS_RETURNED],
+            req_buf: [0; REQ_BUF_LEN],
+            pending_writes: Vec::new(),
+            write_turn_cap: config_write_turn_cap(),
+            write_waits: HashMap::new(),
+            registered: 0,
+            connecting: HashSet::new(),
+            connects: Vec::new()
+        })
+    }
+
+    /// Applies one change of the filter of the fd.
+    ///
+    /// The list of returned events is empty, so the kernel returns the error of the change instead of an `EV_ERROR` event.
+    fn change(&self, fd: RawFd, filter: EventFilter, flags: EventFlag, state_ptr: Ptr<PollState>) -> Result<(), Errno> {
+        let event = KEvent::new(fd as usize, filter, flags, FilterFlag::empty(), 0, state_ptr.as_u64() as isize);
+        self.kqueue.kevent(&[event], &mut [], None).map(drop)
+    }
+
+    /// Waits, until the socket of the connecting [`ConnectTcpState`](crate::io::ConnectTcpState) is writable:
+    /// then the connection is established or has failed.
+    fn watch_connect(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        // The state is handled again after the wake-up, and the socket is already watched.
+        if self.connecting.insert(fd) {
+            self.change(fd, EventFilter::EVFILT_WRITE, EventFlag::EV_ADD, state_ptr).expect("failed to add fd to kqueue");
+            self.registered += 1;
+        }
+    }
+
+    /// Waits, until the socket of the write state is writable, instead of trying the write again at every poll,
+    /// so a slow peer with a full receive buffer doesn't make the worker spin.
+    ///
+    /// While the write waits, the read filter of the fd is disabled, so incoming data doesn't wake the worker up
+    /// and doesn't hand the write state to [`KqueueSelector::handle_state`] twice.
+    /// The stream has only one operation at a time, so no read waits for it meanwhile.
+    fn wait_writable(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        if !self.write_waits.contains_key(&fd) {
+            let is_disabled = match self.change(fd, EventFilter::EVFILT_READ, EventFlag::EV_DISABLE, state_ptr) {
+                Ok(()) => true,
+                // The stream has not read yet (or has read to the end), so its fd has no read filter.
+                Err(Errno::ENOENT) => {
+                    self.registered += 1;
+                    false
+                }
+                Err(err) => panic!("failed to disable fd in kqueue: {} for fd: {}", err, fd)
+            };
+            self.write_waits.insert(fd, is_disabled);
+        }
+        self.change(fd, EventFilter::EVFILT_WRITE, EventFlag::EV_ADD, state_ptr).expect("failed to add fd to kqueue");
+    }
+
+    /// Restores the registration of the fd, when its write doesn't wait for `EVFILT_WRITE` anymore:
+    /// the write filter is removed, and the read filter is enabled again, if it was disabled for the write.
+    #[inline(always)]
+    fn finish_write(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        if likely(self.write_waits.is_empty()) {
+            return;
+        }
+        let Some(is_disabled) = self.write_waits.remove(&fd) else {
+            return;
+        };
+        self.change(fd, EventFilter::EVFILT_WRITE, EventFlag::EV_DELETE, state_ptr).expect("failed to remove fd from kqueue");
+        if is_disabled {
+            self.change(fd, EventFilter::EVFILT_READ, EventFlag::EV_ENABLE, state_ptr).expect("failed to enable fd in kqueue");
+        } else {
+            self.registered -= 1;
+        }
+    }
+
+    /// Stops watching the socket of the [`ConnectTcpState`](crate::io::ConnectTcpState), when its connect is finished.
+    /// The connected stream is registered again at its first read.
+    fn finish_connect(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        if self.connecting.remove(&fd) {
+            self.change(fd, EventFilter::EVFILT_WRITE, EventFlag::EV_DELETE, state_ptr).expect("failed to remove fd from kqueue");
+            self.registered -= 1;
+        }
+        if let Some(i) = self.connects.iter().position(|&(_, ptr)| ptr.as_u64() == state_ptr.as_u64()) {
+            self.connects.swap_remove(i);
+        }
+    }
+
+    /// Returns the time until the nearest deadline of a connect, if it is sooner than `timeout`.
+    #[inline(always)]
+    fn connect_timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let Some(deadline) = self.connects.iter().map(|&(deadline, _)| deadline).min() else {
+            return timeout;
+        };
+        let until = deadline.saturating_duration_since(Instant::now());
+        Some(timeout.map_or(until, |timeout| timeout.min(until)))
+    }
+
+    /// Fails the connects, which deadlines have passed, with [`ETIMEDOUT`](libc::ETIMEDOUT).
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[must_use]
+    fn expire_connects(&mut self, scheduler: &mut Scheduler) -> bool {
+        let now = Instant::now();
+        while let Some(i) = self.connects.iter().position(|&(deadline, _)| deadline <= now) {
+            let (_, state_ptr) = self.connects.swap_remove(i);
+            let PollState::ConnectTcp(state) = (unsafe { state_ptr.read() }) else {
+                panic!("[BUG] a connect deadline of not a ConnectTcp state. Please report this issue.")
+            };
+            unsafe { state_ptr.dealloc() };
+            self.finish_connect(state_ptr, state.socket.as_raw_fd());
+            write_err!(state.result, io::Error::from_raw_os_error(libc::ETIMEDOUT));
+            if unlikely(scheduler.handle_coroutine_state(self, state.coroutine)) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    #[inline(always)]
+    #[must_use]
+    fn handle_state(&mut self, state_ptr: Ptr<PollState>, scheduler: &mut Scheduler) -> bool {
+        state_trace::record_state(unsafe { state_ptr.as_ref() }, StateEventKind::Complete, 0);
+        let state = unsafe { state_ptr.read() };
+        match state {
+            PollState::Empty(_) => { false }
+
+            PollState::AcceptTcp(state) => {
+                // There is no `accept4` on macOS, so the flags are set after the accept.
+                let res = accept(state.fd);
+                if res.is_err() {
+                    let err = res.unwrap_err();
+                    if err == Errno::EAGAIN || err == Errno::EWOULDBLOCK {
+                        return false;
+                    }
+                    write_err!(state.result, io::Error::from_raw_os_error(err as i32));
+                    return scheduler.handle_coroutine_state(self, state.coroutine);
+                }
+
+                // The listener reads the fd from the state at the next accept, so the read one must not stay in the pointer.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
+                let incoming_fd = res.unwrap();
+                setup_connection(incoming_fd);
+                write_ok!(state.result, TcpStream::new(incoming_fd));
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::ConnectTcp(state) => {
+                let fd = state.socket.as_raw_fd();
+                // A failed connect leaves its error in the socket. Otherwise, connecting again tells,
+                // whether it is connected (`EISCONN`) or still connecting. The first handling starts the connect.
+                let res = match state.socket.take_error() {
+                    Ok(Some(err)) => Err(err),
+                    _ => state.socket.connect(&state.address)
+                };
+                let res = match res {
+                    Err(err) if err.raw_os_error() == Some(libc::EISCONN) => Ok(()),
+                    Err(err) if err.raw_os_error() == Some(libc::EINPROGRESS) || err.raw_os_error() == Some(libc::EALREADY) => {
+                        unsafe { state_ptr.write(PollState::ConnectTcp(state)) };
+                        self.watch_connect(state_ptr, fd);
+                        return false;
+                    }
+                    res => res
+                };
+
+                // The state has been read from the pointer, so only the memory is freed.
+                unsafe { state_ptr.dealloc() };
+                self.finish_connect(state_ptr, fd);
+                match res {
+                    Ok(()) => {
+                        setup_connection(fd);
+                        write_ok!(state.result, TcpStream::new(state.socket.into_raw_fd()));
+                    }
+                    Err(err) => write_err!(state.result, err)
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::PollTcp(state) => {
+                // The fd stays registered, so a later event must find the empty state.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
+                let res = recvfrom::<()>(state.fd, &mut self.req_buf);
+                if let Err(Errno::EAGAIN | Errno::EINTR) = res {
+                    // The wake-up was spurious or the read was interrupted, so the coroutine waits for the next event.
+                    unsafe { state_ptr.write(PollState::PollTcp(state)) };
+                    return false;
+                }
+                if res.is_err() {
+                    write_err!(state.result, io::Error::from_raw_os_error(res.unwrap_err_unchecked() as i32));
+                    return scheduler.handle_coroutine_state(self, state.coroutine)
+                }
+
+                let (n, _) = res.unwrap();
+                if n == 0 {
+                    // The socket stays readable after the end of the stream, so the level-triggered filter
+                    // would wake the worker up on every wait. The stream doesn't wait for the fd anymore.
+                    unsafe { state_ptr.write(PollState::new_eof(state.fd)) };
+                    self.deregister(state.fd);
+                }
+                // Reads are not coalesced: it is only an optimization of the Linux selectors.
+                write_ok!(state.result, mem::transmute(&self.req_buf[..n]));
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::ReadTcp(_) => {
+                panic!("[BUG] Kqueue Selector handled State::ReadTcp. Please report this issue.");
+            }
+
+            PollState::WriteTcp(mut state) => {
+                let fd = state.fd;
+                // The owner drops the state, so the read one must not stay in the pointer.
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
+                let res = unsafe { write(BorrowedFd::borrow_raw(fd), state.buffer.as_ref()) };
+                if let Err(Errno::EAGAIN) = res {
+                    unsafe { state_ptr.write(PollState::WriteTcp(state)) };
+                    self.wait_writable(state_ptr, fd);
+                    return false;
+                }
+                self.finish_write(state_ptr, fd);
+
+                if res.is_ok() {
+                    let written = unsafe { res.unwrap_unchecked() };
+                    if written == state.buffer.len() {
+                        write_ok!(state.result, None);
+                    } else {
+                        state.buffer.set_offset(state.buffer.offset() + written);
+                        write_ok!(state.result, Some(state.buffer));
+                    }
+                } else {
+                    write_err!(state.result, io::Error::from_raw_os_error(res.unwrap_err_unchecked() as i32));
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::WriteAllTcp(mut state) => {
+                let fd = state.fd;
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
+                let mut res;
+                let mut written = 0;
+                loop {
+                    if written >= self.write_turn_cap {
+                        // The turn is over, the rest is written after other connections.
+                        // The socket is writable, so it doesn't wait for `EVFILT_WRITE`, that would handle the state twice.
+                        self.finish_write(state_ptr, fd);
+                        unsafe { state_ptr.write(PollState::WriteAllTcp(state)) };
+                        self.pending_writes.push(state_ptr);
+                        return false;
+                    }
+
+                    let len = cmp::min(state.buffer.len(), self.write_turn_cap - written);
+                    res = unsafe { write(BorrowedFd::borrow_raw(fd), &state.buffer.as_ref()[..len]) };
+                    if unlikely(res.is_err()) {
+                        let err = unsafe { res.unwrap_err_unchecked() };
+                        if err == Errno::EAGAIN {
+                            // The send buffer of the socket is full, so the rest is written, when it is writable again.
+                            unsafe { state_ptr.write(PollState::WriteAllTcp(state)) };
+                            self.wait_writable(state_ptr, fd);
+                            return false;
+                        }
+                        self.finish_write(state_ptr, fd);
+                        write_err!(state.result, io::Error::from_raw_os_error(err as i32));
+                        return scheduler.handle_coroutine_state(self, state.coroutine);
+                    }
+
+                    let n = unsafe { res.unwrap_unchecked() };
+                    written += n;
+                    state.buffer.set_offset(state.buffer.offset() + n);
+                    if state.buffer.len() == 0 {
+                        break;
+                    }
+                }
+                self.finish_write(state_ptr, fd);
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::CloseTcp(state) => {
+                let fd = state.fd;
+                // The owner drops the state after closing, so it must not be dropped twice.
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
+                // Closing the fd removes its filters from the kqueue.
+                if let Some(false) = self.write_waits.remove(&fd) {
+                    self.registered -= 1;
+                }
+                self.deregister(fd);
+                close_connection(fd);
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            PollState::WaitReadable(state) => {
+                // The fd stays registered, so the next wait only writes the state.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
+                write_ok!(state.result, ());
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            state => {
+                panic!("[BUG] Kqueue Selector handled {:?}, but it doesn't support files. Please report this issue.", state);
+            }
+        }
+    }
+}
+
+/// Sets an accepted or connected socket up for non-blocking IO.
+///
+/// # Panics
+///
+/// If fcntl or setsockopt fails. This is impossible if provided with a valid socket fd.
+fn setup_connection(fd: RawFd) {
+    setsockopt(&unsafe { BorrowedFd::borrow_raw(fd) }, TcpNoDelay, &true).expect("cannot set TCP_NODELAY");
+    // A write to a closed connection returns `EPIPE` instead of killing the process with `SIGPIPE`.
+    #[cfg(target_vendor = "apple")]
+    {
+        let optval: libc::c_int = 1;
+        let ret = unsafe {
+            libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_NOSIGPIPE, &optval as *const _ as _, mem::size_of_val(&optval) as _)
+        };
+        assert!(ret == 0, "cannot set SO_NOSIGPIPE: {}", io::Error::last_os_error());
+    }
+    let ret = unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) };
+    assert!(ret == 0, "cannot set nonblocking: {}", io::Error::last_os_error());
+    let ret = unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    assert!(ret == 0, "cannot set close-on-exec: {}", io::Error::last_os_error());
+}
+
+/// Closes a connection with a reset like [`EpolledSelector`](crate::io::sys::unix::EpolledSelector).
+///
+/// # Panics
+///
+/// If the syscall fails. For example, if the connection is already closed.
+fn close_connection(fd: RawFd) {
+    const OPTVAL_SOLINGER_TIMEOUT: libc::linger = libc::linger { l_onoff: 1, l_linger: 0 };
+    setsockopt(&unsafe { BorrowedFd::borrow_raw(fd) }, Linger, &OPTVAL_SOLINGER_TIMEOUT).expect("");
+    nix::unistd::close(fd).expect("Failed to close conn_fd");
+}
+
+impl Selector for KqueueSelector {
+    #[inline(always)]
+    fn supports_multishot(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn file_op_support(&self) -> FileOpSupport {
+        FileOpSupport::NONE
+    }
+
+    #[inline(always)]
+    fn poll(&mut self, scheduler: &mut Scheduler, mut timeout: Option<Duration>) -> Result<bool, io::Error> {
+        self.unhandled_states.append(&mut self.pending_writes);
+        // Handled states can wake coroutines up, so they are not delayed by the wait.
+        if !self.unhandled_states.is_empty() {
+            timeout = Some(Duration::ZERO);
+        }
+        timeout = self.connect_timeout(timeout);
+        // The length is re-read on every iteration, because handling a state can push a new one.
+        let mut i = 0;
+        while i < self.unhandled_states.len() {
+            let state_ptr = self.unhandled_states[i];
+            if unlikely(self.handle_state(state_ptr, scheduler)) {
+                return Ok(true);
+            }
+            i += 1;
+        }
+        self.unhandled_states.clear();
+
+        let timeout = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long
+        });
+        let num_incoming_events = match self.kqueue.kevent(&[], &mut self.events, timeout) {
+            Ok(num) => num,
+            // A signal is not a failure, the worker polls again at the next background work.
+            Err(Errno::EINTR) => return Ok(false),
+            Err(errno) => return Err(errno.into())
+        };
+
+        for i in 0..num_incoming_events {
+            let event = &self.events[i];
+            if unlikely(self.handle_state(Ptr::from(event.udata() as u64), scheduler)) {
+                return Ok(true);
+            }
+        }
+        if unlikely(!self.connects.is_empty()) {
+            return Ok(self.expire_connects(scheduler));
+        }
+        Ok(false)
+    }
+
+    #[inline(always)]
+    fn register(&mut self, state_ptr: Ptr<PollState>) {
+        state_trace::record_state(unsafe { state_ptr.as_ref() }, StateEventKind::Submit, 0);
+        // Writes and closes are done in the poll without waiting.
+        if matches!(unsafe { state_ptr.as_ref() }, PollState::WriteTcp(_) | PollState::WriteAllTcp(_) | PollState::CloseTcp(_)) {
+            self.unhandled_states.push(state_ptr);
+            return;
+        }
+        // The connect is started in the poll, and the socket is watched, if it doesn't connect at once.
+        if let PollState::ConnectTcp(state) = unsafe { state_ptr.as_ref() } {
+            if let Err(err) = state.socket.set_nonblocking(true) {
+                panic!("failed to make the socket nonblocking: {}", err);
+            }
+            if let Some(timeout) = state.timeout {
+                self.connects.push((Instant::now() + timeout, state_ptr));
+            }
+            self.unhandled_states.push(state_ptr);
+            return;
+        }
+
+        let fd = unsafe { state_ptr.as_ref() }.fd();
+        if let Err(err) = self.change(fd, EventFilter::EVFILT_READ, EventFlag::EV_ADD, state_ptr) {
+            panic!("failed to add fd to kqueue: {} for fd: {}", err, fd);
+        }
+        self.registered += 1;
+    }
+
+    #[inline(always)]
+    fn deregister(&mut self, fd: RawFd) {
+        // The fd is already removed, if its stream has been read to the end.
+        match self.change(fd, EventFilter::EVFILT_READ, EventFlag::EV_DELETE, Ptr::null()) {
+            Ok(()) => self.registered -= 1,
+            Err(Errno::ENOENT) => (),
+            Err(err) => panic!("failed to remove fd from kqueue: {} for fd: {}", err, fd)
+        }
+    }
+
+    fn register_file(&mut self, _fd: RawFd) -> Result<bool, io::Error> {
+        Ok(false)
+    }
+
+    fn cancel(&mut self, state_ref: Ptr<PollState>) -> Option<CoroutineImpl> {
+        // A write, that waits for `EVFILT_WRITE`, can wait forever for a peer, that doesn't read.
+        if !self.write_waits.is_empty() && self.write_waits.contains_key(&unsafe { state_ref.as_ref() }.fd()) {
+            let (fd, coroutine) = match unsafe { state_ref.read() } {
+                PollState::WriteTcp(state) => {
+                    write_err!(state.result, io::Error::from_raw_os_error(libc::ECANCELED));
+                    (state.fd, state.coroutine)
+                }
+                PollState::WriteAllTcp(state) => {
+                    write_err!(state.result, io::Error::from_raw_os_error(libc::ECANCELED));
+                    (state.fd, state.coroutine)
+                }
+                state => {
+                    unsafe { state_ref.write(state) };
+                    return PollState::cancel(state_ref);
+                }
+            };
+            unsafe { state_ref.write(PollState::new_empty(fd)) };
+            self.finish_write(state_ref, fd);
+            return Some(coroutine);
+        }
+        // The fd stays registered with the empty state, so a later event is ignored.
+        PollState::cancel(state_ref)
+    }
+
+    #[inline(always)]
+    fn sleep(&mut self, _dur: Duration, coroutine: CoroutineImpl) -> Option<CoroutineImpl> {
+        Some(coroutine)
+    }
+
+    #[inline(always)]
+    fn ring_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    #[inline(always)]
+    fn pending_states(&self) -> usize {
+        self.registered + self.unhandled_states.len() + self.pending_writes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind, Read, Write};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::coro;
+    use crate::buf::buffer;
+    use crate::cfg::{config, SelectorType};
+    use crate::io::{AsyncRead, AsyncWrite};
+    use crate::local::Local;
+    use crate::net::TcpStream;
+    use crate::run::run_on_core_with_config;
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+
+    #[coro(crate="crate")]
+    fn connect_all(addrs: Vec<SocketAddr>) -> Vec<Result<(), ErrorKind>> {
+        let mut results = Vec::new();
+        for addr in addrs {
+            let res: Result<TcpStream, Error> = yield TcpStream::connect_timeout(addr, Duration::from_millis(100));
+            results.push(res.map(drop).map_err(|err| err.kind()));
+        }
+        return results;
+    }
+
+    #[test]
+    fn test_connect() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Nobody listens on the port of the closed listener.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Kqueue);
+        let results = run_on_core_with_config(move |res| connect_all(vec![addr, closed], res), core, cfg).unwrap();
+        assert_eq!(results, Some(vec![Ok(()), Err(ErrorKind::ConnectionRefused)]));
+        drop(listener);
+    }
+
+    #[coro(crate="crate")]
+    fn echo_once(addr: SocketAddr) -> Vec<u8> {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut buf = buffer();
+        buf.append(b"ping");
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        res.unwrap();
+        let res: Result<&'static [u8], Error> = yield stream.read();
+        return res.unwrap().to_vec();
+    }
+
+    #[test]
+    fn test_read_and_write() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = [0u8; 4];
+            stream.read_exact(&mut received).unwrap();
+            stream.write_all(b"pong").unwrap();
+            received
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Kqueue);
+        let read = run_on_core_with_config(move |res| echo_once(addr, res), core, cfg).unwrap();
+        assert_eq!(read, Some(b"pong".to_vec()));
+        assert_eq!(&peer.join().unwrap(), b"ping");
+    }
+
+    /// Counts the polls of the worker in 100 milliseconds after the start of the write.
+    #[coro(crate="crate")]
+    fn count_polls(polls: Local<u64>) {
+        yield sleep(Duration::from_millis(10));
+        let start = local_scheduler().metrics().polls_total;
+        yield sleep(Duration::from_millis(100));
+        *polls.get_mut() = local_scheduler().metrics().polls_total - start;
+    }
+
+    #[coro(crate="crate")]
+    fn write_to_slow_peer(addr: SocketAddr, len: usize) -> (bool, u64) {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let polls = Local::new(0);
+        local_scheduler().sched(count_polls(polls.clone(), null_mut()));
+        let mut buf = buffer();
+        buf.append(&vec![7u8; len]);
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        // The stream is closed with a reset, so the peer must have read everything.
+        yield sleep(Duration::from_millis(100));
+        return (res.is_ok(), *polls.get());
+    }
+
+    #[test]
+    fn test_write_waits_writable() {
+        const LEN: usize = 32 * 1024 * 1024;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The send buffer is filled, while the peer doesn't read.
+            std::thread::sleep(Duration::from_millis(200));
+            let mut received = vec![0u8; LEN];
+            stream.read_exact(&mut received).unwrap();
+            received.iter().all(|&b| b == 7)
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Kqueue);
+        let (is_written, polls) = run_on_core_with_config(move |res| write_to_slow_peer(addr, LEN, res), core, cfg).unwrap().unwrap();
+        assert!(is_written);
+        assert!(peer.join().unwrap());
+        // The write waits for `EVFILT_WRITE` instead of trying again at every poll.
+        assert!(polls < 100, "{} polls", polls);
+    }
+}
@@ -0,0 +1,5 @@
+//! This module is for BSD-like systems (macOS, FreeBSD and others), that have neither epoll nor io_uring.
+
+pub(crate) mod kqueue;
+
+pub(crate) use kqueue::*;
@@ -1,3 +1,12 @@
 #[cfg(unix)]
 pub mod unix;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+pub(crate) mod bsd;
 pub(crate) mod fd;
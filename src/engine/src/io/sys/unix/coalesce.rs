@@ -0,0 +1,28 @@
+//! This module contains read coalescing, that is shared by selectors.
+use std::cmp;
+use std::os::fd::RawFd;
+
+/// How many bytes are received by one `recv` of [`recv_more`] at most.
+const RECV_CHUNK_LEN: usize = 16 * 1024;
+
+/// Appends bytes, that are already in the socket, to `buf` with non-blocking `recv`s,
+/// until the socket is drained or `buf` has `max_len` bytes.
+///
+/// Errors and the end of the stream are not reported, because the next read reports them.
+pub(crate) fn recv_more(fd: RawFd, buf: &mut Vec<u8>, max_len: usize) {
+    while buf.len() < max_len {
+        let len = buf.len();
+        let want = cmp::min(max_len - len, RECV_CHUNK_LEN);
+        buf.reserve(want);
+        let n = unsafe { libc::recv(fd, buf.as_mut_ptr().add(len).cast(), want, libc::MSG_DONTWAIT) };
+        if n <= 0 {
+            return;
+        }
+
+        unsafe { buf.set_len(len + n as usize) };
+        // A short read means the socket is drained, so the next recv would only return EAGAIN.
+        if (n as usize) < want {
+            return;
+        }
+    }
+}
@@ -1,7 +1,11 @@
-//! This module is for unix epoll. It provides [`EpolledSelector`] for working with the epoll.
+//! This module is for unix epoll. It provides `EpolledSelector` for working with the epoll.
+//!
+//! The epoll is Linux-only, so on other systems only the network functions are compiled.
 
 pub(crate) mod net;
 pub(crate) mod check_error;
+#[cfg(target_os = "linux")]
 pub(crate) mod selector;
 
+#[cfg(target_os = "linux")]
 pub(crate) use selector::*;
\ No newline at end of file
@@ -1,10 +1,16 @@
 //! This module contains functions for working with the network with the epoll.
 
 use std::net::{IpAddr, SocketAddr};
+#[cfg(not(target_os = "linux"))]
+use std::os::fd::AsFd;
 use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
-use libc::{linger, O_NONBLOCK, SYS_fcntl, F_SETFL, syscall};
+use libc::{c_long, O_NONBLOCK, F_SETFL};
+#[cfg(target_os = "linux")]
+use libc::linger;
 use nix::sys::socket::{AddressFamily, Backlog, listen, setsockopt, SockType, SockFlag, SockProtocol, bind, SockaddrIn};
-use nix::sys::socket::sockopt::{Linger, ReuseAddr, ReusePort, TcpNoDelay};
+use nix::sys::socket::sockopt::{ReuseAddr, ReusePort};
+#[cfg(target_os = "linux")]
+use nix::sys::socket::sockopt::{Linger, TcpNoDelay};
 use crate::io::sys::unix::epoll::check_error::check_error;
 
 /// The value of `SO_REUSEADDR`, `TcpNoDelay` and `SO_REUSEPORT`
@@ -22,12 +28,25 @@ pub(crate) fn get_tcp_listener_fd(socket_addr: SocketAddr) -> OwnedFd {
         }
         IpAddr::V6(_) => {panic!("IPv6 is not supported")}
     }
+    #[cfg(target_os = "linux")]
     let fd = nix::sys::socket::socket(
         AddressFamily::Inet,
         SockType::Stream,
         SockFlag::SOCK_NONBLOCK,
         SockProtocol::Tcp
     ).expect("cannot create socket");
+    // There is no `SOCK_NONBLOCK` on macOS, so the flag is set after the creation.
+    #[cfg(not(target_os = "linux"))]
+    let fd = {
+        let fd = nix::sys::socket::socket(
+            AddressFamily::Inet,
+            SockType::Stream,
+            SockFlag::empty(),
+            SockProtocol::Tcp
+        ).expect("cannot create socket");
+        unsafe { set_nonblocking(&fd.as_fd()) };
+        fd
+    };
 
     setsockopt(&fd, ReuseAddr, &OPTVAL).expect("cannot set SO_REUSEADDR");
     setsockopt(&fd, ReusePort, &OPTVAL).expect("cannot set SO_REUSEPORT");
@@ -43,6 +62,7 @@ pub(crate) fn get_tcp_listener_fd(socket_addr: SocketAddr) -> OwnedFd {
 /// # Panics
 ///
 /// If SETSOCKOPT fails or fcntl fails. This is impossible if provided with a valid socket fd.
+#[cfg(target_os = "linux")]
 #[inline]
 pub(crate) fn setup_connection(fd: &BorrowedFd) {
     unsafe {
@@ -65,7 +85,7 @@ pub(crate) fn setup_connection(fd: &BorrowedFd) {
 #[inline]
 pub(crate) unsafe fn set_nonblocking(fd: &BorrowedFd) {
     unsafe {
-        check_error(syscall(SYS_fcntl, fd.as_raw_fd(), F_SETFL, O_NONBLOCK), "cannot set nonblocking", true);
+        check_error(libc::fcntl(fd.as_raw_fd(), F_SETFL, O_NONBLOCK) as c_long, "cannot set nonblocking", true);
     }
 }
 
@@ -74,6 +94,7 @@ pub(crate) unsafe fn set_nonblocking(fd: &BorrowedFd) {
 /// # Panics
 ///
 /// If the syscall fails. For example, if the connection is already closed.
+#[cfg(target_os = "linux")]
 #[inline(always)]
 pub(crate) unsafe fn close_connection(conn_fd: &BorrowedFd) {
     const OPTVAL_SOLINGER_TIMEOUT: linger = linger { l_onoff: 1, l_linger: 0 };
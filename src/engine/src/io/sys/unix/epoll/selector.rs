@@ -1,17 +1,22 @@
-use std::{io, mem};
-use std::intrinsics::unlikely;
-use std::io::Error;
-use std::os::fd::{BorrowedFd, RawFd};
+use std::{cmp, io, mem};
+use std::collections::HashMap;
+use std::intrinsics::{likely, unlikely};
+use std::time::{Duration, Instant};
+use std::os::fd::{AsRawFd, BorrowedFd, IntoRawFd, RawFd};
 use libc::{CLONE_FILES, SYS_unshare, syscall};
 use nix::errno::Errno;
 use nix::sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags, EpollTimeout};
 use nix::sys::socket::{accept4, recvfrom, SockFlag};
 use nix::unistd::write;
-use crate::io::selector::Selector;
+use crate::cfg::config_write_turn_cap;
+use crate::io::selector::{FileOpSupport, Selector};
 use crate::io::sys::unix::epoll::net::setup_connection;
-use crate::io::sys::unix::check_error::check_error;
+use crate::io::sys::unix::coalesce::recv_more;
+use crate::io::sys::unix::errno::errno_error;
 use crate::io::sys::unix::net;
 use crate::io::PollState;
+use crate::coroutine::CoroutineImpl;
+use crate::io::state_trace::{self, StateEventKind};
 use crate::scheduler::Scheduler;
 use crate::net::TcpStream;
 use crate::{write_err, write_ok};
@@ -25,25 +30,144 @@ pub(crate) struct EpolledSelector {
     epoll: Epoll,
     unhandled_states: Vec<Ptr<PollState>>,
     events: [EpollEvent; MAX_EPOLL_EVENTS_RETURNED],
-    req_buf: [u8; REQ_BUF_LEN]
+    req_buf: [u8; REQ_BUF_LEN],
+    /// The buffer for coalesced reads. Like `req_buf`, a slice of it is valid until the next read.
+    coalesce_buf: Vec<u8>,
+    /// Partially written [`WriteAllTcpState`](crate::io::WriteAllTcpState)s. They are handled in FIFO order
+    /// at the next poll, so connections take turns.
+    pending_writes: Vec<Ptr<PollState>>,
+    /// The maximum number of bytes, that one [`WriteAllTcpState`](crate::io::WriteAllTcpState) writes per turn.
+    write_turn_cap: usize,
+    /// The fds, which writes wait for `EPOLLOUT`, because their send buffers are full, with true,
+    /// if the fd was added to epoll only for the write. Read [`EpolledSelector::wait_writable`].
+    write_waits: HashMap<RawFd, bool>,
+    /// The number of fds, that are added to epoll.
+    registered: usize,
+    /// The deadlines of [`ConnectTcpState`](crate::io::ConnectTcpState)s with a timeout, that are not connected yet.
+    connects: Vec<(Instant, Ptr<PollState>)>
 }
 
 impl EpolledSelector {
     pub(crate) fn new() -> io::Result<Self> {
         let epoll = Epoll::new(EpollCreateFlags::empty())?;
-        unsafe { check_error(syscall(SYS_unshare, CLONE_FILES), "failed to set CLONE_FILES", true) };
+        if unsafe { syscall(SYS_unshare, CLONE_FILES) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
 
         Ok(EpolledSelector {
             epoll,
             unhandled_states: Vec::with_capacity(8),
             events: [EpollEvent::empty(); MAX_EPOLL_EVENTS_RETURNED],
-            req_buf: [0;  REQ_BUF_LEN]
+            req_buf: [0;  REQ_BUF_LEN],
+            coalesce_buf: Vec::new(),
+            pending_writes: Vec::new(),
+            write_turn_cap: config_write_turn_cap(),
+            write_waits: HashMap::new(),
+            registered: 0,
+            connects: Vec::new()
         })
     }
 
+    /// Waits, until the socket of the connecting [`ConnectTcpState`](crate::io::ConnectTcpState) is writable:
+    /// then the connection is established or has failed.
+    fn watch_connect(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        let res = unsafe {
+            self.epoll.add(BorrowedFd::borrow_raw(fd), EpollEvent::new(EpollFlags::EPOLLOUT, state_ptr.as_u64()))
+        };
+        match res {
+            Ok(()) => self.registered += 1,
+            // The state is handled again after the wake-up, and the socket is already watched.
+            Err(Errno::EEXIST) => (),
+            Err(err) => panic!("failed to add fd to epoll: {} for fd: {}", err, fd)
+        }
+    }
+
+    /// Waits, until the socket of the write state is writable, instead of trying the write again at every poll,
+    /// so a slow peer with a full receive buffer doesn't make the worker spin.
+    ///
+    /// While the write waits, the fd is watched only for `EPOLLOUT`, so incoming data doesn't wake the worker up.
+    /// The stream has only one operation at a time, so no read waits for it meanwhile.
+    fn wait_writable(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        let mut event = EpollEvent::new(EpollFlags::EPOLLOUT, state_ptr.as_u64());
+        let is_added = match self.epoll.modify(unsafe { BorrowedFd::borrow_raw(fd) }, &mut event) {
+            Ok(()) => false,
+            // The stream has not read yet (or has read to the end), so its fd is not in epoll.
+            Err(Errno::ENOENT) => {
+                unsafe { self.epoll.add(BorrowedFd::borrow_raw(fd), event) }.expect("failed to add fd to epoll");
+                self.registered += 1;
+                true
+            }
+            Err(err) => panic!("failed to modify fd in epoll: {} for fd: {}", err, fd)
+        };
+        self.write_waits.entry(fd).or_insert(is_added);
+    }
+
+    /// Restores the registration of the fd, when its write doesn't wait for `EPOLLOUT` anymore:
+    /// the fd is removed from epoll, if it was added for the write, or it is watched for `EPOLLIN` again.
+    #[inline(always)]
+    fn finish_write(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        if likely(self.write_waits.is_empty()) {
+            return;
+        }
+        match self.write_waits.remove(&fd) {
+            Some(true) => self.deregister(fd),
+            Some(false) => {
+                let mut event = EpollEvent::new(EpollFlags::EPOLLIN, state_ptr.as_u64());
+                self.epoll.modify(unsafe { BorrowedFd::borrow_raw(fd) }, &mut event).expect("failed to modify fd in epoll");
+            }
+            None => ()
+        }
+    }
+
+    /// Stops watching the socket of the [`ConnectTcpState`](crate::io::ConnectTcpState), when its connect is finished.
+    /// The connected stream is registered again at its first read.
+    fn finish_connect(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        if unsafe { self.epoll.delete(BorrowedFd::borrow_raw(fd)) }.is_ok() {
+            self.registered -= 1;
+        }
+        if let Some(i) = self.connects.iter().position(|&(_, ptr)| ptr.as_u64() == state_ptr.as_u64()) {
+            self.connects.swap_remove(i);
+        }
+    }
+
+    /// Returns the time until the nearest deadline of a connect, if it is sooner than `timeout`.
+    #[inline(always)]
+    fn connect_timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let Some(deadline) = self.connects.iter().map(|&(deadline, _)| deadline).min() else {
+            return timeout;
+        };
+        let until = deadline.saturating_duration_since(Instant::now());
+        Some(timeout.map_or(until, |timeout| timeout.min(until)))
+    }
+
+    /// Fails the connects, which deadlines have passed, with [`ETIMEDOUT`](libc::ETIMEDOUT).
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[must_use]
+    fn expire_connects(&mut self, scheduler: &mut Scheduler) -> bool {
+        let now = Instant::now();
+        while let Some(i) = self.connects.iter().position(|&(deadline, _)| deadline <= now) {
+            let (_, state_ptr) = self.connects.swap_remove(i);
+            let PollState::ConnectTcp(state) = (unsafe { state_ptr.read() }) else {
+                panic!("[BUG] a connect deadline of not a ConnectTcp state. Please report this issue.")
+            };
+            unsafe { state_ptr.dealloc() };
+            self.finish_connect(state_ptr, state.socket.as_raw_fd());
+            write_err!(state.result, errno_error(libc::ETIMEDOUT));
+            if unlikely(scheduler.handle_coroutine_state(self, state.coroutine)) {
+                return true;
+            }
+        }
+
+        false
+    }
+
     #[inline(always)]
     #[must_use]
     fn handle_state(&mut self, state_ptr: Ptr<PollState>, scheduler: &mut Scheduler) -> bool {
+        state_trace::record_state(unsafe { state_ptr.as_ref() }, StateEventKind::Complete, 0);
         let state = unsafe { state_ptr.read() };
         match state {
             PollState::Empty(_) => { false }
@@ -55,10 +179,12 @@ impl EpolledSelector {
                     if err == Errno::EAGAIN || err == Errno::EWOULDBLOCK {
                         return false;
                     }
-                    write_err!(state.result, Error::from(err));
+                    write_err!(state.result, errno_error(err as i32));
                     return scheduler.handle_coroutine_state(self, state.coroutine);
                 }
 
+                // The listener reads the fd from the state at the next accept, so the read one must not stay in the pointer.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
                 let incoming_fd = res.unwrap();
                 unsafe { setup_connection(&BorrowedFd::borrow_raw(incoming_fd)); }
                 write_ok!(state.result, TcpStream::new(incoming_fd));
@@ -66,19 +192,64 @@ impl EpolledSelector {
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
 
-            PollState::ConnectTcp(_state) => {
-                todo!();
+            PollState::ConnectTcp(state) => {
+                let fd = state.socket.as_raw_fd();
+                // A failed connect leaves its error in the socket. Otherwise, connecting again tells,
+                // whether it is connected (`EISCONN`) or still connecting. The first handling starts the connect.
+                let res = match state.socket.take_error() {
+                    Ok(Some(err)) => Err(err),
+                    _ => state.socket.connect(&state.address)
+                };
+                let res = match res {
+                    Err(err) if err.raw_os_error() == Some(libc::EISCONN) => Ok(()),
+                    Err(err) if err.raw_os_error() == Some(libc::EINPROGRESS) || err.raw_os_error() == Some(libc::EALREADY) => {
+                        unsafe { state_ptr.write(PollState::ConnectTcp(state)) };
+                        self.watch_connect(state_ptr, fd);
+                        return false;
+                    }
+                    res => res
+                };
+
+                // The state has been read from the pointer, so only the memory is freed.
+                unsafe { state_ptr.dealloc() };
+                self.finish_connect(state_ptr, fd);
+                match res {
+                    Ok(()) => write_ok!(state.result, TcpStream::new(state.socket.into_raw_fd())),
+                    Err(err) => write_err!(state.result, err)
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
             }
 
             PollState::PollTcp(state) => {
+                // The fd stays registered, so a later event must find the empty state.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
                 let res = recvfrom::<()>(state.fd, &mut self.req_buf);
+                if let Err(Errno::EAGAIN | Errno::EINTR) = res {
+                    // The wake-up was spurious or the read was interrupted, so the coroutine waits for the next event.
+                    unsafe { state_ptr.write(PollState::PollTcp(state)) };
+                    return false;
+                }
                 if res.is_err() {
-                    write_err!(state.result, Error::from(res.unwrap_err_unchecked()));
+                    write_err!(state.result, errno_error(res.unwrap_err_unchecked() as i32));
                     return scheduler.handle_coroutine_state(self, state.coroutine)
                 }
 
                 let (n, _) = res.unwrap();
-                write_ok!(state.result, mem::transmute(&self.req_buf[..n]));
+                if n == 0 {
+                    // The socket stays readable after the end of the stream, so the level-triggered epoll
+                    // would wake the worker up on every wait. The stream doesn't wait for the fd anymore.
+                    unsafe { state_ptr.write(PollState::new_eof(state.fd)) };
+                    self.deregister(state.fd);
+                }
+                if state.coalesce > n && n > 0 {
+                    self.coalesce_buf.clear();
+                    self.coalesce_buf.extend_from_slice(&self.req_buf[..n]);
+                    recv_more(state.fd, &mut self.coalesce_buf, state.coalesce);
+                    write_ok!(state.result, mem::transmute::<&[u8], &'static [u8]>(self.coalesce_buf.as_slice()));
+                } else {
+                    write_ok!(state.result, mem::transmute::<&[u8], &'static [u8]>(&self.req_buf[..n]));
+                }
 
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
@@ -89,7 +260,15 @@ impl EpolledSelector {
 
             PollState::WriteTcp(mut state) => {
                 let fd = state.fd;
+                // The owner drops the state, so the read one must not stay in the pointer.
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
                 let res = unsafe { write(BorrowedFd::borrow_raw(fd), state.buffer.as_ref()) };
+                if let Err(Errno::EAGAIN) = res {
+                    unsafe { state_ptr.write(PollState::WriteTcp(state)) };
+                    self.wait_writable(state_ptr, fd);
+                    return false;
+                }
+                self.finish_write(state_ptr, fd);
 
                 if res.is_ok() {
                     let written = unsafe { res.unwrap_unchecked() };
@@ -100,7 +279,7 @@ impl EpolledSelector {
                         write_ok!(state.result, Some(state.buffer));
                     }
                 } else {
-                    write_err!(state.result, Error::from(res.unwrap_err_unchecked()));
+                    write_err!(state.result, errno_error(res.unwrap_err_unchecked() as i32));
                 }
 
                 scheduler.handle_coroutine_state(self, state.coroutine)
@@ -108,20 +287,43 @@ impl EpolledSelector {
 
             PollState::WriteAllTcp(mut state) => {
                 let fd = state.fd;
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
                 let mut res;
+                let mut written = 0;
                 loop {
-                    res = unsafe { write(BorrowedFd::borrow_raw(fd), state.buffer.as_ref()) };
+                    if written >= self.write_turn_cap {
+                        // The turn is over, the rest is written after other connections.
+                        // The socket is writable, so it doesn't wait for `EPOLLOUT`, that would handle the state twice.
+                        self.finish_write(state_ptr, fd);
+                        unsafe { state_ptr.write(PollState::WriteAllTcp(state)) };
+                        self.pending_writes.push(state_ptr);
+                        return false;
+                    }
+
+                    let len = cmp::min(state.buffer.len(), self.write_turn_cap - written);
+                    res = unsafe { write(BorrowedFd::borrow_raw(fd), &state.buffer.as_ref()[..len]) };
                     if unlikely(res.is_err()) {
-                        write_err!(state.result, Error::from(res.unwrap_err_unchecked()));
+                        let err = unsafe { res.unwrap_err_unchecked() };
+                        if err == Errno::EAGAIN {
+                            // The send buffer of the socket is full, so the rest is written, when it is writable again.
+                            unsafe { state_ptr.write(PollState::WriteAllTcp(state)) };
+                            self.wait_writable(state_ptr, fd);
+                            return false;
+                        }
+                        self.finish_write(state_ptr, fd);
+                        write_err!(state.result, errno_error(err as i32));
                         scheduler.handle_coroutine_state(self, state.coroutine);
                         return false;
                     }
 
-                    state.buffer.set_offset(state.buffer.offset() + unsafe { res.unwrap_unchecked() });
+                    let n = unsafe { res.unwrap_unchecked() };
+                    written += n;
+                    state.buffer.set_offset(state.buffer.offset() + n);
                     if state.buffer.len() == 0 {
                         break;
                     }
                 }
+                self.finish_write(state_ptr, fd);
                 write_ok!(state.result, ());
 
                 scheduler.handle_coroutine_state(self, state.coroutine)
@@ -129,35 +331,72 @@ impl EpolledSelector {
 
             PollState::CloseTcp(state) => {
                 let fd = state.fd;
-                let _ = self.deregister(state.fd);
+                // The owner drops the state after closing, so it must not be dropped twice.
+                unsafe { state_ptr.write(PollState::new_empty(fd)) };
+                self.write_waits.remove(&fd);
+                self.deregister(fd);
                 unsafe { net::close_connection(&BorrowedFd::borrow_raw(fd)); }
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
+
+            PollState::WaitReadable(state) => {
+                // The fd stays registered, so the next wait only writes the state.
+                unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
+                write_ok!(state.result, ());
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+
+            _ => {
+                // The selector reports no file operations in `file_op_support`, so the scheduler runs them on the blocking pool.
+                panic!("[BUG] tried to handle a file operation in [`EpolledSelector`]. Please report this issue.")
+            }
         }
     }
 }
 
 impl Selector for EpolledSelector {
     #[inline(always)]
-    fn need_reregister(&self) -> bool {
-         false
+    fn supports_multishot(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn file_op_support(&self) -> FileOpSupport {
+        // Regular files are always ready for epoll, so their syscalls would block the worker.
+        FileOpSupport::NONE
     }
 
     #[inline(always)]
-    fn poll(&mut self, scheduler: &mut Scheduler) -> Result<bool, ()> {
+    fn poll(&mut self, scheduler: &mut Scheduler, mut timeout: Option<Duration>) -> Result<bool, io::Error> {
+        self.unhandled_states.append(&mut self.pending_writes);
+        // Handled states can wake coroutines up, so they are not delayed by the wait.
+        if !self.unhandled_states.is_empty() {
+            timeout = Some(Duration::ZERO);
+        }
+        timeout = self.connect_timeout(timeout);
         // TODO maybe drain is faster?
-        for i in 0..self.unhandled_states.len() {
+        // The length is re-read on every iteration, because handling a state can push a new one.
+        let mut i = 0;
+        while i < self.unhandled_states.len() {
             let state_ptr = self.unhandled_states[i];
             if unlikely(self.handle_state(state_ptr, scheduler)) {
                 return Ok(true);
             }
+            i += 1;
         }
         self.unhandled_states.clear();
 
-        let num_incoming_events = self.epoll.wait(&mut self.events, EpollTimeout::try_from(1).unwrap()).expect("failed to wait");
-        if num_incoming_events == 0 {
-            return Ok(false);
-        }
+        // epoll counts milliseconds, so a short timeout is rounded up to not spin.
+        let timeout = match timeout {
+            Some(timeout) => EpollTimeout::try_from(timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32).unwrap(),
+            None => EpollTimeout::NONE
+        };
+        let num_incoming_events = match self.epoll.wait(&mut self.events, timeout) {
+            Ok(num) => num,
+            // A signal is not a failure, the worker polls again at the next background work.
+            Err(Errno::EINTR) => return Ok(false),
+            Err(errno) => return Err(errno.into())
+        };
 
         for i in 0..num_incoming_events {
             let event = &self.events[i];
@@ -165,11 +404,32 @@ impl Selector for EpolledSelector {
                 return Ok(true);
             }
         }
+        if unlikely(!self.connects.is_empty()) {
+            return Ok(self.expire_connects(scheduler));
+        }
         Ok(false)
     }
 
     #[inline(always)]
     fn register(&mut self, state_ptr: Ptr<PollState>) {
+        state_trace::record_state(unsafe { state_ptr.as_ref() }, StateEventKind::Submit, 0);
+        // Writes and closes are done in the poll without waiting.
+        if matches!(unsafe { state_ptr.as_ref() }, PollState::WriteTcp(_) | PollState::WriteAllTcp(_) | PollState::CloseTcp(_)) {
+            self.unhandled_states.push(state_ptr);
+            return;
+        }
+        // The connect is started in the poll, and the socket is added to epoll, if it doesn't connect at once.
+        if let PollState::ConnectTcp(state) = unsafe { state_ptr.as_ref() } {
+            if let Err(err) = state.socket.set_nonblocking(true) {
+                panic!("failed to make the socket nonblocking: {}", err);
+            }
+            if let Some(timeout) = state.timeout {
+                self.connects.push((Instant::now() + timeout, state_ptr));
+            }
+            self.unhandled_states.push(state_ptr);
+            return;
+        }
+
         let fd = unsafe { state_ptr.as_ref() }.fd();
         let res = unsafe {
             self.epoll.add(BorrowedFd::borrow_raw(fd), EpollEvent::new(EpollFlags::EPOLLIN, state_ptr.as_u64()))
@@ -178,25 +438,150 @@ impl Selector for EpolledSelector {
         if res.is_err() {
             panic!("failed to add fd to epoll: {} for fd: {}", res.unwrap_err(), fd);
         }
+        self.registered += 1;
     }
 
     #[inline(always)]
     fn deregister(&mut self, fd: RawFd) {
-        unsafe {
-            self.epoll.delete(BorrowedFd::borrow_raw(fd)).expect("failed to remove fd from epoll");
+        // The fd is already removed, if its stream has been read to the end.
+        match unsafe { self.epoll.delete(BorrowedFd::borrow_raw(fd)) } {
+            Ok(()) => self.registered -= 1,
+            Err(Errno::ENOENT) => (),
+            Err(err) => panic!("failed to remove fd from epoll: {} for fd: {}", err, fd)
         }
     }
 
-    fn write(&mut self, state_ref: Ptr<PollState>) {
-        self.unhandled_states.push(state_ref);
+    fn register_file(&mut self, _fd: RawFd) -> Result<bool, io::Error> {
+        Ok(false)
     }
 
-    fn write_all(&mut self, state_ref: Ptr<PollState>) {
-        self.unhandled_states.push(state_ref);
+    fn cancel(&mut self, state_ref: Ptr<PollState>) -> Option<CoroutineImpl> {
+        // A write, that waits for `EPOLLOUT`, can wait forever for a peer, that doesn't read.
+        if !self.write_waits.is_empty() && self.write_waits.contains_key(&unsafe { state_ref.as_ref() }.fd()) {
+            let (fd, coroutine) = match unsafe { state_ref.read() } {
+                PollState::WriteTcp(state) => {
+                    write_err!(state.result, errno_error(libc::ECANCELED));
+                    (state.fd, state.coroutine)
+                }
+                PollState::WriteAllTcp(state) => {
+                    write_err!(state.result, errno_error(libc::ECANCELED));
+                    (state.fd, state.coroutine)
+                }
+                state => {
+                    unsafe { state_ref.write(state) };
+                    return PollState::cancel(state_ref);
+                }
+            };
+            unsafe { state_ref.write(PollState::new_empty(fd)) };
+            self.finish_write(state_ref, fd);
+            return Some(coroutine);
+        }
+        // The fd stays registered with the empty state, so a later event is ignored.
+        PollState::cancel(state_ref)
     }
 
     #[inline(always)]
-    fn close_connection(&mut self, state_ref: Ptr<PollState>) {
-        self.unhandled_states.push(state_ref);
+    fn sleep(&mut self, _dur: Duration, coroutine: CoroutineImpl) -> Option<CoroutineImpl> {
+        Some(coroutine)
     }
-}
\ No newline at end of file
+
+    #[inline(always)]
+    fn ring_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    #[inline(always)]
+    fn pending_states(&self) -> usize {
+        self.registered + self.unhandled_states.len() + self.pending_writes.len()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind, Read};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::coro;
+    use crate::buf::buffer;
+    use crate::cfg::{config, SelectorType};
+    use crate::io::AsyncWrite;
+    use crate::local::Local;
+    use crate::net::TcpStream;
+    use crate::run::run_on_core_with_config;
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+
+    #[coro(crate="crate")]
+    fn connect_all(addrs: Vec<SocketAddr>) -> Vec<Result<(), ErrorKind>> {
+        let mut results = Vec::new();
+        for addr in addrs {
+            let res: Result<TcpStream, Error> = yield TcpStream::connect_timeout(addr, Duration::from_millis(100));
+            results.push(res.map(drop).map_err(|err| err.kind()));
+        }
+        return results;
+    }
+
+    #[test]
+    fn test_connect() {
+        // The backlog of 0 takes one connection, and the SYN of the next one is dropped, while the first is not accepted.
+        let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+        socket.bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into()).unwrap();
+        socket.listen(0).unwrap();
+        let addr = socket.local_addr().unwrap().as_socket().unwrap();
+        // Nobody listens on the port of the closed listener.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Poller);
+        let results = run_on_core_with_config(move |res| connect_all(vec![addr, closed, addr], res), core, cfg).unwrap();
+        assert_eq!(results, Some(vec![Ok(()), Err(ErrorKind::ConnectionRefused), Err(ErrorKind::TimedOut)]));
+        drop(socket);
+    }
+
+    /// Counts the polls of the worker in 100 milliseconds after the start of the write.
+    #[coro(crate="crate")]
+    fn count_polls(polls: Local<u64>) {
+        yield sleep(Duration::from_millis(10));
+        let start = local_scheduler().metrics().polls_total;
+        yield sleep(Duration::from_millis(100));
+        *polls.get_mut() = local_scheduler().metrics().polls_total - start;
+    }
+
+    #[coro(crate="crate")]
+    fn write_to_slow_peer(addr: SocketAddr, len: usize) -> (bool, u64) {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let polls = Local::new(0);
+        local_scheduler().sched(count_polls(polls.clone(), null_mut()));
+        let mut buf = buffer();
+        buf.append(&vec![7u8; len]);
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        // The stream is closed with a reset, so the peer must have read everything.
+        yield sleep(Duration::from_millis(100));
+        return (res.is_ok(), *polls.get());
+    }
+
+    #[test]
+    fn test_write_waits_writable() {
+        const LEN: usize = 32 * 1024 * 1024;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The send buffer is filled, while the peer doesn't read.
+            std::thread::sleep(Duration::from_millis(200));
+            let mut received = vec![0u8; LEN];
+            stream.read_exact(&mut received).unwrap();
+            received.iter().all(|&b| b == 7)
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Poller);
+        let (is_written, polls) = run_on_core_with_config(move |res| write_to_slow_peer(addr, LEN, res), core, cfg).unwrap().unwrap();
+        assert!(is_written);
+        assert!(peer.join().unwrap());
+        // The write waits for `EPOLLOUT` instead of trying again at every poll.
+        assert!(polls < 100, "{} polls", polls);
+    }
+}
@@ -0,0 +1,51 @@
+//! This module contains the conversion of errno to [`Error`], that is shared by selectors.
+use std::io::Error;
+
+/// Converts `errno` to [`Error`].
+///
+/// The kind of the error is classified by `errno`: `ECONNRESET` is [`ConnectionReset`](std::io::ErrorKind::ConnectionReset),
+/// `EPIPE` is [`BrokenPipe`](std::io::ErrorKind::BrokenPipe), `ENOSPC` is [`StorageFull`](std::io::ErrorKind::StorageFull),
+/// `EDQUOT` is [`QuotaExceeded`](std::io::ErrorKind::QuotaExceeded) and so on, and the raw `errno` is kept.
+#[inline(always)]
+pub(crate) fn errno_error(errno: i32) -> Error {
+    Error::from_raw_os_error(errno)
+}
+
+/// Converts the negative result of a completion of io_uring to [`Error`].
+///
+/// io_uring returns `-errno` in the result and doesn't set errno of the thread,
+/// so [`Error::last_os_error`] would return an unrelated error.
+#[inline(always)]
+pub(crate) fn ring_error(ret: i32) -> Error {
+    debug_assert!(ret < 0, "the result of the completion is not an error");
+    errno_error(-ret)
+}
+
+/// Converts the result of a libc syscall, that is made instead of an io_uring operation, to the form of completion results:
+/// `-errno` on failure, so it can be handled as a completion.
+#[inline(always)]
+pub(crate) fn as_ring_ret(ret: i32) -> i32 {
+    if ret < 0 {
+        return -unsafe { *libc::__errno_location() };
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use crate::io::sys::unix::errno::{as_ring_ret, errno_error, ring_error};
+
+    #[test]
+    fn test_errno_error() {
+        assert_eq!(ring_error(-libc::ECONNRESET).kind(), ErrorKind::ConnectionReset);
+        assert_eq!(ring_error(-libc::EPIPE).kind(), ErrorKind::BrokenPipe);
+        assert_eq!(ring_error(-libc::ENOSPC).kind(), ErrorKind::StorageFull);
+        assert_eq!(ring_error(-libc::EDQUOT).kind(), ErrorKind::QuotaExceeded);
+        assert_eq!(ring_error(-libc::ENOENT).raw_os_error(), Some(libc::ENOENT));
+        assert_eq!(errno_error(libc::ECONNREFUSED).kind(), ErrorKind::ConnectionRefused);
+
+        assert_eq!(as_ring_ret(unsafe { libc::close(-1) }), -libc::EBADF);
+        assert_eq!(as_ring_ret(3), 3);
+    }
+}
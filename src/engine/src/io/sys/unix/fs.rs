@@ -0,0 +1,89 @@
+//! This module contains functions for working with the filesystem, that have no io_uring analogue.
+
+use std::ffi::{CStr, OsString};
+use std::io::Error;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::ptr::null_mut;
+
+/// The offset, that means the current position of the fd. It is used for streams (pipes, terminals, sockets),
+/// that can't be read or written with `pread` and `pwrite`. io_uring treats it (-1) the same way.
+pub(crate) const CURRENT_POSITION: u64 = u64::MAX;
+
+/// `pread`, that falls back to `read` for [`CURRENT_POSITION`].
+#[inline(always)]
+pub(crate) unsafe fn read_at(fd: RawFd, buf: *mut u8, len: usize, offset: u64) -> isize {
+    if offset == CURRENT_POSITION {
+        unsafe { libc::read(fd, buf as _, len) }
+    } else {
+        unsafe { libc::pread(fd, buf as _, len, offset as libc::off_t) }
+    }
+}
+
+/// `pwrite`, that falls back to `write` for [`CURRENT_POSITION`].
+#[inline(always)]
+pub(crate) unsafe fn write_at(fd: RawFd, buf: *const u8, len: usize, offset: u64) -> isize {
+    if offset == CURRENT_POSITION {
+        unsafe { libc::write(fd, buf as _, len) }
+    } else {
+        unsafe { libc::pwrite(fd, buf as _, len, offset as libc::off_t) }
+    }
+}
+
+/// Returns the offset after `n` bytes from `offset`. [`CURRENT_POSITION`] is moved by the kernel.
+#[inline(always)]
+pub(crate) fn advance_offset(offset: u64, n: u64) -> u64 {
+    if offset == CURRENT_POSITION {
+        CURRENT_POSITION
+    } else {
+        offset + n
+    }
+}
+
+/// The maximum number of bytes copied by one call of [`copy_chunk`].
+#[cfg(target_os = "linux")]
+pub(crate) const COPY_CHUNK_LEN: usize = 1024 * 1024;
+
+/// Copies up to [`COPY_CHUNK_LEN`] bytes from `src` to `dst` in the kernel, starting from their current positions.
+///
+/// It uses `copy_file_range` and falls back to `sendfile` if `copy_file_range` is not supported
+/// (for example, by an old kernel or for files on different filesystems).
+///
+/// Returns the number of copied bytes (0 means the end of `src`) or -1 with errno set.
+///
+/// It is called by the blocking pool. `copy_file_range` is Linux-only, so off Linux the pool copies files through the memory.
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_chunk(src: RawFd, dst: RawFd) -> isize {
+    let res = unsafe { libc::copy_file_range(src, null_mut(), dst, null_mut(), COPY_CHUNK_LEN, 0) };
+    if res >= 0 {
+        return res;
+    }
+
+    match Error::last_os_error().raw_os_error() {
+        Some(libc::ENOSYS | libc::EXDEV | libc::EINVAL | libc::EOPNOTSUPP) => unsafe {
+            libc::sendfile(dst, src, null_mut(), COPY_CHUNK_LEN)
+        },
+        _ => res
+    }
+}
+
+/// Reads the target of the symbolic link with `readlink`. The buffer grows until the whole target fits.
+pub(crate) fn read_link(path: &CStr) -> Result<PathBuf, Error> {
+    let mut buf: Vec<u8> = Vec::with_capacity(256);
+    loop {
+        let res = unsafe { libc::readlink(path.as_ptr(), buf.as_mut_ptr() as _, buf.capacity()) };
+        if res < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let len = res as usize;
+        // If the target fills the whole buffer, it may be truncated.
+        if len < buf.capacity() {
+            unsafe { buf.set_len(len) };
+            return Ok(PathBuf::from(OsString::from_vec(buf)));
+        }
+        buf.reserve(buf.capacity() * 2);
+    }
+}
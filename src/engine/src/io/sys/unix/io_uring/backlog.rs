@@ -0,0 +1,104 @@
+//! This module contains [`SubmissionStats`], the counters of the submission backlog of io_uring.
+use std::cell::Cell;
+
+thread_local! {
+    /// The counters of the current worker.
+    static STATS: Cell<SubmissionStats> = const { Cell::new(SubmissionStats::new()) };
+}
+
+/// The counters of the submission queue of io_uring of one worker.
+///
+/// When the submission queue is full, or the kernel returns `EBUSY` (the completion queue has overflowed),
+/// the entries wait in the backlog of the worker and are submitted at the next polls. A backlog, that grows
+/// from poll to poll, means that the worker submits more operations than the ring can take.
+///
+/// The completion queue is twice as large as the submission queue, but more operations can be in flight.
+/// Then the completions overflow it and wait in the kernel, until the worker has reaped the queue and flushed them.
+///
+/// Completions can register operations again (a partial write sends the rest, a poll arms the receive),
+/// and woken coroutines register new ones. While the completion queue is reaped, these entries are collected,
+/// and they are pushed and submitted together with one system call at the end of the poll.
+///
+/// The backlog is capped by [`set_ring_backlog_cap`](crate::cfg::set_ring_backlog_cap):
+/// over the cap, the worker stops running coroutines and keeps submitting and reaping completions.
+/// If [`set_ring_resize`](crate::cfg::set_ring_resize) is enabled, the ring is recreated with more entries instead.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::io::submission_stats;
+///
+/// let stats = submission_stats();
+/// if stats.max_backlog > 0 {
+///     println!("the ring of {} entries was full, {} entries were queued", stats.ring_entries, stats.spilled);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionStats {
+    /// The number of entries of the ring.
+    pub ring_entries: u32,
+    /// The number of entries, that didn't fit into the submission queue and were put to the backlog.
+    pub spilled: u64,
+    /// The number of times, the kernel returned `EBUSY` on submission.
+    pub busy: u64,
+    /// The number of entries in the backlog now.
+    pub backlog: usize,
+    /// The largest length of the backlog.
+    pub max_backlog: usize,
+    /// The number of extra submissions, that were made, because the backlog was over the cap.
+    pub capped: u64,
+    /// The number of times, the ring was recreated with more entries.
+    pub resizes: u32,
+    /// The number of times, the completion queue had overflowed, and the worker flushed the completions from the kernel.
+    pub cq_overflows: u64,
+    /// The number of completions, that the kernel dropped, because it couldn't keep them after an overflow.
+    /// Coroutines, that wait for them, are never woken up, so it must stay zero.
+    pub dropped_completions: u64,
+    /// The number of batches of entries, that were added, while the completion queue was reaped.
+    pub resubmit_batches: u64,
+    /// The number of entries in all batches. Divided by [`resubmit_batches`](Self::resubmit_batches),
+    /// it is the average number of entries, that one system call submits after reaping.
+    pub resubmitted: u64,
+    /// The largest batch.
+    pub max_resubmit_batch: usize,
+    /// The number of wake-ups, that other threads have sent to the ring with `IORING_OP_MSG_RING`.
+    /// Read [`Injector::wake`](crate::scheduler::Injector::wake).
+    pub ring_wakeups: u64
+}
+
+impl SubmissionStats {
+    const fn new() -> Self {
+        Self {
+            ring_entries: 0,
+            spilled: 0,
+            busy: 0,
+            backlog: 0,
+            max_backlog: 0,
+            capped: 0,
+            resizes: 0,
+            cq_overflows: 0,
+            dropped_completions: 0,
+            resubmit_batches: 0,
+            resubmitted: 0,
+            max_resubmit_batch: 0,
+            ring_wakeups: 0
+        }
+    }
+}
+
+/// Returns the counters of the submission queue of io_uring of the current worker.
+/// All counters are zero, if the worker uses another [`Selector`](crate::io::Selector).
+pub fn submission_stats() -> SubmissionStats {
+    STATS.with(Cell::get)
+}
+
+/// Updates the counters of the current worker.
+#[inline(always)]
+pub(crate) fn update_stats<F: FnOnce(&mut SubmissionStats)>(f: F) {
+    STATS.with(|stats| {
+        let mut value = stats.get();
+        f(&mut value);
+        stats.set(value);
+    });
+}
+
@@ -0,0 +1,205 @@
+//! This module contains [`uring_capabilities`] and [`file_op_support`], the probing of the file operations of the ring.
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+use std::io::Error;
+use io_uring::{opcode, IoUring, Probe};
+use crate::cfg::config_ring_setup;
+use crate::io::FileOpSupport;
+use crate::io::sys::unix::io_uring::build_ring;
+
+/// The version of the running kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KernelVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32
+}
+
+impl KernelVersion {
+    /// Returns the version of the running kernel from `uname`.
+    pub fn current() -> Result<Self, Error> {
+        let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+        if unsafe { libc::uname(&mut uts) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) }.to_string_lossy();
+        Ok(Self::parse(&release))
+    }
+
+    /// Parses a release like `6.1.0-18-amd64`. Missing or invalid parts are 0.
+    fn parse(release: &str) -> Self {
+        let mut parts = release
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|part| part.parse().unwrap_or(0));
+
+        Self {
+            major: parts.next().unwrap_or(0),
+            minor: parts.next().unwrap_or(0),
+            patch: parts.next().unwrap_or(0)
+        }
+    }
+}
+
+impl Display for KernelVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The report of what io_uring of the running kernel supports and what the engine enables.
+///
+/// Read [`uring_capabilities`] for more information.
+#[derive(Debug, Clone)]
+pub struct UringCapabilities {
+    /// The version of the running kernel.
+    pub kernel_version: KernelVersion,
+    /// Whether the ring of the engine uses the kernel submission polling thread (`IORING_SETUP_SQPOLL`).
+    pub sqpoll: bool,
+    /// Whether the ring of the engine is submitted only by its worker (`IORING_SETUP_SINGLE_ISSUER`).
+    pub single_issuer: bool,
+    /// Whether the ring of the engine doesn't interrupt the worker to run completion work (`IORING_SETUP_COOP_TASKRUN`).
+    pub coop_taskrun: bool,
+    /// Whether the ring of the engine runs completion work only, when the worker polls (`IORING_SETUP_DEFER_TASKRUN`).
+    pub defer_taskrun: bool,
+    /// Whether multishot accept and receive are supported (since Linux 5.19).
+    pub multishot: bool,
+    /// Whether zero-copy send (`IORING_OP_SEND_ZC`) is supported.
+    pub send_zc: bool,
+    /// Whether the ring uses internal polling for not ready sockets instead of worker threads (`IORING_FEAT_FAST_POLL`).
+    pub fast_poll: bool,
+    /// Whether completions are never dropped, when the completion queue is full (`IORING_FEAT_NODROP`).
+    pub nodrop: bool,
+    /// Whether a wait can take a timeout without a timeout entry (`IORING_FEAT_EXT_ARG`). The engine requires it.
+    pub ext_arg: bool,
+    /// The file operations, that the ring makes. Others are run on the blocking pool.
+    /// Read [`Selector::file_op_support`](crate::io::Selector::file_op_support).
+    pub file_ops: FileOpSupport,
+    /// The opcodes, that are supported by the kernel.
+    supported_opcodes: Vec<u8>
+}
+
+impl UringCapabilities {
+    /// Returns true, if the kernel supports the opcode, like `io_uring::opcode::Read::CODE`.
+    pub fn is_opcode_supported(&self, code: u8) -> bool {
+        self.supported_opcodes.contains(&code)
+    }
+
+    /// Returns the opcodes, that are supported by the kernel.
+    pub fn supported_opcodes(&self) -> &[u8] {
+        &self.supported_opcodes
+    }
+}
+
+impl Display for UringCapabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "kernel {}, {} opcodes, sqpoll: {}, single_issuer: {}, coop_taskrun: {}, defer_taskrun: {}, \
+            multishot: {}, send_zc: {}, fast_poll: {}, nodrop: {}, ext_arg: {}",
+            self.kernel_version, self.supported_opcodes.len(), self.sqpoll, self.single_issuer,
+            self.coop_taskrun, self.defer_taskrun, self.multishot,
+            self.send_zc, self.fast_poll, self.nodrop, self.ext_arg
+        )
+    }
+}
+
+/// Returns the io_uring capabilities of the running kernel, so applications and their operators
+/// can verify, that the engine will use the expected fast paths.
+///
+/// It creates a ring with the same parameters, as the ring of the engine (read [`RingSetup`](crate::io::RingSetup)),
+/// and probes it. The flags of the setup, that the kernel doesn't support, are reported as false.
+///
+/// # Errors
+///
+/// Returns an error, if io_uring is not available (an old kernel, or it is disabled by `io_uring_disabled` or seccomp).
+/// In this case use the [`Poller`](crate::cfg::SelectorType::Poller) selector.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::io::uring_capabilities;
+///
+/// match uring_capabilities() {
+///     Ok(capabilities) => println!("io_uring: {}", capabilities),
+///     Err(err) => println!("io_uring is not available: {}", err)
+/// }
+/// ```
+pub fn uring_capabilities() -> Result<UringCapabilities, Error> {
+    let kernel_version = KernelVersion::current()?;
+    let setup = config_ring_setup();
+    let (ring, applied) = build_ring(setup, setup.entries)?;
+    let mut probe = Probe::new();
+    ring.submitter().register_probe(&mut probe)?;
+
+    let supported_opcodes: Vec<u8> = (0..=u8::MAX).filter(|&code| probe.is_supported(code)).collect();
+    let params = ring.params();
+
+    Ok(UringCapabilities {
+        kernel_version,
+        sqpoll: params.is_setup_sqpoll(),
+        single_issuer: applied.single_issuer,
+        coop_taskrun: applied.coop_taskrun,
+        defer_taskrun: applied.defer_taskrun,
+        multishot: kernel_version >= KernelVersion { major: 5, minor: 19, patch: 0 },
+        send_zc: probe.is_supported(opcode::SendZc::CODE),
+        fast_poll: params.is_feature_fast_poll(),
+        nodrop: params.is_feature_nodrop(),
+        ext_arg: params.is_feature_ext_arg(),
+        file_ops: probe_file_ops(&probe),
+        supported_opcodes
+    })
+}
+
+/// Returns the file operations, which opcodes are supported by the probe. io_uring has no opcodes for copying,
+/// reading links and setting permissions, so they are never supported and run on the blocking pool.
+fn probe_file_ops(probe: &Probe) -> FileOpSupport {
+    FileOpSupport {
+        open: probe.is_supported(opcode::OpenAt::CODE),
+        read: probe.is_supported(opcode::Read::CODE),
+        write: probe.is_supported(opcode::Write::CODE),
+        sync: probe.is_supported(opcode::Fsync::CODE),
+        close: probe.is_supported(opcode::Close::CODE),
+        copy: false,
+        symlink: probe.is_supported(opcode::SymlinkAt::CODE),
+        hard_link: probe.is_supported(opcode::LinkAt::CODE),
+        read_link: false,
+        set_permissions: false,
+        advise: probe.is_supported(opcode::Fadvise::CODE),
+        create_dir: probe.is_supported(opcode::MkDirAt::CODE),
+        remove: probe.is_supported(opcode::UnlinkAt::CODE),
+        rename: probe.is_supported(opcode::RenameAt::CODE)
+    }
+}
+
+/// Probes the ring for the file operations, that it can make, so the scheduler runs other operations
+/// on the blocking pool instead of failing them with `EINVAL`.
+pub(crate) fn file_op_support(ring: &IoUring) -> Result<FileOpSupport, Error> {
+    let mut probe = Probe::new();
+    ring.submitter().register_probe(&mut probe)?;
+    Ok(probe_file_ops(&probe))
+}
+
+#[cfg(test)]
+mod tests {
+    use io_uring::opcode;
+    use super::*;
+
+    #[test]
+    fn test_kernel_version_parse() {
+        assert_eq!(KernelVersion::parse("6.1.0-18-amd64"), KernelVersion { major: 6, minor: 1, patch: 0 });
+        assert_eq!(KernelVersion::parse("5.19"), KernelVersion { major: 5, minor: 19, patch: 0 });
+        assert!(KernelVersion::parse("5.4.0") < KernelVersion::parse("5.19.1"));
+    }
+
+    #[test]
+    fn test_uring_capabilities() {
+        let capabilities = uring_capabilities().unwrap();
+        assert!(capabilities.kernel_version.major >= 5);
+        assert!(capabilities.is_opcode_supported(opcode::Nop::CODE));
+        assert!(capabilities.is_opcode_supported(opcode::Accept::CODE));
+        assert!(capabilities.ext_arg);
+        assert!(!capabilities.sqpoll);
+        assert!(capabilities.file_ops.read && capabilities.file_ops.write && capabilities.file_ops.open);
+    }
+}
@@ -0,0 +1,91 @@
+//! This module contains [`FixedFiles`], the slots of the registered files of the ring.
+use std::collections::HashMap;
+use std::os::fd::RawFd;
+
+/// The allocator of the slots of the registered (fixed) files of the ring.
+///
+/// The ring is created with a sparse table of `capacity` slots. A registered fd is put to a free slot,
+/// and operations on it use the slot instead of the fd, so the kernel doesn't look the fd up on every operation.
+/// The slot is freed, when the fd is closed.
+pub(crate) struct FixedFiles {
+    /// The slots of the registered fds.
+    slots: HashMap<RawFd, u32>,
+    /// The free slots. The lowest slot is taken first.
+    free: Vec<u32>
+}
+
+impl FixedFiles {
+    /// Creates the allocator with `capacity` free slots.
+    pub(crate) fn new(capacity: u32) -> Self {
+        Self {
+            slots: HashMap::new(),
+            free: (0..capacity).rev().collect()
+        }
+    }
+
+    /// Returns the number of slots.
+    #[inline(always)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len() + self.free.len()
+    }
+
+    /// Returns the slot of the fd, if it is registered.
+    #[inline(always)]
+    pub(crate) fn get(&self, fd: RawFd) -> Option<u32> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        self.slots.get(&fd).copied()
+    }
+
+    /// Takes a free slot for the fd. Returns `None`, if the fd is already registered or no slots are free.
+    pub(crate) fn alloc(&mut self, fd: RawFd) -> Option<u32> {
+        if self.slots.contains_key(&fd) {
+            return None;
+        }
+
+        let slot = self.free.pop()?;
+        self.slots.insert(fd, slot);
+        Some(slot)
+    }
+
+    /// Frees the slot of the fd. Returns the slot, if the fd was registered.
+    pub(crate) fn free(&mut self, fd: RawFd) -> Option<u32> {
+        let slot = self.slots.remove(&fd)?;
+        self.free.push(slot);
+        Some(slot)
+    }
+
+    /// Returns the table of all slots for `register_files`, where free slots are -1.
+    pub(crate) fn table(&self) -> Vec<RawFd> {
+        let mut table = vec![-1; self.capacity()];
+        for (&fd, &slot) in &self.slots {
+            table[slot as usize] = fd;
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_files() {
+        let mut files = FixedFiles::new(2);
+        assert_eq!(files.alloc(10), Some(0));
+        assert_eq!(files.alloc(10), None);
+        assert_eq!(files.alloc(11), Some(1));
+        assert_eq!(files.alloc(12), None);
+        assert_eq!(files.get(11), Some(1));
+        assert_eq!(files.table(), vec![10, 11]);
+
+        assert_eq!(files.free(10), Some(0));
+        assert_eq!(files.free(10), None);
+        assert_eq!(files.get(10), None);
+        assert_eq!(files.table(), vec![-1, 11]);
+        assert_eq!(files.alloc(12), Some(0));
+        assert_eq!(files.capacity(), 2);
+    }
+}
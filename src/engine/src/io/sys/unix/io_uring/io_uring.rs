@@ -1,22 +1,38 @@
 use std::collections::VecDeque;
 use std::cell::UnsafeCell;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
-use std::{mem, ptr};
-use std::intrinsics::unlikely;
+use std::{cmp, mem, ptr};
+use std::intrinsics::{likely, unlikely};
+use std::time::Duration;
 use io_uring::{cqueue, IoUring, opcode, squeue, types};
 use io_uring::types::{SubmitArgs, Timespec};
-use crate::buf::buffer;
-use crate::io::{Selector, PollState};
+use crate::buf::{buf_pool, buffer, Buffer};
+use crate::cfg::{config_buf_len, config_fixed_buffers, config_fixed_files, config_multishot_recv_buffers, config_ring_backlog_cap, config_ring_resize, config_ring_setup, config_ring_timeouts, config_write_turn_cap};
+use crate::io::{FileOpSupport, Selector, PollState};
+use crate::coroutine::CoroutineImpl;
+use crate::io::state_trace::{self, StateEventKind};
+use crate::io::sys::unix::io_uring::backlog::{update_stats, SubmissionStats};
+use crate::io::sys::unix::io_uring::fixed_files::FixedFiles;
+use crate::io::sys::unix::io_uring::recv_multi::{BufRing, MultishotRecvs, Received, BUF_GROUP, MULTISHOT_TAG};
+use crate::io::sys::unix::io_uring::capabilities::file_op_support;
+use crate::io::sys::unix::io_uring::KernelVersion;
+use crate::io::sys::unix::io_uring::setup::{build_ring, RingSetup};
+use crate::io::sys::unix::io_uring::sleeps::{RingSleeps, SLEEP_TAG};
+use crate::io::sys::unix::io_uring::msg_ring::WAKE_USER_DATA;
+use crate::io::sys::unix::coalesce::recv_more;
+use crate::io::sys::unix::errno::{as_ring_ret, ring_error};
+use crate::io::sys::unix::fs::advance_offset;
+use crate::fs::File;
 use crate::net::TcpStream;
 use crate::scheduler::Scheduler;
 use crate::utils::{Ptr};
-use crate::{write_ok};
+use crate::{write_err, write_ok};
 
 macro_rules! handle_ret {
     ($ret: expr, $state: expr, $scheduler: expr, $selector: expr) => {
         if $ret < 0 {
-            let err = Error::last_os_error();
+            let err = ring_error($ret);
             unsafe { $state.result.write(Err(err)); }
             return $scheduler.handle_coroutine_state($selector, $state.coroutine);
         }
@@ -31,10 +47,79 @@ macro_rules! handle_ret_without_result {
     };
 }
 
-const TIMEOUT: Timespec = Timespec::new().nsec(500_000);
+/// The largest number of entries, that the ring is resized to.
+const MAX_RING_ENTRIES: u32 = 32 * 1024;
+/// Builds the entry with the slot of the fd, if it is registered (read [`FixedFiles`]), or with the fd.
+macro_rules! with_fd {
+    ($selector: expr, $fd: expr, |$target: ident| $build: expr) => {
+        match $selector.fixed_files.get($fd) {
+            Some(slot) => {
+                let $target = types::Fixed(slot);
+                $build
+            }
+            None => {
+                let $target = types::Fd($fd);
+                $build
+            }
+        }
+    };
+}
+
+/// The number of polls in a row with a not empty backlog, after which the ring is resized, if it is enabled.
+const RESIZE_AFTER_POLLS: u32 = 16;
+/// The user data of [`AsyncCancel`](opcode::AsyncCancel) entries. Their completions only report the result of the cancellation.
+const CANCEL_USER_DATA: u64 = u64::MAX;
+/// The bit of the user data of linked entries, that are not the last in their chain. Read [`IoUringSelector::add_link`].
+const LINK_TAG: u64 = 1 << 62;
+/// The bit of the user data of [`LinkTimeout`](opcode::LinkTimeout) entries. The rest of the user data is the pointer to their [`Timespec`].
+const TIMEOUT_TAG: u64 = 1 << 61;
+/// The flag of the completion of [`SendZc`](opcode::SendZc), that notifies, that the kernel doesn't use the buffer anymore.
+const CQE_F_NOTIF: u32 = 1 << 3;
+/// The flag of `io_uring_enter`, that makes the kernel post completions, including the overflowed ones.
+const ENTER_GETEVENTS: u32 = 1 << 0;
+
+/// Registers the memory of the buffers of the [`BufPool`](crate::buf::BufPool) with the ring.
+/// Returns false, if no buffers are registered.
+fn register_fixed_buffers(ring: &IoUring, buffers: &[libc::iovec]) -> Result<bool, Error> {
+    if buffers.is_empty() {
+        return Ok(false);
+    }
+
+    // The pool never frees the registered memory, while it lives (read `Buffer::fixed_index`),
+    // and the kernel keeps its own references to the pages.
+    unsafe { ring.submitter().register_buffers(buffers)? };
+    Ok(true)
+}
+
+/// Provides the buffers of multishot receives to the ring.
+fn register_buf_ring(ring: &IoUring, buf_ring: &BufRing) -> Result<(), Error> {
+    // The ring of buffers lives as long as the selector, and the selector drops the ring first.
+    unsafe { ring.submitter().register_buf_ring(buf_ring.addr(), buf_ring.entries(), BUF_GROUP) }
+}
+
+/// Creates the multishot receives, if [`set_multishot_recv_buffers`](crate::cfg::set_multishot_recv_buffers) is not 0.
+/// Returns `None`, if they are disabled or the kernel doesn't support them (before Linux 6.0).
+fn multishot_recvs(ring: &IoUring) -> Option<MultishotRecvs> {
+    let count = config_multishot_recv_buffers();
+    let is_supported = KernelVersion::current().is_ok_and(|version| version >= KernelVersion { major: 6, minor: 0, patch: 0 });
+    if count == 0 || !is_supported {
+        return None;
+    }
+
+    let buf_ring = BufRing::new(count, config_buf_len());
+    register_buf_ring(ring, &buf_ring).ok()?;
+    Some(MultishotRecvs::new(buf_ring))
+}
+
+/// Registers the table of the slots of the registered files with the ring.
+fn register_fixed_files(ring: &IoUring, fixed_files: &FixedFiles) -> Result<(), Error> {
+    if fixed_files.capacity() > 0 {
+        ring.submitter().register_files(&fixed_files.table())?;
+    }
+    Ok(())
+}
 
 pub(crate) struct IoUringSelector {
-    timeout: SubmitArgs<'static, 'static>,
     /// # Why we need some cell?
     ///
     /// We can't rewrite engine ([`Selector`] trait) to use separately `ring` field and other fields in different methods.
@@ -49,66 +134,594 @@ pub(crate) struct IoUringSelector {
     /// but only after the [`SubmissionQueue`] is submitted we start using the [`CompletionQueue`] that can call the [`IoUringSelector::push_sqe`]
     /// but it is safe, because the [`SubmissionQueue`] has already been read and submitted.
     ring: UnsafeCell<IoUring<squeue::Entry, cqueue::Entry>>,
-    backlog: VecDeque<squeue::Entry>
+    /// The number of entries of the ring.
+    entries: u32,
+    /// The flags, that the ring is created with. Read [`RingSetup`].
+    setup: RingSetup,
+    /// True, if the buffers of the [`BufPool`](crate::buf::BufPool) are registered with the ring.
+    /// Read [`set_fixed_buffers`](crate::cfg::set_fixed_buffers).
+    has_fixed_buffers: bool,
+    /// The slots of the registered files. Read [`set_fixed_files`](crate::cfg::set_fixed_files).
+    fixed_files: FixedFiles,
+    /// The multishot receives, if they are enabled. Read [`set_multishot_recv_buffers`](crate::cfg::set_multishot_recv_buffers).
+    recvs: Option<MultishotRecvs>,
+    /// The multishot receives, which waiting coroutines have got ready completions at registration.
+    /// They are woken up at the next poll.
+    ready_recvs: VecDeque<u32>,
+    /// The sleeping coroutines, if the ring wakes them up. Read [`set_ring_timeouts`](crate::cfg::set_ring_timeouts).
+    sleeps: Option<RingSleeps>,
+    /// Entries, that didn't fit into the submission queue, with the number of the next entries, that are linked to them.
+    /// Read [`SubmissionStats`] for more information.
+    backlog: VecDeque<(squeue::Entry, u32)>,
+    /// Entries, that were added while the completion queue was reaped, in the same form as the backlog.
+    /// Read [`IoUringSelector::submit_batch`].
+    batch: Vec<(squeue::Entry, u32)>,
+    /// True, while the completions are handled, so the added entries are collected into the batch.
+    is_reaping: bool,
+    /// The maximum length of the backlog, after which the worker flushes it in [`Selector::poll`].
+    backlog_cap: usize,
+    /// True, if the ring is recreated with more entries, when the backlog is not empty for [`RESIZE_AFTER_POLLS`] polls.
+    resize: bool,
+    /// The number of polls in a row, after which the backlog was not empty.
+    full_polls: u32,
+    /// The number of submitted entries, which completions are not reaped yet.
+    in_flight: usize,
+    /// The buffer for coalesced reads. A slice of it is valid until the next read, like a slice of a pool buffer.
+    coalesce_buf: Vec<u8>,
+    /// Partially sent [`WriteAllTcpState`](crate::io::WriteAllTcpState)s. They are resubmitted in FIFO order
+    /// at the next poll, so connections take turns.
+    pending_writes: VecDeque<Ptr<PollState>>,
+    /// The maximum number of bytes, that one [`WriteAllTcpState`](crate::io::WriteAllTcpState) sends per turn.
+    write_turn_cap: usize,
+    /// The file operations, which opcodes the kernel supports. It is probed, when the ring is created.
+    file_op_support: FileOpSupport
 }
 
 impl IoUringSelector {
-    pub fn new() -> Self {
+    /// Creates the ring with the [`RingSetup`] of the configuration. Returns an error, if io_uring is not supported
+    /// or not allowed (for example, by seccomp or `io_uring_disabled`), or the ring can't be allocated under `RLIMIT_MEMLOCK`.
+    ///
+    /// Returns [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported), if the kernel can't wait with a timeout
+    /// without a timeout entry (`IORING_FEAT_EXT_ARG`, since Linux 5.11).
+    ///
+    /// Registers the buffers of the [`BufPool`](crate::buf::BufPool), if [`set_fixed_buffers`](crate::cfg::set_fixed_buffers)
+    /// is not 0. Returns an error, if they can't be registered (for example, under `RLIMIT_MEMLOCK`).
+    ///
+    /// Registers the sparse table of [`set_fixed_files`](crate::cfg::set_fixed_files) slots, if it is not 0.
+    ///
+    /// Provides the buffers of multishot receives, if [`set_multishot_recv_buffers`](crate::cfg::set_multishot_recv_buffers)
+    /// is not 0. Multishot receives are disabled, if the kernel doesn't support them.
+    pub fn new() -> Result<Self, Error> {
+        let setup = config_ring_setup();
+        let (ring, setup) = build_ring(setup, setup.entries)?;
+        if !ring.params().is_feature_ext_arg() {
+            return Err(Error::new(std::io::ErrorKind::Unsupported, "io_uring doesn't support IORING_FEAT_EXT_ARG"));
+        }
+        let fixed_buffers = config_fixed_buffers();
+        let has_fixed_buffers = fixed_buffers > 0 && register_fixed_buffers(&ring, buf_pool().alloc_fixed(fixed_buffers))?;
+        let fixed_files = FixedFiles::new(config_fixed_files());
+        register_fixed_files(&ring, &fixed_files)?;
+        let recvs = multishot_recvs(&ring);
+        let file_op_support = file_op_support(&ring)?;
         println!("io_uring");
-        Self {
-            timeout: SubmitArgs::new().timespec(&TIMEOUT),
-            ring: UnsafeCell::new(IoUring::new(1024).unwrap()),
-            backlog: VecDeque::with_capacity(64)
+        update_stats(|stats| *stats = SubmissionStats { ring_entries: setup.entries, ..SubmissionStats::default() });
+        Ok(Self {
+            ring: UnsafeCell::new(ring),
+            entries: setup.entries,
+            setup,
+            has_fixed_buffers,
+            fixed_files,
+            recvs,
+            ready_recvs: VecDeque::new(),
+            sleeps: config_ring_timeouts().then(RingSleeps::new),
+            backlog: VecDeque::with_capacity(64),
+            batch: Vec::with_capacity(64),
+            is_reaping: false,
+            backlog_cap: config_ring_backlog_cap(),
+            resize: config_ring_resize(),
+            full_polls: 0,
+            in_flight: 0,
+            coalesce_buf: Vec::new(),
+            pending_writes: VecDeque::new(),
+            write_turn_cap: config_write_turn_cap(),
+            file_op_support
+        })
+    }
+
+    /// Frees the slot of the fd, if it is registered, before the fd is closed.
+    fn unregister_file(&mut self, fd: RawFd) {
+        if let Some(slot) = self.fixed_files.free(fd) {
+            let ring = unsafe { &*self.ring.get() };
+            // The update fails only if the slot is invalid, and then the slot holds no file.
+            let _ = ring.submitter().register_files_update(slot, &[-1]);
+        }
+    }
+
+    /// Returns the index of the registered memory of the `buffer`, if it is registered with the ring.
+    #[inline(always)]
+    fn fixed_index(&self, buffer: &Buffer) -> Option<u16> {
+        if self.has_fixed_buffers {
+            buffer.fixed_index()
+        } else {
+            None
         }
     }
 
     #[inline(always)]
     fn add_sqe(&mut self, sqe: squeue::Entry) {
+        if self.is_reaping {
+            self.batch.push((sqe, 0));
+            return;
+        }
         let ring = unsafe { &mut *self.ring.get() };
         unsafe {
             if ring.submission().push(&sqe).is_err() {
-                self.backlog.push_back(sqe);
+                self.backlog.push_back((sqe, 0));
+                self.update_spilled(1);
+                return;
+            }
+        }
+        self.in_flight += 1;
+    }
+
+    /// Pushes the chain of linked entries (`IOSQE_IO_LINK`): every entry starts after the previous one has completed,
+    /// and the rest of the chain is cancelled with `ECANCELED`, if an entry fails or is short.
+    ///
+    /// Every entry completes, so a state must not be handled, until all its entries have completed:
+    /// entries before the last one are tagged with [`LINK_TAG`] and keep their results in the state.
+    ///
+    /// The chain is pushed whole to the submission queue or to the backlog, so it is never split between submissions.
+    fn add_link<const N: usize>(&mut self, mut chain: [squeue::Entry; N]) {
+        for entry in &mut chain[..N - 1] {
+            *entry = entry.clone().flags(squeue::Flags::IO_LINK);
+        }
+        if self.is_reaping {
+            for (i, sqe) in chain.into_iter().enumerate() {
+                self.batch.push((sqe, (N - 1 - i) as u32));
+            }
+            return;
+        }
+
+        let ring = unsafe { &mut *self.ring.get() };
+        if unsafe { ring.submission().push_multiple(&chain) }.is_err() {
+            for (i, sqe) in chain.into_iter().enumerate() {
+                self.backlog.push_back((sqe, (N - 1 - i) as u32));
             }
+            self.update_spilled(N as u64);
+            return;
         }
+        self.in_flight += N;
     }
 
+    /// Pushes the entry linked with a timeout (`IORING_OP_LINK_TIMEOUT`). If the entry doesn't complete in `timeout`,
+    /// the kernel cancels it, and it completes with `ECANCELED`.
+    fn add_link_timeout(&mut self, entry: squeue::Entry, timeout: Duration) {
+        // The kernel reads the timespec, when the entry is submitted, so it is freed at the completion of the timeout.
+        let timespec = Box::into_raw(Box::new(Timespec::from(timeout)));
+        let timeout = opcode::LinkTimeout::new(timespec)
+            .build()
+            .user_data(TIMEOUT_TAG | timespec as u64);
+        self.add_link([entry, timeout]);
+    }
+
+    /// Counts the entries, that are put to the backlog.
+    #[cold]
+    fn update_spilled(&self, count: u64) {
+        let len = self.backlog.len();
+        update_stats(|stats| {
+            stats.spilled += count;
+            stats.max_backlog = cmp::max(stats.max_backlog, len);
+        });
+    }
+
+    /// Submits the backlog and waits for a completion at most `timeout` (`None` means without a timeout).
     #[inline(always)]
-    fn submit(&mut self) -> Result<(), Error> {
+    fn submit(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
         let ring = unsafe { &mut *self.ring.get() };
         let mut sq = unsafe { ring.submission_shared() };
         let submitter = ring.submitter();
 
         loop {
-            if sq.is_full() {
+            // A chain of linked entries is pushed whole, so it needs as many free entries.
+            let chain = self.backlog.front().map_or(1, |&(_, linked)| linked as usize + 1);
+            if sq.capacity() - sq.len() < chain {
                 match submitter.submit() {
                     Ok(_) => (),
-                    Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => break,
+                    Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => {
+                        update_stats(|stats| stats.busy += 1);
+                        break;
+                    }
                     Err(err) => return Err(err.into()),
                 }
             }
             sq.sync();
 
-            match self.backlog.pop_front() {
-                Some(sqe) => unsafe {
-                    let _ = sq.push(&sqe);
-                },
-                None => break,
+            // With SQPOLL the kernel thread can be yet to take the submitted entries.
+            if self.backlog.is_empty() || sq.capacity() - sq.len() < chain {
+                break;
             }
+            for (sqe, _) in self.backlog.drain(..chain) {
+                unsafe { sq.push(&sqe).unwrap_unchecked() };
+            }
+            self.in_flight += chain;
         }
 
-        match submitter.submit_with_args(1, &self.timeout) {
+        let res = match timeout {
+            // With DEFER_TASKRUN completions are posted only, when the worker asks for them.
+            Some(timeout) if timeout.is_zero() && self.setup.defer_taskrun => {
+                submitter.submit_with_args(1, &SubmitArgs::new().timespec(&Timespec::new()))
+            }
+            Some(timeout) if timeout.is_zero() => submitter.submit(),
+            Some(timeout) => {
+                let timespec = Timespec::from(timeout);
+                submitter.submit_with_args(1, &SubmitArgs::new().timespec(&timespec))
+            }
+            None => submitter.submit_and_wait(1)
+        };
+        match res {
             Ok(_) => (),
-            Err(ref err) if err.raw_os_error() == Some(libc::ETIME) => (),
-            Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => (),
+            Err(ref err) if err.raw_os_error() == Some(libc::ETIME) || err.raw_os_error() == Some(libc::EINTR) => (),
+            Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) => update_stats(|stats| stats.busy += 1),
             Err(err) => return Err(err.into()),
         };
 
+        let len = self.backlog.len();
+        update_stats(|stats| stats.backlog = len);
         Ok(())
     }
 
+    /// Pushes the entries, that were added while the completion queue was reaped, and submits them with one system call,
+    /// instead of pushing every entry, when its completion is handled.
+    ///
+    /// They go after the backlog, so the order of entries is kept.
+    fn submit_batch(&mut self) -> Result<(), Error> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let len = self.batch.len();
+        update_stats(|stats| {
+            stats.resubmit_batches += 1;
+            stats.resubmitted += len as u64;
+            stats.max_resubmit_batch = cmp::max(stats.max_resubmit_batch, len);
+        });
+        self.backlog.extend(self.batch.drain(..));
+        self.submit(Some(Duration::ZERO))
+    }
+
+    /// Reaps the completions.
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[inline(always)]
+    fn reap(&mut self, scheduler: &mut Scheduler) -> bool {
+        loop {
+            if unlikely(self.reap_queue(scheduler)) {
+                return true;
+            }
+            if likely(!self.has_overflow()) {
+                return false;
+            }
+            if !self.flush_overflow() {
+                return false;
+            }
+        }
+    }
+
+    /// Returns true, if completions have overflowed the completion queue and wait in the kernel.
+    #[inline(always)]
+    fn has_overflow(&self) -> bool {
+        unsafe { (*self.ring.get()).submission_shared().cq_overflow() }
+    }
+
+    /// Enters the kernel with `IORING_ENTER_GETEVENTS`, so it moves the overflowed completions to the reaped completion queue.
+    /// The submission with `SQPOLL` doesn't enter the kernel, so without it they would wait forever.
+    ///
+    /// Returns false, if the kernel can't be entered now. Then they are flushed at the next poll.
+    #[cold]
+    fn flush_overflow(&mut self) -> bool {
+        let ring = unsafe { &mut *self.ring.get() };
+        let dropped = ring.completion().overflow() as u64;
+        update_stats(|stats| {
+            stats.cq_overflows += 1;
+            stats.dropped_completions = dropped;
+        });
+        unsafe { ring.submitter().enter::<libc::sigset_t>(0, 0, ENTER_GETEVENTS, None) }.is_ok()
+    }
+
+    /// Reaps the completions, that are in the completion queue.
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[inline(always)]
+    fn reap_queue(&mut self, scheduler: &mut Scheduler) -> bool {
+        let ring = unsafe { &mut *self.ring.get() };
+        let mut cq = ring.completion();
+        cq.sync();
+
+        for cqe in &mut cq {
+            if unlikely(cqe.user_data() == WAKE_USER_DATA) {
+                // Another thread has woken the worker up. It is not an operation of the worker, so it is not in flight.
+                update_stats(|stats| stats.ring_wakeups += 1);
+                continue;
+            }
+            // A multishot receive stays in flight, until it completes without the MORE flag.
+            if likely(!cqueue::more(cqe.flags())) {
+                self.in_flight -= 1;
+            }
+            if cqe.user_data() == CANCEL_USER_DATA {
+                continue;
+            }
+            let ret = cqe.result();
+            if cqe.user_data() & TIMEOUT_TAG != 0 {
+                drop(unsafe { Box::from_raw((cqe.user_data() & !TIMEOUT_TAG) as *mut Timespec) });
+                continue;
+            }
+            if cqe.user_data() & LINK_TAG != 0 {
+                if unlikely(self.handle_linked_completion(scheduler, ret, Ptr::from(cqe.user_data() & !LINK_TAG))) {
+                    return true;
+                }
+                continue;
+            }
+            if cqe.user_data() & SLEEP_TAG != 0 {
+                let coroutine = self.sleeps.as_mut().unwrap().remove((cqe.user_data() & !SLEEP_TAG) as u32);
+                if unlikely(scheduler.handle_coroutine_state(self, coroutine)) {
+                    return true;
+                }
+                continue;
+            }
+            if cqe.user_data() & MULTISHOT_TAG != 0 {
+                let id = (cqe.user_data() & !MULTISHOT_TAG) as u32;
+                if unlikely(self.handle_multishot_recv(scheduler, id, ret, cqe.flags())) {
+                    return true;
+                }
+                continue;
+            }
+            let token = Ptr::from(cqe.user_data());
+            if unlikely(cqueue::more(cqe.flags())) {
+                // The zero-copy send has completed, but its buffer is released only with the notification.
+                let PollState::WriteTcp(state) = (unsafe { token.as_mut() }) else {
+                    panic!("[BUG] a completion with the MORE flag of not a WriteTcp state. Please report this issue.")
+                };
+                state.sent = Some(ret);
+                continue;
+            }
+            let ret = if unlikely(cqe.flags() & CQE_F_NOTIF != 0) {
+                let PollState::WriteTcp(state) = (unsafe { token.as_mut() }) else {
+                    panic!("[BUG] a notification of not a WriteTcp state. Please report this issue.")
+                };
+                state.sent.take().unwrap()
+            } else {
+                ret
+            };
+            if unlikely(self.handle_completion(scheduler, ret, token)) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Counts the polls with a full submission queue and resizes the ring, if it is enabled and no operations are in flight.
+    #[inline(always)]
+    fn check_resize(&mut self) {
+        if likely(self.backlog.is_empty()) {
+            self.full_polls = 0;
+            return;
+        }
+
+        self.full_polls += 1;
+        if self.resize && self.full_polls >= RESIZE_AFTER_POLLS && self.entries < MAX_RING_ENTRIES && self.in_flight == 0 {
+            self.grow_ring();
+        }
+    }
+
+    /// Recreates the ring with twice as many entries. The old ring must have no operations in flight,
+    /// because their completions would be lost.
+    fn grow_ring(&mut self) {
+        debug_assert_eq!(self.in_flight, 0);
+        let entries = cmp::min(self.entries * 2, MAX_RING_ENTRIES);
+        let ring = build_ring(self.setup, entries).and_then(|(ring, _)| {
+            let has_fixed_buffers = self.has_fixed_buffers && register_fixed_buffers(&ring, buf_pool().fixed())?;
+            register_fixed_files(&ring, &self.fixed_files)?;
+            if let Some(recvs) = &mut self.recvs {
+                // The new ring reads the ring of buffers from the start.
+                recvs.reprovide();
+                register_buf_ring(&ring, &recvs.buf_ring)?;
+            }
+            Ok((ring, has_fixed_buffers))
+        });
+        match ring {
+            Ok((ring, has_fixed_buffers)) => {
+                self.has_fixed_buffers = has_fixed_buffers;
+                self.ring = UnsafeCell::new(ring);
+                self.entries = entries;
+                self.full_polls = 0;
+                update_stats(|stats| {
+                    stats.ring_entries = entries;
+                    stats.resizes += 1;
+                });
+            }
+            // The old ring keeps working, and the resize is not retried.
+            Err(_) => self.resize = false
+        }
+    }
+
+    /// Arms the multishot receive of the fd for the [`PollTcpState`](crate::io::PollTcpState), if it is not armed yet.
+    /// If the receive has ready completions, the coroutine is woken up at the next poll.
+    fn register_multishot_recv(&mut self, state_ptr: Ptr<PollState>, fd: RawFd) {
+        let recvs = self.recvs.as_mut().unwrap();
+        let id = recvs.get_or_insert(fd);
+        // The slice of the previous read is not used anymore.
+        recvs.release_held(id);
+        let recv = recvs.get_mut(id);
+        recv.waiting = Some(state_ptr);
+        if !recv.ready.is_empty() {
+            self.ready_recvs.push_back(id);
+            return;
+        }
+        if recv.is_armed {
+            return;
+        }
+
+        recv.is_armed = true;
+        let entry = with_fd!(self, fd, |fd| {
+            opcode::RecvMulti::new(fd, BUF_GROUP)
+                .build()
+        });
+        self.add_sqe(entry.user_data(MULTISHOT_TAG | id as u64));
+    }
+
+    /// Handles a completion of a multishot receive.
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[must_use]
+    fn handle_multishot_recv(&mut self, scheduler: &mut Scheduler, id: u32, ret: i32, flags: u32) -> bool {
+        let recvs = self.recvs.as_mut().unwrap();
+        if !cqueue::more(flags) {
+            recvs.get_mut(id).is_armed = false;
+        }
+        let received = match cqueue::buffer_select(flags) {
+            Some(bid) if ret > 0 => Received::Data(bid, ret as usize),
+            Some(bid) => {
+                recvs.buf_ring.recycle(bid);
+                Received::Eof
+            }
+            None if ret >= 0 => Received::Eof,
+            None => Received::Error(-ret)
+        };
+
+        let recv = recvs.get_mut(id);
+        if recv.is_closed {
+            // The receive is cancelled, and its connection is closed.
+            let is_armed = recv.is_armed;
+            if let Received::Data(bid, _) = received {
+                recvs.buf_ring.recycle(bid);
+            }
+            if !is_armed {
+                recvs.remove(id);
+            }
+            return false;
+        }
+
+        if received == Received::Error(libc::ENOBUFS) {
+            // All buffers are taken, so the waiting coroutine falls back to a poll and a recv.
+            // If completions are ready, the coroutine gets them first, and the next read arms the receive again.
+            if let Some(state_ptr) = recv.waiting.take_if(|_| recv.ready.is_empty()) {
+                let PollState::PollTcp(state) = (unsafe { state_ptr.read() }) else {
+                    panic!("[BUG] a multishot receive waits with a not PollTcp state. Please report this issue.")
+                };
+                unsafe { state_ptr.write(PollState::new_poll_tcp(state.fd, state.coalesce, state.coroutine, state.result)) };
+                let entry = with_fd!(self, state.fd, |fd| {
+                    opcode::PollAdd::new(fd, libc::POLLIN as _)
+                        .build()
+                });
+                self.add_sqe(entry.user_data(state_ptr.as_u64()));
+            }
+            return false;
+        }
+
+        recv.ready.push_back(received);
+        self.wake_recv(scheduler, id)
+    }
+
+    /// Wakes the coroutine, that waits for data of the multishot receive, up, if a completion is ready for it.
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[must_use]
+    fn wake_recv(&mut self, scheduler: &mut Scheduler, id: u32) -> bool {
+        let recvs = self.recvs.as_mut().unwrap();
+        let Some(state_ptr) = recvs.take_waiting(id) else {
+            return false;
+        };
+        state_trace::record_state(unsafe { state_ptr.as_ref() }, StateEventKind::Complete, 0);
+        let PollState::PollTcp(state) = (unsafe { state_ptr.read() }) else {
+            panic!("[BUG] a multishot receive waits with a not PollTcp state. Please report this issue.")
+        };
+        // The stream reads the fd from the state at the next read.
+        unsafe { state_ptr.write(PollState::new_empty(state.fd)) };
+
+        let res = recvs.read(id, state.coalesce, &mut self.coalesce_buf);
+        if matches!(res, Ok(slice) if slice.is_empty()) {
+            unsafe { state_ptr.write(PollState::new_eof(state.fd)) };
+        }
+        unsafe { state.result.write(res) };
+        scheduler.handle_coroutine_state(self, state.coroutine)
+    }
+
+    /// Handles a completion of a linked entry, that is not the last in its chain. Read [`IoUringSelector::add_link`].
+    ///
+    /// # Return
+    ///
+    /// Returns true, if [`end`](crate::coroutine::YieldStatus::End) was handled.
+    #[must_use]
+    fn handle_linked_completion(&mut self, scheduler: &mut Scheduler, ret: i32, ptr: Ptr<PollState>) -> bool {
+        state_trace::record_state(unsafe { ptr.as_ref() }, StateEventKind::Complete, ret);
+        match unsafe { ptr.as_mut() } {
+            PollState::WriteAllSyncFile(state) => {
+                state.written = Some(ret);
+                if state.synced.is_none() {
+                    return false;
+                }
+            }
+            state => panic!("[BUG] a linked completion of {state:?} in [`IoUringSelector`]. Please report this issue.")
+        }
+
+        self.finish_write_all_sync(scheduler, ptr)
+    }
+
+    /// Handles the [`WriteAllSyncFileState`](crate::io::WriteAllSyncFileState), when both its write and its sync have completed.
+    #[must_use]
+    fn finish_write_all_sync(&mut self, scheduler: &mut Scheduler, ptr: Ptr<PollState>) -> bool {
+        let PollState::WriteAllSyncFile(mut state) = (unsafe { ptr.read() }) else {
+            panic!("[BUG] tried to finish not a WriteAllSyncFile state in [`IoUringSelector`]. Please report this issue.")
+        };
+        unsafe { ptr.write(PollState::new_empty(state.fd)) };
+        let (written, synced) = (state.written.unwrap(), state.synced.unwrap());
+        handle_ret!(written, state, scheduler, self);
+
+        if !state.cursor.is_null() {
+            unsafe { *state.cursor += written as u64 };
+        }
+        if unlikely(written == 0 && state.buffer.len() > 0) {
+            write_err!(state.result, Error::new(ErrorKind::WriteZero, "failed to write the whole buffer"));
+            return scheduler.handle_coroutine_state(self, state.coroutine);
+        }
+        if (written as usize) < state.buffer.len() {
+            // The short write has cancelled the sync, so the rest is written and synced again.
+            state.buffer.set_offset(state.buffer.offset() + written as usize);
+            let offset = advance_offset(state.offset, written as u64);
+            unsafe {
+                ptr.write(PollState::new_write_all_sync_file(
+                    state.fd, state.buffer, offset, state.cursor, state.data_only, state.coroutine, state.result
+                ));
+            }
+
+            self.register(ptr);
+            return false;
+        }
+        handle_ret!(synced, state, scheduler, self);
+
+        write_ok!(state.result, ());
+        scheduler.handle_coroutine_state(self, state.coroutine)
+    }
+
     #[inline(always)]
     #[must_use]
     fn handle_completion(&mut self, scheduler: &mut Scheduler, ret: i32, ptr: Ptr<PollState>) -> bool {
+        state_trace::record_state(unsafe { ptr.as_ref() }, StateEventKind::Complete, ret);
+        if unlikely(ret == -libc::EINTR || ret == -libc::EAGAIN) && unsafe { ptr.as_ref() }.is_retryable() {
+            // The operation was interrupted by a signal or found the fd not ready, so it is submitted again,
+            // and the coroutine keeps waiting instead of getting the error.
+            self.register(ptr);
+            return false;
+        }
         let state = unsafe { ptr.read() };
 
         match state {
@@ -116,6 +729,8 @@ impl IoUringSelector {
                 panic!("[BUG] tried to handle an empty state in [`IoUringSelector`]. Please report this issue.")
             }
             PollState::AcceptTcp(state) => {
+                // The listener reads the fd from the state at the next accept, so the read one must not stay in the pointer.
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret!(ret, state, scheduler, self);
 
                 let accepted_fd = ret;
@@ -124,31 +739,56 @@ impl IoUringSelector {
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
             PollState::ConnectTcp(state) => {
+                // The state has been read from the pointer, so only the memory is freed.
+                unsafe { ptr.dealloc() };
+                // The linked timeout has cancelled the connect.
+                let ret = if ret == -libc::ECANCELED && state.timeout.is_some() { -libc::ETIMEDOUT } else { ret };
                 handle_ret!(ret, state, scheduler, self);
 
                 write_ok!(state.result, TcpStream::new(state.socket.into_raw_fd()));
 
-                let res = scheduler.handle_coroutine_state(self, state.coroutine);
-                unsafe { ptr.drop_in_place() };
-                res
+                scheduler.handle_coroutine_state(self, state.coroutine)
             }
             PollState::PollTcp(state) => {
+                // The stream closes only the empty state, so the read one must not stay in the pointer.
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret!(ret, state, scheduler, self);
 
-                unsafe { ptr.write(PollState::new_read_tcp(state.fd, buffer(), state.coroutine, state.result)) };
+                unsafe { ptr.write(PollState::new_read_tcp(state.fd, buffer(), state.coalesce, state.coroutine, state.result)) };
 
                 self.register(ptr);
                 false
             }
             PollState::ReadTcp(state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret!(ret, state, scheduler, self);
 
-                let slice = unsafe { mem::transmute(&state.buffer.slice[..ret as usize]) };
+                let n = ret as usize;
+                if n == 0 {
+                    unsafe { ptr.write(PollState::new_eof(state.fd)) };
+                }
+                let slice = if state.coalesce > n && n > 0 {
+                    self.coalesce_buf.clear();
+                    self.coalesce_buf.extend_from_slice(&state.buffer.slice[..n]);
+                    recv_more(state.fd, &mut self.coalesce_buf, state.coalesce);
+                    unsafe { mem::transmute::<&[u8], &'static [u8]>(self.coalesce_buf.as_slice()) }
+                } else {
+                    unsafe { mem::transmute::<&[u8], &'static [u8]>(&state.buffer.slice[..n]) }
+                };
                 write_ok!(state.result, slice);
 
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
             PollState::WriteTcp(mut state) => {
+                if state.zero_copy && ret == -libc::EINVAL {
+                    // Kernels without IORING_OP_SEND_ZC return EINVAL, so we fall back to the usual send.
+                    state.zero_copy = false;
+                    unsafe { ptr.write(PollState::WriteTcp(state)) };
+                    self.register(ptr);
+                    return false;
+                }
+                // The owner drops the state, so the read one must not stay in the pointer.
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret!(ret, state, scheduler, self);
 
                 if ret as usize == state.buffer.len() {
@@ -161,6 +801,7 @@ impl IoUringSelector {
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
             PollState::WriteAllTcp(mut state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret!(ret, state, scheduler, self);
 
                 if ret as usize == state.buffer.len() {
@@ -170,13 +811,220 @@ impl IoUringSelector {
                     state.buffer.set_offset(state.buffer.offset() + ret as usize);
                     unsafe { ptr.write(PollState::new_write_all_tcp(state.fd, state.buffer, state.coroutine, state.result)) };
 
-                    self.register(ptr);
+                    self.pending_writes.push_back(ptr);
                     false
                 }
             }
             PollState::CloseTcp(state) => {
+                // The owner drops the state after closing, so it must not be dropped twice.
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
                 handle_ret_without_result!(ret, state, scheduler, self);
 
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::WaitReadable(state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                if ret < 0 {
+                    write_err!(state.result, ring_error(ret));
+                } else {
+                    write_ok!(state.result, ());
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::OpenFile(state) => {
+                unsafe { ptr.dealloc() };
+                if ret < 0 {
+                    // The ring returns -errno and doesn't set errno, so the error is built from ret,
+                    // otherwise create_new would not fail with AlreadyExists.
+                    write_err!(state.result, ring_error(ret));
+                    return scheduler.handle_coroutine_state(self, state.coroutine);
+                }
+
+                write_ok!(state.result, File::from_opened(ret, state.path, state.flags, state.mode));
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::ReadFile(mut state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret!(ret, state, scheduler, self);
+
+                if !state.cursor.is_null() {
+                    unsafe { *state.cursor += ret as u64 };
+                }
+                state.buffer.set_written(ret as usize);
+                write_ok!(state.result, state.buffer);
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::ReadToEndFile(mut state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret!(ret, state, scheduler, self);
+
+                if !state.cursor.is_null() {
+                    unsafe { *state.cursor += ret as u64 };
+                }
+                if ret == 0 {
+                    write_ok!(state.result, state.buffer);
+                    return scheduler.handle_coroutine_state(self, state.coroutine);
+                }
+
+                let len = state.buffer.len() + ret as usize;
+                state.buffer.set_written(len);
+                if len == state.buffer.cap() {
+                    state.buffer.reserve(len);
+                }
+                unsafe { ptr.write(PollState::new_read_to_end_file(state.fd, state.buffer, state.offset, state.cursor, state.coroutine, state.result)) };
+
+                self.register(ptr);
+                false
+            }
+            PollState::WriteFile(mut state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret!(ret, state, scheduler, self);
+
+                if !state.cursor.is_null() {
+                    unsafe { *state.cursor += ret as u64 };
+                }
+                if ret as usize == state.buffer.len() {
+                    write_ok!(state.result, None);
+                } else {
+                    state.buffer.set_offset(state.buffer.offset() + ret as usize);
+                    write_ok!(state.result, Some(state.buffer));
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::WriteAllFile(mut state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret!(ret, state, scheduler, self);
+
+                if !state.cursor.is_null() {
+                    unsafe { *state.cursor += ret as u64 };
+                }
+                if ret as usize == state.buffer.len() {
+                    write_ok!(state.result, ());
+                    scheduler.handle_coroutine_state(self, state.coroutine)
+                } else if unlikely(ret == 0) {
+                    // The file can't take more bytes (like a full device), so submitting the rest again would never end.
+                    write_err!(state.result, Error::new(ErrorKind::WriteZero, "failed to write the whole buffer"));
+                    scheduler.handle_coroutine_state(self, state.coroutine)
+                } else {
+                    state.buffer.set_offset(state.buffer.offset() + ret as usize);
+                    let offset = advance_offset(state.offset, ret as u64);
+                    unsafe { ptr.write(PollState::new_write_all_file(state.fd, state.buffer, offset, state.cursor, state.coroutine, state.result)) };
+
+                    self.register(ptr);
+                    false
+                }
+            }
+            PollState::WriteAllSyncFile(mut state) => {
+                state.synced = Some(ret);
+                let is_written = state.written.is_some();
+                unsafe { ptr.write(PollState::WriteAllSyncFile(state)) };
+                if !is_written {
+                    return false;
+                }
+
+                self.finish_write_all_sync(scheduler, ptr)
+            }
+            PollState::CloseFile(state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret_without_result!(ret, state, scheduler, self);
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::CopyFile(_) => {
+                panic!("[BUG] tried to handle a copy in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::Symlink(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_SYMLINKAT return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::symlink(state.original.as_ptr(), state.link.as_ptr()) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::HardLink(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_LINKAT return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::link(state.original.as_ptr(), state.link.as_ptr()) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::ReadLink(_) => {
+                panic!("[BUG] tried to handle a read of a link in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::SetPermissions(_) | PollState::SetFilePermissions(_) => {
+                panic!("[BUG] tried to handle a change of permissions in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::AdviseFile(state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                // Kernels without IORING_OP_FADVISE return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL {
+                    -unsafe { libc::posix_fadvise(state.fd, state.offset as libc::off_t, state.len as libc::off_t, state.advice) }
+                } else {
+                    ret
+                };
+                if ret < 0 {
+                    write_err!(state.result, ring_error(ret));
+                } else {
+                    write_ok!(state.result, ());
+                }
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::CreateDir(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_MKDIRAT return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::mkdir(state.path.as_ptr(), state.mode as libc::mode_t) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::RemoveFile(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_UNLINKAT return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::unlink(state.path.as_ptr()) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::RemoveDir(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_UNLINKAT or AT_REMOVEDIR support return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::rmdir(state.path.as_ptr()) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::Rename(state) => {
+                unsafe { ptr.dealloc() };
+                // Kernels without IORING_OP_RENAMEAT return EINVAL, so we fall back to the syscall.
+                let ret = if ret == -libc::EINVAL { as_ring_ret(unsafe { libc::rename(state.from.as_ptr(), state.to.as_ptr()) }) } else { ret };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
+                scheduler.handle_coroutine_state(self, state.coroutine)
+            }
+            PollState::SyncFile(state) => {
+                unsafe { ptr.write(PollState::new_empty(state.fd)) };
+                handle_ret!(ret, state, scheduler, self);
+
+                write_ok!(state.result, ());
+
                 scheduler.handle_coroutine_state(self, state.coroutine)
             }
         }
@@ -185,85 +1033,789 @@ impl IoUringSelector {
 
 impl Selector for IoUringSelector {
     #[inline(always)]
-    fn need_reregister(&self) -> bool {
-        true
+    fn supports_multishot(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn file_op_support(&self) -> FileOpSupport {
+        self.file_op_support
     }
 
     fn deregister(&mut self, _fd: RawFd) {}
 
     #[inline(always)]
-    fn poll(&mut self, scheduler: &mut Scheduler) -> Result<bool, ()> {
-        if self.submit().is_err() {
-            return Err(())
+    fn poll(&mut self, scheduler: &mut Scheduler, mut timeout: Option<Duration>) -> Result<bool, Error> {
+        while let Some(state_ptr) = self.pending_writes.pop_front() {
+            self.register(state_ptr);
+        }
+        // Woken coroutines can read again, so only the receives, that are ready now, are handled.
+        for _ in 0..self.ready_recvs.len() {
+            let id = self.ready_recvs.pop_front().unwrap();
+            if unlikely(self.wake_recv(scheduler, id)) {
+                return Ok(true);
+            }
+        }
+        if !self.ready_recvs.is_empty() {
+            timeout = Some(Duration::ZERO);
         }
 
-        let ring = unsafe { &mut *self.ring.get() };
-        let mut cq = ring.completion();
-        cq.sync();
-
-        for cqe in &mut cq {
-            let ret = cqe.result();
-            let token = Ptr::from(cqe.user_data());
-            if unlikely(self.handle_completion(scheduler, ret, token)) {
+        loop {
+            self.submit(timeout)?;
+            timeout = Some(Duration::ZERO);
+            self.is_reaping = true;
+            let is_ended = self.reap(scheduler);
+            self.is_reaping = false;
+            if unlikely(is_ended) {
                 return Ok(true);
             }
+            self.submit_batch()?;
+
+            // Over the cap, the backlog is flushed here instead of growing, while coroutines submit more.
+            if likely(self.backlog.len() <= self.backlog_cap) {
+                break;
+            }
+            update_stats(|stats| stats.capped += 1);
         }
 
+        self.check_resize();
         Ok(false)
     }
 
     #[inline(always)]
     fn register(&mut self, state_ptr: Ptr<PollState>) {
         let state = unsafe { state_ptr.as_mut() };
+        state_trace::record_state(state, StateEventKind::Submit, 0);
+        if let (PollState::PollTcp(state), Some(_)) = (&*state, &self.recvs) {
+            return self.register_multishot_recv(state_ptr, state.fd);
+        }
 
         let mut entry = match state {
             PollState::Empty(_) => { panic!("[BUG] tried to register an empty state in [`IoUringSelector`]. Please report this issue.") }
-            PollState::AcceptTcp(state) => {
-                opcode::Accept::new(types::Fd(state.fd), ptr::null_mut(), ptr::null_mut())
+            PollState::AcceptTcp(state) => with_fd!(self, state.fd, |fd| {
+                opcode::Accept::new(fd, ptr::null_mut(), ptr::null_mut())
                     .build()
-            }
+            }),
             PollState::ConnectTcp(state) => {
-                opcode::Connect::new(types::Fd(state.socket.as_raw_fd()), state.address.as_ptr(), state.address.len())
+                let entry = opcode::Connect::new(types::Fd(state.socket.as_raw_fd()), state.address.as_ptr(), state.address.len())
+                    .build();
+                match state.timeout {
+                    Some(timeout) => return self.add_link_timeout(entry.user_data(state_ptr.as_u64()), timeout),
+                    None => entry
+                }
+            }
+            PollState::PollTcp(state) => with_fd!(self, state.fd, |fd| {
+                opcode::PollAdd::new(fd, libc::POLLIN as _)
+                    .build()
+            }),
+            PollState::ReadTcp(state) => with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                Some(index) => opcode::ReadFixed::new(fd, state.buffer.as_mut_ptr(), state.buffer.cap() as _, index)
+                    .build(),
+                None => opcode::Recv::new(fd, state.buffer.as_mut_ptr(), state.buffer.cap() as _)
+                    .build()
+            }),
+            PollState::WriteTcp(state) if state.zero_copy => with_fd!(self, state.fd, |fd| {
+                opcode::SendZc::new(fd, state.buffer.as_ptr(), state.buffer.len() as _)
+                    .buf_index(self.fixed_index(&state.buffer))
+                    .build()
+            }),
+            PollState::WriteTcp(state) => with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                Some(index) => opcode::WriteFixed::new(fd, state.buffer.as_ptr(), state.buffer.len() as _, index)
+                    .build(),
+                None => opcode::Send::new(fd, state.buffer.as_ptr(), state.buffer.len() as _)
+                    .build()
+            }),
+            PollState::WriteAllTcp(state) => with_fd!(self, state.fd, |fd| {
+                let len = cmp::min(state.buffer.len(), self.write_turn_cap);
+                match self.fixed_index(&state.buffer) {
+                    Some(index) => opcode::WriteFixed::new(fd, state.buffer.as_ptr(), len as _, index)
+                        .build(),
+                    None => opcode::Send::new(fd, state.buffer.as_ptr(), len as _)
+                        .build()
+                }
+            }),
+            PollState::CloseTcp(state) => {
+                // The armed multishot receive holds a reference to the socket too, so it is cancelled first.
+                if let Some(id) = self.recvs.as_mut().and_then(|recvs| recvs.close(state.fd)) {
+                    let entry = opcode::AsyncCancel::new(MULTISHOT_TAG | id as u64)
+                        .build()
+                        .user_data(CANCEL_USER_DATA);
+                    self.add_sqe(entry);
+                }
+                // The slot holds a reference to the file, so the file is not closed, until the slot is freed.
+                self.unregister_file(state.fd);
+                opcode::Close::new(types::Fd(state.fd))
                     .build()
             }
-            PollState::PollTcp(state) => {
-                opcode::PollAdd::new(types::Fd(state.fd), libc::POLLIN as _)
+            PollState::WaitReadable(state) => with_fd!(self, state.fd, |fd| {
+                opcode::PollAdd::new(fd, libc::POLLIN as _)
+                    .build()
+            }),
+            PollState::OpenFile(state) => {
+                opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), state.path.as_ptr())
+                    .flags(state.flags)
+                    .mode(state.mode)
                     .build()
             }
-            PollState::ReadTcp(state) => {
-                opcode::Recv::new(types::Fd(state.fd), state.buffer.as_mut_ptr(), state.buffer.cap() as _)
+            PollState::ReadFile(state) => with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                Some(index) => opcode::ReadFixed::new(fd, state.buffer.as_mut_ptr(), state.buffer.cap() as _, index)
+                    .offset(state.offset)
+                    .build(),
+                None => opcode::Read::new(fd, state.buffer.as_mut_ptr(), state.buffer.cap() as _)
+                    .offset(state.offset)
+                    .build()
+            }),
+            PollState::ReadToEndFile(state) => with_fd!(self, state.fd, |fd| {
+                let len = state.buffer.len();
+                opcode::Read::new(fd, state.buffer.slice[len..].as_mut_ptr(), (state.buffer.cap() - len) as _)
+                    .offset(state.offset + len as u64)
+                    .build()
+            }),
+            PollState::WriteFile(state) => with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                Some(index) => opcode::WriteFixed::new(fd, state.buffer.as_ptr(), state.buffer.len() as _, index)
+                    .offset(state.offset)
+                    .build(),
+                None => opcode::Write::new(fd, state.buffer.as_ptr(), state.buffer.len() as _)
+                    .offset(state.offset)
                     .build()
+            }),
+            PollState::WriteAllFile(state) => with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                Some(index) => opcode::WriteFixed::new(fd, state.buffer.as_ptr(), state.buffer.len() as _, index)
+                    .offset(state.offset)
+                    .build(),
+                None => opcode::Write::new(fd, state.buffer.as_ptr(), state.buffer.len() as _)
+                    .offset(state.offset)
+                    .build()
+            }),
+            PollState::WriteAllSyncFile(state) => {
+                let write = with_fd!(self, state.fd, |fd| match self.fixed_index(&state.buffer) {
+                    Some(index) => opcode::WriteFixed::new(fd, state.buffer.as_ptr(), state.buffer.len() as _, index)
+                        .offset(state.offset)
+                        .build(),
+                    None => opcode::Write::new(fd, state.buffer.as_ptr(), state.buffer.len() as _)
+                        .offset(state.offset)
+                        .build()
+                });
+                let flags = if state.data_only { types::FsyncFlags::DATASYNC } else { types::FsyncFlags::empty() };
+                let sync = with_fd!(self, state.fd, |fd| {
+                    opcode::Fsync::new(fd)
+                        .flags(flags)
+                        .build()
+                });
+                state.written = None;
+                state.synced = None;
+                return self.add_link([write.user_data(LINK_TAG | state_ptr.as_u64()), sync.user_data(state_ptr.as_u64())]);
             }
-            PollState::WriteTcp(state) => {
-                opcode::Send::new(types::Fd(state.fd), state.buffer.as_ptr(), state.buffer.len() as _)
+            PollState::CloseFile(state) => {
+                self.unregister_file(state.fd);
+                opcode::Close::new(types::Fd(state.fd))
                     .build()
             }
-            PollState::WriteAllTcp(state) => {
-                opcode::Send::new(types::Fd(state.fd), state.buffer.as_ptr(), state.buffer.len() as _)
+            PollState::CopyFile(_) => {
+                // io_uring has no copy_file_range, so `file_op_support` doesn't report copies, and they run on the blocking pool.
+                panic!("[BUG] tried to register a copy in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::Symlink(state) => {
+                opcode::SymlinkAt::new(types::Fd(libc::AT_FDCWD), state.original.as_ptr(), state.link.as_ptr())
                     .build()
             }
-            PollState::CloseTcp(state) => {
-                opcode::Close::new(types::Fixed(state.fd as u32))
+            PollState::HardLink(state) => {
+                opcode::LinkAt::new(types::Fd(libc::AT_FDCWD), state.original.as_ptr(), types::Fd(libc::AT_FDCWD), state.link.as_ptr())
                     .build()
             }
+            PollState::ReadLink(_) => {
+                // io_uring has no readlink, so `file_op_support` doesn't report reads of links, and they run on the blocking pool.
+                panic!("[BUG] tried to register a read of a link in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::SetPermissions(_) | PollState::SetFilePermissions(_) => {
+                // io_uring has no chmod, so `file_op_support` doesn't report changes of permissions, and they run on the blocking pool.
+                panic!("[BUG] tried to register a change of permissions in [`IoUringSelector`], that doesn't support it. Please report this issue.")
+            }
+            PollState::AdviseFile(state) => with_fd!(self, state.fd, |fd| {
+                opcode::Fadvise::new(fd, state.len as libc::off_t, state.advice)
+                    .offset(state.offset)
+                    .build()
+            }),
+            PollState::CreateDir(state) => {
+                opcode::MkDirAt::new(types::Fd(libc::AT_FDCWD), state.path.as_ptr())
+                    .mode(state.mode)
+                    .build()
+            }
+            PollState::RemoveFile(state) => {
+                opcode::UnlinkAt::new(types::Fd(libc::AT_FDCWD), state.path.as_ptr())
+                    .build()
+            }
+            PollState::RemoveDir(state) => {
+                opcode::UnlinkAt::new(types::Fd(libc::AT_FDCWD), state.path.as_ptr())
+                    .flags(libc::AT_REMOVEDIR)
+                    .build()
+            }
+            PollState::Rename(state) => {
+                opcode::RenameAt::new(types::Fd(libc::AT_FDCWD), state.from.as_ptr(), types::Fd(libc::AT_FDCWD), state.to.as_ptr())
+                    .build()
+            }
+            PollState::SyncFile(state) => with_fd!(self, state.fd, |fd| {
+                let flags = if state.data_only { types::FsyncFlags::DATASYNC } else { types::FsyncFlags::empty() };
+                opcode::Fsync::new(fd)
+                    .flags(flags)
+                    .build()
+            }),
         };
 
         entry = entry.user_data(state_ptr.as_u64());
         self.add_sqe(entry);
     }
 
-    #[inline(always)]
-    fn write(&mut self, state_ref: Ptr<PollState>) {
-        self.register(state_ref);
+    fn register_file(&mut self, fd: RawFd) -> Result<bool, Error> {
+        let Some(slot) = self.fixed_files.alloc(fd) else {
+            return Ok(self.fixed_files.get(fd).is_some());
+        };
+        let ring = unsafe { &*self.ring.get() };
+        if let Err(err) = ring.submitter().register_files_update(slot, &[fd]) {
+            self.fixed_files.free(fd);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    fn cancel(&mut self, state_ref: Ptr<PollState>) -> Option<CoroutineImpl> {
+        if let (PollState::PollTcp(state), Some(recvs)) = (unsafe { state_ref.as_ref() }, &mut self.recvs) {
+            // The multishot receive stays armed for the next read.
+            if recvs.forget_waiting(state.fd, state_ref) {
+                return PollState::cancel(state_ref);
+            }
+        }
+        // The rest of a write, that waits for its turn, is not submitted yet, so there is nothing to cancel in the ring.
+        if let Some(index) = self.pending_writes.iter().position(|ptr| ptr.as_u64() == state_ref.as_u64()) {
+            self.pending_writes.remove(index);
+            if let PollState::WriteAllTcp(state) = unsafe { state_ref.read() } {
+                unsafe { state_ref.write(PollState::new_empty(state.fd)) };
+                write_err!(state.result, Error::from_raw_os_error(libc::ECANCELED));
+                return Some(state.coroutine);
+            }
+        }
+        // The cancelled operation completes with ECANCELED and wakes its coroutine up as usual.
+        let entry = opcode::AsyncCancel::new(state_ref.as_u64())
+            .build()
+            .user_data(CANCEL_USER_DATA);
+        self.add_sqe(entry);
+        None
+    }
+
+    fn sleep(&mut self, dur: Duration, coroutine: CoroutineImpl) -> Option<CoroutineImpl> {
+        let Some(sleeps) = &mut self.sleeps else {
+            return Some(coroutine);
+        };
+        let (id, timespec) = sleeps.insert(dur, coroutine);
+        let entry = opcode::Timeout::new(timespec)
+            .build()
+            .user_data(SLEEP_TAG | id as u64);
+        self.add_sqe(entry);
+        None
     }
 
     #[inline(always)]
-    fn write_all(&mut self, state_ref: Ptr<PollState>) {
-        self.register(state_ref);
+    fn ring_fd(&self) -> Option<RawFd> {
+        Some(unsafe { &*self.ring.get() }.as_raw_fd())
     }
 
     #[inline(always)]
-    fn close_connection(&mut self, state_ref: Ptr<PollState>) {
-        self.register(state_ref);
+    fn pending_states(&self) -> usize {
+        self.in_flight + self.backlog.len() + self.batch.len() + self.pending_writes.len() + self.ready_recvs.len()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind, Read, Write};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::{Duration, Instant};
+    use io_uring::{opcode, types};
+    use crate::buf::buffer;
+    use crate::cfg::{config, enter_worker, leave_worker, SelectorType};
+    use crate::coro;
+    use crate::io::{AsyncRead, AsyncWrite};
+    use crate::buf::Buffer;
+    use crate::fs::{File, OpenOptions};
+    use crate::local::Local;
+    use crate::local_scheduler;
+    use crate::net::{TcpListener, TcpStream};
+    use crate::run::run_on_core_with_config;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+    use crate::io::{submission_stats, RingSetup};
+    use crate::io::sys::unix::io_uring::IoUringSelector;
+    use crate::io::PollState;
+    use crate::utils::Ptr;
+    use crate::io::sys::unix::io_uring::setup::RING_ENTRIES;
+    use super::{CANCEL_USER_DATA, LINK_TAG};
+
+    /// Submits the backlog and reaps the completions without a scheduler. Only Nops are submitted in the tests.
+    fn flush(selector: &mut IoUringSelector) {
+        selector.submit(Some(Duration::ZERO)).unwrap();
+        let ring = unsafe { &mut *selector.ring.get() };
+        for _ in ring.completion() {
+            selector.in_flight -= 1;
+        }
+    }
+
+    #[test]
+    fn test_backlog() {
+        let mut selector = IoUringSelector::new().unwrap();
+        // More entries at once, than the submission queue can take.
+        for _ in 0..RING_ENTRIES * 3 {
+            selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+        }
+        let stats = submission_stats();
+        assert_eq!(stats.spilled, RING_ENTRIES as u64 * 2);
+        assert_eq!(stats.max_backlog, RING_ENTRIES as usize * 2);
+
+        while selector.in_flight > 0 || !selector.backlog.is_empty() {
+            flush(&mut selector);
+        }
+        assert_eq!(submission_stats().backlog, 0);
+
+        selector.grow_ring();
+        let stats = submission_stats();
+        assert_eq!(stats.ring_entries, RING_ENTRIES * 2);
+        assert_eq!(stats.resizes, 1);
+        for _ in 0..RING_ENTRIES * 2 {
+            selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+        }
+        assert_eq!(submission_stats().spilled, stats.spilled);
+        while selector.in_flight > 0 {
+            flush(&mut selector);
+        }
+    }
+
+    #[test]
+    fn test_ring_setup() {
+        let deferred = RingSetup { entries: 64, single_issuer: true, coop_taskrun: true, defer_taskrun: true, ..RingSetup::default() };
+        let polled = RingSetup { entries: 64, sqpoll: Some(10), ..RingSetup::default() };
+        for setup in [deferred, polled] {
+            enter_worker(config().with_ring_setup(setup));
+            let mut selector = IoUringSelector::new().unwrap();
+            assert_eq!(submission_stats().ring_entries, 64);
+            for _ in 0..256 {
+                selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+            }
+            while selector.in_flight > 0 || !selector.backlog.is_empty() {
+                flush(&mut selector);
+            }
+            leave_worker();
+        }
+    }
+
+    #[test]
+    fn test_link() {
+        enter_worker(config().with_ring_setup(RingSetup { entries: 64, ..RingSetup::default() }));
+        let mut selector = IoUringSelector::new().unwrap();
+        for _ in 0..63 {
+            selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+        }
+        // The chain doesn't fit, so it goes to the backlog whole, and it is submitted whole.
+        let spilled = submission_stats().spilled;
+        let failing = opcode::Close::new(types::Fd(-1)).build().user_data(LINK_TAG | 1);
+        selector.add_link([failing, opcode::Nop::new().build().user_data(2), opcode::Nop::new().build().user_data(3)]);
+        assert_eq!(submission_stats().spilled, spilled + 3);
+        assert_eq!(selector.backlog.iter().map(|(_, linked)| *linked).collect::<Vec<_>>(), vec![2, 1, 0]);
+
+        let mut results = Vec::new();
+        while selector.in_flight > 0 || !selector.backlog.is_empty() {
+            selector.submit(Some(Duration::from_millis(10))).unwrap();
+            let ring = unsafe { &mut *selector.ring.get() };
+            for cqe in ring.completion() {
+                selector.in_flight -= 1;
+                if cqe.user_data() != CANCEL_USER_DATA {
+                    results.push((cqe.user_data() & !LINK_TAG, cqe.result()));
+                }
+            }
+        }
+        // The failed entry cancels the rest of the chain.
+        results.sort();
+        assert_eq!(results, vec![(1, -libc::EBADF), (2, -libc::ECANCELED), (3, -libc::ECANCELED)]);
+        leave_worker();
+    }
+
+    #[coro(crate="crate")]
+    fn complete_with_retryable_errors() -> Vec<(bool, bool, usize)> {
+        let mut selector = IoUringSelector::new().unwrap();
+        let mut result: Result<(), Error> = Ok(());
+        let ptr = Ptr::new(PollState::new_wait_readable(0, Box::pin(#[coroutine] static || {}), &mut result));
+        let mut results = Vec::new();
+        for ret in [-libc::EINTR, -libc::EAGAIN] {
+            let is_ended = selector.handle_completion(local_scheduler(), ret, ptr);
+            let is_waiting = matches!(unsafe { ptr.as_ref() }, PollState::WaitReadable(_));
+            let submitted = unsafe { (*selector.ring.get()).submission().len() };
+            results.push((is_ended, is_waiting, submitted));
+        }
+        unsafe { ptr.drop_in_place(); }
+        return results;
+    }
+
+    #[coro(crate="crate")]
+    fn complete_with_zero_write() -> (bool, ErrorKind, bool, usize) {
+        let mut selector = IoUringSelector::new().unwrap();
+        let mut result: Result<(), Error> = Ok(());
+        let mut buf = buffer();
+        buf.append(b"data");
+        let ptr = Ptr::new(PollState::new_write_all_file(0, buf, 0, null_mut(), Box::pin(#[coroutine] static || {}), &mut result));
+        // The file takes no bytes (like a full device), so the write is not submitted again.
+        let is_ended = selector.handle_completion(local_scheduler(), 0, ptr);
+        let is_empty = unsafe { ptr.as_ref() }.is_empty();
+        let submitted = unsafe { (*selector.ring.get()).submission().len() };
+        unsafe { ptr.drop_in_place(); }
+        return (is_ended, result.unwrap_err().kind(), is_empty, submitted);
+    }
+
+    #[test]
+    fn test_write_zero() {
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring);
+        let res = run_on_core_with_config(complete_with_zero_write, core, cfg).unwrap();
+        assert_eq!(res, Some((false, ErrorKind::WriteZero, true, 0)));
+    }
+
+    #[test]
+    fn test_retry_interrupted() {
+        // The state is submitted again, and its coroutine is not woken up with the error.
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring);
+        let results = run_on_core_with_config(complete_with_retryable_errors, core, cfg).unwrap();
+        assert_eq!(results, Some(vec![(false, true, 1), (false, true, 2)]));
+    }
+
+    #[test]
+    fn test_submit_batch() {
+        enter_worker(config());
+        let mut selector = IoUringSelector::new().unwrap();
+        let stats = submission_stats();
+
+        // Entries, that completions add, wait for the end of the reaping.
+        selector.is_reaping = true;
+        for _ in 0..3 {
+            selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+        }
+        selector.add_link([
+            opcode::Nop::new().build().user_data(CANCEL_USER_DATA),
+            opcode::Nop::new().build().user_data(CANCEL_USER_DATA)
+        ]);
+        selector.is_reaping = false;
+        assert_eq!(selector.in_flight, 0);
+        assert_eq!(selector.batch.iter().map(|(_, linked)| *linked).collect::<Vec<_>>(), vec![0, 0, 0, 1, 0]);
+
+        selector.submit_batch().unwrap();
+        assert!(selector.batch.is_empty());
+        assert_eq!(selector.in_flight, 5);
+        let new_stats = submission_stats();
+        assert_eq!(new_stats.resubmit_batches, stats.resubmit_batches + 1);
+        assert_eq!(new_stats.resubmitted, stats.resubmitted + 5);
+        assert!(new_stats.max_resubmit_batch >= 5);
+
+        // An empty batch is not counted.
+        selector.submit_batch().unwrap();
+        assert_eq!(submission_stats().resubmit_batches, new_stats.resubmit_batches);
+        while selector.in_flight > 0 {
+            flush(&mut selector);
+        }
+        leave_worker();
+    }
+
+    #[test]
+    fn test_cq_overflow() {
+        // The completion queue has 16 entries, so most completions overflow it.
+        enter_worker(config().with_ring_setup(RingSetup { entries: 8, ..RingSetup::default() }));
+        let mut selector = IoUringSelector::new().unwrap();
+        for _ in 0..64 {
+            selector.add_sqe(opcode::Nop::new().build().user_data(CANCEL_USER_DATA));
+        }
+        let overflows = submission_stats().cq_overflows;
+
+        let mut completed = 0;
+        while selector.in_flight > 0 || !selector.backlog.is_empty() {
+            selector.submit(Some(Duration::ZERO)).unwrap();
+            let ring = unsafe { &mut *selector.ring.get() };
+            for _ in ring.completion() {
+                selector.in_flight -= 1;
+                completed += 1;
+            }
+            if selector.has_overflow() {
+                assert!(selector.flush_overflow());
+            }
+        }
+        assert_eq!(completed, 64);
+        assert!(submission_stats().cq_overflows > overflows);
+        assert_eq!(submission_stats().dropped_completions, 0);
+        leave_worker();
+    }
+
+    #[coro(crate="crate")]
+    fn connect_with_timeouts(addr: SocketAddr) -> Vec<Result<(), std::io::ErrorKind>> {
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let res: Result<TcpStream, Error> = yield TcpStream::connect_timeout(addr, Duration::from_millis(100));
+            results.push(res.map(drop).map_err(|err| err.kind()));
+        }
+        return results;
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        // The backlog of 0 takes one connection, and the SYN of the next one is dropped, while the first is not accepted.
+        let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+        socket.bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into()).unwrap();
+        socket.listen(0).unwrap();
+        let addr = socket.local_addr().unwrap().as_socket().unwrap();
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring);
+        let results = run_on_core_with_config(move |res| connect_with_timeouts(addr, res), core, cfg).unwrap();
+        assert_eq!(results, Some(vec![Ok(()), Err(std::io::ErrorKind::TimedOut)]));
+        drop(socket);
+    }
+
+    #[coro(crate="crate")]
+    fn sleep_and_push(dur: Duration, woken: Local<Vec<Duration>>) {
+        let start = Instant::now();
+        yield sleep(dur);
+        if start.elapsed() >= dur {
+            woken.get_mut().push(dur);
+        }
+    }
+
+    #[coro(crate="crate")]
+    fn sleep_with_ring_timeouts() -> (Vec<Duration>, usize, usize) {
+        let woken = Local::new(Vec::new());
+        for millis in [30, 10, 20] {
+            local_scheduler().sched(sleep_and_push(Duration::from_millis(millis), woken.clone(), null_mut()));
+        }
+        yield sleep(Duration::from_millis(5));
+        // The sleeping coroutines are in the ring, not in the timer wheel.
+        let metrics = local_scheduler().metrics();
+        yield sleep(Duration::from_millis(50));
+        return (woken.get().clone(), metrics.sleeping, metrics.pending_states);
+    }
+
+    #[test]
+    fn test_ring_timeouts() {
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_ring_timeouts(true);
+        let (woken, sleeping, pending_states) = run_on_core_with_config(sleep_with_ring_timeouts, core, cfg).unwrap().unwrap();
+        assert_eq!(woken, [10, 20, 30].map(Duration::from_millis));
+        assert_eq!(sleeping, 0);
+        assert!(pending_states >= 3);
+    }
+
+    #[coro(crate="crate")]
+    fn write_zc(addr: SocketAddr, len: usize) -> bool {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut buf = buffer();
+        let is_registered = buf.fixed_index().is_some();
+        buf.append(b"ping");
+        let res: Result<Option<Buffer>, Error> = yield stream.write_zc(buf);
+        if res.unwrap().is_some() {
+            return false;
+        }
+
+        let mut rest = Some(buffer());
+        rest.as_mut().unwrap().append(&vec![7u8; len]);
+        while let Some(buf) = rest {
+            let res: Result<Option<Buffer>, Error> = yield stream.write_zc(buf);
+            rest = res.unwrap();
+        }
+        return is_registered;
+    }
+
+    #[test]
+    fn test_write_zc() {
+        const LEN: usize = 256 * 1024;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut ping = [0u8; 4];
+            stream.read_exact(&mut ping).unwrap();
+            let mut received = vec![0u8; LEN];
+            stream.read_exact(&mut received).unwrap();
+            &ping == b"ping" && received.iter().all(|&b| b == 7)
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_fixed_buffers(4);
+        assert_eq!(run_on_core_with_config(move |res| write_zc(addr, LEN, res), core, cfg).unwrap(), Some(true));
+        assert!(peer.join().unwrap());
+    }
+
+    #[coro(crate="crate")]
+    fn echo_fixed(addr: SocketAddr) -> bool {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut buf = buffer();
+        let is_registered = buf.fixed_index().is_some();
+        buf.append(b"ping");
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        res.unwrap();
+        let res: Result<&[u8], Error> = yield stream.read();
+        let is_echoed = res.unwrap() == b"ping";
+        return is_registered && is_echoed;
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_fixed_buffers() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = [0u8; 4];
+            stream.read_exact(&mut received).unwrap();
+            stream.write_all(&received).unwrap();
+            stream
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_fixed_buffers(4);
+        assert_eq!(run_on_core_with_config(move |res| echo_fixed(addr, res), core, cfg).unwrap(), Some(true));
+        drop(peer.join().unwrap());
+    }
+
+    #[coro(crate="crate")]
+    fn use_fixed_files(path: std::path::PathBuf) -> Vec<bool> {
+        let mut registered = Vec::new();
+        let mut listener: TcpListener = yield TcpListener::new("127.0.0.1:0".parse().unwrap());
+        let res: Result<bool, Error> = yield listener.register_fixed();
+        registered.push(res.unwrap());
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || std::net::TcpStream::connect(addr).unwrap());
+        let stream: Result<TcpStream, Error> = yield listener.accept();
+        stream.unwrap();
+        drop(client.join().unwrap());
+
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true);
+        let mut file: File = (yield options.open(path.clone())).unwrap();
+        let res: Result<bool, Error> = yield file.register_fixed();
+        registered.push(res.unwrap());
+        let mut buf = buffer();
+        buf.append(b"fixed");
+        let res: Result<(), Error> = yield file.write_all(buf);
+        res.unwrap();
+        let buf: Buffer = (yield file.pread(0)).unwrap();
+        registered.push(buf.as_ref() == b"fixed");
+
+        // No slots are free.
+        let other: File = (yield File::open(path.clone())).unwrap();
+        let res: Result<bool, Error> = yield other.register_fixed();
+        registered.push(res.unwrap());
+
+        // The slot of the closed file is freed.
+        drop(file);
+        yield sleep(Duration::from_millis(10));
+        let res: Result<bool, Error> = yield other.register_fixed();
+        registered.push(res.unwrap());
+        return registered;
+    }
+
+    #[test]
+    fn test_fixed_files() {
+        let path = std::env::temp_dir().join(format!("coroeng_test_fixed_files_{}", std::process::id()));
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_fixed_files(2);
+        let path_ = path.clone();
+        let registered = run_on_core_with_config(move |res| use_fixed_files(path_.clone(), res), core, cfg).unwrap();
+        assert_eq!(registered, Some(vec![true, true, true, false, true]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[coro(crate="crate")]
+    fn echo_multishot(addr: SocketAddr, connections: usize, len: usize) -> usize {
+        let mut echoed = 0;
+        for _ in 0..connections {
+            let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+            let mut received = 0;
+            while received < len {
+                let res: Result<&[u8], Error> = yield stream.read();
+                let slice = res.unwrap();
+                received += slice.len();
+                let mut buf = buffer();
+                buf.append(slice);
+                let res: Result<(), Error> = yield stream.write_all(buf);
+                res.unwrap();
+            }
+            echoed += received;
+        }
+        // The last stream is closed.
+        yield sleep(Duration::from_millis(10));
+        return echoed;
+    }
+
+    #[test]
+    fn test_multishot_recv() {
+        const MESSAGES: usize = 16;
+        const CONNECTIONS: usize = 2;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            for connection in 0..CONNECTIONS {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+                for i in 0..MESSAGES {
+                    let message = [(connection * MESSAGES + i) as u8; 100];
+                    stream.write_all(&message).unwrap();
+                    let mut echoed = [0u8; 100];
+                    stream.read_exact(&mut echoed).unwrap();
+                    assert_eq!(echoed, message);
+                }
+                // The receive is armed, when the stream is dropped, but the socket is closed.
+                assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+            }
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_multishot_recv_buffers(8);
+        let echoed = run_on_core_with_config(move |res| echo_multishot(addr, CONNECTIONS, MESSAGES * 100, res), core, cfg).unwrap();
+        assert_eq!(echoed, Some(CONNECTIONS * MESSAGES * 100));
+        peer.join().unwrap();
+    }
+
+    #[coro(crate="crate")]
+    fn read_two_streams(addr: SocketAddr) -> Vec<Vec<u8>> {
+        let mut reads = Vec::new();
+        let mut first: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut second: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let res: Result<&[u8], Error> = yield first.read();
+        reads.push(res.unwrap().to_vec());
+        // The only buffer is held by the first stream, so the second one falls back to a poll and a recv.
+        let res: Result<&[u8], Error> = yield second.read();
+        reads.push(res.unwrap().to_vec());
+
+        let mut buf = buffer();
+        buf.append(b"x");
+        let res: Result<(), Error> = yield first.write_all(buf);
+        res.unwrap();
+        let res: Result<&[u8], Error> = yield first.read();
+        reads.push(res.unwrap().to_vec());
+        return reads;
+    }
+
+    #[test]
+    fn test_multishot_recv_without_buffers() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut first, _) = listener.accept().unwrap();
+            let (mut second, _) = listener.accept().unwrap();
+            first.write_all(b"a").unwrap();
+            second.write_all(b"b").unwrap();
+            first.read_exact(&mut [0u8; 1]).unwrap();
+            first.write_all(b"c").unwrap();
+            (first, second)
+        });
+
+        let core = get_core_ids().unwrap()[0];
+        let cfg = config().with_selector(SelectorType::Ring).with_multishot_recv_buffers(1);
+        let reads = run_on_core_with_config(move |res| read_two_streams(addr, res), core, cfg).unwrap();
+        assert_eq!(reads, Some(vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]));
+        drop(peer.join().unwrap());
+    }
+}
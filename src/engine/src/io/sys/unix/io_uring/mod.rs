@@ -1,3 +1,14 @@
 pub(crate) mod io_uring;
+pub(crate) mod capabilities;
+pub(crate) mod backlog;
+pub(crate) mod setup;
+pub(crate) mod fixed_files;
+pub(crate) mod recv_multi;
+pub(crate) mod sleeps;
+pub(crate) mod msg_ring;
 
-pub(crate) use io_uring::*;
\ No newline at end of file
+pub(crate) use io_uring::*;
+pub use capabilities::{uring_capabilities, KernelVersion, UringCapabilities};
+pub use backlog::{submission_stats, SubmissionStats};
+pub use setup::RingSetup;
+pub(crate) use setup::build_ring;
@@ -0,0 +1,70 @@
+//! This module contains [`wake_ring`], the wake-up of a worker with `IORING_OP_MSG_RING`.
+use std::cell::RefCell;
+use std::os::fd::RawFd;
+use io_uring::{opcode, squeue, types, IoUring, Probe};
+
+/// The user data of the completions, that [`wake_ring`] posts to the ring of a worker.
+/// They are not operations of the worker, so they are not counted as in flight.
+pub(crate) const WAKE_USER_DATA: u64 = u64::MAX - 1;
+
+/// The number of entries of the ring, that sends wake-ups. Only failed messages complete, so it is enough.
+const WAKE_RING_ENTRIES: u32 = 4;
+
+thread_local! {
+    /// The ring of the current thread for sending wake-ups. It is created at the first wake-up,
+    /// and it is `None`, if io_uring or `IORING_OP_MSG_RING` (since Linux 5.18) is not available.
+    static WAKE_RING: RefCell<Option<Option<IoUring>>> = const { RefCell::new(None) };
+}
+
+/// Creates the ring for sending wake-ups, if the kernel supports `IORING_OP_MSG_RING`.
+fn new_wake_ring() -> Option<IoUring> {
+    let ring = IoUring::new(WAKE_RING_ENTRIES).ok()?;
+    let mut probe = Probe::new();
+    ring.submitter().register_probe(&mut probe).ok()?;
+    probe.is_supported(opcode::MsgRingData::CODE).then_some(ring)
+}
+
+/// Posts a completion with [`WAKE_USER_DATA`] to the ring of a worker with `ring_fd`, so the worker returns from its wait.
+/// Any thread can call it: it sends the message with its own small ring.
+///
+/// Returns false, if the message can't be sent (the kernel doesn't support it, or the ring is closed).
+/// Then the caller wakes the worker up with its eventfd.
+pub(crate) fn wake_ring(ring_fd: RawFd) -> bool {
+    WAKE_RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        let Some(ring) = ring.get_or_insert_with(new_wake_ring) else {
+            return false;
+        };
+        // Failures of previous messages, that the kernel has completed in the background, are not interesting anymore.
+        ring.completion().for_each(drop);
+
+        let entry = opcode::MsgRingData::new(types::Fd(ring_fd), 0, WAKE_USER_DATA, None)
+            .build()
+            .flags(squeue::Flags::SKIP_SUCCESS);
+        if unsafe { ring.submission().push(&entry) }.is_err() || ring.submit().is_err() {
+            return false;
+        }
+        // The message is usually sent, when it is submitted, so its failure is already in the completion queue.
+        ring.completion().all(|cqe| cqe.result() >= 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use io_uring::IoUring;
+    use std::os::fd::AsRawFd;
+    use super::{wake_ring, WAKE_USER_DATA};
+
+    #[test]
+    fn test_wake_ring() {
+        let mut target = IoUring::new(4).unwrap();
+        let ring_fd = target.as_raw_fd();
+        std::thread::spawn(move || assert!(wake_ring(ring_fd))).join().unwrap();
+        target.submit_and_wait(1).unwrap();
+        let cqe = target.completion().next().unwrap();
+        assert_eq!(cqe.user_data(), WAKE_USER_DATA);
+
+        // Not a ring.
+        assert!(!wake_ring(std::io::stdout().as_raw_fd()));
+    }
+}
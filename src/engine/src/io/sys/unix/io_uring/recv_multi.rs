@@ -0,0 +1,366 @@
+//! This module contains [`MultishotRecvs`], the multishot receives of the ring with their provided buffers.
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::collections::{HashMap, VecDeque};
+use std::io::Error;
+use std::mem;
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicU16, Ordering};
+use io_uring::types::BufRingEntry;
+use crate::io::PollState;
+use crate::io::sys::unix::errno::errno_error;
+use crate::utils::Ptr;
+
+/// The id of the buffer group of the [`BufRing`].
+pub(crate) const BUF_GROUP: u16 = 0;
+/// The bit of the user data of multishot receives. Pointers of states never have it.
+pub(crate) const MULTISHOT_TAG: u64 = 1 << 63;
+
+/// The ring of buffers, that are provided to the kernel (`IORING_REGISTER_PBUF_RING`).
+///
+/// The kernel takes a buffer, when data arrives, and reports its id in the completion.
+/// The buffer is given back with [`BufRing::recycle`], when the data is not needed anymore.
+pub(crate) struct BufRing {
+    /// The entries of the ring. The tail is in the first entry.
+    ring: *mut BufRingEntry,
+    entries: u16,
+    tail: u16,
+    /// The memory of all buffers. The buffer `bid` starts at `bid * buf_len`.
+    memory: Box<[u8]>,
+    buf_len: usize
+}
+
+impl BufRing {
+    /// Allocates the ring of `entries` (a power of two) buffers of `buf_len` bytes and provides all of them.
+    pub(crate) fn new(entries: u16, buf_len: usize) -> Self {
+        assert!(entries.is_power_of_two(), "the number of provided buffers must be a power of two");
+        let ring = unsafe { alloc_zeroed(Self::layout(entries)) } as *mut BufRingEntry;
+        if ring.is_null() {
+            handle_alloc_error(Self::layout(entries));
+        }
+
+        let mut buf_ring = Self {
+            ring,
+            entries,
+            tail: 0,
+            memory: vec![0; entries as usize * buf_len].into_boxed_slice(),
+            buf_len
+        };
+        buf_ring.provide_all(&[]);
+        buf_ring
+    }
+
+    /// Provides all buffers except the `taken` ones from the start of the ring.
+    /// It is used, when the ring is registered with a new io_uring, which reads it from the start.
+    fn provide_all(&mut self, taken: &[u16]) {
+        self.tail = 0;
+        for bid in 0..self.entries {
+            if !taken.contains(&bid) {
+                self.recycle(bid);
+            }
+        }
+    }
+
+    /// The ring must be page aligned.
+    fn layout(entries: u16) -> Layout {
+        let size = entries as usize * mem::size_of::<BufRingEntry>();
+        Layout::from_size_align(size.max(4096), 4096).unwrap()
+    }
+
+    /// Returns the address of the ring for `register_buf_ring`.
+    #[inline(always)]
+    pub(crate) fn addr(&self) -> u64 {
+        self.ring as u64
+    }
+
+    /// Returns the number of buffers.
+    #[inline(always)]
+    pub(crate) fn entries(&self) -> u16 {
+        self.entries
+    }
+
+    /// Provides the buffer to the kernel again.
+    pub(crate) fn recycle(&mut self, bid: u16) {
+        let entry = unsafe { &mut *self.ring.add((self.tail & (self.entries - 1)) as usize) };
+        entry.set_addr(self.memory[bid as usize * self.buf_len..].as_ptr() as u64);
+        entry.set_len(self.buf_len as u32);
+        entry.set_bid(bid);
+        self.tail = self.tail.wrapping_add(1);
+        // The kernel reads the entry after it sees the new tail.
+        let tail = unsafe { &*(BufRingEntry::tail(self.ring) as *const AtomicU16) };
+        tail.store(self.tail, Ordering::Release);
+    }
+
+    /// Returns the first `len` bytes of the buffer. They are valid, until the buffer is recycled.
+    #[inline(always)]
+    pub(crate) fn slice(&self, bid: u16, len: usize) -> &'static [u8] {
+        let start = bid as usize * self.buf_len;
+        unsafe { mem::transmute(&self.memory[start..start + len]) }
+    }
+}
+
+impl Drop for BufRing {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ring as *mut u8, Self::layout(self.entries)) };
+    }
+}
+
+/// A completion of a multishot receive, that no coroutine has taken yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Received {
+    /// The id and the length of the buffer with the data.
+    Data(u16, usize),
+    /// The peer has closed the connection.
+    Eof,
+    /// The receive failed with the errno.
+    Error(i32)
+}
+
+/// The multishot receive of a connection.
+pub(crate) struct MultishotRecv {
+    /// True, while the receive is in the kernel.
+    pub(crate) is_armed: bool,
+    /// True, if the connection is closed, and the receive only waits for its last completion.
+    pub(crate) is_closed: bool,
+    /// The state of the coroutine, that waits for data.
+    pub(crate) waiting: Option<Ptr<PollState>>,
+    /// The completions, that no coroutine has taken yet.
+    pub(crate) ready: VecDeque<Received>,
+    /// The buffer, which data the coroutine has got at the last read. It is recycled at the next read.
+    pub(crate) held: Option<u16>
+}
+
+/// The multishot receives of the connections and their provided buffers.
+///
+/// A read of a connection arms a multishot receive (`IORING_RECV_MULTISHOT`), that stays in the kernel
+/// and completes every time data arrives, without a poll and a receive per message. Completions, that arrive,
+/// while no coroutine reads, wait in [`MultishotRecv::ready`].
+///
+/// Read [`set_multishot_recv_buffers`](crate::cfg::set_multishot_recv_buffers) for more information.
+pub(crate) struct MultishotRecvs {
+    pub(crate) buf_ring: BufRing,
+    recvs: Vec<Option<MultishotRecv>>,
+    free: Vec<u32>,
+    by_fd: HashMap<RawFd, u32>
+}
+
+impl MultishotRecvs {
+    pub(crate) fn new(buf_ring: BufRing) -> Self {
+        Self {
+            buf_ring,
+            recvs: Vec::new(),
+            free: Vec::new(),
+            by_fd: HashMap::new()
+        }
+    }
+
+    /// Returns the id of the receive of the fd, and creates it, if it doesn't exist.
+    pub(crate) fn get_or_insert(&mut self, fd: RawFd) -> u32 {
+        if let Some(&id) = self.by_fd.get(&fd) {
+            return id;
+        }
+
+        let recv = MultishotRecv { is_armed: false, is_closed: false, waiting: None, ready: VecDeque::new(), held: None };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.recvs[id as usize] = Some(recv);
+                id
+            }
+            None => {
+                self.recvs.push(Some(recv));
+                (self.recvs.len() - 1) as u32
+            }
+        };
+        self.by_fd.insert(fd, id);
+        id
+    }
+
+    /// Returns the receive by its id.
+    #[inline(always)]
+    pub(crate) fn get_mut(&mut self, id: u32) -> &mut MultishotRecv {
+        self.recvs[id as usize].as_mut().expect("[BUG] a completion of a removed multishot receive")
+    }
+
+    /// Takes the state of the coroutine, that waits for data, if a completion is ready for it.
+    pub(crate) fn take_waiting(&mut self, id: u32) -> Option<Ptr<PollState>> {
+        let recv = self.get_mut(id);
+        if recv.ready.is_empty() {
+            return None;
+        }
+
+        recv.waiting.take()
+    }
+
+    /// Forgets the state of the coroutine, that waits for data of the fd. Returns false, if the state doesn't wait here.
+    pub(crate) fn forget_waiting(&mut self, fd: RawFd, state_ptr: Ptr<PollState>) -> bool {
+        let Some(&id) = self.by_fd.get(&fd) else {
+            return false;
+        };
+        let recv = self.get_mut(id);
+        if recv.waiting.map(|waiting| waiting.as_u64()) != Some(state_ptr.as_u64()) {
+            return false;
+        }
+
+        recv.waiting = None;
+        true
+    }
+
+    /// Returns the result of a read from the first ready completion.
+    ///
+    /// If `coalesce` is greater than the length of the data, the data of the following completions is appended,
+    /// while it fits, and the read returns a slice of `coalesce_buf`. Otherwise, it returns the slice of the buffer,
+    /// and the buffer is held until the next read.
+    pub(crate) fn read(&mut self, id: u32, coalesce: usize, coalesce_buf: &mut Vec<u8>) -> Result<&'static [u8], Error> {
+        let recv = self.recvs[id as usize].as_mut().expect("[BUG] a read of a removed multishot receive");
+        match recv.ready.pop_front().expect("[BUG] a read of a multishot receive without completions") {
+            Received::Data(bid, len) if coalesce > len && matches!(recv.ready.front(), Some(Received::Data(..))) => {
+                coalesce_buf.clear();
+                coalesce_buf.extend_from_slice(self.buf_ring.slice(bid, len));
+                self.buf_ring.recycle(bid);
+                while let Some(&Received::Data(bid, len)) = recv.ready.front() {
+                    if coalesce_buf.len() + len > coalesce {
+                        break;
+                    }
+                    recv.ready.pop_front();
+                    coalesce_buf.extend_from_slice(self.buf_ring.slice(bid, len));
+                    self.buf_ring.recycle(bid);
+                }
+                Ok(unsafe { mem::transmute::<&[u8], &'static [u8]>(coalesce_buf.as_slice()) })
+            }
+            Received::Data(bid, len) => {
+                recv.held = Some(bid);
+                Ok(self.buf_ring.slice(bid, len))
+            }
+            Received::Eof => Ok(&[]),
+            Received::Error(errno) => Err(errno_error(errno))
+        }
+    }
+
+    /// Provides all free buffers again for a new io_uring. No receives must be armed.
+    pub(crate) fn reprovide(&mut self) {
+        let mut taken = Vec::new();
+        for recv in self.recvs.iter().flatten() {
+            debug_assert!(!recv.is_armed);
+            taken.extend(recv.held);
+            taken.extend(recv.ready.iter().filter_map(|received| match received {
+                Received::Data(bid, _) => Some(*bid),
+                _ => None
+            }));
+        }
+        self.buf_ring.provide_all(&taken);
+    }
+
+    /// Recycles the buffer, that the coroutine has got at the last read.
+    pub(crate) fn release_held(&mut self, id: u32) {
+        if let Some(bid) = self.get_mut(id).held.take() {
+            self.buf_ring.recycle(bid);
+        }
+    }
+
+    /// Marks the receive of the fd as closed and recycles its buffers. Returns the id of the receive,
+    /// if it is still armed, so it must be cancelled. Otherwise, the receive is removed.
+    pub(crate) fn close(&mut self, fd: RawFd) -> Option<u32> {
+        let id = self.by_fd.remove(&fd)?;
+        self.release_held(id);
+        let recv = self.get_mut(id);
+        recv.is_closed = true;
+        let ready: Vec<Received> = recv.ready.drain(..).collect();
+        let is_armed = recv.is_armed;
+        for received in ready {
+            if let Received::Data(bid, _) = received {
+                self.buf_ring.recycle(bid);
+            }
+        }
+
+        if is_armed {
+            Some(id)
+        } else {
+            self.remove(id);
+            None
+        }
+    }
+
+    /// Removes the closed receive after its last completion.
+    pub(crate) fn remove(&mut self, id: u32) {
+        self.recvs[id as usize] = None;
+        self.free.push(id);
+    }
+
+    /// Returns the number of receives of open connections.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.by_fd.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buf_ring() {
+        let mut buf_ring = BufRing::new(4, 16);
+        assert_eq!(buf_ring.addr() % 4096, 0);
+        assert_eq!(buf_ring.entries(), 4);
+        let tail = unsafe { *BufRingEntry::tail(buf_ring.ring) };
+        assert_eq!(tail, 4);
+        let entry = unsafe { &*buf_ring.ring.add(2) };
+        assert_eq!(entry.bid(), 2);
+        assert_eq!(entry.len(), 16);
+        assert_eq!(entry.addr(), buf_ring.slice(2, 0).as_ptr() as u64);
+
+        buf_ring.recycle(2);
+        let entry = unsafe { &*buf_ring.ring };
+        assert_eq!(entry.bid(), 2);
+        assert_eq!(unsafe { *BufRingEntry::tail(buf_ring.ring) }, 5);
+    }
+
+    #[test]
+    fn test_multishot_recvs() {
+        let mut recvs = MultishotRecvs::new(BufRing::new(2, 16));
+        let id = recvs.get_or_insert(10);
+        assert_eq!(recvs.get_or_insert(10), id);
+        recvs.get_mut(id).is_armed = true;
+        recvs.get_mut(id).ready.push_back(Received::Data(1, 5));
+        assert_eq!(recvs.close(10), Some(id));
+        assert!(recvs.get_mut(id).ready.is_empty());
+        assert_eq!(recvs.len(), 0);
+        recvs.remove(id);
+
+        // The id is reused, and a not armed receive is removed at once.
+        let other = recvs.get_or_insert(11);
+        assert_eq!(other, id);
+        assert_eq!(recvs.close(11), None);
+    }
+
+    #[test]
+    fn test_read() {
+        let mut recvs = MultishotRecvs::new(BufRing::new(4, 4));
+        let id = recvs.get_or_insert(10);
+        let state_ptr = Ptr::new(PollState::new_empty(10));
+        recvs.get_mut(id).waiting = Some(state_ptr);
+        assert!(recvs.take_waiting(id).is_none());
+
+        for (bid, data) in [(0u16, b"ab"), (1, b"cd"), (2, b"ef")] {
+            recvs.buf_ring.memory[bid as usize * 4..bid as usize * 4 + 2].copy_from_slice(data);
+            recvs.get_mut(id).ready.push_back(Received::Data(bid, 2));
+        }
+        recvs.get_mut(id).ready.push_back(Received::Eof);
+        assert_eq!(recvs.take_waiting(id).map(|ptr| ptr.as_u64()), Some(state_ptr.as_u64()));
+
+        let mut coalesce_buf = Vec::new();
+        assert_eq!(recvs.read(id, 4, &mut coalesce_buf).unwrap(), b"abcd");
+        assert_eq!(recvs.read(id, 0, &mut coalesce_buf).unwrap(), b"ef");
+        assert_eq!(recvs.get_mut(id).held, Some(2));
+        assert_eq!(recvs.read(id, 0, &mut coalesce_buf).unwrap(), b"");
+
+        recvs.get_mut(id).waiting = Some(state_ptr);
+        assert!(!recvs.forget_waiting(11, state_ptr));
+        assert!(recvs.forget_waiting(10, state_ptr));
+        assert!(recvs.get_mut(id).waiting.is_none());
+
+        // The held buffer is not provided again.
+        recvs.reprovide();
+        assert_eq!(unsafe { *BufRingEntry::tail(recvs.buf_ring.ring) }, 3);
+        unsafe { state_ptr.drop_in_place() };
+    }
+}
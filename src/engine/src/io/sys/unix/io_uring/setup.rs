@@ -0,0 +1,157 @@
+//! This module contains [`RingSetup`], the parameters of the ring of [`IoUringSelector`](super::IoUringSelector).
+use std::io::Error;
+use io_uring::IoUring;
+use crate::io::sys::unix::io_uring::KernelVersion;
+
+/// The default number of entries in the submission queue of the ring.
+pub(crate) const RING_ENTRIES: u32 = 1024;
+
+/// The parameters, that the ring of every worker is created with.
+///
+/// The flags trade CPU for fewer syscalls:
+///
+/// - `sqpoll` starts a kernel thread, that polls the submission queue, so submitting needs no syscall,
+///   while the thread is awake. The thread sleeps after `sqpoll` milliseconds without submissions and takes a whole core,
+///   while it is awake;
+///
+/// - `single_issuer` tells the kernel, that only the thread of the worker submits, so it skips some synchronization;
+///
+/// - `coop_taskrun` doesn't interrupt the worker to run completion work, the work is run at the next poll;
+///
+/// - `defer_taskrun` runs the completion work only when the worker polls, so it isn't run on the worker,
+///   while a coroutine is running. It requires `single_issuer`.
+///
+/// Flags are probed, when the ring is created: a flag, that the running kernel doesn't support,
+/// is dropped instead of failing the start. `coop_taskrun` and `defer_taskrun` are dropped with `sqpoll`,
+/// because the kernel thread completes the work itself. Read
+/// [`UringCapabilities`](crate::io::UringCapabilities) for the flags, that are applied.
+///
+/// Set it with [`set_ring_setup`](crate::cfg::set_ring_setup) before the start.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::cfg::set_ring_setup;
+/// use engine::io::RingSetup;
+///
+/// // Syscall-free submission for a busy server, that owns enough cores.
+/// set_ring_setup(RingSetup { entries: 4096, sqpoll: Some(10), ..RingSetup::default() });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RingSetup {
+    /// The number of entries in the submission queue. It is rounded up to a power of two by the kernel.
+    pub entries: u32,
+    /// The idle time of the submission polling thread in milliseconds (`IORING_SETUP_SQPOLL`, since Linux 5.11 without privileges).
+    pub sqpoll: Option<u32>,
+    /// `IORING_SETUP_SINGLE_ISSUER`, since Linux 6.0.
+    pub single_issuer: bool,
+    /// `IORING_SETUP_COOP_TASKRUN`, since Linux 5.19.
+    pub coop_taskrun: bool,
+    /// `IORING_SETUP_DEFER_TASKRUN`, since Linux 6.1.
+    pub defer_taskrun: bool
+}
+
+impl RingSetup {
+    /// Returns the setup with [`RING_ENTRIES`] entries and without flags.
+    pub const fn default() -> Self {
+        Self {
+            entries: RING_ENTRIES,
+            sqpoll: None,
+            single_issuer: false,
+            coop_taskrun: false,
+            defer_taskrun: false
+        }
+    }
+
+    /// Returns the setup without the flags, that `kernel_version` doesn't support or that conflict with others.
+    fn supported(mut self, kernel_version: Option<KernelVersion>) -> Self {
+        let since = |major, minor| kernel_version.is_none_or(|version| version >= KernelVersion { major, minor, patch: 0 });
+        self.sqpoll = self.sqpoll.filter(|_| since(5, 11));
+        self.single_issuer &= since(6, 0);
+        self.coop_taskrun &= since(5, 19) && self.sqpoll.is_none();
+        self.defer_taskrun &= since(6, 1) && self.sqpoll.is_none() && self.single_issuer;
+        self
+    }
+
+    /// Drops the newest flag. Returns false, if no flags are left.
+    fn drop_newest_flag(&mut self) -> bool {
+        if self.defer_taskrun {
+            self.defer_taskrun = false;
+        } else if self.single_issuer {
+            self.single_issuer = false;
+        } else if self.coop_taskrun {
+            self.coop_taskrun = false;
+        } else if self.sqpoll.is_some() {
+            self.sqpoll = None;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn build(&self, entries: u32) -> Result<IoUring, Error> {
+        let mut builder = IoUring::builder();
+        if let Some(idle) = self.sqpoll {
+            builder.setup_sqpoll(idle);
+        }
+        if self.single_issuer {
+            builder.setup_single_issuer();
+        }
+        if self.coop_taskrun {
+            builder.setup_coop_taskrun();
+        }
+        if self.defer_taskrun {
+            builder.setup_defer_taskrun();
+        }
+        builder.build(entries)
+    }
+}
+
+/// Creates the ring with `entries` and the flags of the `setup`, that the kernel accepts.
+/// Returns the ring and the setup, that is applied.
+///
+/// Flags are dropped one by one from the newest, while the kernel rejects them with `EINVAL` or `EPERM`.
+/// `sqpoll` is dropped, if the kernel requires registered files for it (before Linux 5.11).
+pub(crate) fn build_ring(setup: RingSetup, entries: u32) -> Result<(IoUring, RingSetup), Error> {
+    let mut applied = setup.supported(KernelVersion::current().ok());
+    loop {
+        match applied.build(entries) {
+            Ok(ring) if applied.sqpoll.is_some() && !ring.params().is_feature_sqpoll_nonfixed() => {
+                applied.sqpoll = None;
+            }
+            Ok(ring) => return Ok((ring, applied)),
+            Err(err) if matches!(err.raw_os_error(), Some(libc::EINVAL | libc::EPERM)) && applied.drop_newest_flag() => (),
+            Err(err) => return Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported() {
+        let setup = RingSetup { sqpoll: Some(10), single_issuer: true, coop_taskrun: true, defer_taskrun: true, ..RingSetup::default() };
+        let old = setup.supported(Some(KernelVersion { major: 5, minor: 15, patch: 0 }));
+        assert_eq!(old, RingSetup { sqpoll: Some(10), ..RingSetup::default() });
+
+        let setup = RingSetup { defer_taskrun: true, coop_taskrun: true, ..RingSetup::default() };
+        let new = setup.supported(Some(KernelVersion { major: 6, minor: 1, patch: 0 }));
+        assert_eq!(new, RingSetup { coop_taskrun: true, ..RingSetup::default() });
+    }
+
+    #[test]
+    fn test_build_ring() {
+        let setup = RingSetup { entries: 64, single_issuer: true, coop_taskrun: true, defer_taskrun: true, ..RingSetup::default() };
+        let (ring, applied) = build_ring(setup, setup.entries).unwrap();
+        assert_eq!(ring.params().sq_entries(), 64);
+        if KernelVersion::current().unwrap() >= (KernelVersion { major: 6, minor: 1, patch: 0 }) {
+            assert_eq!(applied, setup);
+            assert!(ring.params().is_setup_single_issuer());
+        }
+
+        let (_, applied) = build_ring(RingSetup::default(), RING_ENTRIES).unwrap();
+        assert_eq!(applied, RingSetup::default());
+    }
+}
@@ -0,0 +1,94 @@
+//! This module contains [`RingSleeps`].
+use std::time::Duration;
+use io_uring::types::Timespec;
+use crate::coroutine::CoroutineImpl;
+
+/// The bit of the user data of [`Timeout`](io_uring::opcode::Timeout) entries of sleeping coroutines.
+/// The rest of the user data is the id of the sleep in [`RingSleeps`].
+pub(crate) const SLEEP_TAG: u64 = 1 << 60;
+
+/// A sleeping coroutine with its timeout. The timeout is boxed, so it stays in place, until the entry is submitted.
+struct RingSleep {
+    timespec: Timespec,
+    coroutine: CoroutineImpl
+}
+
+/// Coroutines, that sleep with [`Timeout`](io_uring::opcode::Timeout) entries instead of the timing wheel of the scheduler.
+/// Read [`set_ring_timeouts`](crate::cfg::set_ring_timeouts).
+///
+/// They are dropped with the selector, if the worker stops before they are woken up.
+pub(crate) struct RingSleeps {
+    sleeps: Vec<Option<Box<RingSleep>>>,
+    free: Vec<u32>
+}
+
+impl RingSleeps {
+    pub(crate) fn new() -> Self {
+        Self {
+            sleeps: Vec::new(),
+            free: Vec::new()
+        }
+    }
+
+    /// Stores the coroutine, that sleeps for `dur`. Returns the id of the sleep and the pointer to its timeout,
+    /// that is valid until the sleep is removed.
+    pub(crate) fn insert(&mut self, dur: Duration, coroutine: CoroutineImpl) -> (u32, *const Timespec) {
+        let sleep = Box::new(RingSleep { timespec: Timespec::from(dur), coroutine });
+        let timespec = &sleep.timespec as *const Timespec;
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.sleeps[id as usize] = Some(sleep);
+                id
+            }
+            None => {
+                self.sleeps.push(Some(sleep));
+                (self.sleeps.len() - 1) as u32
+            }
+        };
+
+        (id, timespec)
+    }
+
+    /// Removes the sleep, which timeout has expired, and returns its coroutine.
+    pub(crate) fn remove(&mut self, id: u32) -> CoroutineImpl {
+        let sleep = self.sleeps[id as usize].take()
+            .expect("[BUG] a completion of a removed sleep in [`IoUringSelector`]. Please report this issue.");
+        self.free.push(id);
+        sleep.coroutine
+    }
+
+    /// Returns the number of sleeping coroutines.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.sleeps.len() - self.free.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::coroutine::CoroutineImpl;
+    use super::RingSleeps;
+
+    fn empty_coroutine() -> CoroutineImpl {
+        Box::pin(#[coroutine] static || {})
+    }
+
+    #[test]
+    fn test_ring_sleeps() {
+        let mut sleeps = RingSleeps::new();
+        let (first, first_timespec) = sleeps.insert(Duration::from_millis(1), empty_coroutine());
+        let (second, second_timespec) = sleeps.insert(Duration::from_millis(2), empty_coroutine());
+        assert_ne!(first, second);
+        assert_ne!(first_timespec, second_timespec);
+        assert_eq!(sleeps.len(), 2);
+
+        drop(sleeps.remove(first));
+        assert_eq!(sleeps.len(), 1);
+        // The id is reused, but the timeout of the other sleep stays in place.
+        let (third, _) = sleeps.insert(Duration::from_millis(3), empty_coroutine());
+        assert_eq!(third, first);
+        assert_ne!(sleeps.insert(Duration::ZERO, empty_coroutine()).1, second_timespec);
+        assert_eq!(sleeps.len(), 3);
+    }
+}
@@ -1,5 +1,13 @@
 pub(crate) mod epoll;
+#[cfg(target_os = "linux")]
 pub(crate) mod io_uring;
+pub(crate) mod fs;
+#[cfg(target_os = "linux")]
+pub(crate) mod coalesce;
+#[cfg(target_os = "linux")]
+pub(crate) mod errno;
 
+#[cfg(target_os = "linux")]
 pub(crate) use epoll::*;
+#[cfg(target_os = "linux")]
 pub(crate) use io_uring::*;
\ No newline at end of file
@@ -0,0 +1,475 @@
+//! This module contains [`Tty`] and [`WindowSize`].
+use std::ffi::CString;
+use std::fmt::{Debug, Formatter};
+use std::io::{Error, ErrorKind};
+use std::mem::MaybeUninit;
+use std::os::fd::RawFd;
+use std::sync::Once;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::buf::{buffer, Buffer};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local_scheduler;
+use crate::sleep::sleep;
+use crate::utils::Ptr;
+
+/// How often [`Tty::wait_resize`] checks, whether `SIGWINCH` was received.
+const RESIZE_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The number of `SIGWINCH` signals, that the process has received since the handler was installed.
+static RESIZES: AtomicU64 = AtomicU64::new(0);
+/// The handler of `SIGWINCH`, that was installed before ours. It is called by our handler.
+static PREVIOUS_HANDLER: AtomicUsize = AtomicUsize::new(libc::SIG_DFL);
+static INSTALL_HANDLER: Once = Once::new();
+
+extern "C" fn on_sigwinch(signal: libc::c_int) {
+    RESIZES.fetch_add(1, Ordering::Relaxed);
+    let previous = PREVIOUS_HANDLER.load(Ordering::Relaxed);
+    if previous != libc::SIG_DFL && previous != libc::SIG_IGN {
+        let previous: extern "C" fn(libc::c_int) = unsafe { std::mem::transmute(previous) };
+        previous(signal);
+    }
+}
+
+/// Installs the `SIGWINCH` handler once per process.
+fn install_sigwinch_handler() {
+    INSTALL_HANDLER.call_once(|| unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigwinch as extern "C" fn(libc::c_int) as usize;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(libc::SIGWINCH, &action, &mut previous) == 0 {
+            PREVIOUS_HANDLER.store(previous.sa_sigaction, Ordering::Relaxed);
+        }
+    });
+}
+
+/// The size of the terminal window in characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16
+}
+
+/// A terminal, that is read and written without blocking the worker.
+///
+/// It supports:
+///
+/// - the raw mode ([`Tty::enable_raw_mode`]), in which every key press is read at once and is not echoed;
+///
+/// - the window size ([`Tty::size`]) and waiting for its changes ([`Tty::wait_resize`]);
+///
+/// - line-buffered reads ([`Tty::read_line`]), that work in both the raw and the canonical modes.
+///
+/// # Raw mode
+///
+/// The original mode is restored, when the raw mode is disabled or the [`Tty`] is dropped.
+///
+/// # Window size changes
+///
+/// The first [`Tty`] installs a process-wide `SIGWINCH` handler, that calls the previously installed handler.
+/// [`Tty::wait_resize`] checks every 50 milliseconds, whether the signal was received,
+/// because signals are delivered to any thread of the process.
+///
+/// # Coroutines
+///
+/// [`Tty::read`], [`Tty::read_line`], [`Tty::write_all`] and [`Tty::wait_resize`] return coroutines,
+/// so use them with [`wait!`](crate::wait). The [`Tty`] must not be moved, while the coroutine is running.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::io::Tty;
+///
+/// #[coro]
+/// fn prompt() {
+///     let mut tty = Tty::open().unwrap();
+///     let res: Result<(), Error> = wait!(tty.write_all(b"name: "));
+///     res.unwrap();
+///     let name: Result<Option<String>, Error> = wait!(tty.read_line());
+///     println!("hello, {}!", name.unwrap().unwrap_or_default());
+/// }
+/// ```
+pub struct Tty {
+    fd: RawFd,
+    state: Ptr<PollState>,
+    is_registered: bool,
+    /// The mode before [`Tty::enable_raw_mode`]. It is `Some` only in the raw mode.
+    original_mode: Option<libc::termios>,
+    /// Read, but not consumed by [`Tty::read_line`], bytes.
+    pending: Vec<u8>,
+    /// True, if the last line ended with `\r`, so the next `\n` belongs to it.
+    skip_lf: bool,
+    /// The value of [`RESIZES`], that was seen by [`Tty::wait_resize`].
+    seen_resizes: u64
+}
+
+impl Tty {
+    /// Opens the controlling terminal of the process (`/dev/tty`).
+    pub fn open() -> Result<Self, Error> {
+        let path = CString::new("/dev/tty").unwrap();
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self::new(fd))
+    }
+
+    /// Creates a [`Tty`] from the terminal fd. The [`Tty`] owns the fd and closes it, when it is dropped.
+    ///
+    /// The fd is switched to the non-blocking mode. Remember, that duplicated fds (like a `dup` of the standard input)
+    /// share this mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidInput`], if the fd is not a terminal.
+    pub fn from_fd(fd: RawFd) -> Result<Self, Error> {
+        if unsafe { libc::isatty(fd) } == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "the fd is not a terminal"));
+        }
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self::new(fd))
+    }
+
+    fn new(fd: RawFd) -> Self {
+        install_sigwinch_handler();
+        Self {
+            fd,
+            state: Ptr::new(PollState::new_empty(fd)),
+            is_registered: false,
+            original_mode: None,
+            pending: Vec::new(),
+            skip_lf: false,
+            seen_resizes: RESIZES.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Returns the raw file descriptor.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns true, if the raw mode is enabled.
+    #[inline(always)]
+    pub fn is_raw_mode(&self) -> bool {
+        self.original_mode.is_some()
+    }
+
+    /// Enables the raw mode: input is not echoed and not buffered by lines, and signal keys (like `Ctrl+C`)
+    /// are read as bytes. Does nothing, if the raw mode is already enabled.
+    pub fn enable_raw_mode(&mut self) -> Result<(), Error> {
+        if self.original_mode.is_some() {
+            return Ok(());
+        }
+
+        let mut mode = MaybeUninit::<libc::termios>::uninit();
+        if unsafe { libc::tcgetattr(self.fd, mode.as_mut_ptr()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        let original = unsafe { mode.assume_init() };
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &raw) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        self.original_mode = Some(original);
+        Ok(())
+    }
+
+    /// Restores the mode, that was before [`Tty::enable_raw_mode`]. Does nothing, if the raw mode is not enabled.
+    pub fn disable_raw_mode(&mut self) -> Result<(), Error> {
+        if let Some(original) = self.original_mode {
+            if unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &original) } < 0 {
+                return Err(Error::last_os_error());
+            }
+            self.original_mode = None;
+        }
+        Ok(())
+    }
+
+    /// Returns the current size of the terminal window.
+    pub fn size(&self) -> Result<WindowSize, Error> {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut size) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(WindowSize { rows: size.ws_row, cols: size.ws_col })
+    }
+
+    /// Reads available bytes. An empty [`Buffer`] means the end of the input (the terminal was hung up).
+    ///
+    /// Bytes, that are buffered by [`Tty::read_line`], are returned first.
+    pub fn read(&mut self, res: *mut Result<Buffer, Error>) -> CoroutineImpl {
+        read_tty(self, res)
+    }
+
+    /// Reads a line without the line ending. `\n`, `\r` (sent by Enter in the raw mode) and `\r\n` end the line.
+    ///
+    /// Returns `None` at the end of the input. The not terminated last line is returned before it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::InvalidData`] if the line is not valid UTF-8.
+    pub fn read_line(&mut self, res: *mut Result<Option<String>, Error>) -> CoroutineImpl {
+        read_tty_line(self, res)
+    }
+
+    /// Writes all bytes to the terminal.
+    pub fn write_all(&mut self, data: &[u8], res: *mut Result<(), Error>) -> CoroutineImpl {
+        write_all_tty(self.fd, data, res)
+    }
+
+    /// Waits until the window size is changed and returns the new size.
+    ///
+    /// If the size was changed after the previous call (or after the creation of the [`Tty`]), it returns at once.
+    pub fn wait_resize(&mut self, res: *mut Result<WindowSize, Error>) -> CoroutineImpl {
+        wait_tty_resize(self, res)
+    }
+
+    /// Reads available bytes into `buf` without waiting. Returns false, if there are no bytes to read.
+    fn read_available(&mut self, buf: &mut Buffer) -> Result<bool, Error> {
+        let n = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.cap()) };
+        if n < 0 {
+            let err = Error::last_os_error();
+            if err.kind() == ErrorKind::WouldBlock {
+                return Ok(false);
+            }
+            // A pty returns EIO, when the other side is closed.
+            if err.raw_os_error() == Some(libc::EIO) {
+                buf.set_written(0);
+                return Ok(true);
+            }
+            return Err(err);
+        }
+
+        buf.set_written(n as usize);
+        Ok(true)
+    }
+
+    /// Takes a line from the pending bytes. Returns `None`, if there is no full line.
+    fn take_line(&mut self) -> Option<Vec<u8>> {
+        if self.skip_lf && !self.pending.is_empty() {
+            if self.pending[0] == b'\n' {
+                self.pending.remove(0);
+            }
+            self.skip_lf = false;
+        }
+
+        let pos = self.pending.iter().position(|&b| b == b'\n' || b == b'\r')?;
+        self.skip_lf = self.pending[pos] == b'\r';
+        let mut line: Vec<u8> = self.pending.drain(..=pos).collect();
+        line.pop();
+        Some(line)
+    }
+}
+
+#[coro(crate="crate")]
+fn read_tty(tty: *mut Tty) -> Result<Buffer, Error> {
+    let tty = unsafe { &mut *tty };
+    let mut buf = buffer();
+    if !tty.pending.is_empty() {
+        buf.append(&tty.pending);
+        tty.pending.clear();
+        return Ok(buf);
+    }
+
+    loop {
+        let has_read = match tty.read_available(&mut buf) {
+            Ok(has_read) => has_read,
+            Err(err) => return Err(err)
+        };
+        if has_read {
+            return Ok(buf);
+        }
+
+        let res: Result<(), Error> = yield YieldStatus::wait_readable(tty.is_registered, tty.state);
+        tty.is_registered = true;
+        if let Err(err) = res {
+            return Err(err);
+        }
+    };
+}
+
+#[coro(crate="crate")]
+fn read_tty_line(tty: *mut Tty) -> Result<Option<String>, Error> {
+    let tty = unsafe { &mut *tty };
+    loop {
+        if let Some(line) = tty.take_line() {
+            return String::from_utf8(line).map(Some).map_err(|err| Error::new(ErrorKind::InvalidData, err));
+        }
+
+        let mut buf = buffer();
+        let has_read = match tty.read_available(&mut buf) {
+            Ok(has_read) => has_read,
+            Err(err) => return Err(err)
+        };
+        if !has_read {
+            let res: Result<(), Error> = yield YieldStatus::wait_readable(tty.is_registered, tty.state);
+            tty.is_registered = true;
+            if let Err(err) = res {
+                return Err(err);
+            }
+            continue;
+        }
+
+        if buf.len() == 0 {
+            if tty.pending.is_empty() {
+                return Ok(None);
+            }
+            let line = std::mem::take(&mut tty.pending);
+            return String::from_utf8(line).map(Some).map_err(|err| Error::new(ErrorKind::InvalidData, err));
+        }
+        tty.pending.extend_from_slice(buf.as_ref());
+    };
+}
+
+#[coro(crate="crate")]
+fn write_all_tty(fd: RawFd, data: *const [u8]) -> Result<(), Error> {
+    let data = unsafe { &*data };
+    let mut written = 0;
+    while written < data.len() {
+        let n = unsafe { libc::write(fd, data[written..].as_ptr().cast(), data.len() - written) };
+        if n < 0 {
+            let err = Error::last_os_error();
+            if err.kind() != ErrorKind::WouldBlock {
+                return Err(err);
+            }
+            // Terminals are rarely full, so it is not worth registering the fd for writability.
+            yield sleep(Duration::from_millis(1));
+            continue;
+        }
+        written += n as usize;
+    }
+
+    Ok(())
+}
+
+#[coro(crate="crate")]
+fn wait_tty_resize(tty: *mut Tty) -> Result<WindowSize, Error> {
+    let tty = unsafe { &mut *tty };
+    loop {
+        let resizes = RESIZES.load(Ordering::Relaxed);
+        if resizes != tty.seen_resizes {
+            tty.seen_resizes = resizes;
+            return tty.size();
+        }
+
+        yield sleep(RESIZE_CHECK_INTERVAL);
+    };
+}
+
+impl Debug for Tty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tty")
+            .field("fd", &self.fd)
+            .field("is_raw_mode", &self.is_raw_mode())
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+fn close_tty(fd: RawFd, state_ref: Ptr<PollState>) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        yield YieldStatus::file_close(fd, state_ref);
+        unsafe { state_ref.drop_in_place(); }
+    })
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        let _ = self.disable_raw_mode();
+        local_scheduler().sched(close_tty(self.fd, self.state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read, Write};
+    use std::os::fd::FromRawFd;
+    use crate::{test_local, wait};
+    use crate::buf::Buffer;
+    use crate::io::{Tty, WindowSize};
+
+    /// Opens a pseudo terminal and returns its master side and the fd of its slave side.
+    fn pty() -> (std::fs::File, libc::c_int) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 64];
+            #[cfg(target_os = "linux")]
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            // There is no `ptsname_r` on macOS. The name is copied at once, so other calls don't overwrite it.
+            #[cfg(not(target_os = "linux"))]
+            libc::strncpy(name.as_mut_ptr(), libc::ptsname(master), name.len() - 1);
+            let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            assert!(slave >= 0);
+            (std::fs::File::from_raw_fd(master), slave)
+        }
+    }
+
+    #[test_local(crate="crate")]
+    fn test_tty_raw_mode_and_lines() {
+        let (mut master, slave) = pty();
+        let mut tty = Tty::from_fd(slave).unwrap();
+        tty.enable_raw_mode().unwrap();
+        assert!(tty.is_raw_mode());
+
+        master.write_all(b"first\r\nsecond\rthird\n").unwrap();
+        let first: Result<Option<String>, Error> = wait!(tty.read_line());
+        assert_eq!(first.unwrap().as_deref(), Some("first"));
+        let second: Result<Option<String>, Error> = wait!(tty.read_line());
+        assert_eq!(second.unwrap().as_deref(), Some("second"));
+        let third: Result<Option<String>, Error> = wait!(tty.read_line());
+        assert_eq!(third.unwrap().as_deref(), Some("third"));
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            master.write_all(b"q").unwrap();
+            master
+        });
+        let key: Result<Buffer, Error> = wait!(tty.read());
+        assert_eq!(key.unwrap().as_ref(), b"q");
+        let mut master = writer.join().unwrap();
+
+        let res: Result<(), Error> = wait!(tty.write_all(b"out"));
+        res.unwrap();
+        let mut out = [0u8; 3];
+        master.read_exact(&mut out).unwrap();
+        assert_eq!(&out, b"out");
+
+        tty.disable_raw_mode().unwrap();
+        assert!(!tty.is_raw_mode());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_tty_resize() {
+        let (master, slave) = pty();
+        let mut tty = Tty::from_fd(slave).unwrap();
+        let size = libc::winsize { ws_row: 24, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+        assert_eq!(unsafe { libc::ioctl(std::os::fd::AsRawFd::as_raw_fd(&master), libc::TIOCSWINSZ, &size) }, 0);
+        assert_eq!(tty.size().unwrap(), WindowSize { rows: 24, cols: 80 });
+
+        unsafe { libc::raise(libc::SIGWINCH) };
+        let resized: Result<WindowSize, Error> = wait!(tty.wait_resize());
+        assert_eq!(resized.unwrap(), WindowSize { rows: 24, cols: 80 });
+    }
+
+    #[test]
+    fn test_tty_from_not_terminal() {
+        let file = std::fs::File::open("/proc/self/stat").unwrap();
+        let err = Tty::from_fd(std::os::fd::AsRawFd::as_raw_fd(&file)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
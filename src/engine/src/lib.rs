@@ -10,6 +10,7 @@ pub mod coroutine;
 pub mod macros;
 pub mod io;
 pub mod net;
+pub mod fs;
 pub mod local;
 pub mod sleep;
 pub mod sync;
@@ -18,10 +19,21 @@ pub mod cfg;
 pub mod run;
 pub mod buf;
 pub mod scheduler;
-//pub mod work_stealing;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+pub mod blocking;
+pub mod stream;
+pub mod build_info;
+pub mod self_check;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+#[cfg(feature = "admin")]
+pub mod admin;
 
 pub use scheduler::local_scheduler;
 #[allow(unused_imports)]
 pub use macros::*;
 pub use run::*;
-pub use proc::{test_local, coro, wait, spawn_local};
+pub use build_info::{build_info, BuildInfo};
+pub use self_check::{self_check, SelfCheckError};
+pub use proc::{test_local, coro, wait, spawn_local, spawn_local_with_handle, spawn_global, spawn_idle, spawn_local_with_priority, try_spawn_local, wait_within, wait_timeout, select, scope, join, wait_all};
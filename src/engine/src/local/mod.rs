@@ -1,9 +1,11 @@
 pub mod id;
 pub mod local;
+pub mod task_local;
 
 pub use id::{
     get_core_id,
     get_worker_id
 };
 
-pub use local::*;
\ No newline at end of file
+pub use local::*;
+pub use task_local::{AccessError, TaskLocalKey};
\ No newline at end of file
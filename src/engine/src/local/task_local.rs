@@ -0,0 +1,228 @@
+//! This module contains [`task_local!`](crate::task_local) and [`TaskLocalKey`], the storage of a coroutine.
+//!
+//! [`Local`](crate::local::Local) is shared by all coroutines of a worker.
+//! A task-local value is visible only to the coroutine, that is run in its [`scope`](TaskLocalKey::scope),
+//! and to coroutines, that it waits with [`wait!`](crate::wait), so request-scoped data (a request id, a tracing span)
+//! doesn't need to be passed through every call.
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::CoroutineState;
+use std::rc::Rc;
+use crate::coroutine::CoroutineImpl;
+use crate::scheduler::local_scheduler;
+
+/// The task-local values of the running coroutine by the ids of their keys.
+///
+/// The [`Scheduler`](crate::scheduler::Scheduler) holds it, and every [`scope`](TaskLocalKey::scope) swaps its value
+/// in before the resume of its coroutine and out after it, so the map is empty between the coroutines.
+pub(crate) type TaskLocals = HashMap<usize, Rc<dyn Any>>;
+
+/// Declares [`TaskLocalKey`]s.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::ptr::null_mut;
+/// use engine::{coro, spawn_local, task_local, wait};
+///
+/// task_local! {
+///     static REQUEST_ID: u64;
+/// }
+///
+/// #[coro]
+/// fn log(message: &'static str) {
+///     println!("[request {}] {}", REQUEST_ID.get(), message);
+/// }
+///
+/// #[coro]
+/// fn handle_request() {
+///     wait!(log("started"));
+///     wait!(log("done"));
+/// }
+///
+/// #[coro]
+/// fn start() {
+///     for id in 0..10 {
+///         spawn_local!(REQUEST_ID.scope(id, |res| handle_request(res)));
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::local::TaskLocalKey<$t> = $crate::local::TaskLocalKey::new(stringify!($name));
+        $crate::task_local!($($rest)*);
+    };
+    () => {};
+}
+
+/// The key of a task-local value. Declare it with [`task_local!`](crate::task_local).
+///
+/// The value is set by [`scope`](TaskLocalKey::scope) and is read by [`with`](TaskLocalKey::with),
+/// [`try_with`](TaskLocalKey::try_with) or [`get`](TaskLocalKey::get).
+///
+/// # Note
+///
+/// Coroutines, that are spawned in the scope, don't inherit the value, because they outlive it.
+/// Pass it to them explicitly with their own [`scope`](TaskLocalKey::scope).
+pub struct TaskLocalKey<T: 'static> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T: 'static> TaskLocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, _marker: PhantomData }
+    }
+
+    /// Returns the id of the key. Keys are statics and are not zero-sized, so their addresses are unique.
+    #[inline(always)]
+    fn id(&'static self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Returns the coroutine, that runs the coroutine of the `creator` with the `value` of the key.
+    /// The `value` shadows the value of an outer scope.
+    ///
+    /// It takes the result pointer as the last argument, so it can be used with [`wait!`](crate::wait)
+    /// and [`spawn_local!`](crate::spawn_local).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let len: usize = wait!(REQUEST_ID.scope(42, |res| handle_request(stream, res)));
+    /// ```
+    pub fn scope<R, C: FnOnce(*mut R) -> CoroutineImpl>(&'static self, value: T, creator: C, res: *mut R) -> CoroutineImpl {
+        let id = self.id();
+        let value: Rc<dyn Any> = Rc::new(value);
+        let mut func = creator(res);
+        Box::pin(#[coroutine] static move || {
+            loop {
+                let outer = local_scheduler().task_locals().insert(id, value.clone());
+                let state = func.as_mut().resume(());
+                let locals = local_scheduler().task_locals();
+                match outer {
+                    Some(outer) => locals.insert(id, outer),
+                    None => locals.remove(&id)
+                };
+
+                match state {
+                    CoroutineState::Yielded(status) => yield status,
+                    CoroutineState::Complete(()) => break
+                }
+            }
+        })
+    }
+
+    /// Calls `f` with the value of the key in the current coroutine.
+    ///
+    /// Returns [`AccessError`], if the current coroutine is not run in a [`scope`](TaskLocalKey::scope) of the key.
+    pub fn try_with<F: FnOnce(&T) -> R, R>(&'static self, f: F) -> Result<R, AccessError> {
+        // The Rc is cloned, so `f` can enter other scopes.
+        let value = match local_scheduler().task_locals().get(&self.id()) {
+            Some(value) => value.clone(),
+            None => return Err(AccessError { name: self.name })
+        };
+
+        Ok(f(value.downcast_ref::<T>().expect("[BUG] task-local value has another type. Please report this issue.")))
+    }
+
+    /// Calls `f` with the value of the key in the current coroutine.
+    ///
+    /// # Panics
+    ///
+    /// If the current coroutine is not run in a [`scope`](TaskLocalKey::scope) of the key.
+    pub fn with<F: FnOnce(&T) -> R, R>(&'static self, f: F) -> R {
+        match self.try_with(f) {
+            Ok(res) => res,
+            Err(err) => panic!("{}", err)
+        }
+    }
+
+    /// Returns a clone of the value of the key in the current coroutine.
+    ///
+    /// # Panics
+    ///
+    /// If the current coroutine is not run in a [`scope`](TaskLocalKey::scope) of the key.
+    pub fn get(&'static self) -> T where T: Clone {
+        self.with(|value| value.clone())
+    }
+}
+
+impl<T: 'static> Debug for TaskLocalKey<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskLocalKey").field("name", &self.name).finish()
+    }
+}
+
+/// The error, that means, that the current coroutine is not run in a [`scope`](TaskLocalKey::scope) of the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError {
+    name: &'static str
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "task-local {} is not set in the current coroutine", self.name)
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ptr::null_mut;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use crate::{coro, test_local, wait};
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+
+    task_local! {
+        static REQUEST_ID: u64;
+        static SPAN: &'static str;
+    }
+
+    #[coro(crate="crate")]
+    fn request_id() -> Option<u64> {
+        yield sleep(Duration::from_millis(1));
+        REQUEST_ID.try_with(|id| *id).ok()
+    }
+
+    #[coro(crate="crate")]
+    fn handle(seen: Rc<RefCell<Vec<(u64, u64)>>>) {
+        let before = REQUEST_ID.get();
+        let after: Option<u64> = wait!(request_id());
+        seen.borrow_mut().push((before, after.unwrap()));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_task_local() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for id in 1..=3 {
+            let seen = seen.clone();
+            local_scheduler().spawn(REQUEST_ID.scope(id, |res| handle(seen, res), null_mut()));
+        }
+        assert!(REQUEST_ID.try_with(|_| ()).is_err());
+
+        let inner: Option<u64> = wait!(REQUEST_ID.scope(7, |res| request_id(res)));
+        assert_eq!(inner, Some(7));
+        let shadowed: Option<u64> = wait!(REQUEST_ID.scope(8, |res| REQUEST_ID.scope(9, |res| request_id(res), res)));
+        assert_eq!(shadowed, Some(9));
+        let missing: Option<u64> = wait!(SPAN.scope("outer", |res| request_id(res)));
+        assert_eq!(missing, None);
+        assert!(REQUEST_ID.try_with(|_| ()).is_err());
+
+        while Rc::strong_count(&seen) > 1 {
+            yield sleep(Duration::from_millis(1));
+        }
+        let mut seen = seen.borrow().clone();
+        seen.sort();
+        assert_eq!(seen, vec![(1, 1), (2, 2), (3, 3)]);
+    }
+}
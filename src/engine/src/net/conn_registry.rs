@@ -0,0 +1,325 @@
+//! This module contains [`ConnRegistry`].
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::mem::MaybeUninit;
+use std::net::SocketAddr;
+use std::os::fd::RawFd;
+use crate::buf::{buffer, Buffer};
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::local::Local;
+use crate::local_scheduler;
+use crate::net::TcpStream;
+use crate::utils::Ptr;
+
+/// The id of a connection in [`ConnRegistry`]. Ids are not reused in one registry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnId(u64);
+
+impl ConnId {
+    /// Returns the id as a number.
+    #[inline(always)]
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for ConnId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Information about a registered connection.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnInfo {
+    fd: RawFd,
+    peer_addr: Option<SocketAddr>
+}
+
+impl ConnInfo {
+    /// Returns the fd of the connection.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns the address of the other side, if it was known at the registration.
+    #[inline(always)]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+}
+
+#[derive(Clone, Default)]
+struct Registry {
+    conns: HashMap<ConnId, ConnInfo>,
+    next_id: u64,
+    registered_total: u64,
+    forced_closes: u64
+}
+
+/// A table of live connections of the worker.
+///
+/// Handlers register their [`TcpStream`]s and unregister them, when they are done.
+/// Then the registry allows writing to all connections, closing all or selected connections
+/// and reading the counters. It is the foundation for admin endpoints like "kick client X".
+///
+/// [`ConnRegistry`] is backed by [`Local`], so it is cheap to clone and must be used only in the worker, where it was created.
+///
+/// # Ownership
+///
+/// The registry doesn't own connections. Closing a connection is a `shutdown`, that makes the pending and next reads
+/// of the handler return 0 bytes, so the handler drops its [`TcpStream`] as usual.
+/// A handler must unregister the stream before dropping it, otherwise the fd can be reused by another connection.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::io::AsyncRead;
+/// use engine::net::{ConnRegistry, TcpStream};
+///
+/// #[coro]
+/// fn handle(mut stream: TcpStream, registry: ConnRegistry) {
+///     let id = registry.register(&stream);
+///     loop {
+///         let res: Result<&[u8], Error> = yield stream.read();
+///         if res.map(|slice| slice.is_empty()).unwrap_or(true) {
+///             break;
+///         }
+///     }
+///     registry.unregister(id);
+/// }
+///
+/// fn kick(registry: &ConnRegistry, addr: std::net::IpAddr) {
+///     registry.close_if(|_, info| info.peer_addr().map(|peer| peer.ip()) == Some(addr));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ConnRegistry {
+    inner: Local<Registry>
+}
+
+impl ConnRegistry {
+    /// Creates a new empty registry in the current worker.
+    pub fn new() -> Self {
+        Self {
+            inner: Local::new(Registry::default())
+        }
+    }
+
+    /// Registers the connection and returns its id.
+    pub fn register(&self, stream: &TcpStream) -> ConnId {
+        let registry = self.inner.get_mut();
+        let id = ConnId(registry.next_id);
+        registry.next_id += 1;
+        registry.registered_total += 1;
+        registry.conns.insert(id, ConnInfo { fd: stream.fd(), peer_addr: stream.peer_addr().ok() });
+        id
+    }
+
+    /// Removes the connection from the registry. Returns false, if it is not registered (for example, it was closed by the registry).
+    pub fn unregister(&self, id: ConnId) -> bool {
+        self.inner.get_mut().conns.remove(&id).is_some()
+    }
+
+    /// Returns the information about the connection.
+    pub fn get(&self, id: ConnId) -> Option<ConnInfo> {
+        self.inner.get().conns.get(&id).copied()
+    }
+
+    /// Returns true, if the connection is registered.
+    pub fn contains(&self, id: ConnId) -> bool {
+        self.inner.get().conns.contains_key(&id)
+    }
+
+    /// Returns ids of all registered connections in no particular order.
+    pub fn ids(&self) -> Vec<ConnId> {
+        self.inner.get().conns.keys().copied().collect()
+    }
+
+    /// Returns the number of live connections.
+    pub fn len(&self) -> usize {
+        self.inner.get().conns.len()
+    }
+
+    /// Returns true, if there are no live connections.
+    pub fn is_empty(&self) -> bool {
+        self.inner.get().conns.is_empty()
+    }
+
+    /// Returns the number of connections, that have ever been registered.
+    pub fn registered_total(&self) -> u64 {
+        self.inner.get().registered_total
+    }
+
+    /// Returns the number of connections, that have been closed by the registry.
+    pub fn forced_closes(&self) -> u64 {
+        self.inner.get().forced_closes
+    }
+
+    /// Writes `data` to every registered connection.
+    ///
+    /// Writes are scheduled as separate coroutines, so a slow client doesn't delay others. Errors of writes are ignored.
+    /// Returns the number of connections, to which the data is being written.
+    pub fn broadcast(&self, data: &[u8]) -> usize {
+        let registry = self.inner.get();
+        for info in registry.conns.values() {
+            let mut buf = buffer();
+            buf.append(data);
+            local_scheduler().sched(write_to_conn(info.fd, buf));
+        }
+        registry.conns.len()
+    }
+
+    /// Closes the connection and removes it from the registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::NotFound`], if the connection is not registered.
+    pub fn close(&self, id: ConnId) -> Result<(), Error> {
+        let registry = self.inner.get_mut();
+        let info = registry.conns.remove(&id).ok_or_else(|| Error::new(ErrorKind::NotFound, "the connection is not registered"))?;
+        registry.forced_closes += 1;
+        shutdown(info.fd)
+    }
+
+    /// Closes all connections, for which `predicate` returns true, and removes them from the registry.
+    /// Returns the number of closed connections.
+    pub fn close_if<F: FnMut(ConnId, &ConnInfo) -> bool>(&self, mut predicate: F) -> usize {
+        let registry = self.inner.get_mut();
+        let mut closed = 0;
+        registry.conns.retain(|id, info| {
+            if !predicate(*id, info) {
+                return true;
+            }
+            let _ = shutdown(info.fd);
+            closed += 1;
+            false
+        });
+        registry.forced_closes += closed as u64;
+        closed
+    }
+
+    /// Closes all connections and clears the registry. Returns the number of closed connections.
+    pub fn close_all(&self) -> usize {
+        self.close_if(|_, _| true)
+    }
+}
+
+impl Default for ConnRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn shutdown(fd: RawFd) -> Result<(), Error> {
+    if unsafe { libc::shutdown(fd, libc::SHUT_RDWR) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Writes the buffer through its own state, so the state of the [`TcpStream`], that can be waiting for a read, is not touched.
+fn write_to_conn(fd: RawFd, buf: Buffer) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let state_ref = Ptr::new(PollState::new_empty(fd));
+        let mut res = MaybeUninit::<Result<(), Error>>::uninit();
+        yield YieldStatus::tcp_write_all(state_ref, buf, res.as_mut_ptr());
+        unsafe {
+            res.assume_init_drop();
+            // The state has been read by the selector, so only the memory is freed.
+            state_ref.dealloc();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::io::AsyncRead;
+    use crate::local::Local;
+    use crate::net::{ConnRegistry, TcpStream};
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+
+    #[test_local(crate="crate")]
+    fn test_conn_registry() {
+        #[coro(crate="crate")]
+        fn handle(mut stream: TcpStream, registry: ConnRegistry, closed: Local<usize>) {
+            let id = registry.register(&stream);
+            loop {
+                let res: Result<&[u8], Error> = yield stream.read();
+                if res.map(|slice| slice.is_empty()).unwrap_or(true) {
+                    break;
+                }
+            }
+            registry.unregister(id);
+            *closed.get_mut() += 1;
+        }
+
+        const CONNS: usize = 3;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let mut handles = Vec::new();
+            for _ in 0..CONNS {
+                let (mut stream, _) = listener.accept().unwrap();
+                handles.push(std::thread::spawn(move || {
+                    let mut received = Vec::new();
+                    stream.read_to_end(&mut received).unwrap();
+                    received
+                }));
+            }
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+        });
+
+        let registry = ConnRegistry::new();
+        let closed = Local::new(0);
+        for _ in 0..CONNS {
+            let stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+            local_scheduler().sched(handle(stream, registry.clone(), closed.clone(), null_mut()));
+        }
+        yield sleep(Duration::from_millis(1));
+        assert_eq!(registry.len(), CONNS);
+        assert_eq!(registry.registered_total(), CONNS as u64);
+        assert!(registry.ids().iter().all(|id| registry.get(*id).unwrap().peer_addr() == Some(addr)));
+
+        assert_eq!(registry.broadcast(b"hello"), CONNS);
+        yield sleep(Duration::from_millis(10));
+
+        let first = registry.ids()[0];
+        registry.close(first).unwrap();
+        assert!(!registry.contains(first));
+        assert!(registry.close(first).is_err());
+        for _ in 0..100 {
+            if *closed.get() == 1 {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*closed.get(), 1);
+
+        assert_eq!(registry.close_all(), CONNS - 1);
+        for _ in 0..100 {
+            if *closed.get() == CONNS {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*closed.get(), CONNS);
+        assert!(registry.is_empty());
+        assert_eq!(registry.forced_closes(), CONNS as u64);
+
+        for received in peer.join().unwrap() {
+            assert_eq!(received, b"hello");
+        }
+    }
+}
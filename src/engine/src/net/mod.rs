@@ -1,3 +1,5 @@
+pub mod conn_registry;
 pub mod tcp;
 
-pub use tcp::{TcpListener, TcpStream};
\ No newline at end of file
+pub use conn_registry::{ConnId, ConnInfo, ConnRegistry};
+pub use tcp::{ReadStream, TcpListener, TcpStream};
\ No newline at end of file
@@ -1,12 +1,16 @@
 //! This module contains [`TcpListener`].
+use std::fmt::{Debug, Formatter};
 use std::io::Error;
 use std::net::{SocketAddr};
-use std::os::fd::{IntoRawFd, RawFd};
+use std::os::fd::{BorrowedFd, IntoRawFd, RawFd};
+use socket2::SockRef;
 use crate::coroutine::{CoroutineImpl, YieldStatus};
 use crate::io::sys::unix::epoll::net::get_tcp_listener_fd;
 use crate::net::tcp::TcpStream;
+use crate::net::tcp::stream::socket_addr;
 use crate::io::PollState;
-use crate::{local_scheduler};
+use crate::{coro, local_scheduler};
+use crate::stream::CoStream;
 use crate::utils::Ptr;
 
 /// A TCP socket server, listening for connections.
@@ -62,6 +66,7 @@ use crate::utils::Ptr;
 /// }
 /// ```
 pub struct TcpListener {
+    fd: RawFd,
     pub(crate) state_ptr: Ptr<PollState>,
     /// OwnedFd is required for Drop
     pub(crate) is_registered: bool
@@ -71,11 +76,31 @@ impl TcpListener {
     /// Creates a new TcpListener from an existing fd.
     pub fn from_fd(fd: RawFd) -> Self {
         Self {
+            fd,
             state_ptr: Ptr::new(PollState::new_empty(fd)),
             is_registered: false
         }
     }
 
+    /// Returns the raw file descriptor.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Registers the fd of the listener with the selector, so accepts don't look it up in the fd table.
+    /// Returns false, if the selector doesn't register fds or has no free slots.
+    /// Read [`set_fixed_files`](crate::cfg::set_fixed_files) for more information.
+    pub fn register_fixed(&self, res: *mut Result<bool, Error>) -> YieldStatus {
+        YieldStatus::register_file(self.fd, res)
+    }
+
+    /// Returns the local address of the listener. It is useful, when the listener is bound to the port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        let fd = unsafe { BorrowedFd::borrow_raw(self.fd) };
+        socket_addr(SockRef::from(&fd).local_addr())
+    }
+
     /// Returns the state_ptr of the [`TcpListener`].
     ///
     /// Uses for low-level work with the scheduler. If you don't know what it is, don't use it.
@@ -145,6 +170,32 @@ impl TcpListener {
     }
 }
 
+impl Debug for TcpListener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TcpListener");
+        debug.field("fd", &self.fd);
+        debug.field("state", &unsafe { self.state_ptr.as_ref() }.kind());
+        if let Ok(addr) = self.local_addr() {
+            debug.field("local_addr", &addr);
+        }
+        debug.finish()
+    }
+}
+
+/// The stream of accepted connections. It never ends, errors of accepting are returned as items.
+impl CoStream<Result<TcpStream, Error>> for TcpListener {
+    fn next(&mut self, res: *mut Option<Result<TcpStream, Error>>) -> CoroutineImpl {
+        next_connection(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_connection(listener: *mut TcpListener) -> Option<Result<TcpStream, Error>> {
+    let listener = unsafe { &mut *listener };
+    let res: Result<TcpStream, Error> = yield listener.accept();
+    return Some(res);
+}
+
 fn close_listener(state_ptr: Ptr<PollState>) -> CoroutineImpl {
     Box::pin(#[coroutine] static move || {
         yield TcpListener::close(state_ptr);
@@ -161,4 +212,54 @@ impl Drop for TcpListener {
             unsafe { state_ptr.drop_in_place(); }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::net::SocketAddr;
+    use crate::{test_local, wait};
+    use crate::net::{TcpListener, TcpStream};
+    use crate::stream::CoStream;
+
+    #[test_local(crate="crate")]
+    fn test_debug() {
+        let mut listener: TcpListener = yield TcpListener::new("127.0.0.1:0".parse().unwrap());
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        let debug = format!("{:?}", listener);
+        assert!(debug.contains(&format!("fd: {}", listener.fd())), "{}", debug);
+        assert!(debug.contains(&format!("local_addr: {}", addr)), "{}", debug);
+
+        let client = std::thread::spawn(move || std::net::TcpStream::connect(addr).unwrap());
+        let stream: Result<TcpStream, Error> = yield listener.accept();
+        let stream = stream.unwrap();
+        let client = client.join().unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), client.local_addr().unwrap());
+        let debug = format!("{:?}", stream);
+        assert!(debug.starts_with("TcpStream { fd: "), "{}", debug);
+        assert!(debug.contains(&format!("peer_addr: {}", client.local_addr().unwrap())), "{}", debug);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_accept_stream() {
+        let listener: TcpListener = yield TcpListener::new("127.0.0.1:0".parse().unwrap());
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let clients = std::thread::spawn(move || {
+            (0..3).map(|_| std::net::TcpStream::connect(addr).unwrap()).collect::<Vec<_>>()
+        });
+
+        let mut ports = listener.map(|stream: Result<TcpStream, Error>| stream.unwrap().peer_addr().unwrap().port()).take(3);
+        let mut accepted = Vec::new();
+        loop {
+            let port: Option<u16> = wait!(ports.next());
+            match port {
+                Some(port) => accepted.push(port),
+                None => break
+            }
+        }
+        let mut connected: Vec<u16> = clients.join().unwrap().iter().map(|client| client.local_addr().unwrap().port()).collect();
+        accepted.sort();
+        connected.sort();
+        assert_eq!(accepted, connected);
+    }
+}
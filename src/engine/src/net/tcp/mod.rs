@@ -1,5 +1,7 @@
 pub mod listener;
+pub mod read_stream;
 pub mod stream;
 
 pub use listener::TcpListener;
+pub use read_stream::ReadStream;
 pub use stream::TcpStream;
\ No newline at end of file
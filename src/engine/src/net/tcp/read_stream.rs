@@ -0,0 +1,167 @@
+//! This module contains [`ReadStream`].
+use std::cmp;
+use std::io::Error;
+use std::marker::PhantomData;
+use crate::buf::{buffer, Buffer};
+use crate::coro;
+use crate::coroutine::CoroutineImpl;
+use crate::io::AsyncRead;
+use crate::net::TcpStream;
+
+/// A handle for reading a large message from a [`TcpStream`] as successive [`Buffer`]s.
+/// It is created by [`TcpStream::read_stream`].
+///
+/// It allows handling multi-megabyte messages without one giant contiguous [`Buffer`].
+/// The message ends when `max_total` bytes are read or the other side closes the connection.
+///
+/// # Leftover
+///
+/// The last read can contain bytes after the limit, that belong to the next message.
+/// They are not lost, take them with [`ReadStream::take_leftover`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, wait};
+/// use engine::buf::Buffer;
+/// use engine::net::TcpStream;
+///
+/// #[coro]
+/// fn receive_upload(mut stream: TcpStream, content_length: usize) {
+///     let mut upload = stream.read_stream(content_length);
+///     loop {
+///         let chunk: Result<Option<Buffer>, Error> = wait!(upload.next());
+///         match chunk.unwrap() {
+///             Some(chunk) => { /* write the chunk to the disk */ }
+///             None => break
+///         }
+///     }
+/// }
+/// ```
+pub struct ReadStream<'stream> {
+    stream: *mut TcpStream,
+    remaining: usize,
+    leftover: Option<Buffer>,
+    is_closed: bool,
+    _stream: PhantomData<&'stream mut TcpStream>
+}
+
+impl<'stream> ReadStream<'stream> {
+    pub(crate) fn new(stream: &'stream mut TcpStream, max_total: usize) -> Self {
+        Self {
+            stream,
+            remaining: max_total,
+            leftover: None,
+            is_closed: false,
+            _stream: PhantomData
+        }
+    }
+
+    /// Returns the next part of the message, or [`None`] when the message is over.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait).
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self, res: *mut Result<Option<Buffer>, Error>) -> CoroutineImpl {
+        let stream: *mut ReadStream<'stream> = self;
+        next_part(stream.cast(), res)
+    }
+
+    /// Returns how many bytes can be read before the limit.
+    #[inline(always)]
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns true, if the other side closed the connection.
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
+    /// Takes the bytes, that were read after the limit.
+    pub fn take_leftover(&mut self) -> Option<Buffer> {
+        self.leftover.take()
+    }
+}
+
+#[coro(crate="crate")]
+fn next_part(read_stream: *mut ReadStream<'static>) -> Result<Option<Buffer>, Error> {
+    let read_stream = unsafe { &mut *read_stream };
+    if read_stream.remaining == 0 || read_stream.is_closed {
+        return Ok(None);
+    }
+
+    let stream = unsafe { &mut *read_stream.stream };
+    let res: Result<&'static [u8], Error> = yield stream.read();
+    let slice = match res {
+        Ok(slice) => slice,
+        Err(err) => return Err(err)
+    };
+    if slice.is_empty() {
+        read_stream.is_closed = true;
+        return Ok(None);
+    }
+
+    let n = cmp::min(slice.len(), read_stream.remaining);
+    read_stream.remaining -= n;
+    if n < slice.len() {
+        let mut leftover = buffer();
+        leftover.append(&slice[n..]);
+        read_stream.leftover = Some(leftover);
+    }
+
+    let mut buf = buffer();
+    buf.append(&slice[..n]);
+    return Ok(Some(buf));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Write};
+    use std::net::SocketAddr;
+    use crate::{test_local, wait};
+    use crate::buf::Buffer;
+    use crate::net::TcpStream;
+
+    #[test_local(crate="crate")]
+    fn test_read_stream() {
+        const MESSAGE_LEN: usize = 1_000_000;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&vec![7u8; MESSAGE_LEN]).unwrap();
+            stream.write_all(b"next").unwrap();
+        });
+
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut read_stream = stream.read_stream(MESSAGE_LEN);
+        let mut total = 0;
+        let mut parts = 0;
+        loop {
+            let res: Result<Option<Buffer>, Error> = wait!(read_stream.next());
+            match res.unwrap() {
+                Some(part) => {
+                    assert!(part.as_ref().iter().all(|&b| b == 7));
+                    total += part.len();
+                    parts += 1;
+                }
+                None => break
+            }
+        }
+
+        assert_eq!(total, MESSAGE_LEN);
+        assert!(parts > 1, "the message was read as a single buffer");
+        assert_eq!(read_stream.remaining(), 0);
+        assert!(!read_stream.is_closed());
+        let mut next = read_stream.take_leftover().map(|buf| buf.as_ref().to_vec()).unwrap_or_default();
+        while next.len() < 4 {
+            let res: Result<Option<Buffer>, Error> = wait!(stream.read_stream(4 - next.len()).next());
+            next.extend_from_slice(res.unwrap().unwrap().as_ref());
+        }
+        assert_eq!(next, b"next");
+
+        peer.join().unwrap();
+    }
+}
@@ -1,19 +1,31 @@
 //! This module contains [`TcpStream`].
-use std::io::Error;
+use std::fmt::{Debug, Formatter};
+use std::io::{Error, ErrorKind};
 use std::net::SocketAddr;
-use std::os::fd::RawFd;
+use std::os::fd::{BorrowedFd, RawFd};
+use std::time::Duration;
+use socket2::{SockAddr, SockRef};
 use crate::coroutine::{CoroutineImpl, YieldStatus};
 use crate::io::{AsyncRead, AsyncWrite, PollState};
 use crate::{local_scheduler};
 use crate::buf::Buffer;
+use crate::net::ReadStream;
 use crate::utils::Ptr;
 
 // TODO docs for connect. Here we can add reference to docs in TcpListener
 /// A TCP stream between a local and a remote socket.
 ///
+/// # End of stream
+///
+/// A read returns an empty slice, when the other side has closed the connection (or shut down its writing half).
+/// After it, every read returns an empty slice at once, without waiting for the socket.
+/// Failures are always returned as errors, so an empty slice means only the end of the stream.
+///
 /// # Close
 ///
 /// [`TcpStream`] is automatically closed when it is dropped.
+/// If another coroutine still waits for an operation on the stream, the operation fails with
+/// [`ECANCELED`](libc::ECANCELED), and the stream is closed after it.
 ///
 /// # Examples
 ///
@@ -60,7 +72,10 @@ use crate::utils::Ptr;
 /// spawn_local!(connect_to_server());
 /// ```
 pub struct TcpStream {
+    fd: RawFd,
     is_registered: bool,
+    is_eof: bool,
+    read_coalescing: usize,
     data: Ptr<PollState>
 }
 
@@ -68,17 +83,45 @@ impl TcpStream {
     /// Create a new `TcpStream` from a raw file descriptor.
     pub fn new(fd: RawFd) -> Self {
         Self {
+            fd,
             is_registered: false,
+            is_eof: false,
+            read_coalescing: 0,
             data: Ptr::new(PollState::new_empty(fd))
         }
     }
 
+    /// Returns the raw file descriptor.
+    #[inline(always)]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns the local address of the stream.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        let fd = unsafe { BorrowedFd::borrow_raw(self.fd) };
+        socket_addr(SockRef::from(&fd).local_addr())
+    }
+
+    /// Returns the address of the other side of the stream.
+    pub fn peer_addr(&self) -> Result<SocketAddr, Error> {
+        let fd = unsafe { BorrowedFd::borrow_raw(self.fd) };
+        socket_addr(SockRef::from(&fd).peer_addr())
+    }
+
     // TODO more docs
     /// Connects to the specified address.
     pub fn connect(addr: SocketAddr, res: *mut Result<TcpStream, Error>) -> YieldStatus {
         YieldStatus::tcp_connect(addr, res)
     }
 
+    /// Connects to the specified address. It fails with [`ErrorKind::TimedOut`], if the connection is not established in `timeout`.
+    ///
+    /// The `Ring` selector links the timeout to the connect, so the kernel cancels the connect without waking the worker up.
+    pub fn connect_timeout(addr: SocketAddr, timeout: Duration, res: *mut Result<TcpStream, Error>) -> YieldStatus {
+        YieldStatus::tcp_connect_timeout(addr, timeout, res)
+    }
+
     /// Returns the state_ptr of the [`TcpStream`].
     ///
     /// Uses for low-level work with the scheduler. If you don't know what it is, don't use it.
@@ -97,6 +140,40 @@ impl TcpStream {
         self.is_registered = is_registered;
     }
 
+    /// Enables read coalescing: after a read wakes up, bytes, that are already in the socket, are read too,
+    /// until the socket is drained or `max_bytes` bytes are read. 0 disables it (the default).
+    ///
+    /// It reduces wakeups per byte for chatty connections, but costs one more `recv` per read.
+    pub fn set_read_coalescing(&mut self, max_bytes: usize) {
+        self.read_coalescing = max_bytes;
+    }
+
+    /// Returns the byte limit of read coalescing. Read [`TcpStream::set_read_coalescing`].
+    #[inline(always)]
+    pub fn read_coalescing(&self) -> usize {
+        self.read_coalescing
+    }
+
+    /// Returns [`ReadStream`] for reading a message of up to `max_total` bytes as successive [`Buffer`]s.
+    ///
+    /// Read [`ReadStream`] for more information.
+    pub fn read_stream(&mut self, max_total: usize) -> ReadStream<'_> {
+        ReadStream::new(self, max_total)
+    }
+
+    /// Like [`AsyncWrite::write`], but the `Ring` selector sends the buffer with `SendZc` (Linux 6.0+):
+    /// the kernel sends the memory of the buffer without copying it.
+    ///
+    /// The kernel uses the buffer after the send has completed, so the coroutine is woken up only
+    /// after the notification, that the buffer is released, and only then the buffer can return to the pool.
+    /// It pays off for large buffers (tens of kilobytes and more), small ones are cheaper to copy.
+    ///
+    /// The `Epoll` selector and older kernels send the buffer as usual.
+    #[inline(always)]
+    pub fn write_zc(&mut self, data: Buffer, res: *mut Result<Option<Buffer>, Error>) -> YieldStatus {
+        YieldStatus::tcp_write_zc(self.data, data, res)
+    }
+
     /// Closes the stream.
     fn close(state_ref: Ptr<PollState>) -> YieldStatus {
         YieldStatus::tcp_close(state_ref)
@@ -110,7 +187,11 @@ impl AsyncRead<&'static [u8]> for TcpStream {
         if !is_registered {
             self.set_registered(true);
         }
-        YieldStatus::tcp_read(is_registered, self.data, res)
+        // The selector marks the state after an empty read, but a write replaces the state, so the stream remembers it.
+        if unsafe { self.data.as_ref() }.is_eof() {
+            self.is_eof = true;
+        }
+        YieldStatus::tcp_read(is_registered, self.is_eof, self.data, self.read_coalescing, res)
     }
 }
 
@@ -126,9 +207,39 @@ impl AsyncWrite<Buffer> for TcpStream {
     }
 }
 
-fn close_stream(state_ref: Ptr<PollState>) -> CoroutineImpl {
+impl Debug for TcpStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TcpStream");
+        debug.field("fd", &self.fd);
+        debug.field("state", &unsafe { self.data.as_ref() }.kind());
+        if let Ok(addr) = self.local_addr() {
+            debug.field("local_addr", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            debug.field("peer_addr", &addr);
+        }
+        debug.finish()
+    }
+}
+
+/// Converts the address of a TCP socket to [`SocketAddr`].
+pub(crate) fn socket_addr(addr: Result<SockAddr, Error>) -> Result<SocketAddr, Error> {
+    addr?.as_socket().ok_or_else(|| Error::new(ErrorKind::InvalidData, "the socket address is not an IP address"))
+}
+
+fn close_stream(state_ref: Ptr<PollState>, is_registered: bool) -> CoroutineImpl {
     Box::pin(#[coroutine] static move || {
-        yield TcpStream::close(state_ref);
+        // An operation of another coroutine (like a lost branch of select!) can still use the state.
+        // It is cancelled, and the state is reused only after its completion.
+        if !unsafe { state_ref.as_ref() }.is_empty() {
+            yield YieldStatus::cancel(state_ref);
+            while !unsafe { state_ref.as_ref() }.is_empty() {
+                yield YieldStatus::yield_now();
+            }
+        }
+        if is_registered {
+            yield TcpStream::close(state_ref);
+        }
         unsafe { state_ref.drop_in_place(); }
     })
 }
@@ -136,10 +247,195 @@ fn close_stream(state_ref: Ptr<PollState>) -> CoroutineImpl {
 impl Drop for TcpStream {
     fn drop(&mut self) {
         let state_ptr = self.data;
-        if self.is_registered() {
-            local_scheduler().sched(close_stream(state_ptr));
+        if self.is_registered() || !unsafe { state_ptr.as_ref() }.is_empty() {
+            local_scheduler().sched(close_stream(state_ptr, self.is_registered()));
         } else {
             unsafe { state_ptr.drop_in_place(); }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, Read, Write};
+    use std::net::SocketAddr;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::buf::{buf_pool, buffer};
+    use crate::cfg::{config, config_write_turn_cap, SelectorType};
+    use crate::io::{AsyncRead, AsyncWrite};
+    use crate::local::Local;
+    use crate::run::run_on_core_with_config;
+    use crate::scheduler::local_scheduler;
+    use crate::net::TcpStream;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+
+    #[test_local(crate="crate")]
+    fn test_read_coalescing() {
+        const CHUNK_LEN: usize = 1000;
+        const CHUNKS: usize = 64;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for _ in 0..CHUNKS {
+                stream.write_all(&[3u8; CHUNK_LEN]).unwrap();
+            }
+            stream
+        });
+
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        stream.set_read_coalescing(CHUNK_LEN * CHUNKS);
+        assert_eq!(stream.read_coalescing(), CHUNK_LEN * CHUNKS);
+        let peer_stream = peer.join().unwrap();
+        yield sleep(Duration::from_millis(10));
+
+        let mut total = 0;
+        let mut reads = 0;
+        while total < CHUNK_LEN * CHUNKS {
+            let res: Result<&[u8], Error> = yield stream.read();
+            let slice = res.unwrap();
+            assert!(slice.iter().all(|&b| b == 3));
+            total += slice.len();
+            reads += 1;
+        }
+        assert_eq!(total, CHUNK_LEN * CHUNKS);
+        assert!(reads < CHUNK_LEN * CHUNKS / buf_pool().buffer_len(), "the reads were not coalesced");
+        drop(peer_stream);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_write_all_in_turns() {
+        const CONNS: usize = 2;
+        let message_len = config_write_turn_cap() * 4 + 1;
+
+        #[coro(crate="crate")]
+        fn send_message(addr: SocketAddr, message_len: usize, done: Local<usize>) {
+            let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+            let mut buf = buffer();
+            buf.append(&vec![5u8; message_len]);
+            let res: Result<(), Error> = yield stream.write_all(buf);
+            res.unwrap();
+            *done.get_mut() += 1;
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let readers = (0..CONNS).map(|_| {
+                let (mut stream, _) = listener.accept().unwrap();
+                std::thread::spawn(move || {
+                    let mut received = vec![0u8; message_len];
+                    stream.read_exact(&mut received).unwrap();
+                    received.iter().all(|&b| b == 5)
+                })
+            }).collect::<Vec<_>>();
+            readers.into_iter().all(|reader| reader.join().unwrap())
+        });
+
+        let done = Local::new(0);
+        for _ in 0..CONNS {
+            local_scheduler().sched(send_message(addr, message_len, done.clone(), null_mut()));
+        }
+        for _ in 0..1000 {
+            if *done.get() == CONNS {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*done.get(), CONNS);
+        assert!(peer.join().unwrap());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_drop_with_pending_read() {
+        #[coro(crate="crate")]
+        fn read_detached(stream: *mut TcpStream, result: Local<Option<Option<i32>>>) {
+            let stream = unsafe { &mut *stream };
+            let res: Result<&[u8], Error> = yield stream.read();
+            *result.get_mut() = Some(res.err().and_then(|err| err.raw_os_error()));
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 16];
+            stream.read(&mut buf).unwrap()
+        });
+
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let result = Local::new(None);
+        local_scheduler().sched(read_detached(&mut stream, result.clone(), null_mut()));
+        yield sleep(Duration::from_millis(10));
+        assert!(result.get().is_none());
+
+        // The read is cancelled, and only then the stream is closed.
+        drop(stream);
+        for _ in 0..1000 {
+            if result.get().is_some() {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*result.get(), Some(Some(libc::ECANCELED)));
+        yield sleep(Duration::from_millis(10));
+        assert_eq!(peer.join().unwrap(), 0);
+    }
+
+    /// Reads the stream to the end, then reads it twice more around a write.
+    /// Returns the read bytes and the lengths of the reads after the end.
+    #[coro(crate="crate")]
+    fn read_after_eof(addr: SocketAddr) -> (Vec<u8>, Vec<usize>) {
+        let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+        let mut received = Vec::new();
+        let mut after_eof = Vec::new();
+        loop {
+            let slice: &[u8] = (yield stream.read()).unwrap();
+            if slice.is_empty() {
+                break;
+            }
+            received.extend_from_slice(slice);
+        }
+
+        let slice: &[u8] = (yield stream.read()).unwrap();
+        after_eof.push(slice.len());
+        // The other side can still read, and the write replaces the state of the stream.
+        let mut buf = buffer();
+        buf.append(b"bye");
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        res.unwrap();
+        let slice: &[u8] = (yield stream.read()).unwrap();
+        after_eof.push(slice.len());
+        // The worker stops after the coroutine, so the stream is closed before it.
+        drop(stream);
+        yield sleep(Duration::from_millis(10));
+
+        return (received, after_eof);
+    }
+
+    #[test]
+    fn test_read_eof() {
+        for selector in [SelectorType::Ring, SelectorType::Poller] {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr: SocketAddr = listener.local_addr().unwrap();
+            let peer = std::thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream.write_all(b"hello").unwrap();
+                stream.shutdown(std::net::Shutdown::Write).unwrap();
+                let mut bye = [0u8; 3];
+                stream.read_exact(&mut bye).unwrap();
+                bye
+            });
+
+            let core = get_core_ids().unwrap()[0];
+            let cfg = config().with_selector(selector);
+            let (received, after_eof) = run_on_core_with_config(move |res| read_after_eof(addr, res), core, cfg).unwrap().unwrap();
+            assert_eq!(received, b"hello");
+            assert_eq!(after_eof, [0, 0]);
+            assert_eq!(&peer.join().unwrap(), b"bye");
+        }
+    }
+}
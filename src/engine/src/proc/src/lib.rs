@@ -4,9 +4,12 @@
 extern crate proc_macro;
 use proc_macro::{TokenStream};
 use std::ops::{Deref, DerefMut};
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 use syn::{parse_macro_input, ItemFn, ReturnType, Expr, Stmt, Block, Lit};
 use syn::token::Semi;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::Token;
 
 fn get_crate_name(attr: TokenStream) -> proc_macro2::TokenStream {
     let mut engine = quote! { engine };
@@ -32,6 +35,25 @@ fn get_crate_name(attr: TokenStream) -> proc_macro2::TokenStream {
     engine
 }
 
+/// Appends the argument to the call of a function or a method, like the pointer to the result of a coroutine.
+///
+/// # Panics
+///
+/// Panics, if the expression is not a call.
+fn push_arg(expr: Expr, arg: Expr) -> Expr {
+    match expr {
+        Expr::Call(mut call_expr) => {
+            call_expr.args.push(arg);
+            Expr::Call(call_expr)
+        }
+        Expr::MethodCall(mut method_call_expr) => {
+            method_call_expr.args.push(arg);
+            Expr::MethodCall(method_call_expr)
+        }
+        _ => panic!("The macro only supports function or method calls"),
+    }
+}
+
 /// Transforms function body. Replaces all `yield` expressions to
 /// ```ignore
 /// unsafe {
@@ -223,7 +245,7 @@ fn transform_function_yield(block: &mut Block) {
 /// Transforms function body. Replaces all `return` expressions and the implicit return to
 /// ```ignore
 /// {
-///     unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #ret_expr; }
+///     unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#ret_expr); }
 ///     return;
 /// }
 /// ```
@@ -237,14 +259,14 @@ fn transform_function_return(block: &mut Block, level: usize) {
                     transform_expr(&mut else_branch.1, None, level);
                 }
             }
-            Expr::Block(ref mut block_ex) => {
+            Expr::Block(block_ex) => {
                 transform_function_return(&mut block_ex.block, level);
             }
             Expr::Return(ret_ex) => {
                 let ret_expr = ret_ex.clone().expr.unwrap();
                 let new_expr: Expr = syn::parse_quote!(
                     {
-                        unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #ret_expr; }
+                        unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#ret_expr); }
                         return;
                     }
                 );
@@ -255,91 +277,91 @@ fn transform_function_return(block: &mut Block, level: usize) {
                     transform_expr(&mut arm.body, None, level + 1);
                 }
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #match_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#match_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Lit(lit_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #lit_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#lit_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Paren(paren_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #paren_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#paren_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Tuple(tuple_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #tuple_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#tuple_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Reference(ref_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #ref_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#ref_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Closure(closure_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #closure_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#closure_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Field(field_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #field_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#field_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::MethodCall(method_call_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #method_call_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#method_call_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Call(call_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #call_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#call_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Array(array_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #array_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#array_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Cast(cast_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #cast_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#cast_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Struct(struct_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #struct_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#struct_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Repeat(repeat_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #repeat_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#repeat_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Unary(unary_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #unary_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#unary_ex); return;});
                     *expr = new_expr;
                 }
             }
             Expr::Binary(binary_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #binary_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#binary_ex); return;});
                     *expr = new_expr;
                 }
             }
@@ -348,7 +370,7 @@ fn transform_function_return(block: &mut Block, level: usize) {
             }
             Expr::Yield(yield_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #yield_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#yield_ex); return;});
                     *expr = new_expr;
                 }
             }
@@ -366,7 +388,7 @@ fn transform_function_return(block: &mut Block, level: usize) {
             }
             Expr::Range(range_ex) => {
                 if level == 1 && semi.is_none() {
-                    let new_expr: Expr = syn::parse_quote!(unsafe { *coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT = #range_ex; return;});
+                    let new_expr: Expr = syn::parse_quote!(unsafe { coroutine_argument_DONT_NAME_YOUR_VARIABLE_AS_IT.write(#range_ex); return;});
                     *expr = new_expr;
                 }
             }
@@ -524,18 +546,69 @@ pub fn coro(macro_attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn wait(input: TokenStream) -> TokenStream {
     let input_expr = parse_macro_input!(input as Expr);
 
-    let modified_expr = match input_expr {
-        Expr::Call(mut call_expr) => {
-            call_expr.args.push(syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr()));
-            Expr::Call(call_expr)
-        }
-        Expr::MethodCall(mut method_call_expr) => {
-            method_call_expr.args.push(syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr()));
-            Expr::MethodCall(method_call_expr)
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr()));
+
+    let block = quote! {
+        unsafe {
+            let mut coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT = std::mem::MaybeUninit::uninit();
+            let mut coroutine = #modified_expr;
+            loop {
+                match coroutine.as_mut().resume(()) {
+                    std::ops::CoroutineState::Yielded(state) => {
+                        yield state;
+                    },
+                    std::ops::CoroutineState::Complete(res) => break res,
+                }
+            }
+            coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.assume_init()
         }
-        _ => panic!("The macro only supports function or method calls"),
     };
 
+    TokenStream::from(block)
+}
+
+/// Like [`wait!`], but passes the deadline of the parent to the child as its last argument.
+///
+/// The child gets the [`Deadline`](engine::sleep::Deadline) with the remaining time of the parent,
+/// so nested timeouts don't need to be recalculated. The child can shorten it, but not extend.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::time::Duration;
+/// use engine::{coro, wait_within};
+/// use engine::sleep::Deadline;
+///
+/// #[coro]
+/// fn fetch(id: u32, deadline: Deadline) -> Result<String, Error> {
+///     deadline.check()?;
+///     Ok(format!("item {}", id))
+/// }
+///
+/// #[coro]
+/// fn fetch_all() -> Result<(), Error> {
+///     let deadline = Deadline::after(Duration::from_millis(500));
+///     for id in 0..10 {
+///         let item: Result<String, Error> = wait_within!(fetch(id), deadline);
+///         println!("{}", item?);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[proc_macro]
+pub fn wait_within(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input with Punctuated::<Expr, Token![,]>::parse_terminated);
+    if args.len() != 2 {
+        panic!("The macro expects a function or method call and a deadline");
+    }
+    let mut args = args.into_iter();
+    let call = args.next().unwrap();
+    let deadline = args.next().unwrap();
+
+    let modified_expr = push_arg(call, deadline);
+    let modified_expr = push_arg(modified_expr, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr()));
+
     let block = quote! {
         unsafe {
             let mut coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT = std::mem::MaybeUninit::uninit();
@@ -583,21 +656,510 @@ pub fn wait(input: TokenStream) -> TokenStream {
 pub fn spawn_local(input: TokenStream) -> TokenStream {
     let input_expr = parse_macro_input!(input as Expr);
 
-    let modified_expr = match input_expr {
-        // TODO: think about. Maybe we need to hande expr, that is a CoroutineImpl?
-        Expr::Call(mut call_expr) => {
-            call_expr.args.push(syn::parse_quote!(std::ptr::null_mut()));
-            Expr::Call(call_expr)
+    // TODO: think about. Maybe we need to hande expr, that is a CoroutineImpl?
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(std::ptr::null_mut()));
+
+    let block = quote! {
+        engine::local_scheduler().spawn(#modified_expr);
+    };
+
+    TokenStream::from(block)
+}
+
+/// Like [`wait!`], but returns `Err(`[`Elapsed`](engine::coroutine::Elapsed)`)`, if the child doesn't finish
+/// within the duration. Otherwise, returns `Ok` with the result of the child.
+///
+/// The child runs concurrently with the timer. After the timeout its pending IO (accept, read
+/// or waiting for a readable fd) is cancelled, so the child gets `ECANCELED` and should return.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::time::Duration;
+/// use engine::{coro, wait_timeout};
+/// use engine::coroutine::Elapsed;
+/// use engine::net::TcpStream;
+///
+/// #[coro]
+/// fn read_request(stream: &mut TcpStream) -> Result<usize, Error> {
+///     let res: Result<&'static [u8], Error> = yield stream.read();
+///     res.map(|slice| slice.len())
+/// }
+///
+/// #[coro]
+/// fn handle(mut stream: TcpStream) {
+///     let res: Result<Result<usize, Error>, Elapsed> = wait_timeout!(read_request(&mut stream), Duration::from_secs(5));
+///     if res.is_err() {
+///         println!("the client is too slow");
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn wait_timeout(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input with Punctuated::<Expr, Token![,]>::parse_terminated);
+    if args.len() != 2 {
+        panic!("The macro expects a function or method call and a duration");
+    }
+    let mut args = args.into_iter();
+    let call = args.next().unwrap();
+    let dur = args.next().unwrap();
+
+    let modified_expr = push_arg(call, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT));
+
+    let block = quote! {
+        unsafe {
+            let mut handle_DONT_NAME_YOUR_VARIABLE_AS_IT = engine::coroutine::JoinHandle::spawn(move |coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT| #modified_expr);
+            let mut timeout_result_DONT_NAME_YOUR_VARIABLE_AS_IT = std::mem::MaybeUninit::uninit();
+            yield handle_DONT_NAME_YOUR_VARIABLE_AS_IT.wait_timeout(#dur, timeout_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr());
+            let res: Result<_, engine::coroutine::Elapsed> = timeout_result_DONT_NAME_YOUR_VARIABLE_AS_IT.assume_init();
+            if res.is_err() {
+                yield handle_DONT_NAME_YOUR_VARIABLE_AS_IT.cancel(std::ptr::null_mut());
+            }
+            res
         }
-        Expr::MethodCall(mut method_call_expr) => {
-            method_call_expr.args.push(syn::parse_quote!(std::ptr::null_mut()));
-            Expr::MethodCall(method_call_expr)
+    };
+
+    TokenStream::from(block)
+}
+
+/// A branch of [`select!`]: `pattern = operation => handler`.
+struct SelectBranch {
+    pat: syn::Pat,
+    call: Expr,
+    body: Expr
+}
+
+struct SelectInput {
+    branches: Vec<SelectBranch>
+}
+
+impl Parse for SelectInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut branches = Vec::new();
+        while !input.is_empty() {
+            let pat = syn::Pat::parse_single(input)?;
+            input.parse::<Token![=]>()?;
+            let call: Expr = input.parse()?;
+            input.parse::<Token![=>]>()?;
+            let body: Expr = input.parse()?;
+            input.parse::<Option<Token![,]>>()?;
+            branches.push(SelectBranch { pat, call, body });
         }
-        _ => panic!("The macro only supports function or method calls"),
+
+        Ok(Self { branches })
+    }
+}
+
+/// Waits for several operations at once and runs the handler of the first completed one.
+///
+/// Every branch is `pattern = operation => handler`, where the operation is a call, that is used with `yield`
+/// (like `stream.read()` or `sleep(dur)`). All operations are registered at once, and the coroutine is woken up,
+/// when the first of them completes. The pending IO of other branches (accept, read and waiting for a readable fd)
+/// is cancelled, and their results are dropped. Other lost operations, that use the state of the coroutine (like writes),
+/// can't be cancelled, so the macro waits for their completions. The macro returns the value of the handler.
+///
+/// Read [`Select`](engine::coroutine::Select) for more information.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::time::Duration;
+/// use engine::{coro, select};
+/// use engine::io::AsyncRead;
+/// use engine::net::TcpStream;
+/// use engine::sleep::sleep;
+///
+/// #[coro]
+/// fn handle_with_heartbeat(mut stream: TcpStream) {
+///     loop {
+///         let is_alive = select! {
+///             res = stream.read() => {
+///                 let res: Result<&'static [u8], Error> = res;
+///                 matches!(res, Ok(slice) if !slice.is_empty())
+///             },
+///             _ = sleep(Duration::from_secs(30)) => false
+///         };
+///         if !is_alive {
+///             break;
+///         }
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn select(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as SelectInput);
+    if input.branches.is_empty() {
+        panic!("The macro expects at least one branch");
+    }
+
+    let len = input.branches.len();
+    let mut branches = quote! {};
+    let mut arms = quote! {};
+    for (i, branch) in input.branches.into_iter().enumerate() {
+        let modified_expr = push_arg(branch.call, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT));
+        let result = format_ident!("select_result_{}_DONT_NAME_YOUR_VARIABLE_AS_IT", i);
+        let creator = format_ident!("select_creator_{}_DONT_NAME_YOUR_VARIABLE_AS_IT", i);
+        let pat = branch.pat;
+        let body = branch.body;
+
+        // The code of the user is kept outside `unsafe`, so it is checked as usual.
+        branches = quote! {
+            #branches
+            let mut #result = std::mem::MaybeUninit::uninit();
+            let #creator = |coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT| #modified_expr;
+            unsafe { select_DONT_NAME_YOUR_VARIABLE_AS_IT.branch(#i, #result.as_mut_ptr(), #creator) };
+        };
+        arms = quote! {
+            #arms
+            #i => {
+                let #pat = unsafe { #result.assume_init() };
+                #body
+            }
+        };
+    }
+
+    let block = quote! {
+        {
+            let mut select_DONT_NAME_YOUR_VARIABLE_AS_IT = engine::coroutine::Select::new(#len);
+            #branches
+            yield select_DONT_NAME_YOUR_VARIABLE_AS_IT.wait(std::ptr::null_mut());
+            while let Some(status) = select_DONT_NAME_YOUR_VARIABLE_AS_IT.cancel_next() {
+                yield status;
+            }
+            if select_DONT_NAME_YOUR_VARIABLE_AS_IT.has_pending() {
+                yield select_DONT_NAME_YOUR_VARIABLE_AS_IT.drain(std::ptr::null_mut());
+            }
+            match select_DONT_NAME_YOUR_VARIABLE_AS_IT.winner() {
+                #arms
+                _ => unreachable!()
+            }
+        }
+    };
+
+    TokenStream::from(block)
+}
+
+/// Spawn a new coroutine in the local scheduler and return its [`JoinHandle`](engine::coroutine::JoinHandle).
+///
+/// Unlike [`spawn_local!`], the result of the coroutine is kept, and the parent gets it with `yield handle.wait()`.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{coro, spawn_local_with_handle};
+/// use engine::coroutine::JoinHandle;
+///
+/// #[coro]
+/// fn fetch(id: u32) -> String {
+///     format!("item {}", id)
+/// }
+///
+/// #[coro]
+/// fn fetch_both() {
+///     let mut first: JoinHandle<String> = spawn_local_with_handle!(fetch(1));
+///     let mut second: JoinHandle<String> = spawn_local_with_handle!(fetch(2));
+///     // Both coroutines run concurrently.
+///     let first: String = yield first.wait();
+///     let second: String = yield second.wait();
+/// }
+/// ```
+#[proc_macro]
+pub fn spawn_local_with_handle(input: TokenStream) -> TokenStream {
+    let input_expr = parse_macro_input!(input as Expr);
+
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT));
+
+    let block = quote! {
+        engine::coroutine::JoinHandle::spawn(move |coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT| #modified_expr)
+    };
+
+    TokenStream::from(block)
+}
+
+/// Runs all children concurrently and returns the tuple of their results, when all of them are finished.
+///
+/// Unlike several [`wait!`]s, that run the children one by one, all children are spawned at once
+/// with [`JoinHandle`](engine::coroutine::JoinHandle)s, and the parent is suspended until the last result is written.
+/// So the total time is the time of the slowest child, not the sum.
+///
+/// # Panics
+///
+/// If a child panics, the parent panics too.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, join};
+///
+/// #[coro]
+/// fn fetch_user(id: u32) -> Result<String, Error> {
+///     Ok(format!("user {}", id))
+/// }
+///
+/// #[coro]
+/// fn fetch_orders(id: u32) -> Result<Vec<u32>, Error> {
+///     Ok(vec![id])
+/// }
+///
+/// #[coro]
+/// fn fetch_profile(id: u32) {
+///     let (user, orders): (Result<String, Error>, Result<Vec<u32>, Error>) = join!(fetch_user(id), fetch_orders(id));
+/// }
+/// ```
+#[proc_macro]
+pub fn join(input: TokenStream) -> TokenStream {
+    let calls = parse_macro_input!(input with Punctuated::<Expr, Token![,]>::parse_terminated);
+    if calls.is_empty() {
+        panic!("The macro expects at least one function or method call");
+    }
+
+    let mut spawns = quote! {};
+    let mut results = quote! {};
+    for (i, call) in calls.into_iter().enumerate() {
+        let modified_expr = push_arg(call, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT));
+        let handle = syn::Ident::new(&format!("join_handle_{}_DONT_NAME_YOUR_VARIABLE_AS_IT", i), proc_macro2::Span::call_site());
+        spawns = quote! {
+            #spawns
+            let mut #handle = engine::coroutine::JoinHandle::spawn(move |coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT| #modified_expr);
+        };
+        results = quote! {
+            #results
+            {
+                let mut coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT = std::mem::MaybeUninit::uninit();
+                yield #handle.wait(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr());
+                coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT.assume_init()
+            },
+        };
+    }
+
+    let block = quote! {
+        unsafe {
+            #spawns
+            (#results)
+        }
+    };
+
+    TokenStream::from(block)
+}
+
+/// The same as [`join!`].
+#[proc_macro]
+pub fn wait_all(input: TokenStream) -> TokenStream {
+    join(input)
+}
+
+/// Like [`wait!`], but runs the coroutine in a new [`Scope`](engine::coroutine::Scope) and waits for all its children.
+///
+/// The coroutine gets the scope as the last argument before the result pointer and spawns children with
+/// [`Scope::spawn`](engine::coroutine::Scope::spawn). The macro returns only after the coroutine and all children
+/// are finished. If the coroutine fails, the children are cancelled. Read [`Scope`](engine::coroutine::Scope).
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::{coro, scope};
+/// use engine::coroutine::Scope;
+/// use engine::net::TcpStream;
+///
+/// #[coro]
+/// fn fetch(id: u32, results: *mut Vec<String>) {
+///     unsafe { (*results).push(format!("item {}", id)) };
+/// }
+///
+/// #[coro]
+/// fn fetch_all(results: *mut Vec<String>, scope: Scope) {
+///     for id in 0..10 {
+///         scope.spawn(|res| fetch(id, results, res));
+///     }
+/// }
+///
+/// #[coro]
+/// fn handle_tcp_stream(mut stream: TcpStream) {
+///     let mut results = Vec::new();
+///     scope!(fetch_all(&mut results));
+///     // All items are fetched here.
+/// }
+/// ```
+#[proc_macro]
+pub fn scope(input: TokenStream) -> TokenStream {
+    let input_expr = parse_macro_input!(input as Expr);
+
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(scope_DONT_NAME_YOUR_VARIABLE_AS_IT));
+    let modified_expr = push_arg(modified_expr, syn::parse_quote!(coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT));
+
+    let block = quote! {
+        unsafe {
+            let mut scope_result_DONT_NAME_YOUR_VARIABLE_AS_IT = std::mem::MaybeUninit::uninit();
+            let mut coroutine = engine::coroutine::Scope::enter(
+                |scope_DONT_NAME_YOUR_VARIABLE_AS_IT, coroutine_result_DONT_NAME_YOUR_VARIABLE_AS_IT| #modified_expr,
+                scope_result_DONT_NAME_YOUR_VARIABLE_AS_IT.as_mut_ptr()
+            );
+            loop {
+                match coroutine.as_mut().resume(()) {
+                    std::ops::CoroutineState::Yielded(state) => {
+                        yield state;
+                    },
+                    std::ops::CoroutineState::Complete(res) => break res,
+                }
+            }
+            scope_result_DONT_NAME_YOUR_VARIABLE_AS_IT.assume_init()
+        }
+    };
+
+    TokenStream::from(block)
+}
+
+/// Like [`spawn_local!`], but respects the [`CoroutineLimits`](engine::scheduler::CoroutineLimits) of the worker.
+/// Returns `Err(`[`LimitExceeded`](engine::scheduler::LimitExceeded)`)`, if the coroutine was rejected.
+///
+/// Read [`Scheduler::try_spawn`](engine::scheduler::Scheduler::try_spawn) for more information.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{coro, try_spawn_local};
+/// use engine::net::{TcpListener, TcpStream};
+///
+/// #[coro]
+/// fn accept_loop(mut listener: TcpListener) {
+///     loop {
+///         let stream: TcpStream = (yield listener.accept()).expect("accept failed");
+///         if try_spawn_local!(handle_tcp_stream(stream)).is_err() {
+///             // The stream is dropped with the rejected coroutine, so the client is disconnected.
+///         }
+///     }
+/// }
+///
+/// #[coro]
+/// fn handle_tcp_stream(mut stream: TcpStream) {
+///     // process stream
+/// }
+/// ```
+#[proc_macro]
+pub fn try_spawn_local(input: TokenStream) -> TokenStream {
+    let input_expr = parse_macro_input!(input as Expr);
+
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(std::ptr::null_mut()));
+
+    let block = quote! {
+        engine::local_scheduler().try_spawn(#modified_expr)
     };
 
+    TokenStream::from(block)
+}
+
+/// Spawn a new coroutine in the local scheduler with the [`Priority`](engine::scheduler::Priority).
+///
+/// Ready coroutines of a higher priority run first, so latency-critical coroutines (like an accept loop)
+/// are not buried behind background ones. Read [`Priority`](engine::scheduler::Priority) for more information.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{coro, spawn_local, spawn_local_with_priority};
+/// use engine::net::{TcpListener, TcpStream};
+/// use engine::scheduler::Priority;
+///
+/// #[coro]
+/// fn accept_loop(mut listener: TcpListener) {
+///     loop {
+///         let stream: TcpStream = (yield listener.accept()).expect("accept failed");
+///         spawn_local!(handle_tcp_stream(stream));
+///     }
+/// }
+///
+/// #[coro]
+/// fn handle_tcp_stream(mut stream: TcpStream) {
+///     // process stream
+/// }
+///
+/// #[coro]
+/// fn run_server(listener: TcpListener) {
+///     spawn_local_with_priority!(Priority::High, accept_loop(listener));
+/// }
+/// ```
+#[proc_macro]
+pub fn spawn_local_with_priority(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input with Punctuated::<Expr, Token![,]>::parse_terminated);
+    if args.len() != 2 {
+        panic!("The macro expects a priority and a function or method call");
+    }
+    let mut args = args.into_iter();
+    let priority = args.next().unwrap();
+
+    let modified_expr = push_arg(args.next().unwrap(), syn::parse_quote!(std::ptr::null_mut()));
+
+    let block = quote! {
+        engine::local_scheduler().spawn_with_priority(#modified_expr, #priority)
+    };
+
+    TokenStream::from(block)
+}
+
+/// Spawn a new coroutine on the least loaded worker. It can be called from any thread, including threads outside the engine.
+///
+/// Arguments are moved to the worker, so they must be [`Send`].
+/// Returns the id of the core of the worker or an error, if no worker is running.
+/// Read [`spawn_global`](engine::scheduler::spawn_global) for more information.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{coro, spawn_global};
+///
+/// #[coro]
+/// fn handle_job(job: u64) {
+///     println!("job {}", job);
+/// }
+///
+/// fn producer() {
+///     for job in 0..100 {
+///         spawn_global!(handle_job(job)).expect("no worker is running");
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn spawn_global(input: TokenStream) -> TokenStream {
+    let input_expr = parse_macro_input!(input as Expr);
+
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(std::ptr::null_mut()));
+
+    let block = quote! {
+        engine::scheduler::spawn_global(move || #modified_expr)
+    };
+
+    TokenStream::from(block)
+}
+
+/// Spawn a new coroutine in the idle lane of the local scheduler.
+///
+/// The coroutine will run only when the worker has no other ready coroutines and all completions are handled.
+/// Read [`sched_idle`](engine::scheduler::Scheduler::sched_idle) for more information.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{coro, spawn_idle};
+///
+/// #[coro]
+/// fn shrink_pools() {
+///     // release unused memory
+/// }
+///
+/// spawn_idle!(shrink_pools());
+/// ```
+#[proc_macro]
+pub fn spawn_idle(input: TokenStream) -> TokenStream {
+    let input_expr = parse_macro_input!(input as Expr);
+
+    let modified_expr = push_arg(input_expr, syn::parse_quote!(std::ptr::null_mut()));
+
     let block = quote! {
-        engine::local_scheduler().sched(#modified_expr);
+        engine::local_scheduler().sched_idle(#modified_expr)
     };
 
     TokenStream::from(block)
@@ -632,12 +1194,14 @@ pub fn test_local(macro_attr: TokenStream, item: TokenStream) -> TokenStream {
         #fn_vis fn #fn_name #fn_generics (#fn_args) #fn_where_clause {
             #[#crate_name::coro(crate=#crate_name_str)]
             fn coroutine_creator_for_this_test_DO_NOT_CALL_YOUR_FUNCTIONS_AS_IT() {
+                // A failed assertion must fail the test, so panics are not isolated.
+                #crate_name::local_scheduler().set_isolate_panics(false);
                 #fn_block
                 yield #crate_name::coroutine::end();
             }
 
             let core_id = #crate_name::utils::get_core_ids().unwrap()[0];
-            #crate_name::run_on_core(coroutine_creator_for_this_test_DO_NOT_CALL_YOUR_FUNCTIONS_AS_IT, core_id);
+            #crate_name::run_on_core(coroutine_creator_for_this_test_DO_NOT_CALL_YOUR_FUNCTIONS_AS_IT, core_id).expect("failed to run the worker");
         }
     };
 
@@ -0,0 +1,205 @@
+//! This module contains [`CpuProfile`], the CPU profile of one worker, and [`profile_cpu`], the coroutine,
+//! that captures it into a flamegraph. It is available with the `profiling` feature.
+//!
+//! Profiles are sampled by [`pprof`] with `SIGPROF`, so hotspots inside the scheduler and selectors
+//! are captured with the same cost as hotspots inside coroutines. It is cheap enough to be enabled in production
+//! for a few seconds, for example, from a control coroutine, that listens on an admin port.
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use pprof::{ProfilerGuard, ProfilerGuardBuilder, Report};
+use crate::blocking::blocking;
+use crate::coro;
+use crate::sleep::sleep;
+
+/// The sampler of the process. `SIGPROF` is process-wide, so workers, that are profiled at the same time, share it.
+static SAMPLER: Mutex<Weak<ProfilerGuard<'static>>> = Mutex::new(Weak::new());
+
+/// Frames of the sampler itself and of the libraries, which unwinding is not safe in a signal handler.
+const BLOCKLIST: [&str; 4] = ["libc", "libgcc", "pthread", "vdso"];
+
+fn profiling_error(err: pprof::Error) -> Error {
+    Error::new(ErrorKind::Other, err)
+}
+
+/// The running CPU profile of the worker, that has started it. Stop it with [`CpuProfile::stop`].
+///
+/// Samples of all threads are collected, while at least one profile runs, but the report contains only
+/// the samples of the worker of the profile. If profiles of several workers overlap, the report of the later one
+/// can contain samples of its worker since the start of the earlier one, and the frequency of the earlier one is used.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::time::Duration;
+/// use engine::coro;
+/// use engine::profiling::CpuProfile;
+/// use engine::sleep::sleep;
+///
+/// #[coro]
+/// fn capture() {
+///     let profile = CpuProfile::start(99).unwrap();
+///     yield sleep(Duration::from_secs(10));
+///     let report = profile.stop().unwrap();
+///     report.write_folded(std::io::stdout()).unwrap();
+/// }
+/// ```
+pub struct CpuProfile {
+    sampler: Arc<ProfilerGuard<'static>>,
+    thread_id: u64
+}
+
+impl CpuProfile {
+    /// Starts profiling of the current worker with `frequency` samples per second.
+    pub fn start(frequency: i32) -> Result<Self, Error> {
+        let mut shared = SAMPLER.lock().unwrap();
+        let sampler = match shared.upgrade() {
+            Some(sampler) => sampler,
+            None => {
+                let sampler = ProfilerGuardBuilder::default()
+                    .frequency(frequency)
+                    .blocklist(&BLOCKLIST)
+                    .build()
+                    .map_err(profiling_error)?;
+                let sampler = Arc::new(sampler);
+                *shared = Arc::downgrade(&sampler);
+                sampler
+            }
+        };
+
+        Ok(Self { sampler, thread_id: unsafe { libc::pthread_self() } as u64 })
+    }
+
+    /// Stops the profile and returns the report of the worker. The sampler stops, when no profile runs.
+    pub fn stop(self) -> Result<CpuReport, Error> {
+        let mut report = self.sampler.report().build().map_err(profiling_error)?;
+        report.data.retain(|frames, _| frames.thread_id == self.thread_id);
+        Ok(CpuReport { report })
+    }
+}
+
+/// The CPU profile of one worker. It is returned by [`CpuProfile::stop`].
+pub struct CpuReport {
+    report: Report
+}
+
+impl CpuReport {
+    /// Returns the number of samples.
+    pub fn samples(&self) -> usize {
+        self.report.data.values().map(|&count| count as usize).sum()
+    }
+
+    /// Writes the report as an SVG flamegraph.
+    pub fn write_flamegraph<W: Write>(&self, writer: W) -> Result<(), Error> {
+        self.report.flamegraph(writer).map_err(profiling_error)
+    }
+
+    /// Writes the report as folded stacks (one `thread;frame;...;frame count` line per stack).
+    /// It is the input of `inferno-flamegraph`, `flamegraph.pl` and speedscope.
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        for (frames, count) in self.report.data.iter() {
+            let mut line = frames.thread_name_or_id();
+            for frame in frames.frames.iter().rev() {
+                for symbol in frame.iter().rev() {
+                    line.push(';');
+                    line.push_str(&symbol.to_string());
+                }
+            }
+            writeln!(writer, "{} {}", line, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Profiles the current worker for `dur` with `frequency` samples per second and writes the SVG flamegraph to `path`.
+///
+/// The file is written on the blocking pool, so the worker keeps serving while the flamegraph is rendered.
+/// If the worker was idle the whole time, there are no samples, and an error is returned.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::path::PathBuf;
+/// use std::time::Duration;
+/// use engine::{coro, wait};
+/// use engine::profiling::profile_cpu;
+///
+/// #[coro]
+/// fn on_profile_request() {
+///     let res: Result<(), Error> = wait!(profile_cpu(Duration::from_secs(30), 99, PathBuf::from("worker.svg")));
+/// }
+/// ```
+#[coro(crate="crate")]
+pub fn profile_cpu(dur: Duration, frequency: i32, path: PathBuf) -> Result<(), Error> {
+    let profile = match CpuProfile::start(frequency) {
+        Ok(profile) => profile,
+        Err(err) => return Err(err)
+    };
+    yield sleep(dur);
+    let report = match profile.stop() {
+        Ok(report) => report,
+        Err(err) => return Err(err)
+    };
+    // An idle worker doesn't use CPU, so it can have no samples, but a flamegraph can't be empty.
+    if report.samples() == 0 {
+        return Err(Error::new(ErrorKind::Other, "no samples were collected, the worker was idle"));
+    }
+
+    let res: Result<Result<(), Error>, Error> = yield blocking(move || {
+        let file = std::fs::File::create(path)?;
+        report.write_flamegraph(std::io::BufWriter::new(file))
+    });
+    return res.and_then(|res| res);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::time::{Duration, Instant};
+    use crate::{coro, test_local, wait};
+    use crate::coroutine::{yield_now, JoinHandle};
+    use crate::profiling::{profile_cpu, CpuProfile, CpuReport};
+
+    #[inline(never)]
+    fn spin(dur: Duration) -> u64 {
+        let start = Instant::now();
+        let mut x = 0u64;
+        while start.elapsed() < dur {
+            x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+        }
+        x
+    }
+
+    #[coro(crate="crate")]
+    fn busy(dur: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < dur {
+            spin(Duration::from_millis(1));
+            yield yield_now();
+        }
+    }
+
+    #[test_local(crate="crate")]
+    fn test_cpu_profile() {
+        let profile = CpuProfile::start(1000).unwrap();
+        spin(Duration::from_millis(200));
+        let report: CpuReport = profile.stop().unwrap();
+        assert!(report.samples() > 0);
+
+        let mut folded = Vec::new();
+        report.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert!(folded.lines().all(|line| line.rsplit_once(' ').unwrap().1.parse::<usize>().is_ok()));
+        assert!(folded.contains("spin"));
+
+        let path = std::env::temp_dir().join(format!("coroeng_test_profile_{}.svg", std::process::id()));
+        let mut worker = JoinHandle::spawn(|res| busy(Duration::from_millis(200), res));
+        let res: Result<(), Error> = wait!(profile_cpu(Duration::from_millis(100), 1000, path.clone()));
+        res.unwrap();
+        let _: () = yield worker.wait();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("<svg"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,15 +1,77 @@
-//! This module provides functions that run the [`Scheduler`] and [`uninit`] function.
-use std::ptr::null_mut;
+//! This module provides functions that run the [`Scheduler`], [`RunError`] and [`uninit`] function.
+use std::fmt::{Display, Formatter};
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::ops::CoroutineState;
 use crate::{cfg, local_scheduler};
+use crate::cfg::{SchedulerCfg, SelectorType};
 use crate::buf::BufPool;
 use crate::coroutine::{CoroutineImpl};
 use crate::local::id::{set_worker_id_and_core_id, set_worker_id_and_core_id_to_zero};
 use crate::scheduler::{Scheduler};
-use crate::utils::{core};
+use crate::utils::{core, get_worker_core_ids, init_working_dir};
+use crate::utils::core::worker_core_ids;
+
+/// The error, that stopped a worker. It is returned by [`run_on_core`] and [`run_on_all_cores`].
+///
+/// Errors of operations of coroutines are returned to the coroutines. This error means, that the worker itself can't go on,
+/// so all its coroutines are dropped.
+#[derive(Debug)]
+pub enum RunError {
+    /// The selector of the [`SelectorType`] can't be created. For example, io_uring is not supported by the kernel
+    /// or is disabled by seccomp. The application can [`set_selector`](cfg::set_selector) to the other type and run again
+    /// or use [`SelectorType::Auto`], that falls back to epoll by itself.
+    CreateSelector(SelectorType, Error),
+    /// The waker of the parking worker (an eventfd) can't be created, so other threads couldn't wake it up.
+    /// Read [`IdleStrategy`](crate::scheduler::IdleStrategy).
+    Waker(Error),
+    /// The nice value of the worker can't be set. For example, lowering it requires `CAP_SYS_NICE`,
+    /// and off Linux threads have no nice value. Read [`set_worker_nice`](cfg::set_worker_nice).
+    Nice(Error),
+    /// The [`Sandbox`](crate::sandbox::Sandbox) can't be installed.
+    #[cfg(target_os = "linux")]
+    Sandbox(Error),
+    /// The selector failed while polling: submission of io_uring or waiting of epoll returned an error.
+    Poll(Error)
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::CreateSelector(selector, err) => write!(f, "failed to create the {:?} selector: {}", selector, err),
+            RunError::Waker(err) => write!(f, "failed to create the waker of the worker: {}", err),
+            RunError::Nice(err) => write!(f, "failed to set the nice value of the worker: {}", err),
+            #[cfg(target_os = "linux")]
+            RunError::Sandbox(err) => write!(f, "failed to install the sandbox: {}", err),
+            RunError::Poll(err) => write!(f, "failed to poll the selector: {}", err)
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::CreateSelector(_, err) | RunError::Waker(err) | RunError::Nice(err) | RunError::Poll(err) => Some(err),
+            #[cfg(target_os = "linux")]
+            RunError::Sandbox(err) => Some(err)
+        }
+    }
+}
 
 /// Runs the [`Scheduler`] with the provided coroutine on the current core.
 /// This function will block the current thread.
 ///
+/// # Return
+///
+/// The worker stops, when the main coroutine and all coroutines, spawned with [`spawn_local`](crate::spawn_local)
+/// (or other spawn functions), are completed. Then the value, that the main coroutine returned, is returned.
+///
+/// Returns `None`, if the worker was stopped with [`end`](crate::coroutine::end) before the main coroutine was completed
+/// or if the main coroutine panicked. Read [`Scheduler`] for more information about panics of coroutines.
+///
+/// Returns [`RunError`], if the worker failed. Then the thread is ready to run a worker again,
+/// so the application can fall back to the other [`SelectorType`] or exit cleanly.
+///
 /// # Note
 /// This function runs only one [`Scheduler`] on the current core and all spawned coroutines will execute on that same core.
 /// If you want to use other cores, you can use the [`run_on_all_cores`] function,
@@ -33,22 +95,72 @@ use crate::utils::{core};
 /// }
 ///
 /// #[coro]
-/// fn start_app() {
+/// fn start_app() -> usize {
 ///     wait!(print_hello("start_app".to_string()));
+///     42
 /// }
 ///
 /// fn main() {
 ///     let core = get_core_ids().unwrap()[0];
-///     run_on_core(start_app, core);
+///     assert_eq!(run_on_core(start_app, core).unwrap(), Some(42));
 /// }
 /// ```
-pub fn run_on_core<T, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(creator: C, core: core::CoreId) {
+///
+/// Fall back to epoll, if io_uring is not available:
+///
+/// ```ignore
+/// use engine::run_on_core;
+/// use engine::cfg::{set_selector, SelectorType};
+///
+/// fn main() {
+///     let core = get_core_ids().unwrap()[0];
+///     set_selector(SelectorType::Auto);
+///     if let Err(err) = run_on_core(start_app, core) {
+///         eprintln!("{}", err);
+///         std::process::exit(1);
+///     }
+/// }
+/// ```
+pub fn run_on_core<T, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(creator: C, core: core::CoreId) -> Result<Option<T>, RunError> {
+    run_on_core_with_config(creator, core, cfg::config())
+}
+
+/// Like [`run_on_core`], but the worker uses the `config` instead of a copy of the global configuration.
+/// Read [`SchedulerCfg`] for more information.
+pub fn run_on_core_with_config<T, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(
+    creator: C,
+    core: core::CoreId,
+    config: SchedulerCfg
+) -> Result<Option<T>, RunError> {
+    cfg::enter_worker(config);
+    init_working_dir();
     core::set_for_current(core);
+    if let Some(nice) = cfg::config_worker_nice() {
+        core::set_nice_for_current(nice).map_err(RunError::Nice)?;
+    }
     set_worker_id_and_core_id(core.id + 1, core.id);
     BufPool::init_in_local_thread(cfg::config_buf_len());
     Scheduler::init();
     let scheduler = local_scheduler();
-    scheduler.run(creator(null_mut()));
+    let mut res = MaybeUninit::<T>::uninit();
+    let mut is_completed = false;
+    scheduler.run(run_main(creator(res.as_mut_ptr()), &mut is_completed))?;
+    if is_completed {
+        Ok(Some(unsafe { res.assume_init() }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Runs the main coroutine and marks it as completed, so [`run_on_core`] knows, that the result is written.
+fn run_main(mut main: CoroutineImpl, is_completed: *mut bool) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        while let CoroutineState::Yielded(status) = main.as_mut().resume(()) {
+            yield status;
+        }
+
+        unsafe { *is_completed = true };
+    })
 }
 
 /// Uninitializes the [`Scheduler`], [`BufPool`], and set the worker id and core id to zero.
@@ -64,11 +176,24 @@ pub(crate) fn uninit() {
     set_worker_id_and_core_id_to_zero();
     BufPool::uninit_in_local_thread();
     Scheduler::uninit();
+    cfg::leave_worker();
 }
 
 /// Takes a function that returns a coroutine and call this function on all cores with [`run_on_core`].
 /// This function will block the current thread.
 ///
+/// The cores are returned by [`get_worker_core_ids`], so [`set_skip_smt_siblings`](cfg::set_skip_smt_siblings)
+/// and [`set_reserved_cores`](cfg::set_reserved_cores) are respected. Use [`run_on_cores`] to choose the cores yourself.
+///
+/// # Return
+///
+/// Returns the results of [`run_on_core`] in the order of cores, when all workers are stopped.
+/// A [`RunError`] of one worker doesn't stop others.
+///
+/// Worker threads are named `coroeng-worker-{n}`, so profilers and `top -H` show them.
+/// The worker of the first core runs on the current thread, that keeps its name and stack.
+/// Read [`set_worker_stack_size`](cfg::set_worker_stack_size) and [`set_worker_nice`](cfg::set_worker_nice) for thread attributes.
+///
 /// # Note
 ///
 /// For optimal performance, this coroutine should avoid accessing the shared state as much as possible,
@@ -94,17 +219,152 @@ pub(crate) fn uninit() {
 ///     run_on_all_cores(greetings_from_different_cores);
 /// }
 /// ```
-pub fn run_on_all_cores<T, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(creator: C) {
-    let cores = core::get_core_ids().unwrap();
+pub fn run_on_all_cores<T: Send + 'static, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(creator: C) -> Vec<Result<Option<T>, RunError>> {
+    run_on_cores(&get_worker_core_ids().unwrap(), creator)
+}
+
+/// Like [`run_on_all_cores`], but all workers use the `config` instead of a copy of the global configuration.
+/// The cores are chosen by the `config` too. Read [`SchedulerCfg`] for more information.
+pub fn run_on_all_cores_with_config<T: Send + 'static, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(
+    creator: C,
+    config: SchedulerCfg
+) -> Vec<Result<Option<T>, RunError>> {
+    let cores = worker_core_ids(config.skip_smt_siblings, config.reserved_cores).unwrap();
+    run_on_cores_with_config(&cores, creator, config)
+}
+
+/// Like [`run_on_all_cores`], but runs workers only on the `cores`, so other cores can be left to threads outside the engine.
+/// This function will block the current thread.
+///
+/// # Panics
+///
+/// Panics if `cores` is empty.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::run_on_cores;
+/// use engine::utils::get_core_ids;
+///
+/// fn main() {
+///     // The first two cores are left to the logging and the metrics threads.
+///     let cores = get_core_ids().unwrap();
+///     run_on_cores(&cores[2..], start_server);
+/// }
+/// ```
+pub fn run_on_cores<T: Send + 'static, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(cores: &[core::CoreId], creator: C) -> Vec<Result<Option<T>, RunError>> {
+    run_on_cores_with_config(cores, creator, cfg::config())
+}
+
+/// Like [`run_on_cores`], but all workers use the `config` instead of a copy of the global configuration.
+/// Read [`SchedulerCfg`] for more information.
+///
+/// # Panics
+///
+/// Panics if `cores` is empty.
+pub fn run_on_cores_with_config<T: Send + 'static, C: 'static + Send + Clone + Fn(*mut T) -> CoroutineImpl>(
+    cores: &[core::CoreId],
+    creator: C,
+    config: SchedulerCfg
+) -> Vec<Result<Option<T>, RunError>> {
+    assert!(!cores.is_empty(), "no cores to run workers on");
+    init_working_dir();
+    let mut workers = Vec::with_capacity(cores.len() - 1);
     for i in 1..cores.len() {
         let core = cores[i];
         let creator = creator.clone();
-        std::thread::Builder::new()
-            .name(format!("worker on core: {}", i))
-            .spawn(move || {
-                run_on_core(creator, core);
-            }).expect("failed to create worker thread");
+        let config = config.clone();
+        let mut builder = std::thread::Builder::new().name(format!("coroeng-worker-{}", i));
+        if let Some(stack_size) = config.worker_stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        workers.push(builder
+            .spawn(move || run_on_core_with_config(creator, core, config))
+            .expect("failed to create worker thread"));
+    }
+
+    let mut results = Vec::with_capacity(cores.len());
+    results.push(run_on_core_with_config(creator, cores[0], config));
+    for worker in workers {
+        results.push(worker.join().expect("worker thread panicked"));
     }
 
-    run_on_core(creator, cores[0]);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::coro;
+    use crate::coroutine::end;
+    use crate::buf::buffer;
+    use crate::cfg::{config, config_buf_len};
+    use crate::run::{run_on_core, run_on_cores, run_on_cores_with_config};
+    use crate::scheduler::local_scheduler;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+
+    #[coro(crate="crate")]
+    fn child(completed: Arc<AtomicUsize>) {
+        yield sleep(Duration::from_millis(5));
+        completed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[coro(crate="crate")]
+    fn parent(completed: Arc<AtomicUsize>) -> usize {
+        for _ in 0..3 {
+            local_scheduler().spawn(child(completed.clone(), null_mut()));
+        }
+        42
+    }
+
+    #[test]
+    fn test_run_on_core_returns_value() {
+        let core = get_core_ids().unwrap()[0];
+        let completed = Arc::new(AtomicUsize::new(0));
+        let completed_ = completed.clone();
+        assert_eq!(run_on_core(move |res| parent(completed_.clone(), res), core).unwrap(), Some(42));
+        assert_eq!(completed.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_run_on_cores() {
+        let cores = get_core_ids().unwrap();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let completed_ = completed.clone();
+        let results = run_on_cores(&cores[..1], move |res| parent(completed_.clone(), res));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap(), &Some(42));
+        assert_eq!(completed.load(Ordering::SeqCst), 3);
+    }
+
+    #[coro(crate="crate")]
+    fn buf_len() -> (usize, usize) {
+        (config_buf_len(), buffer().cap())
+    }
+
+    #[test]
+    fn test_run_with_config() {
+        let cores = get_core_ids().unwrap();
+        let global = config_buf_len();
+        let results = run_on_cores_with_config(&cores[..1], buf_len, config().with_buf_len(global * 2));
+        assert_eq!(results[0].as_ref().unwrap(), &Some((global * 2, global * 2)));
+        // The global configuration is not changed, and the thread doesn't keep the configuration of the worker.
+        assert_eq!(config_buf_len(), global);
+    }
+
+    #[coro(crate="crate")]
+    fn ended() -> usize {
+        yield end();
+        1
+    }
+
+    #[test]
+    fn test_run_on_core_ended() {
+        let core = get_core_ids().unwrap()[0];
+        assert_eq!(run_on_core(ended, core).unwrap(), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+//! This module contains the landlock part of the [`Sandbox`](crate::sandbox::Sandbox).
+//!
+//! It restricts the filesystem access of the current thread to the listed paths.
+
+use std::ffi::CString;
+use std::io::{Error, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr::null;
+use libc::{c_int, c_long};
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: c_int = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+/// All rights of the first ABI version (from `EXECUTE` to `MAKE_SYM`).
+const ACCESS_FS_ABI_1: u64 = (1 << 13) - 1;
+const ACCESS_FS_REFER: u64 = 1 << 13;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+/// Rights that can be granted on a file. Other rights can be granted only on directories.
+const ACCESS_FILE: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: c_int
+}
+
+/// The access to a path, that is granted by the [`Sandbox`](crate::sandbox::Sandbox).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FsAccess {
+    /// Read files, list directories and execute.
+    ReadOnly,
+    /// Any access.
+    ReadWrite
+}
+
+/// Returns the rights, that the running kernel can handle.
+fn handled_access() -> Result<u64, Error> {
+    let abi = unsafe {
+        libc::syscall(libc::SYS_landlock_create_ruleset, null::<RulesetAttr>(), 0, LANDLOCK_CREATE_RULESET_VERSION)
+    };
+    if abi < 0 {
+        return Err(Error::new(ErrorKind::Unsupported, format!("landlock is not supported: {}", Error::last_os_error())));
+    }
+
+    let mut access = ACCESS_FS_ABI_1;
+    if abi >= 2 {
+        access |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        access |= ACCESS_FS_TRUNCATE;
+    }
+
+    Ok(access)
+}
+
+fn add_rule(ruleset_fd: c_long, path: &Path, access: FsAccess, handled: u64) -> Result<(), Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let mut allowed_access = match access {
+        FsAccess::ReadOnly => ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR,
+        FsAccess::ReadWrite => handled
+    } & handled;
+    if !path.is_dir() {
+        allowed_access &= ACCESS_FILE;
+    }
+
+    let attr = PathBeneathAttr { allowed_access, parent_fd: fd };
+    let res = unsafe {
+        libc::syscall(libc::SYS_landlock_add_rule, ruleset_fd, LANDLOCK_RULE_PATH_BENEATH, &attr as *const PathBeneathAttr, 0)
+    };
+    let err = Error::last_os_error();
+    unsafe { libc::close(fd) };
+
+    if res < 0 {
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Restricts the filesystem access of the current thread to `rules`. All other paths become inaccessible.
+///
+/// # Note
+///
+/// It requires `PR_SET_NO_NEW_PRIVS`, so call it only after the thread has set it,
+/// or before [`install_filter`](crate::sandbox::seccomp::install_filter) which sets it.
+pub(crate) fn restrict_self(rules: &[(PathBuf, FsAccess)]) -> Result<(), Error> {
+    let handled = handled_access()?;
+    let attr = RulesetAttr { handled_access_fs: handled };
+    let ruleset_fd = unsafe {
+        libc::syscall(libc::SYS_landlock_create_ruleset, &attr as *const RulesetAttr, size_of::<RulesetAttr>(), 0)
+    };
+    if ruleset_fd < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let res = (|| {
+        for (path, access) in rules {
+            add_rule(ruleset_fd, path, *access, handled)?;
+        }
+
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) < 0 {
+                return Err(Error::last_os_error());
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset_fd, 0) < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    })();
+
+    unsafe { libc::close(ruleset_fd as c_int) };
+    res
+}
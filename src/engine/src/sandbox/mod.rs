@@ -0,0 +1,218 @@
+//! This module provides [`Sandbox`], an opt-in way to restrict what a worker thread can do
+//! after its [`Selector`](crate::io::selector::Selector) has been set up.
+//!
+//! # [`seccomp`]
+//! This module builds and installs a seccomp filter based on the syscalls, that the selector needs.
+//!
+//! # [`landlock`]
+//! This module restricts the filesystem access with landlock rules.
+
+pub mod seccomp;
+pub mod landlock;
+
+use std::io::Error;
+use std::path::PathBuf;
+use libc::c_long;
+use crate::cfg::SelectorType;
+
+pub use seccomp::{SeccompAction, required_syscalls};
+pub use landlock::FsAccess;
+
+/// A restrictive sandbox for worker threads. It reduces the blast radius for network-facing services.
+///
+/// Set it with [`set_sandbox`](crate::cfg::set_sandbox) before [`run_on_core`](crate::run::run_on_core)
+/// or [`run_on_all_cores`](crate::run::run_on_all_cores).
+/// Every worker installs it for itself right after its [`Selector`](crate::io::selector::Selector) has been created.
+///
+/// # Seccomp
+///
+/// Only syscalls from [`required_syscalls`] and the syscalls added with [`Sandbox::allow_syscall`] are allowed.
+/// The others lead to [`SeccompAction`] (by default, [`SeccompAction::KillProcess`]).
+/// Use [`SeccompAction::Log`] to find out which syscalls your application needs.
+///
+/// # Landlock
+///
+/// If at least one path is added with [`Sandbox::allow_path`] or [`Sandbox::restrict_fs`] is called,
+/// the worker can access only the added paths.
+///
+/// # Be careful
+///
+/// The sandbox cannot be uninstalled. Each coroutine on the worker (including the coroutines that use blocking std functions)
+/// is restricted after the installation. The threads of the blocking pool are started after the installation,
+/// so they are restricted too, and [`Sandbox::allow_syscall`] is needed for syscalls of [`blocking`](crate::blocking::blocking) closures.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::cfg::set_sandbox;
+/// use engine::sandbox::{FsAccess, Sandbox, SeccompAction};
+///
+/// set_sandbox(
+///     Sandbox::new()
+///         .on_violation(SeccompAction::Errno(libc::EPERM))
+///         .allow_path("/var/lib/app", FsAccess::ReadWrite)
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sandbox {
+    action: SeccompAction,
+    extra_syscalls: Vec<c_long>,
+    fs_rules: Vec<(PathBuf, FsAccess)>,
+    restrict_fs: bool
+}
+
+impl Sandbox {
+    /// Creates a new [`Sandbox`] that allows only the syscalls, that the selector needs, and does not restrict the filesystem.
+    pub fn new() -> Self {
+        Self {
+            action: SeccompAction::KillProcess,
+            extra_syscalls: Vec::new(),
+            fs_rules: Vec::new(),
+            restrict_fs: false
+        }
+    }
+
+    /// Sets the action for syscalls, that are not allowed.
+    pub fn on_violation(mut self, action: SeccompAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Allows one more syscall (`libc::SYS_*`).
+    pub fn allow_syscall(mut self, nr: c_long) -> Self {
+        self.extra_syscalls.push(nr);
+        self
+    }
+
+    /// Allows the access to the path (and everything beneath it) and enables the filesystem restriction.
+    pub fn allow_path(mut self, path: impl Into<PathBuf>, access: FsAccess) -> Self {
+        self.fs_rules.push((path.into(), access));
+        self.restrict_fs = true;
+        self
+    }
+
+    /// Enables the filesystem restriction. Without [`Sandbox::allow_path`] no path is accessible.
+    pub fn restrict_fs(mut self) -> Self {
+        self.restrict_fs = true;
+        self
+    }
+
+    /// Returns all syscalls, that will be allowed for the worker with the provided [`SelectorType`].
+    pub fn allowed_syscalls(&self, selector: SelectorType) -> Vec<c_long> {
+        let mut syscalls = required_syscalls(selector);
+        syscalls.extend_from_slice(&self.extra_syscalls);
+        syscalls
+    }
+
+    /// Installs the sandbox for the current thread.
+    ///
+    /// It is called by the [`Scheduler`](crate::scheduler::Scheduler), but can be used for threads,
+    /// that are not workers.
+    pub fn install(&self, selector: SelectorType) -> Result<(), Error> {
+        // landlock first, because the seccomp filter does not allow landlock syscalls
+        if self.restrict_fs {
+            landlock::restrict_self(&self.fs_rules)?;
+        }
+
+        seccomp::install_filter(&seccomp::build_filter(&self.allowed_syscalls(selector), self.action))
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use crate::{coro, wait};
+    use crate::blocking::blocking;
+    use crate::buf::{buffer, Buffer};
+    use crate::cfg::config;
+    use crate::run::run_on_core_with_config;
+    use crate::scheduler::IdleStrategy;
+    use crate::sleep::sleep;
+    use crate::utils::get_core_ids;
+
+    #[test]
+    fn test_filter_layout() {
+        let syscalls = [libc::SYS_read, libc::SYS_write];
+        let filter = seccomp::build_filter(&syscalls, SeccompAction::Errno(libc::EPERM));
+        // arch check (3) + load nr (1) + 2 for each syscall + default action (1)
+        assert_eq!(filter.len(), 3 + 1 + syscalls.len() * 2 + 1);
+        assert_eq!(filter.last().unwrap().k, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32);
+    }
+
+    #[test]
+    fn test_auto_syscalls() {
+        let auto = seccomp::required_syscalls(SelectorType::Auto);
+        for selector in [SelectorType::Ring, SelectorType::Poller] {
+            assert!(seccomp::required_syscalls(selector).iter().all(|syscall| auto.contains(syscall)));
+        }
+    }
+
+    #[test]
+    fn test_install_denies_other_syscalls() {
+        std::thread::spawn(|| {
+            Sandbox::new()
+                .on_violation(SeccompAction::Errno(libc::EPERM))
+                .install(SelectorType::Ring)
+                .expect("failed to install the sandbox");
+
+            let res = unsafe { libc::syscall(libc::SYS_getppid) };
+            assert_eq!(res, -1);
+            assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+            assert!(unsafe { libc::syscall(libc::SYS_gettid) } > 0);
+        }).join().unwrap();
+    }
+
+    #[coro(crate="crate")]
+    fn sleep_and_return() -> u32 {
+        yield sleep(Duration::from_millis(1));
+        return 1;
+    }
+
+    #[test]
+    fn test_parking_worker() {
+        // The parking worker creates its waker (an eventfd), before the sandbox is installed.
+        std::thread::spawn(|| {
+            let sandbox = Sandbox::new().on_violation(SeccompAction::Errno(libc::EPERM));
+            let cfg = config().with_selector(SelectorType::Ring).with_idle_strategy(IdleStrategy::adaptive()).with_sandbox(sandbox);
+            let res = run_on_core_with_config(sleep_and_return, get_core_ids().unwrap()[0], cfg);
+            assert_eq!(res.unwrap(), Some(1));
+        }).join().unwrap();
+    }
+
+    #[coro(crate="crate")]
+    fn write_and_read(path: PathBuf) -> Vec<u8> {
+        let res: Result<u64, Error> = yield blocking(|| 42);
+        assert_eq!(res.unwrap(), 42);
+
+        let mut buf = buffer();
+        buf.append(b"sandboxed");
+        let res: Result<(), Error> = wait!(crate::fs::write(path.clone(), buf));
+        res.unwrap();
+        let res: Result<Buffer, Error> = wait!(crate::fs::read(path));
+        return res.unwrap().as_ref().to_vec();
+    }
+
+    #[test]
+    fn test_blocking_pool() {
+        // The threads of the blocking pool are started after the sandbox is installed, so they inherit the filter.
+        // epoll runs all file operations on the pool.
+        for selector in [SelectorType::Poller, SelectorType::Ring] {
+            let path = std::env::temp_dir().join(format!("coroeng_test_sandbox_{:?}_{}", selector, std::process::id()));
+            let worker_path = path.clone();
+            std::thread::spawn(move || {
+                let sandbox = Sandbox::new().on_violation(SeccompAction::Errno(libc::EPERM));
+                let cfg = config().with_selector(selector).with_sandbox(sandbox);
+                let res = run_on_core_with_config(move |res| write_and_read(worker_path.clone(), res), get_core_ids().unwrap()[0], cfg);
+                assert_eq!(res.unwrap(), Some(b"sandboxed".to_vec()));
+            }).join().unwrap();
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! This module contains the seccomp part of the [`Sandbox`](crate::sandbox::Sandbox).
+//!
+//! It builds a classic BPF program that allows only the listed syscalls and installs it for the current thread.
+
+use std::io::Error;
+use libc::{c_long, c_uint, sock_filter, sock_fprog};
+use crate::cfg::SelectorType;
+
+/// `AUDIT_ARCH_*` value of the current architecture. The filter rejects syscalls from other ABIs.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// Offset of `seccomp_data.nr`.
+const SYSCALL_NR_OFFSET: u32 = 0;
+/// Offset of `seccomp_data.arch`.
+const ARCH_OFFSET: u32 = 4;
+
+/// What the kernel does when a worker calls a syscall that is not allowed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SeccompAction {
+    /// Kills the whole process. It is the default action.
+    KillProcess,
+    /// Kills only the thread that made the syscall.
+    KillThread,
+    /// Fails the syscall with the provided errno.
+    Errno(i32),
+    /// Allows the syscall, but logs it to the audit log. Use it to find out which syscalls your application needs.
+    Log
+}
+
+impl SeccompAction {
+    fn as_ret(&self) -> c_uint {
+        match self {
+            SeccompAction::KillProcess => libc::SECCOMP_RET_KILL_PROCESS,
+            SeccompAction::KillThread => libc::SECCOMP_RET_KILL_THREAD,
+            SeccompAction::Errno(errno) => libc::SECCOMP_RET_ERRNO | (*errno as c_uint & libc::SECCOMP_RET_DATA),
+            SeccompAction::Log => libc::SECCOMP_RET_LOG
+        }
+    }
+}
+
+/// Syscalls that every worker needs regardless of the selector: memory allocation, synchronization,
+/// time, signals, thread start and exit (for the threads of the blocking pool), the socket setup that
+/// [`Scheduler`](crate::scheduler::Scheduler) makes itself (for example, for [`TcpListener::new`](crate::net::TcpListener::new)),
+/// wakers, [`WatchStream`](crate::fs::watch::WatchStream) and [`Tty`](crate::io::Tty).
+const COMMON_SYSCALLS: &[c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_writev,
+    libc::SYS_close,
+    libc::SYS_fcntl,
+    libc::SYS_futex,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    libc::SYS_brk,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigaction,
+    libc::SYS_sigaltstack,
+    libc::SYS_sched_yield,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_getrandom,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_nanosleep,
+    libc::SYS_gettid,
+    libc::SYS_getpid,
+    libc::SYS_tgkill,
+    libc::SYS_restart_syscall,
+    libc::SYS_rseq,
+    libc::SYS_clone,
+    libc::SYS_clone3,
+    libc::SYS_set_robust_list,
+    libc::SYS_prctl,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_socket,
+    libc::SYS_setsockopt,
+    libc::SYS_getsockopt,
+    libc::SYS_bind,
+    libc::SYS_listen,
+    libc::SYS_connect,
+    libc::SYS_getsockname,
+    libc::SYS_getpeername,
+    libc::SYS_shutdown,
+    libc::SYS_eventfd2,
+    libc::SYS_inotify_init1,
+    libc::SYS_inotify_add_watch,
+    libc::SYS_inotify_rm_watch,
+    libc::SYS_ioctl,
+];
+
+/// Syscalls of file operations. The threads of the blocking pool make them for the selectors, that don't support
+/// the operations (read [`Selector::file_op_support`](crate::io::selector::Selector::file_op_support)).
+/// The threads are started lazily, after the filter is installed, so they inherit it.
+const FILE_SYSCALLS: &[c_long] = &[
+    libc::SYS_openat,
+    libc::SYS_pread64,
+    libc::SYS_pwrite64,
+    libc::SYS_lseek,
+    libc::SYS_fsync,
+    libc::SYS_fdatasync,
+    libc::SYS_flock,
+    libc::SYS_fstat,
+    libc::SYS_newfstatat,
+    libc::SYS_statx,
+    libc::SYS_getdents64,
+    libc::SYS_copy_file_range,
+    libc::SYS_sendfile,
+    libc::SYS_fadvise64,
+    libc::SYS_fchmod,
+    libc::SYS_fchmodat,
+    libc::SYS_mkdirat,
+    libc::SYS_unlinkat,
+    libc::SYS_renameat,
+    libc::SYS_renameat2,
+    libc::SYS_symlinkat,
+    libc::SYS_linkat,
+    libc::SYS_readlinkat,
+    // libc uses the old syscalls, where they exist.
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_mkdir,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_rmdir,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_unlink,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_rename,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_symlink,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_link,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_readlink,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_chmod,
+];
+
+/// Syscalls that [`IoUringSelector`](crate::io::sys::unix::IoUringSelector) makes after the ring is created.
+const RING_SYSCALLS: &[c_long] = &[
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+];
+
+/// Syscalls that [`EpolledSelector`](crate::io::sys::unix::EpolledSelector) makes after the epoll is created.
+const POLLER_SYSCALLS: &[c_long] = &[
+    libc::SYS_epoll_ctl,
+    // aarch64 has only epoll_pwait, and libc calls it for epoll_wait.
+    libc::SYS_epoll_pwait,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_epoll_wait,
+    libc::SYS_accept4,
+    libc::SYS_recvfrom,
+    libc::SYS_sendto,
+];
+
+/// Returns the syscalls that a worker with the provided [`SelectorType`] needs after the selector has been created.
+pub fn required_syscalls(selector: SelectorType) -> Vec<c_long> {
+    let mut syscalls = COMMON_SYSCALLS.to_vec();
+    syscalls.extend_from_slice(FILE_SYSCALLS);
+    match selector {
+        SelectorType::Ring => syscalls.extend_from_slice(RING_SYSCALLS),
+        SelectorType::Poller => syscalls.extend_from_slice(POLLER_SYSCALLS),
+        SelectorType::Auto => {
+            syscalls.extend_from_slice(RING_SYSCALLS);
+            syscalls.extend_from_slice(POLLER_SYSCALLS);
+        }
+    }
+
+    syscalls
+}
+
+#[inline(always)]
+fn stmt(code: u32, k: u32) -> sock_filter {
+    sock_filter { code: code as u16, jt: 0, jf: 0, k }
+}
+
+#[inline(always)]
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter { code: code as u16, jt, jf, k }
+}
+
+/// Builds a BPF program, that allows only `syscalls` and applies `action` to others.
+pub(crate) fn build_filter(syscalls: &[c_long], action: SeccompAction) -> Vec<sock_filter> {
+    let mut filter = Vec::with_capacity(syscalls.len() * 2 + 5);
+
+    filter.push(stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET));
+    filter.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, AUDIT_ARCH, 1, 0));
+    filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS));
+
+    filter.push(stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, SYSCALL_NR_OFFSET));
+    for nr in syscalls {
+        filter.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, *nr as u32, 0, 1));
+        filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+    }
+    filter.push(stmt(libc::BPF_RET | libc::BPF_K, action.as_ret()));
+
+    filter
+}
+
+/// Installs the filter for the current thread. Other threads are not affected.
+///
+/// # Note
+///
+/// It sets `PR_SET_NO_NEW_PRIVS` for the current thread, because the kernel requires it for unprivileged filters.
+pub(crate) fn install_filter(filter: &[sock_filter]) -> Result<(), Error> {
+    let prog = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_ptr() as *mut sock_filter
+    };
+
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        if libc::syscall(libc::SYS_seccomp, libc::SECCOMP_SET_MODE_FILTER, 0, &prog as *const sock_fprog) < 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
@@ -1,38 +1,71 @@
-// TODO r
+//! This module contains [`BlockingPool`].
+use std::sync::Arc;
 use std::thread;
+use std::thread::Thread;
 use crate::coroutine::CoroutineImpl;
 use crate::io::BlockingState;
 use crate::scheduler::blocking_pool::worker::Worker;
+use crate::scheduler::Injector;
 
+/// A pool of helper threads for operations, that can only be done with a blocking syscall (like `flock`),
+/// and for closures of [`blocking`](crate::blocking::blocking).
+///
+/// The worker of the scheduler puts a [`BlockingState`] to the pool and handles other coroutines.
+/// When the operation is done, the coroutine is returned by [`BlockingPool::get_ready`].
+///
+/// Threads share one queue of states. A new thread is started, when there are more queued states than parked threads,
+/// until there are `max_threads` threads. So, workers without blocking operations don't have threads.
 pub(crate) struct BlockingPool {
-    // TODO try many workers
-    worker: Worker
+    worker: Arc<Worker>,
+    threads: Vec<Thread>,
+    max_threads: usize
 }
 
 impl BlockingPool {
-    pub(crate) fn new() -> Self {
-        let worker = Worker::new();
-        let pool = Self {
-            worker
-        };
-
-        pool
+    /// Creates a new pool with at most `max_threads` threads (at least one) for the scheduler with the `injector`.
+    pub(crate) fn new(max_threads: usize, injector: Injector) -> Self {
+        Self {
+            worker: Arc::new(Worker::new(injector)),
+            threads: Vec::new(),
+            max_threads: max_threads.max(1)
+        }
     }
 
-    pub(crate) fn run(&self) {
-        let worker_ref = unsafe { std::mem::transmute::<&Worker, &'static Worker>(&self.worker) };
-        thread::spawn(move || {
-            worker_ref.run();
-        });
+    /// Starts a new thread.
+    fn spawn(&mut self) {
+        let worker = self.worker.clone();
+        let handle = thread::Builder::new()
+            .name("coroeng-blocking".to_string())
+            .spawn(move || worker.run())
+            .expect("failed to spawn a blocking pool thread");
+        self.threads.push(handle.thread().clone());
     }
 
     #[inline(always)]
     pub(crate) fn get_ready(&self, ready: &mut Vec<CoroutineImpl>) {
-        self.worker.get_ready(ready);
+        if !self.threads.is_empty() {
+            self.worker.get_ready(ready);
+        }
     }
 
     #[inline(always)]
-    pub(crate) fn put_state(&self, state: BlockingState) {
+    pub(crate) fn put_state(&mut self, state: BlockingState) {
         self.worker.put_state(state);
+        if self.worker.queued() > self.worker.idle() && self.threads.len() < self.max_threads {
+            self.spawn();
+        }
+        // Any parked thread can take the state, so all of them are woken up.
+        for thread in &self.threads {
+            thread.unpark();
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Drop for BlockingPool {
+    fn drop(&mut self) {
+        self.worker.close();
+        for thread in &self.threads {
+            thread.unpark();
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! This module contains file operations, that are run on the [`BlockingPool`](super::BlockingPool),
+//! because the selector doesn't support them. Read [`Selector::file_op_support`](crate::io::Selector::file_op_support).
+//!
+//! An operation is split into two parts:
+//!
+//! * [`FileOp::run`] makes the syscalls on a thread of the pool and writes the result. It doesn't drop anything,
+//!   because [`Buffer`](crate::buf::Buffer)s return to the local [`BufPool`](crate::buf::BufPool) of the thread,
+//!   that drops them, and the threads of the pool have no pool.
+//!
+//! * [`finish_file_op`] runs on the worker, when the pool has done the syscalls. It leaves the empty state in the pointer
+//!   (or frees it), drops the rest of the state and returns the coroutine of the operation.
+use std::io::{Error, ErrorKind};
+use std::mem;
+use std::os::fd::RawFd;
+use crate::buf::Buffer;
+use crate::coroutine::CoroutineImpl;
+use crate::fs::File;
+use crate::io::PollState;
+use crate::io::sys::unix::fs::{self, advance_offset, read_at, read_link, write_at};
+use crate::utils::Ptr;
+use crate::{write_err, write_ok};
+
+/// The length of the chunk, that [`copy_chunk`] reads and writes.
+#[cfg(not(target_os = "linux"))]
+const COPY_CHUNK_LEN: usize = 64 * 1024;
+
+/// Syncs the file. macOS has no `fdatasync`, so it always syncs the metadata too.
+fn sync(fd: RawFd, data_only: bool) -> i32 {
+    #[cfg(target_vendor = "apple")]
+    {
+        let _ = data_only;
+        unsafe { libc::fsync(fd) }
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    {
+        if data_only { unsafe { libc::fdatasync(fd) } } else { unsafe { libc::fsync(fd) } }
+    }
+}
+
+/// Writes the whole buffer at `offset`. Moves the cursor, if it is not null.
+unsafe fn write_all_at(fd: RawFd, buf: &[u8], offset: u64, cursor: *mut u64) -> Result<(), Error> {
+    unsafe { write_all_with(buf, offset, cursor, |buf, offset| write_at(fd, buf.as_ptr(), buf.len(), offset)) }
+}
+
+/// Writes the whole buffer at `offset` with `write`, that makes one syscall and returns its result.
+/// Moves the cursor, if it is not null.
+unsafe fn write_all_with<W: FnMut(&[u8], u64) -> isize>(mut buf: &[u8], mut offset: u64, cursor: *mut u64, mut write: W) -> Result<(), Error> {
+    while !buf.is_empty() {
+        let res = write(buf, offset);
+        if res < 0 {
+            return Err(Error::last_os_error());
+        }
+        if res == 0 {
+            // The file can't take more bytes (like a full device), so retrying would never end.
+            return Err(Error::new(ErrorKind::WriteZero, "failed to write the whole buffer"));
+        }
+
+        let written = res as usize;
+        if !cursor.is_null() {
+            unsafe { *cursor += written as u64 };
+        }
+        offset = advance_offset(offset, written as u64);
+        buf = &buf[written..];
+    }
+
+    Ok(())
+}
+
+/// Copies one chunk from `src` to `dst` in the kernel with [`copy_chunk`](fs::copy_chunk), so the `buf` is not used.
+///
+/// Returns the number of copied bytes (0 means the end of `src`).
+#[cfg(target_os = "linux")]
+fn copy_chunk(src: RawFd, dst: RawFd, _buf: &mut [u8]) -> Result<usize, Error> {
+    let res = fs::copy_chunk(src, dst);
+    if res < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(res as usize)
+}
+
+/// Copies up to [`COPY_CHUNK_LEN`] bytes from `src` to `dst`, starting from their current positions.
+/// Unlike the Linux version, it copies through the memory, because `copy_file_range` and `sendfile`
+/// between files are Linux-only.
+///
+/// Returns the number of copied bytes (0 means the end of `src`).
+#[cfg(not(target_os = "linux"))]
+fn copy_chunk(src: RawFd, dst: RawFd, buf: &mut [u8]) -> Result<usize, Error> {
+    let res = unsafe { libc::read(src, buf.as_mut_ptr() as _, buf.len()) };
+    if res < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let read = res as usize;
+    unsafe { write_all_at(dst, &buf[..read], fs::CURRENT_POSITION, std::ptr::null_mut()) }?;
+    Ok(read)
+}
+
+/// Converts the result of a libc syscall, that returns -1 with errno set, to [`Result`].
+#[inline(always)]
+fn check(ret: i32) -> Result<(), Error> {
+    if ret < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// The pointer to the [`PollState`] of a file operation, that is sent to the blocking pool.
+///
+/// # Safety
+///
+/// The state is not touched by the worker, until the operation is finished, and [`FileOp::run`] drops nothing.
+pub(crate) struct FileOp(Ptr<PollState>);
+
+unsafe impl Send for FileOp {}
+
+impl FileOp {
+    pub(crate) fn new(state_ptr: Ptr<PollState>) -> Self {
+        Self(state_ptr)
+    }
+
+    /// Makes the syscalls of the operation and writes its result. It is called by a thread of the blocking pool.
+    pub(crate) fn run(self) {
+        match unsafe { self.0.as_mut() } {
+            PollState::OpenFile(state) => {
+                let fd = unsafe { libc::open(state.path.as_ptr(), state.flags, state.mode as libc::c_uint) };
+                if fd < 0 {
+                    write_err!(state.result, Error::last_os_error());
+                } else {
+                    write_ok!(state.result, File::from_opened(fd, mem::take(&mut state.path), state.flags, state.mode));
+                }
+            }
+
+            PollState::ReadFile(state) => {
+                let res = unsafe { read_at(state.fd, state.buffer.as_mut_ptr(), state.buffer.cap(), state.offset) };
+                if res < 0 {
+                    write_err!(state.result, Error::last_os_error());
+                } else {
+                    if !state.cursor.is_null() {
+                        unsafe { *state.cursor += res as u64 };
+                    }
+                    state.buffer.set_written(res as usize);
+                    write_ok!(state.result, mem::take(&mut state.buffer));
+                }
+            }
+
+            PollState::ReadToEndFile(state) => {
+                // A full buffer is grown by moving the data to a new one, because `reserve` tells the pool of the thread,
+                // that the buffer has left it. The buffer of the state is dropped with the state on the worker.
+                let mut grown: Option<Buffer> = None;
+                loop {
+                    let buffer = grown.as_mut().unwrap_or(&mut state.buffer);
+                    let len = buffer.len();
+                    let res = unsafe {
+                        read_at(state.fd, buffer.slice[len..].as_mut_ptr(), buffer.cap() - len, advance_offset(state.offset, len as u64))
+                    };
+                    if res < 0 {
+                        write_err!(state.result, Error::last_os_error());
+                        return;
+                    }
+                    if res == 0 {
+                        break;
+                    }
+
+                    if !state.cursor.is_null() {
+                        unsafe { *state.cursor += res as u64 };
+                    }
+                    buffer.set_written(len + res as usize);
+                    if buffer.len() == buffer.cap() {
+                        let mut new_buffer = Buffer::new_aligned(buffer.cap() * 2, buffer.align());
+                        new_buffer.append(buffer.as_ref());
+                        grown = Some(new_buffer);
+                    }
+                }
+                write_ok!(state.result, grown.unwrap_or_else(|| mem::take(&mut state.buffer)));
+            }
+
+            PollState::WriteFile(state) => {
+                let res = unsafe { write_at(state.fd, state.buffer.as_ptr(), state.buffer.len(), state.offset) };
+                if res < 0 {
+                    write_err!(state.result, Error::last_os_error());
+                } else {
+                    let written = res as usize;
+                    if !state.cursor.is_null() {
+                        unsafe { *state.cursor += written as u64 };
+                    }
+                    if written == state.buffer.len() {
+                        // The written buffer is dropped with the state on the worker.
+                        write_ok!(state.result, None);
+                    } else {
+                        state.buffer.set_offset(state.buffer.offset() + written);
+                        write_ok!(state.result, Some(mem::take(&mut state.buffer)));
+                    }
+                }
+            }
+
+            PollState::WriteAllFile(state) => {
+                let res = unsafe { write_all_at(state.fd, state.buffer.as_ref(), state.offset, state.cursor) };
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::WriteAllSyncFile(state) => {
+                let res = unsafe { write_all_at(state.fd, state.buffer.as_ref(), state.offset, state.cursor) }
+                    .and_then(|()| check(sync(state.fd, state.data_only)));
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::CloseFile(state) => {
+                unsafe { libc::close(state.fd) };
+            }
+
+            PollState::CopyFile(state) => {
+                #[cfg(target_os = "linux")]
+                let mut buf = Vec::new();
+                #[cfg(not(target_os = "linux"))]
+                let mut buf = vec![0u8; COPY_CHUNK_LEN];
+                loop {
+                    match copy_chunk(state.src_fd, state.dst_fd, &mut buf) {
+                        Ok(0) => break,
+                        Ok(copied) => state.copied += copied as u64,
+                        Err(err) => {
+                            write_err!(state.result, err);
+                            return;
+                        }
+                    }
+                }
+                write_ok!(state.result, state.copied);
+            }
+
+            PollState::Symlink(state) => {
+                let res = check(unsafe { libc::symlink(state.original.as_ptr(), state.link.as_ptr()) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::HardLink(state) => {
+                let res = check(unsafe { libc::link(state.original.as_ptr(), state.link.as_ptr()) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::ReadLink(state) => {
+                unsafe { state.result.write(read_link(&state.path)) };
+            }
+
+            PollState::SetPermissions(state) => {
+                let res = check(unsafe { libc::chmod(state.path.as_ptr(), state.mode as libc::mode_t) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::SetFilePermissions(state) => {
+                let res = check(unsafe { libc::fchmod(state.fd, state.mode as libc::mode_t) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::AdviseFile(state) => {
+                // The advice is only a hint, so it is ignored, where `posix_fadvise` is not available.
+                #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+                {
+                    // posix_fadvise returns the error number instead of setting errno.
+                    let ret = unsafe { libc::posix_fadvise(state.fd, state.offset as libc::off_t, state.len as libc::off_t, state.advice) };
+                    if ret != 0 {
+                        write_err!(state.result, Error::from_raw_os_error(ret));
+                        return;
+                    }
+                }
+                write_ok!(state.result, ());
+            }
+
+            PollState::CreateDir(state) => {
+                let res = check(unsafe { libc::mkdir(state.path.as_ptr(), state.mode as libc::mode_t) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::RemoveFile(state) => {
+                let res = check(unsafe { libc::unlink(state.path.as_ptr()) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::RemoveDir(state) => {
+                let res = check(unsafe { libc::rmdir(state.path.as_ptr()) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::Rename(state) => {
+                let res = check(unsafe { libc::rename(state.from.as_ptr(), state.to.as_ptr()) });
+                unsafe { state.result.write(res) };
+            }
+
+            PollState::SyncFile(state) => {
+                let res = check(sync(state.fd, state.data_only));
+                unsafe { state.result.write(res) };
+            }
+
+            state => panic!("[BUG] {:?} is run on the blocking pool as a file operation. Please report this issue.", state)
+        }
+    }
+}
+
+/// Finishes the file operation, which syscalls have been made by [`FileOp::run`]: leaves the empty state in the pointer
+/// for operations on an open file (its owner drops the state) or frees the state of other operations.
+///
+/// Returns the coroutine of the operation to wake up.
+///
+/// # Safety
+///
+/// It must be called on the worker, that has yielded the state, after [`FileOp::run`].
+pub(crate) unsafe fn finish_file_op(state_ptr: Ptr<PollState>) -> CoroutineImpl {
+    let (coroutine, fd) = match unsafe { state_ptr.read() } {
+        PollState::OpenFile(state) => (state.coroutine, None),
+        PollState::ReadFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::ReadToEndFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::WriteFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::WriteAllFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::WriteAllSyncFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::CloseFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::CopyFile(state) => (state.coroutine, None),
+        PollState::Symlink(state) => (state.coroutine, None),
+        PollState::HardLink(state) => (state.coroutine, None),
+        PollState::ReadLink(state) => (state.coroutine, None),
+        PollState::SetPermissions(state) => (state.coroutine, None),
+        PollState::SetFilePermissions(state) => (state.coroutine, Some(state.fd)),
+        PollState::AdviseFile(state) => (state.coroutine, Some(state.fd)),
+        PollState::CreateDir(state) => (state.coroutine, None),
+        PollState::RemoveFile(state) => (state.coroutine, None),
+        PollState::RemoveDir(state) => (state.coroutine, None),
+        PollState::Rename(state) => (state.coroutine, None),
+        PollState::SyncFile(state) => (state.coroutine, Some(state.fd)),
+        state => panic!("[BUG] {:?} is finished as a file operation. Please report this issue.", state)
+    };
+
+    match fd {
+        Some(fd) => unsafe { state_ptr.write(PollState::new_empty(fd)) },
+        // The state has been read from the pointer, so only the memory is freed.
+        None => unsafe { state_ptr.dealloc() }
+    }
+    coroutine
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::ptr::null_mut;
+    use super::write_all_with;
+
+    #[test]
+    fn test_write_zero() {
+        // The first write is short, and then the file takes no more bytes.
+        let mut calls = Vec::new();
+        let mut cursor = 0;
+        let res = unsafe {
+            write_all_with(&[1; 10], 5, &mut cursor, |buf, offset| {
+                calls.push((buf.len(), offset));
+                if calls.len() == 1 { 4 } else { 0 }
+            })
+        };
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(calls, vec![(10, 5), (6, 9)]);
+        assert_eq!(cursor, 4);
+
+        let res = unsafe { write_all_with(&[1; 10], 0, null_mut(), |buf, _| buf.len() as isize) };
+        assert!(res.is_ok());
+    }
+}
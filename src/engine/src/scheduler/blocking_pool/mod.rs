@@ -0,0 +1,5 @@
+pub(crate) mod blocking_pool;
+pub(crate) mod file_op;
+mod worker;
+
+pub(crate) use blocking_pool::BlockingPool;
@@ -1,51 +1,86 @@
-// TODO r
-use std::hint::spin_loop;
+use std::io::Error;
 use std::os::fd::IntoRawFd;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::thread;
 use crossbeam::queue::{SegQueue};
 use crate::io::BlockingState;
 use crate::net::TcpStream;
 use crate::{write_err, write_ok};
 use crate::coroutine::CoroutineImpl;
+use crate::scheduler::Injector;
 
-// TODO add thread park
 pub(super) struct Worker {
     input: SegQueue<BlockingState>,
-    output: SegQueue<CoroutineImpl>
+    output: SegQueue<CoroutineImpl>,
+    /// The number of parked threads.
+    idle: AtomicUsize,
+    is_closed: AtomicBool,
+    /// The injector of the scheduler. It wakes the scheduler up, when a coroutine is ready, so a parked scheduler doesn't miss it.
+    injector: Injector
 }
 
 impl Worker {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(injector: Injector) -> Self {
         Self {
             input: SegQueue::new(),
-            output: SegQueue::new()
+            output: SegQueue::new(),
+            idle: AtomicUsize::new(0),
+            is_closed: AtomicBool::new(false),
+            injector
         }
     }
 
     pub(super) fn run(&self) {
         loop {
-            for _ in 0..3 {
-                while let Some(state) = self.input.pop() {
-                    match state {
-                        BlockingState::ConnectTcp(state) => {
-                            let stream = std::net::TcpStream::connect(state.address);
-                            if let Ok(stream) = stream {
-                                write_ok!(state.result, TcpStream::new(stream.into_raw_fd()));
-                            } else {
-                                write_err!(state.result, stream.unwrap_err_unchecked());
-                            }
-
-                            self.output.push(state.coroutine);
-                        }
-                    }
+            while let Some(state) = self.input.pop() {
+                self.handle_state(state);
+                self.injector.wake();
+            }
+
+            if self.is_closed.load(Ordering::Acquire) {
+                return;
+            }
+
+            // A state, that is put after the check, unparks the thread, so it is not lost.
+            self.idle.fetch_add(1, Ordering::AcqRel);
+            thread::park();
+            self.idle.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    fn handle_state(&self, state: BlockingState) {
+        match state {
+            BlockingState::ConnectTcp(state) => {
+                let stream = std::net::TcpStream::connect(state.address);
+                match stream {
+                    Ok(stream) => write_ok!(state.result, TcpStream::new(stream.into_raw_fd())),
+                    Err(err) => write_err!(state.result, err)
                 }
 
-                for _ in 0..10 {
-                    spin_loop();
+                self.output.push(state.coroutine);
+            }
+
+            BlockingState::LockFile(state) => {
+                loop {
+                    if unsafe { libc::flock(state.fd, state.operation) } == 0 {
+                        write_ok!(state.result, ());
+                        break;
+                    }
+
+                    let err = Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        write_err!(state.result, err);
+                        break;
+                    }
                 }
+
+                self.output.push(state.coroutine);
             }
 
-            thread::yield_now();
+            BlockingState::RunClosure(state) => {
+                (state.job)();
+                self.output.push(state.coroutine);
+            }
         }
     }
 
@@ -60,7 +95,23 @@ impl Worker {
     pub(super) fn put_state(&self, state: BlockingState) {
         self.input.push(state);
     }
+
+    /// Returns the number of states, that are not taken by threads yet.
+    #[inline(always)]
+    pub(super) fn queued(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Returns the number of parked threads.
+    #[inline(always)]
+    pub(super) fn idle(&self) -> usize {
+        self.idle.load(Ordering::Acquire)
+    }
+
+    pub(super) fn close(&self) {
+        self.is_closed.store(true, Ordering::Release);
+    }
 }
 
 unsafe impl Send for Worker {}
-unsafe impl Sync for Worker {}
\ No newline at end of file
+unsafe impl Sync for Worker {}
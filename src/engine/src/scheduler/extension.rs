@@ -0,0 +1,18 @@
+//! This module contains [`ExtensionId`] and [`ExtensionHandler`] for adding new awaitable operations to the [`Scheduler`](crate::scheduler::Scheduler)
+//! without modifying [`YieldStatus`](crate::coroutine::YieldStatus).
+
+use crate::coroutine::CoroutineImpl;
+use crate::utils::Ptr;
+
+/// The handler of an extension. It is registered by [`Scheduler::register_extension`](crate::scheduler::Scheduler::register_extension).
+///
+/// It takes the yielded coroutine and the payload of [`YieldStatus::Extension`](crate::coroutine::YieldStatus::Extension).
+/// The handler owns the coroutine after the call. It must store the coroutine and return it to the scheduler with
+/// [`sched`](crate::scheduler::Scheduler::sched) when the operation is done, otherwise the coroutine will never be woken up.
+pub type ExtensionHandler = Box<dyn Fn(CoroutineImpl, Ptr<()>)>;
+
+/// The id of a registered extension. Pass it to [`YieldStatus::extension`](crate::coroutine::YieldStatus::extension).
+///
+/// The id is valid only in the worker, where the extension was registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtensionId(pub(crate) usize);
@@ -0,0 +1,193 @@
+//! This module contains [`SchedulerHandle`], the handle of a worker for other threads.
+use std::io::Error;
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::io::PollState;
+use crate::scheduler::{Injector, SchedulerMetrics};
+use crate::scheduler::injection::reset_waker;
+use crate::utils::Ptr;
+
+/// A handle to a worker, that can be moved to other threads (including threads outside the engine).
+/// [`local_scheduler`](crate::local_scheduler) works only on the worker itself.
+///
+/// The handle can:
+///
+/// - spawn coroutines on the worker through its [`Injector`] and wake it up, so they are started at once
+///   instead of after a poll timeout;
+///
+/// - wake the selector of the worker up (with `IORING_OP_MSG_RING`, if the worker uses io_uring);
+///
+/// - return the [`SchedulerMetrics`] of the worker.
+///
+/// Get it with [`Scheduler::handle`](crate::scheduler::Scheduler::handle) on the worker.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::coro;
+/// use engine::scheduler::{local_scheduler, SchedulerHandle};
+///
+/// #[coro]
+/// fn handle_job(job: u64) {
+///     println!("job {}", job);
+/// }
+///
+/// #[coro]
+/// fn start() {
+///     let handle: SchedulerHandle = local_scheduler().handle();
+///     std::thread::spawn(move || {
+///         for job in 0..10 {
+///             handle.spawn(move || handle_job(job, std::ptr::null_mut())).unwrap();
+///         }
+///         println!("{} coroutines are ready", handle.metrics().ready);
+///     });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    injector: Injector,
+    /// The metrics of the worker at its last background work.
+    metrics: Arc<Mutex<SchedulerMetrics>>
+}
+
+impl SchedulerHandle {
+    /// Creates the handle of the worker with the `injector`, which must have a waker. Read [`Injector::wake`].
+    pub(crate) fn new(injector: Injector) -> Self {
+        Self {
+            injector,
+            metrics: Arc::new(Mutex::new(SchedulerMetrics::default()))
+        }
+    }
+
+    /// Stores the metrics of the worker. It is called by the worker, so it never waits for readers.
+    #[inline(always)]
+    pub(crate) fn publish_metrics(&self, metrics: SchedulerMetrics) {
+        if let Ok(mut published) = self.metrics.try_lock() {
+            *published = metrics;
+        }
+    }
+
+    /// Returns the id of the core of the worker.
+    #[inline(always)]
+    pub fn core_id(&self) -> usize {
+        self.injector.core_id()
+    }
+
+    /// Returns true, if the worker is stopped, and it doesn't accept coroutines anymore.
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.injector.is_closed()
+    }
+
+    /// Returns the [`Injector`] of the worker.
+    #[inline(always)]
+    pub fn injector(&self) -> &Injector {
+        &self.injector
+    }
+
+    /// Sends `creator` to the worker and wakes it up. Read [`Injector::spawn`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::NotConnected`](std::io::ErrorKind::NotConnected), if the worker is stopped.
+    pub fn spawn<F: FnOnce() -> CoroutineImpl + Send + 'static>(&self, creator: F) -> Result<(), Error> {
+        self.injector.spawn(creator)
+    }
+
+    /// Wakes the selector of the worker up. Read [`Injector::wake`].
+    #[inline(always)]
+    pub fn wake(&self) {
+        self.injector.wake();
+    }
+
+    /// Returns the [`SchedulerMetrics`] of the worker at its last background work.
+    /// Read [`set_poll_interval`](crate::cfg::set_poll_interval) for how often it is run.
+    pub fn metrics(&self) -> SchedulerMetrics {
+        *self.metrics.lock().unwrap()
+    }
+}
+
+/// Waits for wake ups of the [`Injector`]. The selector returns from the poll, when the waker is readable,
+/// so it is enough to reset the waker.
+#[coro(crate="crate")]
+pub(crate) fn listen_wakeups(waker_fd: RawFd) {
+    let state = Ptr::new(PollState::new_empty(waker_fd));
+    let mut is_registered = false;
+    loop {
+        let res: Result<(), Error> = yield YieldStatus::wait_readable(is_registered, state);
+        is_registered = true;
+        if res.is_err() {
+            break;
+        }
+
+        reset_waker(waker_fd);
+    }
+    unsafe { state.drop_in_place() };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    #[cfg(target_os = "linux")]
+    use crate::io::submission_stats;
+    use crate::scheduler::{local_scheduler, SchedulerHandle};
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn increment(counter: Arc<AtomicUsize>) {
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_handle() {
+        let handle: SchedulerHandle = local_scheduler().handle();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_ = counter.clone();
+        let thread = std::thread::spawn(move || {
+            for _ in 0..3 {
+                let counter = counter_.clone();
+                handle.spawn(move || increment(counter, null_mut())).unwrap();
+            }
+            handle.wake();
+            while handle.metrics().completed_total < 3 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            handle
+        });
+
+        while counter.load(Ordering::SeqCst) < 3 {
+            yield sleep(Duration::from_millis(1));
+        }
+        let handle = loop {
+            if thread.is_finished() {
+                break thread.join().unwrap();
+            }
+            yield sleep(Duration::from_millis(1));
+        };
+        assert_eq!(handle.core_id(), local_scheduler().injector().core_id());
+        assert!(!handle.is_closed());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test_local(crate="crate")]
+    fn test_wake_with_msg_ring() {
+        let handle: SchedulerHandle = local_scheduler().handle();
+        let wakeups = submission_stats().ring_wakeups;
+        std::thread::spawn(move || handle.wake()).join().unwrap();
+
+        // The wake-up is a completion of the ring, not a read of the eventfd.
+        for _ in 0..1000 {
+            if submission_stats().ring_wakeups > wakeups {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(submission_stats().ring_wakeups, wakeups + 1);
+    }
+}
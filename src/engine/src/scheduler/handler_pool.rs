@@ -0,0 +1,143 @@
+//! This module contains [`HandlerPool`] for warm-starting handler coroutines.
+use crate::coroutine::CoroutineImpl;
+use crate::local_scheduler;
+use crate::utils::Ptr;
+
+/// The input of a parked handler. The handler gets it with [`Parked::take`] when it is started.
+pub struct Parked<T> {
+    slot: Ptr<Option<T>>
+}
+
+impl<T> Parked<T> {
+    /// Takes the input, that was given to the handler in [`HandlerPool::spawn`].
+    pub fn take(self) -> T {
+        let value = unsafe { self.slot.as_mut() }.take();
+        value.expect("[BUG] the parked handler was started without an input. Please report this issue.")
+    }
+}
+
+impl<T> Drop for Parked<T> {
+    fn drop(&mut self) {
+        unsafe { self.slot.drop_in_place(); }
+    }
+}
+
+/// A pool of pre-created handler coroutines, that are parked until they are given an input (for example, a connection).
+///
+/// Creating a coroutine allocates its frame. When the first burst of connections comes after the startup,
+/// these allocations happen all at once and lead to a latency spike.
+/// [`HandlerPool`] creates the coroutines in advance, so [`HandlerPool::spawn`] only gives the input and schedules the coroutine.
+///
+/// The pool is local for the worker, so create it on every core in the coroutine passed to [`run_on_core`](crate::run::run_on_core).
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::coro;
+/// use engine::net::{TcpListener, TcpStream};
+/// use engine::scheduler::{HandlerPool, Parked};
+///
+/// #[coro]
+/// fn handle_tcp_stream(parked: Parked<TcpStream>) {
+///     let mut stream = parked.take();
+///     // process stream
+/// }
+///
+/// #[coro]
+/// fn run_server() {
+///     let mut pool = HandlerPool::new(handle_tcp_stream, 1024);
+///     let mut listener = yield TcpListener::new("localhost:8081".to_socket_addrs().unwrap().next().unwrap());
+///     loop {
+///         let stream = (yield listener.accept()).expect("accept failed");
+///         pool.spawn(stream);
+///     }
+/// }
+/// ```
+pub struct HandlerPool<T, C: Fn(Parked<T>, *mut ()) -> CoroutineImpl> {
+    creator: C,
+    parked: Vec<(CoroutineImpl, Ptr<Option<T>>)>
+}
+
+impl<T, C: Fn(Parked<T>, *mut ()) -> CoroutineImpl> HandlerPool<T, C> {
+    /// Creates a new [`HandlerPool`] and pre-creates `size` handlers with the `creator`.
+    pub fn new(creator: C, size: usize) -> Self {
+        let mut pool = Self {
+            creator,
+            parked: Vec::with_capacity(size)
+        };
+        pool.warm(size);
+
+        pool
+    }
+
+    /// Pre-creates `count` more handlers.
+    pub fn warm(&mut self, count: usize) {
+        self.parked.reserve(count);
+        for _ in 0..count {
+            let handler = self.create();
+            self.parked.push(handler);
+        }
+    }
+
+    /// Returns the number of parked handlers.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.parked.len()
+    }
+
+    /// Returns true, if there are no parked handlers.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.parked.is_empty()
+    }
+
+    /// Gives the input to a parked handler and schedules it in the local scheduler.
+    /// If there are no parked handlers, a new one is created.
+    pub fn spawn(&mut self, input: T) {
+        let (handler, slot) = match self.parked.pop() {
+            Some(handler) => handler,
+            None => self.create()
+        };
+
+        unsafe { slot.write(Some(input)); }
+        local_scheduler().sched(handler);
+    }
+
+    /// Creates a new handler with an empty slot.
+    fn create(&self) -> (CoroutineImpl, Ptr<Option<T>>) {
+        let slot = Ptr::new(None);
+        ((self.creator)(Parked { slot }, std::ptr::null_mut()), slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{coro, test_local};
+    use crate::coroutine::yield_now;
+    use crate::local::Local;
+    use crate::scheduler::{HandlerPool, Parked};
+
+    #[test_local(crate="crate")]
+    fn test_handler_pool() {
+        #[coro(crate="crate")]
+        fn handle(parked: Parked<(u16, Local<Vec<u16>>)>) {
+            let (number, arr) = parked.take();
+            arr.get_mut().push(number);
+        }
+
+        let arr = Local::new(Vec::new());
+        let mut pool = HandlerPool::new(handle, 2);
+        assert_eq!(pool.len(), 2);
+
+        pool.spawn((1, arr.clone()));
+        pool.spawn((2, arr.clone()));
+        pool.spawn((3, arr.clone()));
+        assert!(pool.is_empty());
+
+        yield yield_now();
+
+        let mut handled = arr.get().clone();
+        handled.sort();
+        assert_eq!(handled, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,186 @@
+//! This module contains [`IdleStrategy`].
+use std::time::Duration;
+
+/// How long the worker waits in the selector, when it has nothing to run.
+///
+/// While coroutines are ready, the selector is polled without waiting. When the worker is idle, every poll waits for
+/// completions with a timeout, that grows with the number of idle polls in a row:
+///
+/// - the first `spin_polls` polls wait at most `spin_timeout`, so a new request is handled with a low latency;
+///
+/// - the next `backoff_polls` polls wait at most `backoff_timeout`;
+///
+/// - then, if `park` is true, the worker waits without a timeout, until an operation completes, a timer expires
+///   or another thread wakes it up (with [`Injector::spawn`](crate::scheduler::Injector::spawn),
+///   [`SchedulerHandle`](crate::scheduler::SchedulerHandle) or the end of a blocking operation).
+///   Otherwise, it keeps waiting with the `backoff_timeout`.
+///
+/// A poll never waits longer than until the next timer, and it returns at once, when an outstanding operation completes.
+/// Any work found by a poll starts the sequence again.
+///
+/// Set it with [`set_idle_strategy`](crate::cfg::set_idle_strategy) before the start
+/// or with [`Scheduler::set_idle_strategy`](crate::scheduler::Scheduler::set_idle_strategy) for the current worker.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::cfg::set_idle_strategy;
+/// use engine::scheduler::IdleStrategy;
+///
+/// // The lowest latency of work, that doesn't wake the worker up, at the cost of the CPU.
+/// set_idle_strategy(IdleStrategy::spin());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdleStrategy {
+    /// The number of idle polls with the `spin_timeout`.
+    pub spin_polls: u32,
+    /// The timeout of the first idle polls.
+    pub spin_timeout: Duration,
+    /// The number of idle polls with the `backoff_timeout` after the spinning.
+    pub backoff_polls: u32,
+    /// The timeout of idle polls after the spinning.
+    pub backoff_timeout: Duration,
+    /// Whether the worker waits without a timeout after the backoff.
+    pub park: bool
+}
+
+impl IdleStrategy {
+    /// Returns the strategy, that always waits at most half a millisecond.
+    /// An idle worker wakes up 2000 times per second, even if it has no outstanding operations and timers.
+    pub const fn spin() -> Self {
+        Self {
+            spin_polls: u32::MAX,
+            spin_timeout: Duration::from_micros(500),
+            backoff_polls: 0,
+            backoff_timeout: Duration::from_micros(500),
+            park: false
+        }
+    }
+
+    /// Returns the strategy, that waits half a millisecond 64 times (about 30 ms), then 10 ms 64 times and then parks.
+    pub const fn backoff() -> Self {
+        Self {
+            spin_polls: 64,
+            spin_timeout: Duration::from_micros(500),
+            backoff_polls: 64,
+            backoff_timeout: Duration::from_millis(10),
+            park: true
+        }
+    }
+
+    /// Returns the strategy, that waits until the next timer or the completion of an outstanding operation
+    /// and parks without timers. It is the default.
+    ///
+    /// Other threads, spawning coroutines or completing blocking operations, wake the worker up, so the latency is not lost.
+    /// Only a worker, that checks something by itself (for example, steals work), waits at most half a millisecond.
+    pub const fn adaptive() -> Self {
+        Self {
+            spin_polls: 0,
+            spin_timeout: Duration::from_micros(500),
+            backoff_polls: 0,
+            backoff_timeout: Duration::from_micros(500),
+            park: true
+        }
+    }
+
+    /// Returns the timeout of the `idle_polls`-th idle poll in a row (from 1). `None` means to wait without a timeout.
+    #[inline(always)]
+    pub(crate) fn timeout(&self, idle_polls: u32) -> Option<Duration> {
+        if idle_polls <= self.spin_polls {
+            return Some(self.spin_timeout);
+        }
+        if !self.park || idle_polls - self.spin_polls <= self.backoff_polls {
+            return Some(self.backoff_timeout);
+        }
+
+        None
+    }
+}
+
+impl Default for IdleStrategy {
+    fn default() -> Self {
+        Self::adaptive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::ptr::null_mut;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+    use crate::{coro, test_local};
+    use crate::net::{TcpListener, TcpStream};
+    use crate::scheduler::{local_scheduler, IdleStrategy};
+    use crate::sleep::sleep;
+
+    #[test]
+    fn test_timeout() {
+        let strategy = IdleStrategy {
+            spin_polls: 2,
+            spin_timeout: Duration::from_micros(100),
+            backoff_polls: 1,
+            backoff_timeout: Duration::from_millis(5),
+            park: true
+        };
+        assert_eq!(strategy.timeout(1), Some(Duration::from_micros(100)));
+        assert_eq!(strategy.timeout(2), Some(Duration::from_micros(100)));
+        assert_eq!(strategy.timeout(3), Some(Duration::from_millis(5)));
+        assert_eq!(strategy.timeout(4), None);
+        assert_eq!(IdleStrategy { park: false, ..strategy }.timeout(100), Some(Duration::from_millis(5)));
+        assert_eq!(IdleStrategy::spin().timeout(u32::MAX), Some(Duration::from_micros(500)));
+        assert_eq!(IdleStrategy::adaptive().timeout(1), None);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_poll_until_deadline() {
+        local_scheduler().set_idle_strategy(IdleStrategy::adaptive());
+        let polls = local_scheduler().metrics().polls_total;
+        let start = Instant::now();
+        yield sleep(Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        // The spinning strategy would poll about a hundred times.
+        let polls = local_scheduler().metrics().polls_total - polls;
+        assert!(polls < 10, "the idle worker polled {} times", polls);
+    }
+
+    #[coro(crate="crate")]
+    fn mark(injected: Arc<AtomicBool>) {
+        injected.store(true, Ordering::SeqCst);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_park() {
+        local_scheduler().set_idle_strategy(IdleStrategy {
+            spin_polls: 1,
+            spin_timeout: Duration::from_micros(100),
+            backoff_polls: 1,
+            backoff_timeout: Duration::from_millis(1),
+            park: true
+        });
+        let mut listener: TcpListener = yield TcpListener::new("127.0.0.1:0".parse().unwrap());
+        let addr = listener.local_addr().unwrap();
+        let injected = Arc::new(AtomicBool::new(false));
+
+        // The worker has only the accept and parks, so the injected coroutine is run only if the spawn wakes it up.
+        let injector = local_scheduler().injector();
+        let injected_ = injected.clone();
+        let thread = std::thread::spawn(move || {
+            let injected = injected_;
+            std::thread::sleep(Duration::from_millis(50));
+            let injected_ = injected.clone();
+            injector.spawn(move || mark(injected_, null_mut())).unwrap();
+            let start = Instant::now();
+            while !injected.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(1) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            std::net::TcpStream::connect(addr).unwrap()
+        });
+
+        let res: Result<TcpStream, Error> = yield listener.accept();
+        res.unwrap();
+        assert!(injected.load(Ordering::SeqCst));
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+//! This module contains [`Injector`], [`spawn_on`] and [`spawn_global`].
+use std::io::{Error, ErrorKind};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use crossbeam::queue::SegQueue;
+use crate::coroutine::CoroutineImpl;
+#[cfg(target_os = "linux")]
+use crate::io::sys::unix::io_uring::msg_ring::wake_ring;
+
+/// A function, that creates a coroutine on the worker, that runs it.
+///
+/// Coroutines are not [`Send`], so other threads send their creators instead.
+pub type CoroutineCreator = Box<dyn FnOnce() -> CoroutineImpl + Send>;
+
+struct InjectionQueue {
+    core_id: usize,
+    creators: SegQueue<CoroutineCreator>,
+    /// The number of ready coroutines of the worker at the last tick.
+    ready: AtomicUsize,
+    is_closed: AtomicBool,
+    /// The [`Waker`], that wakes the selector of the worker up. It is created on demand. Read [`Injector::wake`].
+    waker: OnceLock<Waker>,
+    /// The fd of the io_uring of the worker or -1, if the worker doesn't use io_uring. Read [`Injector::wake`].
+    ring_fd: AtomicI32
+}
+
+/// A handle to the injection queue of a worker. Other threads (including threads outside the engine)
+/// use it to submit coroutines to the worker.
+///
+/// Injected coroutines are started by the worker in its next background tick. If the worker has a waker
+/// (it has a [`SchedulerHandle`](crate::scheduler::SchedulerHandle) or its [`IdleStrategy`](crate::scheduler::IdleStrategy) parks),
+/// it is woken up at once, else it takes up to a poll timeout.
+///
+/// Get it with [`Scheduler::injector`](crate::scheduler::Scheduler::injector) or [`injector`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::coro;
+/// use engine::scheduler::{local_scheduler, Injector};
+///
+/// #[coro]
+/// fn handle_job(job: u64) {
+///     println!("job {}", job);
+/// }
+///
+/// #[coro]
+/// fn start() {
+///     let injector: Injector = local_scheduler().injector();
+///     std::thread::spawn(move || {
+///         for job in 0..10 {
+///             injector.spawn(move || handle_job(job, std::ptr::null_mut())).unwrap();
+///         }
+///     });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Injector {
+    queue: Arc<InjectionQueue>
+}
+
+impl Injector {
+    pub(crate) fn new(core_id: usize) -> Self {
+        Self {
+            queue: Arc::new(InjectionQueue {
+                core_id,
+                creators: SegQueue::new(),
+                ready: AtomicUsize::new(0),
+                is_closed: AtomicBool::new(false),
+                waker: OnceLock::new(),
+                ring_fd: AtomicI32::new(-1)
+            })
+        }
+    }
+
+    /// Returns the id of the core of the worker.
+    #[inline(always)]
+    pub fn core_id(&self) -> usize {
+        self.queue.core_id
+    }
+
+    /// Returns the load of the worker: the number of its ready coroutines at the last tick and injected coroutines,
+    /// that are not started yet.
+    pub fn load(&self) -> usize {
+        self.queue.ready.load(Ordering::Relaxed) + self.queue.creators.len()
+    }
+
+    /// Returns true, if the worker is stopped, and it doesn't accept coroutines anymore.
+    pub fn is_closed(&self) -> bool {
+        self.queue.is_closed.load(Ordering::Acquire)
+    }
+
+    /// Sends `creator` to the worker and [wakes](Injector::wake) it up. The worker calls it and runs the returned coroutine.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ErrorKind::NotConnected`], if the worker is stopped.
+    pub fn spawn<F: FnOnce() -> CoroutineImpl + Send + 'static>(&self, creator: F) -> Result<(), Error> {
+        if self.is_closed() {
+            return Err(Error::new(ErrorKind::NotConnected, "the worker is stopped"));
+        }
+        self.queue.creators.push(Box::new(creator));
+        self.wake();
+        Ok(())
+    }
+
+    /// Wakes the selector of the worker up, so it runs its background work (starts injected coroutines and so on) at once.
+    ///
+    /// If the worker uses io_uring, the wake-up is posted to its completion queue with `IORING_OP_MSG_RING`
+    /// (since Linux 5.18), so the worker returns from its wait without a read of an eventfd.
+    /// The calling thread sends it with its own small ring, that is created at its first wake-up.
+    ///
+    /// Otherwise, the [`Waker`] of the worker is written, and wake ups, that are sent before the worker handles them,
+    /// are merged into one. It does nothing, if the worker has no waker.
+    #[inline(always)]
+    pub fn wake(&self) {
+        #[cfg(target_os = "linux")]
+        {
+            let ring_fd = self.queue.ring_fd.load(Ordering::Acquire);
+            if ring_fd >= 0 && wake_ring(ring_fd) {
+                return;
+            }
+        }
+        if let Some(waker) = self.queue.waker.get() {
+            waker.wake();
+        }
+    }
+
+    /// Sets the fd of the io_uring of the worker for [`Injector::wake`]. It is called by the worker,
+    /// when the ring is created or recreated, and with `None` before the ring is closed.
+    #[inline(always)]
+    pub(crate) fn set_ring_fd(&self, ring_fd: Option<RawFd>) {
+        let ring_fd = ring_fd.unwrap_or(-1);
+        if self.queue.ring_fd.load(Ordering::Relaxed) != ring_fd {
+            self.queue.ring_fd.store(ring_fd, Ordering::Release);
+        }
+    }
+
+    /// Creates the waker of the worker, if it has no one. It is called by the worker.
+    ///
+    /// Returns the readable fd of the created waker, so the worker can start listening to it.
+    pub(crate) fn init_waker(&self) -> Result<Option<RawFd>, Error> {
+        if self.queue.waker.get().is_some() {
+            return Ok(None);
+        }
+
+        let waker = Waker::new()?;
+        let fd = waker.read.as_raw_fd();
+        let _ = self.queue.waker.set(waker);
+        Ok(Some(fd))
+    }
+
+    /// Takes injected creators. It is called by the worker.
+    #[inline(always)]
+    pub(crate) fn pop(&self) -> Option<CoroutineCreator> {
+        self.queue.creators.pop()
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_ready(&self, ready: usize) {
+        self.queue.ready.store(ready, Ordering::Relaxed);
+    }
+
+    /// Returns the number of creators, that are not started yet.
+    #[inline(always)]
+    pub(crate) fn queued(&self) -> usize {
+        self.queue.creators.len()
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_same(&self, other: &Injector) -> bool {
+        Arc::ptr_eq(&self.queue, &other.queue)
+    }
+}
+
+/// The fds, that wake the selector of a worker up. The worker waits, until `read` is readable.
+///
+/// On Linux it is one eventfd. Off Linux there is no eventfd, so it is a pipe.
+struct Waker {
+    read: OwnedFd,
+    #[cfg(not(target_os = "linux"))]
+    write: OwnedFd
+}
+
+impl Waker {
+    #[cfg(target_os = "linux")]
+    fn new() -> Result<Self, Error> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(Self { read: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new() -> Result<Self, Error> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let waker = unsafe { Self { read: OwnedFd::from_raw_fd(fds[0]), write: OwnedFd::from_raw_fd(fds[1]) } };
+        for fd in fds {
+            // There is no `pipe2` on macOS, so the flags are set after the creation.
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) } < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(waker)
+    }
+
+    #[inline(always)]
+    fn wake(&self) {
+        #[cfg(target_os = "linux")]
+        unsafe { libc::eventfd_write(self.read.as_raw_fd(), 1) };
+        // A full pipe already has a wake-up, that is not handled, so the error is ignored.
+        #[cfg(not(target_os = "linux"))]
+        unsafe { libc::write(self.write.as_raw_fd(), [1u8].as_ptr() as _, 1) };
+    }
+}
+
+/// Resets the [`Waker`] by its readable fd, so the worker waits for the next wake-up.
+pub(crate) fn reset_waker(read_fd: RawFd) {
+    #[cfg(target_os = "linux")]
+    {
+        let mut value = 0;
+        unsafe { libc::eventfd_read(read_fd, &mut value) };
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(read_fd, buf.as_mut_ptr() as _, buf.len()) } == buf.len() as isize {}
+    }
+}
+
+/// Injectors of the running workers.
+static INJECTORS: Mutex<Vec<Injector>> = Mutex::new(Vec::new());
+
+/// Creates the injector of a new worker and makes it available for [`spawn_on`] and [`spawn_global`].
+pub(crate) fn register(core_id: usize) -> Injector {
+    let injector = Injector::new(core_id);
+    INJECTORS.lock().unwrap().push(injector.clone());
+    injector
+}
+
+/// Removes the injector of the stopped worker. Creators, that were not started, are dropped.
+pub(crate) fn unregister(injector: &Injector) {
+    injector.queue.is_closed.store(true, Ordering::Release);
+    INJECTORS.lock().unwrap().retain(|registered| !registered.is_same(injector));
+    while injector.pop().is_some() {}
+}
+
+/// Returns injectors of all running workers.
+pub(crate) fn injectors() -> Vec<Injector> {
+    INJECTORS.lock().unwrap().clone()
+}
+
+/// Returns the injector of the worker on the core.
+pub fn injector(core_id: usize) -> Option<Injector> {
+    INJECTORS.lock().unwrap().iter().find(|injector| injector.core_id() == core_id).cloned()
+}
+
+/// Sends `creator` to the worker on the core. Read [`Injector::spawn`] for more information.
+///
+/// # Errors
+///
+/// Returns [`ErrorKind::NotFound`], if there is no worker on the core.
+pub fn spawn_on<F: FnOnce() -> CoroutineImpl + Send + 'static>(core_id: usize, creator: F) -> Result<(), Error> {
+    match injector(core_id) {
+        Some(injector) => injector.spawn(creator),
+        None => Err(Error::new(ErrorKind::NotFound, "there is no worker on the core"))
+    }
+}
+
+/// Sends `creator` to the least loaded worker and returns the id of its core.
+/// Use [`spawn_global!`](crate::spawn_global) instead if you don't want to low-level work.
+///
+/// # Errors
+///
+/// Returns [`ErrorKind::NotFound`], if no worker is running.
+pub fn spawn_global<F: FnOnce() -> CoroutineImpl + Send + 'static>(creator: F) -> Result<usize, Error> {
+    let injector = INJECTORS.lock().unwrap().iter().min_by_key(|injector| injector.load()).cloned();
+    match injector {
+        Some(injector) => {
+            injector.spawn(creator)?;
+            Ok(injector.core_id())
+        }
+        None => Err(Error::new(ErrorKind::NotFound, "no worker is running"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::local::get_core_id;
+    use crate::scheduler::{injector, local_scheduler, Injector};
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn count(counter: Arc<AtomicUsize>) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_injector() {
+        const JOBS: usize = 10;
+
+        let local: Injector = local_scheduler().injector();
+        assert_eq!(local.core_id(), get_core_id());
+        assert!(injector(get_core_id()).is_some());
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let producer = {
+            let counter = counter.clone();
+            std::thread::spawn(move || {
+                for _ in 0..JOBS {
+                    let counter = counter.clone();
+                    local.spawn(move || count(counter, std::ptr::null_mut())).unwrap();
+                }
+            })
+        };
+        producer.join().unwrap();
+
+        for _ in 0..100 {
+            if counter.load(Ordering::Relaxed) == JOBS {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), JOBS);
+    }
+}
@@ -0,0 +1,175 @@
+//! This module contains [`CoroutineLimits`], the limits of the load of one worker, and [`spawn_limited`].
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::scheduler::local_scheduler;
+
+/// What happens with a coroutine, that is spawned, while the worker is over its [`CoroutineLimits`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// The coroutine is dropped without being started, and [`LimitExceeded`] is returned.
+    Reject,
+    /// The spawner is suspended, until the worker is under the limits, and then the coroutine is spawned.
+    ///
+    /// Only [`spawn_limited`] can suspend the spawner, so [`Scheduler::try_spawn`](crate::scheduler::Scheduler::try_spawn)
+    /// rejects the coroutine with this policy.
+    Wait,
+    /// The coroutine is put to the backlog, which coroutines are spawned in FIFO order, while the worker is under the limits.
+    /// If the backlog is longer than `backlog`, its oldest coroutine is dropped without being started.
+    DropOldest {
+        /// The maximum length of the backlog.
+        backlog: usize
+    }
+}
+
+/// The limits of the load of one worker. Without them, an overload grows the queues, until the process runs out of memory.
+///
+/// The worker is over the limits, when the run queue, the sleeping coroutines or the [`PollState`](crate::io::PollState)s
+/// of the selector (registered fds and operations in flight, counted at the last poll) reach their maximum.
+/// Then coroutines, that are spawned with [`spawn_limited`], [`try_spawn_local!`](crate::try_spawn_local)
+/// or [`Scheduler::try_spawn`](crate::scheduler::Scheduler::try_spawn), are handled by the [`LimitPolicy`].
+/// Other spawn functions ignore the limits, so the engine itself is never blocked by them.
+///
+/// Set it with [`set_coroutine_limits`](crate::cfg::set_coroutine_limits) before the start
+/// or with [`Scheduler::set_coroutine_limits`](crate::scheduler::Scheduler::set_coroutine_limits) for the current worker.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::cfg::set_coroutine_limits;
+/// use engine::scheduler::{CoroutineLimits, LimitPolicy};
+///
+/// // Suspend spawners at 10_000 ready coroutines, 100_000 sleeping ones or 50_000 states of the selector.
+/// set_coroutine_limits(Some(CoroutineLimits::new(10_000, 100_000, 50_000, LimitPolicy::Wait)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoroutineLimits {
+    /// The maximum number of ready coroutines in the run queue.
+    pub max_ready: usize,
+    /// The maximum number of sleeping coroutines.
+    pub max_sleeping: usize,
+    /// The maximum number of [`PollState`](crate::io::PollState)s of the selector.
+    pub max_pending_states: usize,
+    /// What happens with a coroutine, that is spawned over the limits.
+    pub policy: LimitPolicy
+}
+
+impl CoroutineLimits {
+    /// Creates new [`CoroutineLimits`]. Use `usize::MAX` to not limit a queue.
+    pub const fn new(max_ready: usize, max_sleeping: usize, max_pending_states: usize, policy: LimitPolicy) -> Self {
+        Self { max_ready, max_sleeping, max_pending_states, policy }
+    }
+
+    /// Returns true, if a coroutine can't be spawned now.
+    #[inline(always)]
+    pub(crate) fn is_exceeded(&self, ready: usize, sleeping: usize, pending_states: usize) -> bool {
+        ready >= self.max_ready || sleeping >= self.max_sleeping || pending_states >= self.max_pending_states
+    }
+}
+
+/// The error, that means, that the coroutine was not spawned, because the worker is over its [`CoroutineLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded;
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the worker is over its coroutine limits")
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for Error {
+    fn from(exceeded: LimitExceeded) -> Self {
+        Error::new(ErrorKind::WouldBlock, exceeded)
+    }
+}
+
+/// Spawns the coroutine like [`spawn_local!`](crate::spawn_local), but respects the [`CoroutineLimits`] of the worker.
+///
+/// With [`LimitPolicy::Wait`] the spawner is suspended, until the coroutine is spawned. Otherwise, the result is written at once,
+/// and the spawner is only yielded. Read [`Scheduler::try_spawn`](crate::scheduler::Scheduler::try_spawn) for the result.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use engine::coro;
+/// use engine::net::TcpStream;
+/// use engine::scheduler::{spawn_limited, LimitExceeded};
+///
+/// #[coro]
+/// fn handle_connection(stream: TcpStream) {
+///     // ...
+/// }
+///
+/// #[coro]
+/// fn on_connection(stream: TcpStream) {
+///     let res: Result<(), LimitExceeded> = yield spawn_limited(handle_connection(stream, std::ptr::null_mut()));
+/// }
+/// ```
+pub fn spawn_limited(func: CoroutineImpl, res: *mut Result<(), LimitExceeded>) -> YieldStatus {
+    let scheduler = local_scheduler();
+    if scheduler.is_over_limits() && scheduler.coroutine_limits().map(|limits| limits.policy) == Some(LimitPolicy::Wait) {
+        return YieldStatus::wait_capacity(func, res);
+    }
+
+    YieldStatus::ready(res, scheduler.try_spawn(func))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::ptr::null_mut;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::scheduler::{local_scheduler, spawn_limited, CoroutineLimits, LimitExceeded, LimitPolicy};
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn sleeper(started: Rc<Cell<u32>>) {
+        started.set(started.get() + 1);
+        yield sleep(Duration::from_millis(2));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_reject_and_drop_oldest() {
+        let scheduler = local_scheduler();
+        let started = Rc::new(Cell::new(0));
+        let before = scheduler.metrics();
+        scheduler.set_coroutine_limits(Some(CoroutineLimits::new(usize::MAX, 2, usize::MAX, LimitPolicy::Reject)));
+        for _ in 0..3 {
+            scheduler.spawn(sleeper(started.clone(), null_mut()));
+        }
+        yield sleep(Duration::ZERO);
+        assert_eq!(scheduler.try_spawn(sleeper(started.clone(), null_mut())), Err(LimitExceeded));
+
+        scheduler.set_coroutine_limits(Some(CoroutineLimits::new(usize::MAX, 2, usize::MAX, LimitPolicy::DropOldest { backlog: 2 })));
+        for _ in 0..3 {
+            assert_eq!(scheduler.try_spawn(sleeper(started.clone(), null_mut())), Ok(()));
+        }
+        assert_eq!(started.get(), 3);
+        yield sleep(Duration::from_millis(10));
+
+        // One is rejected, one is dropped from the backlog and two are spawned from the backlog, when the sleepers are done.
+        assert_eq!(started.get(), 5);
+        assert_eq!(scheduler.metrics().shed_total, before.shed_total + 2);
+        scheduler.set_coroutine_limits(None);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_wait() {
+        let scheduler = local_scheduler();
+        let started = Rc::new(Cell::new(0));
+        scheduler.set_coroutine_limits(Some(CoroutineLimits::new(usize::MAX, 2, usize::MAX, LimitPolicy::Wait)));
+        for _ in 0..4 {
+            let res: Result<(), LimitExceeded> = yield spawn_limited(sleeper(started.clone(), null_mut()));
+            res.unwrap();
+            yield sleep(Duration::ZERO);
+            assert!(scheduler.metrics().sleeping <= 2, "the spawner was not suspended");
+        }
+        assert_eq!(started.get(), 4);
+        scheduler.set_coroutine_limits(None);
+    }
+}
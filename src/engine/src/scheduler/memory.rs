@@ -0,0 +1,150 @@
+//! This module contains [`MemoryUsage`] and the soft memory limit of the worker.
+use std::fmt::{Debug, Formatter};
+
+/// How often (in background ticks) the memory usage is checked against the soft limit.
+/// Counting coroutines walks the queues, so it is not done on every tick.
+const CHECK_INTERVAL: u32 = 16;
+
+/// Approximate memory, that is held by the worker.
+///
+/// Read [`Scheduler::memory_usage`](crate::scheduler::Scheduler::memory_usage) for more information.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of [`Buffer`](crate::buf::Buffer)s of the [`BufPool`](crate::buf::BufPool): pooled, reserved and in use.
+    pub buffers: usize,
+    /// Bytes of ready, idle and sleeping coroutines.
+    pub coroutines: usize,
+    /// The number of ready, idle and sleeping coroutines.
+    pub coroutine_count: usize
+}
+
+impl MemoryUsage {
+    /// Returns the total number of bytes.
+    #[inline(always)]
+    pub fn total(&self) -> usize {
+        self.buffers + self.coroutines
+    }
+}
+
+/// The callback of the soft memory limit. It gets true, when the limit is exceeded, and false, when the usage is back under it.
+pub type MemoryLimitCallback = Box<dyn Fn(bool, MemoryUsage)>;
+
+/// The soft memory limit of the worker.
+///
+/// The limit is exceeded, when the total usage is above it, and it is recovered, when the usage is not above 90% of it,
+/// so the flag doesn't flap around a single value.
+pub(crate) struct MemoryWatch {
+    limit: Option<usize>,
+    is_exceeded: bool,
+    ticks: u32,
+    callback: Option<MemoryLimitCallback>
+}
+
+impl MemoryWatch {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self { limit, is_exceeded: false, ticks: 0, callback: None }
+    }
+
+    #[inline(always)]
+    pub(crate) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Sets the limit. The flag is reset, so it is checked against the new limit.
+    pub(crate) fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        self.is_exceeded = false;
+        self.ticks = 0;
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.is_exceeded
+    }
+
+    pub(crate) fn set_callback(&mut self, callback: Option<MemoryLimitCallback>) {
+        self.callback = callback;
+    }
+
+    /// Returns true, if the usage must be checked on this tick.
+    #[inline(always)]
+    pub(crate) fn should_check(&mut self) -> bool {
+        if self.limit.is_none() {
+            return false;
+        }
+        self.ticks = self.ticks.wrapping_add(1);
+        self.ticks % CHECK_INTERVAL == 1
+    }
+
+    /// Updates the flag with the usage and calls the callback, if the flag is changed.
+    pub(crate) fn update(&mut self, usage: MemoryUsage) {
+        let Some(limit) = self.limit else { return };
+        let is_exceeded = if self.is_exceeded {
+            usage.total() > limit / 10 * 9
+        } else {
+            usage.total() > limit
+        };
+        if is_exceeded == self.is_exceeded {
+            return;
+        }
+
+        self.is_exceeded = is_exceeded;
+        if let Some(callback) = &self.callback {
+            callback(is_exceeded, usage);
+        }
+    }
+}
+
+impl Debug for MemoryWatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryWatch")
+            .field("limit", &self.limit)
+            .field("is_exceeded", &self.is_exceeded)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ptr::null_mut;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::scheduler::{local_scheduler, MemoryUsage};
+    use crate::sleep::sleep;
+
+    #[coro(crate="crate")]
+    fn sleeper() {
+        yield sleep(Duration::from_millis(1));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_soft_memory_limit() {
+        let scheduler = local_scheduler();
+        // The running coroutine is not in the queues, so another one is queued to be counted.
+        scheduler.sched(sleeper(null_mut()));
+        let usage = scheduler.memory_usage();
+        assert!(usage.coroutine_count > 0);
+        assert_eq!(usage.total(), usage.buffers + usage.coroutines);
+
+        // The callback lives until the scheduler is dropped, so it doesn't capture a Local.
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_ = events.clone();
+        scheduler.on_memory_limit(move |is_exceeded, _usage: MemoryUsage| events_.borrow_mut().push(is_exceeded));
+        scheduler.set_soft_memory_limit(Some(1));
+        for _ in 0..100 {
+            if scheduler.is_over_memory_limit() {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert!(scheduler.is_over_memory_limit());
+
+        scheduler.set_soft_memory_limit(Some(usize::MAX));
+        yield sleep(Duration::from_millis(1));
+        assert!(!scheduler.is_over_memory_limit());
+        assert_eq!(*events.borrow(), vec![true]);
+        scheduler.set_soft_memory_limit(None);
+    }
+}
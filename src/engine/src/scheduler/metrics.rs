@@ -0,0 +1,146 @@
+//! This module contains [`SchedulerMetrics`] and [`report_metrics`], the coroutine, that reports them periodically.
+use std::time::{Duration, Instant};
+use crate::coro;
+use crate::io::FileOpSupport;
+use crate::scheduler::local_scheduler;
+use crate::sleep::interval;
+
+/// The counters of one worker. They are returned by [`Scheduler::metrics`](crate::scheduler::Scheduler::metrics).
+///
+/// Counters are maintained by the scheduler as plain integers, and gauges are read from the queues on the call,
+/// so taking the metrics costs a few loads and can be done as often as needed.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::local_scheduler;
+///
+/// let metrics = local_scheduler().metrics();
+/// println!("{} ready, {} sleeping, {} waiting for IO", metrics.ready, metrics.sleeping, metrics.pending_states);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SchedulerMetrics {
+    /// The number of coroutines, that were spawned on the worker since its start, including the main one.
+    pub spawned_total: u64,
+    /// The number of spawned coroutines, that are completed.
+    pub completed_total: u64,
+    /// The number of spawned coroutines, that were dropped by a panic.
+    pub panicked_total: u64,
+    /// The number of coroutines, that were rejected or dropped without being started because of the
+    /// [`CoroutineLimits`](crate::scheduler::CoroutineLimits). They are not counted in `spawned_total`.
+    pub shed_total: u64,
+    /// The number of times, coroutines were run: every start and every wake up is counted.
+    pub resumed_total: u64,
+    /// The number of iterations of the background work: polls of the selector, wake-ups of timers and so on.
+    /// Read [`set_poll_interval`](crate::cfg::set_poll_interval).
+    pub polls_total: u64,
+    /// The number of ready coroutines in the run queue.
+    pub ready: usize,
+    /// The number of idle coroutines, that are not started yet.
+    pub idle: usize,
+    /// The number of sleeping coroutines.
+    pub sleeping: usize,
+    /// The number of [`PollState`](crate::io::PollState)s, that the selector holds: registered fds and operations in flight.
+    /// It is updated after every poll of the selector.
+    pub pending_states: usize,
+    /// The number of entries in the submission backlog of io_uring. It is zero for other selectors.
+    /// Read [`SubmissionStats`](crate::io::SubmissionStats) for more information.
+    pub submission_backlog: usize,
+    /// The number of times, the completion queue of io_uring had overflowed. It is zero for other selectors.
+    /// Read [`SubmissionStats`](crate::io::SubmissionStats) for more information.
+    pub cq_overflows: u64,
+    /// The file operations, that the selector handles. io_uring of an old kernel doesn't support some of them.
+    /// Read [`Selector::file_op_support`](crate::io::Selector::file_op_support).
+    pub file_op_support: FileOpSupport,
+    /// The number of file operations, that were run on the blocking pool, because the selector doesn't support them.
+    pub offloaded_file_ops: u64
+}
+
+impl SchedulerMetrics {
+    /// Returns the number of spawned coroutines, that are not completed and have not panicked.
+    #[inline(always)]
+    pub fn running(&self) -> u64 {
+        self.spawned_total - self.completed_total - self.panicked_total
+    }
+}
+
+/// Calls `report` with the [`SchedulerMetrics`] of the current worker every `period`, until the worker is stopped.
+///
+/// Start it with [`Scheduler::sched`](crate::scheduler::Scheduler::sched), so it doesn't keep the worker alive.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::ptr::null_mut;
+/// use std::time::Duration;
+/// use engine::local_scheduler;
+/// use engine::scheduler::report_metrics;
+///
+/// local_scheduler().sched(report_metrics(Duration::from_secs(10), |metrics| println!("{:?}", metrics), null_mut()));
+/// ```
+#[coro(crate="crate")]
+pub fn report_metrics<F: FnMut(SchedulerMetrics) + 'static>(period: Duration, report: F) {
+    let mut report = report;
+    let mut ticker = interval(period);
+    // The first tick completes immediately, but the metrics of the first period are more useful.
+    let _: Instant = yield ticker.tick();
+    loop {
+        let _: Instant = yield ticker.tick();
+        report(local_scheduler().metrics());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ptr::null_mut;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::coroutine::yield_now;
+    use crate::scheduler::{local_scheduler, report_metrics, SchedulerMetrics};
+    use crate::sleep::sleep;
+
+    #[test_local(crate="crate")]
+    fn test_metrics() {
+        #[coro(crate="crate")]
+        fn sleeper() {
+            yield sleep(Duration::from_millis(5));
+        }
+
+        #[coro(crate="crate")]
+        fn yielding() {
+            yield yield_now();
+        }
+
+        let scheduler = local_scheduler();
+        let before = scheduler.metrics();
+        assert!(before.running() >= 1);
+
+        for _ in 0..3 {
+            scheduler.spawn(sleeper(null_mut()));
+        }
+        scheduler.spawn(yielding(null_mut()));
+        let metrics = scheduler.metrics();
+        assert_eq!(metrics.spawned_total, before.spawned_total + 4);
+        assert!(metrics.ready >= 4);
+
+        yield sleep(Duration::from_millis(1));
+        let metrics = scheduler.metrics();
+        assert_eq!(metrics.sleeping, 3);
+        assert_eq!(metrics.completed_total, before.completed_total + 1);
+        assert!(metrics.resumed_total > before.resumed_total);
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reports_ = reports.clone();
+        scheduler.sched(report_metrics(Duration::from_millis(2), move |metrics: SchedulerMetrics| {
+            reports_.borrow_mut().push(metrics);
+        }, null_mut()));
+        yield sleep(Duration::from_millis(10));
+
+        let reports = reports.borrow();
+        assert!(reports.len() >= 2);
+        assert_eq!(reports.last().unwrap().completed_total, before.completed_total + 4);
+        assert_eq!(scheduler.metrics().running(), before.running());
+    }
+}
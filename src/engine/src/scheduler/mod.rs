@@ -1,3 +1,31 @@
 pub(crate) mod scheduler;
+pub mod handler_pool;
+pub mod extension;
+pub mod handle;
+pub mod idle;
+pub mod trace;
+pub mod overload;
+pub mod warmup;
+pub mod injection;
+pub mod limits;
+pub mod memory;
+pub mod metrics;
+pub mod panic;
+pub mod work_stealing;
+pub mod priority;
+pub(crate) mod blocking_pool;
 
-pub use scheduler::{Scheduler, local_scheduler, LOCAL_SCHEDULER};
\ No newline at end of file
+pub use scheduler::{Scheduler, local_scheduler, LOCAL_SCHEDULER};
+pub use handler_pool::{HandlerPool, Parked};
+pub use extension::{ExtensionHandler, ExtensionId};
+pub use handle::SchedulerHandle;
+pub use idle::IdleStrategy;
+pub use trace::{QueueSample, QUEUE_TRACE_CAPACITY};
+pub use overload::OverloadProtection;
+pub use warmup::AcceptWarmup;
+pub use limits::{spawn_limited, CoroutineLimits, LimitExceeded, LimitPolicy};
+pub use memory::{MemoryUsage, MemoryLimitCallback};
+pub use metrics::{report_metrics, SchedulerMetrics};
+pub use panic::{CoroutinePanic, PanicHook};
+pub use injection::{injector, spawn_on, spawn_global, CoroutineCreator, Injector};
+pub use priority::{Priority, STARVATION_LIMIT};
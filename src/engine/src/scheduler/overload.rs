@@ -0,0 +1,113 @@
+//! This module contains [`OverloadProtection`].
+
+/// Thresholds of the built-in overload protection.
+///
+/// When the run queue or the number of [`Buffer`](crate::buf::Buffer)s in use
+/// (read [`BufPool::in_use`](crate::buf::BufPool::in_use)) is above the pause threshold,
+/// the worker stops accepting new connections: the accept states are unregistered from the selector,
+/// and the pending connections wait in the backlog of the listener.
+/// Accepting is resumed, when both signals are not above the resume thresholds.
+///
+/// The gap between the thresholds prevents flapping around a single value.
+///
+/// Set it with [`set_overload_protection`](crate::cfg::set_overload_protection) before the start
+/// or with [`Scheduler::set_overload_protection`](crate::scheduler::Scheduler::set_overload_protection) for the current worker.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::cfg::set_overload_protection;
+/// use engine::scheduler::OverloadProtection;
+///
+/// // Pause accepting at 10_000 ready coroutines or 50_000 buffers in use, resume at a half.
+/// set_overload_protection(Some(OverloadProtection::new(10_000, 50_000)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OverloadProtection {
+    /// Accepting is paused, when the run queue is longer than it.
+    pub pause_task_queue_len: usize,
+    /// Accepting is resumed, when the run queue is not longer than it.
+    pub resume_task_queue_len: usize,
+    /// Accepting is paused, when more buffers than it are in use.
+    pub pause_buffers_in_use: usize,
+    /// Accepting is resumed, when not more buffers than it are in use.
+    pub resume_buffers_in_use: usize
+}
+
+impl OverloadProtection {
+    /// Creates a new [`OverloadProtection`] with the given pause thresholds. Resume thresholds are a half of them.
+    pub const fn new(max_task_queue_len: usize, max_buffers_in_use: usize) -> Self {
+        Self {
+            pause_task_queue_len: max_task_queue_len,
+            resume_task_queue_len: max_task_queue_len / 2,
+            pause_buffers_in_use: max_buffers_in_use,
+            resume_buffers_in_use: max_buffers_in_use / 2
+        }
+    }
+
+    /// Returns true, if accepting must be paused.
+    #[inline(always)]
+    pub(crate) fn is_overloaded(&self, task_queue_len: usize, buffers_in_use: usize) -> bool {
+        task_queue_len > self.pause_task_queue_len || buffers_in_use > self.pause_buffers_in_use
+    }
+
+    /// Returns true, if paused accepting can be resumed.
+    #[inline(always)]
+    pub(crate) fn is_relieved(&self, task_queue_len: usize, buffers_in_use: usize) -> bool {
+        task_queue_len <= self.resume_task_queue_len && buffers_in_use <= self.resume_buffers_in_use
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::net::SocketAddr;
+    use std::os::fd::IntoRawFd;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::buf::{buffer, Buffer};
+    use crate::local::Local;
+    use crate::net::{TcpListener, TcpStream};
+    use crate::scheduler::{local_scheduler, OverloadProtection};
+    use crate::sleep::sleep;
+
+    #[test_local(crate="crate")]
+    fn test_pause_accept() {
+        #[coro(crate="crate")]
+        fn accept_one(mut listener: TcpListener, accepted: Local<bool>) {
+            let res: Result<TcpStream, Error> = yield listener.accept();
+            res.unwrap();
+            *accepted.get_mut() = true;
+        }
+
+        let scheduler = local_scheduler();
+        scheduler.set_overload_protection(Some(OverloadProtection::new(usize::MAX, 4)));
+
+        let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        std_listener.set_nonblocking(true).unwrap();
+        let addr: SocketAddr = std_listener.local_addr().unwrap();
+        let listener = TcpListener::from_fd(std_listener.into_raw_fd());
+        let held: Vec<Buffer> = (0..10).map(|_| buffer()).collect();
+        let accepted = Local::new(false);
+        scheduler.sched(accept_one(listener, accepted.clone(), null_mut()));
+
+        let client = std::thread::spawn(move || std::net::TcpStream::connect(addr).unwrap());
+        yield sleep(Duration::from_millis(20));
+        assert!(scheduler.is_accept_paused());
+        assert!(!*accepted.get(), "the connection was accepted under pressure");
+
+        drop(held);
+        for _ in 0..100 {
+            if *accepted.get() {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert!(*accepted.get());
+        assert!(!scheduler.is_accept_paused());
+
+        scheduler.set_overload_protection(None);
+        client.join().unwrap();
+    }
+}
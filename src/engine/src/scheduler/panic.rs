@@ -0,0 +1,104 @@
+//! This module contains [`CoroutinePanic`] and [`PanicHook`], the hook, that is called, when a coroutine panics.
+use std::any::Any;
+
+/// The description of a panic of a coroutine. It is passed to the [`PanicHook`].
+///
+/// The message is already printed by the panic hook of the standard library,
+/// so the [`PanicHook`] is the place to count panics, report them or stop the service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoroutinePanic {
+    /// The id of the worker, that has run the coroutine.
+    pub worker_id: usize,
+    /// The message of the panic or `Box<dyn Any>`, if the payload is not a string.
+    pub message: String
+}
+
+impl CoroutinePanic {
+    pub(crate) fn new(worker_id: usize, payload: &(dyn Any + Send)) -> Self {
+        let message = match payload.downcast_ref::<&'static str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "Box<dyn Any>".to_string()
+            }
+        };
+        Self { worker_id, message }
+    }
+}
+
+/// The hook, that is called by the worker after a coroutine has panicked.
+///
+/// Set it with [`set_panic_hook`](crate::cfg::set_panic_hook) before the start
+/// or with [`Scheduler::set_panic_hook`](crate::scheduler::Scheduler::set_panic_hook) for the current worker.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::cfg::set_panic_hook;
+/// use engine::scheduler::CoroutinePanic;
+///
+/// fn report(panic: &CoroutinePanic) {
+///     eprintln!("a coroutine on the worker {} panicked: {}", panic.worker_id, panic.message);
+/// }
+///
+/// set_panic_hook(Some(report));
+/// ```
+pub type PanicHook = fn(&CoroutinePanic);
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::coroutine::JoinHandle;
+    use crate::local::Local;
+    use crate::scheduler::{local_scheduler, CoroutinePanic};
+    use crate::sleep::sleep;
+
+    thread_local! {
+        static PANICS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(panic: &CoroutinePanic) {
+        PANICS.with(|panics| panics.borrow_mut().push(panic.message.clone()));
+    }
+
+    #[coro(crate="crate")]
+    fn panicking(after: Duration) -> usize {
+        yield sleep(after);
+        panic!("the test panic");
+    }
+
+    #[coro(crate="crate")]
+    fn waiting_parent(done: Local<bool>) {
+        let mut child = JoinHandle::spawn(|res| panicking(Duration::from_millis(1), res));
+        let _: usize = yield child.wait();
+        *done.get_mut() = true;
+    }
+
+    #[test_local(crate="crate")]
+    fn test_panic_isolation() {
+        let scheduler = local_scheduler();
+        scheduler.set_isolate_panics(true);
+        scheduler.set_panic_hook(Some(record));
+        let before = scheduler.metrics();
+
+        scheduler.spawn(panicking(Duration::ZERO, null_mut()));
+        // The parent of a panicked child panics too, instead of waiting forever.
+        let done = Local::new(false);
+        scheduler.spawn(waiting_parent(done.clone(), null_mut()));
+        yield sleep(Duration::from_millis(5));
+
+        let metrics = scheduler.metrics();
+        assert!(!*done.get());
+        assert_eq!(metrics.panicked_total, before.panicked_total + 3);
+        assert_eq!(metrics.running(), before.running());
+        let panics = PANICS.with(|panics| panics.take());
+        assert_eq!(panics.iter().filter(|message| *message == "the test panic").count(), 2);
+        assert!(panics.contains(&"the child coroutine panicked".to_string()));
+
+        scheduler.set_panic_hook(None);
+        scheduler.set_isolate_panics(false);
+    }
+}
@@ -0,0 +1,234 @@
+//! This module contains [`Priority`] and the queues of ready coroutines of the [`Scheduler`](crate::scheduler::Scheduler).
+use std::collections::VecDeque;
+use crate::cfg::SchedulingPolicy;
+
+/// How many coroutines of a higher priority can run in a row, while coroutines of a lower priority are ready.
+/// After that one coroutine of a lower priority runs, so it is not starved.
+pub const STARVATION_LIMIT: u32 = 32;
+
+/// The priority of a coroutine. Read [`spawn_local_with_priority!`](crate::spawn_local_with_priority).
+///
+/// Ready coroutines of a higher priority run first, but after [`STARVATION_LIMIT`] coroutines in a row
+/// one coroutine of a lower priority runs.
+///
+/// The priority is kept, when the coroutine yields, so it is suitable for latency-critical loops, like an accept loop.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High
+}
+
+/// Queues of ready coroutines, one per [`Priority`].
+///
+/// Every queue is ordered by the [`SchedulingPolicy`]: in the LIFO modes new coroutines are pushed to the back
+/// and popped from the back, and yielded coroutines are pushed to the front. In the FIFO mode all coroutines are pushed
+/// to the back and popped from the front.
+pub(crate) struct PriorityQueues<T> {
+    high: VecDeque<T>,
+    normal: VecDeque<T>,
+    low: VecDeque<T>,
+    policy: SchedulingPolicy,
+    /// How many items were popped from the back in a row. It is used by [`SchedulingPolicy::LifoWithBudget`].
+    lifo_streak: usize,
+    /// How many high-priority items were popped in a row.
+    high_streak: u32,
+    /// How many normal-priority items were popped in a row.
+    normal_streak: u32
+}
+
+impl<T> PriorityQueues<T> {
+    pub(crate) fn new(policy: SchedulingPolicy) -> Self {
+        Self {
+            high: VecDeque::new(),
+            normal: VecDeque::with_capacity(8),
+            low: VecDeque::new(),
+            policy,
+            lifo_streak: 0,
+            high_streak: 0,
+            normal_streak: 0
+        }
+    }
+
+    #[inline(always)]
+    fn queue_mut(&mut self, priority: Priority) -> &mut VecDeque<T> {
+        match priority {
+            Priority::High => &mut self.high,
+            Priority::Normal => &mut self.normal,
+            Priority::Low => &mut self.low
+        }
+    }
+
+    /// Sets the [`SchedulingPolicy`]. Already queued items keep their places.
+    pub(crate) fn set_policy(&mut self, policy: SchedulingPolicy) {
+        self.policy = policy;
+        self.lifo_streak = 0;
+    }
+
+    /// Pushes the new item. In the LIFO modes it is popped before the other items of the same priority.
+    #[inline(always)]
+    pub(crate) fn push_new(&mut self, priority: Priority, item: T) {
+        self.queue_mut(priority).push_back(item);
+    }
+
+    /// Pushes the yielded item. It is popped after the other items of the same priority.
+    #[inline(always)]
+    pub(crate) fn push_yielded(&mut self, priority: Priority, item: T) {
+        if self.policy == SchedulingPolicy::Fifo {
+            self.queue_mut(priority).push_back(item);
+        } else {
+            self.queue_mut(priority).push_front(item);
+        }
+    }
+
+    /// Pops the item of the highest priority, unless a lower priority has waited for [`STARVATION_LIMIT`] pops.
+    #[inline(always)]
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        if !self.high.is_empty() && (self.high_streak < STARVATION_LIMIT || (self.normal.is_empty() && self.low.is_empty())) {
+            self.high_streak += 1;
+            return pop_by_policy(&mut self.high, self.policy, &mut self.lifo_streak);
+        }
+        self.high_streak = 0;
+
+        if !self.normal.is_empty() && (self.normal_streak < STARVATION_LIMIT || self.low.is_empty()) {
+            self.normal_streak += 1;
+            return pop_by_policy(&mut self.normal, self.policy, &mut self.lifo_streak);
+        }
+        self.normal_streak = 0;
+
+        pop_by_policy(&mut self.low, self.policy, &mut self.lifo_streak)
+    }
+
+    /// Returns the number of items of all priorities.
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.high.len() + self.normal.len() + self.low.len()
+    }
+
+    /// Returns the number of items of the priority.
+    pub(crate) fn len_of(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::High => self.high.len(),
+            Priority::Normal => self.normal.len(),
+            Priority::Low => self.low.len()
+        }
+    }
+
+    /// Moves the first item, that matches, from the queue of a lower priority to the queue of the `priority`.
+    /// It is popped after the other items of the `priority`. Returns false, if no such item is queued.
+    pub(crate) fn promote<F: Fn(&T) -> bool>(&mut self, priority: Priority, matches: F) -> bool {
+        for lower in [Priority::Low, Priority::Normal] {
+            if lower >= priority {
+                break;
+            }
+            let queue = self.queue_mut(lower);
+            if let Some(index) = queue.iter().position(&matches) {
+                let item = queue.remove(index).unwrap();
+                self.push_yielded(priority, item);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.high.is_empty() && self.normal.is_empty() && self.low.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.high.iter().chain(self.normal.iter()).chain(self.low.iter())
+    }
+}
+
+/// Pops the item of the queue in the order of the [`SchedulingPolicy`].
+#[inline(always)]
+fn pop_by_policy<T>(queue: &mut VecDeque<T>, policy: SchedulingPolicy, lifo_streak: &mut usize) -> Option<T> {
+    match policy {
+        SchedulingPolicy::Lifo => queue.pop_back(),
+        SchedulingPolicy::Fifo => queue.pop_front(),
+        SchedulingPolicy::LifoWithBudget { n } => {
+            if *lifo_streak >= n {
+                *lifo_streak = 0;
+                queue.pop_front()
+            } else {
+                *lifo_streak += 1;
+                queue.pop_back()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::SchedulingPolicy;
+    use crate::scheduler::priority::{Priority, PriorityQueues, STARVATION_LIMIT};
+
+    #[test]
+    fn test_priority_queues() {
+        let mut queues = PriorityQueues::new(SchedulingPolicy::Lifo);
+        queues.push_new(Priority::Low, 0);
+        queues.push_new(Priority::Normal, 1);
+        queues.push_new(Priority::High, 2);
+        queues.push_yielded(Priority::High, 3);
+        assert_eq!(queues.len(), 4);
+        assert_eq!(queues.len_of(Priority::High), 2);
+
+        assert_eq!(queues.pop(), Some(2));
+        assert_eq!(queues.pop(), Some(3));
+        assert_eq!(queues.pop(), Some(1));
+        assert_eq!(queues.pop(), Some(0));
+        assert_eq!(queues.pop(), None);
+        assert!(queues.is_empty());
+    }
+
+    #[test]
+    fn test_scheduling_policies() {
+        let pop_all = |policy| {
+            let mut queues = PriorityQueues::new(policy);
+            queues.push_yielded(Priority::Normal, 0);
+            for i in 1..6 {
+                queues.push_new(Priority::Normal, i);
+            }
+            std::iter::from_fn(|| queues.pop()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(pop_all(SchedulingPolicy::Lifo), vec![5, 4, 3, 2, 1, 0]);
+        assert_eq!(pop_all(SchedulingPolicy::Fifo), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(pop_all(SchedulingPolicy::LifoWithBudget { n: 2 }), vec![5, 4, 0, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_promote() {
+        let mut queues = PriorityQueues::new(SchedulingPolicy::Fifo);
+        queues.push_new(Priority::High, 0);
+        queues.push_new(Priority::Normal, 1);
+        queues.push_new(Priority::Low, 2);
+        queues.push_new(Priority::Low, 3);
+
+        assert!(queues.promote(Priority::High, |&item| item == 3));
+        assert!(!queues.promote(Priority::Normal, |&item| item == 0));
+        assert!(!queues.promote(Priority::High, |&item| item == 4));
+        assert_eq!(queues.len_of(Priority::High), 2);
+        assert_eq!(std::iter::from_fn(|| queues.pop()).collect::<Vec<_>>(), vec![0, 3, 1, 2]);
+    }
+
+    #[test]
+    fn test_starvation_guard() {
+        let mut queues = PriorityQueues::new(SchedulingPolicy::Lifo);
+        for _ in 0..STARVATION_LIMIT * 2 {
+            queues.push_new(Priority::High, Priority::High);
+        }
+        queues.push_new(Priority::Normal, Priority::Normal);
+        queues.push_new(Priority::Low, Priority::Low);
+
+        let order: Vec<Priority> = std::iter::from_fn(|| queues.pop()).collect();
+        let limit = STARVATION_LIMIT as usize;
+        assert!(order[..limit].iter().all(|&p| p == Priority::High));
+        assert_eq!(order[limit], Priority::Normal);
+        assert_eq!(order[limit + 1..limit * 2 + 1].iter().filter(|&&p| p == Priority::High).count(), limit);
+        assert_eq!(order[limit * 2 + 1], Priority::Low);
+    }
+}
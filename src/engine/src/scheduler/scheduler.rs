@@ -1,22 +1,61 @@
 use std::cell::{UnsafeCell};
-use std::collections::{BTreeSet, VecDeque};
-use std::intrinsics::unlikely;
-use std::mem::{MaybeUninit, transmute};
+use std::collections::VecDeque;
+use std::intrinsics::{likely, unlikely};
+use std::mem;
+use std::mem::MaybeUninit;
+use std::ptr::null_mut;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 #[allow(unused_imports)] // compiler will complain if it's not used, but we need it for resume()
 use std::ops::{Coroutine, CoroutineState};
-use std::ptr::null_mut;
-use std::time::Instant;
-use proc::coro;
-use crate::cfg::{config_selector, SelectorType};
+use std::time::{Duration, Instant};
+use crate::cfg::{config_accept_warmup, config_blocking_threads, config_coroutine_limits, config_idle_strategy, config_isolate_panics, config_overload_protection, config_panic_hook, config_poll_interval, config_scheduling_policy, config_selector, config_soft_memory_limit, config_timer_tick, config_work_stealing, SchedulingPolicy, SelectorType};
 use crate::coroutine::coroutine::{CoroutineImpl};
-use crate::coroutine::{end, yield_now, YieldStatus};
+use crate::coroutine::YieldStatus;
+#[cfg(target_os = "linux")]
 use crate::io::sys::unix::{EpolledSelector, IoUringSelector};
-use crate::io::{Selector, PollState};
+#[cfg(target_os = "linux")]
+use crate::cfg::config_sandbox;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+use crate::io::sys::bsd::KqueueSelector;
+use crate::io::{BlockingState, FileOpSupport, Selector, PollState};
+#[cfg(target_os = "linux")]
+use crate::io::submission_stats;
 use crate::net::{TcpListener};
-use crate::{write_err};
-use crate::run::uninit;
-use crate::sleep::SleepingCoroutine;
+use crate::{write_err, write_ok};
+use crate::run::{uninit, RunError};
+use crate::local::task_local::TaskLocals;
+use crate::buf::{buf_pool, buffer, Buffer};
+use crate::fs::DIRECT_IO_ALIGN;
+use crate::sleep::{SleepingCoroutine, Timers};
 use crate::utils::Ptr;
+use crate::scheduler::extension::{ExtensionHandler, ExtensionId};
+use crate::scheduler::trace::{QueueSample, QueueTrace};
+use crate::scheduler::overload::OverloadProtection;
+use crate::scheduler::warmup::{AcceptWarmup, WarmupLimiter};
+use crate::scheduler::blocking_pool::BlockingPool;
+use crate::scheduler::blocking_pool::file_op::{finish_file_op, FileOp};
+use crate::scheduler::injection::{self, Injector};
+use crate::scheduler::handle::{listen_wakeups, SchedulerHandle};
+use crate::scheduler::idle::IdleStrategy;
+use crate::scheduler::memory::{MemoryUsage, MemoryWatch};
+use crate::scheduler::metrics::SchedulerMetrics;
+use crate::scheduler::panic::{CoroutinePanic, PanicHook};
+use crate::scheduler::limits::{CoroutineLimits, LimitExceeded, LimitPolicy};
+use crate::scheduler::work_stealing;
+use crate::scheduler::priority::{Priority, PriorityQueues};
+use crate::sync::mutex::inherited_priority;
+use crate::local::{get_core_id, get_worker_id};
+
+/// How many immediate operations in a row a coroutine can complete in [`Scheduler::handle_coroutine_state`]
+/// before it is put to the queue.
+const INLINE_COMPLETION_BUDGET: usize = 64;
 
 thread_local! {
     /// [`Scheduler`] for the current thread. It can be uninitialized.
@@ -39,29 +78,144 @@ thread_local! {
 /// The FIFO approach ensures fairness. However, our goal is not to achieve fairness, but to maximize performance.
 /// The LIFO approach is more efficient because it allows us to take advantage of data from previous tasks.
 /// This is because the data is already stored in the processor cache (by the parent coroutine), so we can use it more effectively.
+///
+/// But long chains of spawned coroutines can starve earlier ones, so the order can be changed
+/// with [`set_scheduling_policy`](crate::cfg::set_scheduling_policy). Read [`SchedulingPolicy`] for more information.
+///
+/// # Priorities
+///
+/// Ready coroutines are kept in one queue per [`Priority`]. Read [`Priority`] for more information.
+///
+/// # Background work
+///
+/// The worker polls the selector, wakes up timers and starts injected coroutines between coroutines:
+/// after every [`poll_interval`](crate::cfg::set_poll_interval) coroutines and whenever no coroutine is ready.
+/// So a coroutine, that loops on [`yield_now`](crate::coroutine::yield_now), can't delay IO and timers
+/// longer than the interval, whatever the [`SchedulingPolicy`] and the priorities are.
+///
+/// # Panics of coroutines
+///
+/// A panic of a coroutine is caught, the coroutine is dropped, and the [`PanicHook`] is called,
+/// so other coroutines of the worker keep running. A parent, that waits for the panicked child
+/// with [`JoinHandle`](crate::coroutine::JoinHandle), panics too. Read [`set_isolate_panics`](Scheduler::set_isolate_panics).
 pub struct Scheduler {
-    task_queue: VecDeque<CoroutineImpl>,
-    sleeping: BTreeSet<SleepingCoroutine>,
+    task_queue: PriorityQueues<CoroutineImpl>,
+    /// The priority of the running coroutine. Yielded coroutines are put to the queue of this priority.
+    current_priority: Priority,
+    /// The address of the running coroutine. Read [`task_address`].
+    running: usize,
+    /// The task-local values of the running coroutine. Read [`TaskLocalKey`](crate::local::TaskLocalKey).
+    task_locals: TaskLocals,
+    idle_queue: VecDeque<CoroutineImpl>,
+    /// Whether the running coroutine is idle. Yielded idle coroutines are put back to the idle queue. Read [`as_idle`].
+    is_idle: bool,
+    /// Whether the running coroutine is started by [`run_idle`](Scheduler::run_idle). Idle coroutines,
+    /// that are woken up by something else, go back to the idle queue without running.
+    is_idle_turn: bool,
+    sleeping: Timers,
+    extensions: Vec<ExtensionHandler>,
+    trace: QueueTrace,
+    /// The number of calls of [`handle_coroutine_state`](Scheduler::handle_coroutine_state). It is used to count completions.
+    handled: u64,
+    overload_protection: Option<OverloadProtection>,
+    is_accept_paused: bool,
+    /// Accept states, that are not registered in the selector, because accepting is paused.
+    paused_accepts: Vec<Ptr<PollState>>,
+    accept_warmup: Option<WarmupLimiter>,
+    /// Accept states, that wait for a token of the [`AcceptWarmup`].
+    throttled_accepts: VecDeque<Ptr<PollState>>,
 
-    //blocking_pool: BlockingPool,
-    //ready_coroutines: Vec<CoroutineImpl>
+    blocking_pool: BlockingPool,
+    /// The file operations, that the selector handles. Others are run on the blocking pool.
+    /// Read [`Selector::file_op_support`].
+    file_op_support: FileOpSupport,
+    /// The number of file operations, that were run on the blocking pool, because the selector doesn't support them.
+    offloaded_file_ops: u64,
+    ready_coroutines: Vec<CoroutineImpl>,
+    /// The queue of coroutines, that are sent by other threads.
+    injector: Injector,
+    /// The handle of the worker, if it was requested. Read [`Scheduler::handle`].
+    handle: Option<SchedulerHandle>,
+    /// Whether the worker steals not started coroutines of other workers, when it is idle.
+    work_stealing: bool,
+    memory_watch: MemoryWatch,
+    /// The number of spawned coroutines, that are not completed. The worker stops, when it becomes zero.
+    spawned: usize,
+    spawned_total: u64,
+    completed_total: u64,
+    /// The number of spawned coroutines, that were dropped by a panic.
+    panicked_total: u64,
+    isolate_panics: bool,
+    panic_hook: Option<PanicHook>,
+    /// The number of [`PollState`]s, that the selector held at the last poll. Read [`Selector::pending_states`].
+    pending_states: usize,
+    /// The maximum number of coroutines, that are run between two iterations of the background work.
+    poll_interval: u32,
+    /// The number of iterations of the background work.
+    polls_total: u64,
+    coroutine_limits: Option<CoroutineLimits>,
+    /// Coroutines, that wait for the room under the limits with [`LimitPolicy::DropOldest`]. They are not started yet.
+    spawn_backlog: VecDeque<CoroutineImpl>,
+    /// Spawners, that wait for the room under the limits with [`LimitPolicy::Wait`], with the coroutines to spawn
+    /// and the priorities of the spawners.
+    capacity_waiters: VecDeque<(CoroutineImpl, CoroutineImpl, *mut Result<(), LimitExceeded>, Priority)>,
+    /// The number of coroutines, that were rejected or dropped because of the limits.
+    shed_total: u64,
+    idle_strategy: IdleStrategy,
+    /// The number of polls in a row, that found no work. Read [`IdleStrategy`].
+    idle_polls: u32
 }
 
 impl Scheduler {
     /// Initializes the [`Scheduler`] in the [`LOCAL_SCHEDULER`]).
     pub fn init() {
+        let injector = injection::register(get_core_id());
         let scheduler = Self {
-            task_queue: VecDeque::with_capacity(8),
-            sleeping: BTreeSet::new(),
+            task_queue: PriorityQueues::new(config_scheduling_policy()),
+            current_priority: Priority::Normal,
+            running: 0,
+            task_locals: TaskLocals::new(),
+            idle_queue: VecDeque::new(),
+            is_idle: false,
+            is_idle_turn: false,
+            sleeping: Timers::new(config_timer_tick()),
+            extensions: Vec::new(),
+            trace: QueueTrace::new(),
+            handled: 0,
+            overload_protection: config_overload_protection(),
+            is_accept_paused: false,
+            paused_accepts: Vec::new(),
+            accept_warmup: config_accept_warmup().map(|warmup| WarmupLimiter::new(warmup, Instant::now())),
+            throttled_accepts: VecDeque::new(),
 
-            //blocking_pool: BlockingPool::new(),
-            //ready_coroutines: Vec::with_capacity(8)
+            blocking_pool: BlockingPool::new(config_blocking_threads(), injector.clone()),
+            file_op_support: FileOpSupport::NONE,
+            offloaded_file_ops: 0,
+            ready_coroutines: Vec::with_capacity(8),
+            injector,
+            handle: None,
+            work_stealing: config_work_stealing(),
+            memory_watch: MemoryWatch::new(config_soft_memory_limit()),
+            spawned: 0,
+            spawned_total: 0,
+            completed_total: 0,
+            panicked_total: 0,
+            isolate_panics: config_isolate_panics(),
+            panic_hook: config_panic_hook(),
+            poll_interval: config_poll_interval(),
+            polls_total: 0,
+            coroutine_limits: config_coroutine_limits(),
+            spawn_backlog: VecDeque::new(),
+            capacity_waiters: VecDeque::new(),
+            shed_total: 0,
+            idle_strategy: config_idle_strategy(),
+            idle_polls: 0,
+            pending_states: 0
         };
 
         LOCAL_SCHEDULER.with(|local| {
             unsafe {
                 *(&mut *local.get()) = MaybeUninit::new(scheduler);
-                //(&*local.get()).assume_init_ref().blocking_pool.run();
             };
         });
     }
@@ -70,6 +224,7 @@ impl Scheduler {
     pub fn uninit() {
         LOCAL_SCHEDULER.with(|local| {
             unsafe {
+                injection::unregister(&(&*local.get()).assume_init_ref().injector);
                 (&mut *local.get()).assume_init_drop();
             };
         });
@@ -78,6 +233,11 @@ impl Scheduler {
     /// Stores the [`coroutine`](CoroutineImpl) in the [`Scheduler`] to wake it up later.
     /// Use [`spawn_local`](crate::spawn_local) instead if you don't want to low-level work.
     ///
+    /// # Note
+    ///
+    /// The coroutine is not counted as spawned, so the worker can stop before it is completed.
+    /// Use [`spawn`](Scheduler::spawn) for new coroutines.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -92,7 +252,538 @@ impl Scheduler {
     /// local_scheduler().sched(say_hello("sched method", null_mut()));
     /// ```
     pub fn sched(&mut self, func: CoroutineImpl) {
-        self.task_queue.push_back(func);
+        let priority = self.queue_priority(&func, Priority::Normal);
+        self.task_queue.push_new(priority, func);
+    }
+
+    /// Puts the yielded coroutine to the queue of its priority.
+    #[inline(always)]
+    fn push_yielded(&mut self, task: CoroutineImpl) {
+        if unlikely(self.is_idle) {
+            self.idle_queue.push_back(task);
+            return;
+        }
+
+        let priority = self.queue_priority(&task, self.current_priority);
+        self.task_queue.push_yielded(priority, task);
+    }
+
+    /// Returns the priority of the queue for the coroutine: the `priority` or the priority,
+    /// that the coroutine inherits from the waiters of a [`Mutex`](crate::sync::Mutex), that it holds, if it is higher.
+    #[inline(always)]
+    fn queue_priority(&self, task: &CoroutineImpl, priority: Priority) -> Priority {
+        inherited_priority(task_address(task), priority)
+    }
+
+    /// Returns the priority of the running coroutine.
+    #[inline(always)]
+    pub(crate) fn current_priority(&self) -> Priority {
+        self.current_priority
+    }
+
+    /// Returns the address of the running coroutine. It doesn't change, while the coroutine is queued.
+    #[inline(always)]
+    pub(crate) fn running(&self) -> usize {
+        self.running
+    }
+
+    /// Moves the ready coroutine with the `address` to the queue of the `priority`, if it is queued with a lower priority.
+    /// It is used, when a waiter of a [`Mutex`](crate::sync::Mutex) raises the priority of the holder.
+    pub(crate) fn promote(&mut self, address: usize, priority: Priority) {
+        self.task_queue.promote(priority, |task| task_address(task) == address);
+    }
+
+    /// Stores the new [`coroutine`](CoroutineImpl) in the [`Scheduler`] and counts it as spawned.
+    /// Use [`spawn_local`](crate::spawn_local) instead if you don't want to low-level work.
+    ///
+    /// The worker stops, when the main coroutine and all spawned coroutines are completed.
+    pub fn spawn(&mut self, func: CoroutineImpl) {
+        self.spawned += 1;
+        self.spawned_total += 1;
+        self.task_queue.push_new(Priority::Normal, track_spawned(func));
+    }
+
+    /// Like [`spawn`](Scheduler::spawn), but respects the [`CoroutineLimits`] of the worker.
+    /// Use [`try_spawn_local`](crate::try_spawn_local) instead if you don't want to low-level work.
+    ///
+    /// # Return
+    ///
+    /// If the worker is under the limits, the coroutine is spawned, and `Ok` is returned. Otherwise:
+    ///
+    /// - with [`LimitPolicy::Reject`] and [`LimitPolicy::Wait`] the coroutine is dropped, and [`LimitExceeded`] is returned.
+    ///   Use [`spawn_limited`](crate::scheduler::spawn_limited) to wait;
+    ///
+    /// - with [`LimitPolicy::DropOldest`] the coroutine is put to the backlog, and `Ok` is returned.
+    ///
+    /// While the backlog or spawners wait, new coroutines are handled like over the limits, so they don't overtake.
+    pub fn try_spawn(&mut self, func: CoroutineImpl) -> Result<(), LimitExceeded> {
+        let limits = match self.coroutine_limits {
+            Some(limits) if self.is_over_limits() || !self.spawn_backlog.is_empty() || !self.capacity_waiters.is_empty() => limits,
+            _ => {
+                self.spawn(func);
+                return Ok(());
+            }
+        };
+
+        match limits.policy {
+            LimitPolicy::Reject | LimitPolicy::Wait => {
+                self.shed_total += 1;
+                Err(LimitExceeded)
+            }
+            LimitPolicy::DropOldest { backlog } => {
+                self.spawn_backlog.push_back(func);
+                if self.spawn_backlog.len() > backlog {
+                    self.spawn_backlog.pop_front();
+                    self.shed_total += 1;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets the [`CoroutineLimits`] of this worker. `None` disables them,
+    /// and the waiting coroutines are spawned at the next background work.
+    ///
+    /// The default value is read from [`config_coroutine_limits`](crate::cfg::config_coroutine_limits).
+    pub fn set_coroutine_limits(&mut self, limits: Option<CoroutineLimits>) {
+        self.coroutine_limits = limits;
+    }
+
+    /// Returns the [`CoroutineLimits`] of this worker.
+    #[inline(always)]
+    pub fn coroutine_limits(&self) -> Option<CoroutineLimits> {
+        self.coroutine_limits
+    }
+
+    /// Returns true, if the worker is over its [`CoroutineLimits`].
+    #[inline(always)]
+    pub fn is_over_limits(&self) -> bool {
+        match self.coroutine_limits {
+            Some(limits) => limits.is_exceeded(self.task_queue.len(), self.sleeping.len(), self.pending_states),
+            None => false
+        }
+    }
+
+    /// Spawns the waiting coroutines, while the worker is under its limits. Spawners are woken up before the backlog.
+    fn release_capacity(&mut self) {
+        while !self.is_over_limits() {
+            if let Some((task, func, result_ptr, priority)) = self.capacity_waiters.pop_front() {
+                self.spawn(func);
+                unsafe { result_ptr.write(Ok(())) };
+                self.task_queue.push_new(priority, task);
+            } else if let Some(func) = self.spawn_backlog.pop_front() {
+                self.spawn(func);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Like [`spawn`](Scheduler::spawn), but the coroutine is put to the queue of the `priority`.
+    /// Use [`spawn_local_with_priority`](crate::spawn_local_with_priority) instead if you don't want to low-level work.
+    ///
+    /// Read [`Priority`] for more information.
+    pub fn spawn_with_priority(&mut self, func: CoroutineImpl, priority: Priority) {
+        if priority == Priority::Normal {
+            return self.spawn(func);
+        }
+        self.spawned += 1;
+        self.spawned_total += 1;
+        self.task_queue.push_new(priority, track_spawned(with_priority(func, priority)));
+    }
+
+    /// Starts the coroutines after their durations. Read [`sleep_many`](crate::sleep::sleep_many).
+    pub fn sleep_many<I: IntoIterator<Item = (Duration, CoroutineImpl)>>(&mut self, timers: I) {
+        let now = Instant::now();
+        let spawned = &mut self.spawned;
+        let spawned_total = &mut self.spawned_total;
+        self.sleeping.extend(timers.into_iter().map(|(dur, func)| {
+            *spawned += 1;
+            *spawned_total += 1;
+            SleepingCoroutine::at(now + dur, track_spawned(func))
+        }));
+    }
+
+    /// Returns the number of ready coroutines of the `priority`.
+    pub fn ready_with_priority(&self, priority: Priority) -> usize {
+        self.task_queue.len_of(priority)
+    }
+
+    /// Returns the number of spawned coroutines, that are not completed. It includes the main coroutine.
+    #[inline(always)]
+    pub fn spawned(&self) -> usize {
+        self.spawned
+    }
+
+    /// Returns the [`SchedulerMetrics`] of the worker. It is cheap, so it can be called on every request.
+    ///
+    /// Use [`report_metrics`](crate::scheduler::report_metrics) to report them periodically.
+    pub fn metrics(&self) -> SchedulerMetrics {
+        // There is no ring off Linux, so nothing is backlogged.
+        #[cfg(target_os = "linux")]
+        let (submission_backlog, cq_overflows) = (submission_stats().backlog, submission_stats().cq_overflows);
+        #[cfg(not(target_os = "linux"))]
+        let (submission_backlog, cq_overflows) = (0, 0);
+        SchedulerMetrics {
+            spawned_total: self.spawned_total,
+            completed_total: self.completed_total,
+            panicked_total: self.panicked_total,
+            shed_total: self.shed_total,
+            resumed_total: self.handled,
+            polls_total: self.polls_total,
+            ready: self.task_queue.len(),
+            idle: self.idle_queue.len(),
+            sleeping: self.sleeping.len(),
+            pending_states: self.pending_states,
+            submission_backlog,
+            cq_overflows,
+            file_op_support: self.file_op_support,
+            offloaded_file_ops: self.offloaded_file_ops
+        }
+    }
+
+    /// Sets whether panics of coroutines are caught. If it is false, a panic of a coroutine stops the worker thread.
+    ///
+    /// The default value is read from [`config_isolate_panics`](crate::cfg::config_isolate_panics).
+    pub fn set_isolate_panics(&mut self, isolate_panics: bool) {
+        self.isolate_panics = isolate_panics;
+    }
+
+    /// Sets the maximum number of coroutines, that are run between two iterations of the background work.
+    ///
+    /// The default value is read from [`config_poll_interval`](crate::cfg::config_poll_interval).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is 0.
+    pub fn set_poll_interval(&mut self, interval: u32) {
+        assert!(interval > 0, "the poll interval must be positive");
+        self.poll_interval = interval;
+    }
+
+    /// Sets the [`IdleStrategy`] of this worker. If it parks, other threads wake the worker up through its [`Injector`].
+    ///
+    /// The default value is read from [`config_idle_strategy`](crate::cfg::config_idle_strategy).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker parks, and its waker can't be created.
+    pub fn set_idle_strategy(&mut self, strategy: IdleStrategy) {
+        self.idle_strategy = strategy;
+        self.idle_polls = 0;
+        if strategy.park {
+            self.init_waker().expect("failed to create the waker of the worker");
+        }
+    }
+
+    /// Sets the [`PanicHook`] of this worker. It is called after a panic of a coroutine is caught.
+    ///
+    /// The default value is read from [`config_panic_hook`](crate::cfg::config_panic_hook).
+    pub fn set_panic_hook(&mut self, hook: Option<PanicHook>) {
+        self.panic_hook = hook;
+    }
+
+    /// Handles the caught panic of the coroutine, that has already been dropped.
+    /// If panics are not isolated, the panic is resumed.
+    #[cold]
+    fn handle_panic(&mut self, payload: Box<dyn std::any::Any + Send>) {
+        if !self.isolate_panics {
+            resume_unwind(payload);
+        }
+        if let Some(hook) = self.panic_hook {
+            hook(&CoroutinePanic::new(get_worker_id(), payload.as_ref()));
+        }
+    }
+
+    /// Returns the task-local values of the running coroutine.
+    #[inline(always)]
+    pub(crate) fn task_locals(&mut self) -> &mut TaskLocals {
+        &mut self.task_locals
+    }
+
+    /// Returns the [`Injector`] of the worker. Other threads use it to send coroutines to the worker.
+    pub fn injector(&self) -> Injector {
+        self.injector.clone()
+    }
+
+    /// Returns the [`SchedulerHandle`] of the worker. Other threads use it to spawn coroutines, wake the worker up
+    /// and read its metrics.
+    ///
+    /// The first call creates the waker of the worker (read [`Injector::wake`]), so workers without handles don't pay for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the waker can't be created.
+    pub fn handle(&mut self) -> SchedulerHandle {
+        if let Some(handle) = &self.handle {
+            return handle.clone();
+        }
+
+        self.init_waker().expect("failed to create the waker of the worker");
+        let handle = SchedulerHandle::new(self.injector.clone());
+        handle.publish_metrics(self.metrics());
+        self.handle = Some(handle.clone());
+        handle
+    }
+
+    /// Creates the waker of the [`Injector`] and starts a coroutine, that listens to it, if the worker has no waker.
+    fn init_waker(&mut self) -> Result<(), std::io::Error> {
+        if let Some(waker_fd) = self.injector.init_waker()? {
+            self.sched(listen_wakeups(waker_fd, null_mut()));
+        }
+        Ok(())
+    }
+
+    /// Sets whether the worker steals not started coroutines of other workers, when it is idle.
+    /// Read [`work_stealing`](crate::scheduler::work_stealing) for more information.
+    ///
+    /// The default value is read from [`config_work_stealing`](crate::cfg::config_work_stealing).
+    pub fn set_work_stealing(&mut self, work_stealing: bool) {
+        self.work_stealing = work_stealing;
+    }
+
+    /// Sets the order, in which ready coroutines of the same priority are run.
+    ///
+    /// The default value is read from [`config_scheduling_policy`](crate::cfg::config_scheduling_policy).
+    pub fn set_scheduling_policy(&mut self, policy: SchedulingPolicy) {
+        self.task_queue.set_policy(policy);
+    }
+
+    /// Starts coroutines, that are sent by other threads, and publishes the load (and the metrics for the handle) of the worker.
+    /// In the work-stealing mode the idle worker also steals coroutines of other workers.
+    #[inline(always)]
+    fn run_injected(&mut self) {
+        while let Some(creator) = self.injector.pop() {
+            self.spawn(creator());
+        }
+        if unlikely(self.work_stealing && self.task_queue.is_empty()) {
+            let mut stolen = Vec::new();
+            work_stealing::steal(&self.injector, &mut stolen);
+            for creator in stolen {
+                self.spawn(creator());
+            }
+        }
+        self.injector.set_ready(self.task_queue.len());
+        if let Some(handle) = &self.handle {
+            handle.publish_metrics(self.metrics());
+        }
+    }
+
+    /// Stores the [`coroutine`](CoroutineImpl) in the idle lane of the [`Scheduler`].
+    /// Use [`spawn_idle`](crate::spawn_idle) instead if you don't want to low-level work.
+    ///
+    /// Idle coroutines are started only when the worker has no other ready coroutines and all completions are handled.
+    /// It is suitable for background maintenance, like shrinking pools, aggregating metrics or evicting caches.
+    ///
+    /// # Note
+    ///
+    /// Idle coroutines are run in FIFO order, one per iteration of the background work.
+    /// An idle coroutine stays idle: after a yield or a wake-up it goes back to the idle lane,
+    /// so a maintenance loop, that yields, doesn't delay other coroutines.
+    pub fn sched_idle(&mut self, func: CoroutineImpl) {
+        self.spawned += 1;
+        self.spawned_total += 1;
+        self.idle_queue.push_back(track_spawned(as_idle(func)));
+    }
+
+    /// Registers the handler of an extension and returns its [`ExtensionId`].
+    ///
+    /// Extensions allow adding new awaitable operations (GPU waits, custom drivers) without modifying [`YieldStatus`].
+    /// A coroutine yields [`extension`](crate::coroutine::extension) with the returned id and a payload,
+    /// and the scheduler passes the coroutine and the payload to the handler.
+    /// The handler must [`sched`](Scheduler::sched) the coroutine when the operation is done.
+    /// If the id is not registered in the worker, the coroutine gets an error of [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported).
+    ///
+    /// # Note
+    ///
+    /// The extension is registered only in the current worker.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use std::io::Error;
+    /// use engine::local_scheduler;
+    /// use engine::coroutine::extension;
+    /// use engine::utils::Ptr;
+    ///
+    /// let id = local_scheduler().register_extension(Box::new(|task, _payload| {
+    ///     // The operation is done immediately.
+    ///     local_scheduler().sched(task);
+    /// }));
+    ///
+    /// let res: Result<(), Error> = yield extension(id, Ptr::null());
+    /// ```
+    pub fn register_extension(&mut self, handler: ExtensionHandler) -> ExtensionId {
+        self.extensions.push(handler);
+        ExtensionId(self.extensions.len() - 1)
+    }
+
+    /// Returns per-millisecond samples of the run queue length, completions and sleep wakeups of this worker,
+    /// from the oldest to the newest. Milliseconds without any activity are skipped.
+    ///
+    /// Only the last [`QUEUE_TRACE_CAPACITY`](crate::scheduler::QUEUE_TRACE_CAPACITY) samples are kept,
+    /// so dump them soon after an incident to reconstruct the saturation timeline.
+    pub fn dump_queue_trace(&self) -> Vec<QueueSample> {
+        self.trace.dump()
+    }
+
+    /// Sets the [`OverloadProtection`] of this worker. `None` disables it, and paused accepting is resumed.
+    ///
+    /// The default value is read from [`config_overload_protection`](crate::cfg::config_overload_protection).
+    pub fn set_overload_protection(&mut self, protection: Option<OverloadProtection>) {
+        self.overload_protection = protection;
+    }
+
+    /// Returns true, if accepting of new connections is paused by the [`OverloadProtection`].
+    #[inline(always)]
+    pub fn is_accept_paused(&self) -> bool {
+        self.is_accept_paused
+    }
+
+    /// Sets the [`AcceptWarmup`] of this worker and starts it from now. `None` disables it.
+    ///
+    /// The default value is read from [`config_accept_warmup`](crate::cfg::config_accept_warmup) and starts with the worker.
+    pub fn set_accept_warmup(&mut self, warmup: Option<AcceptWarmup>) {
+        self.accept_warmup = warmup.map(|warmup| WarmupLimiter::new(warmup, Instant::now()));
+    }
+
+    /// Returns true, if the accept rate is limited by the [`AcceptWarmup`].
+    #[inline(always)]
+    pub fn is_warming_up(&self) -> bool {
+        self.accept_warmup.is_some()
+    }
+
+    /// Takes a token of the [`AcceptWarmup`] for one accept. Returns false, if the accept must wait.
+    #[inline(always)]
+    fn try_acquire_accept(&mut self) -> bool {
+        match self.accept_warmup.as_mut() {
+            Some(limiter) => limiter.try_acquire(Instant::now()),
+            None => true
+        }
+    }
+
+    /// Registers throttled accepts, for which the [`AcceptWarmup`] has tokens, and ends the warm-up, when it is over.
+    pub(crate) fn release_throttled_accepts<S: Selector>(&mut self, selector: &mut S) {
+        let now = Instant::now();
+        if self.accept_warmup.as_ref().is_some_and(|limiter| limiter.is_over(now)) {
+            self.accept_warmup = None;
+        }
+
+        while let Some(&state_ptr) = self.throttled_accepts.front() {
+            if let Some(limiter) = self.accept_warmup.as_mut() && !limiter.try_acquire(now) {
+                break;
+            }
+            self.throttled_accepts.pop_front();
+            if self.is_accept_paused {
+                self.paused_accepts.push(state_ptr);
+            } else {
+                selector.register(state_ptr);
+            }
+        }
+    }
+
+    /// Returns the approximate memory, that is held by the worker: buffers of the [`BufPool`](crate::buf::BufPool)
+    /// and ready, idle and sleeping coroutines.
+    ///
+    /// Coroutines, that wait for IO, are owned by the selector and are not counted.
+    /// Counting walks the queues, so don't call it too often on a busy worker.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            buffers: buf_pool().held_bytes(),
+            coroutines: 0,
+            coroutine_count: 0
+        };
+        let waiting = self.spawn_backlog.iter().chain(self.capacity_waiters.iter().flat_map(|(task, func, _, _)| [task, func]));
+        for coroutine in self.task_queue.iter().chain(self.idle_queue.iter()).chain(self.sleeping.iter()).chain(waiting) {
+            usage.coroutines += mem::size_of_val(&**coroutine);
+            usage.coroutine_count += 1;
+        }
+        usage
+    }
+
+    /// Sets the soft memory limit of this worker in bytes. `None` disables it.
+    ///
+    /// When [`memory_usage`](Scheduler::memory_usage) is above the limit, [`is_over_memory_limit`](Scheduler::is_over_memory_limit)
+    /// returns true and the callback of [`on_memory_limit`](Scheduler::on_memory_limit) is called,
+    /// so the service can shed load before the OOM killer does. The engine itself doesn't reject anything.
+    ///
+    /// The default value is read from [`config_soft_memory_limit`](crate::cfg::config_soft_memory_limit).
+    pub fn set_soft_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_watch.set_limit(limit);
+    }
+
+    /// Returns the soft memory limit of this worker.
+    #[inline(always)]
+    pub fn soft_memory_limit(&self) -> Option<usize> {
+        self.memory_watch.limit()
+    }
+
+    /// Returns true, if the soft memory limit is exceeded. Read [`set_soft_memory_limit`](Scheduler::set_soft_memory_limit).
+    #[inline(always)]
+    pub fn is_over_memory_limit(&self) -> bool {
+        self.memory_watch.is_exceeded()
+    }
+
+    /// Sets the callback, that is called, when the soft memory limit is exceeded (with true) and when the usage is back under it (with false).
+    pub fn on_memory_limit<F: Fn(bool, MemoryUsage) + 'static>(&mut self, callback: F) {
+        self.memory_watch.set_callback(Some(Box::new(callback)));
+    }
+
+    /// Checks the memory usage against the soft memory limit.
+    #[inline(always)]
+    fn check_memory(&mut self) {
+        if self.memory_watch.should_check() {
+            let usage = self.memory_usage();
+            self.memory_watch.update(usage);
+        }
+    }
+
+    /// Checks the pressure signals and pauses or resumes accepting. Read [`OverloadProtection`] for more information.
+    pub(crate) fn check_overload<S: Selector>(&mut self, selector: &mut S) {
+        let task_queue_len = self.task_queue.len();
+        let buffers_in_use = buf_pool().in_use();
+        match self.overload_protection {
+            Some(protection) => {
+                if !self.is_accept_paused {
+                    self.is_accept_paused = protection.is_overloaded(task_queue_len, buffers_in_use);
+                    return;
+                }
+                if !protection.is_relieved(task_queue_len, buffers_in_use) {
+                    return;
+                }
+            }
+            None => {
+                if !self.is_accept_paused {
+                    return;
+                }
+            }
+        }
+
+        self.is_accept_paused = false;
+        for state_ptr in mem::take(&mut self.paused_accepts) {
+            selector.register(state_ptr);
+        }
+    }
+
+    /// Runs one idle coroutine, if there are no other ready coroutines.
+    ///
+    /// # Return
+    ///
+    /// Returns true if [`end`](YieldStatus::End) was handled.
+    pub(crate) fn run_idle<S: Selector>(&mut self, selector: &mut S) -> bool {
+        if !self.task_queue.is_empty() {
+            return false;
+        }
+
+        match self.idle_queue.pop_front() {
+            Some(task) => {
+                self.is_idle_turn = true;
+                let is_ended = self.handle_coroutine_state(selector, task);
+                self.is_idle_turn = false;
+                is_ended
+            }
+            None => false
+        }
     }
 
     /// Wakes up the sleeping coroutines, which are ready to run.
@@ -102,180 +793,587 @@ impl Scheduler {
     /// Returns true if [`end`](YieldStatus::End) was handled.
     pub(crate) fn awake_coroutines<S: Selector>(&mut self, selector: &mut S) -> bool {
         let now = Instant::now();
-        loop {
-            if let Some(sleeping_coroutine) = self.sleeping.pop_first() {
-                if now >= sleeping_coroutine.execution_time {
-                    if unlikely(self.handle_coroutine_state(selector, sleeping_coroutine.co)) {
-                        return true;
-                    }
-                } else {
-                    self.sleeping.insert(sleeping_coroutine);
-                    break;
-                }
-            } else {
-                break;
+        while let Some(task) = self.sleeping.pop_expired(now) {
+            self.trace.record_wakeup();
+            if unlikely(self.handle_coroutine_state(selector, task)) {
+                return true;
             }
         }
 
         false
     }
 
+    /// Registers the file operation with the selector, or runs it on the blocking pool,
+    /// if the selector doesn't [`support it`](Selector::file_op_support).
+    #[inline(always)]
+    fn register_file_op<S: Selector>(&mut self, selector: &mut S, state_ptr: Ptr<PollState>) {
+        if likely(self.file_op_support.supports(unsafe { state_ptr.as_ref() })) {
+            selector.register(state_ptr);
+        } else {
+            self.offloaded_file_ops += 1;
+            self.run_file_op(state_ptr);
+        }
+    }
+
+    /// Runs the file operation on the blocking pool. When a thread of the pool has made the syscalls,
+    /// a small coroutine finishes the state on the worker and wakes the coroutine of the operation up.
+    /// Read [`file_op`](crate::scheduler::blocking_pool::file_op).
+    fn run_file_op(&mut self, state_ptr: Ptr<PollState>) {
+        let op = FileOp::new(state_ptr);
+        let finish = Box::pin(#[coroutine] static move || {
+            let coroutine = unsafe { finish_file_op(state_ptr) };
+            local_scheduler().ready_coroutines.push(coroutine);
+        });
+        self.blocking_pool.put_state(BlockingState::new_run_closure(Box::new(move || op.run()), finish));
+    }
+
     /// Resume the provided [`coroutine`](CoroutineImpl) and process the result.
     ///
+    /// If the coroutine yields an operation, that completes immediately (like [`NewTcpListener`](YieldStatus::NewTcpListener)),
+    /// the coroutine is resumed again at once, but not more than [`INLINE_COMPLETION_BUDGET`] times in a row.
+    /// After that, the coroutine is put to the queue to let other coroutines run.
+    ///
     /// # Return
     ///
     /// Returns true if [`end`](YieldStatus::End) was handled.
-    #[inline(always)]
+    #[inline]
     pub(crate) fn handle_coroutine_state<S: Selector>(&mut self, selector: &mut S, mut task: CoroutineImpl) -> bool {
-        let res: CoroutineState<YieldStatus, ()> = task.as_mut().resume(());
-        match res {
-            CoroutineState::Yielded(status) => {
-                match status {
-                    YieldStatus::Sleep(dur) => {
-                        let sleep = SleepingCoroutine::new(dur, task);
-                        self.sleeping.insert(sleep);
-                    }
+        // Immediate operations are handled in this loop instead of recursion, so long chains of them can't overflow the stack.
+        let mut budget = INLINE_COMPLETION_BUDGET;
+        self.handled += 1;
+        loop {
+            // Coroutines with a not normal priority set it themselves. Read `with_priority` and `as_idle`.
+            self.current_priority = Priority::Normal;
+            self.is_idle = false;
+            self.running = task_address(&task);
+            let res: CoroutineState<YieldStatus, ()> = match catch_unwind(AssertUnwindSafe(|| task.as_mut().resume(()))) {
+                Ok(res) => res,
+                Err(payload) => {
+                    // The coroutine can't be resumed after a panic. Its scopes have not swapped their values out.
+                    drop(task);
+                    self.task_locals.clear();
+                    self.handle_panic(payload);
+                    return false;
+                }
+            };
+            match res {
+                CoroutineState::Yielded(status) => {
+                    match status {
+                        YieldStatus::Sleep(dur) => {
+                            if let Some(task) = selector.sleep(dur, task) {
+                                let sleep = SleepingCoroutine::new(dur, task);
+                                self.sleeping.insert(sleep);
+                            }
+                        }
 
-                    YieldStatus::Yield => {
-                        self.task_queue.push_front(task);
-                    }
+                        YieldStatus::SleepUntil(instant) => {
+                            if let Some(task) = selector.sleep(instant.saturating_duration_since(Instant::now()), task) {
+                                self.sleeping.insert(SleepingCoroutine::at(instant, task));
+                            }
+                        }
 
-                    YieldStatus::End => {
-                        return true;
-                    }
+                        YieldStatus::Yield => {
+                            self.push_yielded(task);
+                        }
 
-                    YieldStatus::NewTcpListener(status) => {
-                        let fd = TcpListener::get_fd(status.address);
-                        unsafe { status.listener_ptr.write(TcpListener::from_fd(fd)); }
+                        YieldStatus::End => {
+                            return true;
+                        }
 
-                        self.handle_coroutine_state(selector, task);
-                    }
+                        YieldStatus::NewTcpListener(status) => {
+                            let fd = TcpListener::get_fd(status.address);
+                            unsafe { status.listener_ptr.write(TcpListener::from_fd(fd)); }
 
-                    YieldStatus::TcpConnect(status) => {
-                        let state_ = PollState::new_connect_tcp(socket2::SockAddr::from(status.address), task, status.stream_ptr);
-                        if state_.is_err() {
-                            let (error, task) = unsafe { state_.unwrap_err_unchecked() };
-                            write_err!(status.stream_ptr, error);
-                            self.handle_coroutine_state(selector, task);
-                            return false;
+                            if budget > 0 {
+                                budget -= 1;
+                                continue;
+                            }
+
+                            self.push_yielded(task);
                         }
 
-                        selector.register(unsafe {Ptr::new(state_.unwrap_unchecked())});
-                    }
+                        YieldStatus::TcpConnect(status) => {
+                            let state_ = PollState::new_connect_tcp(socket2::SockAddr::from(status.address), status.timeout, task, status.stream_ptr);
+                            if state_.is_err() {
+                                let (error, returned_task) = unsafe { state_.unwrap_err_unchecked() };
+                                write_err!(status.stream_ptr, error);
+                                task = returned_task;
+                                if budget > 0 {
+                                    budget -= 1;
+                                    continue;
+                                }
+
+                                self.push_yielded(task);
+                                return false;
+                            }
+
+                            selector.register(unsafe {Ptr::new(state_.unwrap_unchecked())});
+                        }
+
+                        YieldStatus::TcpAccept(status) => {
+                            let state_ptr = status.state_ref;
+                            let state_ref = unsafe { state_ptr.as_ref() };
+                            let fd = state_ref.fd();
+                            unsafe { state_ptr.write(PollState::new_accept_tcp(fd, task, status.result_ptr)) };
+                            if unlikely(self.overload_protection.is_some()) {
+                                self.check_overload(selector);
+                            }
+                            if unlikely(self.is_accept_paused) {
+                                // The connection waits in the backlog of the listener, until accepting is resumed.
+                                if status.is_registered && selector.supports_multishot() {
+                                    selector.deregister(fd);
+                                }
+                                self.paused_accepts.push(state_ptr);
+                            } else if unlikely(!self.try_acquire_accept()) {
+                                // The connection waits in the backlog of the listener, until the warm-up gives a token.
+                                if status.is_registered && selector.supports_multishot() {
+                                    selector.deregister(fd);
+                                }
+                                self.throttled_accepts.push_back(state_ptr);
+                            } else if !selector.supports_multishot() || !status.is_registered {
+                                selector.register(state_ptr);
+                            }
+                        }
+
+                        YieldStatus::TcpRead(status) => {
+                            if status.is_eof {
+                                // The other side has closed the connection, so the socket is readable forever.
+                                write_ok!(status.result_ptr, &[]);
+                                if budget > 0 {
+                                    budget -= 1;
+                                    continue;
+                                }
+
+                                self.push_yielded(task);
+                                return false;
+                            }
+
+                            let state_ptr = status.state_ref;
+                            let state_ref = unsafe { state_ptr.as_ref() };
+                            unsafe { state_ptr.write(PollState::new_poll_tcp(state_ref.fd(), status.coalesce, task, status.result_ptr)) };
+                            if !selector.supports_multishot() || !status.is_registered {
+                                selector.register(state_ptr);
+                            }
+                        }
 
-                    YieldStatus::TcpAccept(status) => {
-                        let state_ptr = status.state_ref;
-                        let state_ref = unsafe { state_ptr.as_ref() };
-                        unsafe { state_ptr.write(PollState::new_accept_tcp(state_ref.fd(), task, status.result_ptr)) };
-                        if selector.need_reregister() || !status.is_registered {
+                        YieldStatus::TcpWrite(status) => {
+                            let state_ptr = status.state_ref;
+                            let state_ref = unsafe { state_ptr.as_ref() };
+                            let fd = state_ref.fd();
+                            unsafe { state_ptr.write(PollState::new_write_tcp(fd, status.buffer, status.zero_copy, task, status.result_ptr)) };
                             selector.register(state_ptr);
                         }
-                    }
 
-                    YieldStatus::TcpRead(status) => {
-                        let state_ptr = status.state_ref;
-                        let state_ref = unsafe { state_ptr.as_ref() };
-                        unsafe { state_ptr.write(PollState::new_poll_tcp(state_ref.fd(), task, status.result_ptr)) };
-                        if selector.need_reregister() || !status.is_registered {
+                        YieldStatus::TcpWriteAll(status) => {
+                            let state_ptr = status.state_ref;
+                            let state_ref = unsafe { state_ptr.as_ref() };
+                            unsafe { state_ptr.write(PollState::new_write_all_tcp(state_ref.fd(), status.buffer, task, status.result_ptr)) };
                             selector.register(state_ptr);
                         }
-                    }
 
-                    YieldStatus::TcpWrite(status) => {
-                        let state_ptr = status.state_ref;
-                        let state_ref = unsafe { state_ptr.as_ref() };
-                        let fd = state_ref.fd();
-                        unsafe { state_ptr.write(PollState::new_write_tcp(fd, status.buffer, task, status.result_ptr)) };
-                        selector.write(state_ptr);
-                    }
+                        YieldStatus::TcpClose(status) => {
+                            let state_ptr = status.state_ptr;
+                            let state_ref = unsafe { state_ptr.as_mut() };
+                            unsafe { state_ptr.write(PollState::new_close_tcp(state_ref.fd(), task)) };
+                            selector.register(state_ptr);
+                            //self.handle_coroutine_state(selector, task);
+                        }
 
-                    YieldStatus::TcpWriteAll(status) => {
-                        let state_ptr = status.state_ref;
-                        let state_ref = unsafe { state_ptr.as_ref() };
-                        unsafe { state_ptr.write(PollState::new_write_all_tcp(state_ref.fd(), status.buffer, task, status.result_ptr)) };
-                        selector.write_all(state_ptr);
-                    }
+                        YieldStatus::WaitReadable(status) => {
+                            let state_ptr = status.state_ref;
+                            let state_ref = unsafe { state_ptr.as_ref() };
+                            unsafe { state_ptr.write(PollState::new_wait_readable(state_ref.fd(), task, status.result_ptr)) };
+                            if !selector.supports_multishot() || !status.is_registered {
+                                selector.register(state_ptr);
+                            }
+                        }
+
+                        YieldStatus::OpenFile(status) => {
+                            let state_ptr = Ptr::new(PollState::new_open_file(status.path, status.flags, status.mode, task, status.result_ptr));
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileRead(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_read_file(status.fd, file_buffer(status.direct), status.offset, status.cursor, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileReadToEnd(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_read_to_end_file(status.fd, file_buffer(status.direct), status.offset, status.cursor, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileWrite(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_write_file(status.fd, status.buffer, status.offset, status.cursor, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileWriteAll(status) => {
+                            let state_ptr = status.state_ref;
+                            let state = match status.sync {
+                                Some(data_only) => PollState::new_write_all_sync_file(
+                                    status.fd, status.buffer, status.offset, status.cursor, data_only, task, status.result_ptr
+                                ),
+                                None => PollState::new_write_all_file(status.fd, status.buffer, status.offset, status.cursor, task, status.result_ptr)
+                            };
+                            unsafe { state_ptr.write(state) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileClose(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_close_file(status.fd, task)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::CopyFile(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_copy_file(status.src_fd, status.dst_fd, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::Symlink(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_symlink(status.original, status.link, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::HardLink(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_hard_link(status.original, status.link, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::ReadLink(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_read_link(status.path, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::SetPermissions(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_set_permissions(status.path, status.mode, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::FileSetPermissions(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_set_file_permissions(status.fd, status.mode, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::FileAdvise(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_advise_file(status.fd, status.offset, status.len, status.advice, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::LockFile(status) => {
+                            self.blocking_pool.put_state(BlockingState::new_lock_file(status.fd, status.operation, task, status.result_ptr));
+                        }
 
-                    YieldStatus::TcpClose(status) => {
-                        let state_ptr = status.state_ptr;
-                        let state_ref = unsafe { state_ptr.as_mut() };
-                        unsafe { state_ptr.write(PollState::new_close_tcp(state_ref.fd(), task)) };
-                        selector.close_connection(state_ptr);
-                        //self.handle_coroutine_state(selector, task);
+                        YieldStatus::Join(status) => {
+                            unsafe { status.waiter.write(Some(task)) };
+                            if let Some((dur, timer)) = status.timeout {
+                                self.sleeping.insert(SleepingCoroutine::new(dur, timer));
+                            }
+                        }
+
+                        YieldStatus::Cancel(state_ptr) => {
+                            // A paused or throttled accept is not in the selector, so it is cancelled here.
+                            let paused = self.paused_accepts.len() + self.throttled_accepts.len();
+                            self.paused_accepts.retain(|ptr| ptr.as_u64() != state_ptr.as_u64());
+                            self.throttled_accepts.retain(|ptr| ptr.as_u64() != state_ptr.as_u64());
+                            let cancelled = if paused != self.paused_accepts.len() + self.throttled_accepts.len() {
+                                PollState::cancel(state_ptr)
+                            } else {
+                                selector.cancel(state_ptr)
+                            };
+                            if let Some(coroutine) = cancelled {
+                                self.sched(coroutine);
+                            }
+
+                            if budget > 0 {
+                                budget -= 1;
+                                continue;
+                            }
+
+                            self.push_yielded(task);
+                        }
+
+                        YieldStatus::WaitCapacity(status) => {
+                            self.capacity_waiters.push_back((task, status.func, status.result_ptr, self.current_priority));
+                        }
+
+                        YieldStatus::Blocking(status) => {
+                            self.blocking_pool.put_state(BlockingState::new_run_closure(status.job, task));
+                        }
+
+                        YieldStatus::CreateDir(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_create_dir(status.path, status.mode, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::RemoveFile(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_remove_file(status.path, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::RemoveDir(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_remove_dir(status.path, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::Rename(status) => {
+                            self.register_file_op(selector, Ptr::new(PollState::new_rename(status.from, status.to, task, status.result_ptr)));
+                        }
+
+                        YieldStatus::FileSync(status) => {
+                            let state_ptr = status.state_ref;
+                            unsafe { state_ptr.write(PollState::new_sync_file(status.fd, status.data_only, task, status.result_ptr)) };
+                            self.register_file_op(selector, state_ptr);
+                        }
+
+                        YieldStatus::RegisterFile(status) => {
+                            unsafe { status.result_ptr.write(selector.register_file(status.fd)) };
+
+                            if budget > 0 {
+                                budget -= 1;
+                                continue;
+                            }
+
+                            self.push_yielded(task);
+                        }
+
+                        YieldStatus::Extension(status) => {
+                            let handler = match self.extensions.get(status.id.0) {
+                                Some(handler) => handler,
+                                None => {
+                                    let err = std::io::Error::new(std::io::ErrorKind::Unsupported, "the extension is not registered in this worker");
+                                    unsafe { status.result_ptr.write(Err(err)) };
+                                    self.push_yielded(task);
+                                    return false;
+                                }
+                            };
+                            unsafe { status.result_ptr.write(Ok(())) };
+                            // The handler can call the scheduler, so it is called not through the borrow of self.
+                            let handler: *const dyn Fn(CoroutineImpl, Ptr<()>) = &**handler;
+                            unsafe { (*handler)(task, status.payload) };
+                        }
                     }
                 }
+                CoroutineState::Complete(_) => {}
+            }
+
+            return false;
+        }
+    }
+
+    /// Wakes up the coroutines, whose blocking operations are done.
+    ///
+    /// # Return
+    ///
+    /// Returns true if [`end`](YieldStatus::End) was handled.
+    #[inline(always)]
+    fn process_ready_coroutines<S: Selector>(&mut self, selector: &mut S) -> bool {
+        self.blocking_pool.get_ready(&mut self.ready_coroutines);
+        while let Some(task) = self.ready_coroutines.pop() {
+            if unlikely(self.handle_coroutine_state(selector, task)) {
+                return true;
             }
-            CoroutineState::Complete(_) => {}
         }
 
         false
     }
 
-    // #[inline(always)]
-    // fn process_ready_coroutines<S: Selector>(&mut self, selector: &mut S) {
-    //     //self.blocking_pool.get_ready(&mut self.ready_coroutines);
-    //     let ptr = self.ready_coroutines.as_ptr();
-    //     unsafe {
-    //         for i in 0. .self.ready_coroutines.len() {
-    //             self.handle_coroutine_state(selector, ptr.add(i).read());
-    //         }
-    //         self.ready_coroutines.set_len(0);
-    //     }
-    // }
-
     /// Start the [`Scheduler`] and create [`Selector`].
-    pub fn run(&mut self, main_func: CoroutineImpl) {
-        match config_selector() {
-            SelectorType::Poller => self.run_with_selector(main_func, EpolledSelector::new().expect("Failed to create epoll selector")),
-            SelectorType::Ring => self.run_with_selector(main_func, IoUringSelector::new()),
+    ///
+    /// # Return
+    ///
+    /// Returns [`RunError`], if the selector can't be created or fails. Then the [`Scheduler`] is uninitialized
+    /// like after a normal stop. Read [`run_on_core`](crate::run_on_core) for more information.
+    pub fn run(&mut self, main_func: CoroutineImpl) -> Result<(), RunError> {
+        let res = match config_selector() {
+            #[cfg(target_os = "linux")]
+            SelectorType::Poller => EpolledSelector::new()
+                .map(|epoll| self.run_with_selector(main_func, epoll, SelectorType::Poller))
+                .map_err(|err| (SelectorType::Poller, err)),
+            #[cfg(target_os = "linux")]
+            SelectorType::Ring => IoUringSelector::new()
+                .map(|ring| self.run_with_selector(main_func, ring, SelectorType::Ring))
+                .map_err(|err| (SelectorType::Ring, err)),
+            #[cfg(target_os = "linux")]
+            SelectorType::Auto => match IoUringSelector::new() {
+                Ok(ring) => Ok(self.run_with_selector(main_func, ring, SelectorType::Ring)),
+                Err(_) => EpolledSelector::new()
+                    .map(|epoll| self.run_with_selector(main_func, epoll, SelectorType::Poller))
+                    .map_err(|err| (SelectorType::Poller, err))
+            },
+            // kqueue is the only selector of BSD-like systems, so the other types fall back to it.
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly"
+            ))]
+            _ => KqueueSelector::new()
+                .map(|kqueue| self.run_with_selector(main_func, kqueue, SelectorType::Kqueue))
+                .map_err(|err| (SelectorType::Kqueue, err))
+        };
+
+        match res {
+            Ok(res) => res,
+            Err((selector, err)) => {
+                uninit();
+                Err(RunError::CreateSelector(selector, err))
+            }
         }
     }
 
-    /// Start the background work. Specifically, it:
+    /// Runs one iteration of the background work. Specifically, it:
+    ///
+    /// - Awakes coroutines, whose blocking operations are done.
+    ///
+    /// - Starts coroutines, that are sent by other threads.
+    ///
+    /// - Stops the worker, when the main coroutine and all spawned coroutines are completed.
     ///
     /// - Awakes sleeping coroutines, which are ready to run.
     ///
     /// - Polls [`Selector`].
-    #[coro(crate="crate")]
-    fn background_work<S: Selector>(selector_ref: &'static mut S) {
-        let scheduler = local_scheduler();
-        loop {
-            //scheduler.process_ready_coroutines(selector_ref);
-            if unlikely(scheduler.awake_coroutines(selector_ref)) {
-                yield end();
-            }
+    ///
+    /// - Starts an idle coroutine, if nothing else is ready.
+    ///
+    /// - Records the activity to the queue trace.
+    ///
+    /// # Return
+    ///
+    /// Returns true if [`end`](YieldStatus::End) was handled or the worker has nothing to run anymore.
+    ///
+    /// Returns [`RunError::Poll`], if the selector failed.
+    fn background_work<S: Selector>(&mut self, selector: &mut S) -> Result<bool, RunError> {
+        self.polls_total += 1;
+        if unlikely(self.process_ready_coroutines(selector)) {
+            return Ok(true);
+        }
+        self.run_injected();
+        if unlikely(!self.spawn_backlog.is_empty() || !self.capacity_waiters.is_empty()) {
+            self.release_capacity();
+        }
+        if unlikely(self.spawned == 0 && self.task_queue.is_empty() && self.spawn_backlog.is_empty()) {
+            return Ok(true);
+        }
+        self.check_memory();
+        self.trace.tick(self.task_queue.len());
+        if unlikely(self.awake_coroutines(selector)) {
+            return Ok(true);
+        }
 
-            if unlikely(selector_ref.poll(scheduler).expect("Poll error")) {
-                yield end();
-            }
+        let handled = self.handled;
+        let timeout = self.poll_timeout();
+        if unlikely(selector.poll(self, timeout).map_err(RunError::Poll)?) {
+            return Ok(true);
+        }
+        if self.handled != handled {
+            self.idle_polls = 0;
+        }
+        self.trace.record_completions((self.handled - handled) as u32);
+        self.pending_states = selector.pending_states();
+        // The ring can be recreated with more entries in the poll.
+        self.injector.set_ring_fd(selector.ring_fd());
+        if unlikely(self.overload_protection.is_some() || self.is_accept_paused) {
+            self.check_overload(selector);
+        }
+        if unlikely(self.accept_warmup.is_some() || !self.throttled_accepts.is_empty()) {
+            self.release_throttled_accepts(selector);
+        }
 
-            yield yield_now();
+        Ok(self.run_idle(selector))
+    }
+
+    /// Returns the timeout of the next poll of the [`Selector`] by the [`IdleStrategy`].
+    ///
+    /// The worker doesn't wait, while it has work, and doesn't wait longer than until the next timer.
+    /// Outstanding operations end the wait, when they complete.
+    ///
+    /// It doesn't park, while it has to check something by itself: steal work, throttle accepts or watch the overload,
+    /// or while the selector has no outstanding operations (even the waker), so nothing could end the wait.
+    #[inline(always)]
+    fn poll_timeout(&mut self) -> Option<Duration> {
+        // Without spawned coroutines the worker stops at the next background work, so it must not wait.
+        if !self.task_queue.is_empty() || !self.idle_queue.is_empty() || self.spawned == 0
+            || !self.spawn_backlog.is_empty() || !self.capacity_waiters.is_empty() {
+            self.idle_polls = 0;
+            return Some(Duration::ZERO);
+        }
+
+        self.idle_polls = self.idle_polls.saturating_add(1);
+        let mut timeout = self.idle_strategy.timeout(self.idle_polls);
+        if timeout.is_none() && (self.work_stealing || !self.throttled_accepts.is_empty()
+            || self.is_accept_paused || self.overload_protection.is_some() || self.pending_states == 0) {
+            timeout = Some(self.idle_strategy.backoff_timeout);
+        }
+
+        match (timeout, self.sleeping.next_expiration(Instant::now())) {
+            (Some(timeout), Some(next)) => Some(timeout.min(next)),
+            (None, next) => next,
+            (timeout, None) => timeout
         }
     }
 
     /// Start the [`Scheduler`].
-    fn run_with_selector<S: Selector + 'static>(&mut self, main_func: CoroutineImpl, mut selector: S) {
-        self.task_queue.push_back(main_func);
-        let selector_ref = unsafe { transmute::<&mut S, &'static mut S>(&mut selector) };
-
-        self.sched(Self::background_work(selector_ref, null_mut()));
+    ///
+    /// The background work is interleaved with coroutines by the run loop itself, not by a coroutine in the queue,
+    /// so neither the [`SchedulingPolicy`] nor the priorities can postpone it. Read [`Scheduler`] for more information.
+    ///
+    /// On an error the [`Scheduler`] is uninitialized, and the coroutines are dropped with it.
+    fn run_with_selector<S: Selector + 'static>(&mut self, main_func: CoroutineImpl, mut selector: S, selector_type: SelectorType) -> Result<(), RunError> {
+        // The waker is created before the sandbox is installed, so its eventfd is not filtered.
+        if self.idle_strategy.park && let Err(err) = self.init_waker() {
+            uninit();
+            return Err(RunError::Waker(err));
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(sandbox) = config_sandbox() && let Err(err) = sandbox.install(selector_type) {
+            uninit();
+            return Err(RunError::Sandbox(err));
+        }
+        // The sandbox is Linux-only.
+        #[cfg(not(target_os = "linux"))]
+        let _ = selector_type;
+        self.injector.set_ring_fd(selector.ring_fd());
+        self.file_op_support = selector.file_op_support();
 
-        let mut task_;
-        let mut task;
+        self.spawn(main_func);
 
-        loop {
-            task_ = self.task_queue.pop_back();
+        let mut since_poll = 0;
+        let res = loop {
+            if since_poll >= self.poll_interval || self.task_queue.is_empty() {
+                since_poll = 0;
+                match self.background_work(&mut selector) {
+                    Ok(false) => continue,
+                    Ok(true) => break Ok(()),
+                    Err(err) => break Err(err)
+                }
+            }
 
-            task = unsafe { task_.unwrap_unchecked() };
+            since_poll += 1;
+            let task = unsafe { self.task_queue.pop().unwrap_unchecked() };
             if unlikely(self.handle_coroutine_state(&mut selector, task)) {
-                break;
+                break Ok(());
             }
-        }
+        };
 
+        // Other threads must not send wake-ups to the ring, when it is closed, and its fd can be reused.
+        self.injector.set_ring_fd(None);
         uninit();
+        res
     }
 }
 
+/// Returns a buffer for a file read. Files opened with `O_DIRECT` need aligned memory, so the buffer is not from the pool.
+#[inline(always)]
+fn file_buffer(direct: bool) -> Buffer {
+    if unlikely(direct) {
+        let len = buf_pool().buffer_len().next_multiple_of(DIRECT_IO_ALIGN);
+        return Buffer::new_aligned(len, DIRECT_IO_ALIGN);
+    }
+
+    buffer()
+}
+
 /// Returns the [`Scheduler`] from the current thread. Used for low-level work.
 pub fn local_scheduler() -> &'static mut Scheduler {
     LOCAL_SCHEDULER.with(|local| {
@@ -283,11 +1381,90 @@ pub fn local_scheduler() -> &'static mut Scheduler {
     })
 }
 
+/// Returns the address of the coroutine. It is stable, because the coroutine is pinned,
+/// so the scheduler can recognize the coroutine, while it is queued.
+#[inline(always)]
+fn task_address(task: &CoroutineImpl) -> usize {
+    task.as_ref().get_ref() as *const _ as *const () as usize
+}
+
+/// Runs the coroutine and sets its priority before every resume, so it is put to the queue of the priority, when it yields.
+fn with_priority(mut func: CoroutineImpl, priority: Priority) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        loop {
+            local_scheduler().current_priority = priority;
+            match func.as_mut().resume(()) {
+                CoroutineState::Yielded(status) => yield status,
+                CoroutineState::Complete(()) => break
+            }
+        }
+    })
+}
+
+/// Runs the idle coroutine and marks it as idle before every resume, so it is put back to the idle queue, when it yields.
+///
+/// If the coroutine is woken up not by [`run_idle`](Scheduler::run_idle), it yields at once without running,
+/// and it is resumed, when the worker has nothing else to do.
+fn as_idle(mut func: CoroutineImpl) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        loop {
+            let scheduler = local_scheduler();
+            scheduler.is_idle = true;
+            if !scheduler.is_idle_turn {
+                yield YieldStatus::Yield;
+                continue;
+            }
+
+            match func.as_mut().resume(()) {
+                CoroutineState::Yielded(status) => yield status,
+                CoroutineState::Complete(()) => break
+            }
+        }
+    })
+}
+
+/// Decrements the number of spawned coroutines, when the coroutine, that owns it, is dropped by a panic.
+///
+/// It does nothing, when the coroutine is dropped without a panic (for example, with the scheduler),
+/// because the scheduler can be already dropped.
+struct PanicGuard;
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let scheduler = local_scheduler();
+            scheduler.spawned -= 1;
+            scheduler.panicked_total += 1;
+        }
+    }
+}
+
+/// Runs the spawned coroutine and decrements the number of spawned coroutines, when it is completed or dropped by a panic.
+fn track_spawned(mut func: CoroutineImpl) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        let _guard = PanicGuard;
+        while let CoroutineState::Yielded(status) = func.as_mut().resume(()) {
+            yield status;
+        }
+
+        let scheduler = local_scheduler();
+        scheduler.spawned -= 1;
+        scheduler.completed_total += 1;
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cell::{Cell, RefCell};
+    #[cfg(target_os = "linux")]
+    use std::os::fd::RawFd;
+    use std::ptr::null_mut;
+    use std::rc::Rc;
     use std::time::Duration;
     use super::*;
-    use crate::{test_local, coro, sleep::sleep};
+    use crate::{test_local, coro, wait, sleep::sleep};
+    use crate::buf::BufPool;
+    use crate::coroutine::{end, yield_now};
     use crate::local::Local;
 
     #[test_local(crate="crate")]
@@ -328,4 +1505,301 @@ mod tests {
         yield sleep(Duration::from_millis(5));
         assert_eq!(&vec![1, 2, 3, 4], arr.get());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_deeply_chained_immediate_ops() {
+        #[coro(crate="crate")]
+        fn chain() {
+            let addr = "127.0.0.1:0".parse().unwrap();
+            for _ in 0..2000 {
+                let _listener: TcpListener = yield TcpListener::new(addr);
+            }
+            yield end();
+        }
+
+        // Immediate ops are handled without returning to the selector, so they must not grow the stack.
+        // A fixed stack size makes the test independent of RUST_MIN_STACK.
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                let core_id = crate::utils::get_core_ids().unwrap()[0];
+                crate::run_on_core(chain, core_id).unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test_local(crate="crate")]
+    fn test_sched_idle() {
+        #[coro(crate="crate")]
+        fn insert(number: u16, arr: Local<Vec<u16>>) {
+            arr.get_mut().push(number);
+        }
+
+        let scheduler = local_scheduler();
+        let arr = Local::new(Vec::new());
+
+        scheduler.sched_idle(insert(2, arr.clone(), null_mut()));
+        scheduler.sched(insert(1, arr.clone(), null_mut()));
+
+        yield yield_now();
+        assert_eq!(&vec![1], arr.get());
+
+        yield sleep(Duration::from_millis(1));
+        assert_eq!(&vec![1, 2], arr.get());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_idle_yield_waits_for_normal_work() {
+        #[coro(crate="crate")]
+        fn normal_loop(arr: Local<Vec<&'static str>>) {
+            for _ in 0..3 {
+                arr.get_mut().push("normal");
+                yield yield_now();
+            }
+        }
+
+        #[coro(crate="crate")]
+        fn idle_loop(arr: Local<Vec<&'static str>>) {
+            // The yielded idle coroutine doesn't run, while the normal coroutine is ready.
+            local_scheduler().sched(normal_loop(arr.clone(), null_mut()));
+            for _ in 0..3 {
+                arr.get_mut().push("idle");
+                yield yield_now();
+            }
+        }
+
+        let arr = Local::new(Vec::new());
+        local_scheduler().sched_idle(idle_loop(arr.clone(), null_mut()));
+        // The parent sleeps, so the idle coroutine starts.
+        yield sleep(Duration::from_millis(5));
+        assert_eq!(&vec!["idle", "normal", "normal", "normal", "idle", "idle"], arr.get());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_priorities() {
+        #[coro(crate="crate")]
+        fn insert(number: u16, arr: Local<Vec<u16>>) {
+            arr.get_mut().push(number);
+        }
+
+        let scheduler = local_scheduler();
+        let arr = Local::new(Vec::new());
+
+        scheduler.spawn_with_priority(insert(0, arr.clone(), null_mut()), Priority::Low);
+        scheduler.spawn_with_priority(insert(1, arr.clone(), null_mut()), Priority::Normal);
+        scheduler.spawn_with_priority(insert(2, arr.clone(), null_mut()), Priority::High);
+        assert_eq!(scheduler.ready_with_priority(Priority::High), 1);
+
+        yield sleep(Duration::from_millis(1));
+        assert_eq!(&vec![2, 1, 0], arr.get());
+    }
+
+    #[test_local(crate="crate")]
+    fn test_capacity_waiter_keeps_priority() {
+        #[coro(crate="crate")]
+        fn noop() {}
+
+        let scheduler = local_scheduler();
+        let mut res: Result<(), LimitExceeded> = Err(LimitExceeded);
+        let high = scheduler.ready_with_priority(Priority::High);
+        scheduler.capacity_waiters.push_back((noop(null_mut()), noop(null_mut()), &mut res, Priority::High));
+        scheduler.release_capacity();
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(scheduler.ready_with_priority(Priority::High), high + 1);
+        yield sleep(Duration::from_millis(1));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_queue_trace() {
+        #[coro(crate="crate")]
+        fn yielding() {
+            // Enough yields, that all of them are still queued at the background work after the poll interval.
+            for _ in 0..5 {
+                yield yield_now();
+            }
+        }
+
+        let scheduler = local_scheduler();
+        for _ in 0..10 {
+            scheduler.sched(yielding(null_mut()));
+        }
+        yield sleep(Duration::from_millis(2));
+        yield sleep(Duration::from_millis(2));
+
+        let samples = scheduler.dump_queue_trace();
+        assert!(samples.windows(2).all(|pair| pair[0].millis < pair[1].millis));
+        assert!(samples.iter().map(|sample| sample.wakeups).sum::<u32>() >= 2);
+        assert!(samples.iter().any(|sample| sample.run_queue_len >= 10));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_extension() {
+        thread_local! {
+            static PARKED: RefCell<Vec<(CoroutineImpl, Ptr<()>)>> = RefCell::new(Vec::new());
+        }
+
+        #[coro(crate="crate")]
+        fn driver() {
+            yield sleep(Duration::from_millis(1));
+            for (task, payload) in PARKED.with(|parked| parked.take()) {
+                unsafe { payload.cast::<u32>().write(42) };
+                local_scheduler().sched(task);
+            }
+        }
+
+        let scheduler = local_scheduler();
+        let id = scheduler.register_extension(Box::new(|task, payload| {
+            PARKED.with(|parked| parked.borrow_mut().push((task, payload)));
+        }));
+        scheduler.sched(driver(null_mut()));
+
+        let mut result = 0u32;
+        let res: Result<(), std::io::Error> = yield crate::coroutine::extension(id, Ptr::from(&mut result).cast());
+        res.unwrap();
+        assert_eq!(result, 42);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_unregistered_extension() {
+        let id = ExtensionId(local_scheduler().extensions.len());
+        let res: Result<(), std::io::Error> = yield crate::coroutine::extension(id, Ptr::null());
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_poll_interval() {
+        #[coro(crate="crate")]
+        fn spinner(iterations: u32, max_gap: Rc<Cell<u64>>) {
+            let mut last_polls = local_scheduler().metrics().polls_total;
+            let mut gap = 0;
+            for _ in 0..iterations {
+                let polls = local_scheduler().metrics().polls_total;
+                if polls == last_polls {
+                    gap += 1;
+                    max_gap.set(max_gap.get().max(gap));
+                } else {
+                    last_polls = polls;
+                    gap = 0;
+                }
+                yield yield_now();
+            }
+        }
+
+        let scheduler = local_scheduler();
+        scheduler.set_poll_interval(4);
+        let max_gap = Rc::new(Cell::new(0));
+        // Two spinners are always ready, so the queue is never empty, and only the interval lets the background work run.
+        scheduler.spawn_with_priority(spinner(100, max_gap.clone(), null_mut()), Priority::High);
+        scheduler.spawn_with_priority(spinner(100, max_gap.clone(), null_mut()), Priority::High);
+        let start = Instant::now();
+        yield sleep(Duration::from_millis(1));
+
+        assert!(start.elapsed() < Duration::from_millis(100), "the sleep was delayed by the spinners");
+        while Rc::strong_count(&max_gap) > 1 {
+            yield sleep(Duration::from_millis(1));
+        }
+        assert!(max_gap.get() <= 4, "the spinners have run {} times without the background work", max_gap.get());
+        scheduler.set_poll_interval(config_poll_interval());
+    }
+
+    /// An [`IoUringSelector`], that handles only some file operations, like io_uring of an old kernel.
+    #[cfg(target_os = "linux")]
+    struct LimitedFiles(IoUringSelector, FileOpSupport);
+
+    #[cfg(target_os = "linux")]
+    impl Selector for LimitedFiles {
+        fn supports_multishot(&self) -> bool { self.0.supports_multishot() }
+        fn file_op_support(&self) -> FileOpSupport { self.1 }
+        fn poll(&mut self, scheduler: &mut Scheduler, timeout: Option<Duration>) -> Result<bool, std::io::Error> { self.0.poll(scheduler, timeout) }
+        fn register(&mut self, state_ptr: Ptr<PollState>) {
+            let state = unsafe { state_ptr.as_ref() };
+            assert!(!state.is_file_op() || self.1.supports(state), "a not supported file operation is registered: {:?}", state);
+            self.0.register(state_ptr)
+        }
+        fn deregister(&mut self, fd: RawFd) { self.0.deregister(fd) }
+        fn register_file(&mut self, fd: RawFd) -> Result<bool, std::io::Error> { self.0.register_file(fd) }
+        fn cancel(&mut self, state_ref: Ptr<PollState>) -> Option<CoroutineImpl> { self.0.cancel(state_ref) }
+        fn sleep(&mut self, dur: Duration, coroutine: CoroutineImpl) -> Option<CoroutineImpl> { self.0.sleep(dur, coroutine) }
+        fn ring_fd(&self) -> Option<RawFd> { self.0.ring_fd() }
+        fn pending_states(&self) -> usize { self.0.pending_states() }
+    }
+
+    /// Writes and reads a file on a worker with the selector.
+    /// Returns the read bytes and the number of offloaded file operations.
+    fn write_and_read_with<S, F>(new_selector: F, selector_type: SelectorType, name: &str) -> (Vec<u8>, u64)
+    where
+        S: Selector + 'static,
+        F: FnOnce() -> S + Send + 'static
+    {
+        #[coro(crate="crate")]
+        fn write_and_read(path: std::path::PathBuf, read: Rc<RefCell<Vec<u8>>>, offloaded: Rc<Cell<u64>>) {
+            let mut buf = buffer();
+            buf.append(b"blocking pool");
+            let res: Result<(), std::io::Error> = wait!(crate::fs::write(path.clone(), buf));
+            res.unwrap();
+            let res: Result<Buffer, std::io::Error> = wait!(crate::fs::read(path));
+            read.borrow_mut().extend_from_slice(res.unwrap().as_ref());
+            offloaded.set(local_scheduler().metrics().offloaded_file_ops);
+            yield end();
+        }
+
+        let path = std::env::temp_dir().join(format!("coroeng_test_{}_{}", name, std::process::id()));
+        let read_path = path.clone();
+        let res = std::thread::spawn(move || {
+            crate::cfg::enter_worker(crate::cfg::config());
+            BufPool::init_in_local_thread(crate::cfg::config_buf_len());
+            Scheduler::init();
+            let read = Rc::new(RefCell::new(Vec::new()));
+            let offloaded = Rc::new(Cell::new(0));
+            let main = write_and_read(read_path, read.clone(), offloaded.clone(), null_mut());
+            local_scheduler().run_with_selector(main, new_selector(), selector_type).unwrap();
+            (read.take(), offloaded.get())
+        }).join().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        res
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_file_ops_without_selector_support() {
+        let limited = |file_op_support| move || LimitedFiles(IoUringSelector::new().unwrap(), file_op_support);
+        let (read, all_offloaded) = write_and_read_with(limited(FileOpSupport::NONE), SelectorType::Ring, "sockets_only");
+        assert_eq!(read, b"blocking pool");
+        assert!(all_offloaded > 0);
+
+        // Like an old kernel, that can't open files with io_uring: only opens are offloaded.
+        let no_open = FileOpSupport { open: false, ..FileOpSupport::ALL };
+        let (read, offloaded) = write_and_read_with(limited(no_open), SelectorType::Ring, "no_open");
+        assert_eq!(read, b"blocking pool");
+        assert_eq!(offloaded, 2);
+
+        // Regular files are always ready for epoll, so all file operations are offloaded.
+        let (read, offloaded) = write_and_read_with(|| EpolledSelector::new().unwrap(), SelectorType::Poller, "epoll");
+        assert_eq!(read, b"blocking pool");
+        assert_eq!(offloaded, all_offloaded);
+
+        let (read, offloaded) = write_and_read_with(limited(FileOpSupport::ALL), SelectorType::Ring, "all_files");
+        assert_eq!(read, b"blocking pool");
+        assert_eq!(offloaded, 0);
+    }
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly"
+    ))]
+    #[test]
+    fn test_file_ops_with_kqueue() {
+        // kqueue can't wait for regular files, so all file operations are offloaded.
+        let (read, offloaded) = write_and_read_with(|| KqueueSelector::new().unwrap(), SelectorType::Kqueue, "kqueue");
+        assert_eq!(read, b"blocking pool");
+        assert!(offloaded > 0);
+    }
+}
@@ -0,0 +1,107 @@
+//! This module contains [`QueueSample`] and the ring buffer of them, that the [`Scheduler`](crate::scheduler::Scheduler) records
+//! to reconstruct saturation timelines after an incident.
+
+use std::time::Instant;
+
+/// How many samples are kept. One sample covers one millisecond, so it is about 4 seconds of history.
+pub const QUEUE_TRACE_CAPACITY: usize = 4096;
+
+/// The activity of the worker during one millisecond.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueSample {
+    /// Milliseconds from the start of the worker to the start of the sample.
+    pub millis: u64,
+    /// The maximum length of the run queue, observed between iterations of the background work.
+    pub run_queue_len: usize,
+    /// The number of coroutines woken up by the selector.
+    pub completions: u32,
+    /// The number of sleeping coroutines woken up.
+    pub wakeups: u32
+}
+
+/// The ring buffer of [`QueueSample`]s. Milliseconds without any activity are not recorded.
+pub(crate) struct QueueTrace {
+    start: Instant,
+    current: QueueSample,
+    samples: Vec<QueueSample>,
+    /// The index of the oldest sample, when the buffer is full.
+    head: usize
+}
+
+impl QueueTrace {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            current: QueueSample::default(),
+            samples: Vec::new(),
+            head: 0
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record_completions(&mut self, completions: u32) {
+        self.current.completions += completions;
+    }
+
+    #[inline(always)]
+    pub(crate) fn record_wakeup(&mut self) {
+        self.current.wakeups += 1;
+    }
+
+    /// Observes the run queue length and closes the current sample, if its millisecond is over.
+    #[inline(always)]
+    pub(crate) fn tick(&mut self, run_queue_len: usize) {
+        let millis = self.start.elapsed().as_millis() as u64;
+        if millis != self.current.millis {
+            self.push(self.current);
+            self.current = QueueSample { millis, ..QueueSample::default() };
+        }
+
+        if run_queue_len > self.current.run_queue_len {
+            self.current.run_queue_len = run_queue_len;
+        }
+    }
+
+    fn push(&mut self, sample: QueueSample) {
+        if sample.run_queue_len == 0 && sample.completions == 0 && sample.wakeups == 0 {
+            return;
+        }
+
+        if self.samples.len() < QUEUE_TRACE_CAPACITY {
+            self.samples.push(sample);
+        } else {
+            self.samples[self.head] = sample;
+            self.head = (self.head + 1) % QUEUE_TRACE_CAPACITY;
+        }
+    }
+
+    /// Returns the recorded samples from the oldest to the newest, including the current one.
+    pub(crate) fn dump(&self) -> Vec<QueueSample> {
+        let mut samples = Vec::with_capacity(self.samples.len() + 1);
+        samples.extend_from_slice(&self.samples[self.head..]);
+        samples.extend_from_slice(&self.samples[..self.head]);
+        samples.push(self.current);
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let mut trace = QueueTrace::new();
+        for millis in 1..=(QUEUE_TRACE_CAPACITY as u64 + 10) {
+            trace.push(QueueSample { millis, run_queue_len: 1, completions: 0, wakeups: 0 });
+        }
+        // An idle millisecond is not recorded.
+        trace.push(QueueSample { millis: 0, run_queue_len: 0, completions: 0, wakeups: 0 });
+
+        let samples = trace.dump();
+        assert_eq!(samples.len(), QUEUE_TRACE_CAPACITY + 1);
+        assert_eq!(samples[0].millis, 11);
+        assert_eq!(samples[QUEUE_TRACE_CAPACITY - 1].millis, QUEUE_TRACE_CAPACITY as u64 + 10);
+    }
+}
@@ -0,0 +1,159 @@
+//! This module contains [`AcceptWarmup`].
+use std::time::{Duration, Instant};
+
+/// The gradual ramp of the accept rate after the start of the worker.
+///
+/// Right after a restart caches are cold, so accepting the whole backlog at once makes the first requests slow.
+/// With [`AcceptWarmup`] the worker accepts at most `start_rate` connections per second at the start,
+/// the limit grows linearly up to `end_rate` during `duration`, and then accepting is not limited.
+/// Connections above the limit wait in the backlog of the listener.
+///
+/// Set it with [`set_accept_warmup`](crate::cfg::set_accept_warmup) before the start
+/// or with [`Scheduler::set_accept_warmup`](crate::scheduler::Scheduler::set_accept_warmup) for the current worker.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::time::Duration;
+/// use engine::cfg::set_accept_warmup;
+/// use engine::scheduler::AcceptWarmup;
+///
+/// // From 100 to 5_000 accepts per second during the first 30 seconds.
+/// set_accept_warmup(Some(AcceptWarmup::new(Duration::from_secs(30), 100, 5_000)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcceptWarmup {
+    /// How long the accept rate is limited.
+    pub duration: Duration,
+    /// The limit of accepts per second at the start.
+    pub start_rate: u32,
+    /// The limit of accepts per second at the end of the warm-up.
+    pub end_rate: u32
+}
+
+impl AcceptWarmup {
+    /// Creates a new [`AcceptWarmup`].
+    pub const fn new(duration: Duration, start_rate: u32, end_rate: u32) -> Self {
+        Self { duration, start_rate, end_rate }
+    }
+
+    /// Returns the limit of accepts per second after `elapsed` since the start.
+    fn rate(&self, elapsed: Duration) -> f64 {
+        let progress = (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0);
+        self.start_rate as f64 + (self.end_rate as f64 - self.start_rate as f64) * progress
+    }
+}
+
+/// The token bucket, that limits accepts during [`AcceptWarmup`].
+pub(crate) struct WarmupLimiter {
+    warmup: AcceptWarmup,
+    started: Instant,
+    last_refill: Instant,
+    tokens: f64
+}
+
+impl WarmupLimiter {
+    pub(crate) fn new(warmup: AcceptWarmup, now: Instant) -> Self {
+        let mut limiter = Self { warmup, started: now, last_refill: now, tokens: 0.0 };
+        limiter.tokens = limiter.burst(now);
+        limiter
+    }
+
+    /// Returns how many tokens the bucket can hold: a tenth of a second of the current rate, but at least one.
+    fn burst(&self, now: Instant) -> f64 {
+        (self.warmup.rate(now - self.started) / 10.0).max(1.0)
+    }
+
+    /// Returns true, if the warm-up is over, and accepting is not limited anymore.
+    #[inline(always)]
+    pub(crate) fn is_over(&self, now: Instant) -> bool {
+        now - self.started >= self.warmup.duration
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now - self.last_refill;
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.warmup.rate(now - self.started)).min(self.burst(now));
+    }
+
+    /// Takes a token for one accept. Returns false, if the accept must wait.
+    pub(crate) fn try_acquire(&mut self, now: Instant) -> bool {
+        if self.is_over(now) {
+            return true;
+        }
+
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::net::SocketAddr;
+    use std::os::fd::IntoRawFd;
+    use std::ptr::null_mut;
+    use std::time::{Duration, Instant};
+    use crate::{coro, test_local};
+    use crate::local::Local;
+    use crate::net::{TcpListener, TcpStream};
+    use crate::scheduler::{local_scheduler, AcceptWarmup};
+    use crate::scheduler::warmup::WarmupLimiter;
+    use crate::sleep::sleep;
+
+    #[test]
+    fn test_limiter() {
+        let start = Instant::now();
+        let mut limiter = WarmupLimiter::new(AcceptWarmup::new(Duration::from_secs(10), 10, 1_000), start);
+        assert!(limiter.try_acquire(start));
+        assert!(!limiter.try_acquire(start));
+        assert!(limiter.try_acquire(start + Duration::from_millis(101)));
+        assert!(!limiter.is_over(start + Duration::from_secs(9)));
+        assert!(limiter.is_over(start + Duration::from_secs(10)));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_accept_warmup() {
+        const CONNS: usize = 6;
+
+        #[coro(crate="crate")]
+        fn accept_all(mut listener: TcpListener, accepted: Local<usize>) {
+            for _ in 0..CONNS {
+                let res: Result<TcpStream, Error> = yield listener.accept();
+                res.unwrap();
+                *accepted.get_mut() += 1;
+            }
+        }
+
+        let scheduler = local_scheduler();
+        scheduler.set_accept_warmup(Some(AcceptWarmup::new(Duration::from_millis(300), 10, 10)));
+        assert!(scheduler.is_warming_up());
+
+        let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        std_listener.set_nonblocking(true).unwrap();
+        let addr: SocketAddr = std_listener.local_addr().unwrap();
+        let listener = TcpListener::from_fd(std_listener.into_raw_fd());
+        let accepted = Local::new(0);
+        scheduler.sched(accept_all(listener, accepted.clone(), null_mut()));
+
+        let clients = std::thread::spawn(move || {
+            (0..CONNS).map(|_| std::net::TcpStream::connect(addr).unwrap()).collect::<Vec<_>>()
+        });
+        yield sleep(Duration::from_millis(50));
+        assert!(*accepted.get() < CONNS, "all connections were accepted at once");
+
+        for _ in 0..500 {
+            if *accepted.get() == CONNS {
+                break;
+            }
+            yield sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*accepted.get(), CONNS);
+        assert!(!scheduler.is_warming_up());
+        clients.join().unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+//! This module contains the work-stealing mode of workers.
+//!
+//! Coroutines are not [`Send`] and they own worker-local resources (buffers of the pool, states of the selector, [`Local`](crate::local::Local)s),
+//! so a started coroutine never moves to another worker. Instead, workers steal coroutines, that are not started yet:
+//! their creators wait in the [`Injector`] of the worker (its local deque), and an idle worker takes the oldest of them
+//! from the most loaded worker.
+//!
+//! Enable it with [`set_work_stealing`](crate::cfg::set_work_stealing) and spawn stealable work with [`spawn_stealable`].
+use std::io::Error;
+use crate::coroutine::CoroutineImpl;
+use crate::local_scheduler;
+use crate::scheduler::injection::{injectors, CoroutineCreator, Injector};
+
+/// The maximum number of creators, that are stolen at once.
+const MAX_STEAL_BATCH: usize = 32;
+
+/// Puts `creator` to the local deque of the current worker.
+///
+/// The coroutine is started by this worker at its next background tick, unless an idle worker steals it first
+/// (only in the work-stealing mode). So, one core, that accepts all connections, can spread handlers across cores:
+///
+/// ```ignore
+/// use std::os::fd::IntoRawFd;
+/// use engine::coro;
+/// use engine::net::{TcpListener, TcpStream};
+/// use engine::scheduler::work_stealing::spawn_stealable;
+///
+/// #[coro]
+/// fn handle(stream: TcpStream) {
+///     // process the stream
+/// }
+///
+/// #[coro]
+/// fn accept_loop(mut listener: TcpListener) {
+///     loop {
+///         let stream: TcpStream = (yield listener.accept()).unwrap();
+///         let fd = stream.into_raw_fd();
+///         spawn_stealable(move || handle(TcpStream::new(fd), std::ptr::null_mut())).unwrap();
+///     }
+/// }
+/// ```
+///
+/// Arguments of the creator are moved to another thread, so they must be [`Send`].
+///
+/// # Note
+///
+/// Epoll workers don't share the fd table (read [`EpolledSelector`](crate::io::sys::unix::EpolledSelector)),
+/// so don't pass fds to stealable coroutines with the epoll selector.
+pub fn spawn_stealable<F: FnOnce() -> CoroutineImpl + Send + 'static>(creator: F) -> Result<(), Error> {
+    local_scheduler().injector().spawn(creator)
+}
+
+/// Steals the oldest creators of the most loaded victim. Returns the number of stolen creators.
+///
+/// A half of the queue of the victim is stolen, but not more than [`MAX_STEAL_BATCH`].
+pub(crate) fn steal_from(victims: &[Injector], thief: &Injector, stolen: &mut Vec<CoroutineCreator>) -> usize {
+    let victim = victims
+        .iter()
+        .filter(|victim| !victim.is_same(thief))
+        .max_by_key(|victim| victim.queued());
+    let Some(victim) = victim else { return 0 };
+
+    let batch = victim.queued().div_ceil(2).min(MAX_STEAL_BATCH);
+    let mut count = 0;
+    while count < batch {
+        match victim.pop() {
+            Some(creator) => stolen.push(creator),
+            None => break
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Steals creators from other workers for the idle worker. Read [`steal_from`].
+pub(crate) fn steal(thief: &Injector, stolen: &mut Vec<CoroutineCreator>) -> usize {
+    steal_from(&injectors(), thief, stolen)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coroutine::{CoroutineImpl, YieldStatus};
+    use crate::scheduler::injection::Injector;
+    use crate::scheduler::work_stealing::steal_from;
+
+    fn empty() -> CoroutineImpl {
+        Box::pin(#[coroutine] static move || {
+            yield YieldStatus::yield_now();
+        })
+    }
+
+    #[test]
+    fn test_steal_from() {
+        let thief = Injector::new(0);
+        let light = Injector::new(1);
+        let heavy = Injector::new(2);
+        light.spawn(empty).unwrap();
+        for _ in 0..10 {
+            heavy.spawn(empty).unwrap();
+        }
+        thief.spawn(empty).unwrap();
+        let victims = [thief.clone(), light.clone(), heavy.clone()];
+
+        let mut stolen = Vec::new();
+        assert_eq!(steal_from(&victims, &thief, &mut stolen), 5);
+        assert_eq!(stolen.len(), 5);
+        assert_eq!(heavy.queued(), 5);
+        assert_eq!(thief.queued(), 1);
+
+        assert_eq!(steal_from(&[thief.clone()], &thief, &mut stolen), 0);
+    }
+}
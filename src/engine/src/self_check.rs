@@ -0,0 +1,240 @@
+//! This module contains [`self_check`], the startup check of the selected backend.
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::ptr::null_mut;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use crate::{coro, local_scheduler, run_on_core, wait, RunError};
+use crate::buf::{buffer, Buffer};
+use crate::fs::{tempfile, File};
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::net::{TcpListener, TcpStream};
+use crate::sleep::sleep;
+use crate::utils::core;
+
+/// The time, after which a hanging check is failed with [`SelfCheckError::TimedOut`].
+const SELF_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// The duration of the checked sleep.
+const SLEEP_DURATION: Duration = Duration::from_millis(10);
+/// The maximum delay of the wake up after the checked sleep.
+const MAX_SLEEP_DELAY: Duration = Duration::from_millis(500);
+/// The data, that is echoed over TCP and written to the temporary file.
+const CHECK_MESSAGE: &[u8] = b"coroeng self check";
+
+/// The error of [`self_check`].
+#[derive(Debug)]
+pub enum SelfCheckError {
+    /// The worker can't be run. For example, the selector can't be created.
+    Run(RunError),
+    /// The check failed with the error.
+    Failed {
+        /// The name of the check: `tcp echo`, `file` or `sleep`.
+        check: &'static str,
+        error: Error
+    },
+    /// The worker panicked.
+    Panicked,
+    /// The checks were not completed in time. The worker thread is left running.
+    TimedOut
+}
+
+impl Display for SelfCheckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelfCheckError::Run(err) => write!(f, "self check failed: {}", err),
+            SelfCheckError::Failed { check, error } => write!(f, "self check {} failed: {}", check, error),
+            SelfCheckError::Panicked => f.write_str("self check failed: the worker panicked"),
+            SelfCheckError::TimedOut => write!(f, "self check failed: the checks were not completed in {:?}", SELF_CHECK_TIMEOUT)
+        }
+    }
+}
+
+impl std::error::Error for SelfCheckError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SelfCheckError::Run(err) => Some(err),
+            SelfCheckError::Failed { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
+
+/// Checks, that the selected backend works, so deployments can fail fast on broken kernels or restricted environments
+/// (for example, when io_uring is disabled by seccomp).
+///
+/// It runs a temporary worker on a new thread with the current [`config`](crate::cfg) (the selector, the sandbox and so on),
+/// which performs a loopback TCP echo, a write and a read of a temporary file and a sleep with timing assertions.
+/// The current thread is blocked, until the checks are completed.
+///
+/// Call it before [`run_on_core`] or [`run_on_all_cores`](crate::run_on_all_cores).
+///
+/// # Note
+///
+/// If the [`Sandbox`](crate::sandbox::Sandbox) kills the process on violations, a failed check kills it too.
+///
+/// # Example
+///
+/// ```ignore
+/// use engine::{run_on_all_cores, self_check};
+/// use engine::cfg::{set_selector, SelectorType};
+///
+/// fn main() {
+///     if let Err(err) = self_check() {
+///         eprintln!("{}, falling back to epoll", err);
+///         set_selector(SelectorType::Poller);
+///         self_check().expect("the engine can't work in this environment");
+///     }
+///     run_on_all_cores(start_server);
+/// }
+/// ```
+pub fn self_check() -> Result<(), SelfCheckError> {
+    let core = core::get_core_ids().unwrap()[0];
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("coroeng-self-check".to_string())
+        .spawn(move || {
+            let _ = sender.send(run_on_core(run_checks, core));
+        })
+        .expect("failed to create the self check thread");
+
+    match receiver.recv_timeout(SELF_CHECK_TIMEOUT) {
+        Ok(Ok(Some(res))) => res,
+        Ok(Ok(None)) | Err(RecvTimeoutError::Disconnected) => Err(SelfCheckError::Panicked),
+        Ok(Err(err)) => Err(SelfCheckError::Run(err)),
+        Err(RecvTimeoutError::Timeout) => Err(SelfCheckError::TimedOut)
+    }
+}
+
+#[coro(crate="crate")]
+fn run_checks() -> Result<(), SelfCheckError> {
+    let res: Result<(), Error> = wait!(check_tcp_echo());
+    if let Err(error) = res {
+        return Err(SelfCheckError::Failed { check: "tcp echo", error });
+    }
+
+    let res: Result<(), Error> = wait!(check_file());
+    if let Err(error) = res {
+        return Err(SelfCheckError::Failed { check: "file", error });
+    }
+
+    // The last one, so streams and files of the previous checks are closed before the worker is stopped.
+    let res: Result<(), Error> = wait!(check_sleep());
+    if let Err(error) = res {
+        return Err(SelfCheckError::Failed { check: "sleep", error });
+    }
+
+    Ok(())
+}
+
+#[coro(crate="crate")]
+fn check_tcp_echo() -> Result<(), Error> {
+    let listener: TcpListener = yield TcpListener::new(SocketAddr::from(([127, 0, 0, 1], 0)));
+    let addr = match listener.local_addr() {
+        Ok(addr) => addr,
+        Err(err) => return Err(err)
+    };
+    local_scheduler().spawn(echo_once(listener, null_mut()));
+
+    let res: Result<TcpStream, Error> = yield TcpStream::connect(addr);
+    let mut stream = match res {
+        Ok(stream) => stream,
+        Err(err) => return Err(err)
+    };
+    let mut buf = buffer();
+    buf.append(CHECK_MESSAGE);
+    let res: Result<(), Error> = yield stream.write_all(buf);
+    if let Err(err) = res {
+        return Err(err);
+    }
+
+    let mut echoed = Vec::with_capacity(CHECK_MESSAGE.len());
+    while echoed.len() < CHECK_MESSAGE.len() {
+        let res: Result<&[u8], Error> = yield stream.read();
+        let slice = match res {
+            Ok(slice) => slice,
+            Err(err) => return Err(err)
+        };
+        if slice.is_empty() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "the echo connection was closed"));
+        }
+        echoed.extend_from_slice(slice);
+    }
+
+    if echoed != CHECK_MESSAGE {
+        return Err(Error::new(ErrorKind::InvalidData, "the echo differs from the message"));
+    }
+    Ok(())
+}
+
+/// Accepts one connection and echoes [`CHECK_MESSAGE`] back.
+#[coro(crate="crate")]
+fn echo_once(mut listener: TcpListener) {
+    let res: Result<TcpStream, Error> = yield listener.accept();
+    let mut stream = match res {
+        Ok(stream) => stream,
+        Err(_) => return
+    };
+
+    let mut echoed = 0;
+    while echoed < CHECK_MESSAGE.len() {
+        let res: Result<&[u8], Error> = yield stream.read();
+        let mut buf = buffer();
+        match res {
+            Ok(slice) if !slice.is_empty() => buf.append(slice),
+            _ => return
+        };
+        echoed += buf.len();
+
+        let res: Result<(), Error> = yield stream.write_all(buf);
+        if res.is_err() {
+            return;
+        }
+    }
+}
+
+#[coro(crate="crate")]
+fn check_file() -> Result<(), Error> {
+    let res: Result<File, Error> = wait!(tempfile());
+    let mut file = match res {
+        Ok(file) => file,
+        Err(err) => return Err(err)
+    };
+    let mut buf = buffer();
+    buf.append(CHECK_MESSAGE);
+    let res: Result<(), Error> = yield file.write_all(buf);
+    if let Err(err) = res {
+        return Err(err);
+    }
+
+    let res: Result<Buffer, Error> = yield file.pread(0);
+    return match res {
+        Ok(read) if read.as_ref() == CHECK_MESSAGE => Ok(()),
+        Ok(_) => Err(Error::new(ErrorKind::InvalidData, "the read data differs from the written one")),
+        Err(err) => Err(err)
+    };
+}
+
+#[coro(crate="crate")]
+fn check_sleep() -> Result<(), Error> {
+    let start = Instant::now();
+    yield sleep(SLEEP_DURATION);
+    let elapsed = start.elapsed();
+    if elapsed < SLEEP_DURATION {
+        return Err(Error::other(format!("woke up after {:?} instead of {:?}", elapsed, SLEEP_DURATION)));
+    }
+    if elapsed > SLEEP_DURATION + MAX_SLEEP_DELAY {
+        return Err(Error::new(ErrorKind::TimedOut, format!("woke up after {:?} instead of {:?}", elapsed, SLEEP_DURATION)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::self_check::self_check;
+
+    #[test]
+    fn test_self_check() {
+        self_check().unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+//! This module contains [`Deadline`].
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
+
+/// A point in time, after which an operation is timed out. It can be [`never`](Deadline::never).
+///
+/// Pass the deadline of the parent to the child with [`wait_within!`](crate::wait_within),
+/// so the child gets the remaining time of the parent without nested timeout math.
+/// The child can only shorten it with [`Deadline::shorten`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::time::Duration;
+/// use engine::{coro, wait_within};
+/// use engine::sleep::Deadline;
+///
+/// #[coro]
+/// fn query(deadline: Deadline) -> Result<(), Error> {
+///     // The query itself takes at most 100 milliseconds, but not more than the parent has.
+///     let deadline = deadline.shorten(Duration::from_millis(100));
+///     deadline.check()?;
+///     Ok(())
+/// }
+///
+/// #[coro]
+/// fn handle_request() -> Result<(), Error> {
+///     let deadline = Deadline::after(Duration::from_secs(1));
+///     let first: Result<(), Error> = wait_within!(query(), deadline);
+///     // The second query gets only the rest of the second.
+///     let second: Result<(), Error> = wait_within!(query(), deadline);
+///     first.and(second)
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Deadline {
+    instant: Option<Instant>
+}
+
+impl Deadline {
+    /// Creates a deadline after `dur` from now.
+    pub fn after(dur: Duration) -> Self {
+        Self { instant: Instant::now().checked_add(dur) }
+    }
+
+    /// Creates a deadline at `instant`.
+    pub const fn at(instant: Instant) -> Self {
+        Self { instant: Some(instant) }
+    }
+
+    /// Creates a deadline, that never expires.
+    pub const fn never() -> Self {
+        Self { instant: None }
+    }
+
+    /// Returns the instant of the deadline or `None`, if it never expires.
+    #[inline(always)]
+    pub fn instant(&self) -> Option<Instant> {
+        self.instant
+    }
+
+    /// Returns the remaining time or `None`, if the deadline never expires. Expired deadline returns [`Duration::ZERO`].
+    pub fn remaining(&self) -> Option<Duration> {
+        self.instant.map(|instant| instant.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns true, if the deadline is expired.
+    pub fn is_expired(&self) -> bool {
+        match self.instant {
+            Some(instant) => Instant::now() >= instant,
+            None => false
+        }
+    }
+
+    /// Returns the earliest of the two deadlines.
+    pub fn min(self, other: Self) -> Self {
+        match (self.instant, other.instant) {
+            (Some(a), Some(b)) => Self::at(a.min(b)),
+            (Some(_), None) => self,
+            (None, _) => other
+        }
+    }
+
+    /// Returns the deadline, that is not later than `dur` from now.
+    pub fn shorten(self, dur: Duration) -> Self {
+        self.min(Self::after(dur))
+    }
+
+    /// Returns [`ErrorKind::TimedOut`], if the deadline is expired.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_expired() {
+            return Err(Error::new(ErrorKind::TimedOut, "the deadline is expired"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+    use std::time::Duration;
+    use crate::{coro, test_local, wait_within};
+    use crate::sleep::{sleep, Deadline};
+
+    #[test]
+    fn test_deadline() {
+        let never = Deadline::never();
+        assert_eq!(never.remaining(), None);
+        assert!(!never.is_expired());
+
+        let soon = Deadline::after(Duration::from_secs(10));
+        assert!(soon.remaining().unwrap() <= Duration::from_secs(10));
+        assert_eq!(never.min(soon), soon);
+        assert_eq!(soon.min(never), soon);
+        assert!(soon.shorten(Duration::from_secs(1)).remaining().unwrap() <= Duration::from_secs(1));
+        assert_eq!(soon.shorten(Duration::from_secs(100)), soon);
+
+        let expired = Deadline::after(Duration::ZERO);
+        assert!(expired.is_expired());
+        assert_eq!(expired.remaining(), Some(Duration::ZERO));
+        assert_eq!(expired.check().unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_wait_within() {
+        #[coro(crate="crate")]
+        fn step(deadline: Deadline) -> Result<Duration, Error> {
+            yield sleep(Duration::from_millis(60));
+            if let Err(err) = deadline.check() {
+                return Err(err);
+            }
+            Ok(deadline.remaining().unwrap())
+        }
+
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let first: Result<Duration, Error> = wait_within!(step(), deadline);
+        assert!(first.unwrap() <= Duration::from_millis(40));
+        let second: Result<Duration, Error> = wait_within!(step(), deadline);
+        assert_eq!(second.unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+}
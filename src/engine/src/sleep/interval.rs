@@ -0,0 +1,158 @@
+//! This module contains [`interval`] and [`Ticker`].
+use std::time::{Duration, Instant};
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::stream::CoStream;
+
+/// Returns the [`Ticker`], that ticks every `period`. The first tick completes immediately.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::time::{Duration, Instant};
+/// use engine::coro;
+/// use engine::sleep::{interval, Ticker};
+///
+/// #[coro]
+/// fn flush_metrics() {
+///     let mut ticker = interval(Duration::from_secs(10));
+///     loop {
+///         let _: Instant = yield ticker.tick();
+///         // The flush takes some time, but the next one starts exactly 10 seconds after this one.
+///     }
+/// }
+/// ```
+pub fn interval(period: Duration) -> Ticker {
+    interval_at(Instant::now(), period)
+}
+
+/// Returns the [`Ticker`], that ticks every `period` starting at `start`.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Ticker {
+    assert!(!period.is_zero(), "the period of the interval must be greater than zero");
+    Ticker { next: start, period, missed: 0 }
+}
+
+/// Ticks with a fixed period. It is created by [`interval`] and [`interval_at`].
+///
+/// Ticks are scheduled from the start, not from the previous wake up, so the time of the work between ticks
+/// and late wake ups don't accumulate drift. If the coroutine is late for whole periods, the missed ticks are skipped
+/// (and counted by [`Ticker::missed`]) instead of completing in a burst.
+///
+/// [`Ticker`] is a [`CoStream`] of the instants of ticks, that never ends.
+pub struct Ticker {
+    /// The instant of the next tick.
+    next: Instant,
+    period: Duration,
+    missed: u64
+}
+
+/// Writes the scheduled instant of the tick before the wait, because the sleep has no result.
+fn write_tick(res: *mut Instant, scheduled: Instant) {
+    unsafe { res.write(scheduled) };
+}
+
+impl Ticker {
+    /// Waits for the next tick and returns its scheduled instant. Use it with `yield`.
+    pub fn tick(&mut self, res: *mut Instant) -> YieldStatus {
+        let now = Instant::now();
+        let scheduled = self.next;
+        write_tick(res, scheduled);
+
+        self.next = scheduled + self.period;
+        if self.next <= now {
+            // Whole periods are missed, so the next tick is the first one after now.
+            let behind = (now - self.next).as_nanos() / self.period.as_nanos() + 1;
+            self.missed += behind as u64;
+            self.next += self.period * behind as u32;
+        }
+
+        if scheduled <= now {
+            return YieldStatus::yield_now();
+        }
+        YieldStatus::sleep_until(scheduled)
+    }
+
+    /// Returns the period.
+    #[inline(always)]
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns the scheduled instant of the next tick.
+    #[inline(always)]
+    pub fn next_tick(&self) -> Instant {
+        self.next
+    }
+
+    /// Returns the number of skipped ticks.
+    #[inline(always)]
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Schedules the next tick after the period from now.
+    pub fn reset(&mut self) {
+        self.next = Instant::now() + self.period;
+    }
+}
+
+impl CoStream<Instant> for Ticker {
+    fn next(&mut self, res: *mut Option<Instant>) -> CoroutineImpl {
+        next_instant(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_instant(ticker: *mut Ticker) -> Option<Instant> {
+    let ticker = unsafe { &mut *ticker };
+    let instant: Instant = yield ticker.tick();
+    return Some(instant);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::{test_local, wait};
+    use crate::sleep::{interval, sleep};
+    use crate::stream::CoStream;
+
+    #[test_local(crate="crate")]
+    fn test_interval() {
+        let period = Duration::from_millis(5);
+        let start = Instant::now();
+        let mut ticker = interval(period);
+        let mut instants = Vec::new();
+        for _ in 0..4 {
+            let instant: Instant = yield ticker.tick();
+            assert!(Instant::now() >= instant);
+            instants.push(instant);
+            // The work between ticks doesn't shift the next ones.
+            yield sleep(Duration::from_millis(2));
+        }
+        assert!(start.elapsed() >= period * 3);
+        assert!(instants.windows(2).all(|pair| pair[1] - pair[0] == period));
+        assert_eq!(ticker.missed(), 0);
+
+        // Missed ticks are skipped.
+        yield sleep(period * 3);
+        let late: Instant = yield ticker.tick();
+        assert_eq!(late, instants[3] + period);
+        assert!(ticker.missed() >= 2);
+        assert!(ticker.next_tick() > Instant::now());
+
+        let mut ticks = ticker.take(2);
+        let first: Option<Instant> = wait!(ticks.next());
+        let second: Option<Instant> = wait!(ticks.next());
+        assert_eq!(second.unwrap() - first.unwrap(), period);
+        let end: Option<Instant> = wait!(ticks.next());
+        assert!(end.is_none());
+    }
+}
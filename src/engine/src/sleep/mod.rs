@@ -1,18 +1,35 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 use std::time::{Duration, Instant};
 use crate::coroutine::coroutine::{CoroutineImpl};
 use crate::coroutine::YieldStatus;
+use crate::local_scheduler;
+
+pub mod deadline;
+pub mod interval;
+mod wheel;
+
+pub use deadline::Deadline;
+pub use interval::{interval, interval_at, Ticker};
+use wheel::TimerWheel;
 
 /// A coroutine that will be executed after a certain amount of time.
 pub(crate) struct SleepingCoroutine {
     pub(crate) execution_time: Instant,
+    /// The insertion order. It is set by [`Timers`], so coroutines with the same execution time are woken up in FIFO order.
+    pub(crate) seq: u64,
     pub(crate) co: CoroutineImpl,
 }
 
 impl SleepingCoroutine {
     pub fn new(dur: Duration, co: CoroutineImpl) -> Self {
+        Self::at(Instant::now() + dur, co)
+    }
+
+    pub fn at(execution_time: Instant, co: CoroutineImpl) -> Self {
         Self {
-            execution_time: Instant::now() + dur,
+            execution_time,
+            seq: 0,
             co
         }
     }
@@ -44,24 +61,284 @@ pub fn sleep(dur: Duration, _res: *mut ()) -> YieldStatus {
     YieldStatus::sleep(dur)
 }
 
+/// Tell the scheduler to wake the coroutine up at the instant. An instant in the past wakes it up at the next poll.
+///
+/// Unlike [`sleep`], the deadline is fixed, so the coroutine doesn't recompute the remaining time around other yields.
+/// Coroutines with the same instant are woken up in the order, in which they fell asleep.
+///
+/// # Note
+///
+/// It can be woken up later, than it was indicated, but never earlier.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::time::{Duration, Instant};
+/// use engine::coro;
+/// use engine::sleep::sleep_until;
+///
+/// #[coro]
+/// fn retry_until(deadline: Instant) {
+///     let next_attempt = Instant::now() + Duration::from_millis(100);
+///     // some work here
+///     yield sleep_until(next_attempt.min(deadline));
+/// }
+/// ```
+pub fn sleep_until(instant: Instant, _res: *mut ()) -> YieldStatus {
+    YieldStatus::sleep_until(instant)
+}
+
+/// Starts the coroutines after their durations. It is the batch version of `spawn_local!` with [`sleep`] at the start.
+///
+/// All timers are inserted at once: the current time is read once, and a large batch is put straight into the timer wheel,
+/// so it is much faster than inserting millions of timers one by one.
+///
+/// The coroutines are counted as spawned, read [`Scheduler::spawn`](crate::scheduler::Scheduler::spawn).
+///
+/// # Example
+///
+/// ```ignore
+/// use std::ptr::null_mut;
+/// use std::time::Duration;
+/// use engine::coro;
+/// use engine::sleep::sleep_many;
+///
+/// #[coro]
+/// fn expire(session: u64) {
+///     println!("session {} is expired", session);
+/// }
+///
+/// #[coro]
+/// fn schedule_expirations() {
+///     sleep_many((0..1_000_000).map(|session| (Duration::from_secs(60), expire(session, null_mut()))));
+/// }
+/// ```
+pub fn sleep_many<I: IntoIterator<Item = (Duration, CoroutineImpl)>>(timers: I) {
+    local_scheduler().sleep_many(timers);
+}
+
+/// The number of timers, after which [`Timers`] moves them from the binary heap to the [`TimerWheel`].
+const LARGE_TIMERS: usize = 4096;
+
+/// The storage of [`Timers`].
+enum Storage {
+    /// A binary heap. Timers are stored in one contiguous allocation and are woken up exactly at their time,
+    /// but inserting and popping are `O(log n)`.
+    Small(BinaryHeap<Reverse<SleepingCoroutine>>),
+    /// A hierarchical timing wheel. Inserting and popping are `O(1)`, but timers are woken up with the precision of the tick.
+    Large(TimerWheel)
+}
+
+impl Storage {
+    /// Moves the timers from the heap to the wheel.
+    fn use_wheel(&mut self, tick: Duration) {
+        let mut wheel = TimerWheel::new(tick, Instant::now());
+        if let Storage::Small(heap) = self {
+            heap.drain().for_each(|Reverse(timer)| wheel.insert(timer));
+        }
+        *self = Storage::Large(wheel);
+    }
+}
+
+/// The storage of [`SleepingCoroutine`]s. Timers with the same execution time are not deduplicated.
+///
+/// A few timers are stored in a binary heap. When there are more than [`LARGE_TIMERS`] of them,
+/// they are moved to a [`TimerWheel`] with the [`tick`](crate::cfg::config_timer_tick) from the config,
+/// and it is used until all of its timers are woken up.
+pub(crate) struct Timers {
+    storage: Storage,
+    tick: Duration,
+    /// The sequence number of the next inserted timer.
+    next_seq: u64
+}
+
+impl Timers {
+    pub(crate) fn new(tick: Duration) -> Self {
+        Self { storage: Storage::Small(BinaryHeap::new()), tick, next_seq: 0 }
+    }
+
+    #[inline(always)]
+    pub(crate) fn insert(&mut self, mut timer: SleepingCoroutine) {
+        timer.seq = self.next_seq;
+        self.next_seq += 1;
+        match &mut self.storage {
+            Storage::Small(heap) => {
+                heap.push(Reverse(timer));
+                if heap.len() > LARGE_TIMERS {
+                    self.storage.use_wheel(self.tick);
+                }
+            }
+            Storage::Large(wheel) => wheel.insert(timer)
+        }
+    }
+
+    /// Inserts all timers. A large batch is inserted into the wheel, else the storage of the heap is reserved once.
+    pub(crate) fn extend<I: Iterator<Item = SleepingCoroutine>>(&mut self, timers: I) {
+        let next_seq = &mut self.next_seq;
+        let timers = timers.map(|mut timer| {
+            timer.seq = *next_seq;
+            *next_seq += 1;
+            timer
+        });
+        if let Storage::Small(heap) = &mut self.storage {
+            let additional = timers.size_hint().0;
+            if heap.len() + additional <= LARGE_TIMERS {
+                heap.reserve(additional);
+                heap.extend(timers.map(Reverse));
+                if heap.len() > LARGE_TIMERS {
+                    self.storage.use_wheel(self.tick);
+                }
+                return;
+            }
+            self.storage.use_wheel(self.tick);
+        }
+
+        if let Storage::Large(wheel) = &mut self.storage {
+            timers.for_each(|timer| wheel.insert(timer));
+        }
+    }
+
+    /// Pops the coroutine, which execution time is not after `now`.
+    #[inline(always)]
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<CoroutineImpl> {
+        match &mut self.storage {
+            Storage::Small(heap) => match heap.peek() {
+                Some(Reverse(timer)) if timer.execution_time <= now => heap.pop().map(|Reverse(timer)| timer.co),
+                _ => None
+            },
+            Storage::Large(wheel) => {
+                let co = wheel.pop_expired(now);
+                if wheel.len() == 0 {
+                    self.storage = Storage::Small(BinaryHeap::new());
+                }
+                co
+            }
+        }
+    }
+
+    /// Returns the time until the next timer expires. With the wheel it is the tick, because its timers are not ordered.
+    #[inline(always)]
+    pub(crate) fn next_expiration(&self, now: Instant) -> Option<Duration> {
+        match &self.storage {
+            Storage::Small(heap) => heap.peek().map(|Reverse(timer)| timer.execution_time.saturating_duration_since(now)),
+            Storage::Large(_) => Some(self.tick)
+        }
+    }
+
+    /// Returns the number of sleeping coroutines.
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Small(heap) => heap.len(),
+            Storage::Large(wheel) => wheel.len()
+        }
+    }
+
+    pub(crate) fn iter(&self) -> Box<dyn Iterator<Item = &CoroutineImpl> + '_> {
+        match &self.storage {
+            Storage::Small(heap) => Box::new(heap.iter().map(|Reverse(timer)| &timer.co)),
+            Storage::Large(wheel) => Box::new(wheel.iter())
+        }
+    }
+}
+
 unsafe impl Send for SleepingCoroutine {}
 
 impl Eq for SleepingCoroutine {}
 
 impl PartialEq<Self> for SleepingCoroutine {
     fn eq(&self, other: &Self) -> bool {
-        self.execution_time == other.execution_time
+        self.execution_time == other.execution_time && self.seq == other.seq
     }
 }
 
 impl PartialOrd<Self> for SleepingCoroutine {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.execution_time.partial_cmp(&other.execution_time)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for SleepingCoroutine {
+    /// Coroutines with the same execution time are ordered by the insertion, so they never collide.
     fn cmp(&self, other: &Self) -> Ordering {
-        self.execution_time.cmp(&other.execution_time)
+        self.execution_time.cmp(&other.execution_time).then(self.seq.cmp(&other.seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::local::Local;
+    use crate::sleep::{sleep, sleep_many, sleep_until};
+    use crate::local_scheduler;
+    use std::time::Instant;
+
+    #[test_local(crate="crate")]
+    fn test_sleep_many() {
+        #[coro(crate="crate")]
+        fn insert(number: u16, arr: Local<Vec<u16>>) {
+            arr.get_mut().push(number);
+        }
+
+        let arr = Local::new(Vec::new());
+        // Timers with the same execution time must not be lost.
+        sleep_many((0..100).map(|_| (Duration::from_millis(2), insert(1, arr.clone(), null_mut()))));
+        sleep_many([
+            (Duration::from_millis(1), insert(0, arr.clone(), null_mut())),
+            (Duration::from_millis(3), insert(2, arr.clone(), null_mut()))
+        ]);
+        assert!(arr.get().is_empty());
+
+        yield sleep(Duration::from_millis(10));
+        let arr = arr.get();
+        assert_eq!(arr.len(), 102);
+        assert_eq!(arr[0], 0);
+        assert!(arr[1..101].iter().all(|&number| number == 1));
+        assert_eq!(arr[101], 2);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_sleep_until() {
+        #[coro(crate="crate")]
+        fn insert_at(instant: Instant, number: u16, asleep: Local<Vec<u16>>, woken: Local<Vec<u16>>) {
+            asleep.get_mut().push(number);
+            yield sleep_until(instant);
+            assert!(Instant::now() >= instant);
+            woken.get_mut().push(number);
+        }
+
+        let asleep = Local::new(Vec::new());
+        let woken = Local::new(Vec::new());
+        let instant = Instant::now() + Duration::from_millis(3);
+        // Coroutines with the same instant are woken up in the order, in which they fell asleep.
+        for number in 1..=10 {
+            local_scheduler().spawn(insert_at(instant, number, asleep.clone(), woken.clone(), null_mut()));
+        }
+        // An instant in the past wakes the coroutine up at once.
+        local_scheduler().spawn(insert_at(Instant::now() - Duration::from_millis(1), 0, Local::new(Vec::new()), woken.clone(), null_mut()));
+
+        yield sleep_until(instant + Duration::from_millis(5));
+        let mut expected = vec![0];
+        expected.extend_from_slice(&asleep.get());
+        assert_eq!(*woken.get(), expected);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_many_sleepers() {
+        #[coro(crate="crate")]
+        fn insert(number: u16, arr: Local<Vec<u16>>) {
+            arr.get_mut().push(number);
+        }
+
+        // More timers, than the binary heap stores, so they are moved to the timer wheel.
+        let arr = Local::new(Vec::new());
+        sleep_many((0..10_000).map(|i| (Duration::from_millis(1 + i % 5), insert((i % 5) as u16, arr.clone(), null_mut()))));
+        yield sleep(Duration::from_millis(20));
+
+        let arr = arr.get();
+        assert_eq!(arr.len(), 10_000);
+        assert!(arr.is_sorted());
     }
 }
@@ -0,0 +1,264 @@
+//! This module contains [`TimerWheel`], the storage of timers for many sleeping coroutines.
+use std::collections::VecDeque;
+use std::mem;
+use std::time::{Duration, Instant};
+use crate::coroutine::CoroutineImpl;
+use crate::sleep::SleepingCoroutine;
+
+/// The number of bits of the slot index in a level.
+const SLOT_BITS: u32 = 6;
+/// The number of slots in a level.
+const SLOTS: usize = 1 << SLOT_BITS;
+/// The number of levels. With the tick of 1ms the wheel covers `64^6` ms (more than 2 years).
+const LEVELS: usize = 6;
+/// The ticks of one turn of the wheel are the ticks with the same bits above this mask.
+const TURN_MASK: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// A timer and the tick, after which it expires.
+struct WheelTimer {
+    tick: u64,
+    timer: SleepingCoroutine
+}
+
+/// One level of the wheel. A slot of the level `n` stores the timers of `64^n` ticks.
+struct Level {
+    slots: [Vec<WheelTimer>; SLOTS],
+    /// The bit `i` is set, if the slot `i` is not empty.
+    occupied: u64
+}
+
+impl Level {
+    fn new() -> Self {
+        Self { slots: std::array::from_fn(|_| Vec::new()), occupied: 0 }
+    }
+}
+
+/// A hierarchical timing wheel. It is used by [`Timers`](crate::sleep::Timers), when there are many sleeping coroutines.
+///
+/// The time is divided into ticks. Inserting a timer and expiring it are `O(1)`:
+/// a timer is put into the slot of its tick, and the expired slots are taken at once.
+/// Far timers are stored in the higher levels with coarse slots and are moved down, when their slot comes.
+///
+/// Timers expire at the start of the tick after their execution time, so they are never woken up earlier,
+/// but can be woken up up to one tick later. Timers of one tick are woken up in the insertion order.
+pub(crate) struct TimerWheel {
+    start: Instant,
+    tick: Duration,
+    /// The last processed tick. All timers of this and earlier ticks are in `expired`.
+    elapsed: u64,
+    levels: Box<[Level; LEVELS]>,
+    /// Timers of the next turns of the wheel. They are reinserted, when the turn ends.
+    overflow: Vec<WheelTimer>,
+    expired: VecDeque<CoroutineImpl>,
+    len: usize
+}
+
+impl TimerWheel {
+    /// Creates an empty wheel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is zero.
+    pub(crate) fn new(tick: Duration, now: Instant) -> Self {
+        assert!(!tick.is_zero(), "the tick of the timer wheel must be greater than zero");
+        Self {
+            start: now,
+            tick,
+            elapsed: 0,
+            levels: Box::new(std::array::from_fn(|_| Level::new())),
+            overflow: Vec::new(),
+            expired: VecDeque::new(),
+            len: 0
+        }
+    }
+
+    /// Returns the number of timers, including expired ones, that are not popped yet.
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the first tick, that starts not earlier than `time`.
+    fn tick_of(&self, time: Instant) -> u64 {
+        let since_start = time.saturating_duration_since(self.start).as_nanos();
+        since_start.div_ceil(self.tick.as_nanos()) as u64
+    }
+
+    /// Returns the last tick, that has started not later than `time`.
+    fn passed_ticks(&self, time: Instant) -> u64 {
+        (time.saturating_duration_since(self.start).as_nanos() / self.tick.as_nanos()) as u64
+    }
+
+    pub(crate) fn insert(&mut self, timer: SleepingCoroutine) {
+        let tick = self.tick_of(timer.execution_time);
+        self.len += 1;
+        self.insert_at(WheelTimer { tick, timer });
+    }
+
+    /// Puts the timer into its slot or into the expired ones.
+    fn insert_at(&mut self, timer: WheelTimer) {
+        if timer.tick <= self.elapsed {
+            self.expired.push_back(timer.timer.co);
+            return;
+        }
+
+        let level = level_for(self.elapsed, timer.tick);
+        if level == LEVELS {
+            self.overflow.push(timer);
+            return;
+        }
+
+        let slot = slot_for(timer.tick, level);
+        let level = &mut self.levels[level];
+        level.slots[slot].push(timer);
+        level.occupied |= 1 << slot;
+    }
+
+    /// Returns the first tick, when a slot must be processed, or [`None`], if the wheel is empty.
+    /// The slot `0` of the level [`LEVELS`] means the overflow.
+    fn next_expiration(&self) -> Option<(u64, usize, usize)> {
+        for (number, level) in self.levels.iter().enumerate() {
+            if level.occupied == 0 {
+                continue;
+            }
+
+            let shift = SLOT_BITS * number as u32;
+            let slot_range = 1u64 << shift;
+            let level_range = slot_range << SLOT_BITS;
+            let current = ((self.elapsed >> shift) as usize) % SLOTS;
+            let distance = level.occupied.rotate_right(current as u32).trailing_zeros() as usize;
+            let slot = (current + distance) % SLOTS;
+
+            let level_start = self.elapsed & !(level_range - 1);
+            let mut tick = level_start + slot as u64 * slot_range;
+            if tick <= self.elapsed && number > 0 {
+                tick += level_range;
+            }
+            // Lower levels expire earlier than the slots of higher levels, so the first occupied level is the answer.
+            return Some((tick.max(self.elapsed + 1), number, slot));
+        }
+
+        if !self.overflow.is_empty() {
+            return Some(((self.elapsed | TURN_MASK) + 1, LEVELS, 0));
+        }
+        None
+    }
+
+    /// Moves all timers, that expire not later than `now`, to the expired ones.
+    fn advance(&mut self, now: Instant) {
+        let target = self.passed_ticks(now);
+        while let Some((tick, level, slot)) = self.next_expiration() {
+            if tick > target {
+                break;
+            }
+
+            self.elapsed = tick;
+            let timers = if level == LEVELS {
+                mem::take(&mut self.overflow)
+            } else {
+                let level = &mut self.levels[level];
+                level.occupied &= !(1 << slot);
+                mem::take(&mut level.slots[slot])
+            };
+            for timer in timers {
+                self.insert_at(timer);
+            }
+        }
+        self.elapsed = self.elapsed.max(target);
+    }
+
+    /// Pops the coroutine, which execution time is not after `now`.
+    #[inline(always)]
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<CoroutineImpl> {
+        if self.expired.is_empty() {
+            if self.len == 0 {
+                return None;
+            }
+            self.advance(now);
+        }
+
+        let co = self.expired.pop_front()?;
+        self.len -= 1;
+        Some(co)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &CoroutineImpl> {
+        self.expired.iter()
+            .chain(self.levels.iter().flat_map(|level| {
+                level.slots.iter().flat_map(|slot| slot.iter().map(|timer| &timer.timer.co))
+            }))
+            .chain(self.overflow.iter().map(|timer| &timer.timer.co))
+    }
+}
+
+/// Returns the level, in which the timer of the `tick` is stored, when `elapsed` ticks are processed.
+/// It is the level of the highest bits, that differ, or [`LEVELS`], if the tick is in the next turns.
+#[inline(always)]
+fn level_for(elapsed: u64, tick: u64) -> usize {
+    let masked = (elapsed ^ tick) | (SLOTS as u64 - 1);
+    let significant = 63 - masked.leading_zeros();
+    ((significant / SLOT_BITS) as usize).min(LEVELS)
+}
+
+#[inline(always)]
+fn slot_for(tick: u64, level: usize) -> usize {
+    ((tick >> (SLOT_BITS * level as u32)) as usize) % SLOTS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::coroutine::CoroutineImpl;
+    use crate::sleep::SleepingCoroutine;
+    use crate::sleep::wheel::TimerWheel;
+
+    fn timer(execution_time: Instant, id: usize, woken: *mut Vec<usize>) -> SleepingCoroutine {
+        let co: CoroutineImpl = Box::pin(#[coroutine] static move || {
+            unsafe { (*woken).push(id) };
+        });
+        SleepingCoroutine::at(execution_time, co)
+    }
+
+    fn wake(wheel: &mut TimerWheel, now: Instant) {
+        while let Some(mut co) = wheel.pop_expired(now) {
+            let _ = co.as_mut().resume(());
+        }
+    }
+
+    #[test]
+    fn test_timer_wheel() {
+        let start = Instant::now();
+        let tick = Duration::from_millis(1);
+        let mut wheel = TimerWheel::new(tick, start);
+        let mut woken = Vec::new();
+        let woken_ptr: *mut Vec<usize> = &mut woken;
+
+        // Timers of all levels and one beyond the wheel.
+        let offsets = [0, 1, 5, 63, 64, 65, 4095, 4096, 300_000, 20_000_000, 1 << 40];
+        for (id, &offset) in offsets.iter().enumerate().rev() {
+            wheel.insert(timer(start + Duration::from_millis(offset) + Duration::from_nanos(1), id, woken_ptr));
+        }
+        assert_eq!(wheel.len(), offsets.len());
+
+        for (id, &offset) in offsets.iter().enumerate() {
+            // A timer is never woken up before its execution time.
+            wake(&mut wheel, start + Duration::from_millis(offset));
+            assert_eq!(woken.len(), id, "the timer {} is woken up too early", id);
+            // And it is woken up at the next tick.
+            wake(&mut wheel, start + Duration::from_millis(offset + 1));
+            assert_eq!(woken, (0..=id).collect::<Vec<_>>());
+        }
+        assert_eq!(wheel.len(), 0);
+
+        // Timers of one tick are woken up in the insertion order.
+        let now = start + Duration::from_millis(1 << 40);
+        for id in 0..3 {
+            wheel.insert(timer(now + tick * 10, 100 + id, woken_ptr));
+        }
+        wheel.insert(timer(now + tick * 100, 103, woken_ptr));
+        wake(&mut wheel, now + tick * 11);
+        assert_eq!(&woken[woken.len() - 3..], &[100, 101, 102]);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(wheel.iter().count(), 1);
+    }
+}
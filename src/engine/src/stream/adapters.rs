@@ -0,0 +1,191 @@
+//! This module contains the adapters of [`CoStream`]: [`Map`], [`Take`] and [`BufferChunks`].
+use std::marker::PhantomData;
+use std::mem;
+use crate::{coro, wait};
+use crate::coroutine::CoroutineImpl;
+use crate::stream::CoStream;
+
+/// The stream, that applies the function to every item. It is created by [`CoStream::map`].
+pub struct Map<S, T, F> {
+    stream: S,
+    f: F,
+    _item: PhantomData<T>
+}
+
+impl<S, T, F> Map<S, T, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f, _item: PhantomData }
+    }
+
+    /// Returns the inner stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<T: 'static, U: 'static, S: CoStream<T> + 'static, F: FnMut(T) -> U + 'static> CoStream<U> for Map<S, T, F> {
+    fn next(&mut self, res: *mut Option<U>) -> CoroutineImpl {
+        next_mapped(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_mapped<T: 'static, U: 'static, S: CoStream<T> + 'static, F: FnMut(T) -> U + 'static>(map: *mut Map<S, T, F>) -> Option<U> {
+    let map = unsafe { &mut *map };
+    let item: Option<T> = wait!(map.stream.next());
+    return item.map(&mut map.f);
+}
+
+/// The stream, that ends after the number of items. It is created by [`CoStream::take`].
+pub struct Take<S> {
+    stream: S,
+    remaining: usize
+}
+
+impl<S> Take<S> {
+    pub(crate) fn new(stream: S, n: usize) -> Self {
+        Self { stream, remaining: n }
+    }
+
+    /// Returns how many items can be returned before the end.
+    #[inline(always)]
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns the inner stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<T: 'static, S: CoStream<T> + 'static> CoStream<T> for Take<S> {
+    fn next(&mut self, res: *mut Option<T>) -> CoroutineImpl {
+        next_taken(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_taken<T: 'static, S: CoStream<T> + 'static>(take: *mut Take<S>) -> Option<T> {
+    let take = unsafe { &mut *take };
+    if take.remaining == 0 {
+        return None;
+    }
+
+    let item: Option<T> = wait!(take.stream.next());
+    if item.is_some() {
+        take.remaining -= 1;
+    } else {
+        take.remaining = 0;
+    }
+    return item;
+}
+
+/// The stream, that groups items into vectors. It is created by [`CoStream::buffer_chunks`].
+pub struct BufferChunks<S, T> {
+    stream: S,
+    size: usize,
+    is_ended: bool,
+    chunk: Vec<T>
+}
+
+impl<S, T> BufferChunks<S, T> {
+    pub(crate) fn new(stream: S, size: usize) -> Self {
+        assert!(size > 0, "the size of chunks must be greater than 0");
+        Self { stream, size, is_ended: false, chunk: Vec::with_capacity(size) }
+    }
+
+    /// Returns the inner stream. Items of the unfinished chunk are lost.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<T: 'static, S: CoStream<T> + 'static> CoStream<Vec<T>> for BufferChunks<S, T> {
+    fn next(&mut self, res: *mut Option<Vec<T>>) -> CoroutineImpl {
+        next_chunk(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_chunk<T: 'static, S: CoStream<T> + 'static>(chunks: *mut BufferChunks<S, T>) -> Option<Vec<T>> {
+    let chunks = unsafe { &mut *chunks };
+    // The chunk is kept in the adapter, so items are not lost, if the coroutine is dropped while waiting.
+    while !chunks.is_ended && chunks.chunk.len() < chunks.size {
+        let item: Option<T> = wait!(chunks.stream.next());
+        match item {
+            Some(item) => chunks.chunk.push(item),
+            None => chunks.is_ended = true
+        };
+    }
+
+    if chunks.chunk.is_empty() {
+        return None;
+    }
+    let size = chunks.size;
+    return Some(mem::replace(&mut chunks.chunk, Vec::with_capacity(size)));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{coro, test_local, wait};
+    use crate::coroutine::CoroutineImpl;
+    use crate::sleep::sleep;
+    use crate::stream::CoStream;
+    use std::time::Duration;
+
+    /// Returns the numbers from 0 to `end` with a sleep before each of them.
+    pub(crate) struct Counter {
+        current: usize,
+        end: usize
+    }
+
+    impl Counter {
+        pub(crate) fn new(end: usize) -> Self {
+            Self { current: 0, end }
+        }
+    }
+
+    impl CoStream<usize> for Counter {
+        fn next(&mut self, res: *mut Option<usize>) -> CoroutineImpl {
+            next_number(self, res)
+        }
+    }
+
+    #[coro(crate="crate")]
+    fn next_number(counter: *mut Counter) -> Option<usize> {
+        let counter = unsafe { &mut *counter };
+        yield sleep(Duration::from_millis(1));
+        if counter.current == counter.end {
+            return None;
+        }
+        counter.current += 1;
+        return Some(counter.current - 1);
+    }
+
+    #[test_local(crate="crate")]
+    fn test_adapters() {
+        let mut strings = Counter::new(10).map(|n| n.to_string()).take(3);
+        let mut got = Vec::new();
+        loop {
+            let item: Option<String> = wait!(strings.next());
+            match item {
+                Some(item) => got.push(item),
+                None => break
+            }
+        }
+        assert_eq!(got, vec!["0", "1", "2"]);
+        assert_eq!(strings.remaining(), 0);
+
+        let mut chunks = Counter::new(5).buffer_chunks(2);
+        let mut got = Vec::new();
+        loop {
+            let chunk: Option<Vec<usize>> = wait!(chunks.next());
+            match chunk {
+                Some(chunk) => got.push(chunk),
+                None => break
+            }
+        }
+        assert_eq!(got, vec![vec![0, 1], vec![2, 3], vec![4]]);
+    }
+}
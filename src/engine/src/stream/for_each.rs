@@ -0,0 +1,73 @@
+//! This module contains [`for_each_concurrent`], the implementation of [`CoStream::for_each_concurrent`].
+use crate::{coro, wait};
+use crate::coroutine::{CoroutineImpl, JoinHandle};
+use crate::stream::CoStream;
+use crate::sync::{Semaphore, SemaphorePermit};
+use std::ops::CoroutineState;
+
+#[coro(crate="crate")]
+pub(crate) fn for_each_concurrent<T: 'static, S: CoStream<T> + 'static, F: FnMut(T, *mut ()) -> CoroutineImpl + 'static>(mut stream: S, limit: usize, mut handler: F) {
+    let semaphore = Semaphore::new(limit);
+    let mut children: Vec<JoinHandle<()>> = Vec::new();
+    loop {
+        // The next item is taken only when it can be handled, so the stream is not drained faster, than items are handled.
+        let permit: SemaphorePermit = yield semaphore.acquire();
+        let item: Option<T> = wait!(stream.next());
+        let item = match item {
+            Some(item) => item,
+            None => break
+        };
+
+        children.retain(|child| !child.is_finished());
+        children.push(JoinHandle::spawn(|res| with_permit(handler(item, res), permit)));
+    }
+
+    for mut child in children {
+        let _: () = yield child.wait();
+    }
+}
+
+/// Runs the child and releases the permit after it.
+fn with_permit(mut child: CoroutineImpl, permit: SemaphorePermit) -> CoroutineImpl {
+    Box::pin(#[coroutine] static move || {
+        while let CoroutineState::Yielded(status) = child.as_mut().resume(()) {
+            yield status;
+        }
+        drop(permit);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{coro, test_local, wait};
+    use crate::local::Local;
+    use crate::sleep::sleep;
+    use crate::stream::adapters::tests::Counter;
+    use crate::stream::CoStream;
+
+    #[test_local(crate="crate")]
+    fn test_for_each_concurrent() {
+        #[coro(crate="crate")]
+        fn handle(n: usize, running: Local<usize>, max_running: Local<usize>, handled: Local<Vec<usize>>) {
+            *running.get_mut() += 1;
+            *max_running.get_mut() = (*max_running.get()).max(*running.get());
+            yield sleep(Duration::from_millis(10));
+            handled.get_mut().push(n);
+            *running.get_mut() -= 1;
+        }
+
+        let running = Local::new(0);
+        let max_running = Local::new(0);
+        let handled = Local::new(Vec::new());
+        let (running_, max_running_, handled_) = (running.clone(), max_running.clone(), handled.clone());
+        let numbers = Counter::new(20);
+        wait!(numbers.for_each_concurrent(4, move |n, res| handle(n, running_.clone(), max_running_.clone(), handled_.clone(), res)));
+
+        assert_eq!(*running.get(), 0);
+        assert_eq!(*max_running.get(), 4);
+        let mut handled = handled.get().clone();
+        handled.sort();
+        assert_eq!(handled, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,118 @@
+//! This module contains [`CoStream`], an iteration model for coroutines, and its adapters.
+//!
+//! [`CoStream`] is implemented for [`TcpListener`](crate::net::TcpListener) (accepted connections),
+//! [`ReadDir`](crate::fs::ReadDir) (entries of a directory), [`Ticker`](crate::sleep::Ticker) (periodic ticks)
+//! and [`Receiver`](crate::sync::Receiver) (values of a channel).
+use crate::coroutine::CoroutineImpl;
+
+pub mod adapters;
+mod for_each;
+
+pub use adapters::{BufferChunks, Map, Take};
+
+/// A source of items, that are produced asynchronously, one by one. It is like [`Iterator`], but for coroutines.
+///
+/// [`CoStream::next`] is a coroutine, so use it with [`wait!`](crate::wait).
+/// It returns [`None`], when the stream is over. Streams, that never end (like accepted connections), never return [`None`].
+///
+/// Streams are combined with adapters: [`CoStream::map`], [`CoStream::take`] and [`CoStream::buffer_chunks`].
+/// Items are handled concurrently with a limit by [`CoStream::for_each_concurrent`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io::Error;
+/// use std::net::SocketAddr;
+/// use engine::{coro, spawn_local, wait};
+/// use engine::net::{TcpListener, TcpStream};
+/// use engine::stream::CoStream;
+///
+/// #[coro]
+/// fn serve(addr: SocketAddr) {
+///     let listener: TcpListener = yield TcpListener::new(addr);
+///     let mut connections = listener.take(100);
+///     loop {
+///         let stream: Option<Result<TcpStream, Error>> = wait!(connections.next());
+///         match stream {
+///             Some(Ok(stream)) => { spawn_local!(handle(stream)); }
+///             Some(Err(err)) => println!("accept failed, reason: {}", err),
+///             None => break
+///         }
+///     }
+/// }
+/// ```
+pub trait CoStream<T> {
+    /// Returns the next item, or [`None`], when the stream is over.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait).
+    fn next(&mut self, res: *mut Option<T>) -> CoroutineImpl;
+
+    /// Returns the stream, that applies `f` to every item.
+    fn map<U, F: FnMut(T) -> U>(self, f: F) -> Map<Self, T, F>
+    where
+        Self: Sized
+    {
+        Map::new(self, f)
+    }
+
+    /// Returns the stream, that ends after `n` items. The inner stream is not polled after that.
+    fn take(self, n: usize) -> Take<Self>
+    where
+        Self: Sized
+    {
+        Take::new(self, n)
+    }
+
+    /// Returns the stream, that groups items into vectors of `size` items. The last vector can be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    fn buffer_chunks(self, size: usize) -> BufferChunks<Self, T>
+    where
+        Self: Sized
+    {
+        BufferChunks::new(self, size)
+    }
+
+    /// Runs `handler` for every item in its own coroutine, but at most `limit` of them at once.
+    /// It is a coroutine, so use it with [`wait!`](crate::wait). It ends, when the stream ends and all handlers finish.
+    ///
+    /// The next item is taken only when a handler finishes, so the stream applies backpressure
+    /// (for example, connections wait in the backlog of the listener) instead of spawning coroutines without a limit.
+    ///
+    /// `handler` is usually a function with [`#[coro]`](crate::coro), that takes the item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use std::io::Error;
+    /// use std::net::SocketAddr;
+    /// use engine::{coro, wait};
+    /// use engine::net::{TcpListener, TcpStream};
+    /// use engine::stream::CoStream;
+    ///
+    /// #[coro]
+    /// fn handle_client(stream: Result<TcpStream, Error>) {
+    ///     // ...
+    /// }
+    ///
+    /// #[coro]
+    /// fn serve(addr: SocketAddr) {
+    ///     let listener: TcpListener = yield TcpListener::new(addr);
+    ///     wait!(listener.for_each_concurrent(10_000, handle_client));
+    /// }
+    /// ```
+    fn for_each_concurrent<F>(self, limit: usize, handler: F, res: *mut ()) -> CoroutineImpl
+    where
+        Self: Sized + 'static,
+        T: 'static,
+        F: FnMut(T, *mut ()) -> CoroutineImpl + 'static
+    {
+        assert!(limit > 0, "the limit of concurrent handlers must be greater than 0");
+        for_each::for_each_concurrent(self, limit, handler, res)
+    }
+}
@@ -0,0 +1,454 @@
+//! This module contains [`channel`] and [`bounded_channel`], the queues of values between the coroutines of one worker.
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use crate::coro;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::local_scheduler;
+use crate::stream::CoStream;
+
+/// The error of [`Sender::send`]. The [`Receiver`] is dropped, so the value is returned back.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// The error of [`Sender::try_send`]. The value is returned back.
+#[derive(PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The bounded channel is full.
+    Full(T),
+    /// The [`Receiver`] is dropped.
+    Closed(T)
+}
+
+impl<T> Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::Closed(_) => f.write_str("Closed(..)")
+        }
+    }
+}
+
+impl<T> Display for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("sending on a full channel"),
+            TrySendError::Closed(_) => f.write_str("sending on a closed channel")
+        }
+    }
+}
+
+impl<T> std::error::Error for TrySendError<T> {}
+
+/// The error of [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The channel has no values now, but senders can send more.
+    Empty,
+    /// The channel has no values, and all [`Sender`]s are dropped.
+    Closed
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Closed => f.write_str("receiving on a closed channel")
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// A coroutine, that waits for the room in the bounded channel, with the value to send.
+struct SendWaiter<T> {
+    /// The boxed place of the waiting coroutine. It is filled by the scheduler, when the coroutine yields.
+    coroutine: Box<Option<CoroutineImpl>>,
+    value: T,
+    res: *mut Result<(), SendError<T>>
+}
+
+/// The receiver, that waits for a value.
+struct RecvWaiter<T> {
+    /// The boxed place of the waiting coroutine. It is filled by the scheduler, when the coroutine yields.
+    coroutine: Box<Option<CoroutineImpl>>,
+    res: *mut Option<T>
+}
+
+struct ChannelState<T> {
+    values: VecDeque<T>,
+    /// The maximum number of values in the channel. `None` means unbounded.
+    capacity: Option<usize>,
+    /// Senders, that wait for the room, in the order of sending.
+    send_waiters: VecDeque<SendWaiter<T>>,
+    /// The receiver, if it waits. If it waits, the channel is empty.
+    recv_waiter: Option<RecvWaiter<T>>,
+    /// The number of alive [`Sender`]s.
+    senders: usize,
+    is_receiver_dropped: bool
+}
+
+impl<T> ChannelState<T> {
+    #[inline(always)]
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|capacity| self.values.len() >= capacity)
+    }
+
+    /// Moves the value of the first waiting sender to the channel and wakes the sender up.
+    fn admit_sender(&mut self) {
+        if self.is_full() {
+            return;
+        }
+        if let Some(mut waiter) = self.send_waiters.pop_front() {
+            unsafe { waiter.res.write(Ok(())) };
+            self.values.push_back(waiter.value);
+            let coroutine = waiter.coroutine.take().expect("[BUG] the sender of the channel is not parked. Please report this issue.");
+            local_scheduler().sched(coroutine);
+        }
+    }
+}
+
+/// Creates an unbounded channel for the coroutines of one worker. Sending never waits.
+///
+/// The [`Receiver`] is woken up, when a value is sent, instead of polling a shared state.
+/// Read [`bounded_channel`] for a channel with backpressure.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::{coro, spawn_local};
+/// use engine::sync::{channel, Receiver, SendError, Sender};
+///
+/// #[coro]
+/// fn produce(sender: Sender<u32>) {
+///     for i in 0..10 {
+///         let res: Result<(), SendError<u32>> = yield sender.send(i);
+///         if res.is_err() {
+///             return;
+///         }
+///     }
+/// }
+///
+/// #[coro]
+/// fn consume() {
+///     let (sender, receiver) = channel();
+///     spawn_local!(produce(sender));
+///     loop {
+///         let value: Option<u32> = yield receiver.recv();
+///         match value {
+///             Some(value) => println!("{}", value),
+///             None => break // All senders are dropped.
+///         }
+///     }
+/// }
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    new_channel(None)
+}
+
+/// Creates a channel for the coroutines of one worker, that holds at most `capacity` values.
+/// When it is full, [`Sender::send`] waits, until the [`Receiver`] takes a value, so fast senders are slowed down.
+///
+/// # Panics
+///
+/// Panics, if `capacity` is zero.
+pub fn bounded_channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "the capacity of the channel must be greater than zero");
+    new_channel(Some(capacity))
+}
+
+fn new_channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let state = Rc::new(UnsafeCell::new(ChannelState {
+        values: VecDeque::new(),
+        capacity,
+        send_waiters: VecDeque::new(),
+        recv_waiter: None,
+        senders: 1,
+        is_receiver_dropped: false
+    }));
+
+    (Sender { state: state.clone() }, Receiver { state })
+}
+
+/// The sending half of a [`channel`]. Clones send to the same [`Receiver`].
+///
+/// When all senders are dropped, the receiver gets the rest of the values and then `None`.
+pub struct Sender<T> {
+    state: Rc<UnsafeCell<ChannelState<T>>>
+}
+
+impl<T> Sender<T> {
+    /// Sends the value without waiting. Returns it back, if the channel is full or the [`Receiver`] is dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let state = unsafe { &mut *self.state.get() };
+        if state.is_receiver_dropped {
+            return Err(TrySendError::Closed(value));
+        }
+
+        if let Some(mut waiter) = state.recv_waiter.take() {
+            unsafe { waiter.res.write(Some(value)) };
+            let coroutine = waiter.coroutine.take().expect("[BUG] the receiver of the channel is not parked. Please report this issue.");
+            local_scheduler().sched(coroutine);
+            return Ok(());
+        }
+
+        // Waiting senders go first, so the order of sending is kept.
+        if state.is_full() || !state.send_waiters.is_empty() {
+            return Err(TrySendError::Full(value));
+        }
+        state.values.push_back(value);
+        Ok(())
+    }
+
+    /// Sends the value. If the bounded channel is full, the coroutine waits for the room. Use it with `yield`.
+    ///
+    /// Writes the value back in the [`SendError`], if the [`Receiver`] is dropped.
+    pub fn send(&self, value: T, res: *mut Result<(), SendError<T>>) -> YieldStatus {
+        let value = match self.try_send(value) {
+            Ok(()) => return YieldStatus::ready(res, Ok(())),
+            Err(TrySendError::Closed(value)) => return YieldStatus::ready(res, Err(SendError(value))),
+            Err(TrySendError::Full(value)) => value
+        };
+
+        let state = unsafe { &mut *self.state.get() };
+        let mut waiter = SendWaiter { coroutine: Box::new(None), value, res };
+        let place: *mut Option<CoroutineImpl> = &mut *waiter.coroutine;
+        state.send_waiters.push_back(waiter);
+        YieldStatus::join(place)
+    }
+
+    /// Returns true, if the [`Receiver`] is dropped, so nothing can be sent.
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        unsafe { (*self.state.get()).is_receiver_dropped }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        unsafe { (*self.state.get()).senders += 1 };
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let state = unsafe { &mut *self.state.get() };
+        state.senders -= 1;
+        if state.senders > 0 {
+            return;
+        }
+
+        if let Some(mut waiter) = state.recv_waiter.take() {
+            unsafe { waiter.res.write(None) };
+            let coroutine = waiter.coroutine.take().expect("[BUG] the receiver of the channel is not parked. Please report this issue.");
+            local_scheduler().sched(coroutine);
+        }
+    }
+}
+
+/// The receiving half of a [`channel`]. There is only one receiver, so it is not cloned.
+///
+/// When it is dropped, the values in the channel are dropped, and waiting senders get their values back.
+///
+/// It is a [`CoStream`] of the values, that ends, when the channel is empty, and all [`Sender`]s are dropped.
+pub struct Receiver<T> {
+    state: Rc<UnsafeCell<ChannelState<T>>>
+}
+
+impl<T> Receiver<T> {
+    /// Returns the next value without waiting.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = unsafe { &mut *self.state.get() };
+        match state.values.pop_front() {
+            Some(value) => {
+                state.admit_sender();
+                Ok(value)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty)
+        }
+    }
+
+    /// Returns the next value. If the channel is empty, the coroutine waits for it. Use it with `yield`.
+    ///
+    /// Writes `None`, if the channel is empty, and all [`Sender`]s are dropped.
+    pub fn recv(&self, res: *mut Option<T>) -> YieldStatus {
+        match self.try_recv() {
+            Ok(value) => return YieldStatus::ready(res, Some(value)),
+            Err(TryRecvError::Closed) => return YieldStatus::ready(res, None),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let state = unsafe { &mut *self.state.get() };
+        let mut waiter = RecvWaiter { coroutine: Box::new(None), res };
+        let place: *mut Option<CoroutineImpl> = &mut *waiter.coroutine;
+        state.recv_waiter = Some(waiter);
+        YieldStatus::join(place)
+    }
+
+    /// Returns the number of values in the channel.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        unsafe { (*self.state.get()).values.len() }
+    }
+
+    /// Returns true, if the channel has no values.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the bounded channel, or `None`, if the channel is unbounded.
+    #[inline(always)]
+    pub fn capacity(&self) -> Option<usize> {
+        unsafe { (*self.state.get()).capacity }
+    }
+}
+
+impl<T: 'static> CoStream<T> for Receiver<T> {
+    fn next(&mut self, res: *mut Option<T>) -> CoroutineImpl {
+        next_value(self, res)
+    }
+}
+
+#[coro(crate="crate")]
+fn next_value<T: 'static>(receiver: *const Receiver<T>) -> Option<T> {
+    let receiver = unsafe { &*receiver };
+    let value: Option<T> = yield receiver.recv();
+    return value;
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let state = unsafe { &mut *self.state.get() };
+        state.is_receiver_dropped = true;
+        state.values.clear();
+        while let Some(mut waiter) = state.send_waiters.pop_front() {
+            unsafe { waiter.res.write(Err(SendError(waiter.value))) };
+            let coroutine = waiter.coroutine.take().expect("[BUG] the sender of the channel is not parked. Please report this issue.");
+            local_scheduler().sched(coroutine);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{coro, test_local, wait};
+    use crate::coroutine::{yield_now, JoinHandle};
+    use crate::stream::CoStream;
+    use crate::sync::{bounded_channel, channel, SendError, Sender, TryRecvError, TrySendError};
+
+    #[coro(crate="crate")]
+    fn produce(sender: Sender<u32>, from: u32, to: u32) -> Result<(), SendError<u32>> {
+        for i in from..to {
+            let res: Result<(), SendError<u32>> = yield sender.send(i);
+            if res.is_err() {
+                return res;
+            }
+        }
+        Ok(())
+    }
+
+    #[test_local(crate="crate")]
+    fn test_channel() {
+        let (sender, receiver) = channel();
+        let producers = [
+            JoinHandle::spawn({ let sender = sender.clone(); move |res| produce(sender, 0, 100, res) }),
+            JoinHandle::spawn({ let sender = sender.clone(); move |res| produce(sender, 100, 200, res) })
+        ];
+        drop(sender);
+
+        let mut received = Vec::new();
+        loop {
+            let value: Option<u32> = yield receiver.recv();
+            match value {
+                Some(value) => received.push(value),
+                None => break
+            }
+        }
+        for mut producer in producers {
+            let res: Result<(), SendError<u32>> = yield producer.wait();
+            res.unwrap();
+        }
+
+        assert_eq!(received.len(), 200);
+        // Values of one sender keep their order.
+        assert!(received.iter().filter(|&&value| value < 100).is_sorted());
+        assert!(received.iter().filter(|&&value| value >= 100).is_sorted());
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_bounded_channel() {
+        let (sender, receiver) = bounded_channel(2);
+        let mut producer = JoinHandle::spawn({ let sender = sender.clone(); move |res| produce(sender, 0, 10, res) });
+
+        // Every send yields, so the producer is given a few turns to fill the channel.
+        for _ in 0..5 {
+            yield yield_now();
+        }
+        // The producer waits for the room.
+        assert_eq!(receiver.len(), 2);
+        assert!(!producer.is_finished());
+
+        let mut received = Vec::new();
+        while received.len() < 10 {
+            let value: Option<u32> = yield receiver.recv();
+            received.push(value.unwrap());
+            assert!(receiver.len() <= 2);
+        }
+        let res: Result<(), SendError<u32>> = yield producer.wait();
+        res.unwrap();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+
+        assert_eq!(sender.try_send(1), Ok(()));
+        assert_eq!(sender.try_send(2), Ok(()));
+        assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+
+        // The waiting sender gets its value back, when the receiver is dropped.
+        let mut blocked = JoinHandle::spawn({ let sender = sender.clone(); move |res| produce(sender, 4, 5, res) });
+        yield yield_now();
+        assert!(!blocked.is_finished());
+        drop(receiver);
+        let res: Result<(), SendError<u32>> = yield blocked.wait();
+        assert_eq!(res, Err(SendError(4)));
+        assert!(sender.is_closed());
+        assert_eq!(sender.try_send(5), Err(TrySendError::Closed(5)));
+    }
+
+    #[test_local(crate="crate")]
+    fn test_receiver_stream() {
+        let (sender, receiver) = bounded_channel(4);
+        let mut producer = JoinHandle::spawn(move |res| produce(sender, 0, 10, res));
+
+        // The stream ends, when the producer finishes and drops the sender.
+        let mut chunks = receiver.map(|value| value * 2).buffer_chunks(3);
+        let mut received = Vec::new();
+        loop {
+            let chunk: Option<Vec<u32>> = wait!(chunks.next());
+            match chunk {
+                Some(chunk) => received.push(chunk),
+                None => break
+            }
+        }
+        assert_eq!(received, vec![vec![0, 2, 4], vec![6, 8, 10], vec![12, 14, 16], vec![18]]);
+        let res: Result<(), SendError<u32>> = yield producer.wait();
+        res.unwrap();
+    }
+}
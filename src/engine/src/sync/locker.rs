@@ -3,7 +3,7 @@ use std::ops::{Deref, DerefMut};
 use crate::coroutine::YieldStatus;
 use crate::sync::spin::spin;
 
-const TRIES: usize = 10;
+pub(crate) const TRIES: usize = 10;
 
 pub trait Locker<'a, T: ?Sized>: Sync {
     const YIELD: YieldStatus;
@@ -1,6 +1,10 @@
+pub mod channel;
 pub mod locker;
 pub mod mutex;
+pub mod semaphore;
 mod spin;
 
+pub use channel::{bounded_channel, channel, Receiver, SendError, Sender, TryRecvError, TrySendError};
 pub use locker::*;
-pub use mutex::Mutex;
\ No newline at end of file
+pub use mutex::Mutex;
+pub use semaphore::{Semaphore, SemaphorePermit};
@@ -1,22 +1,70 @@
 use std::cell::UnsafeCell;
+use std::intrinsics::{likely, unlikely};
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 use crossbeam::utils::{CachePadded};
 use crate::coroutine::YieldStatus;
+use crate::local::get_worker_id;
+use crate::scheduler::{local_scheduler, Priority};
 use crate::sync::{Locker};
+use crate::sync::locker::TRIES;
+use crate::sync::spin::spin;
 use std::fmt;
 use std::fmt::{Debug, Display};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
+/// A mutual exclusion primitive, that can be shared between workers.
+///
+/// A contended [`lock`](Locker::lock) returns [`YieldStatus::Yield`], and the coroutine tries again after yielding.
+///
+/// # Priority inheritance
+///
+/// A coroutine, that fails to lock the mutex, registers the holder in the inheritance table, and the holder inherits
+/// the highest of the [`priorities`](Priority) of the waiters until it unlocks the mutex. So a [`Priority::Low`] holder
+/// doesn't block a [`Priority::High`] waiter, while [`Priority::Normal`] coroutines are ready. A queued holder is moved
+/// to the queue of the inherited priority at once, if it runs on the worker of the waiter, otherwise it gets the priority,
+/// when it is queued next time.
+///
+/// An uncontended lock is one compare-and-swap, and the table is touched only after a waiter appears.
 pub struct Mutex<'a, T> {
+    /// [`UNLOCKED`] or the address of the holder (or [`UNKNOWN_HOLDER`]) with the [`CONTENDED`] bit.
     state: CachePadded<AtomicUsize>,
     value: UnsafeCell<T>,
     phantom: PhantomData<&'a T>
 }
 
 const UNLOCKED: usize = 0;
-const LOCKED: usize = 1;
+/// It is set by a waiter, that registered the holder in the [`INHERITANCE`].
+const CONTENDED: usize = 1;
+/// The holder is not a coroutine of a worker, so it doesn't inherit priorities.
+const UNKNOWN_HOLDER: usize = 2;
+
+/// A holder, that inherits the `priority` of the waiters of the mutex at the address `mutex`.
+struct Inheritance {
+    holder: usize,
+    mutex: usize,
+    priority: Priority
+}
+
+/// The holders of contended mutexes of all workers. Read [`inherited_priority`].
+static INHERITANCE: std::sync::Mutex<Vec<Inheritance>> = std::sync::Mutex::new(Vec::new());
+/// The length of the [`INHERITANCE`], so the schedulers don't lock it, while no mutex is contended.
+static INHERITING: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the `priority` or the priority, that the coroutine with the `address` inherits from the waiters
+/// of the mutexes, that it holds, if it is higher.
+#[inline(always)]
+pub(crate) fn inherited_priority(address: usize, priority: Priority) -> Priority {
+    if likely(INHERITING.load(Relaxed) == 0) {
+        return priority;
+    }
+
+    INHERITANCE.lock().unwrap().iter()
+        .filter(|inheritance| inheritance.holder == address)
+        .map(|inheritance| inheritance.priority)
+        .fold(priority, Priority::max)
+}
 
 impl<'a, T> Mutex<'a, T> {
     pub const fn new(value: T) -> Self {
@@ -26,6 +74,65 @@ impl<'a, T> Mutex<'a, T> {
             phantom: PhantomData
         }
     }
+
+    /// Returns the state of the mutex, that is locked by the running coroutine.
+    #[inline(always)]
+    fn holder() -> usize {
+        if get_worker_id() == 0 {
+            return UNKNOWN_HOLDER;
+        }
+
+        let address = local_scheduler().running();
+        debug_assert_eq!(address & CONTENDED, 0);
+        if address == UNLOCKED {
+            UNKNOWN_HOLDER
+        } else {
+            address
+        }
+    }
+
+    /// Marks the mutex as contended and makes the holder inherit the priority of the running coroutine.
+    fn register_waiter(&self) {
+        if get_worker_id() == 0 {
+            return;
+        }
+
+        // The holder removes its inheritance under the lock, so it can't be removed before it is added.
+        let mut inheritance = INHERITANCE.lock().unwrap();
+        let state = self.state.load(Relaxed);
+        let holder = state & !CONTENDED;
+        if holder == UNLOCKED || holder == UNKNOWN_HOLDER {
+            return;
+        }
+        if state & CONTENDED == 0 && self.state.compare_exchange(state, state | CONTENDED, Relaxed, Relaxed).is_err() {
+            // The mutex is unlocked or relocked, so the coroutine tries to lock it again.
+            return;
+        }
+
+        let scheduler = local_scheduler();
+        let mutex = self as *const Self as usize;
+        let mut priority = scheduler.current_priority();
+        match inheritance.iter_mut().find(|inheritance| inheritance.holder == holder && inheritance.mutex == mutex) {
+            Some(inheritance) => {
+                inheritance.priority = inheritance.priority.max(priority);
+                priority = inheritance.priority;
+            }
+            None => {
+                inheritance.push(Inheritance { holder, mutex, priority });
+                INHERITING.store(inheritance.len(), Relaxed);
+            }
+        }
+        scheduler.promote(holder, priority);
+    }
+
+    /// Removes the inheritance of the `holder`, that has unlocked the contended mutex.
+    #[cold]
+    fn unregister_holder(&self, holder: usize) {
+        let mut inheritance = INHERITANCE.lock().unwrap();
+        let mutex = self as *const Self as usize;
+        inheritance.retain(|inheritance| inheritance.holder != holder || inheritance.mutex != mutex);
+        INHERITING.store(inheritance.len(), Relaxed);
+    }
 }
 
 impl<'a, T> Locker<'a, T> for Mutex<'a, T> {
@@ -39,16 +146,38 @@ impl<'a, T> Locker<'a, T> for Mutex<'a, T> {
 
     #[inline(always)]
     unsafe fn try_unsafe_lock(&self) -> Result<(), ()> {
-        if self.state.compare_exchange_weak(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok() {
+        if self.state.compare_exchange_weak(UNLOCKED, Self::holder(), Acquire, Relaxed).is_ok() {
             Ok(())
         } else {
             Err(())
         }
     }
 
+    unsafe fn unsafe_lock(&self) -> Result<(), YieldStatus> {
+        unsafe {
+            let mut step = 0;
+            loop {
+                if self.try_unsafe_lock().is_ok() {
+                    return Ok(());
+                }
+
+                spin(step);
+                if unlikely(step == TRIES) {
+                    self.register_waiter();
+                    return Err(Self::YIELD);
+                } else {
+                    step += 1;
+                }
+            }
+        }
+    }
+
     #[inline(always)]
     unsafe fn unlock(&self) {
-        self.state.store(UNLOCKED, Release);
+        let state = self.state.swap(UNLOCKED, Release);
+        if unlikely(state & CONTENDED != 0) {
+            self.unregister_holder(state & !CONTENDED);
+        }
     }
 
     #[inline(always)]
@@ -112,4 +241,65 @@ impl<T: Display> Display for MutexGuard<'_, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         (**self).fmt(f)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::coroutine::yield_now;
+    use crate::local::Local;
+    use crate::scheduler::{local_scheduler, Priority};
+    use crate::sleep::sleep;
+    use crate::sync::{Locker, Mutex};
+
+    #[coro(crate="crate")]
+    fn hold(mutex: &'static Mutex<'static, ()>, log: Local<Vec<&'static str>>) {
+        let guard = mutex.try_lock().unwrap();
+        log.get_mut().push("locked");
+        for _ in 0..3 {
+            yield yield_now();
+        }
+        log.get_mut().push("unlocked");
+        drop(guard);
+    }
+
+    #[coro(crate="crate")]
+    fn wait_for_lock(mutex: &'static Mutex<'static, ()>, log: Local<Vec<&'static str>>) {
+        // A contended lock returns `YieldStatus::Yield`.
+        while mutex.lock().is_err() {
+            yield yield_now();
+        }
+        log.get_mut().push("high");
+    }
+
+    #[coro(crate="crate")]
+    fn spin_normal(log: Local<Vec<&'static str>>) {
+        for _ in 0..10 {
+            log.get_mut().push("normal");
+            yield yield_now();
+        }
+    }
+
+    #[test_local(crate="crate")]
+    fn test_priority_inheritance() {
+        let mutex: &'static Mutex<'static, ()> = Box::leak(Box::new(Mutex::new(())));
+        let log = Local::new(Vec::new());
+        local_scheduler().spawn_with_priority(hold(mutex, log.clone(), null_mut()), Priority::Low);
+        while log.get().is_empty() {
+            yield yield_now();
+        }
+
+        // The low priority holder is queued with the lock. The high priority waiter raises it above the normal coroutines.
+        local_scheduler().spawn_with_priority(wait_for_lock(mutex, log.clone(), null_mut()), Priority::High);
+        for _ in 0..2 {
+            local_scheduler().spawn(spin_normal(log.clone(), null_mut()));
+        }
+        yield sleep(Duration::from_millis(10));
+
+        assert_eq!(log.get()[..3], ["locked", "unlocked", "high"]);
+        assert_eq!(log.get().len(), 23);
+        assert!(mutex.try_lock().is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+//! This module contains [`Semaphore`] and [`SemaphorePermit`].
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use crate::coroutine::{CoroutineImpl, YieldStatus};
+use crate::local_scheduler;
+
+/// A coroutine, that waits for a permit, and where the permit is written for it.
+struct Waiter {
+    /// The boxed place of the waiting coroutine. It is filled by the scheduler, when the coroutine yields.
+    coroutine: Box<Option<CoroutineImpl>>,
+    permit: *mut SemaphorePermit
+}
+
+struct SemaphoreState {
+    permits: usize,
+    waiters: VecDeque<Waiter>
+}
+
+/// A semaphore for the coroutines of one worker. It limits how many coroutines do something at once.
+///
+/// Unlike [`Mutex`](crate::sync::Mutex), the waiting coroutines don't spin or yield in a loop.
+/// They are parked in the FIFO queue and woken up, when a permit is released.
+///
+/// Clones share the permits.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::{coro, spawn_local};
+/// use engine::sync::{Semaphore, SemaphorePermit};
+///
+/// #[coro]
+/// fn query_db(limit: Semaphore) {
+///     let _permit: SemaphorePermit = yield limit.acquire();
+///     // At most 10 coroutines query the database at once.
+/// }
+///
+/// #[coro]
+/// fn run_queries() {
+///     let limit = Semaphore::new(10);
+///     for _ in 0..100 {
+///         spawn_local!(query_db(limit.clone()));
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Semaphore {
+    state: Rc<UnsafeCell<SemaphoreState>>
+}
+
+impl Semaphore {
+    /// Creates a new [`Semaphore`] with the number of permits.
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: Rc::new(UnsafeCell::new(SemaphoreState { permits, waiters: VecDeque::new() }))
+        }
+    }
+
+    /// Returns the number of free permits.
+    #[inline(always)]
+    pub fn available_permits(&self) -> usize {
+        unsafe { (*self.state.get()).permits }
+    }
+
+    /// Returns the permit, if there is a free one.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit> {
+        let state = unsafe { &mut *self.state.get() };
+        if state.permits == 0 {
+            return None;
+        }
+        state.permits -= 1;
+        Some(SemaphorePermit { semaphore: self.clone() })
+    }
+
+    /// Returns the permit. If there are no free permits, the coroutine waits in the queue. Use it with `yield`.
+    pub fn acquire(&self, res: *mut SemaphorePermit) -> YieldStatus {
+        if let Some(permit) = self.try_acquire() {
+            return YieldStatus::ready(res, permit);
+        }
+
+        let state = unsafe { &mut *self.state.get() };
+        let mut waiter = Waiter { coroutine: Box::new(None), permit: res };
+        let place: *mut Option<CoroutineImpl> = &mut *waiter.coroutine;
+        state.waiters.push_back(waiter);
+        YieldStatus::join(place)
+    }
+
+    /// Passes the permit to the first waiting coroutine or returns it to the free ones.
+    fn release(&self) {
+        let state = unsafe { &mut *self.state.get() };
+        match state.waiters.pop_front() {
+            Some(mut waiter) => {
+                unsafe { waiter.permit.write(SemaphorePermit { semaphore: self.clone() }) };
+                let coroutine = waiter.coroutine.take().expect("[BUG] the waiter of the semaphore is not parked. Please report this issue.");
+                local_scheduler().sched(coroutine);
+            }
+            None => state.permits += 1
+        }
+    }
+}
+
+/// The permit of a [`Semaphore`]. It is released, when it is dropped.
+pub struct SemaphorePermit {
+    semaphore: Semaphore
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{coro, test_local};
+    use crate::local::Local;
+    use crate::sleep::sleep;
+    use crate::sync::{Semaphore, SemaphorePermit};
+    use crate::coroutine::JoinHandle;
+
+    #[coro(crate="crate")]
+    fn hold(semaphore: Semaphore, running: Local<usize>, max_running: Local<usize>) {
+        let _permit: SemaphorePermit = yield semaphore.acquire();
+        *running.get_mut() += 1;
+        *max_running.get_mut() = (*max_running.get()).max(*running.get());
+        yield sleep(Duration::from_millis(2));
+        *running.get_mut() -= 1;
+    }
+
+    #[test_local(crate="crate")]
+    fn test_semaphore() {
+        let semaphore = Semaphore::new(3);
+        let running = Local::new(0);
+        let max_running = Local::new(0);
+        let mut handles = Vec::new();
+        for _ in 0..10 {
+            let (semaphore, running, max_running) = (semaphore.clone(), running.clone(), max_running.clone());
+            handles.push(JoinHandle::spawn(move |res| hold(semaphore, running, max_running, res)));
+        }
+        for mut handle in handles {
+            let _: () = yield handle.wait();
+        }
+
+        assert_eq!(*max_running.get(), 3);
+        assert_eq!(semaphore.available_permits(), 3);
+        let permit = semaphore.try_acquire();
+        assert!(permit.is_some());
+        assert_eq!(semaphore.available_permits(), 2);
+        drop(permit);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+}
@@ -0,0 +1,123 @@
+//! This module contains [`cpu_quota`], that reads the CPU quota of the cgroup of the process.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The mount point of cgroups.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Returns the CPU quota of the cgroup of the process in CPUs (for example, `1.5` for `cpu.max = 150000 100000`),
+/// or `None`, if the quota is not set or cgroups are not available.
+///
+/// Both cgroup v2 (`cpu.max`) and cgroup v1 (`cpu.cfs_quota_us` and `cpu.cfs_period_us`) are supported.
+/// The smallest quota of the cgroup and its ancestors is returned, because all of them limit the process.
+pub fn cpu_quota() -> Option<f64> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let root = Path::new(CGROUP_ROOT);
+    if let Some(path) = parse_cgroup_path(&cgroups, None) {
+        let quota = min_quota(root, &path, |dir| parse_cpu_max(&fs::read_to_string(dir.join("cpu.max")).ok()?));
+        if quota.is_some() {
+            return quota;
+        }
+    }
+    if let Some(path) = parse_cgroup_path(&cgroups, Some("cpu")) {
+        for mount in ["cpu", "cpu,cpuacct", "cpuacct,cpu"] {
+            let quota = min_quota(&root.join(mount), &path, |dir| {
+                let quota = fs::read_to_string(dir.join("cpu.cfs_quota_us")).ok()?;
+                let period = fs::read_to_string(dir.join("cpu.cfs_period_us")).ok()?;
+                parse_cfs_quota(&quota, &period)
+            });
+            if quota.is_some() {
+                return quota;
+            }
+        }
+    }
+    None
+}
+
+/// Returns the smallest quota of `path` under `root` and its ancestors up to `root`.
+///
+/// In a container with a cgroup namespace the path is `/`, so only the root of the mount is read.
+fn min_quota<F: Fn(&Path) -> Option<f64>>(root: &Path, path: &Path, read: F) -> Option<f64> {
+    let mut dir: PathBuf = root.join(path.strip_prefix("/").unwrap_or(path));
+    let mut min: Option<f64> = None;
+    loop {
+        if let Some(quota) = read(&dir) {
+            min = Some(min.map_or(quota, |min| min.min(quota)));
+        }
+        if dir == root || !dir.pop() || !dir.starts_with(root) {
+            return min;
+        }
+    }
+}
+
+/// Returns the path of the cgroup from the content of `/proc/self/cgroup`.
+/// `None` as the controller means the unified hierarchy of cgroup v2 (the line `0::/path`).
+fn parse_cgroup_path(cgroups: &str, controller: Option<&str>) -> Option<PathBuf> {
+    for line in cgroups.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(_id), Some(controllers), Some(path)) = (parts.next(), parts.next(), parts.next()) else { continue };
+        let is_match = match controller {
+            None => controllers.is_empty(),
+            Some(controller) => controllers.split(',').any(|name| name == controller)
+        };
+        if is_match {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Parses `cpu.max` of cgroup v2: `max 100000` or `150000 100000`.
+fn parse_cpu_max(content: &str) -> Option<f64> {
+    let mut parts = content.split_whitespace();
+    let quota = parts.next()?;
+    let period: f64 = parts.next()?.parse().ok()?;
+    if quota == "max" || period <= 0.0 {
+        return None;
+    }
+    Some(quota.parse::<f64>().ok()? / period)
+}
+
+/// Parses `cpu.cfs_quota_us` and `cpu.cfs_period_us` of cgroup v1. The quota is `-1`, if it is not set.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: i64 = period.trim().parse().ok()?;
+    if quota <= 0 || period <= 0 {
+        return None;
+    }
+    Some(quota as f64 / period as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(parse_cfs_quota("200000\n", "100000\n"), Some(2.0));
+
+        let v2 = "0::/kubepods/pod1/container\n";
+        assert_eq!(parse_cgroup_path(v2, None), Some(PathBuf::from("/kubepods/pod1/container")));
+        assert_eq!(parse_cgroup_path(v2, Some("cpu")), None);
+
+        let v1 = "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc\n";
+        assert_eq!(parse_cgroup_path(v1, Some("cpu")), Some(PathBuf::from("/docker/abc")));
+        assert_eq!(parse_cgroup_path(v1, None), None);
+    }
+
+    #[test]
+    fn test_min_quota() {
+        let root = Path::new("/sys/fs/cgroup");
+        let quota = min_quota(root, Path::new("/a/b"), |dir| match dir.to_str().unwrap() {
+            "/sys/fs/cgroup/a/b" => Some(4.0),
+            "/sys/fs/cgroup/a" => Some(2.0),
+            _ => None
+        });
+        assert_eq!(quota, Some(2.0));
+        assert_eq!(min_quota(root, Path::new("/"), |_| None), None);
+    }
+}
@@ -1,12 +1,159 @@
+use std::io::Error;
+use crate::cfg::{config_reserved_cores, config_skip_smt_siblings};
+use crate::utils::cgroup::cpu_quota;
+
 /// ID of the CPU core.
 pub type CoreId = core_affinity::CoreId;
 
-/// Returns the list of available CPU cores.
+/// Returns the list of CPU cores, that the process can use.
+///
+/// The list respects the affinity mask of the process (and so the cpuset of its cgroup),
+/// and it is truncated to [`effective_parallelism`], so [`run_on_all_cores`](crate::run::run_on_all_cores)
+/// inside a container with a CPU quota doesn't start more workers than the quota allows.
 pub fn get_core_ids() -> Option<Vec<CoreId>> {
-    core_affinity::get_core_ids()
+    let mut cores = core_affinity::get_core_ids()?;
+    truncate_to_quota(&mut cores);
+    Some(cores)
+}
+
+/// Returns the list of CPU cores, that [`run_on_all_cores`](crate::run::run_on_all_cores) starts workers on.
+///
+/// It is like [`get_core_ids`], but:
+///
+/// - with [`set_skip_smt_siblings`](crate::cfg::set_skip_smt_siblings) only the first hardware thread
+///   of every physical core is kept (the topology is read from `/sys/devices/system/cpu`);
+///
+/// - [`set_reserved_cores`](crate::cfg::set_reserved_cores) last cores are left to other threads, but at least one core is returned.
+pub fn get_worker_core_ids() -> Option<Vec<CoreId>> {
+    worker_core_ids(config_skip_smt_siblings(), config_reserved_cores())
+}
+
+/// Like [`get_worker_core_ids`], but with the provided options instead of the config.
+pub(crate) fn worker_core_ids(skip_smt: bool, reserved_cores: usize) -> Option<Vec<CoreId>> {
+    let mut cores = core_affinity::get_core_ids()?;
+    if skip_smt {
+        cores = skip_smt_siblings(cores, read_thread_siblings);
+    }
+    truncate_to_quota(&mut cores);
+    let workers = cores.len().saturating_sub(reserved_cores).max(1);
+    cores.truncate(workers);
+    Some(cores)
+}
+
+/// Truncates the list of cores to the CPU quota of the cgroup.
+fn truncate_to_quota(cores: &mut Vec<CoreId>) {
+    if let Some(quota) = cpu_quota() {
+        cores.truncate(quota_to_parallelism(quota));
+    }
+}
+
+/// Keeps a core, only if none of its siblings is kept. `siblings` returns the hardware threads of the physical core
+/// of the core (including itself). Cores with an unknown topology are kept.
+fn skip_smt_siblings<F: Fn(usize) -> Option<Vec<usize>>>(cores: Vec<CoreId>, siblings: F) -> Vec<CoreId> {
+    let mut kept: Vec<CoreId> = Vec::with_capacity(cores.len());
+    for core in cores {
+        let siblings = siblings(core.id).unwrap_or_default();
+        if !kept.iter().any(|kept| siblings.contains(&kept.id)) {
+            kept.push(core);
+        }
+    }
+    kept
+}
+
+/// Reads the hardware threads of the physical core of the core from sysfs.
+fn read_thread_siblings(core: usize) -> Option<Vec<usize>> {
+    let path = format!("/sys/devices/system/cpu/cpu{}/topology/thread_siblings_list", core);
+    parse_cpu_list(std::fs::read_to_string(path).ok()?.trim())
+}
+
+/// Parses a CPU list of the kernel, like `0-3,8,10-11`.
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.split(',') {
+        match range.split_once('-') {
+            Some((start, end)) => cpus.extend(start.parse::<usize>().ok()?..=end.parse::<usize>().ok()?),
+            None => cpus.push(range.parse().ok()?)
+        }
+    }
+    Some(cpus)
+}
+
+/// Returns how many threads the process can run in parallel: the number of cores in the affinity mask,
+/// limited by the CPU quota of the cgroup. It is at least 1. Use it for sizing pools.
+pub fn effective_parallelism() -> usize {
+    get_core_ids().map(|cores| cores.len()).unwrap_or(1).max(1)
+}
+
+/// Converts a CPU quota to the number of threads. A partial CPU counts as one more thread.
+fn quota_to_parallelism(quota: f64) -> usize {
+    (quota.ceil() as usize).max(1)
 }
 
 /// Sets the affinity of the current thread to the given CPU core.
 pub fn set_for_current(core_id: CoreId) {
     core_affinity::set_for_current(core_id);
-}
\ No newline at end of file
+}
+
+/// Sets the nice value of the current thread. Higher values mean lower priority.
+/// Lowering the nice value below the current one requires `CAP_SYS_NICE`.
+///
+/// Only Linux has the nice value of a thread, so off Linux it returns [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported).
+#[cfg(target_os = "linux")]
+pub fn set_nice_for_current(nice: i32) -> Result<(), Error> {
+    let tid = unsafe { libc::gettid() };
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sets the nice value of the current thread. Higher values mean lower priority.
+///
+/// Only Linux has the nice value of a thread, so off Linux it returns [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported).
+#[cfg(not(target_os = "linux"))]
+pub fn set_nice_for_current(_nice: i32) -> Result<(), Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "the nice value of a thread is supported only on Linux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{effective_parallelism, get_core_ids, set_nice_for_current, CoreId};
+    use crate::utils::core::{parse_cpu_list, quota_to_parallelism, skip_smt_siblings};
+
+    #[test]
+    fn test_effective_parallelism() {
+        assert_eq!(quota_to_parallelism(0.5), 1);
+        assert_eq!(quota_to_parallelism(1.5), 2);
+        assert_eq!(quota_to_parallelism(4.0), 4);
+        assert_eq!(effective_parallelism(), get_core_ids().unwrap().len());
+        assert!(effective_parallelism() >= 1);
+    }
+
+    #[test]
+    fn test_skip_smt_siblings() {
+        assert_eq!(parse_cpu_list("0-2,8,10-11"), Some(vec![0, 1, 2, 8, 10, 11]));
+        assert_eq!(parse_cpu_list("3"), Some(vec![3]));
+        assert_eq!(parse_cpu_list("a-b"), None);
+
+        // 4 physical cores with 2 threads each: cpu n and cpu n + 4 are siblings. The topology of cpu 7 is unknown.
+        let cores = (0..8).map(|id| CoreId { id }).collect::<Vec<_>>();
+        let kept = skip_smt_siblings(cores, |id| if id == 7 { None } else { Some(vec![id % 4, id % 4 + 4]) });
+        assert_eq!(kept.iter().map(|core| core.id).collect::<Vec<_>>(), vec![0, 1, 2, 3, 7]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_nice_for_current() {
+        std::thread::spawn(|| {
+            set_nice_for_current(19).unwrap();
+            let tid = unsafe { libc::gettid() };
+            assert_eq!(unsafe { libc::getpriority(libc::PRIO_PROCESS, tid as libc::id_t) }, 19);
+        }).join().unwrap();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_set_nice_for_current() {
+        assert_eq!(set_nice_for_current(19).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,119 @@
+//! This module contains [`IdGen`].
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::local::id::get_worker_id;
+
+/// The start of timestamps of ids, 2024-01-01T00:00:00Z in milliseconds since the unix epoch.
+pub const ID_EPOCH_MILLIS: u64 = 1_704_067_200_000;
+
+const WORKER_ID_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_WORKER_ID: u64 = (1 << WORKER_ID_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// A generator of unique sortable 64-bit ids (snowflake-style).
+///
+/// An id is `timestamp (41 bits) | worker id (10 bits) | sequence (12 bits)`, where the timestamp is milliseconds
+/// since [`ID_EPOCH_MILLIS`]. So ids from the same worker are increasing, and ids from all workers are sorted by time
+/// (with the millisecond precision). It is handy for request tracing across workers.
+///
+/// Create one generator per worker, because ids are unique only if worker ids are different.
+///
+/// # Sequence overflow
+///
+/// If more than 4096 ids are generated in a millisecond, the timestamp is taken from the next millisecond,
+/// so the generator never blocks the worker. The same is done, if the clock goes back.
+///
+/// # Examples
+///
+/// ```ignore
+/// use engine::utils::IdGen;
+///
+/// let mut ids = IdGen::new();
+/// let request_id = ids.next_id();
+/// println!("request {} from worker {}", request_id, IdGen::worker_id_of(request_id));
+/// ```
+pub struct IdGen {
+    worker_id: u64,
+    last_millis: u64,
+    sequence: u64
+}
+
+impl IdGen {
+    /// Creates a new [`IdGen`] for the current worker. Read [`get_worker_id`](crate::local::id::get_worker_id).
+    pub fn new() -> Self {
+        Self::with_worker_id(get_worker_id() as u64)
+    }
+
+    /// Creates a new [`IdGen`] with the given worker id. Only the lowest 10 bits of it are used.
+    pub fn with_worker_id(worker_id: u64) -> Self {
+        Self {
+            worker_id: worker_id & MAX_WORKER_ID,
+            last_millis: 0,
+            sequence: 0
+        }
+    }
+
+    /// Returns the next id.
+    pub fn next_id(&mut self) -> u64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let now = now.saturating_sub(ID_EPOCH_MILLIS);
+        if now > self.last_millis {
+            self.last_millis = now;
+            self.sequence = 0;
+        } else if self.sequence < MAX_SEQUENCE {
+            self.sequence += 1;
+        } else {
+            self.last_millis += 1;
+            self.sequence = 0;
+        }
+
+        self.last_millis << (WORKER_ID_BITS + SEQUENCE_BITS) | self.worker_id << SEQUENCE_BITS | self.sequence
+    }
+
+    /// Returns the time of the id in milliseconds since the unix epoch.
+    pub fn unix_millis_of(id: u64) -> u64 {
+        (id >> (WORKER_ID_BITS + SEQUENCE_BITS)) + ID_EPOCH_MILLIS
+    }
+
+    /// Returns the worker id of the id.
+    pub fn worker_id_of(id: u64) -> u64 {
+        (id >> SEQUENCE_BITS) & MAX_WORKER_ID
+    }
+
+    /// Returns the sequence number of the id in its millisecond.
+    pub fn sequence_of(id: u64) -> u64 {
+        id & MAX_SEQUENCE
+    }
+}
+
+impl Default for IdGen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_gen() {
+        let mut ids = IdGen::with_worker_id(5);
+        let mut last = 0;
+        for _ in 0..10_000 {
+            let id = ids.next_id();
+            assert!(id > last);
+            assert_eq!(IdGen::worker_id_of(id), 5);
+            last = id;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let millis = IdGen::unix_millis_of(last);
+        // 10_000 ids can borrow a few milliseconds from the future.
+        assert!(millis + 1000 > now && millis < now + 1000);
+
+        let other = IdGen::with_worker_id(6).next_id();
+        assert_eq!(IdGen::worker_id_of(other), 6);
+        assert_eq!(IdGen::sequence_of(other), 0);
+    }
+}
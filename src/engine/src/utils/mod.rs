@@ -3,7 +3,14 @@ pub mod hide_unsafe;
 pub mod write_result;
 pub mod ptr;
 pub mod core;
+pub mod cgroup;
+pub mod path;
+pub mod rng;
+pub mod id_gen;
 
 pub use hide_unsafe::*;
 pub use ptr::*;
-pub use core::*;
\ No newline at end of file
+pub use core::*;
+pub use path::*;
+pub use rng::{rng, Rng};
+pub use id_gen::{IdGen, ID_EPOCH_MILLIS};
\ No newline at end of file
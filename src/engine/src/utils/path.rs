@@ -0,0 +1,87 @@
+//! This module contains functions for working with paths, that are passed to syscalls.
+//!
+//! Relative paths are resolved against the working directory, that was stored at the runtime startup
+//! (read [`init_working_dir`]). So, file operations remain correct even if some library calls `chdir`.
+use std::ffi::CString;
+use std::io::{Error, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The working directory, that was stored at the runtime startup.
+static WORKING_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Stores the current working directory. Relative paths will be resolved against it.
+///
+/// It is called in [`run_on_core`](crate::run::run_on_core), so you don't need to call it.
+/// Only the first call stores the directory, later calls do nothing.
+pub fn init_working_dir() {
+    if WORKING_DIR.get().is_none() && let Ok(dir) = std::env::current_dir() {
+        let _ = WORKING_DIR.set(dir);
+    }
+}
+
+/// Returns the working directory, that was stored by [`init_working_dir`].
+pub fn working_dir() -> Option<&'static Path> {
+    WORKING_DIR.get().map(PathBuf::as_path)
+}
+
+/// Makes the path absolute against the [`working_dir`] and removes `.` components.
+///
+/// `..` components are kept, because resolving them without the filesystem is wrong for symlinks.
+/// If the working directory is not stored, relative paths stay relative.
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let mut normalized = match working_dir() {
+        Some(dir) if path.is_relative() => dir.to_path_buf(),
+        _ => PathBuf::with_capacity(path.as_os_str().len())
+    };
+
+    for component in path.components() {
+        if component != Component::CurDir {
+            normalized.push(component);
+        }
+    }
+
+    normalized
+}
+
+/// Converts the path to [`CString`], that can be passed to syscalls. The path is normalized with [`normalize_path`].
+///
+/// # Errors
+///
+/// Returns [`ErrorKind::InvalidInput`] if the path contains a nul byte.
+pub fn path_to_c_string<P: AsRef<Path>>(path: P) -> Result<CString, Error> {
+    raw_path_to_c_string(normalize_path(path))
+}
+
+/// Converts the path to [`CString`] as is, without normalization.
+/// It is used for paths, that are not resolved by the syscall, like the target of a symbolic link.
+///
+/// # Errors
+///
+/// Returns [`ErrorKind::InvalidInput`] if the path contains a nul byte.
+pub fn raw_path_to_c_string<P: AsRef<Path>>(path: P) -> Result<CString, Error> {
+    CString::new(path.as_ref().as_os_str().as_bytes())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains an interior nul byte"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        init_working_dir();
+        let dir = working_dir().unwrap().to_path_buf();
+
+        assert_eq!(normalize_path("/var/./log/../tmp"), PathBuf::from("/var/log/../tmp"));
+        assert_eq!(normalize_path("./data/./file"), dir.join("data/file"));
+        assert_eq!(normalize_path("../file"), dir.join("../file"));
+        assert_eq!(normalize_path("."), dir);
+
+        assert_eq!(path_to_c_string("/tmp/./file").unwrap().as_bytes(), b"/tmp/file");
+        assert_eq!(path_to_c_string("/tmp/fi\0le").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -64,6 +64,14 @@ impl<T> Ptr<T> {
         unsafe { &mut *self.ptr }
     }
 
+    /// Cast the pointer to a pointer of another type.
+    #[inline(always)]
+    pub fn cast<U>(self) -> Ptr<U> {
+        Ptr {
+            ptr: self.ptr as *mut U
+        }
+    }
+
     /// Returns the pointer as an u64.
     /// Use [`Ptr::from`](#method.from) to convert it back.
     #[inline(always)]
@@ -86,6 +94,21 @@ impl<T> Ptr<T> {
         }
     }
 
+    /// Deallocate the memory. It will not lead to the pointer value being dropped.
+    /// Use it after [`Ptr::read`], when the value has already been moved out.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be created by [`Ptr::new`] and must not be used after it.
+    #[inline(always)]
+    pub unsafe fn dealloc(self) {
+        if self.ptr.is_null() {
+            return;
+        }
+
+        unsafe { dealloc(self.ptr as *mut u8, Layout::new::<T>()); }
+    }
+
     /// Return the value. It will not lead to the pointer value being dropped.
     ///
     /// # Panics
@@ -0,0 +1,150 @@
+//! This module contains [`Rng`] and [`rng`].
+use std::cell::UnsafeCell;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::local::id::{get_core_id, get_worker_id};
+
+thread_local! {
+    /// Local [`Rng`]. So, it is lockless. It is seeded on the first use.
+    pub static RNG: UnsafeCell<Rng> = UnsafeCell::new(Rng::from_local_seed());
+}
+
+/// Get [`Rng`] of the current worker. So, it is lockless.
+///
+/// It is suitable for jitter, load-balancing choices and request ids in hot coroutine paths,
+/// but it is not cryptographically secure.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::time::Duration;
+/// use engine::coro;
+/// use engine::sleep::sleep;
+/// use engine::utils::rng;
+///
+/// #[coro]
+/// fn retry_with_jitter() {
+///     let jitter = rng().gen_range(0..50);
+///     yield sleep(Duration::from_millis(100 + jitter));
+/// }
+/// ```
+#[inline(always)]
+pub fn rng() -> &'static mut Rng {
+    RNG.with(|rng| unsafe { &mut *rng.get() })
+}
+
+/// A fast pseudo-random number generator (wyrand).
+///
+/// Use [`rng`] to get the generator of the current worker. Every worker has its own state,
+/// that is seeded from the core id, the worker id, the time and the process id, so workers produce different sequences.
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    /// Creates a new [`Rng`] with the given seed. The same seed gives the same sequence.
+    pub const fn with_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Creates a new [`Rng`] with a seed, that is unique for the current worker.
+    fn from_local_seed() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        let mut seed = nanos ^ (std::process::id() as u64).rotate_left(32);
+        seed ^= splitmix64((get_core_id() as u64) << 32 | get_worker_id() as u64);
+        // The address of the thread-local state differs between threads, that are not workers.
+        seed ^= splitmix64(&seed as *const u64 as u64);
+
+        Self::with_seed(splitmix64(seed))
+    }
+
+    /// Returns the next random [`u64`].
+    #[inline(always)]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0xa0761d6478bd642f);
+        let t = (self.state as u128).wrapping_mul((self.state ^ 0xe7037ed1a0b428db) as u128);
+        (t >> 64) as u64 ^ t as u64
+    }
+
+    /// Returns the next random [`u32`].
+    #[inline(always)]
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a random [`f64`] in `[0, 1)`.
+    #[inline(always)]
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Returns a random number in the range. It is unbiased.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    #[inline(always)]
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "gen_range called with an empty range");
+        let len = range.end - range.start;
+        // Lemire's method: the high part of the product is uniform, if the low part is not in the biased zone.
+        let threshold = len.wrapping_neg() % len;
+        loop {
+            let m = (self.next_u64() as u128) * (len as u128);
+            if (m as u64) >= threshold {
+                return range.start + (m >> 64) as u64;
+            }
+        }
+    }
+
+    /// Returns true with the probability `p`.
+    #[inline(always)]
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Fills `dest` with random bytes.
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// Mixes bits of `x`, so close seeds give different states.
+#[inline(always)]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::with_seed(42);
+        let mut b = Rng::with_seed(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        for _ in 0..10_000 {
+            let n = a.gen_range(10..17);
+            assert!((10..17).contains(&n));
+            let f = a.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+
+        let mut bytes = [0u8; 13];
+        a.fill_bytes(&mut bytes);
+        assert!(bytes.iter().any(|&b| b != 0));
+
+        let other = std::thread::spawn(|| rng().next_u64()).join().unwrap();
+        assert_ne!(rng().next_u64(), other);
+    }
+}
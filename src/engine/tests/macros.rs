@@ -0,0 +1,210 @@
+#![feature(coroutines, coroutine_trait)]
+
+use std::cell::RefCell;
+use std::io::{Error, Read, Write};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+use engine::{coro, join, scope, select, spawn_idle, spawn_local, spawn_local_with_handle, spawn_local_with_priority, test_local, try_spawn_local, wait, wait_all, wait_timeout};
+use engine::buf::Buffer;
+use engine::coroutine::{yield_now, Elapsed, JoinHandle, Scope};
+use engine::io::{AsyncRead, AsyncWrite};
+use engine::net::TcpStream;
+use engine::scheduler::Priority;
+use engine::sleep::sleep;
+
+#[coro]
+fn double(value: u64) -> u64 {
+    yield yield_now();
+    value * 2
+}
+
+#[coro]
+fn sleep_and_return(duration: Duration, value: u64) -> u64 {
+    yield sleep(duration);
+    return value;
+}
+
+#[coro]
+fn push_to_log(log: Rc<RefCell<Vec<u64>>>, value: u64) {
+    log.borrow_mut().push(value);
+}
+
+#[coro]
+fn push_after_sleep(log: Rc<RefCell<Vec<u64>>>, duration: Duration, value: u64) {
+    yield sleep(duration);
+    log.borrow_mut().push(value);
+}
+
+#[coro]
+fn spawn_pushers(log: Rc<RefCell<Vec<u64>>>, scope: Scope) {
+    for value in 1..4 {
+        let log = log.clone();
+        scope.spawn(move |res| push_after_sleep(log, Duration::from_millis(value), value, res));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Counter {
+    value: u64
+}
+
+impl Counter {
+    #[coro]
+    fn add(self, value: u64) -> u64 {
+        yield yield_now();
+        return self.value + value;
+    }
+}
+
+#[test_local]
+fn test_wait() {
+    assert_eq!(wait!(double(21)), 42);
+
+    let counter = Counter { value: 1 };
+    assert_eq!(wait!(counter.add(2)), 3);
+}
+
+#[test_local]
+fn test_join() {
+    let (first, second) = join!(double(1), sleep_and_return(Duration::from_millis(1), 7));
+    assert_eq!((first, second), (2, 7));
+
+    let (first, second, third) = wait_all!(double(2), double(3), sleep_and_return(Duration::from_millis(1), 5));
+    assert_eq!((first, second, third), (4, 6, 5));
+}
+
+#[test_local]
+fn test_scope() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    scope!(spawn_pushers(log.clone()));
+    assert_eq!(*log.borrow(), vec![1, 2, 3]);
+}
+
+#[test_local]
+fn test_wait_timeout() {
+    let res: Result<u64, Elapsed> = wait_timeout!(double(5), Duration::from_secs(1));
+    assert_eq!(res, Ok(10));
+
+    let res: Result<u64, Elapsed> = wait_timeout!(sleep_and_return(Duration::from_secs(10), 5), Duration::from_millis(1));
+    assert_eq!(res, Err(Elapsed));
+}
+
+#[test_local]
+fn test_spawn_local() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    spawn_local!(push_to_log(log.clone(), 1));
+    assert!(try_spawn_local!(push_to_log(log.clone(), 2)).is_ok());
+    assert!(log.borrow().is_empty());
+
+    yield yield_now();
+    log.borrow_mut().sort();
+    assert_eq!(*log.borrow(), vec![1, 2]);
+
+    let mut handle: JoinHandle<u64> = spawn_local_with_handle!(double(4));
+    let res: u64 = yield handle.wait();
+    assert_eq!(res, 8);
+}
+
+#[test_local]
+fn test_spawn_local_with_priority() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    spawn_local_with_priority!(Priority::Low, push_to_log(log.clone(), 1));
+    spawn_local!(push_to_log(log.clone(), 2));
+    spawn_local_with_priority!(Priority::High, push_to_log(log.clone(), 3));
+
+    // The parent sleeps, so the low priority coroutine is not starved by it.
+    yield sleep(Duration::from_millis(1));
+    assert_eq!(*log.borrow(), vec![3, 2, 1]);
+}
+
+#[test_local]
+fn test_spawn_idle() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    spawn_idle!(push_to_log(log.clone(), 1));
+
+    // The parent is always ready, so the worker is never idle.
+    for _ in 0..10 {
+        yield yield_now();
+        assert!(log.borrow().is_empty());
+    }
+
+    yield sleep(Duration::from_millis(1));
+    assert_eq!(*log.borrow(), vec![1]);
+}
+
+#[test_local]
+fn test_select() {
+    let first = select! {
+        _ = sleep(Duration::from_secs(10)) => 1,
+        _ = sleep(Duration::from_millis(1)) => 2
+    };
+    assert_eq!(first, 2);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel::<()>();
+    let peer = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        receiver.recv().unwrap();
+        stream.write_all(b"ping").unwrap();
+        receiver.recv().unwrap();
+    });
+    let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+
+    // The peer sends nothing, so the sleep wins, and the read is cancelled.
+    let is_read = select! {
+        _ = stream.read() => true,
+        _ = sleep(Duration::from_millis(10)) => false
+    };
+    assert!(!is_read);
+
+    sender.send(()).unwrap();
+    let read = select! {
+        res = stream.read() => {
+            let res: Result<&'static [u8], Error> = res;
+            res.unwrap().to_vec()
+        },
+        _ = sleep(Duration::from_secs(10)) => Vec::new()
+    };
+    assert_eq!(read, b"ping");
+
+    sender.send(()).unwrap();
+    peer.join().unwrap();
+}
+
+#[test_local]
+fn test_select_waits_for_writes() {
+    const SIZE: usize = 16 * 1024 * 1024;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let mut data = vec![0; SIZE + 3];
+        stream.read_exact(&mut data).unwrap();
+        data
+    });
+    let mut stream: TcpStream = (yield TcpStream::connect(addr)).unwrap();
+
+    let mut buf = Buffer::new(SIZE);
+    buf.append(&vec![1; SIZE]);
+    let is_written = select! {
+        res = stream.write_all(buf) => {
+            let res: Result<(), Error> = res;
+            res.is_ok()
+        },
+        _ = sleep(Duration::from_millis(1)) => false
+    };
+    assert!(!is_written);
+
+    // The lost write is completed, so the next write doesn't interleave with it.
+    let mut end = Buffer::new(3);
+    end.append(b"end");
+    let res: Result<(), Error> = yield stream.write_all(end);
+    res.unwrap();
+
+    let data = peer.join().unwrap();
+    assert!(data[..SIZE].iter().all(|byte| *byte == 1));
+    assert_eq!(&data[SIZE..], b"end");
+}
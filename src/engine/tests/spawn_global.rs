@@ -0,0 +1,30 @@
+#![feature(coroutines, coroutine_trait)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use engine::{coro, spawn_global, test_local};
+use engine::sleep::sleep;
+
+#[coro]
+fn add_job(jobs: Arc<AtomicU64>, job: u64) {
+    jobs.fetch_add(job, Ordering::SeqCst);
+}
+
+#[test_local]
+fn test_spawn_global() {
+    let jobs = Arc::new(AtomicU64::new(0));
+    let producer_jobs = jobs.clone();
+    let producer = std::thread::spawn(move || {
+        for job in 1..=10 {
+            let jobs = producer_jobs.clone();
+            spawn_global!(add_job(jobs, job)).expect("no worker is running");
+        }
+    });
+
+    // The only worker runs this test, so it gets all the jobs.
+    while jobs.load(Ordering::SeqCst) != 55 {
+        yield sleep(Duration::from_millis(1));
+    }
+    producer.join().unwrap();
+}
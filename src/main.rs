@@ -41,11 +41,15 @@ use std::time::Duration;
 use io_uring::types::{SubmitArgs, Timespec};
 use engine::{coro, run_on_all_cores, run_on_core, spawn_local, wait};
 use engine::net::{TcpListener, TcpStream};
+use engine::stream::CoStream;
 use engine::sleep::sleep;
 use engine::buf::{buf_pool, Buffer, buffer, BufPool};
 use engine::io::{AsyncRead, AsyncWrite, PollState};
 use engine::utils::{CoreId, get_core_ids, Ptr, set_for_current};
 
+/// How many connections are handled by one worker at once. Other connections wait in the backlog.
+const MAX_CONNECTIONS: usize = 10_000;
+
 fn docs() {
     #[coro]
     fn difficult_write(mut stream: TcpStream, mut buf: Buffer) -> usize {
@@ -65,25 +69,21 @@ fn docs() {
     }
 
     #[coro]
-    fn handle_tcp_stream(stream: TcpStream) {
+    fn handle_tcp_stream(stream_: Result<TcpStream, Error>) {
+        if stream_.is_err() {
+            println!("accept failed, reason: {}", stream_.err().unwrap());
+            return;
+        }
+
+        let stream: TcpStream = stream_.unwrap();
         let res = wait!(difficult_write(stream, engine::buf::buffer()));
         println!("{}", res);
     }
 
     #[coro]
     fn start_server() {
-        let mut listener = yield TcpListener::new("engine:8081".to_socket_addrs().unwrap().next().unwrap());
-        loop {
-            let stream_ = yield listener.accept();
-
-            if stream_.is_err() {
-                println!("accept failed, reason: {}", stream_.err().unwrap());
-                continue;
-            }
-
-            let stream: TcpStream = stream_.unwrap();
-            spawn_local!(handle_tcp_stream(stream));
-        }
+        let listener: TcpListener = yield TcpListener::new("engine:8081".to_socket_addrs().unwrap().next().unwrap());
+        wait!(listener.for_each_concurrent(MAX_CONNECTIONS, handle_tcp_stream));
     }
 
     run_on_all_cores(start_server);
@@ -101,9 +101,7 @@ fn ping_pong() {
         }
 
         let mut stream: TcpStream = stream_.unwrap();
-        unsafe {
-            println!("connected: {}, fd: {}", C.fetch_add(1, SeqCst) + 1, stream.state_ptr().as_ref().fd());
-        }
+        println!("connected: {}, {:?}", C.fetch_add(1, SeqCst) + 1, stream);
         let mut buf: Buffer;
         let mut res: Result<&[u8], Error>;
 
@@ -133,7 +131,13 @@ fn ping_pong() {
     #[coro]
     fn server() {
         #[coro]
-        fn handle_tcp_client(mut stream: TcpStream) {
+        fn handle_tcp_client(stream_: Result<TcpStream, Error>) {
+            if stream_.is_err() {
+                println!("accept failed, reason: {}", stream_.err().unwrap());
+                return;
+            }
+
+            let mut stream: TcpStream = stream_.unwrap();
             loop {
                 let slice: &[u8] = (yield stream.read()).unwrap();
 
@@ -154,20 +158,10 @@ fn ping_pong() {
             }
         }
 
-        let mut listener: TcpListener = yield TcpListener::new("engine:8082".to_socket_addrs().unwrap().next().unwrap());
-        unsafe {
-            println!("listener is created, fd: {}", listener.state_ptr().as_ref().fd());
-        }
+        let listener: TcpListener = yield TcpListener::new("engine:8082".to_socket_addrs().unwrap().next().unwrap());
+        println!("listener is created: {:?}", listener);
 
-        loop {
-            let stream_ = yield listener.accept();
-            if stream_.is_err() {
-                println!("accept failed, reason: {}", stream_.err().unwrap());
-                continue;
-            }
-            let stream: TcpStream = stream_.unwrap();
-            spawn_local!(handle_tcp_client(stream));
-        }
+        wait!(listener.for_each_concurrent(MAX_CONNECTIONS, handle_tcp_client));
     }
 
     spawn_local!(server());
@@ -180,7 +174,13 @@ fn ping_pong() {
 
 fn tcp_benchmark() {
     #[coro]
-    fn handle_tcp_client(mut stream: TcpStream) {
+    fn handle_tcp_client(stream_: Result<TcpStream, Error>) {
+        if stream_.is_err() {
+            println!("accept failed, reason: {}", stream_.err().unwrap());
+            return;
+        }
+
+        let mut stream: TcpStream = stream_.unwrap();
         loop {
             let slice: &[u8] = (yield stream.read()).unwrap();
 
@@ -202,18 +202,8 @@ fn tcp_benchmark() {
 
     #[coro]
     fn start_server() {
-        let mut listener = yield TcpListener::new("engine:8081".to_socket_addrs().unwrap().next().unwrap());
-        loop {
-            let stream_ = yield listener.accept();
-
-            if stream_.is_err() {
-                println!("accept failed, reason: {}", stream_.err().unwrap());
-                continue;
-            }
-
-            let stream: TcpStream = stream_.unwrap();
-            spawn_local!(handle_tcp_client(stream));
-        }
+        let listener: TcpListener = yield TcpListener::new("engine:8081".to_socket_addrs().unwrap().next().unwrap());
+        wait!(listener.for_each_concurrent(MAX_CONNECTIONS, handle_tcp_client));
     }
 
     run_on_all_cores(start_server);
@@ -244,7 +234,7 @@ fn benchmark_sleep() {
 fn main() {
     //io_uring();
     //tcp_benchmark();
-    run_on_core(ping_pong, get_core_ids().unwrap()[0]);
+    run_on_core(ping_pong, get_core_ids().unwrap()[0]).expect("failed to run the worker");
 }
 
 // TODO r